@@ -2,7 +2,7 @@
 # ─────────────────────────────────────────────────────────────────────────────
 # MissonControl — Health Monitor Sidecar
 #
-# Polls the /health endpoint on a configurable interval and emits structured
+# Polls the /health/ready endpoint on a configurable interval and emits structured
 # log lines. Designed to run as a background process via entrypoint.sh.
 #
 # Environment variables:
@@ -16,7 +16,7 @@ HOST="localhost"
 PORT="${PORT:-8080}"
 INTERVAL="${SIDECAR_INTERVAL:-30}"
 STARTUP_DELAY="${SIDECAR_STARTUP_DELAY:-5}"
-HEALTH_URL="http://${HOST}:${PORT}/health"
+HEALTH_URL="http://${HOST}:${PORT}/health/ready"
 
 log() {
     local level="$1"; shift
@@ -0,0 +1,40 @@
+//! Append-only record of sensitive admin operations, in `audit_log`.
+
+use bson::{doc, Document};
+use uuid::Uuid;
+
+use crate::{
+    db::{Db, AUDIT_LOG},
+    handlers::auth::Claims,
+};
+
+pub const BACKUP: &str = "backup";
+pub const RESTORE: &str = "restore";
+/// `details.user_id`'s personal data was erased.
+pub const USER_ANONYMIZE: &str = "user_anonymize";
+/// `details.user_id` was changed by a bulk `details.action`: `deactivate`,
+/// `activate` or `set_role` to `details.role`.
+pub const USER_BULK_UPDATE: &str = "user_bulk_update";
+/// `details.check` was repaired with `details.action`, changing
+/// `details.count` tasks or notes.
+pub const INTEGRITY_REPAIR: &str = "integrity_repair";
+/// `details.user_ids` joined team `details.team_id`.
+pub const TEAM_MEMBERS_ADD: &str = "team_members_add";
+/// `details.user_ids` left team `details.team_id`, or it was deleted.
+pub const TEAM_MEMBERS_REMOVE: &str = "team_members_remove";
+
+/// Records that `actor` performed `action`. Best effort: a failure is logged
+/// rather than returned, since the operation itself has already happened.
+pub async fn record(db: &Db, actor: &Claims, action: &str, details: Document) {
+    let entry = doc! {
+        "_id": Uuid::new_v4().to_string(),
+        "action": action,
+        "actor_id": &actor.sub,
+        "actor_username": &actor.username,
+        "details": details,
+        "created_at": bson::DateTime::now(),
+    };
+    if let Err(e) = db.collection::<Document>(AUDIT_LOG).insert_one(entry, None).await {
+        tracing::error!(action, actor = %actor.sub, "Could not write audit entry: {e}");
+    }
+}
@@ -0,0 +1,493 @@
+//! Admin backups: one line-delimited JSON archive of the core collections,
+//! optionally gzipped, written and read as a stream so neither side holds
+//! the database in memory.
+//!
+//! The first line is a `header` carrying the format version, then one `doc`
+//! line per document, then an `end` line with per-collection counts. An
+//! archive without a matching `end` line is treated as truncated.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{ready, Context, Poll},
+    time::Instant,
+};
+
+use async_compression::tokio::bufread::GzipDecoder;
+use axum::body::Body;
+use bson::{doc, Bson, Document};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf};
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+use crate::{
+    config::AppConfig,
+    db::{
+        indexes, Db, CTI_CATEGORIES, CTI_ITEMS, CTI_TYPES, TASKS, USERS, WORKSPACES, WORKSPACE_MEMBERS,
+    },
+    errors::{AppError, AppResult},
+};
+
+pub const FORMAT: &str = "missioncontrol-backup";
+/// Bumped whenever a restore could no longer read older archives as is.
+pub const VERSION: u32 = 1;
+
+/// What a backup contains, in restore order.
+pub const COLLECTIONS: &[&str] =
+    &[USERS, WORKSPACES, WORKSPACE_MEMBERS, CTI_CATEGORIES, CTI_TYPES, CTI_ITEMS, TASKS];
+
+/// Documents per `insert_many` while staging a restore.
+const BATCH_SIZE: usize = 500;
+const PROGRESS_EVERY: u64 = 10_000;
+/// Longest archive line a restore accepts. MongoDB caps documents at 16 MiB
+/// of BSON, which can grow somewhat as Extended JSON.
+const MAX_LINE_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Line {
+    Header { format: String, version: u32, created_at: DateTime<Utc> },
+    /// `doc` is the document as relaxed Extended JSON.
+    Doc { collection: String, doc: serde_json::Value },
+    End { counts: BTreeMap<String, u64> },
+}
+
+async fn write_line<W: AsyncWrite + Unpin>(out: &mut W, line: &Line) -> anyhow::Result<()> {
+    let mut bytes = serde_json::to_vec(line)?;
+    bytes.push(b'\n');
+    out.write_all(&bytes).await?;
+    Ok(())
+}
+
+/// Writes every document in `COLLECTIONS` to `out` and shuts it down,
+/// returning the number written per collection.
+pub async fn write_archive<W: AsyncWrite + Unpin>(db: &Db, out: &mut W) -> anyhow::Result<BTreeMap<String, u64>> {
+    write_line(out, &Line::Header { format: FORMAT.into(), version: VERSION, created_at: Utc::now() }).await?;
+    let mut counts = BTreeMap::new();
+    for &collection in COLLECTIONS {
+        let mut cursor = db.collection::<Document>(collection).find(None, None).await?;
+        let mut written = 0;
+        while cursor.advance().await? {
+            let doc = Bson::Document(cursor.deserialize_current()?).into_relaxed_extjson();
+            write_line(out, &Line::Doc { collection: collection.into(), doc }).await?;
+            written += 1;
+        }
+        counts.insert(collection.to_string(), written);
+    }
+    write_line(out, &Line::End { counts: counts.clone() }).await?;
+    out.shutdown().await?;
+    Ok(counts)
+}
+
+/// Marks the read error raised once an upload, or what it decompresses to,
+/// passes `RESTORE_MAX_BYTES`.
+#[derive(Debug, thiserror::Error)]
+#[error("archive is larger than the configured limit")]
+struct TooLarge;
+
+/// A request body as a buffered reader that fails after `limit` bytes.
+pub fn body_reader(body: Body, limit: usize) -> impl AsyncBufRead + Unpin + Send {
+    let mut seen = 0;
+    let chunks = body.into_data_stream().map_err(io::Error::other).and_then(move |chunk| {
+        seen += chunk.len();
+        let result = if seen > limit { Err(io::Error::other(TooLarge)) } else { Ok(chunk) };
+        std::future::ready(result)
+    });
+    StreamReader::new(chunks)
+}
+
+/// A reader that fails with `TooLarge` once more than `left` bytes have
+/// been read through it.
+struct Capped<R> {
+    inner: R,
+    left: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Capped<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = buf.filled().len() - before;
+        self.left = self.left.checked_sub(read).ok_or_else(|| io::Error::other(TooLarge))?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads through gzip if the input starts with the gzip magic bytes. The
+/// decompressed stream fails after `limit` bytes, so a small upload cannot
+/// expand past the limit the compressed one was held to.
+pub async fn maybe_gunzip<R>(mut input: R, limit: usize) -> AppResult<Box<dyn AsyncBufRead + Unpin + Send>>
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    let head = input.fill_buf().await.map_err(read_error)?;
+    Ok(if head.starts_with(&[0x1f, 0x8b]) {
+        Box::new(BufReader::new(Capped { inner: GzipDecoder::new(input), left: limit }))
+    } else {
+        Box::new(input)
+    })
+}
+
+fn read_error(e: io::Error) -> AppError {
+    if e.get_ref().is_some_and(|inner| inner.is::<TooLarge>()) {
+        return AppError::PayloadTooLarge;
+    }
+    AppError::BadRequest(format!("Could not read the archive: {e}"))
+}
+
+fn invalid(message: impl std::fmt::Display) -> AppError {
+    AppError::BadRequest(format!("Invalid backup archive: {message}"))
+}
+
+/// Reads an archive line by line, checking the header up front and the
+/// counts in the `end` line at the end. Lines over `MAX_LINE_BYTES` are
+/// rejected without being buffered in full.
+pub struct ArchiveReader<R> {
+    input: R,
+    line: Vec<u8>,
+    max_line: usize,
+    line_no: u64,
+    counts: BTreeMap<String, u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl<R: AsyncBufRead + Unpin> ArchiveReader<R> {
+    pub async fn open(input: R) -> AppResult<Self> {
+        Self::open_with_max_line(input, MAX_LINE_BYTES).await
+    }
+
+    async fn open_with_max_line(input: R, max_line: usize) -> AppResult<Self> {
+        let mut reader = Self {
+            input,
+            line: Vec::new(),
+            max_line,
+            line_no: 0,
+            counts: BTreeMap::new(),
+            created_at: Utc::now(),
+        };
+        match reader.next_line().await? {
+            Some(Line::Header { format, version, created_at }) if format == FORMAT => {
+                if version != VERSION {
+                    return Err(invalid(format!("version {version} is not supported (expected {VERSION})")));
+                }
+                reader.created_at = created_at;
+                Ok(reader)
+            }
+            _ => Err(invalid("it does not start with a MissionControl backup header")),
+        }
+    }
+
+    async fn next_line(&mut self) -> AppResult<Option<Line>> {
+        loop {
+            self.line.clear();
+            // One byte past the limit is enough to tell that a line is too long.
+            let mut input = (&mut self.input).take(self.max_line as u64 + 1);
+            if input.read_until(b'\n', &mut self.line).await.map_err(read_error)? == 0 {
+                return Ok(None);
+            }
+            self.line_no += 1;
+            if self.line.last() == Some(&b'\n') {
+                self.line.pop();
+            }
+            if self.line.len() > self.max_line {
+                return Err(invalid(format!("line {} is longer than {} bytes", self.line_no, self.max_line)));
+            }
+            if self.line.trim_ascii().is_empty() {
+                continue;
+            }
+            return serde_json::from_slice(&self.line)
+                .map(Some)
+                .map_err(|e| invalid(format!("line {}: {e}", self.line_no)));
+        }
+    }
+
+    /// The next document and its collection; `None` once a valid `end` line
+    /// has been read.
+    pub async fn next_doc(&mut self) -> AppResult<Option<(&'static str, Document)>> {
+        match self.next_line().await? {
+            Some(Line::Doc { collection, doc }) => {
+                let collection = COLLECTIONS
+                    .iter()
+                    .copied()
+                    .find(|c| *c == collection)
+                    .ok_or_else(|| invalid(format!("line {}: unknown collection '{collection}'", self.line_no)))?;
+                let doc = match Bson::try_from(doc) {
+                    Ok(Bson::Document(doc)) if doc.contains_key("_id") => doc,
+                    _ => return Err(invalid(format!("line {}: not a document with an _id", self.line_no))),
+                };
+                *self.counts.entry(collection.to_string()).or_default() += 1;
+                Ok(Some((collection, doc)))
+            }
+            Some(Line::End { mut counts }) => {
+                counts.retain(|_, n| *n > 0);
+                if counts != self.counts {
+                    return Err(invalid(format!("document counts {:?} do not match the end line {counts:?}", self.counts)));
+                }
+                Ok(None)
+            }
+            Some(Line::Header { .. }) => Err(invalid(format!("line {}: unexpected second header", self.line_no))),
+            None => Err(invalid("it is truncated (no end line)")),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CollectionReport {
+    pub collection: String,
+    pub restored: u64,
+}
+
+/// Response to `POST /api/admin/restore`.
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    pub archive_created_at: DateTime<Utc>,
+    pub wiped: bool,
+    pub collections: Vec<CollectionReport>,
+    pub documents: u64,
+    pub duration_ms: u64,
+}
+
+static RESTORING: AtomicBool = AtomicBool::new(false);
+
+/// Clears `RESTORING` when the restore ends, however it ends.
+struct RestoreGuard;
+
+impl Drop for RestoreGuard {
+    fn drop(&mut self) {
+        RESTORING.store(false, Ordering::Release);
+    }
+}
+
+/// Restores `archive` into `db`. Documents are first written to staging
+/// collections, so a bad or truncated archive never touches live data; only
+/// once all of it has been read are the staged collections swapped in. With
+/// `wipe`, each collection is replaced (`$out`, atomic per collection);
+/// otherwise the archive is merged over it by `_id`.
+pub async fn restore<R: AsyncBufRead + Unpin>(
+    db: &Db,
+    config: &AppConfig,
+    archive: R,
+    wipe: bool,
+) -> AppResult<RestoreReport> {
+    if RESTORING.swap(true, Ordering::AcqRel) {
+        return Err(AppError::ServiceUnavailable("Another restore is already running".into()));
+    }
+    let _guard = RestoreGuard;
+    let started = Instant::now();
+    let run = Uuid::new_v4().simple().to_string();
+    let staging = |collection: &str| format!("restore_{}_{collection}", &run[..8]);
+
+    let result = async {
+        let mut reader = ArchiveReader::open(archive).await?;
+        let counts = stage(db, &mut reader, &staging).await?;
+        prepare_swap(db, config, &staging).await?;
+        swap(db, &staging, wipe).await?;
+        Ok::<_, AppError>((reader.created_at, counts))
+    }
+    .await;
+
+    for collection in COLLECTIONS {
+        if let Err(e) = db.collection::<Document>(&staging(collection)).drop(None).await {
+            tracing::warn!("Could not drop restore staging collection for {collection}: {e}");
+        }
+    }
+    let (archive_created_at, counts) = result?;
+
+    Ok(RestoreReport {
+        archive_created_at,
+        wiped: wipe,
+        documents: counts.values().sum(),
+        collections: COLLECTIONS
+            .iter()
+            .map(|c| CollectionReport { collection: c.to_string(), restored: counts.get(*c).copied().unwrap_or(0) })
+            .collect(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Copies the archive's documents into the staging collections.
+async fn stage<R: AsyncBufRead + Unpin>(
+    db: &Db,
+    reader: &mut ArchiveReader<R>,
+    staging: &impl Fn(&str) -> String,
+) -> AppResult<HashMap<&'static str, u64>> {
+    for collection in COLLECTIONS {
+        db.create_collection(staging(collection), None).await?;
+    }
+    let mut batches: HashMap<&'static str, Vec<Document>> = HashMap::new();
+    let mut counts: HashMap<&'static str, u64> = HashMap::new();
+    let mut total = 0;
+    while let Some((collection, doc)) = reader.next_doc().await? {
+        let batch = batches.entry(collection).or_default();
+        batch.push(doc);
+        if batch.len() >= BATCH_SIZE {
+            db.collection::<Document>(&staging(collection)).insert_many(batch.drain(..), None).await?;
+        }
+        *counts.entry(collection).or_default() += 1;
+        total += 1;
+        if total % PROGRESS_EVERY == 0 {
+            tracing::info!(documents = total, "Restore staging in progress");
+        }
+    }
+    for (collection, batch) in batches.into_iter().filter(|(_, b)| !b.is_empty()) {
+        db.collection::<Document>(&staging(collection)).insert_many(batch, None).await?;
+    }
+    tracing::info!(documents = total, "Restore staged");
+    Ok(counts)
+}
+
+/// Builds the unique indexes on the staged data, so a duplicate email or
+/// username fails here, before any live collection has been replaced.
+async fn prepare_swap(db: &Db, config: &AppConfig, staging: &impl Fn(&str) -> String) -> AppResult<()> {
+    for spec in indexes::specs(config) {
+        let unique = spec.options.as_ref().is_some_and(|o| o.unique == Some(true));
+        if !unique || !COLLECTIONS.contains(&spec.collection) {
+            continue;
+        }
+        let model = mongodb::IndexModel::builder().keys(spec.keys).options(spec.options).build();
+        db.collection::<Document>(&staging(spec.collection))
+            .create_index(model, None)
+            .await
+            .map_err(|e| invalid(format!("{} would break a unique index: {e}", spec.collection)))?;
+    }
+    Ok(())
+}
+
+async fn swap(db: &Db, staging: &impl Fn(&str) -> String, wipe: bool) -> AppResult<()> {
+    for &collection in COLLECTIONS {
+        // `$out` replaces the target in one step and keeps its indexes.
+        let stage = if wipe {
+            doc! { "$out": collection }
+        } else {
+            doc! { "$merge": { "into": collection, "on": "_id", "whenMatched": "replace", "whenNotMatched": "insert" } }
+        };
+        db.collection::<Document>(&staging(collection)).aggregate(vec![stage], None).await?;
+        tracing::info!(collection, wipe, "Restored collection");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_compression::tokio::write::GzipEncoder;
+
+    fn archive(lines: &[Line]) -> Vec<u8> {
+        lines.iter().flat_map(|l| [serde_json::to_vec(l).unwrap(), b"\n".to_vec()].concat()).collect()
+    }
+
+    fn header(version: u32) -> Line {
+        Line::Header { format: FORMAT.into(), version, created_at: Utc::now() }
+    }
+
+    fn task(id: &str) -> Line {
+        Line::Doc { collection: TASKS.into(), doc: serde_json::json!({ "_id": id, "title": "T" }) }
+    }
+
+    fn end(tasks: u64) -> Line {
+        Line::End { counts: [(TASKS.to_string(), tasks), (USERS.to_string(), 0)].into() }
+    }
+
+    async fn read_all(bytes: Vec<u8>) -> AppResult<Vec<(&'static str, Document)>> {
+        drain(ArchiveReader::open(maybe_gunzip(io::Cursor::new(bytes), usize::MAX).await?).await?).await
+    }
+
+    async fn drain<R: AsyncBufRead + Unpin>(mut reader: ArchiveReader<R>) -> AppResult<Vec<(&'static str, Document)>> {
+        let mut docs = Vec::new();
+        while let Some(doc) = reader.next_doc().await? {
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+
+    #[tokio::test]
+    async fn reads_a_complete_archive() {
+        let docs = read_all(archive(&[header(VERSION), task("t1"), task("t2"), end(2)])).await.unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[1], (TASKS, doc! { "_id": "t2", "title": "T" }));
+    }
+
+    #[tokio::test]
+    async fn reads_gzipped_archives() {
+        let mut gz = GzipEncoder::new(Vec::new());
+        gz.write_all(&archive(&[header(VERSION), task("t1"), end(1)])).await.unwrap();
+        gz.shutdown().await.unwrap();
+        assert_eq!(read_all(gz.into_inner()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_archives() {
+        let cases = [
+            archive(&[header(VERSION + 1), end(0)]),
+            archive(&[task("t1"), end(1)]),
+            archive(&[header(VERSION), task("t1")]),
+            archive(&[header(VERSION), task("t1"), end(2)]),
+            archive(&[
+                header(VERSION),
+                Line::Doc { collection: "settings".into(), doc: serde_json::json!({ "_id": "x" }) },
+                end(0),
+            ]),
+            archive(&[
+                header(VERSION),
+                Line::Doc { collection: TASKS.into(), doc: serde_json::json!({ "title": "no id" }) },
+                end(1),
+            ]),
+            b"not json\n".to_vec(),
+        ];
+        for bytes in cases {
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            assert!(matches!(read_all(bytes).await, Err(AppError::BadRequest(_))), "{text}");
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_uploads_are_413() {
+        let body = Body::from(archive(&[header(VERSION), task("t1"), end(1)]));
+        let result = ArchiveReader::open(body_reader(body, 10)).await;
+        assert!(matches!(result, Err(AppError::PayloadTooLarge)));
+    }
+
+    #[tokio::test]
+    async fn archives_that_decompress_past_the_limit_are_413() {
+        let mut bytes = archive(&[header(VERSION), task("t1")]);
+        bytes.resize(bytes.len() + 1024 * 1024, b'\n');
+        bytes.extend(archive(&[end(1)]));
+        let mut gz = GzipEncoder::new(Vec::new());
+        gz.write_all(&bytes).await.unwrap();
+        gz.shutdown().await.unwrap();
+        let gz = gz.into_inner();
+        let limit = 64 * 1024;
+        assert!(gz.len() < limit && limit < bytes.len());
+
+        let input = maybe_gunzip(body_reader(Body::from(gz), limit), limit).await.unwrap();
+        let result = drain(ArchiveReader::open(input).await.unwrap()).await;
+        assert!(matches!(result, Err(AppError::PayloadTooLarge)));
+    }
+
+    #[tokio::test]
+    async fn overlong_lines_are_rejected() {
+        let doc = serde_json::json!({ "_id": "t1", "title": "T".repeat(500) });
+        let long = Line::Doc { collection: TASKS.into(), doc };
+        let line = serde_json::to_vec(&long).unwrap();
+        let bytes = archive(&[header(VERSION), long, end(1)]);
+
+        let reader = ArchiveReader::open_with_max_line(io::Cursor::new(bytes.clone()), line.len()).await.unwrap();
+        assert_eq!(drain(reader).await.unwrap().len(), 1);
+
+        let mut reader = ArchiveReader::open_with_max_line(io::Cursor::new(bytes), line.len() - 1).await.unwrap();
+        let Err(AppError::BadRequest(message)) = reader.next_doc().await else { panic!("expected a 400") };
+        assert!(message.contains("line 2 is longer than"), "{message}");
+    }
+
+    #[test]
+    fn extended_json_round_trips_dates() {
+        let original = doc! { "_id": "n1", "created_at": bson::DateTime::from_millis(1_700_000_000_000) };
+        let json = Bson::Document(original.clone()).into_relaxed_extjson();
+        assert_eq!(Bson::try_from(json).unwrap(), Bson::Document(original));
+    }
+}
@@ -0,0 +1,96 @@
+//! The cleanup job. Every `CLEANUP_INTERVAL_MINUTES` it deletes the expired
+//! documents `models::cleanup` describes, `CLEANUP_BATCH_SIZE` at a time,
+//! counting them in `cleanup_deleted_total`.
+//!
+//! Every replica runs it without coordinating: each delete re-checks the
+//! sweep's filter, so a document renewed meanwhile (a lock taken again) is
+//! left alone, and one already deleted elsewhere is simply not counted.
+
+use bson::{doc, Bson, Document};
+use chrono::{DateTime, Utc};
+use mongodb::options::FindOptions;
+use tokio::time::{interval, Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::CleanupConfig,
+    db::{batch_filter, collect, Db},
+    errors::AppResult,
+    models::{
+        cleanup::{CleanupReport, Sweep, SweepOutcome},
+        dates,
+    },
+};
+
+/// Runs every `config.interval_minutes`, starting at once, until `shutdown`
+/// is cancelled.
+pub async fn run_cleanup(db: Db, config: CleanupConfig, shutdown: CancellationToken) {
+    let mut ticker = interval(Duration::from_secs(config.interval_minutes * 60));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => {
+                tracing::info!("Cleanup job stopped");
+                return;
+            }
+        }
+        let report = run(&db, &config, dates::now()).await;
+        if report.failed() {
+            tracing::warn!(deleted = report.deleted(), "Cleanup pass finished with errors");
+        } else if report.deleted() > 0 {
+            tracing::info!(deleted = report.deleted(), "Cleanup pass finished");
+        }
+    }
+}
+
+/// Runs every sweep as of `now`. A sweep that fails is noted in its outcome
+/// and the rest still run.
+pub async fn run(db: &Db, config: &CleanupConfig, now: DateTime<Utc>) -> CleanupReport {
+    let mut sweeps = Vec::new();
+    for sweep in Sweep::ALL {
+        let mut outcome = SweepOutcome::new(sweep);
+        if let Err(e) = delete_expired(db, sweep, sweep.filter(now, config), config.batch_size, &mut outcome).await {
+            tracing::warn!(sweep = sweep.name(), "Cleanup sweep failed: {e:?}");
+            outcome.error = Some(e.to_string());
+        }
+        if outcome.deleted > 0 {
+            tracing::info!(sweep = sweep.name(), deleted = outcome.deleted, "Expired documents deleted");
+        }
+        sweeps.push(outcome);
+    }
+    CleanupReport { started_at: now, finished_at: dates::now(), sweeps }
+}
+
+/// Deletes what `filter` matches, `batch_size` documents at a time.
+async fn delete_expired(
+    db: &Db,
+    sweep: Sweep,
+    filter: Document,
+    batch_size: u32,
+    outcome: &mut SweepOutcome,
+) -> AppResult<()> {
+    let collection = db.collection::<Document>(sweep.collection());
+    loop {
+        let options = FindOptions::builder()
+            .projection(doc! { "_id": 1 })
+            .sort(doc! { "_id": 1 })
+            .limit(i64::from(batch_size))
+            .build();
+        let batch = collect(collection.find(filter.clone(), options).await?).await?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<Bson> = batch.iter().filter_map(|d| d.get("_id").cloned()).collect();
+        let deleted = collection.delete_many(batch_filter(&filter, &ids), None).await?;
+        if deleted.deleted_count == 0 {
+            // Taken by another replica meanwhile; it will carry on.
+            return Ok(());
+        }
+        outcome.deleted += deleted.deleted_count;
+        outcome.batches += 1;
+        metrics::counter!("cleanup_deleted_total", "collection" => sweep.collection()).increment(deleted.deleted_count);
+        if ids.len() < batch_size as usize {
+            return Ok(());
+        }
+    }
+}
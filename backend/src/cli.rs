@@ -0,0 +1,259 @@
+//! Command-line interface: `serve` (the default) plus maintenance commands
+//! that share the server's configuration and database connection.
+
+use anyhow::{bail, Context, Result};
+use bson::{doc, to_bson};
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use mongodb::{
+    options::{FindOneOptions, ReplaceOptions},
+    Collection,
+};
+use serde::Serialize;
+
+use crate::{
+    clock::{SystemClock, UuidIds},
+    config::AppConfig,
+    db::{indexes::case_insensitive, Db, CTI_CATEGORIES, CTI_ITEMS, CTI_TYPES, TASKS, USERS},
+    diagnostics,
+    models::{
+        cti::{Category, CtiItem, CtiSelection, CtiType},
+        task::Task,
+        user::{normalize_email, User},
+    },
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "MissionControl API server")]
+pub struct Cli {
+    /// Apply pending data migrations and exit without serving.
+    #[arg(long, global = true)]
+    pub migrate_only: bool,
+    /// Run the self-check, print its report and exit: 0 unless a check
+    /// failed. For smoke tests after a deploy.
+    #[arg(long, global = true)]
+    pub check: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand, PartialEq, Eq)]
+pub enum Command {
+    /// Run the API server (the default).
+    Serve,
+    /// Promote an existing account to admin.
+    ///
+    /// Accounts are created by signing in through Keycloak, which owns
+    /// passwords; for one that has not signed in yet, set ADMIN_EMAIL instead.
+    CreateAdmin {
+        #[arg(long)]
+        email: String,
+        /// Also require the account's username to match, as a safeguard.
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// Load a demo CTI taxonomy and sample tasks for development. Safe to
+    /// re-run: seeded records have fixed ids and are overwritten.
+    Seed,
+}
+
+/// `--check`: the self-check's report as JSON on stdout; an error if any
+/// check failed.
+pub async fn check(db: &Db, config: &AppConfig) -> Result<()> {
+    let report = diagnostics::run(db, config).await;
+    diagnostics::log(&report);
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.passed() {
+        bail!("Self-check failed");
+    }
+    Ok(())
+}
+
+pub async fn create_admin(db: &Db, email: &str, username: Option<&str>) -> Result<()> {
+    let users = db.collection::<User>(USERS);
+    let mut filter = doc! { "email": normalize_email(email) };
+    if let Some(username) = username {
+        filter.insert("username", username);
+    }
+    let options = FindOneOptions::builder().collation(case_insensitive()).build();
+    let Some(user) = users.find_one(filter, options).await.context("Could not look up the user")? else {
+        bail!(
+            "No account matches {email}{}. It is created on first sign-in through Keycloak; \
+             sign in once and re-run this, or set ADMIN_EMAIL={email} to promote it on first sign-in.",
+            username.map(|u| format!(" with username {u}")).unwrap_or_default()
+        );
+    };
+    if user.role == "admin" && user.active {
+        println!("{} ({}) is already an admin.", user.username, user.email);
+        return Ok(());
+    }
+
+    let now = to_bson(&Utc::now())?;
+    users
+        .update_one(
+            doc! { "_id": &user.id },
+            doc! { "$set": { "role": "admin", "active": true, "updated_at": now } },
+            None,
+        )
+        .await
+        .context("Could not update the user")?;
+    tracing::warn!("CLI: promoted {} ({}) to admin", user.username, user.id);
+    println!(
+        "Promoted {} ({}) to admin. A running server picks this up within USER_CACHE_TTL_SECONDS.",
+        user.username, user.email
+    );
+    Ok(())
+}
+
+/// A CTI type name and its item names.
+type DemoType = (&'static str, &'static [&'static str]);
+
+/// Category → type → items.
+const DEMO_TAXONOMY: &[(&str, &[DemoType])] = &[
+    ("Network", &[
+        ("Connectivity", &["Outage", "Packet loss", "Slow link"]),
+        ("Firewall", &["Rule change", "Blocked traffic"]),
+    ]),
+    ("Hardware", &[
+        ("Laptop", &["Won't boot", "Battery", "Screen"]),
+        ("Printer", &["Paper jam", "Driver"]),
+    ]),
+    ("Access", &[
+        ("Accounts", &["New account", "Password reset"]),
+        ("Permissions", &["Group membership", "Shared drive"]),
+    ]),
+];
+
+/// Title, status and `(category, type, item)` names from `DEMO_TAXONOMY`.
+const DEMO_TASKS: &[(&str, &str, (&str, &str, &str))] = &[
+    ("Branch office VPN down", "in_progress", ("Network", "Connectivity", "Outage")),
+    ("Open port 8443 for the metrics gateway", "todo", ("Network", "Firewall", "Rule change")),
+    ("Replace battery in loaner laptop #12", "done", ("Hardware", "Laptop", "Battery")),
+    ("Second-floor printer jams on duplex", "todo", ("Hardware", "Printer", "Paper jam")),
+    ("Onboard new analyst", "in_progress", ("Access", "Accounts", "New account")),
+    ("Grant ops team access to runbooks share", "done", ("Access", "Permissions", "Shared drive")),
+];
+
+fn slug(parts: &[&str]) -> String {
+    let joined = parts.join("-").to_lowercase();
+    let cleaned: String = joined.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+    format!("seed-{cleaned}")
+}
+
+struct DemoData {
+    categories: Vec<Category>,
+    types: Vec<CtiType>,
+    items: Vec<CtiItem>,
+    tasks: Vec<Task>,
+}
+
+fn demo_data() -> DemoData {
+    let mut data = DemoData { categories: vec![], types: vec![], items: vec![], tasks: vec![] };
+    for (category, types) in DEMO_TAXONOMY {
+        let mut c = Category::new(&SystemClock, &UuidIds, category.to_string());
+        c.id = slug(&[category]);
+        for (cti_type, items) in *types {
+            let mut t = CtiType::new(&SystemClock, &UuidIds, cti_type.to_string(), c.id.clone());
+            t.id = slug(&[category, cti_type]);
+            for item in *items {
+                let mut i = CtiItem::new(&SystemClock, &UuidIds, item.to_string(), t.id.clone());
+                i.id = slug(&[category, cti_type, item]);
+                data.items.push(i);
+            }
+            data.types.push(t);
+        }
+        data.categories.push(c);
+    }
+    for (n, (title, status, (category, cti_type, item))) in DEMO_TASKS.iter().enumerate() {
+        let mut task = Task::new(&SystemClock, &UuidIds, title.to_string(), format!("Sample task seeded for development: {title}."));
+        task.id = format!("seed-task-{}", n + 1);
+        task.status = status.to_string();
+        if *status == "done" {
+            task.status_changed_at = Some(task.updated_at);
+        }
+        task.cti = Some(CtiSelection {
+            category_id: slug(&[category]),
+            type_id: slug(&[category, cti_type]),
+            item_id: slug(&[category, cti_type, item]),
+        });
+        data.tasks.push(task);
+    }
+    data
+}
+
+async fn upsert_all<T: Serialize + Send + Sync>(
+    collection: Collection<T>,
+    docs: &[T],
+    id: impl Fn(&T) -> &str,
+) -> Result<()> {
+    let options = ReplaceOptions::builder().upsert(true).build();
+    for d in docs {
+        collection
+            .replace_one(doc! { "_id": id(d) }, d, options.clone())
+            .await
+            .with_context(|| format!("Could not write {}", collection.name()))?;
+    }
+    Ok(())
+}
+
+pub async fn seed(db: &Db) -> Result<()> {
+    let data = demo_data();
+    upsert_all(db.collection(CTI_CATEGORIES), &data.categories, |c: &Category| &c.id).await?;
+    upsert_all(db.collection(CTI_TYPES), &data.types, |t: &CtiType| &t.id).await?;
+    upsert_all(db.collection(CTI_ITEMS), &data.items, |i: &CtiItem| &i.id).await?;
+    upsert_all(db.collection(TASKS), &data.tasks, |t: &Task| &t.id).await?;
+    println!(
+        "Seeded {} CTI categories, {} types, {} items and {} tasks.",
+        data.categories.len(),
+        data.types.len(),
+        data.items.len(),
+        data.tasks.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serve_is_the_default() {
+        let cli = Cli::try_parse_from(["missoncontrol"]).unwrap();
+        assert!(cli.command.is_none());
+        let cli = Cli::try_parse_from(["missoncontrol", "--migrate-only"]).unwrap();
+        assert!(cli.migrate_only);
+        let cli = Cli::try_parse_from(["missoncontrol", "serve", "--migrate-only"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Serve));
+        assert!(cli.migrate_only);
+        assert!(!cli.check);
+        assert!(Cli::try_parse_from(["missoncontrol", "--check"]).unwrap().check);
+    }
+
+    #[test]
+    fn create_admin_requires_an_email() {
+        assert!(Cli::try_parse_from(["missoncontrol", "create-admin"]).is_err());
+        let cli = Cli::try_parse_from(["missoncontrol", "create-admin", "--email", "a@example.com"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::CreateAdmin { email: "a@example.com".into(), username: None })
+        );
+    }
+
+    #[test]
+    fn demo_tasks_point_at_seeded_cti() {
+        let data = demo_data();
+        for task in &data.tasks {
+            let cti = task.cti.as_ref().unwrap();
+            assert!(data.categories.iter().any(|c| c.id == cti.category_id), "{}", task.title);
+            assert!(data.types.iter().any(|t| t.id == cti.type_id && t.category_id == cti.category_id));
+            assert!(data.items.iter().any(|i| i.id == cti.item_id && i.type_id == cti.type_id));
+        }
+    }
+
+    #[test]
+    fn seeded_ids_are_stable() {
+        assert_eq!(slug(&["Hardware", "Laptop", "Won't boot"]), "seed-hardware-laptop-won-t-boot");
+        let ids = |d: DemoData| d.items.into_iter().map(|i| i.id).collect::<Vec<_>>();
+        assert_eq!(ids(demo_data()), ids(demo_data()));
+    }
+}
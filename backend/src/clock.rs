@@ -0,0 +1,112 @@
+//! Where the current time and new ids come from, so time-dependent logic
+//! (quota windows, invite and session expiry) can be tested by moving a fake
+//! clock instead of sleeping.
+//!
+//! Handlers use `state.clock` and `state.ids`; code without an `AppState`
+//! (migrations, the CLI, background workers) still calls `dates::now()` and
+//! `Uuid::new_v4()` directly.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::models::dates;
+
+pub trait Clock: Send + Sync {
+    /// The current time, at the millisecond precision BSON dates keep.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub trait IdGen: Send + Sync {
+    /// A fresh document id.
+    fn new_id(&self) -> String;
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        dates::now()
+    }
+}
+
+/// Random v4 UUIDs, as stored everywhere.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidIds;
+
+impl IdGen for UuidIds {
+    fn new_id(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// A clock for tests that only moves when told to.
+#[derive(Debug)]
+pub struct FakeClock(Mutex<DateTime<Utc>>);
+
+impl FakeClock {
+    pub fn at(now: DateTime<Utc>) -> Self {
+        Self(Mutex::new(now))
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Default for FakeClock {
+    /// 2024-01-01T00:00:00Z, so expected timestamps can be written out.
+    fn default() -> Self {
+        Self::at(DateTime::from_timestamp(1_704_067_200, 0).unwrap())
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Ids for tests: `id-1`, `id-2`, …
+#[derive(Debug, Default)]
+pub struct SequentialIds(AtomicU64);
+
+impl IdGen for SequentialIds {
+    fn new_id(&self) -> String {
+        format!("id-{}", self.0.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_clock_moves_only_when_told() {
+        let clock = FakeClock::default();
+        let start = clock.now();
+        assert_eq!(start.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::minutes(90));
+        assert_eq!(clock.now() - start, Duration::minutes(90));
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+
+    #[test]
+    fn sequential_ids_count_up() {
+        let ids = SequentialIds::default();
+        assert_eq!([ids.new_id(), ids.new_id()], ["id-1", "id-2"]);
+        assert_ne!(UuidIds.new_id(), UuidIds.new_id());
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bson::{doc, Document};
+use tokio::sync::RwLock;
+
+use crate::{
+    db::{CtiRepo, Db, WORKSPACES},
+    errors::AppResult,
+    models::{
+        cti::{Category, CtiItem, CtiType},
+        workspace::DEFAULT_WORKSPACE_ID,
+    },
+};
+
+/// How long clients may reuse a CTI list response, in seconds. The taxonomy
+/// rarely changes, and the ETag lets them revalidate cheaply afterwards.
+pub const CTI_MAX_AGE_SECS: u64 = 60;
+
+/// One workspace's whole CTI taxonomy.
+#[derive(Debug, Default)]
+pub struct CtiTree {
+    pub categories: Vec<Category>,
+    pub types: Vec<CtiType>,
+    pub items: Vec<CtiItem>,
+}
+
+impl CtiTree {
+    pub async fn load(repo: &dyn CtiRepo, ws: &str) -> AppResult<Self> {
+        Ok(Self {
+            categories: repo.find_categories(ws, doc! {}).await?,
+            types: repo.find_types(ws, doc! {}).await?,
+            items: repo.find_items(ws, doc! {}).await?,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Bumped by every invalidation, so a load that raced with a write is
+    /// returned to its caller but never stored.
+    generation: u64,
+    /// Each tree with when it was loaded.
+    trees: HashMap<String, (Instant, Arc<CtiTree>)>,
+}
+
+/// In-process cache of each workspace's CTI taxonomy, kept until a CTI write
+/// invalidates it or `ttl` (`CTI_CACHE_TTL_SECONDS`) passes. Writes made by
+/// another instance, or straight to MongoDB, are seen once the ttl is up, or
+/// at once after `POST /api/admin/cache/cti/refresh`.
+#[derive(Clone)]
+pub struct CtiCache {
+    ttl: Duration,
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl CtiCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, inner: Arc::default() }
+    }
+
+    /// The taxonomy of `ws`, loaded from `repo` on a miss.
+    pub async fn tree(&self, repo: &dyn CtiRepo, ws: &str) -> AppResult<Arc<CtiTree>> {
+        self.get_or_load(ws, CtiTree::load(repo, ws)).await
+    }
+
+    async fn get_or_load<F>(&self, ws: &str, load: F) -> AppResult<Arc<CtiTree>>
+    where
+        F: Future<Output = AppResult<CtiTree>>,
+    {
+        let generation = {
+            let inner = self.inner.read().await;
+            if let Some((_, tree)) = inner.trees.get(ws).filter(|(at, _)| at.elapsed() < self.ttl) {
+                return Ok(tree.clone());
+            }
+            inner.generation
+        };
+        let tree = Arc::new(load.await?);
+        let mut inner = self.inner.write().await;
+        if inner.generation == generation {
+            inner.trees.insert(ws.to_string(), (Instant::now(), tree.clone()));
+        }
+        Ok(tree)
+    }
+
+    /// Drops the taxonomy of `ws`; call after every CTI write.
+    pub async fn invalidate(&self, ws: &str) {
+        let mut inner = self.inner.write().await;
+        inner.generation += 1;
+        inner.trees.remove(ws);
+    }
+
+    /// Drops every workspace's taxonomy, e.g. after a restore.
+    pub async fn invalidate_all(&self) {
+        let mut inner = self.inner.write().await;
+        inner.generation += 1;
+        inner.trees.clear();
+    }
+
+    pub async fn generation(&self) -> u64 {
+        self.inner.read().await.generation
+    }
+
+    /// Loads every workspace's taxonomy, at startup and on a forced refresh.
+    /// Returns how many workspaces were loaded.
+    pub async fn warm(&self, db: &Db, repo: &dyn CtiRepo) -> AppResult<usize> {
+        let ids = db.collection::<Document>(WORKSPACES).distinct("_id", None, None).await?;
+        let mut ids: Vec<String> = ids.into_iter().filter_map(|id| id.as_str().map(str::to_string)).collect();
+        if !ids.iter().any(|id| id == DEFAULT_WORKSPACE_ID) {
+            ids.push(DEFAULT_WORKSPACE_ID.to_string());
+        }
+        for ws in &ids {
+            self.tree(repo, ws).await?;
+        }
+        Ok(ids.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::sync::oneshot;
+
+    use super::*;
+    use crate::clock::{SystemClock, UuidIds};
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn tree_named(name: &str) -> CtiTree {
+        CtiTree { categories: vec![Category::new(&SystemClock, &UuidIds, name.into())], ..Default::default() }
+    }
+
+    async fn counted(calls: &AtomicUsize, name: &str) -> AppResult<CtiTree> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Ok(tree_named(name))
+    }
+
+    #[tokio::test]
+    async fn loads_once_until_invalidated() {
+        let cache = CtiCache::new(TTL);
+        let calls = AtomicUsize::new(0);
+        cache.get_or_load("ws1", counted(&calls, "a")).await.unwrap();
+        let tree = cache.get_or_load("ws1", counted(&calls, "b")).await.unwrap();
+        assert_eq!(tree.categories[0].name, "a");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Other workspaces are cached separately and survive the invalidation.
+        cache.get_or_load("ws2", counted(&calls, "c")).await.unwrap();
+        cache.invalidate("ws1").await;
+        assert_eq!(cache.get_or_load("ws1", counted(&calls, "d")).await.unwrap().categories[0].name, "d");
+        assert_eq!(cache.get_or_load("ws2", counted(&calls, "e")).await.unwrap().categories[0].name, "c");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        cache.invalidate_all().await;
+        assert_eq!(cache.get_or_load("ws2", counted(&calls, "f")).await.unwrap().categories[0].name, "f");
+        assert_eq!(cache.generation().await, 2);
+    }
+
+    #[tokio::test]
+    async fn trees_are_reloaded_once_the_ttl_is_up() {
+        let cache = CtiCache::new(Duration::ZERO);
+        let calls = AtomicUsize::new(0);
+        cache.get_or_load("ws1", counted(&calls, "a")).await.unwrap();
+        let tree = cache.get_or_load("ws1", counted(&calls, "b")).await.unwrap();
+        assert_eq!(tree.categories[0].name, "b");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn a_load_overtaken_by_a_write_is_not_cached() {
+        let cache = CtiCache::new(TTL);
+        let (release, released) = oneshot::channel::<()>();
+        let slow = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_load("ws1", async {
+                        released.await.unwrap();
+                        Ok(tree_named("stale"))
+                    })
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        // A write lands while the slow read is still loading.
+        cache.invalidate("ws1").await;
+        release.send(()).unwrap();
+        assert_eq!(slow.await.unwrap().unwrap().categories[0].name, "stale");
+
+        let calls = AtomicUsize::new(0);
+        let tree = cache.get_or_load("ws1", counted(&calls, "fresh")).await.unwrap();
+        assert_eq!(tree.categories[0].name, "fresh");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_readers_and_writers_settle_on_the_latest_tree() {
+        let cache = CtiCache::new(TTL);
+        let tasks: Vec<_> = (0..50)
+            .map(|i| {
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    if i % 5 == 0 {
+                        cache.invalidate("ws1").await;
+                    } else {
+                        cache.get_or_load("ws1", async move { Ok(tree_named(&i.to_string())) }).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        cache.invalidate("ws1").await;
+        let tree = cache.get_or_load("ws1", async { Ok(tree_named("last")) }).await.unwrap();
+        assert_eq!(tree.categories[0].name, "last");
+        assert_eq!(cache.generation().await, 11);
+    }
+}
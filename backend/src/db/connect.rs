@@ -0,0 +1,84 @@
+//! Waiting for MongoDB at startup. Containers often start before the
+//! database does; rather than exit and crash-loop, the server pings with
+//! exponential backoff for up to `MONGO_CONNECT_MAX_WAIT_SECONDS`, so
+//! migrations and index creation only start once it answers.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use bson::doc;
+
+use crate::{
+    db::Db,
+    errors::mongo::{classify, DbErrorClass},
+};
+
+const FIRST_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Delays between attempts: doubling from `FIRST_DELAY` up to `MAX_DELAY`.
+struct Backoff {
+    next: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self { next: FIRST_DELAY }
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.next;
+        self.next = (self.next * 2).min(MAX_DELAY);
+        Some(delay)
+    }
+}
+
+/// One ping may take this long; a server-selection wait would otherwise
+/// block for the driver's 30 s default.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pings `db` until it answers. Transient failures (network, server
+/// selection, elections) and slow pings are retried until `max_wait` has
+/// passed; anything else, such as bad credentials, fails at once.
+pub async fn wait_for_mongo(db: &Db, max_wait: Duration) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let mut delays = Backoff::new();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let reason = match tokio::time::timeout(PING_TIMEOUT, db.run_command(doc! { "ping": 1 }, None)).await {
+            Ok(Ok(_)) => {
+                if attempt > 1 {
+                    tracing::info!(attempt, waited_ms = started.elapsed().as_millis() as u64, "Connected to MongoDB");
+                }
+                return Ok(());
+            }
+            Ok(Err(e)) if classify(&e) != DbErrorClass::Transient => {
+                return Err(e).context("Could not connect to MongoDB");
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("ping timed out after {PING_TIMEOUT:?}"),
+        };
+        let left = max_wait.saturating_sub(started.elapsed());
+        let Some(delay) = delays.next().map(|d| d.min(left)).filter(|d| !d.is_zero()) else {
+            bail!("MongoDB still unreachable after {attempt} attempts over {:?}: {reason}", started.elapsed());
+        };
+        tracing::warn!(attempt, retry_in_ms = delay.as_millis() as u64, "MongoDB not reachable yet: {reason}");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        let ms: Vec<u128> = Backoff::new().take(7).map(|d| d.as_millis()).collect();
+        assert_eq!(ms, [500, 1_000, 2_000, 4_000, 8_000, 10_000, 10_000]);
+    }
+}
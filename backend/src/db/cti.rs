@@ -0,0 +1,170 @@
+use axum::async_trait;
+use bson::{doc, Document};
+use mongodb::Collection;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::instrument;
+
+use crate::{
+    db::{check_workspace, collect, Db, QueryTimer, Transactions, CTI_CATEGORIES, CTI_ITEMS, CTI_TYPES},
+    errors::{AppError, AppResult},
+    models::cti::{Category, CtiItem, CtiType},
+};
+
+/// Like `TaskRepo`, every method is scoped to the workspace `ws`.
+#[async_trait]
+pub trait CtiRepo: Send + Sync {
+    async fn list_categories(&self, ws: &str) -> AppResult<Vec<Category>>;
+    /// Everything matching `filter`, for batched lookups such as
+    /// `{ "_id": { "$in": ids } }`.
+    async fn find_categories(&self, ws: &str, filter: Document) -> AppResult<Vec<Category>>;
+    async fn find_types(&self, ws: &str, filter: Document) -> AppResult<Vec<CtiType>>;
+    async fn find_items(&self, ws: &str, filter: Document) -> AppResult<Vec<CtiItem>>;
+    /// Inserts fail if the document belongs to a workspace other than `ws`.
+    async fn insert_category(&self, ws: &str, category: &Category) -> AppResult<()>;
+    async fn insert_type(&self, ws: &str, cti_type: &CtiType) -> AppResult<()>;
+    async fn insert_item(&self, ws: &str, item: &CtiItem) -> AppResult<()>;
+    /// Each delete returns whether something was deleted. Deleting a
+    /// category or type also deletes everything beneath it.
+    async fn delete_category(&self, ws: &str, id: &str) -> AppResult<bool>;
+    async fn delete_type(&self, ws: &str, id: &str) -> AppResult<bool>;
+    async fn delete_item(&self, ws: &str, id: &str) -> AppResult<bool>;
+}
+
+pub struct MongoCtiRepo {
+    categories: Collection<Category>,
+    types: Collection<CtiType>,
+    items: Collection<CtiItem>,
+    txn: Transactions,
+    timer: QueryTimer,
+}
+
+impl MongoCtiRepo {
+    pub fn new(db: &Db, txn: Transactions, timer: QueryTimer) -> Self {
+        Self {
+            categories: db.collection(CTI_CATEGORIES),
+            types: db.collection(CTI_TYPES),
+            items: db.collection(CTI_ITEMS),
+            txn,
+            timer,
+        }
+    }
+
+    async fn find<T>(&self, name: &'static str, collection: &Collection<T>, filter: Document) -> AppResult<Vec<T>>
+    where
+        T: DeserializeOwned + Send + Sync,
+    {
+        let cursor = self.timer.time(name, "find", &filter, collection.find(filter.clone(), None)).await?;
+        Ok(collect(cursor).await?)
+    }
+
+    async fn insert<T: Serialize + Send + Sync>(&self, name: &'static str, collection: &Collection<T>, doc: &T) -> AppResult<()> {
+        self.timer.time(name, "insert_one", &doc! {}, collection.insert_one(doc, None)).await.map_err(AppError::from)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CtiRepo for MongoCtiRepo {
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_CATEGORIES))]
+    async fn list_categories(&self, ws: &str) -> AppResult<Vec<Category>> {
+        self.find(CTI_CATEGORIES, &self.categories, doc! { "workspace_id": ws }).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_CATEGORIES))]
+    async fn find_categories(&self, ws: &str, mut filter: Document) -> AppResult<Vec<Category>> {
+        filter.insert("workspace_id", ws);
+        self.find(CTI_CATEGORIES, &self.categories, filter).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_TYPES))]
+    async fn find_types(&self, ws: &str, mut filter: Document) -> AppResult<Vec<CtiType>> {
+        filter.insert("workspace_id", ws);
+        self.find(CTI_TYPES, &self.types, filter).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_ITEMS))]
+    async fn find_items(&self, ws: &str, mut filter: Document) -> AppResult<Vec<CtiItem>> {
+        filter.insert("workspace_id", ws);
+        self.find(CTI_ITEMS, &self.items, filter).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_CATEGORIES))]
+    async fn insert_category(&self, ws: &str, category: &Category) -> AppResult<()> {
+        check_workspace(ws, &category.workspace_id)?;
+        self.insert(CTI_CATEGORIES, &self.categories, category).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_TYPES))]
+    async fn insert_type(&self, ws: &str, cti_type: &CtiType) -> AppResult<()> {
+        check_workspace(ws, &cti_type.workspace_id)?;
+        self.insert(CTI_TYPES, &self.types, cti_type).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_ITEMS))]
+    async fn insert_item(&self, ws: &str, item: &CtiItem) -> AppResult<()> {
+        check_workspace(ws, &item.workspace_id)?;
+        self.insert(CTI_ITEMS, &self.items, item).await
+    }
+
+    // Children go first, so without transactions an interrupted delete
+    // leaves the parent in place and can simply be repeated.
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_CATEGORIES))]
+    async fn delete_category(&self, ws: &str, id: &str) -> AppResult<bool> {
+        let filter = doc! { "_id": id, "workspace_id": ws };
+        let delete = self
+            .txn
+            .with_txn(|session| {
+                let (categories, types, items) = (self.categories.clone(), self.types.clone(), self.items.clone());
+                let (ws, id) = (ws.to_string(), id.to_string());
+                Box::pin(async move {
+                    let children = doc! { "workspace_id": &ws, "category_id": &id };
+                    let type_ids = types.distinct_with_session("_id", children.clone(), None, &mut *session).await?;
+                    items
+                        .delete_many_with_session(
+                            doc! { "workspace_id": &ws, "type_id": { "$in": type_ids } },
+                            None,
+                            &mut *session,
+                        )
+                        .await?;
+                    types.delete_many_with_session(children, None, &mut *session).await?;
+                    let result = categories
+                        .delete_one_with_session(doc! { "_id": &id, "workspace_id": &ws }, None, session)
+                        .await?;
+                    Ok(result.deleted_count > 0)
+                })
+            });
+        self.timer.time(CTI_CATEGORIES, "delete_cascade", &filter, delete).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_TYPES))]
+    async fn delete_type(&self, ws: &str, id: &str) -> AppResult<bool> {
+        let filter = doc! { "_id": id, "workspace_id": ws };
+        let delete = self
+            .txn
+            .with_txn(|session| {
+                let (types, items) = (self.types.clone(), self.items.clone());
+                let (ws, id) = (ws.to_string(), id.to_string());
+                Box::pin(async move {
+                    items
+                        .delete_many_with_session(doc! { "workspace_id": &ws, "type_id": &id }, None, &mut *session)
+                        .await?;
+                    let result = types
+                        .delete_one_with_session(doc! { "_id": &id, "workspace_id": &ws }, None, session)
+                        .await?;
+                    Ok(result.deleted_count > 0)
+                })
+            });
+        self.timer.time(CTI_TYPES, "delete_cascade", &filter, delete).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = CTI_ITEMS))]
+    async fn delete_item(&self, ws: &str, id: &str) -> AppResult<bool> {
+        let filter = doc! { "_id": id, "workspace_id": ws };
+        let result = self
+            .timer
+            .time(CTI_ITEMS, "delete_one", &filter, self.items.delete_one(filter.clone(), None))
+            .await
+            .map_err(AppError::from)?;
+        Ok(result.deleted_count > 0)
+    }
+}
@@ -0,0 +1,218 @@
+//! Indexes the app relies on, created at startup. Creating an index that
+//! already exists with the same keys and options is a no-op, so this is safe
+//! to run on every boot.
+
+use std::time::Duration;
+
+use bson::{doc, Document};
+use mongodb::{
+    options::{Collation, CollationStrength, IndexOptions},
+    IndexModel,
+};
+
+use crate::{
+    config::AppConfig,
+    db::{
+        Db, API_KEYS, ARCHIVED_TASKS, CTI_CATEGORIES, CTI_ITEMS, CTI_TYPES, INVITES, NOTIFICATIONS, RETENTION_RUNS,
+        SAVED_VIEWS, TASKS, TASK_REVISIONS, TASK_SHARES, TEAMS, USERS, WORKSPACE_MEMBERS,
+    },
+};
+
+/// Compares strings ignoring case (but not accents). Queries must pass the
+/// same collation to use an index built with it.
+pub fn case_insensitive() -> Collation {
+    Collation::builder().locale("en").strength(CollationStrength::Secondary).build()
+}
+
+/// One index on one collection.
+pub struct IndexSpec {
+    pub collection: &'static str,
+    pub keys: Document,
+    pub options: Option<IndexOptions>,
+}
+
+impl IndexSpec {
+    fn new(collection: &'static str, keys: Document) -> Self {
+        Self { collection, keys, options: None }
+    }
+
+    fn unique(mut self) -> Self {
+        self.options = Some(IndexOptions::builder().unique(true).build());
+        self
+    }
+
+    /// Compares with `case_insensitive`, so with `unique` two values that
+    /// differ only in case clash.
+    fn case_insensitive(mut self) -> Self {
+        let mut options = self.options.take().unwrap_or_default();
+        options.collation = Some(case_insensitive());
+        self.options = Some(options);
+        self
+    }
+
+    fn expire_after(mut self, ttl: Duration) -> Self {
+        self.options = Some(IndexOptions::builder().expire_after(ttl).build());
+        self
+    }
+
+    /// The name MongoDB gives an index when none is set, e.g. `status_1_created_at_-1`.
+    pub fn name(&self) -> String {
+        self.keys
+            .iter()
+            .map(|(field, direction)| format!("{field}_{direction}"))
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+pub fn specs(config: &AppConfig) -> Vec<IndexSpec> {
+    vec![
+        // `Bob@example.com` and `bob@example.com` are one account; see
+        // migration 0008.
+        IndexSpec::new(USERS, doc! { "email": 1 }).unique().case_insensitive(),
+        IndexSpec::new(USERS, doc! { "username": 1 }).unique().case_insensitive(),
+        // Task lists stay within a workspace, filter by status and sort
+        // newest first; "my work" and reassignment go by assignee; reports
+        // group by CTI item.
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "status": 1, "created_at": -1 }),
+        IndexSpec::new(TASKS, doc! { "assignee_id": 1, "status": 1 }),
+        // The daily digest lists someone's open tasks by due date.
+        IndexSpec::new(TASKS, doc! { "assignee_id": 1, "due_at": 1 }),
+        IndexSpec::new(TASKS, doc! { "cti.item_id": 1 }),
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "created_at": -1 }),
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "updated_at": -1 }),
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "created_by": 1, "created_at": -1 }),
+        // Double-submitted creates are found by creator and exact title.
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "created_by": 1, "title": 1, "created_at": -1 }),
+        // The stale report finds open tasks by last update; the unassigned
+        // one lists open tasks without an assignee, oldest first.
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "status": 1, "updated_at": 1 }),
+        IndexSpec::new(TASKS, doc! { "workspace_id": 1, "assignee_id": 1, "status": 1, "created_at": 1 }),
+        // A task's revisions are listed and pruned newest first.
+        IndexSpec::new(TASK_REVISIONS, doc! { "task_id": 1, "created_at": -1 }),
+        // Share links are looked up by token hash; a deleted task's go by task.
+        IndexSpec::new(TASK_SHARES, doc! { "token_hash": 1 }).unique(),
+        IndexSpec::new(TASK_SHARES, doc! { "task_id": 1 }),
+        // A deleted task's note attachments are found by task.
+        IndexSpec::new("note_attachments.files", doc! { "metadata.workspace_id": 1, "metadata.task_id": 1 }),
+        // The retention job purges archived tasks by when they were archived;
+        // its runs are listed newest first.
+        IndexSpec::new(ARCHIVED_TASKS, doc! { "archived_at": 1 }),
+        IndexSpec::new(RETENTION_RUNS, doc! { "started_at": -1 }),
+        // CTI categories are listed per workspace; children are listed and
+        // cascade-deleted by parent.
+        IndexSpec::new(CTI_CATEGORIES, doc! { "workspace_id": 1 }),
+        IndexSpec::new(CTI_TYPES, doc! { "category_id": 1 }),
+        IndexSpec::new(CTI_ITEMS, doc! { "type_id": 1 }),
+        // Memberships are checked by `_id`; a user's workspaces are listed by user.
+        IndexSpec::new(WORKSPACE_MEMBERS, doc! { "user_id": 1 }),
+        IndexSpec::new(WORKSPACE_MEMBERS, doc! { "workspace_id": 1, "role": 1 }),
+        // API keys are looked up by hash on every request and listed per owner.
+        IndexSpec::new(API_KEYS, doc! { "key_hash": 1 }).unique(),
+        IndexSpec::new(API_KEYS, doc! { "owner_id": 1 }),
+        IndexSpec::new(INVITES, doc! { "code": 1 }).unique(),
+        // Login history: listed per user, newest first, expired by the retention TTL.
+        IndexSpec::new("login_events", doc! { "user_id": 1, "created_at": -1 }),
+        IndexSpec::new("login_events", doc! { "created_at": 1 })
+            .expire_after(Duration::from_secs(config.login_event_retention_days * 86400)),
+        // Notifications: listed per recipient (optionally unread only), newest
+        // first; read ones expire after the retention period.
+        IndexSpec::new(NOTIFICATIONS, doc! { "recipient_id": 1, "read_at": 1, "created_at": -1 }),
+        IndexSpec::new(NOTIFICATIONS, doc! { "read_at": 1 })
+            .expire_after(Duration::from_secs(config.notification_retention_days * 86400)),
+        // Saved views are listed, and the default one found, per owner and workspace.
+        IndexSpec::new(SAVED_VIEWS, doc! { "owner_id": 1, "workspace_id": 1, "is_default": 1 }),
+        // Team names are unique per workspace, where teams are listed by name;
+        // the `team_id` task filter loads a team by `_id`.
+        IndexSpec::new(TEAMS, doc! { "workspace_id": 1, "name": 1 }).unique(),
+        // Weather: locations by user, alerts deduplicated by NWS id.
+        IndexSpec::new("weather_locations", doc! { "user_id": 1 }),
+        IndexSpec::new("weather_alerts", doc! { "nws_id": 1 }).unique(),
+        IndexSpec::new("weather_alerts", doc! { "location_id": 1, "fetched_at": -1 }),
+        IndexSpec::new("weather_observations", doc! { "location_id": 1, "timestamp": -1 }),
+        // Observations expire after 48 hours.
+        IndexSpec::new("weather_observations", doc! { "fetched_at": 1 })
+            .expire_after(Duration::from_secs(48 * 3600)),
+    ]
+}
+
+/// Index names on `collection`; empty if the collection does not exist yet.
+async fn existing_names(db: &Db, collection: &str) -> mongodb::error::Result<Vec<String>> {
+    match db.collection::<Document>(collection).list_index_names().await {
+        Ok(names) => Ok(names),
+        // NamespaceNotFound
+        Err(e) if matches!(e.kind.as_ref(), mongodb::error::ErrorKind::Command(c) if c.code == 26) => {
+            Ok(Vec::new())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates every index in `specs`, logging which were new.
+pub async fn ensure_indexes(db: &Db, config: &AppConfig) -> mongodb::error::Result<()> {
+    let (mut created, mut present) = (0, 0);
+    for spec in specs(config) {
+        let name = spec.name();
+        if existing_names(db, spec.collection).await?.contains(&name) {
+            present += 1;
+            continue;
+        }
+        let model = IndexModel::builder().keys(spec.keys).options(spec.options).build();
+        db.collection::<Document>(spec.collection).create_index(model, None).await?;
+        tracing::info!(collection = spec.collection, index = %name, "Created MongoDB index");
+        created += 1;
+    }
+    tracing::info!(created, already_present = present, "MongoDB indexes ensured");
+    Ok(())
+}
+
+/// `collection.index` for every index in `specs` not yet created, as after
+/// a failed startup or on a database the server never ran against.
+pub async fn missing_indexes(db: &Db, config: &AppConfig) -> mongodb::error::Result<Vec<String>> {
+    let mut missing = Vec::new();
+    for spec in specs(config) {
+        let name = spec.name();
+        if !existing_names(db, spec.collection).await?.contains(&name) {
+            missing.push(format!("{}.{name}", spec.collection));
+        }
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_follow_mongodb_defaults() {
+        let spec = IndexSpec::new(TASKS, doc! { "status": 1, "created_at": -1 });
+        assert_eq!(spec.name(), "status_1_created_at_-1");
+        assert_eq!(IndexSpec::new(TASKS, doc! { "cti.item_id": 1 }).name(), "cti.item_id_1");
+    }
+
+    #[test]
+    fn every_index_is_listed_once() {
+        let specs = specs(&AppConfig::for_tests());
+        let mut names: Vec<_> = specs.iter().map(|s| format!("{}.{}", s.collection, s.name())).collect();
+        let total = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), total);
+        for expected in [
+            "tasks.workspace_id_1_status_1_created_at_-1",
+            "tasks.assignee_id_1_status_1",
+            "tasks.cti.item_id_1",
+            "tasks.workspace_id_1_updated_at_-1",
+            "tasks.workspace_id_1_status_1_updated_at_1",
+            "tasks.workspace_id_1_created_by_1_title_1_created_at_-1",
+            "cti_categories.workspace_id_1",
+            "workspace_members.user_id_1",
+            "cti_types.category_id_1",
+            "cti_items.type_id_1",
+            "notifications.recipient_id_1_read_at_1_created_at_-1",
+            "notifications.read_at_1",
+        ] {
+            assert!(names.iter().any(|n| n == expected), "missing {expected}");
+        }
+    }
+}
@@ -1 +1,141 @@
+//! Typed access to MongoDB. Handlers go through the repositories in `Repos`
+//! rather than naming collections themselves, so tests can swap in
+//! in-memory fakes.
+
+use std::sync::Arc;
+
+use bson::{doc, Bson, Document};
+use mongodb::{error::Error, Cursor};
+use serde::de::DeserializeOwned;
+
+use crate::errors::{AppError, AppResult};
+
+pub mod connect;
+pub mod cti;
+pub mod indexes;
+pub mod tasks;
+pub mod timing;
+pub mod txn;
+pub mod users;
+
+pub use cti::{CtiRepo, MongoCtiRepo};
+pub use tasks::{MongoTaskRepo, TaskRepo};
+pub use timing::QueryTimer;
+pub use txn::Transactions;
+pub use users::{MongoUserRepo, UserRepo};
+
 pub type Db = mongodb::Database;
+
+pub const TASKS: &str = "tasks";
+pub const USERS: &str = "users";
+pub const CTI_CATEGORIES: &str = "cti_categories";
+pub const CTI_TYPES: &str = "cti_types";
+pub const CTI_ITEMS: &str = "cti_items";
+/// Small documents bumped inside transactions purely to make concurrent ones conflict.
+pub const LOCKS: &str = "locks";
+/// Ids of the migrations already applied; see `migrations`.
+pub const SCHEMA_MIGRATIONS: &str = "schema_migrations";
+pub const WEBHOOKS: &str = "webhooks";
+pub const NOTIFICATIONS: &str = "notifications";
+pub const WORKSPACES: &str = "workspaces";
+pub const WORKSPACE_MEMBERS: &str = "workspace_members";
+pub const AUDIT_LOG: &str = "audit_log";
+pub const SAVED_VIEWS: &str = "saved_views";
+pub const TEAMS: &str = "teams";
+pub const API_KEYS: &str = "api_keys";
+pub const INVITES: &str = "invites";
+/// Task statuses, keyed by the value tasks store; see `models::workflow`.
+pub const WORKFLOW_STATUSES: &str = "workflow_statuses";
+/// Earlier values of task fields; see `models::revision`.
+pub const TASK_REVISIONS: &str = "task_revisions";
+/// Read-only task links for people without accounts; see `models::share`.
+pub const TASK_SHARES: &str = "task_shares";
+/// GridFS bucket of files attached to task notes; see `handlers::attachments`.
+pub const NOTE_ATTACHMENTS: &str = "note_attachments";
+/// Tasks the retention job moved out of `tasks`; see `retention`.
+pub const ARCHIVED_TASKS: &str = "archived_tasks";
+/// One summary per retention run; see `models::retention`.
+pub const RETENTION_RUNS: &str = "retention_runs";
+/// Throwaway documents the self-check writes and deletes; see `diagnostics`.
+pub const DIAGNOSTICS: &str = "diagnostics";
+
+/// The repositories handlers use, shared through `AppState`.
+#[derive(Clone)]
+pub struct Repos {
+    pub tasks: Arc<dyn TaskRepo>,
+    pub users: Arc<dyn UserRepo>,
+    pub cti: Arc<dyn CtiRepo>,
+    pub txn: Transactions,
+}
+
+impl Repos {
+    pub fn mongo(db: &Db, txn: Transactions, timer: QueryTimer) -> Self {
+        Self {
+            tasks: Arc::new(MongoTaskRepo::new(db, timer)),
+            users: Arc::new(MongoUserRepo::new(db, timer)),
+            cti: Arc::new(MongoCtiRepo::new(db, txn.clone(), timer)),
+            txn,
+        }
+    }
+}
+
+/// Drains a cursor into a `Vec`.
+pub async fn collect<T>(mut cursor: Cursor<T>) -> Result<Vec<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    let mut items = Vec::new();
+    while cursor.advance().await? {
+        items.push(cursor.deserialize_current()?);
+    }
+    Ok(items)
+}
+
+/// `filter` narrowed to `ids`, so a batch write found by `filter` only takes
+/// documents that still match it.
+pub fn batch_filter(filter: &Document, ids: &[Bson]) -> Document {
+    doc! { "$and": [filter.clone(), { "_id": { "$in": ids } }] }
+}
+
+/// Escapes regex metacharacters so user input can be matched literally.
+pub fn escape_regex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Rejects a document being written into a workspace it does not belong to.
+pub fn check_workspace(ws: &str, doc_workspace: &str) -> AppResult<()> {
+    if doc_workspace != ws {
+        return Err(AppError::Internal(anyhow::anyhow!(
+            "document for workspace '{doc_workspace}' written in workspace '{ws}'"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_are_written_only_while_they_still_match() {
+        let filter = doc! { "expires_at": { "$lt": 5 } };
+        let ids = [Bson::from("a"), Bson::from("b")];
+        assert_eq!(
+            batch_filter(&filter, &ids),
+            doc! { "$and": [{ "expires_at": { "$lt": 5 } }, { "_id": { "$in": ["a", "b"] } }] }
+        );
+    }
+
+    #[test]
+    fn escape_regex_quotes_metacharacters() {
+        assert_eq!(escape_regex("a.b+c"), r"a\.b\+c");
+        assert_eq!(escape_regex("plain_name"), "plain_name");
+    }
+}
@@ -0,0 +1,177 @@
+use axum::async_trait;
+use bson::{doc, Document};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
+use mongodb::{
+    options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReturnDocument, UpdateModifications},
+    Collection,
+};
+use tracing::instrument;
+
+use crate::{
+    db::{check_workspace, Db, QueryTimer, TASKS},
+    errors::{AppError, AppResult},
+    models::{
+        task::Task,
+        task_fields::{PartialTask, TaskFields},
+    },
+    pagination::{paginate, PageParams, Paginated},
+};
+
+/// Every method is scoped to the workspace `ws`: tasks in other workspaces
+/// are invisible, exactly as if they did not exist.
+#[async_trait]
+pub trait TaskRepo: Send + Sync {
+    /// One page of tasks matching `filter`, newest first.
+    async fn find_page(&self, ws: &str, filter: Document, sort: Document, page: PageParams) -> AppResult<Paginated<Task>>;
+    async fn find_by_id(&self, ws: &str, id: &str) -> AppResult<Option<Task>>;
+    /// `find_page`, reading only `fields` from the database.
+    async fn find_page_fields(
+        &self,
+        ws: &str,
+        filter: Document,
+        sort: Document,
+        fields: &TaskFields,
+        page: PageParams,
+    ) -> AppResult<Paginated<PartialTask>>;
+    /// `find_by_id`, reading only `fields` from the database.
+    async fn find_by_id_fields(&self, ws: &str, id: &str, fields: &TaskFields) -> AppResult<Option<PartialTask>>;
+    /// The first task matching `filter` in `sort` order.
+    async fn find_first(&self, ws: &str, filter: Document, sort: Document) -> AppResult<Option<Task>>;
+    /// Every matching task, read from the cursor as the stream is polled.
+    async fn find_stream(&self, ws: &str, filter: Document, sort: Document) -> AppResult<BoxStream<'static, AppResult<Task>>>;
+    /// Fails if `task` belongs to a workspace other than `ws`.
+    async fn insert(&self, ws: &str, task: &Task) -> AppResult<()>;
+    /// Applies `update` to the task if it also matches `guard`, returning the
+    /// updated task. `None` means no such task, or `guard` did not match.
+    async fn update_fields(
+        &self,
+        ws: &str,
+        id: &str,
+        guard: Document,
+        update: UpdateModifications,
+    ) -> AppResult<Option<Task>>;
+    /// Returns whether a task was deleted.
+    async fn delete(&self, ws: &str, id: &str) -> AppResult<bool>;
+    /// How many tasks match `filter`; keep `filter` on indexed fields.
+    async fn count(&self, ws: &str, filter: Document) -> AppResult<u64>;
+}
+
+pub struct MongoTaskRepo {
+    collection: Collection<Task>,
+    timer: QueryTimer,
+}
+
+impl MongoTaskRepo {
+    pub fn new(db: &Db, timer: QueryTimer) -> Self {
+        Self { collection: db.collection(TASKS), timer }
+    }
+}
+
+#[async_trait]
+impl TaskRepo for MongoTaskRepo {
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn find_page(&self, ws: &str, mut filter: Document, sort: Document, page: PageParams) -> AppResult<Paginated<Task>> {
+        filter.insert("workspace_id", ws);
+        let options = FindOptions::builder().sort(sort).build();
+        self.timer.time(TASKS, "find_page", &filter, paginate(&self.collection, filter.clone(), options, page)).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn find_by_id(&self, ws: &str, id: &str) -> AppResult<Option<Task>> {
+        let filter = doc! { "_id": id, "workspace_id": ws };
+        self.timer
+            .time(TASKS, "find_one", &filter, self.collection.find_one(filter.clone(), None))
+            .await
+            .map_err(AppError::from)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn find_page_fields(
+        &self,
+        ws: &str,
+        mut filter: Document,
+        sort: Document,
+        fields: &TaskFields,
+        page: PageParams,
+    ) -> AppResult<Paginated<PartialTask>> {
+        filter.insert("workspace_id", ws);
+        let options = FindOptions::builder().sort(sort).projection(fields.projection()).build();
+        let collection = self.collection.clone_with_type::<PartialTask>();
+        let page = self.timer.time(TASKS, "find_page", &filter, paginate(&collection, filter.clone(), options, page)).await?;
+        Ok(page.map(|t| t.with_fields(fields)))
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn find_by_id_fields(&self, ws: &str, id: &str, fields: &TaskFields) -> AppResult<Option<PartialTask>> {
+        let filter = doc! { "_id": id, "workspace_id": ws };
+        let options = FindOneOptions::builder().projection(fields.projection()).build();
+        let collection = self.collection.clone_with_type::<PartialTask>();
+        let task = self.timer.time(TASKS, "find_one", &filter, collection.find_one(filter.clone(), options)).await?;
+        Ok(task.map(|t| t.with_fields(fields)))
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn find_first(&self, ws: &str, mut filter: Document, sort: Document) -> AppResult<Option<Task>> {
+        filter.insert("workspace_id", ws);
+        let options = FindOneOptions::builder().sort(sort).build();
+        self.timer
+            .time(TASKS, "find_one", &filter, self.collection.find_one(filter.clone(), options))
+            .await
+            .map_err(AppError::from)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn find_stream(&self, ws: &str, mut filter: Document, sort: Document) -> AppResult<BoxStream<'static, AppResult<Task>>> {
+        filter.insert("workspace_id", ws);
+        let options = FindOptions::builder().sort(sort).build();
+        let cursor = self.timer.time(TASKS, "find", &filter, self.collection.find(filter.clone(), options)).await?;
+        Ok(cursor.map_err(AppError::from).boxed())
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn insert(&self, ws: &str, task: &Task) -> AppResult<()> {
+        check_workspace(ws, &task.workspace_id)?;
+        self.timer
+            .time(TASKS, "insert_one", &doc! {}, self.collection.insert_one(task, None))
+            .await
+            .map_err(AppError::from)?;
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn update_fields(
+        &self,
+        ws: &str,
+        id: &str,
+        guard: Document,
+        update: UpdateModifications,
+    ) -> AppResult<Option<Task>> {
+        let mut filter = guard;
+        filter.insert("_id", id);
+        filter.insert("workspace_id", ws);
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        self.timer
+            .time(TASKS, "find_one_and_update", &filter, self.collection.find_one_and_update(filter.clone(), update, options))
+            .await
+            .map_err(AppError::from)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn delete(&self, ws: &str, id: &str) -> AppResult<bool> {
+        let filter = doc! { "_id": id, "workspace_id": ws };
+        let result = self
+            .timer
+            .time(TASKS, "delete_one", &filter, self.collection.delete_one(filter.clone(), None))
+            .await
+            .map_err(AppError::from)?;
+        Ok(result.deleted_count > 0)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = TASKS))]
+    async fn count(&self, ws: &str, mut filter: Document) -> AppResult<u64> {
+        filter.insert("workspace_id", ws);
+        Ok(self.timer.time(TASKS, "count_documents", &filter, self.collection.count_documents(filter.clone(), None)).await?)
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use bson::{Bson, Document};
+
+use crate::middleware::{db_budget::count_db_op, request_id::current_request_id};
+
+/// Times the repositories' MongoDB calls: every call lands in the
+/// `mongodb_operation_duration_seconds` summary, and one slower than the
+/// threshold (`SLOW_QUERY_MS`) is also logged and counted in
+/// `mongodb_slow_operations_total`. Each call also counts against the
+/// request's `DB_OPS_BUDGET`.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryTimer {
+    /// Zero turns the slow-operation log off.
+    threshold: Duration,
+}
+
+impl QueryTimer {
+    pub fn new(threshold: Duration) -> Self {
+        Self { threshold }
+    }
+
+    /// Awaits `op`, the `operation` (e.g. `"find_one"`) on `collection`.
+    /// `filter` is only ever logged as its shape; see `filter_shape`.
+    pub async fn time<F: Future>(
+        &self,
+        collection: &'static str,
+        operation: &'static str,
+        filter: &Document,
+        op: F,
+    ) -> F::Output {
+        count_db_op();
+        let start = Instant::now();
+        let output = op.await;
+        let elapsed = start.elapsed();
+        metrics::histogram!("mongodb_operation_duration_seconds", "collection" => collection, "operation" => operation)
+            .record(elapsed.as_secs_f64());
+        if !self.threshold.is_zero() && elapsed >= self.threshold {
+            metrics::counter!("mongodb_slow_operations_total", "collection" => collection, "operation" => operation)
+                .increment(1);
+            tracing::warn!(
+                collection,
+                operation,
+                elapsed_ms = elapsed.as_millis() as u64,
+                filter = %filter_shape(filter),
+                request_id = current_request_id().as_deref().unwrap_or("-"),
+                "Slow MongoDB operation",
+            );
+        }
+        output
+    }
+}
+
+/// `filter` with every value replaced by `?`, e.g.
+/// `{workspace_id: ?, status: {$in: [?, ?]}}`. Only key names and operators
+/// survive, so a filter on a password hash or token never reaches the log.
+pub fn filter_shape(filter: &Document) -> String {
+    fn shape(value: &Bson, out: &mut String) {
+        match value {
+            Bson::Document(doc) => {
+                out.push('{');
+                for (i, (key, value)) in doc.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    out.push_str(key);
+                    out.push_str(": ");
+                    shape(value, out);
+                }
+                out.push('}');
+            }
+            Bson::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    shape(item, out);
+                }
+                out.push(']');
+            }
+            _ => out.push('?'),
+        }
+    }
+    let mut out = String::new();
+    shape(&Bson::Document(filter.clone()), &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+
+    #[test]
+    fn filter_shape_keeps_keys_and_drops_values() {
+        let filter = doc! {
+            "workspace_id": "ws-secret",
+            "password_hash": "$argon2id$v=19$abc",
+            "status": { "$in": ["todo", "done"] },
+            "$or": [{ "email": "a@example.com" }, { "notes.3": { "$exists": false } }],
+        };
+        let shape = filter_shape(&filter);
+        assert_eq!(
+            shape,
+            "{workspace_id: ?, password_hash: ?, status: {$in: [?, ?]}, $or: [{email: ?}, {notes.3: {$exists: ?}}]}"
+        );
+        for secret in ["ws-secret", "argon2", "todo", "example.com", "false"] {
+            assert!(!shape.contains(secret), "{secret} leaked into {shape}");
+        }
+        assert_eq!(filter_shape(&doc! {}), "{}");
+    }
+
+    #[test]
+    fn only_operations_over_the_threshold_count_as_slow() {
+        let recorder = metrics_exporter_prometheus::PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+            rt.block_on(async {
+                let pause = || tokio::time::sleep(Duration::from_millis(2));
+                QueryTimer::new(Duration::from_secs(60)).time("tasks", "find_one", &doc! {}, pause()).await;
+                QueryTimer::new(Duration::from_millis(1)).time("tasks", "count_documents", &doc! {}, pause()).await;
+                QueryTimer::new(Duration::ZERO).time("tasks", "find", &doc! {}, pause()).await;
+            });
+        });
+        let text = handle.render();
+        assert!(text.contains(r#"mongodb_slow_operations_total{collection="tasks",operation="count_documents"} 1"#), "{text}");
+        assert!(!text.contains(r#"mongodb_slow_operations_total{collection="tasks",operation="find"#), "{text}");
+        // Every operation is timed, slow or not.
+        assert!(text.contains(r#"mongodb_operation_duration_seconds_count{collection="tasks",operation="find_one"} 1"#), "{text}");
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+use mongodb::{
+    error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+    Client, ClientSession,
+};
+
+use crate::errors::{AppError, AppResult};
+
+/// What a `with_txn` body returns; it borrows the session for its lifetime.
+pub type TxnFuture<'s, T> = Pin<Box<dyn Future<Output = AppResult<T>> + Send + 's>>;
+
+/// Same cut-off as the driver's own `with_transaction`.
+const RETRY_WINDOW: Duration = Duration::from_secs(120);
+
+/// Runs multi-collection writes atomically. Deployments on a standalone
+/// `mongod` (no replica set) cannot use transactions; with
+/// `MONGO_TRANSACTIONS=false` the same bodies run once, in order, on a plain
+/// session, so a failure part-way leaves the earlier writes applied.
+#[derive(Clone)]
+pub struct Transactions {
+    client: Client,
+    enabled: bool,
+}
+
+impl Transactions {
+    pub fn new(client: Client, enabled: bool) -> Self {
+        Self { client, enabled }
+    }
+
+    /// Runs `body` in a transaction and commits it. The body may run more
+    /// than once: it is retried on `TransientTransactionError`, and the commit
+    /// on `UnknownTransactionCommitResult`, for up to two minutes. Any other
+    /// error aborts the transaction and is returned, so bodies must pass
+    /// database errors up rather than swallow them.
+    ///
+    /// ```ignore
+    /// txn.with_txn(|session| Box::pin(async move {
+    ///     users.delete_one_with_session(filter, None, session).await?;
+    ///     tasks.update_many_with_session(query, update, None, session).await?;
+    ///     Ok(())
+    /// })).await?;
+    /// ```
+    pub async fn with_txn<T, F>(&self, mut body: F) -> AppResult<T>
+    where
+        F: for<'s> FnMut(&'s mut ClientSession) -> TxnFuture<'s, T>,
+    {
+        let mut session = self.client.start_session(None).await?;
+        if !self.enabled {
+            return body(&mut session).await;
+        }
+
+        let started = Instant::now();
+        let within_window = || started.elapsed() < RETRY_WINDOW;
+        'transaction: loop {
+            session.start_transaction(None).await?;
+            let value = match body(&mut session).await {
+                Ok(value) => value,
+                Err(e) => {
+                    // Errors from abort are ignored by the driver; a no-op
+                    // when the server already aborted.
+                    session.abort_transaction().await.ok();
+                    if has_label(&e, TRANSIENT_TRANSACTION_ERROR) && within_window() {
+                        tracing::debug!("Retrying transaction after transient error: {e}");
+                        continue 'transaction;
+                    }
+                    return Err(e);
+                }
+            };
+            loop {
+                match session.commit_transaction().await {
+                    Ok(()) => return Ok(value),
+                    Err(e) if e.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) && within_window() => {
+                        tracing::debug!("Retrying commit with unknown result: {e}");
+                    }
+                    Err(e) if e.contains_label(TRANSIENT_TRANSACTION_ERROR) && within_window() => {
+                        tracing::debug!("Retrying transaction after transient commit error: {e}");
+                        continue 'transaction;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+fn has_label(e: &AppError, label: &str) -> bool {
+    matches!(e, AppError::Database(e) | AppError::DatabaseUnavailable(e) if e.contains_label(label))
+}
+
@@ -0,0 +1,104 @@
+use axum::async_trait;
+use bson::{doc, Document};
+use futures_util::{stream::BoxStream, StreamExt, TryStreamExt};
+use mongodb::{
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+    Collection,
+};
+use tracing::instrument;
+
+use crate::{
+    db::{collect, Db, QueryTimer, USERS},
+    errors::{mongo::is_duplicate_key, AppError, AppResult},
+    models::user::{User, UserSummary},
+    pagination::{paginate, PageParams, Paginated},
+};
+
+#[async_trait]
+pub trait UserRepo: Send + Sync {
+    /// One page of users matching `filter`, by username.
+    async fn find_page(&self, filter: Document, page: PageParams) -> AppResult<Paginated<User>>;
+    /// Every user matching `filter`, by username.
+    async fn find_all(&self, filter: Document) -> AppResult<Vec<User>>;
+    /// Every user matching `filter`, by username, read from the cursor as
+    /// the stream is polled.
+    async fn find_stream(&self, filter: Document) -> AppResult<BoxStream<'static, AppResult<User>>>;
+    /// Ids and usernames only, by username.
+    async fn find_summaries(&self, filter: Document) -> AppResult<Vec<UserSummary>>;
+    async fn find_by_id(&self, id: &str) -> AppResult<Option<User>>;
+    /// `$set`s `fields` and returns the updated user. Taking another
+    /// account's email or username fails with `DuplicateUser`.
+    async fn update_fields(&self, id: &str, fields: Document) -> AppResult<Option<User>>;
+}
+
+pub struct MongoUserRepo {
+    collection: Collection<User>,
+    timer: QueryTimer,
+}
+
+impl MongoUserRepo {
+    pub fn new(db: &Db, timer: QueryTimer) -> Self {
+        Self { collection: db.collection(USERS), timer }
+    }
+}
+
+fn by_username() -> FindOptions {
+    FindOptions::builder().sort(doc! { "username": 1 }).build()
+}
+
+#[async_trait]
+impl UserRepo for MongoUserRepo {
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = USERS))]
+    async fn find_page(&self, filter: Document, page: PageParams) -> AppResult<Paginated<User>> {
+        self.timer.time(USERS, "find_page", &filter, paginate(&self.collection, filter.clone(), by_username(), page)).await
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = USERS))]
+    async fn find_all(&self, filter: Document) -> AppResult<Vec<User>> {
+        let cursor = self.timer.time(USERS, "find", &filter, self.collection.find(filter.clone(), by_username())).await?;
+        Ok(collect(cursor).await?)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = USERS))]
+    async fn find_stream(&self, filter: Document) -> AppResult<BoxStream<'static, AppResult<User>>> {
+        let cursor = self.timer.time(USERS, "find", &filter, self.collection.find(filter.clone(), by_username())).await?;
+        Ok(cursor.map_err(AppError::from).boxed())
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = USERS))]
+    async fn find_summaries(&self, filter: Document) -> AppResult<Vec<UserSummary>> {
+        let mut options = by_username();
+        options.projection = Some(doc! { "_id": 1, "username": 1 });
+        let summaries = self.collection.clone_with_type::<UserSummary>();
+        let cursor = self.timer.time(USERS, "find", &filter, summaries.find(filter.clone(), options)).await?;
+        Ok(collect(cursor).await?)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = USERS))]
+    async fn find_by_id(&self, id: &str) -> AppResult<Option<User>> {
+        let filter = doc! { "_id": id };
+        self.timer
+            .time(USERS, "find_one", &filter, self.collection.find_one(filter.clone(), None))
+            .await
+            .map_err(AppError::from)
+    }
+
+    #[instrument(skip_all, fields(otel.kind = "client", db.system = "mongodb", db.collection.name = USERS))]
+    async fn update_fields(&self, id: &str, fields: Document) -> AppResult<Option<User>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let filter = doc! { "_id": id };
+        let update = self.collection.find_one_and_update(filter.clone(), doc! { "$set": fields }, options);
+        self.timer
+            .time(USERS, "find_one_and_update", &filter, update)
+            .await
+            .map_err(|e| {
+                if is_duplicate_key(&e) {
+                    AppError::DuplicateUser
+                } else {
+                    e.into()
+                }
+            })
+    }
+}
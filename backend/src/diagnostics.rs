@@ -0,0 +1,116 @@
+//! The self-check run at startup, by `--check` and by
+//! `GET /api/admin/diagnostics`: can we reach MongoDB, write to it and
+//! create indexes there, do our clocks agree, is Keycloak's signing key
+//! strong enough, and are the indexes from `db::indexes` in place. Each
+//! check has `CHECK_TIMEOUT`, so a hung dependency fails its check rather
+//! than the run.
+
+use std::{fmt::Display, future::Future, time::Instant};
+
+use bson::{doc, Document};
+use chrono::Utc;
+use mongodb::{options::IndexOptions, IndexModel};
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+
+use crate::{
+    config::AppConfig,
+    db::{indexes::missing_indexes, Db, DIAGNOSTICS},
+    keycloak,
+    models::{
+        dates,
+        diagnostics::{Check, CheckStatus, DiagnosticReport},
+    },
+};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Documents left in `diagnostics` by a check cut short expire after this.
+const LEFTOVER_TTL: Duration = Duration::from_secs(3600);
+
+/// Runs every check at once.
+pub async fn run(db: &Db, config: &AppConfig) -> DiagnosticReport {
+    let checked_at = dates::now();
+    let (ping, write, index, clock, key, indexes) = tokio::join!(
+        ping(db),
+        write(db),
+        create_index(db),
+        clock_skew(db),
+        signing_key(config),
+        indexes(db, config),
+    );
+    DiagnosticReport::new(checked_at, vec![ping, write, index, clock, key, indexes])
+}
+
+/// One event per check, at a level to match its status, then the verdict.
+pub fn log(report: &DiagnosticReport) {
+    for c in &report.checks {
+        match c.status {
+            CheckStatus::Ok => tracing::info!(check = %c.name, detail = %c.detail, "Self-check passed"),
+            CheckStatus::Warn => tracing::warn!(check = %c.name, detail = %c.detail, "Self-check warning"),
+            CheckStatus::Fail => tracing::error!(check = %c.name, detail = %c.detail, "Self-check failed"),
+        }
+    }
+    tracing::info!(status = ?report.status, "Self-check finished");
+}
+
+async fn within<T, E: Display>(fut: impl Future<Output = Result<T, E>>) -> Result<T, String> {
+    match timeout(CHECK_TIMEOUT, fut).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("timed out after {CHECK_TIMEOUT:?}")),
+    }
+}
+
+async fn ping(db: &Db) -> Check {
+    let started = Instant::now();
+    let result = within(db.run_command(doc! { "ping": 1 }, None)).await;
+    Check::from_result("mongodb_ping", result.map(|_| format!("answered in {}ms", started.elapsed().as_millis())))
+}
+
+/// Writes and deletes a throwaway document.
+async fn write(db: &Db) -> Check {
+    let collection = db.collection::<Document>(DIAGNOSTICS);
+    let id = Uuid::new_v4().to_string();
+    let result = within(async {
+        collection.insert_one(doc! { "_id": &id, "checked_at": bson::DateTime::now() }, None).await?;
+        collection.delete_one(doc! { "_id": &id }, None).await?;
+        Ok::<_, mongodb::error::Error>(format!("inserted and deleted a document in {DIAGNOSTICS}"))
+    })
+    .await;
+    Check::from_result("mongodb_write", result)
+}
+
+/// Creating an index that exists is a no-op, so this needs the privilege
+/// but changes nothing after the first run.
+async fn create_index(db: &Db) -> Check {
+    let options = IndexOptions::builder().expire_after(LEFTOVER_TTL).build();
+    let model = IndexModel::builder().keys(doc! { "checked_at": 1 }).options(options).build();
+    let result = within(db.collection::<Document>(DIAGNOSTICS).create_index(model, None)).await;
+    Check::from_result("mongodb_create_index", result.map(|_| format!("created an index on {DIAGNOSTICS}")))
+}
+
+async fn clock_skew(db: &Db) -> Check {
+    let sent = Utc::now();
+    let reply = match within(db.run_command(doc! { "hello": 1 }, None)).await {
+        Ok(reply) => reply,
+        Err(e) => return Check::new("clock_skew", CheckStatus::Fail, e),
+    };
+    let midway = sent + (Utc::now() - sent) / 2;
+    match reply.get_datetime("localTime") {
+        Ok(server) => Check::clock_skew(midway, server.to_chrono()),
+        Err(_) => Check::new("clock_skew", CheckStatus::Warn, "MongoDB did not report its time"),
+    }
+}
+
+async fn signing_key(config: &AppConfig) -> Check {
+    match within(keycloak::signing_key_bits(config)).await {
+        Ok(bits) => Check::signing_key(bits),
+        Err(e) => Check::new("jwt_signing_key", CheckStatus::Fail, e),
+    }
+}
+
+async fn indexes(db: &Db, config: &AppConfig) -> Check {
+    match within(missing_indexes(db, config)).await {
+        Ok(missing) => Check::indexes(&missing),
+        Err(e) => Check::new("indexes", CheckStatus::Fail, e),
+    }
+}
@@ -0,0 +1,166 @@
+//! Sends the daily digest (`models::digest`) to every user who turned it on,
+//! at their preferred hour in their timezone, through email when they take
+//! notification emails and as an in-app notification otherwise.
+
+use bson::doc;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use mongodb::options::FindOptions;
+use tokio::time::{interval, Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    clock::{SystemClock, UuidIds},
+    db::{collect, Db, NOTIFICATIONS, TASKS, USERS},
+    errors::AppResult,
+    models::{
+        dates,
+        digest::{digest_due, Digest, DigestTask},
+        notification::{Notification, DIGEST},
+        task::Task,
+        user::User,
+    },
+    notifier::{templates, Email, Notifier},
+    workflow_cache,
+};
+
+/// How often users are checked for a digest that is due; a digest goes out
+/// at most this long after the user's hour.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Checks every `CHECK_INTERVAL` until `shutdown` is cancelled.
+pub async fn run_digests(db: Db, notifier: Notifier, frontend_origin: String, shutdown: CancellationToken) {
+    let mut ticker = interval(CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => {
+                tracing::info!("Digest scheduler stopped");
+                return;
+            }
+        }
+        if let Err(e) = send_due_digests(&db, &notifier, &frontend_origin, dates::now()).await {
+            tracing::error!("Digest run failed: {e:?}");
+        }
+    }
+}
+
+/// Sends each digest that is due at `now`. One user's failure does not stop
+/// the rest.
+pub async fn send_due_digests(
+    db: &Db,
+    notifier: &Notifier,
+    frontend_origin: &str,
+    now: DateTime<Utc>,
+) -> AppResult<()> {
+    let open = workflow_cache::load(db).await?.open_keys();
+    let cursor = db
+        .collection::<User>(USERS)
+        .find(doc! { "preferences.digest": "daily", "active": { "$ne": false } }, None)
+        .await?;
+    for user in collect(cursor).await? {
+        let Some(date) = digest_due(&user.preferences, user.digest_sent_on, now) else {
+            continue;
+        };
+        if let Err(e) = send_digest(db, notifier, frontend_origin, &user, &open, date, now).await {
+            tracing::warn!(user_id = %user.id, "Could not send digest: {e:?}");
+        }
+    }
+    Ok(())
+}
+
+async fn send_digest(
+    db: &Db,
+    notifier: &Notifier,
+    frontend_origin: &str,
+    user: &User,
+    open: &[String],
+    date: NaiveDate,
+    now: DateTime<Utc>,
+) -> AppResult<()> {
+    // Claim the day before sending, so neither a restart nor another
+    // instance sends it again. A failure after this skips the day.
+    let claimed = db
+        .collection::<User>(USERS)
+        .update_one(
+            doc! { "_id": &user.id, "digest_sent_on": { "$ne": date.to_string() } },
+            doc! { "$set": { "digest_sent_on": date.to_string() } },
+            None,
+        )
+        .await?;
+    if claimed.modified_count == 0 {
+        return Ok(());
+    }
+    let tz = user.preferences.tz();
+    let digest = assemble(db, &user.id, open, tz, now).await?;
+    let Some(first) = digest.overdue.first().or(digest.due_today.first()) else {
+        return Ok(());
+    };
+    if user.preferences.notifications.email {
+        let listing = listing(&digest, tz, frontend_origin);
+        let (subject, body) = templates::digest(&date.to_string(), &digest.summary(), &listing);
+        notifier.enqueue(Email { to: user.email.clone(), subject, body });
+    } else {
+        let message = format!("Your tasks today: {}", digest.summary());
+        let (task_id, user_id) = (first.task_id.clone(), user.id.clone());
+        let notification =
+            Notification::new(&SystemClock, &UuidIds, user_id.clone(), DIGEST, task_id, user_id, message);
+        db.collection::<Notification>(NOTIFICATIONS).insert_one(notification, None).await?;
+    }
+    Ok(())
+}
+
+/// `user_id`'s digest as of `now`, for the local day it is in `tz`.
+pub async fn assemble(db: &Db, user_id: &str, open: &[String], tz: Tz, now: DateTime<Utc>) -> AppResult<Digest> {
+    let date = now.with_timezone(&tz).date_naive();
+    let options = FindOptions::builder().sort(doc! { "due_at": 1 }).build();
+    let cursor = db
+        .collection::<Task>(TASKS)
+        .find(Digest::filter(user_id, open.to_vec(), tz, date), options)
+        .await?;
+    Ok(Digest::new(collect(cursor).await?, tz, date, now))
+}
+
+/// The digest as email text: each task with its local due time and a link.
+fn listing(digest: &Digest, tz: Tz, frontend_origin: &str) -> String {
+    let origin = frontend_origin.trim_end_matches('/');
+    let section = |heading: &str, tasks: &[DigestTask]| {
+        if tasks.is_empty() {
+            return String::new();
+        }
+        let mut text = format!("{heading}:\n");
+        for t in tasks {
+            let due = t.due_at.with_timezone(&tz).format("%Y-%m-%d %H:%M");
+            text.push_str(&format!("    {} (due {due})\n    {origin}/tasks/{}\n", t.title, t.task_id));
+        }
+        text + "\n"
+    };
+    section("Overdue", &digest.overdue) + &section("Due today", &digest.due_today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_shows_local_due_times_and_links() {
+        let task = |title: &str, due: &str| DigestTask {
+            task_id: format!("t-{title}"),
+            workspace_id: "default".into(),
+            title: title.into(),
+            status: "todo".into(),
+            due_at: due.parse().unwrap(),
+        };
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let digest = Digest {
+            date: "2026-06-01".parse().unwrap(),
+            timezone: tz.name().into(),
+            overdue: vec![task("renew", "2026-05-31T08:00:00Z")],
+            due_today: vec![],
+        };
+        assert_eq!(
+            listing(&digest, tz, "https://mc.example.com/"),
+            "Overdue:\n    renew (due 2026-05-31 10:00)\n    https://mc.example.com/tasks/t-renew\n\n"
+        );
+    }
+}
@@ -1,54 +1,463 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 
+use crate::middleware::request_id::current_request_id;
+
+pub mod mongo;
+
+/// Why a request was rejected with 401, reported to clients as `code`:
+///
+/// - `token_missing` — no credentials were sent; prompt for sign-in.
+/// - `token_expired` — the token was valid but has expired; refresh silently.
+/// - `token_invalid` — malformed, wrongly signed, or unknown credentials; force logout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthErrorKind {
+    Missing,
+    Expired,
+    Invalid,
+}
+
+impl AuthErrorKind {
+    pub fn code(self) -> &'static str {
+        match self {
+            AuthErrorKind::Missing => "token_missing",
+            AuthErrorKind::Expired => "token_expired",
+            AuthErrorKind::Invalid => "token_invalid",
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            AuthErrorKind::Missing => "Authentication required",
+            AuthErrorKind::Expired => "Session expired",
+            AuthErrorKind::Invalid => "Invalid token",
+        }
+    }
+}
+
+/// One invalid input field, so the client can highlight it. `code` is stable
+/// (e.g. `required`, `out_of_range`, `invalid_status`); `message` is for humans.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: &'static str, message: impl Into<String>) -> Self {
+        Self { field: field.into(), code, message: message.into() }
+    }
+}
+
+/// Every `code` an error response can carry. Clients should branch on these,
+/// never on the English `error` text.
+///
+/// | code | status | meaning |
+/// |------|--------|---------|
+/// | `not_found` | 404 | No such resource |
+/// | `route_not_found` | 404 | No route matches the path |
+/// | `method_not_allowed` | 405 | The route exists but not for this method; see `allowed` |
+/// | `token_missing` / `token_expired` / `token_invalid` | 401 | See `AuthErrorKind` |
+/// | `forbidden` | 403 | Authenticated but not allowed |
+/// | `missing_scope` | 403 | The API key or integration token lacks the route's scope; see `scope` |
+/// | `email_not_verified` | 403 | Keycloak email not verified |
+/// | `account_inactive` | 403 | Account deactivated by an admin |
+/// | `bad_request` | 400 | Malformed request not tied to a field, e.g. invalid JSON |
+/// | `malformed_id` | 400 | An id in the path is not a UUID, so cannot name anything |
+/// | `page_out_of_range` | 400 | `page` is past the last page (only with `STRICT_PAGINATION`); see `total_pages` |
+/// | `unsupported_media_type` | 415 | A request body that isn't `application/json` |
+/// | `validation_failed` | 422 | See `fields[].code` |
+/// | `conflict_duplicate_user` | 409 | Email or username already taken |
+/// | `duplicate_key` | 409 | Clashes with an existing record; see `index` and `field` |
+/// | `last_admin` | 409 | Would leave no active admin |
+/// | `already_assigned` | 409 | Claiming a task someone else holds; see `assignee_id` |
+/// | `status_in_use` | 409 | Tasks are still in the workflow status; see `tasks`, or pass `migrate_to` |
+/// | `payload_too_large` | 413 | Request body exceeds the size limit |
+/// | `quota_exceeded` | 429 | A `TASK_QUOTA_*` limit was reached; see `quota`, `current` and `limit` |
+/// | `service_unavailable` | 503 | An upstream service is unreachable |
+/// | `bad_gateway` | 502 | An upstream service answered badly |
+/// | `gateway_timeout` | 504 | The request took longer than the server allows |
+/// | `internal_error` | 500 | Unexpected server failure |
+/// | `database_unavailable` | 503 | MongoDB is briefly unreachable; retry after `Retry-After` |
+/// | `database_error` | 500 | MongoDB failure |
+pub const ERROR_CODES: &[&str] = &[
+    "not_found",
+    "route_not_found",
+    "method_not_allowed",
+    "token_missing",
+    "token_expired",
+    "token_invalid",
+    "forbidden",
+    "missing_scope",
+    "email_not_verified",
+    "account_inactive",
+    "bad_request",
+    "malformed_id",
+    "page_out_of_range",
+    "unsupported_media_type",
+    "validation_failed",
+    "conflict_duplicate_user",
+    "duplicate_key",
+    "last_admin",
+    "already_assigned",
+    "status_in_use",
+    "payload_too_large",
+    "quota_exceeded",
+    "service_unavailable",
+    "bad_gateway",
+    "gateway_timeout",
+    "internal_error",
+    "database_unavailable",
+    "database_error",
+];
+
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("Not found")]
     NotFound,
+    /// No route matches the request path (the router's fallback).
+    #[error("not found")]
+    RouteNotFound,
+    /// The path matched but the method did not; carries the methods it does allow.
+    #[error("method not allowed")]
+    MethodNotAllowed(Vec<String>),
     #[error("Unauthorized")]
-    Unauthorized,
+    Unauthorized(AuthErrorKind),
     #[error("Forbidden")]
     Forbidden,
-    #[error("Bad request: {0}")]
+    /// A scoped credential (API key or integration token) lacks the scope
+    /// the route needs.
+    #[error("Missing scope {0}")]
+    MissingScope(&'static str),
+    #[error("Email address has not been verified")]
+    EmailNotVerified,
+    #[error("Account has been deactivated")]
+    AccountInactive,
+    #[error("{0}")]
     BadRequest(String),
-    #[error("Conflict: {0}")]
-    Conflict(String),
-    #[error("Service unavailable: {0}")]
+    /// A path id that is not a UUID; see `models::id::Id`.
+    #[error("{0}")]
+    MalformedId(String),
+    #[error("Content-Type must be application/json")]
+    UnsupportedMediaType,
+    /// Input failed validation; rendered as 422 with per-field details.
+    #[error("Validation failed")]
+    Validation(Vec<FieldError>),
+    #[error("Email or username already taken")]
+    DuplicateUser,
+    /// A write hit a unique index; see `mongo::translate`.
+    #[error("Conflicts with an existing record")]
+    Conflict { index: Option<String>, field: Option<String> },
+    #[error("cannot remove the last admin")]
+    LastAdmin,
+    /// Claiming a task someone else holds; carries who, or `None` when they
+    /// had already released it again by the time we looked.
+    #[error("Task is already assigned")]
+    AlreadyAssigned { assignee_id: Option<String> },
+    /// `page` is past the last of `total_pages` non-empty pages.
+    #[error("Page {page} is past the last page ({total_pages})")]
+    PageOutOfRange { page: u64, total_pages: u64 },
+    /// Deleting a workflow status that `tasks` tasks are still in.
+    #[error("Status is still in use")]
+    StatusInUse { tasks: u64 },
+    #[error("Request body too large")]
+    PayloadTooLarge,
+    /// `current` has reached `limit` for the quota named `quota`.
+    #[error("Quota exceeded: {quota}")]
+    QuotaExceeded { quota: &'static str, current: u64, limit: u64 },
+    #[error("{0}")]
     ServiceUnavailable(String),
-    #[error("Bad gateway: {0}")]
+    #[error("{0}")]
     BadGateway(String),
+    #[error("Request timed out")]
+    GatewayTimeout,
     #[error("Internal server error")]
     Internal(#[from] anyhow::Error),
+    /// A transient MongoDB failure, such as a dropped connection or an election.
+    #[error("Database temporarily unavailable")]
+    DatabaseUnavailable(mongodb::error::Error),
     #[error("Database error")]
-    Database(#[from] mongodb::error::Error),
+    Database(mongodb::error::Error),
+}
+
+impl AppError {
+    /// Stable machine-readable code; one of `ERROR_CODES`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound => "not_found",
+            AppError::RouteNotFound => "route_not_found",
+            AppError::MethodNotAllowed(_) => "method_not_allowed",
+            AppError::Unauthorized(kind) => kind.code(),
+            AppError::Forbidden => "forbidden",
+            AppError::MissingScope(_) => "missing_scope",
+            AppError::EmailNotVerified => "email_not_verified",
+            AppError::AccountInactive => "account_inactive",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::MalformedId(_) => "malformed_id",
+            AppError::PageOutOfRange { .. } => "page_out_of_range",
+            AppError::UnsupportedMediaType => "unsupported_media_type",
+            AppError::Validation(_) => "validation_failed",
+            AppError::DuplicateUser => "conflict_duplicate_user",
+            AppError::Conflict { .. } => "duplicate_key",
+            AppError::LastAdmin => "last_admin",
+            AppError::AlreadyAssigned { .. } => "already_assigned",
+            AppError::StatusInUse { .. } => "status_in_use",
+            AppError::PayloadTooLarge => "payload_too_large",
+            AppError::QuotaExceeded { .. } => "quota_exceeded",
+            AppError::ServiceUnavailable(_) => "service_unavailable",
+            AppError::BadGateway(_) => "bad_gateway",
+            AppError::GatewayTimeout => "gateway_timeout",
+            AppError::Internal(_) => "internal_error",
+            AppError::DatabaseUnavailable(_) => "database_unavailable",
+            AppError::Database(_) => "database_error",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound | AppError::RouteNotFound => StatusCode::NOT_FOUND,
+            AppError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden
+            | AppError::MissingScope(_)
+            | AppError::EmailNotVerified
+            | AppError::AccountInactive => StatusCode::FORBIDDEN,
+            AppError::BadRequest(_) | AppError::MalformedId(_) | AppError::PageOutOfRange { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+            AppError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::DuplicateUser
+            | AppError::Conflict { .. }
+            | AppError::LastAdmin
+            | AppError::AlreadyAssigned { .. }
+            | AppError::StatusInUse { .. } => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::ServiceUnavailable(_) | AppError::DatabaseUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            AppError::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Internal(_) | AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match &self {
-            AppError::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
-            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
-            AppError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
-            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
-            AppError::BadGateway(msg) => (StatusCode::BAD_GATEWAY, msg.clone()),
+        let status = self.status();
+        let code = self.code();
+        debug_assert!(ERROR_CODES.contains(&code), "undocumented error code {code}");
+        let mut body = match &self {
+            AppError::Unauthorized(kind) => json!({ "error": kind.message(), "code": code }),
+            AppError::Validation(fields) => {
+                json!({ "error": "validation_failed", "code": code, "fields": fields })
+            }
+            AppError::MethodNotAllowed(allowed) => {
+                json!({ "error": self.to_string(), "code": code, "allowed": allowed })
+            }
             AppError::Internal(e) => {
-                tracing::error!("Internal error: {e:?}");
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".into())
+                tracing::error!(status = status.as_u16(), code, "Internal error: {e:?}");
+                json!({ "error": self.to_string(), "code": code })
+            }
+            AppError::Conflict { index, field } => {
+                json!({ "error": self.to_string(), "code": code, "index": index, "field": field })
+            }
+            AppError::MissingScope(scope) => {
+                json!({ "error": self.to_string(), "code": code, "scope": scope })
+            }
+            AppError::AlreadyAssigned { assignee_id } => {
+                json!({ "error": self.to_string(), "code": code, "assignee_id": assignee_id })
+            }
+            AppError::StatusInUse { tasks } => {
+                json!({ "error": self.to_string(), "code": code, "tasks": tasks })
+            }
+            AppError::PageOutOfRange { total_pages, .. } => {
+                json!({ "error": self.to_string(), "code": code, "total_pages": total_pages })
+            }
+            AppError::QuotaExceeded { quota, current, limit } => {
+                json!({ "error": self.to_string(), "code": code, "quota": quota, "current": current, "limit": limit })
+            }
+            AppError::DatabaseUnavailable(e) => {
+                tracing::warn!(status = status.as_u16(), code, "Transient database error: {e:?}");
+                json!({ "error": self.to_string(), "code": code })
             }
             AppError::Database(e) => {
-                tracing::error!("Database error: {e:?}");
-                (StatusCode::INTERNAL_SERVER_ERROR, "Database error".into())
+                tracing::error!(status = status.as_u16(), code, "Database error: {e:?}");
+                json!({ "error": self.to_string(), "code": code })
             }
+            _ => json!({ "error": self.to_string(), "code": code }),
         };
-        (status, Json(json!({ "error": message }))).into_response()
+        // Server errors are the ones users report; give them something to quote.
+        if status.is_server_error() {
+            if let Some(id) = current_request_id() {
+                body["request_id"] = id.into();
+            }
+        }
+        let mut response = (status, Json(body)).into_response();
+        if let AppError::MethodNotAllowed(allowed) = &self {
+            if let Ok(value) = HeaderValue::from_str(&allowed.join(",")) {
+                response.headers_mut().insert(header::ALLOW, value);
+            }
+        }
+        if let AppError::DatabaseUnavailable(_) = &self {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(mongo::RETRY_AFTER_SECS));
+        }
+        response
+    }
+}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(e: mongodb::error::Error) -> Self {
+        mongo::translate(e)
+    }
+}
+
+impl From<FieldError> for AppError {
+    fn from(e: FieldError) -> Self {
+        AppError::Validation(vec![e])
     }
 }
 
 pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_json(err: AppError) -> (StatusCode, serde_json::Value) {
+        let resp = err.into_response();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn email_not_verified_has_distinct_code() {
+        let (status, body) = body_json(AppError::EmailNotVerified).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "email_not_verified");
+    }
+
+    #[tokio::test]
+    async fn unauthorized_reports_kind_code() {
+        for (kind, code) in [
+            (AuthErrorKind::Missing, "token_missing"),
+            (AuthErrorKind::Expired, "token_expired"),
+            (AuthErrorKind::Invalid, "token_invalid"),
+        ] {
+            let (status, body) = body_json(AppError::Unauthorized(kind)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["code"], code);
+        }
+    }
+
+    #[tokio::test]
+    async fn account_inactive_has_distinct_code() {
+        let (status, body) = body_json(AppError::AccountInactive).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "account_inactive");
+    }
+
+    #[tokio::test]
+    async fn missing_scope_names_the_scope() {
+        let (_, body) = body_json(AppError::MissingScope("admin:write")).await;
+        assert_eq!(body["scope"], "admin:write");
+    }
+
+    #[tokio::test]
+    async fn every_variant_has_its_documented_code() {
+        let cases = [
+            (AppError::NotFound, StatusCode::NOT_FOUND, "not_found"),
+            (AppError::RouteNotFound, StatusCode::NOT_FOUND, "route_not_found"),
+            (AppError::MethodNotAllowed(vec!["GET".into()]), StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed"),
+            (AppError::Forbidden, StatusCode::FORBIDDEN, "forbidden"),
+            (AppError::MissingScope("tasks:write"), StatusCode::FORBIDDEN, "missing_scope"),
+            (AppError::BadRequest("x".into()), StatusCode::BAD_REQUEST, "bad_request"),
+            (AppError::MalformedId("x".into()), StatusCode::BAD_REQUEST, "malformed_id"),
+            (AppError::UnsupportedMediaType, StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type"),
+            (AppError::Validation(vec![]), StatusCode::UNPROCESSABLE_ENTITY, "validation_failed"),
+            (AppError::DuplicateUser, StatusCode::CONFLICT, "conflict_duplicate_user"),
+            (AppError::Conflict { index: None, field: None }, StatusCode::CONFLICT, "duplicate_key"),
+            (AppError::LastAdmin, StatusCode::CONFLICT, "last_admin"),
+            (AppError::AlreadyAssigned { assignee_id: Some("u1".into()) }, StatusCode::CONFLICT, "already_assigned"),
+            (AppError::StatusInUse { tasks: 3 }, StatusCode::CONFLICT, "status_in_use"),
+            (AppError::PayloadTooLarge, StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large"),
+            (
+                AppError::QuotaExceeded { quota: "tasks", current: 5, limit: 5 },
+                StatusCode::TOO_MANY_REQUESTS,
+                "quota_exceeded",
+            ),
+            (AppError::ServiceUnavailable("x".into()), StatusCode::SERVICE_UNAVAILABLE, "service_unavailable"),
+            (AppError::BadGateway("x".into()), StatusCode::BAD_GATEWAY, "bad_gateway"),
+            (AppError::GatewayTimeout, StatusCode::GATEWAY_TIMEOUT, "gateway_timeout"),
+            (AppError::Internal(anyhow::anyhow!("boom")), StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+        ];
+        for (err, expected_status, expected_code) in cases {
+            assert!(ERROR_CODES.contains(&expected_code));
+            let (status, body) = body_json(err).await;
+            assert_eq!(status, expected_status);
+            assert_eq!(body["code"], expected_code);
+            assert!(body["error"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_are_passed_through_and_internals_hidden() {
+        let (_, body) = body_json(AppError::BadRequest("CA path not permitted".into())).await;
+        assert_eq!(body["error"], "CA path not permitted");
+        let (_, body) = body_json(AppError::Internal(anyhow::anyhow!("secret detail"))).await;
+        assert_eq!(body["error"], "Internal server error");
+        assert!(body.get("request_id").is_none(), "no request in scope");
+    }
+
+    #[tokio::test]
+    async fn validation_lists_each_field() {
+        let err = AppError::Validation(vec![
+            FieldError::new("limit", "out_of_range", "limit must be between 1 and 100"),
+            FieldError::new("status", "invalid_status", "invalid status 'x'"),
+        ]);
+        let (status, body) = body_json(err).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"], "validation_failed");
+        assert_eq!(
+            body["fields"],
+            json!([
+                { "field": "limit", "code": "out_of_range", "message": "limit must be between 1 and 100" },
+                { "field": "status", "code": "invalid_status", "message": "invalid status 'x'" },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn database_errors_are_classified() {
+        let transient: mongodb::error::Error = std::io::ErrorKind::ConnectionReset.into();
+        let resp = AppError::from(transient).into_response();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "5");
+
+        let (status, body) =
+            body_json(AppError::Conflict { index: Some("email_1".into()), field: Some("email".into()) }).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!((&body["index"], &body["field"]), (&json!("email_1"), &json!("email")));
+
+        let other = mongodb::error::Error::custom("boom");
+        let (status, body) = body_json(other.into()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["code"], "database_error");
+    }
+
+    #[tokio::test]
+    async fn single_field_error_converts_to_validation() {
+        let err: AppError = FieldError::new("role", "invalid_role", "bad role").into();
+        let (status, body) = body_json(err).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "role");
+    }
+}
@@ -0,0 +1,165 @@
+//! Turns driver errors into responses. A duplicate key is the client's
+//! conflict (409), a network blip or election is worth retrying (503 with
+//! `Retry-After`), and anything else is our fault (500).
+
+use mongodb::error::{Error, ErrorKind, WriteFailure, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
+
+use crate::errors::AppError;
+
+/// Seconds a client is told to wait after a transient database failure.
+pub const RETRY_AFTER_SECS: u64 = 5;
+
+/// DuplicateKey, plus the legacy codes some servers still report for it.
+const DUPLICATE_KEY_CODES: &[i32] = &[11000, 11001, 12582];
+
+/// Server codes for failures that go away on their own: the network, a
+/// stepped-down or shutting-down primary, a timeout or a write conflict.
+const TRANSIENT_CODES: &[i32] = &[
+    6,     // HostUnreachable
+    7,     // HostNotFound
+    50,    // MaxTimeMSExpired
+    89,    // NetworkTimeout
+    91,    // ShutdownInProgress
+    112,   // WriteConflict
+    189,   // PrimarySteppedDown
+    262,   // ExceededTimeLimit
+    9001,  // SocketException
+    10058, // LegacyNotPrimary
+    10107, // NotWritablePrimary
+    11600, // InterruptedAtShutdown
+    11602, // InterruptedDueToReplStateChange
+    13435, // NotPrimaryNoSecondaryOk
+    13436, // NotPrimaryOrSecondary
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbErrorClass {
+    /// A write hit a unique index; whichever of its name and first field the
+    /// server reported.
+    DuplicateKey { index: Option<String>, field: Option<String> },
+    Transient,
+    Other,
+}
+
+/// The server's code and message, for the error kinds that carry them.
+fn code_and_message(e: &Error) -> Option<(i32, &str)> {
+    match e.kind.as_ref() {
+        ErrorKind::Command(ce) => Some((ce.code, &ce.message)),
+        ErrorKind::Write(WriteFailure::WriteError(we)) => Some((we.code, &we.message)),
+        ErrorKind::Write(WriteFailure::WriteConcernError(wce)) => Some((wce.code, &wce.message)),
+        ErrorKind::BulkWrite(failure) => match (&failure.write_errors, &failure.write_concern_error) {
+            (Some(errors), _) if !errors.is_empty() => Some((errors[0].code, &errors[0].message)),
+            (_, Some(wce)) => Some((wce.code, &wce.message)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn classify(e: &Error) -> DbErrorClass {
+    if let Some((code, message)) = code_and_message(e) {
+        if DUPLICATE_KEY_CODES.contains(&code) {
+            let (index, field) = parse_duplicate_key(message);
+            return DbErrorClass::DuplicateKey { index, field };
+        }
+        if TRANSIENT_CODES.contains(&code) {
+            return DbErrorClass::Transient;
+        }
+    }
+    let transient = matches!(
+        e.kind.as_ref(),
+        ErrorKind::Io(_) | ErrorKind::ServerSelection { .. } | ErrorKind::ConnectionPoolCleared { .. }
+    ) || e.contains_label(RETRYABLE_WRITE_ERROR)
+        || e.contains_label(TRANSIENT_TRANSACTION_ERROR);
+    if transient { DbErrorClass::Transient } else { DbErrorClass::Other }
+}
+
+/// Whether a write failed on a unique index.
+pub fn is_duplicate_key(e: &Error) -> bool {
+    matches!(classify(e), DbErrorClass::DuplicateKey { .. })
+}
+
+/// The response for `e`. Handlers that give a duplicate key a more specific
+/// meaning (e.g. `AppError::DuplicateUser`) check `is_duplicate_key` first.
+pub fn translate(e: Error) -> AppError {
+    match classify(&e) {
+        DbErrorClass::DuplicateKey { index, field } => AppError::Conflict { index, field },
+        DbErrorClass::Transient => AppError::DatabaseUnavailable(e),
+        DbErrorClass::Other => AppError::Database(e),
+    }
+}
+
+/// Pulls the index name and first key out of a message such as
+/// `E11000 duplicate key error collection: mc.users index: email_1 dup key: { email: "a@b.c" }`.
+/// The duplicated value itself is left out: it may be personal data.
+fn parse_duplicate_key(message: &str) -> (Option<String>, Option<String>) {
+    let index = message
+        .split_once("index: ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .map(str::to_string);
+    let field = message
+        .split_once("dup key: {")
+        .and_then(|(_, rest)| rest.split_once(':'))
+        .map(|(key, _)| key.trim().trim_matches('"').to_string())
+        .filter(|key| !key.is_empty());
+    (index, field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+    use mongodb::error::{BulkWriteFailure, CommandError, WriteError};
+
+    const DUP: &str = r#"E11000 duplicate key error collection: mc.users index: email_1 dup key: { email: "a@b.c" }"#;
+
+    fn command(code: i32, message: &str) -> Error {
+        let ce: CommandError = bson::from_document(doc! { "code": code, "codeName": "X", "errmsg": message }).unwrap();
+        ErrorKind::Command(ce).into()
+    }
+
+    fn write(code: i32, message: &str) -> Error {
+        let we: WriteError = bson::from_document(doc! { "code": code, "errmsg": message }).unwrap();
+        ErrorKind::Write(WriteFailure::WriteError(we)).into()
+    }
+
+    #[test]
+    fn duplicate_keys_name_index_and_field() {
+        let expected = DbErrorClass::DuplicateKey { index: Some("email_1".into()), field: Some("email".into()) };
+        assert_eq!(classify(&write(11000, DUP)), expected);
+        assert_eq!(classify(&command(11000, DUP)), expected);
+        let bulk: BulkWriteFailure =
+            bson::from_document(doc! { "writeErrors": [{ "index": 0, "code": 11000, "errmsg": DUP }] }).unwrap();
+        assert_eq!(classify(&ErrorKind::BulkWrite(bulk).into()), expected);
+        assert!(is_duplicate_key(&write(11000, DUP)));
+    }
+
+    #[test]
+    fn compound_and_unparseable_duplicates_still_conflict() {
+        let compound = "E11000 duplicate key error collection: mc.x index: a_1_b_1 dup key: { a: 1, b: 2 }";
+        assert_eq!(
+            classify(&write(11000, compound)),
+            DbErrorClass::DuplicateKey { index: Some("a_1_b_1".into()), field: Some("a".into()) }
+        );
+        assert_eq!(classify(&write(11000, "E11000")), DbErrorClass::DuplicateKey { index: None, field: None });
+        assert!(matches!(translate(write(11000, DUP)), AppError::Conflict { .. }));
+    }
+
+    #[test]
+    fn network_and_elections_are_transient() {
+        assert_eq!(classify(&std::io::ErrorKind::ConnectionReset.into()), DbErrorClass::Transient);
+        assert_eq!(classify(&command(10107, "not primary")), DbErrorClass::Transient);
+        assert_eq!(classify(&command(112, "write conflict")), DbErrorClass::Transient);
+        assert_eq!(classify(&command(50, "operation exceeded time limit")), DbErrorClass::Transient);
+        assert_eq!(classify(&std::io::ErrorKind::TimedOut.into()), DbErrorClass::Transient);
+        assert!(matches!(translate(command(189, "stepped down")), AppError::DatabaseUnavailable(_)));
+    }
+
+    #[test]
+    fn everything_else_is_a_server_error() {
+        assert_eq!(classify(&command(2, "BadValue")), DbErrorClass::Other);
+        assert_eq!(classify(&write(121, "Document failed validation")), DbErrorClass::Other);
+        assert!(!is_duplicate_key(&command(2, DUP)));
+        assert!(matches!(translate(command(2, "BadValue")), AppError::Database(_)));
+    }
+}
@@ -0,0 +1,256 @@
+//! In-process domain events. Handlers `publish` what changed once a write
+//! has succeeded; a background worker hands each event to every registered
+//! `Subscriber` (webhooks, notifications, ...), so side effects stay out of
+//! the handlers and off the request path.
+//!
+//! Delivery is at least once within the process: a subscriber that fails is
+//! retried, and events still queued at shutdown are drained before the
+//! worker stops. Events are lost if the process dies, and dropped (and
+//! counted in `domain_events_dropped_total`) when the queue is full.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use axum::async_trait;
+use futures_util::future::join_all;
+use tokio::{sync::mpsc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    handlers::auth::Claims,
+    models::task::{Task, TaskNote},
+    retry::backoff,
+};
+
+/// Events waiting for the worker; beyond this, new ones are dropped rather
+/// than slowing down the request that raised them.
+const QUEUE_CAPACITY: usize = 1024;
+/// Tries per subscriber per event, including the first.
+const MAX_ATTEMPTS: u32 = 3;
+/// Retries back off from here: 200 ms, 400 ms, 800 ms, …
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Who made a change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Actor {
+    pub id: String,
+    pub username: String,
+}
+
+impl From<&Claims> for Actor {
+    fn from(claims: &Claims) -> Self {
+        Self { id: claims.sub.clone(), username: claims.username.clone() }
+    }
+}
+
+/// Something that happened to a task. Each carries the task as saved.
+#[derive(Debug, Clone)]
+pub enum DomainEvent {
+    /// Includes any assignee it was created with.
+    TaskCreated { task: Task, actor: Actor },
+    /// `changes` names the fields whose value changed, e.g. `"title"`.
+    TaskUpdated { task: Task, changes: Vec<&'static str>, actor: Actor },
+    /// The task moved into a terminal status.
+    TaskCompleted { task: Task, actor: Actor },
+    /// An existing task changed hands; `assignee` is `None` when it was
+    /// unassigned.
+    UserAssigned { task: Task, assignee: Option<String>, actor: Actor },
+    CtiChanged { task: Task, actor: Actor },
+    NoteAdded { task: Task, note: TaskNote, actor: Actor },
+    TaskDeleted { workspace_id: String, task_id: String, actor: Actor },
+}
+
+impl DomainEvent {
+    /// Label for logs and metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TaskCreated { .. } => "task_created",
+            Self::TaskUpdated { .. } => "task_updated",
+            Self::TaskCompleted { .. } => "task_completed",
+            Self::UserAssigned { .. } => "user_assigned",
+            Self::CtiChanged { .. } => "cti_changed",
+            Self::NoteAdded { .. } => "note_added",
+            Self::TaskDeleted { .. } => "task_deleted",
+        }
+    }
+}
+
+/// Reacts to events. `handle` may run more than once for one event, after
+/// an error or a retry that races a slow success, so it should tolerate
+/// repeats.
+#[async_trait]
+pub trait Subscriber: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn handle(&self, event: &DomainEvent) -> anyhow::Result<()>;
+}
+
+/// Cheap to clone; shared through `AppState`.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: mpsc::Sender<DomainEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub fn channel() -> (Self, mpsc::Receiver<DomainEvent>) {
+        Self::with_capacity(QUEUE_CAPACITY)
+    }
+
+    fn with_capacity(capacity: usize) -> (Self, mpsc::Receiver<DomainEvent>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Self { tx, dropped: Arc::new(AtomicU64::new(0)) }, rx)
+    }
+
+    /// Queues `event` without waiting. Call it only after the change is
+    /// saved; subscribers trust that it happened.
+    pub fn publish(&self, event: DomainEvent) {
+        let name = event.name();
+        match self.tx.try_send(event) {
+            Ok(()) => metrics::counter!("domain_events_published_total", "event" => name).increment(1),
+            Err(e) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                metrics::counter!("domain_events_dropped_total", "event" => name).increment(1);
+                tracing::warn!(event = name, "Dropped domain event: {e}");
+            }
+        }
+    }
+
+    /// Events dropped since startup because the queue was full or closed.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Gives `event` to `subscriber`, retrying failures up to `MAX_ATTEMPTS`.
+async fn deliver(subscriber: &dyn Subscriber, event: &DomainEvent) {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let Err(e) = subscriber.handle(event).await else {
+            return;
+        };
+        metrics::counter!("domain_event_handler_failures_total", "subscriber" => subscriber.name()).increment(1);
+        if attempts >= MAX_ATTEMPTS {
+            tracing::error!(subscriber = subscriber.name(), event = event.name(), attempts, "Giving up on event: {e:#}");
+            return;
+        }
+        tracing::warn!(subscriber = subscriber.name(), event = event.name(), attempts, "Event handler failed; retrying: {e:#}");
+        tokio::time::sleep(backoff(FIRST_RETRY_DELAY, attempts)).await;
+    }
+}
+
+/// Hands each queued event to every subscriber, one event at a time so
+/// subscribers see a task's events in order. Once `shutdown` is cancelled,
+/// the events already queued are still delivered before it returns.
+pub async fn run_subscribers(
+    mut events: mpsc::Receiver<DomainEvent>,
+    subscribers: Vec<Arc<dyn Subscriber>>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+            _ = shutdown.cancelled() => break,
+        };
+        join_all(subscribers.iter().map(|s| deliver(s.as_ref(), &event))).await;
+    }
+    events.close();
+    let mut drained = 0;
+    while let Some(event) = events.recv().await {
+        join_all(subscribers.iter().map(|s| deliver(s.as_ref(), &event))).await;
+        drained += 1;
+    }
+    tracing::info!(drained, "Event worker stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FakeClock, SequentialIds};
+    use std::sync::Mutex;
+
+    /// Records event names, failing the first `fail` calls.
+    struct Recorder {
+        seen: Mutex<Vec<&'static str>>,
+        fail: AtomicU64,
+    }
+
+    impl Recorder {
+        fn new(fail: u64) -> Arc<Self> {
+            Arc::new(Self { seen: Mutex::new(vec![]), fail: AtomicU64::new(fail) })
+        }
+    }
+
+    #[async_trait]
+    impl Subscriber for Recorder {
+        fn name(&self) -> &'static str {
+            "recorder"
+        }
+
+        async fn handle(&self, event: &DomainEvent) -> anyhow::Result<()> {
+            self.seen.lock().unwrap().push(event.name());
+            if self.fail.load(Ordering::SeqCst) > 0 {
+                self.fail.fetch_sub(1, Ordering::SeqCst);
+                anyhow::bail!("not yet");
+            }
+            Ok(())
+        }
+    }
+
+    fn created() -> DomainEvent {
+        let task = Task::new(&FakeClock::default(), &SequentialIds::default(), "Patch".into(), "".into());
+        DomainEvent::TaskCreated { task, actor: Actor { id: "u1".into(), username: "alice".into() } }
+    }
+
+    #[test]
+    fn a_full_queue_drops_and_counts() {
+        let (bus, _rx) = EventBus::with_capacity(1);
+        bus.publish(created());
+        assert_eq!(bus.dropped(), 0);
+        bus.publish(created());
+        assert_eq!(bus.dropped(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_subscribers_are_retried_until_they_succeed() {
+        let (bus, rx) = EventBus::channel();
+        let flaky = Recorder::new(2);
+        let steady = Recorder::new(0);
+        let subscribers: Vec<Arc<dyn Subscriber>> = vec![flaky.clone(), steady.clone()];
+        bus.publish(created());
+        drop(bus);
+        run_subscribers(rx, subscribers, CancellationToken::new()).await;
+        assert_eq!(*flaky.seen.lock().unwrap(), ["task_created"; 3]);
+        assert_eq!(*steady.seen.lock().unwrap(), ["task_created"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_stop_after_max_attempts() {
+        let (bus, rx) = EventBus::channel();
+        let broken = Recorder::new(u64::MAX);
+        bus.publish(created());
+        drop(bus);
+        run_subscribers(rx, vec![broken.clone()], CancellationToken::new()).await;
+        assert_eq!(broken.seen.lock().unwrap().len(), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn queued_events_are_delivered_after_shutdown() {
+        let (bus, rx) = EventBus::channel();
+        let recorder = Recorder::new(0);
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        bus.publish(created());
+        bus.publish(created());
+        run_subscribers(rx, vec![recorder.clone()], shutdown).await;
+        assert_eq!(recorder.seen.lock().unwrap().len(), 2);
+        // The worker has stopped; later events are counted as dropped.
+        bus.publish(created());
+        assert_eq!(bus.dropped(), 1);
+    }
+}
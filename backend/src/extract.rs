@@ -0,0 +1,224 @@
+//! `AppJson`, the request-body extractor every handler uses in place of
+//! axum's `Json`, and `AppPath`, its counterpart for path parameters, so that
+//! bad input gets our error envelope instead of axum's plain-text rejections.
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{
+        path::ErrorKind,
+        rejection::PathRejection,
+        FromRequest, FromRequestParts, Path, Request,
+    },
+    http::{header, request::Parts, HeaderMap, StatusCode},
+};
+use serde::de::DeserializeOwned;
+
+use crate::errors::{AppError, FieldError};
+
+/// A JSON request body. Fails with:
+///
+/// - 415 `unsupported_media_type` unless `Content-Type` is JSON;
+/// - 400 `bad_request` for malformed JSON, giving the line and column;
+/// - 422 `validation_failed` when the JSON doesn't fit `T`, naming the
+///   field (dotted, e.g. `cti.item_id`) with code `required` or `invalid_value`.
+pub struct AppJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for AppJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(req.headers()) {
+            return Err(AppError::UnsupportedMediaType);
+        }
+        let bytes = Bytes::from_request(req, state).await.map_err(|e| match e.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge,
+            _ => AppError::BadRequest(e.body_text()),
+        })?;
+        parse(&bytes).map(AppJson)
+    }
+}
+
+/// `application/json`, or any `application/*+json`, with or without parameters.
+fn is_json(headers: &HeaderMap) -> bool {
+    let Some(value) = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let essence = value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    essence == "application/json" || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+fn parse<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, AppError> {
+    let de = &mut serde_json::Deserializer::from_slice(bytes);
+    let e = match serde_path_to_error::deserialize(de) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    let path = e.path().to_string();
+    let inner = e.into_inner();
+    if !inner.is_data() {
+        return Err(AppError::BadRequest(format!(
+            "Malformed JSON at line {} column {}",
+            inner.line(),
+            inner.column()
+        )));
+    }
+    // serde reports a missing field against the object that lacks it.
+    let message = inner.to_string();
+    let message = message.split(" at line ").next().unwrap_or_default();
+    let field = |name: &str| match path.as_str() {
+        "." => name.to_string(),
+        parent => format!("{parent}.{name}"),
+    };
+    let error = match message.strip_prefix("missing field `").and_then(|s| s.strip_suffix('`')) {
+        Some(name) => FieldError::new(field(name), "required", format!("{} is required", field(name))),
+        None if path == "." => FieldError::new("body", "invalid_value", message),
+        None => FieldError::new(path, "invalid_value", message),
+    };
+    Err(error.into())
+}
+
+/// Path parameters. A segment that fails to deserialize, such as a
+/// `models::id::Id` that is not a UUID, is a 400 `malformed_id`.
+pub struct AppPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for AppPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(AppPath(value)),
+            Err(PathRejection::FailedToDeserializePathParams(e)) => match e.into_kind() {
+                ErrorKind::Message(message) => Err(AppError::MalformedId(message)),
+                kind => Err(AppError::BadRequest(kind.to_string())),
+            },
+            Err(e) => Err(AppError::Internal(anyhow::anyhow!(e.body_text()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        response::IntoResponse,
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Payload {
+        title: String,
+        count: u32,
+        cti: Option<Cti>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Cti {
+        item_id: String,
+    }
+
+    async fn post_body(content_type: Option<&str>, body: &str) -> (StatusCode, serde_json::Value) {
+        let app = Router::new().route("/x", post(|AppJson(p): AppJson<Payload>| async move { Json(p.title) }));
+        let mut req = Request::builder().method("POST").uri("/x");
+        if let Some(content_type) = content_type {
+            req = req.header(header::CONTENT_TYPE, content_type);
+        }
+        let resp = app.oneshot(req.body(Body::from(body.to_string())).unwrap()).await.unwrap().into_response();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn well_formed_json_is_accepted() {
+        let body = r#"{"title":"t","count":1}"#;
+        assert_eq!(post_body(Some("application/json"), body).await, (StatusCode::OK, "t".into()));
+        let (status, _) = post_body(Some("application/merge-patch+json; charset=utf-8"), body).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn non_json_content_types_are_415() {
+        for content_type in [None, Some("application/x-www-form-urlencoded"), Some("text/plain")] {
+            let (status, body) = post_body(content_type, "title=t&count=1").await;
+            assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            assert_eq!(body["code"], "unsupported_media_type");
+        }
+    }
+
+    #[tokio::test]
+    async fn syntax_errors_give_the_position() {
+        let (status, body) = post_body(Some("application/json"), "{\n  \"title\": \"t\",,\n}").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["error"], "Malformed JSON at line 2 column 16");
+    }
+
+    #[tokio::test]
+    async fn data_errors_name_the_field() {
+        let (status, body) = post_body(Some("application/json"), r#"{"count":1}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "title");
+        assert_eq!(body["fields"][0]["code"], "required");
+
+        let (_, body) = post_body(Some("application/json"), r#"{"title":"t","count":"one"}"#).await;
+        assert_eq!(body["fields"][0]["field"], "count");
+        assert_eq!(body["fields"][0]["code"], "invalid_value");
+        assert_eq!(body["fields"][0]["message"], "invalid type: string \"one\", expected u32");
+
+        let (_, body) = post_body(Some("application/json"), r#"{"title":"t","count":1,"cti":{}}"#).await;
+        assert_eq!(body["fields"][0]["field"], "cti.item_id");
+
+        let (_, body) = post_body(Some("application/json"), "[]").await;
+        assert_eq!(body["fields"][0]["field"], "body");
+    }
+
+    #[tokio::test]
+    async fn malformed_path_ids_are_400() {
+        use crate::models::id::Id;
+
+        let app = Router::new()
+            .route("/tasks/:id", get(|AppPath(id): AppPath<Id>| async move { id.into_inner() }))
+            .route(
+                "/tasks/:id/notes/:note_id",
+                get(|AppPath((_, note)): AppPath<(Id, Id)>| async move { note.into_inner() }),
+            );
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let resp = app.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+                let status = resp.status();
+                let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+                (status, bytes)
+            }
+        };
+        let id = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let (status, body) = get(format!("/tasks/{}", id.to_uppercase())).await;
+        assert_eq!((status, &body[..]), (StatusCode::OK, id.as_bytes()));
+
+        for uri in ["/tasks/not-a-uuid".to_string(), format!("/tasks/{id}/notes/7")] {
+            let (status, body) = get(uri.clone()).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], "malformed_id");
+            assert!(body["error"].as_str().unwrap().contains("is not a valid id"), "{body}");
+        }
+    }
+}
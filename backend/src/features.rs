@@ -0,0 +1,158 @@
+//! Feature flags, set per deployment so a feature can ship dark and be turned
+//! on (for everyone, or only for admins) without a frontend release.
+//!
+//! Flags come from `FEATURE_FLAGS`, a JSON object, or the file named by
+//! `FEATURE_FLAGS_FILE`: `{"task_board": false, "task_stream": {"enabled":
+//! true, "admin_only": true}}`. Every flag must be in `KNOWN_FLAGS`; those
+//! left out keep their default. Gated handlers call `FeatureFlags::require`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{AppError, AppResult},
+    handlers::auth::Claims,
+    permissions::{has_permission, USERS_MANAGE},
+};
+
+/// GET /api/tasks/board.
+pub const TASK_BOARD: &str = "task_board";
+/// GET /api/tasks/stream.
+pub const TASK_STREAM: &str = "task_stream";
+
+/// Every flag and its default, which is on for everyone.
+pub const KNOWN_FLAGS: &[&str] = &[TASK_BOARD, TASK_STREAM];
+
+/// One flag's effective setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Flag {
+    pub enabled: bool,
+    /// Only admins see the flag or get past it; others get 403.
+    pub admin_only: bool,
+    /// Set by configuration rather than left at its default.
+    pub configured: bool,
+}
+
+impl Default for Flag {
+    fn default() -> Self {
+        Self { enabled: true, admin_only: false, configured: false }
+    }
+}
+
+/// A flag as written in `FEATURE_FLAGS`: a bare on/off, or the full form.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FlagSetting {
+    Enabled(bool),
+    Full {
+        enabled: bool,
+        #[serde(default)]
+        admin_only: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureFlags(BTreeMap<&'static str, Flag>);
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self(KNOWN_FLAGS.iter().map(|name| (*name, Flag::default())).collect())
+    }
+}
+
+impl FeatureFlags {
+    /// The defaults overridden by the JSON object `json`; names outside
+    /// `KNOWN_FLAGS` are an error, so a typo can't silently do nothing.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let settings: BTreeMap<String, FlagSetting> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut flags = Self::default();
+        for (name, setting) in settings {
+            let Some(flag) = flags.0.iter_mut().find(|(known, _)| **known == name).map(|(_, flag)| flag) else {
+                return Err(format!("unknown feature flag '{name}'; known flags are {}", KNOWN_FLAGS.join(", ")));
+            };
+            let (enabled, admin_only) = match setting {
+                FlagSetting::Enabled(enabled) => (enabled, false),
+                FlagSetting::Full { enabled, admin_only } => (enabled, admin_only),
+            };
+            *flag = Flag { enabled, admin_only, configured: true };
+        }
+        Ok(flags)
+    }
+
+    /// Whether `name` is on at all; admin-only flags count as on.
+    pub fn enabled(&self, name: &str) -> bool {
+        self.0.get(name).is_some_and(|f| f.enabled)
+    }
+
+    /// For gated handlers: the same 404 as an unknown route while `name` is
+    /// off, and 403 for non-admins while it is admin-only.
+    pub fn require(&self, name: &str, claims: &Claims) -> AppResult<()> {
+        match self.0.get(name) {
+            Some(flag) if flag.enabled => {
+                if flag.admin_only && !has_permission(&claims.role, USERS_MANAGE) {
+                    return Err(AppError::Forbidden);
+                }
+                Ok(())
+            }
+            _ => Err(AppError::RouteNotFound),
+        }
+    }
+
+    /// Whether each flag is on, as `admin` sees it. Admin-only flags are left
+    /// out for everyone else.
+    pub fn visible_to(&self, admin: bool) -> BTreeMap<&'static str, bool> {
+        self.0.iter().filter(|(_, f)| admin || !f.admin_only).map(|(name, f)| (*name, f.enabled)).collect()
+    }
+
+    /// Every flag with its full setting.
+    pub fn all(&self) -> &BTreeMap<&'static str, Flag> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD_OFF_STREAM_ADMINS: &str = r#"{"task_board": false, "task_stream": {"enabled": true, "admin_only": true}}"#;
+
+    fn claims(role: &str) -> Claims {
+        Claims {
+            sub: "u1".into(),
+            email: "u@example.com".into(),
+            email_verified: true,
+            username: "u".into(),
+            role: role.into(),
+            exp: usize::MAX,
+            scopes: None,
+            auth_time: None,
+            workspace_id: None,
+            workspace_role: None,
+        }
+    }
+
+    #[test]
+    fn configuration_overrides_the_defaults() {
+        let flags = FeatureFlags::from_json(BOARD_OFF_STREAM_ADMINS).unwrap();
+        assert!(!flags.enabled(TASK_BOARD));
+        assert_eq!(flags.all()[TASK_STREAM], Flag { enabled: true, admin_only: true, configured: true });
+        assert!(FeatureFlags::default().enabled(TASK_BOARD));
+        assert!(!flags.enabled("no_such_flag"));
+
+        let err = FeatureFlags::from_json(r#"{"task_bored": true}"#).unwrap_err();
+        assert!(err.starts_with("unknown feature flag 'task_bored'"), "{err}");
+        assert!(FeatureFlags::from_json(r#"{"task_board": "yes"}"#).is_err());
+    }
+
+    #[test]
+    fn gates_hide_off_flags_and_keep_admin_only_ones_for_admins() {
+        let flags = FeatureFlags::from_json(BOARD_OFF_STREAM_ADMINS).unwrap();
+        assert!(matches!(flags.require(TASK_BOARD, &claims("admin")), Err(AppError::RouteNotFound)));
+        assert!(matches!(flags.require(TASK_STREAM, &claims("user")), Err(AppError::Forbidden)));
+        assert!(flags.require(TASK_STREAM, &claims("admin")).is_ok());
+
+        assert_eq!(flags.visible_to(false), BTreeMap::from([(TASK_BOARD, false)]));
+        assert_eq!(flags.visible_to(true), BTreeMap::from([(TASK_BOARD, false), (TASK_STREAM, true)]));
+    }
+}
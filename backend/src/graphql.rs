@@ -0,0 +1,662 @@
+//! GraphQL view of tasks, users and the CTI tree, at `/api/graphql`.
+//!
+//! REST stays the primary API. Resolvers go through the same repositories
+//! and task service functions as the REST handlers, and nested users and CTI
+//! names are batched through per-request dataloaders so a page of tasks
+//! costs a fixed number of queries rather than one per task.
+
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, EmptySubscription, ErrorExtensions, InputObject, MaybeUndefined, Object, Schema, SimpleObject, ID,
+};
+use bson::doc;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    db::{CtiRepo, UserRepo},
+    errors::AppError,
+    handlers::{
+        auth::{AppState, Claims},
+        tasks::{self, CreateTaskRequest, UpdateTaskRequest},
+        workspaces::member_ids,
+    },
+    models::{
+        cti::{Category, CtiItem, CtiSelection, CtiType},
+        task::{Task, TaskNote},
+        user::UserSummary,
+    },
+    pagination::PageQuery,
+};
+
+pub type MissionControlSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// The deepest real query (the CTI tree under a task) is seven levels; the
+/// rest is headroom for client introspection queries.
+const MAX_DEPTH: usize = 15;
+const MAX_COMPLEXITY: usize = 500;
+
+pub fn schema() -> MissionControlSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Attaches what resolvers need for one request: the caller, the app state
+/// and fresh dataloaders, so batching and caching never span requests.
+pub fn request_data(request: async_graphql::Request, state: &AppState, claims: Claims) -> async_graphql::Request {
+    let cti = CtiLoader { repo: state.repos.cti.clone(), ws: claims.workspace_id.clone().unwrap_or_default() };
+    request
+        .data(DataLoader::new(UserLoader(state.repos.users.clone()), tokio::spawn))
+        .data(DataLoader::new(cti, tokio::spawn))
+        .data(state.clone())
+        .data(claims)
+}
+
+// ── Errors ───────────────────────────────────────────────────────────────────
+
+/// Same message and `code` as the REST error body, under `extensions`.
+fn gql(e: AppError) -> async_graphql::Error {
+    log_server_error(&e);
+    extend(&e)
+}
+
+fn extend(e: &AppError) -> async_graphql::Error {
+    async_graphql::Error::new(e.to_string()).extend_with(|_, ext| {
+        ext.set("code", e.code());
+        if let AppError::Validation(fields) = e {
+            if let Ok(fields) = async_graphql::Value::from_json(serde_json::json!(fields)) {
+                ext.set("fields", fields);
+            }
+        }
+    })
+}
+
+fn log_server_error(e: &AppError) {
+    if e.status().is_server_error() {
+        tracing::error!(code = e.code(), "GraphQL resolver error: {e:?}");
+    }
+}
+
+/// Loader errors are shared by every resolver waiting on the batch.
+type LoadError = Arc<AppError>;
+
+fn load_error(e: AppError) -> LoadError {
+    log_server_error(&e);
+    Arc::new(e)
+}
+
+// ── Dataloaders ──────────────────────────────────────────────────────────────
+
+fn index<K: Hash + Eq, V>(values: Vec<V>, key: impl Fn(&V) -> K) -> HashMap<K, V> {
+    values.into_iter().map(|v| (key(&v), v)).collect()
+}
+
+fn group<K: Hash + Eq, V>(values: Vec<V>, key: impl Fn(&V) -> K) -> HashMap<K, Vec<V>> {
+    let mut groups: HashMap<K, Vec<V>> = HashMap::new();
+    for v in values {
+        groups.entry(key(&v)).or_default().push(v);
+    }
+    groups
+}
+
+/// Users by id. Only ids and usernames, as the REST assignee list exposes.
+pub struct UserLoader(Arc<dyn UserRepo>);
+
+impl Loader<String> for UserLoader {
+    type Value = UserSummary;
+    type Error = LoadError;
+
+    async fn load(&self, ids: &[String]) -> Result<HashMap<String, UserSummary>, LoadError> {
+        let users = self.0.find_summaries(doc! { "_id": { "$in": ids.to_vec() } }).await.map_err(load_error)?;
+        Ok(index(users, |u| u.id.clone()))
+    }
+}
+
+/// CTI lookups within one workspace, keyed by what is being looked up.
+pub struct CtiLoader {
+    repo: Arc<dyn CtiRepo>,
+    ws: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CategoryId(String);
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeId(String);
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemId(String);
+/// The types under a category.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypesOf(String);
+/// The items under a type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemsOf(String);
+
+fn ids<K>(keys: &[K], id: impl Fn(&K) -> &String) -> Vec<String> {
+    keys.iter().map(|k| id(k).clone()).collect()
+}
+
+impl Loader<CategoryId> for CtiLoader {
+    type Value = Category;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[CategoryId]) -> Result<HashMap<CategoryId, Category>, LoadError> {
+        let filter = doc! { "_id": { "$in": ids(keys, |k| &k.0) } };
+        let found = self.repo.find_categories(&self.ws, filter).await.map_err(load_error)?;
+        Ok(index(found, |c| CategoryId(c.id.clone())))
+    }
+}
+
+impl Loader<TypeId> for CtiLoader {
+    type Value = CtiType;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[TypeId]) -> Result<HashMap<TypeId, CtiType>, LoadError> {
+        let filter = doc! { "_id": { "$in": ids(keys, |k| &k.0) } };
+        let found = self.repo.find_types(&self.ws, filter).await.map_err(load_error)?;
+        Ok(index(found, |t| TypeId(t.id.clone())))
+    }
+}
+
+impl Loader<ItemId> for CtiLoader {
+    type Value = CtiItem;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[ItemId]) -> Result<HashMap<ItemId, CtiItem>, LoadError> {
+        let filter = doc! { "_id": { "$in": ids(keys, |k| &k.0) } };
+        let found = self.repo.find_items(&self.ws, filter).await.map_err(load_error)?;
+        Ok(index(found, |i| ItemId(i.id.clone())))
+    }
+}
+
+impl Loader<TypesOf> for CtiLoader {
+    type Value = Vec<CtiType>;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[TypesOf]) -> Result<HashMap<TypesOf, Vec<CtiType>>, LoadError> {
+        let filter = doc! { "category_id": { "$in": ids(keys, |k| &k.0) } };
+        let found = self.repo.find_types(&self.ws, filter).await.map_err(load_error)?;
+        Ok(group(found, |t| TypesOf(t.category_id.clone())))
+    }
+}
+
+impl Loader<ItemsOf> for CtiLoader {
+    type Value = Vec<CtiItem>;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[ItemsOf]) -> Result<HashMap<ItemsOf, Vec<CtiItem>>, LoadError> {
+        let filter = doc! { "type_id": { "$in": ids(keys, |k| &k.0) } };
+        let found = self.repo.find_items(&self.ws, filter).await.map_err(load_error)?;
+        Ok(group(found, |i| ItemsOf(i.type_id.clone())))
+    }
+}
+
+async fn load_one<L, K>(ctx: &Context<'_>, key: K) -> async_graphql::Result<Option<L::Value>>
+where
+    L: Loader<K, Error = LoadError>,
+    K: Send + Sync + Hash + Eq + Clone + 'static,
+{
+    ctx.data_unchecked::<DataLoader<L>>().load_one(key).await.map_err(|e| extend(&e))
+}
+
+async fn user(ctx: &Context<'_>, id: Option<&String>) -> async_graphql::Result<Option<UserObject>> {
+    let Some(id) = id else { return Ok(None) };
+    Ok(load_one::<UserLoader, _>(ctx, id.clone()).await?.map(UserObject))
+}
+
+// ── Output types ─────────────────────────────────────────────────────────────
+
+pub struct UserObject(UserSummary);
+
+#[Object(name = "User")]
+impl UserObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+}
+
+pub struct TaskObject(Task);
+
+#[Object(name = "Task")]
+impl TaskObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn workspace_id(&self) -> &str {
+        &self.0.workspace_id
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn status(&self) -> &str {
+        &self.0.status
+    }
+
+    async fn assignee_id(&self) -> Option<&str> {
+        self.0.assignee_id.as_deref()
+    }
+
+    async fn assignee(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserObject>> {
+        user(ctx, self.0.assignee_id.as_ref()).await
+    }
+
+    async fn created_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserObject>> {
+        user(ctx, self.0.created_by.as_ref()).await
+    }
+
+    async fn cti(&self) -> Option<CtiSelectionObject> {
+        self.0.cti.clone().map(CtiSelectionObject)
+    }
+
+    async fn notes(&self) -> Vec<NoteObject> {
+        self.0.notes.iter().cloned().map(NoteObject).collect()
+    }
+
+    async fn status_changed_at(&self) -> Option<DateTime<Utc>> {
+        self.0.status_changed_at
+    }
+
+    async fn assigned_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserObject>> {
+        user(ctx, self.0.assigned_by.as_ref()).await
+    }
+
+    async fn assigned_at(&self) -> Option<DateTime<Utc>> {
+        self.0.assigned_at
+    }
+
+    async fn due_at(&self) -> Option<DateTime<Utc>> {
+        self.0.due_at
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.0.updated_at
+    }
+}
+
+pub struct NoteObject(TaskNote);
+
+#[Object(name = "Note")]
+impl NoteObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn note(&self) -> &str {
+        &self.0.note
+    }
+
+    /// Sanitized HTML, when notes are markdown; see `NOTE_FORMAT`.
+    async fn rendered_html(&self) -> Option<&str> {
+        self.0.rendered_html.as_deref()
+    }
+
+    async fn author_id(&self) -> &str {
+        &self.0.author
+    }
+
+    async fn author(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserObject>> {
+        user(ctx, Some(&self.0.author)).await
+    }
+
+    /// Orders notes written in the same millisecond; notes are already
+    /// listed oldest first.
+    async fn seq(&self) -> i64 {
+        self.0.seq
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+}
+
+/// A task's classification, with the names resolved. A category, type or
+/// item deleted since the task was classified resolves to null.
+pub struct CtiSelectionObject(CtiSelection);
+
+#[Object(name = "CtiSelection")]
+impl CtiSelectionObject {
+    async fn category_id(&self) -> &str {
+        &self.0.category_id
+    }
+
+    async fn type_id(&self) -> &str {
+        &self.0.type_id
+    }
+
+    async fn item_id(&self) -> &str {
+        &self.0.item_id
+    }
+
+    async fn category(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<CategoryObject>> {
+        let key = CategoryId(self.0.category_id.clone());
+        Ok(load_one::<CtiLoader, _>(ctx, key).await?.map(CategoryObject))
+    }
+
+    #[graphql(name = "type")]
+    async fn cti_type(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<CtiTypeObject>> {
+        let key = TypeId(self.0.type_id.clone());
+        Ok(load_one::<CtiLoader, _>(ctx, key).await?.map(CtiTypeObject))
+    }
+
+    async fn item(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<CtiItemObject>> {
+        let key = ItemId(self.0.item_id.clone());
+        Ok(load_one::<CtiLoader, _>(ctx, key).await?.map(CtiItemObject))
+    }
+}
+
+pub struct CategoryObject(Category);
+
+#[Object(name = "CtiCategory")]
+impl CategoryObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn types(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<CtiTypeObject>> {
+        let types = load_one::<CtiLoader, _>(ctx, TypesOf(self.0.id.clone())).await?;
+        Ok(types.unwrap_or_default().into_iter().map(CtiTypeObject).collect())
+    }
+}
+
+pub struct CtiTypeObject(CtiType);
+
+#[Object(name = "CtiType")]
+impl CtiTypeObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn category_id(&self) -> &str {
+        &self.0.category_id
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn items(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<CtiItemObject>> {
+        let items = load_one::<CtiLoader, _>(ctx, ItemsOf(self.0.id.clone())).await?;
+        Ok(items.unwrap_or_default().into_iter().map(CtiItemObject).collect())
+    }
+}
+
+pub struct CtiItemObject(CtiItem);
+
+#[Object(name = "CtiItem")]
+impl CtiItemObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.clone())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn type_id(&self) -> &str {
+        &self.0.type_id
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+}
+
+/// Same shape as a REST page, with the items under `items`.
+#[derive(SimpleObject)]
+pub struct TaskPage {
+    items: Vec<TaskObject>,
+    total: u64,
+    page: u64,
+    limit: u64,
+    total_pages: u64,
+    has_next: bool,
+    has_prev: bool,
+    /// The `limit` asked for, when it was lowered to the maximum page size.
+    requested_limit: Option<u64>,
+}
+
+// ── Inputs ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Default, InputObject)]
+pub struct TaskFilter {
+    /// Workflow status keys, as listed by GET /api/statuses; empty or absent means all.
+    #[graphql(default)]
+    status: Vec<String>,
+}
+
+#[derive(Debug, Clone, InputObject)]
+pub struct CtiSelectionInput {
+    category_id: String,
+    type_id: String,
+    item_id: String,
+}
+
+impl From<CtiSelectionInput> for CtiSelection {
+    fn from(input: CtiSelectionInput) -> Self {
+        Self { category_id: input.category_id, type_id: input.type_id, item_id: input.item_id }
+    }
+}
+
+#[derive(Debug, InputObject)]
+pub struct CreateTaskInput {
+    title: String,
+    description: String,
+    assignee_id: Option<String>,
+    cti: Option<CtiSelectionInput>,
+    due_at: Option<DateTime<Utc>>,
+}
+
+impl From<CreateTaskInput> for CreateTaskRequest {
+    fn from(input: CreateTaskInput) -> Self {
+        Self {
+            title: input.title,
+            description: input.description,
+            assignee_id: input.assignee_id,
+            cti: input.cti.map(Into::into),
+            due_at: input.due_at,
+        }
+    }
+}
+
+/// Omitted fields are left alone; `assigneeId`, `cti` and `dueAt` may be set to null
+/// to clear them, as in the REST `PUT`.
+#[derive(Debug, InputObject)]
+pub struct UpdateTaskInput {
+    title: Option<String>,
+    description: Option<String>,
+    status: Option<String>,
+    assignee_id: MaybeUndefined<String>,
+    cti: MaybeUndefined<CtiSelectionInput>,
+    due_at: MaybeUndefined<DateTime<Utc>>,
+}
+
+fn nullable<T, U: From<T>>(value: MaybeUndefined<T>) -> Option<Option<U>> {
+    match value {
+        MaybeUndefined::Undefined => None,
+        MaybeUndefined::Null => Some(None),
+        MaybeUndefined::Value(v) => Some(Some(v.into())),
+    }
+}
+
+impl From<UpdateTaskInput> for UpdateTaskRequest {
+    fn from(input: UpdateTaskInput) -> Self {
+        Self {
+            title: input.title,
+            description: input.description,
+            status: input.status,
+            assignee_id: nullable(input.assignee_id),
+            cti: nullable(input.cti),
+            due_at: nullable(input.due_at),
+        }
+    }
+}
+
+// ── Roots ────────────────────────────────────────────────────────────────────
+
+fn state<'a>(ctx: &Context<'a>) -> &'a AppState {
+    ctx.data_unchecked::<AppState>()
+}
+
+fn claims<'a>(ctx: &Context<'a>) -> &'a Claims {
+    ctx.data_unchecked::<Claims>()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Tasks in the current workspace, newest first.
+    async fn tasks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: TaskFilter,
+        #[graphql(default = 1)] page: u64,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<TaskPage> {
+        let (page, mut errors) = PageQuery { page, limit }.resolve(&state(ctx).config.page_limits);
+        let workflow = state(ctx).workflow.get(&state(ctx).db).await.map_err(gql)?;
+        let statuses = workflow.parse_statuses(&filter.status.join(",")).unwrap_or_else(|message| {
+            errors.push(crate::errors::FieldError::new("status", "invalid_status", message));
+            None
+        });
+        if !errors.is_empty() {
+            return Err(gql(AppError::Validation(errors)));
+        }
+        let filter = match statuses {
+            None => doc! {},
+            Some(list) => doc! { "status": { "$in": list } },
+        };
+        let ws = claims(ctx).workspace().map_err(gql)?;
+        let found = state(ctx).repos.tasks.find_page(ws, filter, doc! { "created_at": -1 }, page).await.map_err(gql)?;
+        Ok(TaskPage {
+            has_next: found.has_next(),
+            has_prev: found.has_prev(),
+            items: found.items.into_iter().map(TaskObject).collect(),
+            total: found.total,
+            page: found.page,
+            limit: found.limit,
+            total_pages: found.total_pages,
+            requested_limit: found.requested_limit,
+        })
+    }
+
+    async fn task(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<TaskObject>> {
+        let ws = claims(ctx).workspace().map_err(gql)?;
+        let task = state(ctx).repos.tasks.find_by_id(ws, &id).await.map_err(gql)?;
+        Ok(task.map(TaskObject))
+    }
+
+    /// Active members of the workspace by username, as the REST assignee
+    /// list returns them.
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] include_inactive: bool,
+    ) -> async_graphql::Result<Vec<UserObject>> {
+        let ws = claims(ctx).workspace().map_err(gql)?;
+        let mut filter = if include_inactive { doc! {} } else { doc! { "active": { "$ne": false } } };
+        filter.insert("_id", doc! { "$in": member_ids(&state(ctx).db, ws).await.map_err(gql)? });
+        let users = state(ctx).repos.users.find_summaries(filter).await.map_err(gql)?;
+        Ok(users.into_iter().map(UserObject).collect())
+    }
+
+    /// The workspace's CTI categories; types and items nest beneath them.
+    async fn cti(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<CategoryObject>> {
+        let ws = claims(ctx).workspace().map_err(gql)?;
+        let categories = state(ctx).repos.cti.list_categories(ws).await.map_err(gql)?;
+        Ok(categories.into_iter().map(CategoryObject).collect())
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_task(&self, ctx: &Context<'_>, input: CreateTaskInput) -> async_graphql::Result<TaskObject> {
+        let task = tasks::create(state(ctx), claims(ctx), input.into()).await.map_err(gql)?;
+        Ok(TaskObject(task))
+    }
+
+    async fn update_task(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        input: UpdateTaskInput,
+    ) -> async_graphql::Result<TaskObject> {
+        let task = tasks::update(state(ctx), claims(ctx), &id, input.into()).await.map_err(gql)?;
+        Ok(TaskObject(task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_input_keeps_null_apart_from_absent() {
+        let input = UpdateTaskInput {
+            title: Some("T".into()),
+            description: None,
+            status: None,
+            assignee_id: MaybeUndefined::Null,
+            cti: MaybeUndefined::Undefined,
+            due_at: MaybeUndefined::Null,
+        };
+        let request = UpdateTaskRequest::from(input);
+        assert_eq!(request.assignee_id, Some(None));
+        assert!(request.cti.is_none());
+        assert_eq!(request.due_at, Some(None));
+    }
+
+    #[test]
+    fn errors_carry_the_rest_code() {
+        let e = gql(AppError::NotFound);
+        assert_eq!(e.message, "Not found");
+        let ext = e.extensions.unwrap();
+        assert_eq!(ext.get("code"), Some(&async_graphql::Value::from("not_found")));
+    }
+
+    #[tokio::test]
+    async fn overly_deep_queries_are_rejected_before_resolving() {
+        let deep = format!("{{ __schema {{ types {{ fields {{ type {}name{} }} }} }} }}", "{ ofType ".repeat(MAX_DEPTH), " }".repeat(MAX_DEPTH));
+        let response = schema().execute(deep).await;
+        assert!(response.errors.iter().any(|e| e.message.contains("nested too deep")), "{response:?}");
+    }
+
+    #[test]
+    fn sdl_exposes_the_agreed_operations() {
+        let sdl = schema().sdl();
+        for field in ["tasks(", "task(", "users(", "cti:", "createTask(", "updateTask("] {
+            assert!(sdl.contains(field), "{field}");
+        }
+    }
+}
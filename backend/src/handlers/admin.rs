@@ -1,16 +1,33 @@
+use std::collections::{BTreeMap, HashMap};
+
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Query, State},
     Json,
 };
-use bson::{doc, to_bson};
-use chrono::Utc;
-use serde::Deserialize;
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    options::{FindOneAndUpdateOptions, FindOptions, ReplaceOptions, ReturnDocument, UpdateOptions},
+    ClientSession, Database,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
 
 use crate::{
-    errors::{AppError, AppResult},
-    handlers::auth::{AppState, Claims},
-    models::user::{User, UserPublic},
+    audit,
+    db::{collect, escape_regex, API_KEYS, LOCKS, NOTIFICATIONS, SAVED_VIEWS, TASKS, USERS, WORKSPACE_MEMBERS},
+    errors::{AppError, AppResult, FieldError},
+    extract::{AppJson, AppPath},
+    handlers::auth::{AdminUser, AppState, Claims},
+    models::{
+        dates::to_bson_date,
+        id::Id,
+        task::Task,
+        user::{anonymized_email, anonymized_username, normalize_email, AdminUserDetail, User, UserPublic},
+        workflow::Workflow,
+    },
+    pagination::{paginate, PageParams, PageQuery, Paginated},
+    permissions::validate_role,
 };
 
 #[derive(Debug, Deserialize)]
@@ -24,76 +41,346 @@ pub struct UpdateRoleRequest {
     pub role: String,
 }
 
-fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
-    match e.kind.as_ref() {
-        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(we)) => {
-            we.code == 11000
+/// Filters shared by the admin user list and its export.
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminUserQuery {
+    pub role: Option<String>,
+    /// Case-insensitive substring of the email or username.
+    pub q: Option<String>,
+    pub active: Option<bool>,
+}
+
+impl AdminUserQuery {
+    pub fn to_filter(&self) -> Result<bson::Document, FieldError> {
+        let mut filter = doc! {};
+        if let Some(role) = self.role.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+            validate_role(role)?;
+            filter.insert("role", role);
+        }
+        if let Some(q) = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            let pattern = doc! { "$regex": escape_regex(q), "$options": "i" };
+            filter.insert("$or", vec![doc! { "email": pattern.clone() }, doc! { "username": pattern }]);
+        }
+        match self.active {
+            // Records from before deactivation existed have no `active` field.
+            Some(true) => filter.insert("active", doc! { "$ne": false }),
+            Some(false) => filter.insert("active", false),
+            None => None,
+        };
+        Ok(filter)
+    }
+}
+
+/// Ways a task can belong to a user, for `GET /api/admin/users/:id/tasks`.
+/// Tasks have no watchers, so there is no `watching`.
+pub const TASK_RELATIONS: &[&str] = &["assigned", "created"];
+
+/// Query parameters for `GET /api/admin/users/:id/tasks`.
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminUserTasksQuery {
+    /// Comma-separated statuses; open ones when absent, all when blank.
+    pub status: Option<String>,
+    /// One of `TASK_RELATIONS`; `assigned` when absent.
+    pub relation: Option<String>,
+}
+
+impl AdminUserTasksQuery {
+    /// The filter for `user_id`'s tasks, in any workspace.
+    pub fn to_filter(&self, user_id: &str, workflow: &Workflow) -> Result<bson::Document, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let field = match self.relation.as_deref().map(str::trim).unwrap_or("assigned") {
+            "assigned" | "" => "assignee_id",
+            "created" => "created_by",
+            other => {
+                errors.push(FieldError::new(
+                    "relation",
+                    "invalid_relation",
+                    format!("invalid relation '{other}': must be one of {}", TASK_RELATIONS.join(", ")),
+                ));
+                "assignee_id"
+            }
+        };
+        let mut filter = doc! { field: user_id };
+        let statuses = match &self.status {
+            None => Ok(Some(workflow.open_keys())),
+            Some(s) => workflow.parse_statuses(s),
+        };
+        match statuses {
+            Ok(Some(list)) => {
+                filter.insert("status", doc! { "$in": list });
+            }
+            Ok(None) => {}
+            Err(message) => errors.push(FieldError::new("status", "invalid_status", message)),
         }
-        mongodb::error::ErrorKind::Command(ce) => ce.code == 11000,
-        _ => false,
+        if errors.is_empty() { Ok(filter) } else { Err(errors) }
     }
 }
 
+/// GET /api/admin/users?page=&limit=&role=&q=&active= — sorted by username.
 pub async fn admin_list_users(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    _: AdminUser,
     State(state): State<AppState>,
-) -> AppResult<Json<Vec<UserPublic>>> {
-    let collection = state.db.collection::<User>("users");
-    let mut cursor = collection
-        .find(None, None)
+    page: PageParams,
+    Query(params): Query<AdminUserQuery>,
+) -> AppResult<Json<Paginated<UserPublic>>> {
+    let users = state.repos.users.find_page(params.to_filter()?, page).await?;
+    Ok(Json(users.in_range(state.config.strict_pagination)?.map(UserPublic::from)))
+}
+
+/// GET /api/admin/users/:id
+pub async fn admin_get_user(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<AdminUserDetail>> {
+    let user = load_user(&state, &id).await?;
+    let assigned = state
+        .db
+        .collection::<Task>(TASKS)
+        .count_documents(doc! { "assignee_id": &id }, None)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
+    Ok(Json(AdminUserDetail::new(user, assigned)))
+}
 
-    let mut users = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        let user = cursor
-            .deserialize_current()
-            .map_err(AppError::Database)?;
-        users.push(UserPublic::from(user));
+/// GET /api/admin/users/:id/tasks?relation=assigned|created&status= — the
+/// user's tasks across every workspace, newest first, for offboarding.
+/// Works for deactivated and anonymized users, and for deleted ones whose id
+/// tasks still carry; 404 only when neither a user nor a task knows the id.
+pub async fn admin_user_tasks(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    Query(page): Query<PageQuery>,
+    Query(params): Query<AdminUserTasksQuery>,
+) -> AppResult<Json<Paginated<Task>>> {
+    let workflow = state.workflow.get(&state.db).await?;
+    let (page, mut errors) = page.resolve(&state.config.page_limits);
+    let filter = params.to_filter(&id, &workflow).unwrap_or_else(|e| {
+        errors.extend(e);
+        doc! {}
+    });
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    let tasks = state.db.collection::<Task>(TASKS);
+    if state.repos.users.find_by_id(&id).await?.is_none() {
+        let referenced = doc! { "$or": [{ "assignee_id": &*id }, { "created_by": &*id }] };
+        if tasks.count_documents(referenced, None).await? == 0 {
+            return Err(AppError::NotFound);
+        }
     }
-    Ok(Json(users))
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    Ok(Json(paginate(&tasks, filter, options, page).await?.in_range(state.config.strict_pagination)?))
 }
 
 pub async fn admin_update_user(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    _: AdminUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
-    Json(payload): Json<UpdateUserRequest>,
+    AppPath(id): AppPath<Id>,
+    AppJson(payload): AppJson<UpdateUserRequest>,
 ) -> AppResult<Json<UserPublic>> {
-    let mut set_doc = doc! { "updated_at": to_bson(&Utc::now()).unwrap() };
+    let now = to_bson_date(state.clock.now());
+    let mut set_doc = doc! { "updated_at": now };
 
     if let Some(email) = payload.email {
-        set_doc.insert("email", email);
+        set_doc.insert("email", normalize_email(&email));
     }
     if let Some(username) = payload.username {
         set_doc.insert("username", username);
     }
 
-    let options = mongodb::options::FindOneAndUpdateOptions::builder()
-        .return_document(mongodb::options::ReturnDocument::After)
-        .build();
-
-    let collection = state.db.collection::<User>("users");
-    let user = collection
-        .find_one_and_update(doc! { "_id": &id }, doc! { "$set": set_doc }, options)
-        .await
-        .map_err(|e| {
-            if is_duplicate_key(&e) {
-                AppError::Conflict("Email or username already taken".into())
-            } else {
-                AppError::Database(e)
-            }
-        })?
+    let user = state
+        .repos
+        .users
+        .update_fields(&id, set_doc)
+        .await?
         .ok_or(AppError::NotFound)?;
 
     Ok(Json(user.into()))
 }
 
+/// An admin operation that may take away a user's admin powers.
+#[derive(Debug, Clone)]
+enum AdminChange {
+    SetRole(String),
+    SetActive(bool),
+    Anonymize,
+    Delete,
+}
+
+impl AdminChange {
+    /// Whether applying this to an active admin leaves them without admin powers.
+    fn removes_admin(&self) -> bool {
+        match self {
+            AdminChange::SetRole(role) => role != "admin",
+            AdminChange::SetActive(active) => !active,
+            AdminChange::Anonymize | AdminChange::Delete => true,
+        }
+    }
+}
+
+/// Storage operations used by `apply_guarded`, abstracted so the
+/// concurrent-demotion handling can be exercised without MongoDB.
+trait AdminStore {
+    /// Applies the change and returns the user as it was before, if found.
+    async fn apply(&self, id: &str, change: &AdminChange) -> AppResult<Option<User>>;
+    /// Puts a previously returned document back verbatim.
+    async fn restore(&self, user: &User) -> AppResult<()>;
+    /// Writes the document every change that may remove an admin writes, so
+    /// that two of them in concurrent transactions conflict.
+    async fn lock_admins(&self) -> AppResult<()>;
+    async fn count_active_admins(&self) -> AppResult<u64>;
+}
+
+/// Runs inside `with_txn`, so the change, the admin count and any follow-up
+/// writes commit together.
+struct MongoAdminStore<'a> {
+    db: &'a Database,
+    session: Mutex<&'a mut ClientSession>,
+    /// Stamped as `updated_at` on the changed user.
+    now: DateTime<Utc>,
+}
+
+impl<'a> MongoAdminStore<'a> {
+    fn new(db: &'a Database, session: &'a mut ClientSession, now: DateTime<Utc>) -> Self {
+        Self { db, session: Mutex::new(session), now }
+    }
+
+    fn into_session(self) -> &'a mut ClientSession {
+        self.session.into_inner()
+    }
+}
+
+impl AdminStore for MongoAdminStore<'_> {
+    async fn apply(&self, id: &str, change: &AdminChange) -> AppResult<Option<User>> {
+        let collection = self.db.collection::<User>(USERS);
+        let now = to_bson_date(self.now);
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+        let update = match change {
+            AdminChange::SetRole(role) => doc! { "$set": { "role": role, "updated_at": now } },
+            AdminChange::SetActive(active) => doc! { "$set": { "active": active, "updated_at": now } },
+            AdminChange::Anonymize => doc! {
+                "$set": {
+                    "email": anonymized_email(id),
+                    "email_verified": false,
+                    "username": anonymized_username(id),
+                    "active": false,
+                    "anonymized_at": now.clone(),
+                    "updated_at": now,
+                },
+                "$unset": { "preferences": "", "active_workspace_id": "", "last_login_at": "" },
+            },
+            AdminChange::Delete => {
+                return collection
+                    .find_one_and_delete_with_session(doc! { "_id": id }, None, *self.session.lock().await)
+                    .await
+                    .map_err(AppError::from);
+            }
+        };
+        collection
+            .find_one_and_update_with_session(
+                doc! { "_id": id },
+                update,
+                options,
+                *self.session.lock().await,
+            )
+            .await
+            .map_err(AppError::from)
+    }
+
+    async fn restore(&self, user: &User) -> AppResult<()> {
+        self.db
+            .collection::<User>(USERS)
+            .replace_one_with_session(
+                doc! { "_id": &user.id },
+                user,
+                ReplaceOptions::builder().upsert(true).build(),
+                *self.session.lock().await,
+            )
+            .await
+            .map_err(AppError::from)?;
+        Ok(())
+    }
+
+    async fn lock_admins(&self) -> AppResult<()> {
+        self.db
+            .collection::<bson::Document>(LOCKS)
+            .update_one_with_session(
+                doc! { "_id": "last_admin" },
+                doc! { "$inc": { "version": 1 } },
+                UpdateOptions::builder().upsert(true).build(),
+                *self.session.lock().await,
+            )
+            .await
+            .map_err(AppError::from)?;
+        Ok(())
+    }
+
+    async fn count_active_admins(&self) -> AppResult<u64> {
+        self.db
+            .collection::<User>(USERS)
+            .count_documents_with_session(
+                doc! { "role": "admin", "active": { "$ne": false } },
+                None,
+                *self.session.lock().await,
+            )
+            .await
+            .map_err(AppError::from)
+    }
+}
+
+/// Applies `change` and refuses it (409) if it would leave no active admin.
+///
+/// Transactions read from a snapshot, so two concurrent demotions of the last
+/// two admins would each still count the other. Every change that may remove
+/// an admin therefore takes `lock_admins` before anything else: of two such
+/// transactions one write-conflicts, is retried by `with_txn` and then counts
+/// from a snapshot that includes the other's change. The write happens before
+/// the count and is undone if the count hit zero; without transactions
+/// (`MONGO_TRANSACTIONS=false`) that undo is all that guards the last admin.
+async fn apply_guarded<S: AdminStore>(store: &S, id: &str, change: AdminChange) -> AppResult<User> {
+    if change.removes_admin() {
+        store.lock_admins().await?;
+    }
+    let previous = store.apply(id, &change).await?.ok_or(AppError::NotFound)?;
+
+    if previous.role == "admin" && previous.active && change.removes_admin()
+        && store.count_active_admins().await? == 0
+    {
+        store.restore(&previous).await?;
+        tracing::warn!("Refused {:?} on {id}: it would remove the last admin", change);
+        return Err(AppError::LastAdmin);
+    }
+    Ok(previous)
+}
+
+/// `apply_guarded` in its own transaction.
+async fn apply_guarded_txn(state: &AppState, id: &str, change: AdminChange) -> AppResult<()> {
+    state
+        .repos
+        .txn
+        .with_txn(|session| {
+            let (db, id, change, now) = (state.db.clone(), id.to_string(), change.clone(), state.clock.now());
+            Box::pin(async move {
+                apply_guarded(&MongoAdminStore::new(&db, session, now), &id, change).await?;
+                Ok(())
+            })
+        })
+        .await
+}
+
+async fn load_user(state: &AppState, id: &str) -> AppResult<User> {
+    state.repos.users.find_by_id(id).await?.ok_or(AppError::NotFound)
+}
+
 pub async fn admin_update_role(
-    axum::Extension(claims): axum::Extension<Claims>,
+    AdminUser(claims): AdminUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
-    Json(payload): Json<UpdateRoleRequest>,
+    AppPath(id): AppPath<Id>,
+    AppJson(payload): AppJson<UpdateRoleRequest>,
 ) -> AppResult<Json<UserPublic>> {
     if claims.sub == id {
         return Err(AppError::BadRequest(
@@ -101,50 +388,711 @@ pub async fn admin_update_role(
         ));
     }
 
-    if payload.role != "user" && payload.role != "admin" {
-        return Err(AppError::BadRequest(
-            "Role must be 'user' or 'admin'".into(),
-        ));
-    }
+    validate_role(&payload.role)?;
 
-    let options = mongodb::options::FindOneAndUpdateOptions::builder()
-        .return_document(mongodb::options::ReturnDocument::After)
-        .build();
+    apply_guarded_txn(&state, &id, AdminChange::SetRole(payload.role)).await?;
+    state.user_cache.invalidate(&id).await;
 
-    let collection = state.db.collection::<User>("users");
-    let user = collection
-        .find_one_and_update(
-            doc! { "_id": &id },
-            doc! { "$set": { "role": &payload.role, "updated_at": to_bson(&Utc::now()).unwrap() } },
-            options,
-        )
-        .await
-        .map_err(AppError::Database)?
-        .ok_or(AppError::NotFound)?;
+    Ok(Json(load_user(&state, &id).await?.into()))
+}
 
-    Ok(Json(user.into()))
+#[derive(Debug, Deserialize)]
+pub struct DeleteUserQuery {
+    /// Hand the deleted user's tasks to this user instead of unassigning them.
+    pub reassign_to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteUserResponse {
+    pub tasks_updated: u64,
 }
 
+/// Deletes a user and either reassigns or unassigns their tasks. Notes keep
+/// the deleted author's id; clients should render unknown authors gracefully.
 pub async fn admin_delete_user(
-    axum::Extension(claims): axum::Extension<Claims>,
+    AdminUser(claims): AdminUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
-) -> AppResult<StatusCode> {
+    AppPath(id): AppPath<Id>,
+    Query(params): Query<DeleteUserQuery>,
+) -> AppResult<Json<DeleteUserResponse>> {
     if claims.sub == id {
         return Err(AppError::BadRequest(
             "Cannot delete your own account".into(),
         ));
     }
 
-    let collection = state.db.collection::<User>("users");
-    let result = collection
-        .delete_one(doc! { "_id": &id }, None)
-        .await
-        .map_err(AppError::Database)?;
+    let reassign_to = params.reassign_to.filter(|r| !r.trim().is_empty());
+    if let Some(target) = &reassign_to {
+        let invalid = |code, message: &str| AppError::from(FieldError::new("reassign_to", code, message));
+        if *target == id {
+            return Err(invalid("same_user", "reassign_to must be a different user"));
+        }
+        let target_user = load_user(&state, target).await.map_err(|e| match e {
+            AppError::NotFound => invalid("not_found", "reassign_to user not found"),
+            other => other,
+        })?;
+        if !target_user.active {
+            return Err(invalid("inactive", "reassign_to user is deactivated"));
+        }
+    }
+
+    let assignee = match &reassign_to {
+        Some(target) => bson::Bson::String(target.clone()),
+        None => bson::Bson::Null,
+    };
+    let now_dt = state.clock.now();
+    let now = to_bson_date(now_dt);
+    // The user is removed, their tasks handed over and their memberships
+    // dropped together, so a failure cannot leave tasks pointing at a deleted
+    // user or memberships that still count them.
+    let tasks_updated = state
+        .repos
+        .txn
+        .with_txn(|session| {
+            let (db, id, assignee, now) = (state.db.clone(), id.clone(), assignee.clone(), now.clone());
+            Box::pin(async move {
+                let store = MongoAdminStore::new(&db, session, now_dt);
+                apply_guarded(&store, &id, AdminChange::Delete).await?;
+                let session = store.into_session();
+                let result = db
+                    .collection::<Task>(TASKS)
+                    .update_many_with_session(
+                        doc! { "assignee_id": &id },
+                        doc! { "$set": { "assignee_id": assignee, "updated_at": now } },
+                        None,
+                        &mut *session,
+                    )
+                    .await?;
+                db.collection::<bson::Document>(WORKSPACE_MEMBERS)
+                    .delete_many_with_session(doc! { "user_id": &id }, None, session)
+                    .await?;
+                Ok(result.modified_count)
+            })
+        })
+        .await?;
+    state.user_cache.invalidate(&id).await;
+
+    tracing::info!(
+        "User {} deleted by {}; {} task(s) {}",
+        id,
+        claims.sub,
+        tasks_updated,
+        if reassign_to.is_some() { "reassigned" } else { "unassigned" }
+    );
+    Ok(Json(DeleteUserResponse { tasks_updated }))
+}
+
+async fn set_active(state: &AppState, claims: &Claims, id: &str, active: bool) -> AppResult<UserPublic> {
+    if claims.sub == id {
+        return Err(AppError::BadRequest(
+            "Cannot change your own account status".into(),
+        ));
+    }
+
+    if active && load_user(state, id).await?.anonymized_at.is_some() {
+        return Err(AppError::BadRequest("Anonymized accounts cannot be reactivated".into()));
+    }
+    apply_guarded_txn(state, id, AdminChange::SetActive(active)).await?;
+
+    state.user_cache.invalidate(id).await;
+    tracing::info!("User {} {} by {}", id, if active { "activated" } else { "deactivated" }, claims.sub);
+    Ok(load_user(state, id).await?.into())
+}
+
+pub async fn admin_deactivate_user(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<UserPublic>> {
+    Ok(Json(set_active(&state, &claims, &id, false).await?))
+}
+
+pub async fn admin_activate_user(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<UserPublic>> {
+    Ok(Json(set_active(&state, &claims, &id, true).await?))
+}
+
+/// Most accounts one bulk request may change.
+pub const BULK_USERS_MAX: usize = 500;
+
+/// A change `POST /api/admin/users/bulk` applies to every listed user:
+/// `"deactivate"`, `"activate"` or `{ "set_role": "user" }`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkUserAction {
+    Deactivate,
+    Activate,
+    SetRole(String),
+}
 
-    if result.deleted_count == 0 {
-        return Err(AppError::NotFound);
+impl BulkUserAction {
+    fn change(&self) -> AdminChange {
+        match self {
+            BulkUserAction::Deactivate => AdminChange::SetActive(false),
+            BulkUserAction::Activate => AdminChange::SetActive(true),
+            BulkUserAction::SetRole(role) => AdminChange::SetRole(role.clone()),
+        }
+    }
+
+    /// Label for audit entries.
+    fn name(&self) -> &'static str {
+        match self {
+            BulkUserAction::Deactivate => "deactivate",
+            BulkUserAction::Activate => "activate",
+            BulkUserAction::SetRole(_) => "set_role",
+        }
+    }
+
+    /// `$set` for the new state, apart from `updated_at`.
+    fn set_doc(&self) -> bson::Document {
+        match self {
+            BulkUserAction::Deactivate => doc! { "active": false },
+            BulkUserAction::Activate => doc! { "active": true },
+            BulkUserAction::SetRole(role) => doc! { "role": role },
+        }
     }
 
-    Ok(StatusCode::NO_CONTENT)
+    /// Whether `user` is already in the state this action sets.
+    fn is_noop(&self, user: &User) -> bool {
+        match self {
+            BulkUserAction::Deactivate => !user.active,
+            BulkUserAction::Activate => user.active,
+            BulkUserAction::SetRole(role) => user.role == *role,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkUserRequest {
+    pub ids: Vec<String>,
+    pub action: BulkUserAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOutcome {
+    Applied,
+    Skipped,
+    NotFound,
+}
+
+/// What happened to one id, in the order the ids were sent.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BulkUserResult {
+    pub id: String,
+    pub outcome: BulkOutcome,
+    /// Why a user was skipped: `self`, `unchanged`, `anonymized`,
+    /// `last_admin` or `changed_concurrently`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkUserResponse {
+    pub results: Vec<BulkUserResult>,
+}
+
+/// How each id will be handled, worked out from the users as read.
+#[derive(Debug, Default, PartialEq)]
+struct BulkPlan {
+    /// Settled already: not found, or skipped with a reason.
+    settled: Vec<(String, BulkOutcome, Option<&'static str>)>,
+    /// Cannot take away anyone's admin powers; one `update_many`.
+    unguarded: Vec<String>,
+    /// Would demote or deactivate an active admin; `apply_guarded` each.
+    guarded: Vec<String>,
+}
+
+fn plan_bulk(action: &BulkUserAction, ids: &[String], users: &[User], actor: &str) -> BulkPlan {
+    let change = action.change();
+    let mut plan = BulkPlan::default();
+    for id in ids {
+        let Some(user) = users.iter().find(|u| u.id == *id) else {
+            plan.settled.push((id.clone(), BulkOutcome::NotFound, None));
+            continue;
+        };
+        let skip = if id == actor {
+            Some("self")
+        } else if action.is_noop(user) {
+            Some("unchanged")
+        } else if *action == BulkUserAction::Activate && user.anonymized_at.is_some() {
+            Some("anonymized")
+        } else {
+            None
+        };
+        match skip {
+            Some(reason) => plan.settled.push((id.clone(), BulkOutcome::Skipped, Some(reason))),
+            None if user.role == "admin" && user.active && change.removes_admin() => plan.guarded.push(id.clone()),
+            None => plan.unguarded.push(id.clone()),
+        }
+    }
+    plan
+}
+
+/// Applies `plan.unguarded` with one `update_many`, returning the ids it
+/// changed. The filter repeats the checks the plan made, so a user who
+/// became an active admin or was anonymized since is left alone.
+async fn apply_unguarded(
+    db: &Database,
+    action: &BulkUserAction,
+    ids: &[String],
+    now: DateTime<Utc>,
+) -> AppResult<Vec<String>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut filter = doc! { "_id": { "$in": ids } };
+    match action {
+        BulkUserAction::Activate => {
+            filter.insert("anonymized_at", bson::Bson::Null);
+        }
+        BulkUserAction::SetRole(role) if role == "admin" => {}
+        _ => {
+            filter.insert("$nor", vec![doc! { "role": "admin", "active": { "$ne": false } }]);
+        }
+    }
+    let mut set = action.set_doc();
+    set.insert("updated_at", to_bson_date(now));
+    let users = db.collection::<User>(USERS);
+    let result = users.update_many(filter, doc! { "$set": set }, None).await?;
+    if result.matched_count == ids.len() as u64 {
+        return Ok(ids.to_vec());
+    }
+    // Some changed in between; report only those now in the new state.
+    let mut settled = action.set_doc();
+    settled.insert("_id", doc! { "$in": ids });
+    let changed = users.distinct("_id", settled, None).await?;
+    Ok(changed.into_iter().filter_map(|id| id.as_str().map(str::to_string)).collect())
+}
+
+/// POST /api/admin/users/bulk — applies one action to many users, with the
+/// same rules as the single-user endpoints: your own account is skipped, and
+/// so is any change that would leave no active admin. Users the change
+/// cannot demote are updated together; active admins one at a time, each
+/// guarded like `PUT /api/admin/users/:id/role`.
+pub async fn admin_bulk_update_users(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<BulkUserRequest>,
+) -> AppResult<Json<BulkUserResponse>> {
+    let mut ids: Vec<String> = Vec::with_capacity(payload.ids.len());
+    for id in payload.ids {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    if ids.is_empty() {
+        return Err(FieldError::new("ids", "required", "ids must list at least one user").into());
+    }
+    if ids.len() > BULK_USERS_MAX {
+        return Err(FieldError::new("ids", "too_many", format!("at most {BULK_USERS_MAX} users per request")).into());
+    }
+    if let BulkUserAction::SetRole(role) = &payload.action {
+        validate_role(role)?;
+    }
+    let action = payload.action;
+
+    let cursor = state.db.collection::<User>(USERS).find(doc! { "_id": { "$in": &ids } }, None).await?;
+    let users = collect(cursor).await?;
+    let plan = plan_bulk(&action, &ids, &users, &claims.sub);
+
+    let mut outcomes: HashMap<String, (BulkOutcome, Option<&'static str>)> =
+        plan.settled.into_iter().map(|(id, outcome, reason)| (id, (outcome, reason))).collect();
+    let changed = apply_unguarded(&state.db, &action, &plan.unguarded, state.clock.now()).await?;
+    for id in plan.unguarded {
+        let outcome = if changed.contains(&id) {
+            (BulkOutcome::Applied, None)
+        } else {
+            (BulkOutcome::Skipped, Some("changed_concurrently"))
+        };
+        outcomes.insert(id, outcome);
+    }
+    for id in plan.guarded {
+        let outcome = match apply_guarded_txn(&state, &id, action.change()).await {
+            Ok(()) => (BulkOutcome::Applied, None),
+            Err(AppError::LastAdmin) => (BulkOutcome::Skipped, Some("last_admin")),
+            Err(AppError::NotFound) => (BulkOutcome::NotFound, None),
+            Err(e) => return Err(e),
+        };
+        outcomes.insert(id, outcome);
+    }
+
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        let (outcome, reason) = outcomes[&id];
+        if outcome == BulkOutcome::Applied {
+            state.user_cache.invalidate(&id).await;
+            let mut details = doc! { "user_id": &id, "action": action.name() };
+            if let BulkUserAction::SetRole(role) = &action {
+                details.insert("role", role);
+            }
+            audit::record(&state.db, &claims, audit::USER_BULK_UPDATE, details).await;
+        }
+        results.push(BulkUserResult { id, outcome, reason });
+    }
+    let applied = results.iter().filter(|r| r.outcome == BulkOutcome::Applied).count();
+    tracing::info!("Bulk {} applied to {applied} of {} user(s) by {}", action.name(), results.len(), claims.sub);
+    Ok(Json(BulkUserResponse { results }))
+}
+
+/// Collections holding nothing but one user's own data, with the field
+/// naming the user. `anonymize` deletes their documents outright.
+const PERSONAL_COLLECTIONS: &[(&str, &str)] = &[
+    (API_KEYS, "owner_id"),
+    ("login_events", "user_id"),
+    (NOTIFICATIONS, "recipient_id"),
+    (SAVED_VIEWS, "owner_id"),
+    ("feeds", "user_id"),
+    ("weather_locations", "user_id"),
+];
+
+#[derive(Debug, Serialize)]
+pub struct AnonymizeUserResponse {
+    pub user: UserPublic,
+    /// Documents deleted, by collection.
+    pub deleted: BTreeMap<String, u64>,
+}
+
+/// Deletes the documents in `PERSONAL_COLLECTIONS` belonging to `id`, plus
+/// the alerts and observations cached for their weather locations.
+async fn erase_personal_data(
+    db: &Database,
+    id: &str,
+    session: &mut ClientSession,
+) -> AppResult<BTreeMap<String, u64>> {
+    let locations: Vec<String> = db
+        .collection::<bson::Document>("weather_locations")
+        .distinct_with_session("_id", doc! { "user_id": id }, None, &mut *session)
+        .await?
+        .into_iter()
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect();
+    let mut deleted = BTreeMap::new();
+    let owned = PERSONAL_COLLECTIONS.iter().map(|&(collection, field)| (collection, doc! { field: id }));
+    let cached = ["weather_alerts", "weather_observations"]
+        .into_iter()
+        .map(|collection| (collection, doc! { "location_id": { "$in": &locations } }));
+    for (collection, filter) in owned.chain(cached) {
+        let result = db
+            .collection::<bson::Document>(collection)
+            .delete_many_with_session(filter, None, &mut *session)
+            .await?;
+        deleted.insert(collection.to_string(), result.deleted_count);
+    }
+    Ok(deleted)
+}
+
+/// POST /api/admin/users/:id/anonymize — erases a departed user's personal
+/// data for good. The account keeps its id, so tasks, notes and
+/// `created_by` still point at it, but its email and username become
+/// `anonymized_username`/`anonymized_email` placeholders, it is deactivated
+/// and cannot be reactivated, and everything in `PERSONAL_COLLECTIONS` is
+/// deleted. Credentials live in Keycloak and must be removed there.
+pub async fn admin_anonymize_user(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<AnonymizeUserResponse>> {
+    if claims.sub == id {
+        return Err(AppError::BadRequest("Cannot anonymize your own account".into()));
+    }
+    // The account and its data go together, so a failure leaves neither
+    // half-erased.
+    let deleted = state
+        .repos
+        .txn
+        .with_txn(|session| {
+            let (db, id, now) = (state.db.clone(), id.clone(), state.clock.now());
+            Box::pin(async move {
+                let store = MongoAdminStore::new(&db, session, now);
+                apply_guarded(&store, &id, AdminChange::Anonymize).await?;
+                erase_personal_data(&db, &id, store.into_session()).await
+            })
+        })
+        .await?;
+    state.user_cache.invalidate(&id).await;
+
+    let details = doc! { "user_id": &id };
+    audit::record(&state.db, &claims, audit::USER_ANONYMIZE, details).await;
+    tracing::info!("User {} anonymized by {}", id, claims.sub);
+    Ok(Json(AnonymizeUserResponse { user: load_user(&state, &id).await?.into(), deleted }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str, role: &str) -> User {
+        User {
+            id: id.to_string(),
+            email: format!("{id}@example.com"),
+            email_verified: true,
+            username: id.to_string(),
+            role: role.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            last_login_at: None,
+            active: true,
+            preferences: Default::default(),
+            active_workspace_id: None,
+            anonymized_at: None,
+            digest_sent_on: None,
+        }
+    }
+
+    #[test]
+    fn user_tasks_default_to_open_assigned_ones() {
+        let workflow = Workflow::default();
+        let q = |status: Option<&str>, relation: Option<&str>| AdminUserTasksQuery {
+            status: status.map(Into::into),
+            relation: relation.map(Into::into),
+        };
+        assert_eq!(
+            q(None, None).to_filter("u1", &workflow).unwrap(),
+            doc! { "assignee_id": "u1", "status": { "$in": ["todo", "in_progress"] } }
+        );
+        assert_eq!(q(Some(""), Some("created")).to_filter("u1", &workflow).unwrap(), doc! { "created_by": "u1" });
+        assert_eq!(
+            q(Some("done"), None).to_filter("u1", &workflow).unwrap(),
+            doc! { "assignee_id": "u1", "status": { "$in": ["done"] } }
+        );
+        let codes: Vec<_> = q(Some("nope"), Some("watching"))
+            .to_filter("u1", &workflow)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.code)
+            .collect();
+        assert_eq!(codes, ["invalid_relation", "invalid_status"]);
+    }
+
+    /// What the fake transactions have committed.
+    #[derive(Clone, Default)]
+    struct FakeDb {
+        users: HashMap<String, User>,
+        guard: u64,
+    }
+
+    struct FakeStore {
+        committed: Mutex<FakeDb>,
+    }
+
+    impl FakeStore {
+        fn with(users: Vec<User>) -> Self {
+            let users = users.into_iter().map(|u| (u.id.clone(), u)).collect();
+            Self { committed: Mutex::new(FakeDb { users, guard: 0 }) }
+        }
+
+        async fn admins(&self) -> usize {
+            self.committed.lock().await.users.values().filter(|u| u.role == "admin" && u.active).count()
+        }
+
+        /// `apply_guarded` in a fake transaction, retried on write conflicts
+        /// the way `with_txn` retries them.
+        async fn apply_guarded(&self, id: &str, change: AdminChange) -> AppResult<User> {
+            loop {
+                let txn = FakeTxn::begin(self).await;
+                let previous = apply_guarded(&txn, id, change.clone()).await?;
+                if txn.commit().await {
+                    return Ok(previous);
+                }
+            }
+        }
+    }
+
+    /// A transaction with MongoDB's snapshot isolation: it reads and writes
+    /// its own copy of the data, and its commit fails if it bumped the guard
+    /// after another transaction committed a bump. Yields between every step
+    /// so concurrent transactions interleave.
+    struct FakeTxn<'a> {
+        store: &'a FakeStore,
+        snapshot_guard: u64,
+        view: Mutex<FakeDb>,
+        touched: Mutex<Vec<String>>,
+    }
+
+    impl<'a> FakeTxn<'a> {
+        async fn begin(store: &'a FakeStore) -> Self {
+            let view = store.committed.lock().await.clone();
+            Self { store, snapshot_guard: view.guard, view: Mutex::new(view), touched: Mutex::default() }
+        }
+
+        async fn commit(self) -> bool {
+            let mut committed = self.store.committed.lock().await;
+            let view = self.view.into_inner();
+            if view.guard != self.snapshot_guard && committed.guard != self.snapshot_guard {
+                return false;
+            }
+            for id in self.touched.into_inner() {
+                match view.users.get(&id) {
+                    Some(user) => committed.users.insert(id, user.clone()),
+                    None => committed.users.remove(&id),
+                };
+            }
+            committed.guard = committed.guard.max(view.guard);
+            true
+        }
+    }
+
+    impl AdminStore for FakeTxn<'_> {
+        async fn apply(&self, id: &str, change: &AdminChange) -> AppResult<Option<User>> {
+            tokio::task::yield_now().await;
+            let users = &mut self.view.lock().await.users;
+            let previous = users.get(id).cloned();
+            match change {
+                AdminChange::SetRole(role) => {
+                    if let Some(u) = users.get_mut(id) { u.role = role.clone(); }
+                }
+                AdminChange::SetActive(active) => {
+                    if let Some(u) = users.get_mut(id) { u.active = *active; }
+                }
+                AdminChange::Anonymize => {
+                    if let Some(u) = users.get_mut(id) {
+                        u.username = anonymized_username(id);
+                        u.active = false;
+                    }
+                }
+                AdminChange::Delete => { users.remove(id); }
+            }
+            self.touched.lock().await.push(id.to_string());
+            tokio::task::yield_now().await;
+            Ok(previous)
+        }
+
+        async fn restore(&self, user: &User) -> AppResult<()> {
+            self.view.lock().await.users.insert(user.id.clone(), user.clone());
+            Ok(())
+        }
+
+        async fn lock_admins(&self) -> AppResult<()> {
+            tokio::task::yield_now().await;
+            self.view.lock().await.guard += 1;
+            Ok(())
+        }
+
+        async fn count_active_admins(&self) -> AppResult<u64> {
+            tokio::task::yield_now().await;
+            let view = self.view.lock().await;
+            Ok(view.users.values().filter(|u| u.role == "admin" && u.active).count() as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn demoting_the_only_admin_is_refused_and_rolled_back() {
+        let store = FakeStore::with(vec![user("a", "admin"), user("u", "user")]);
+        let err = store.apply_guarded("a", AdminChange::SetRole("user".into())).await.unwrap_err();
+        assert!(matches!(err, AppError::LastAdmin));
+        assert_eq!(store.committed.lock().await.users["a"].role, "admin");
+    }
+
+    #[tokio::test]
+    async fn demoting_one_of_two_admins_is_allowed() {
+        let store = FakeStore::with(vec![user("a", "admin"), user("b", "admin")]);
+        store.apply_guarded("a", AdminChange::SetRole("user".into())).await.unwrap();
+        assert_eq!(store.admins().await, 1);
+    }
+
+    #[tokio::test]
+    async fn deleting_or_deactivating_the_last_admin_is_refused() {
+        let store = FakeStore::with(vec![user("a", "admin")]);
+        assert!(store.apply_guarded("a", AdminChange::Delete).await.is_err());
+        assert!(store.apply_guarded("a", AdminChange::SetActive(false)).await.is_err());
+        assert!(store.apply_guarded("a", AdminChange::Anonymize).await.is_err());
+        assert_eq!(store.admins().await, 1);
+        assert_eq!(store.committed.lock().await.users["a"].username, "a");
+    }
+
+    #[tokio::test]
+    async fn concurrent_demotions_never_leave_zero_admins() {
+        let store = FakeStore::with(vec![user("a", "admin"), user("b", "admin")]);
+        let (ra, rb) = tokio::join!(
+            store.apply_guarded("a", AdminChange::SetRole("user".into())),
+            store.apply_guarded("b", AdminChange::Delete),
+        );
+        assert!(ra.is_err() || rb.is_err(), "both demotions must not succeed");
+        assert_eq!(store.admins().await, 1);
+    }
+
+    #[test]
+    fn bulk_plans_skip_self_and_no_ops_and_guard_admins() {
+        let mut inactive = user("off", "user");
+        inactive.active = false;
+        let mut erased = user("gone", "user");
+        erased.active = false;
+        erased.anonymized_at = Some(Utc::now());
+        let users =
+            vec![user("me", "admin"), user("a", "admin"), user("u", "user"), user("m", "manager"), inactive, erased];
+        let ids: Vec<String> = ["me", "a", "u", "m", "off", "gone", "nobody"].map(String::from).to_vec();
+        let settled =
+            |plan: &BulkPlan| plan.settled.iter().map(|(id, _, reason)| (id.clone(), *reason)).collect::<Vec<_>>();
+
+        let plan = plan_bulk(&BulkUserAction::Deactivate, &ids, &users, "me");
+        assert_eq!(plan.guarded, ["a"]);
+        assert_eq!(plan.unguarded, ["u", "m"]);
+        assert_eq!(
+            settled(&plan),
+            [
+                ("me".into(), Some("self")),
+                ("off".into(), Some("unchanged")),
+                ("gone".into(), Some("unchanged")),
+                ("nobody".into(), None)
+            ]
+        );
+        assert_eq!(plan.settled[3].1, BulkOutcome::NotFound);
+
+        let plan = plan_bulk(&BulkUserAction::Activate, &ids, &users, "me");
+        assert!(plan.guarded.is_empty());
+        assert_eq!(plan.unguarded, ["off"]);
+        assert!(settled(&plan).contains(&("gone".into(), Some("anonymized"))));
+
+        let plan = plan_bulk(&BulkUserAction::SetRole("user".into()), &ids, &users, "me");
+        assert_eq!(plan.guarded, ["a"]);
+        assert_eq!(plan.unguarded, ["m"]);
+        let plan = plan_bulk(&BulkUserAction::SetRole("admin".into()), &ids, &users, "me");
+        assert!(plan.guarded.is_empty());
+    }
+
+    #[test]
+    fn bulk_actions_parse_as_a_word_or_a_role() {
+        let parse = |v: serde_json::Value| serde_json::from_value::<BulkUserAction>(v);
+        assert_eq!(parse(serde_json::json!("deactivate")).unwrap(), BulkUserAction::Deactivate);
+        assert_eq!(parse(serde_json::json!({ "set_role": "user" })).unwrap(), BulkUserAction::SetRole("user".into()));
+        assert!(parse(serde_json::json!("delete")).is_err());
+    }
+
+    #[test]
+    fn delete_query_reassign_is_optional() {
+        let q: DeleteUserQuery = serde_json::from_str("{}").unwrap();
+        assert!(q.reassign_to.is_none());
+        let q: DeleteUserQuery = serde_json::from_str(r#"{"reassign_to":"u2"}"#).unwrap();
+        assert_eq!(q.reassign_to.as_deref(), Some("u2"));
+    }
+
+    #[tokio::test]
+    async fn missing_user_is_not_found() {
+        let store = FakeStore::with(vec![]);
+        let err = store.apply_guarded("x", AdminChange::Delete).await.unwrap_err();
+        assert!(matches!(err, AppError::NotFound));
+    }
+
+    #[test]
+    fn user_query_builds_a_literal_case_insensitive_search() {
+        assert_eq!(AdminUserQuery::default().to_filter().unwrap(), doc! {});
+
+        let q = AdminUserQuery { role: Some("admin".into()), q: Some(" a.b ".into()), active: Some(true) };
+        let pattern = doc! { "$regex": r"a\.b", "$options": "i" };
+        assert_eq!(
+            q.to_filter().unwrap(),
+            doc! {
+                "role": "admin",
+                "$or": [{ "email": pattern.clone() }, { "username": pattern }],
+                "active": { "$ne": false },
+            }
+        );
+
+        let q = AdminUserQuery { role: Some("root".into()), ..Default::default() };
+        assert_eq!(q.to_filter().unwrap_err().field, "role");
+    }
 }
@@ -0,0 +1,217 @@
+//! GET /api/admin/stats; see `models::admin_stats`.
+
+use std::{collections::BTreeMap, future::Future};
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bson::{doc, Bson, Document};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use mongodb::options::AggregateOptions;
+use tokio::time::Instant;
+
+use crate::{
+    db::{collect, Db, TASKS, USERS},
+    errors::AppResult,
+    handlers::auth::{AdminUser, AppState},
+    models::{
+        admin_stats::{
+            created_per_day, AdminStats, AdminStatsQuery, CollectionStats, NotesDistribution, TaskStats,
+            CREATED_PER_DAY_DAYS,
+        },
+        dates::to_bson_date,
+    },
+};
+
+/// How long one computation may take in all; whatever is unfinished by then
+/// is left out with a warning.
+const STATS_BUDGET: std::time::Duration = std::time::Duration::from_secs(10);
+/// How long computed stats are served before being gathered again.
+pub const ADMIN_STATS_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// GET /api/admin/stats — cached for `ADMIN_STATS_CACHE_TTL`; `?fresh=true`
+/// recomputes.
+pub async fn admin_stats(
+    AdminUser(_claims): AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<AdminStatsQuery>,
+) -> AppResult<Json<AdminStats>> {
+    let stats = state
+        .admin_stats_cache
+        .get_or_refresh(params.fresh, || async { AppResult::Ok(gather(&state.db, state.clock.now()).await) })
+        .await?;
+    Ok(Json(stats))
+}
+
+/// Gathers every section within `STATS_BUDGET`. Never fails: a section
+/// that errors or runs out of time is left out and explained in `warnings`.
+pub async fn gather(db: &Db, now: DateTime<Utc>) -> AdminStats {
+    let deadline = Instant::now() + STATS_BUDGET;
+    let mut warnings = Vec::new();
+    let (collections, tasks, users_by_role) = tokio::join!(
+        collection_stats(db, deadline),
+        task_stats(db, now, deadline),
+        users_by_role(db, deadline),
+    );
+    let collections = collections.unwrap_or_else(|(partial, warning)| {
+        warnings.push(warning);
+        partial
+    });
+    let tasks = tasks.map_err(|e| warnings.push(format!("task stats: {e}"))).ok();
+    let users_by_role = users_by_role.map_err(|e| warnings.push(format!("users by role: {e}"))).ok();
+    AdminStats { generated_at: now, collections, tasks, users_by_role, warnings }
+}
+
+/// Runs `fut`, giving up at `deadline`.
+async fn within<T>(deadline: Instant, fut: impl Future<Output = mongodb::error::Result<T>>) -> Result<T, String> {
+    match tokio::time::timeout_at(deadline, fut).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err("ran out of time".to_string()),
+    }
+}
+
+/// Aggregation options that stop the server at `deadline` too.
+fn until(deadline: Instant) -> AggregateOptions {
+    AggregateOptions::builder().max_time(deadline.saturating_duration_since(Instant::now())).build()
+}
+
+/// A count or size from a command reply, whatever its numeric type.
+fn number(doc: &Document, key: &str) -> Option<u64> {
+    match doc.get(key)? {
+        Bson::Int32(n) => u64::try_from(*n).ok(),
+        Bson::Int64(n) => u64::try_from(*n).ok(),
+        Bson::Double(n) if *n >= 0.0 => Some(*n as u64),
+        _ => None,
+    }
+}
+
+/// `collStats` reply fields as `CollectionStats`.
+fn from_coll_stats(name: &str, reply: &Document) -> CollectionStats {
+    CollectionStats {
+        name: name.to_string(),
+        documents: number(reply, "count").unwrap_or(0),
+        avg_document_bytes: number(reply, "avgObjSize"),
+        data_bytes: number(reply, "size"),
+        storage_bytes: number(reply, "storageSize"),
+        index_bytes: number(reply, "totalIndexSize"),
+        indexes: reply
+            .get_document("indexSizes")
+            .ok()
+            .map(|sizes| sizes.keys().filter_map(|k| Some((k.clone(), number(sizes, k)?))).collect()),
+    }
+}
+
+/// Every collection's size from `collStats`. Where that is not permitted
+/// (or not supported), falls back to estimated counts; the `Err` carries
+/// those with the reason.
+async fn collection_stats(db: &Db, deadline: Instant) -> Result<Vec<CollectionStats>, (Vec<CollectionStats>, String)> {
+    let mut names = match within(deadline, db.list_collection_names(None)).await {
+        Ok(names) => names,
+        Err(e) => return Err((vec![], format!("collections: {e}"))),
+    };
+    names.retain(|n| !n.starts_with("system."));
+    names.sort();
+    let mut stats = Vec::with_capacity(names.len());
+    let mut warning = None;
+    for name in names {
+        if warning.is_none() {
+            match within(deadline, db.run_command(doc! { "collStats": &name }, None)).await {
+                Ok(reply) => {
+                    stats.push(from_coll_stats(&name, &reply));
+                    continue;
+                }
+                Err(e) => warning = Some(format!("collStats unavailable, sizes left out: {e}")),
+            }
+        }
+        let documents = within(deadline, db.collection::<Document>(&name).estimated_document_count(None)).await;
+        stats.push(CollectionStats { name, documents: documents.unwrap_or(0), ..Default::default() });
+    }
+    match warning {
+        None => Ok(stats),
+        Some(warning) => Err((stats, warning)),
+    }
+}
+
+async fn task_stats(db: &Db, now: DateTime<Utc>, deadline: Instant) -> Result<TaskStats, String> {
+    let tasks = db.collection::<Document>(TASKS);
+    let totals = async {
+        let pipeline = [doc! { "$group": {
+            "_id": Bson::Null,
+            "count": { "$sum": 1 },
+            "avg_size": { "$avg": { "$bsonSize": "$$ROOT" } },
+        } }];
+        collect(tasks.aggregate(pipeline, until(deadline)).await?).await
+    };
+    let notes = async {
+        let pipeline = [doc! { "$group": {
+            "_id": { "$size": { "$ifNull": ["$notes", []] } },
+            "tasks": { "$sum": 1 },
+        } }];
+        collect(tasks.aggregate(pipeline, until(deadline)).await?).await
+    };
+    let today = now.date_naive();
+    let first_day = today - Duration::days(CREATED_PER_DAY_DAYS - 1);
+    let per_day = async {
+        let start = to_bson_date(first_day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+        let pipeline = [
+            doc! { "$match": { "created_at": { "$gte": start } } },
+            doc! { "$group": {
+                "_id": { "$dateToString": { "format": "%Y-%m-%d", "date": "$created_at" } },
+                "tasks": { "$sum": 1 },
+            } },
+        ];
+        collect(tasks.aggregate(pipeline, until(deadline)).await?).await
+    };
+    let (totals, notes, per_day) = within(deadline, async { tokio::try_join!(totals, notes, per_day) }).await?;
+
+    let totals = totals.first();
+    let notes: Vec<(u64, u64)> =
+        notes.iter().filter_map(|d| Some((number(d, "_id")?, number(d, "tasks")?))).collect();
+    let per_day: Vec<(NaiveDate, u64)> = per_day
+        .iter()
+        .filter_map(|d| Some((d.get_str("_id").ok()?.parse().ok()?, number(d, "tasks")?)))
+        .collect();
+    Ok(TaskStats {
+        count: totals.and_then(|t| number(t, "count")).unwrap_or(0),
+        avg_size_bytes: totals.and_then(|t| number(t, "avg_size")).unwrap_or(0),
+        notes_per_task: NotesDistribution::from_counts(&notes),
+        created_per_day: created_per_day(&per_day, today),
+    })
+}
+
+async fn users_by_role(db: &Db, deadline: Instant) -> Result<BTreeMap<String, u64>, String> {
+    let pipeline = [doc! { "$group": { "_id": "$role", "users": { "$sum": 1 } } }];
+    let users = db.collection::<Document>(USERS);
+    let groups = within(deadline, async { collect(users.aggregate(pipeline, until(deadline)).await?).await }).await?;
+    Ok(groups
+        .iter()
+        .filter_map(|d| Some((d.get_str("_id").unwrap_or("none").to_string(), number(d, "users")?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coll_stats_replies_of_any_numeric_type_are_read() {
+        let reply = doc! {
+            "ns": "mc.tasks",
+            "count": 1200_i32,
+            "avgObjSize": 812.5,
+            "size": 975_000_i64,
+            "storageSize": 409_600_i32,
+            "totalIndexSize": 65_536_i32,
+            "indexSizes": { "_id_": 36_864_i32, "workspace_id_1_status_1_created_at_-1": 28_672_i64 },
+        };
+        let stats = from_coll_stats("tasks", &reply);
+        assert_eq!(stats.documents, 1200);
+        assert_eq!(stats.avg_document_bytes, Some(812));
+        assert_eq!(stats.index_bytes, Some(65_536));
+        assert_eq!(stats.indexes.unwrap()["workspace_id_1_status_1_created_at_-1"], 28_672);
+
+        let bare = from_coll_stats("tasks", &doc! { "count": -1 });
+        assert_eq!(bare, CollectionStats { name: "tasks".into(), ..Default::default() });
+    }
+}
@@ -0,0 +1,136 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use serde::Deserialize;
+
+use crate::{
+    db::API_KEYS,
+    errors::{AppError, AppResult, FieldError},
+    extract::AppJson,
+    handlers::{
+        auth::{AppState, CurrentUser},
+        Created,
+    },
+    models::api_key::{ApiKey, ApiKeyPublic, CreatedApiKey, API_KEY_SCOPES},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+fn validate_scopes(scopes: &[String]) -> Result<(), FieldError> {
+    if scopes.is_empty() {
+        return Err(FieldError::new("scopes", "required", "at least one scope is required"));
+    }
+    if let Some(bad) = scopes.iter().find(|s| !API_KEY_SCOPES.contains(&s.as_str())) {
+        return Err(FieldError::new(
+            "scopes",
+            "invalid_scope",
+            format!("invalid scope '{}': must be one of {}", bad, API_KEY_SCOPES.join(", ")),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create_api_key(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateApiKeyRequest>,
+) -> AppResult<Created<CreatedApiKey>> {
+    let mut errors = Vec::new();
+    if payload.name.trim().is_empty() {
+        errors.push(FieldError::new("name", "required", "name must not be empty"));
+    }
+    if let Err(e) = validate_scopes(&payload.scopes) {
+        errors.push(e);
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let (clock, ids) = (state.clock.as_ref(), state.ids.as_ref());
+    let (api_key, secret) = ApiKey::generate(clock, ids, payload.name.trim().to_string(), claims.sub, payload.scopes);
+    state
+        .db
+        .collection::<ApiKey>(API_KEYS)
+        .insert_one(&api_key, None)
+        .await
+        .map_err(AppError::from)?;
+
+    let path = format!("/auth/api-keys/{}", api_key.id);
+    Ok(Created::at(path, CreatedApiKey { api_key: api_key.into(), key: secret }))
+}
+
+pub async fn get_api_key(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<ApiKeyPublic>> {
+    let key = state
+        .db
+        .collection::<ApiKey>(API_KEYS)
+        .find_one(doc! { "_id": &id, "owner_id": &claims.sub }, None)
+        .await
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(key.into()))
+}
+
+pub async fn list_api_keys(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<ApiKeyPublic>>> {
+    let collection = state.db.collection::<ApiKey>(API_KEYS);
+    let mut cursor = collection
+        .find(doc! { "owner_id": &claims.sub }, None)
+        .await
+        .map_err(AppError::from)?;
+
+    let mut keys = Vec::new();
+    while cursor.advance().await.map_err(AppError::from)? {
+        let key: ApiKey = cursor.deserialize_current().map_err(AppError::from)?;
+        keys.push(key.into());
+    }
+    Ok(Json(keys))
+}
+
+/// Revocation deletes the key outright; `require_auth` looks keys up on every
+/// request, so it stops working immediately.
+pub async fn delete_api_key(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    let result = state
+        .db
+        .collection::<ApiKey>(API_KEYS)
+        .delete_one(doc! { "_id": &id, "owner_id": &claims.sub }, None)
+        .await
+        .map_err(AppError::from)?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_scopes_accepts_known() {
+        assert!(validate_scopes(&["tasks:read".to_string(), "cti:write".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn validate_scopes_rejects_unknown_and_empty() {
+        assert!(validate_scopes(&["users:write".to_string()]).is_err());
+        assert!(validate_scopes(&[]).is_err());
+    }
+}
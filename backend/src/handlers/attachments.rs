@@ -0,0 +1,321 @@
+//! Files attached to task notes. `POST /api/tasks/:id/notes` also accepts
+//! `multipart/form-data`: a `note` part holding the text and up to
+//! `NOTE_ATTACHMENTS_MAX` `file` parts. Each file is stored in the
+//! `note_attachments` GridFS bucket and its id listed in the note's
+//! `attachment_ids`; it is deleted along with the note or its task.
+
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequest, Multipart, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bson::{doc, Bson};
+use futures_util::TryStreamExt;
+use mongodb::{
+    gridfs::GridFsBucket,
+    options::{GridFsBucketOptions, GridFsUploadOptions},
+};
+use tokio_util::{compat::FuturesAsyncReadCompatExt, io::ReaderStream};
+
+use crate::{
+    db::{Db, NOTE_ATTACHMENTS},
+    errors::{AppError, AppResult, FieldError},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AppState, CurrentUser},
+        tasks::AddNoteRequest,
+    },
+    models::{id::Id, task_export::AttachmentInfo},
+};
+
+/// Served with `Content-Disposition: inline` so frontends can show them in
+/// place; everything else downloads.
+const INLINE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+/// Longest stored file name, in characters.
+const FILENAME_MAX_CHARS: usize = 200;
+
+/// One `file` part, read into memory.
+#[derive(Debug)]
+pub struct Upload {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+/// The body of `POST /api/tasks/:id/notes`: JSON as before, or multipart with
+/// files. Fails with 422 `too_many` / `too_large` on field `file` when the
+/// files break `NOTE_ATTACHMENTS_MAX` or `NOTE_ATTACHMENT_MAX_BYTES`, and
+/// 422 `required` on `note` when there is no text part.
+#[derive(Debug)]
+pub struct NoteUpload {
+    pub note: AddNoteRequest,
+    pub files: Vec<Upload>,
+}
+
+#[async_trait]
+impl FromRequest<AppState> for NoteUpload {
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &AppState) -> Result<Self, Self::Rejection> {
+        if !is_multipart(req.headers()) {
+            let AppJson(note) = AppJson::from_request(req, state).await?;
+            return Ok(Self { note, files: Vec::new() });
+        }
+        let multipart = Multipart::from_request(req, state).await.map_err(|e| AppError::BadRequest(e.body_text()))?;
+        read_parts(multipart, state.config.note_attachments_max, state.config.note_attachment_max_bytes).await
+    }
+}
+
+fn is_multipart(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|essence| essence.trim().eq_ignore_ascii_case("multipart/form-data"))
+}
+
+fn multipart_error(e: axum::extract::multipart::MultipartError) -> AppError {
+    match e.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge,
+        _ => AppError::BadRequest(e.body_text()),
+    }
+}
+
+/// Reads the `note` and `file` parts, refusing a file as soon as it is one
+/// too many or grows past `max_bytes`.
+async fn read_parts(mut multipart: Multipart, max_files: usize, max_bytes: usize) -> AppResult<NoteUpload> {
+    let mut note = None;
+    let mut files = Vec::new();
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        match field.name() {
+            Some("note") => note = Some(field.text().await.map_err(multipart_error)?),
+            Some("file") => {
+                if files.len() >= max_files {
+                    let message = format!("a note may carry at most {max_files} files");
+                    return Err(FieldError::new("file", "too_many", message).into());
+                }
+                let filename = safe_filename(field.file_name().unwrap_or_default());
+                let content_type = field.content_type().unwrap_or("application/octet-stream").to_ascii_lowercase();
+                let mut data = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                    if data.len() + chunk.len() > max_bytes {
+                        let message = format!("{filename} is larger than {max_bytes} bytes");
+                        return Err(FieldError::new("file", "too_large", message).into());
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                files.push(Upload { filename, content_type, data: data.into() });
+            }
+            name => return Err(AppError::BadRequest(format!("unexpected part {:?}", name.unwrap_or_default()))),
+        }
+    }
+    let note = note.ok_or_else(|| FieldError::new("note", "required", "note is required"))?;
+    Ok(NoteUpload { note: AddNoteRequest { note }, files })
+}
+
+/// The last path segment of `name`, with anything but letters, digits,
+/// spaces, `.`, `-` and `_` replaced, so it is safe in a header.
+fn safe_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, ' ' | '.' | '-' | '_') { c } else { '_' })
+        .take(FILENAME_MAX_CHARS)
+        .collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "attachment".to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn bucket(db: &Db) -> GridFsBucket {
+    db.gridfs_bucket(GridFsBucketOptions::builder().bucket_name(NOTE_ATTACHMENTS.to_string()).build())
+}
+
+/// Stores `files` for note `note_id` of task `task_id`, returning their ids
+/// in order. If one fails, those already stored are removed again.
+pub async fn store_attachments(
+    state: &AppState,
+    ws: &str,
+    task_id: &str,
+    note_id: &str,
+    uploaded_by: &str,
+    files: Vec<Upload>,
+) -> AppResult<Vec<String>> {
+    let bucket = bucket(&state.db);
+    let mut ids = Vec::with_capacity(files.len());
+    for file in files {
+        let id = state.ids.new_id();
+        let metadata = doc! {
+            "workspace_id": ws,
+            "task_id": task_id,
+            "note_id": note_id,
+            "content_type": &file.content_type,
+            "uploaded_by": uploaded_by,
+        };
+        let options = GridFsUploadOptions::builder().metadata(metadata).build();
+        let (name, data) = (&file.filename, &file.data[..]);
+        if let Err(e) = bucket.upload_from_futures_0_3_reader_with_id(id.clone().into(), name, data, options).await {
+            discard_attachments(state, &ids).await;
+            return Err(e.into());
+        }
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+/// Deletes the files `ids`, logging (not failing on) any that can't be.
+/// For cleanup after the note referencing them is gone or was never saved.
+pub async fn discard_attachments(state: &AppState, ids: &[String]) {
+    let bucket = bucket(&state.db);
+    for id in ids {
+        if let Err(e) = bucket.delete(id.as_str().into()).await {
+            tracing::warn!(attachment_id = %id, "Could not delete a note attachment: {e}");
+        }
+    }
+}
+
+/// Deletes every file attached to the notes of task `task_id`.
+pub async fn delete_task_attachments(db: &Db, ws: &str, task_id: &str) -> AppResult<()> {
+    let bucket = bucket(db);
+    let filter = doc! { "metadata.workspace_id": ws, "metadata.task_id": task_id };
+    let files: Vec<_> = bucket.find(filter, None).await?.try_collect().await?;
+    for file in files {
+        bucket.delete(file.id).await?;
+    }
+    Ok(())
+}
+
+/// What is known about each file attached to the notes of task `task_id`,
+/// in upload order.
+pub async fn task_attachment_info(db: &Db, ws: &str, task_id: &str) -> AppResult<Vec<AttachmentInfo>> {
+    let filter = doc! { "metadata.workspace_id": ws, "metadata.task_id": task_id };
+    let files: Vec<_> = bucket(db).find(filter, None).await?.try_collect().await?;
+    let mut info: Vec<AttachmentInfo> = files
+        .into_iter()
+        .map(|file| {
+            let metadata = file.metadata.unwrap_or_default();
+            let field = |key: &str| metadata.get_str(key).ok().map(str::to_string);
+            AttachmentInfo {
+                id: match file.id {
+                    Bson::String(id) => id,
+                    other => other.to_string(),
+                },
+                note_id: field("note_id").unwrap_or_default(),
+                filename: file.filename.unwrap_or_default(),
+                content_type: field("content_type").unwrap_or_else(|| "application/octet-stream".to_string()),
+                length: file.length,
+                uploaded_by: field("uploaded_by"),
+                uploaded_at: file.upload_date.to_chrono(),
+            }
+        })
+        .collect();
+    info.sort_by_key(|a| a.uploaded_at);
+    Ok(info)
+}
+
+/// GET /api/tasks/:id/attachments/:attachment_id — a file attached to one of
+/// the task's notes. Raster images are served inline, anything else as a
+/// download. 404 unless a note of this task lists the file.
+pub async fn download_attachment(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath((task_id, attachment_id)): AppPath<(Id, Id)>,
+) -> AppResult<Response> {
+    let task = state.repos.tasks.find_by_id(claims.workspace()?, &task_id).await?.ok_or(AppError::NotFound)?;
+    if !task.notes.iter().any(|n| n.attachment_ids.iter().any(|a| **a == *attachment_id)) {
+        return Err(AppError::NotFound);
+    }
+    let bucket = bucket(&state.db);
+    let id = Bson::String(attachment_id.to_string());
+    let file = bucket.find(doc! { "_id": &id }, None).await?.try_next().await?.ok_or(AppError::NotFound)?;
+    let content_type = file
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get_str("content_type").ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let disposition = if INLINE_TYPES.contains(&content_type.as_str()) { "inline" } else { "attachment" };
+    let filename = safe_filename(file.filename.as_deref().unwrap_or_default());
+    let stream = bucket.open_download_stream(id).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_LENGTH, file.length.to_string()),
+            (header::CONTENT_DISPOSITION, format!("{disposition}; filename=\"{filename}\"")),
+        ],
+        Body::from_stream(ReaderStream::new(stream.compat())),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    /// A multipart body with `parts` of (name, file name, content).
+    async fn multipart(parts: &[(&str, Option<&str>, &str)]) -> Multipart {
+        let mut body = String::new();
+        for (name, filename, content) in parts {
+            body.push_str(&format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\""));
+            match filename {
+                Some(f) => body.push_str(&format!("; filename=\"{f}\"\r\nContent-Type: image/png\r\n\r\n")),
+                None => body.push_str("\r\n\r\n"),
+            }
+            body.push_str(content);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{BOUNDARY}--\r\n"));
+        let req = Request::builder()
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={BOUNDARY}"))
+            .body(Body::from(body))
+            .unwrap();
+        Multipart::from_request(req, &()).await.unwrap()
+    }
+
+    fn field_code(err: AppError) -> (String, &'static str) {
+        let AppError::Validation(errors) = err else { panic!("expected a 422, got {err:?}") };
+        (errors[0].field.clone(), errors[0].code)
+    }
+
+    #[tokio::test]
+    async fn parts_become_the_note_and_its_files() {
+        let parts = [("note", None, "see screenshot"), ("file", Some("shot.png"), "PNGDATA")];
+        let upload = read_parts(multipart(&parts).await, 5, 1024).await.unwrap();
+        assert_eq!(upload.note.note, "see screenshot");
+        assert_eq!(upload.files.len(), 1);
+        assert_eq!(upload.files[0].filename, "shot.png");
+        assert_eq!(upload.files[0].content_type, "image/png");
+        assert_eq!(&upload.files[0].data[..], b"PNGDATA");
+    }
+
+    #[tokio::test]
+    async fn files_are_limited_in_number_and_size() {
+        let parts = [("note", None, "x"), ("file", Some("a.png"), "1"), ("file", Some("b.png"), "2")];
+        let err = read_parts(multipart(&parts).await, 1, 1024).await.unwrap_err();
+        assert_eq!(field_code(err), ("file".into(), "too_many"));
+
+        let parts = [("note", None, "x"), ("file", Some("big.png"), "0123456789")];
+        let err = read_parts(multipart(&parts).await, 5, 9).await.unwrap_err();
+        assert_eq!(field_code(err), ("file".into(), "too_large"));
+
+        let err = read_parts(multipart(&[("file", Some("a.png"), "1")]).await, 5, 1024).await.unwrap_err();
+        assert_eq!(field_code(err), ("note".into(), "required"));
+        let err = read_parts(multipart(&[("other", None, "1")]).await, 5, 1024).await.unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(_)));
+    }
+
+    #[test]
+    fn file_names_are_made_header_safe() {
+        assert_eq!(safe_filename("screen shot (1).png"), "screen shot _1_.png");
+        assert_eq!(safe_filename("C:\\Users\\me\\report.pdf"), "report.pdf");
+        assert_eq!(safe_filename("../../etc/passwd"), "passwd");
+        assert_eq!(safe_filename("evil\"\r\n.txt"), "evil___.txt");
+        assert_eq!(safe_filename(".."), "attachment");
+        assert_eq!(safe_filename(""), "attachment");
+    }
+}
@@ -1,57 +1,191 @@
-use axum::{extract::State, Json};
-use bson::{doc, to_bson};
-use chrono::Utc;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Query, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    Json,
+};
+use bson::doc;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::DecodingKey;
 use mongodb::{
     options::{FindOneAndUpdateOptions, ReturnDocument},
     Database,
 };
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use serde_json::{json, Value};
+use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
 use crate::{
+    clock::{Clock, IdGen},
     config::AppConfig,
-    errors::{AppError, AppResult},
-    models::user::{User, UserPublic},
+    cti_cache::CtiCache,
+    db::{Repos, USERS},
+    errors::{mongo::is_duplicate_key, AppError, AppResult, AuthErrorKind},
+    events::EventBus,
+    graphql::MissionControlSchema,
+    handlers::{
+        invites::consume_invite,
+        logins::{peer_addr, record_login},
+        workspaces::join_default_workspace,
+    },
+    middleware::cookie::{clear_session_cookie, csrf_cookie, session_cookie},
+    models::{
+        admin_stats::AdminStats,
+        dashboard::DashboardSnapshot,
+        dates::to_bson_date,
+        user::{normalize_email, MeResponse, User, UserPublic},
+    },
+    notifier::Notifier,
     nws_client::NwsClient,
+    permissions::{has_permission, USERS_MANAGE},
+    stats_cache::{KeyedStatsCache, StatsCache},
+    webhooks::WebhookDispatcher,
+    user_cache::UserStatusCache,
+    workflow_cache::WorkflowCache,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: String,
     pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
     pub username: String,
     pub role: String,
     pub exp: usize,
+    /// Set only for API-key requests; `None` means a human session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
+    /// The workspace this request acts in and the caller's role there, filled
+    /// in by `require_workspace`; `None` on routes outside it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_role: Option<String>,
+}
+
+impl Claims {
+    /// The active workspace. Only routes behind `require_workspace` have
+    /// one; anywhere else this is a 403 rather than a silent default.
+    pub fn workspace(&self) -> AppResult<&str> {
+        self.workspace_id.as_deref().ok_or_else(|| {
+            tracing::error!("Workspace-scoped handler reached without require_workspace");
+            AppError::Forbidden
+        })
+    }
+
+    /// Whether the caller's role in the active workspace grants
+    /// `permission`. Task, CTI and share checks go by this, so a global role
+    /// carries no powers into workspaces where the caller is only a member.
+    pub fn workspace_permits(&self, permission: &str) -> bool {
+        self.workspace_role.as_deref().is_some_and(|role| has_permission(role, permission))
+    }
+}
+
+/// The caller's `Claims`, as put in the request extensions by `require_auth`.
+/// On a route left outside that middleware it rejects with 401 rather than
+/// panicking like a bare `Extension<Claims>` would.
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub Claims);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Claims>()
+            .cloned()
+            .map(CurrentUser)
+            .ok_or(AppError::Unauthorized(AuthErrorKind::Missing))
+    }
+}
+
+/// A `CurrentUser` who may manage users (admins); anyone else gets 403.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub Claims);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AdminUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let CurrentUser(claims) = CurrentUser::from_request_parts(parts, state).await?;
+        if !has_permission(&claims.role, USERS_MANAGE) {
+            return Err(AppError::Forbidden);
+        }
+        Ok(AdminUser(claims))
+    }
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
+    pub repos: Repos,
     pub config: AppConfig,
     pub nws_client: Arc<NwsClient>,
     pub ca_client: reqwest::Client,
     pub intermediate_cert_der: Arc<Vec<u8>>,
     pub keycloak_decoding_key: Arc<RwLock<DecodingKey>>,
+    pub user_cache: UserStatusCache,
+    pub dashboard_cache: KeyedStatsCache<DashboardSnapshot>,
+    pub admin_stats_cache: StatsCache<AdminStats>,
+    pub cti_cache: CtiCache,
+    pub workflow: WorkflowCache,
+    pub webhooks: WebhookDispatcher,
+    /// Task changes for the `events` subscribers; see `crate::events`.
+    pub events: EventBus,
+    pub notifier: Notifier,
+    pub graphql: MissionControlSchema,
+    /// `SystemClock` and `UuidIds` in production; see `crate::clock`.
+    pub clock: Arc<dyn Clock>,
+    pub ids: Arc<dyn IdGen>,
 }
 
 pub async fn me(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-) -> AppResult<Json<UserPublic>> {
-    let now = Utc::now();
-    let collection = state.db.collection::<User>("users");
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(params): Query<MeQuery>,
+) -> AppResult<Json<MeResponse>> {
+    let now = state.clock.now();
+    let collection = state.db.collection::<User>(USERS);
     let filter = doc! { "_id": &claims.sub };
+
+    let existing = collection.find_one(filter.clone(), None).await?;
+    // The upsert below would write the Keycloak profile straight back over
+    // the placeholders.
+    if existing.as_ref().is_some_and(|u| u.anonymized_at.is_some()) {
+        tracing::warn!("Rejected sign-in by anonymized user {}", claims.sub);
+        return Err(AppError::AccountInactive);
+    }
+
+    let mut initial_role = claims.role.clone();
+    if state.config.invite_only && existing.is_none() && !may_skip_invite(&state, &claims).await? {
+        let code = params.invite_code.as_deref().ok_or_else(|| {
+            tracing::warn!("Rejected first sign-in by {} without an invite code", claims.sub);
+            AppError::Forbidden
+        })?;
+        initial_role = consume_invite(&state, code, &claims.email, &claims.sub).await?.role;
+    }
+
+    let now_bson = to_bson_date(now);
     let update = doc! {
         "$set": {
-            "email": &claims.email,
+            "email": normalize_email(&claims.email),
+            "email_verified": claims.email_verified,
             "username": &claims.username,
-            "updated_at": to_bson(&now).unwrap(),
+            "updated_at": now_bson.clone(),
         },
         "$setOnInsert": {
-            "role": &claims.role,
-            "created_at": to_bson(&now).unwrap(),
+            "role": &initial_role,
+            "created_at": now_bson,
         }
     };
     let options = FindOneAndUpdateOptions::builder()
@@ -62,7 +196,234 @@ pub async fn me(
         .find_one_and_update(filter, update, options)
         .await?
         .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Upsert returned no document")))?;
+    if !user.active {
+        tracing::warn!("Rejected sign-in by inactive user {}", user.id);
+        return Err(AppError::AccountInactive);
+    }
+    let created = user.created_at == now;
+    let promoted = created && bootstrap_admin(&state, &user).await?;
+    if created {
+        join_default_workspace(&state, &user.id, if promoted { "admin" } else { &user.role }).await?;
+    }
+    state.user_cache.invalidate(&claims.sub).await;
+    let last_login_at = record_login(&state, &claims, &user, &headers, peer_addr(connect_info)).await?;
+    let preferences = user.preferences.clone();
     let mut user_public: UserPublic = user.into();
-    user_public.role = claims.role;
-    Ok(Json(user_public))
+    user_public.last_login_at = last_login_at;
+    if promoted {
+        user_public.role = "admin".to_string();
+    } else if !state.config.revalidate_users {
+        user_public.role = claims.role;
+    }
+    Ok(Json(MeResponse { user: user_public, preferences }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeQuery {
+    /// Required on first sign-in when the deployment is invite-only.
+    pub invite_code: Option<String>,
+}
+
+/// In invite-only mode the bootstrap admin (see `bootstrap_admin`) must still be
+/// able to get in, since nobody exists yet to mint them an invite.
+async fn may_skip_invite(state: &AppState, claims: &Claims) -> AppResult<bool> {
+    if state
+        .config
+        .admin_email
+        .as_deref()
+        .is_some_and(|e| e.eq_ignore_ascii_case(&claims.email))
+    {
+        return Ok(true);
+    }
+    let admins = state
+        .db
+        .collection::<User>(USERS)
+        .count_documents(doc! { "role": "admin" }, None)
+        .await
+        .map_err(AppError::from)?;
+    Ok(admins == 0)
+}
+
+/// Promotes a freshly provisioned user to admin when they match `ADMIN_EMAIL`,
+/// or when they are the first account to arrive on a deployment with no admin.
+/// The first-admin path is claimed through a unique marker document, so two
+/// simultaneous first logins cannot both be promoted.
+async fn bootstrap_admin(state: &AppState, user: &User) -> AppResult<bool> {
+    if user.role == "admin" {
+        return Ok(false);
+    }
+    let users = state.db.collection::<User>(USERS);
+
+    let matches_admin_email = state
+        .config
+        .admin_email
+        .as_deref()
+        .is_some_and(|e| e.eq_ignore_ascii_case(&user.email));
+
+    if !matches_admin_email {
+        let admins = users
+            .count_documents(doc! { "role": "admin" }, None)
+            .await
+            .map_err(AppError::from)?;
+        if admins > 0 {
+            return Ok(false);
+        }
+        let marker = doc! { "_id": "admin_bootstrap", "user_id": &user.id };
+        match state
+            .db
+            .collection::<bson::Document>("settings")
+            .insert_one(marker, None)
+            .await
+        {
+            Ok(_) => {}
+            Err(e) if is_duplicate_key(&e) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let now = to_bson_date(state.clock.now());
+    users
+        .update_one(
+            doc! { "_id": &user.id },
+            doc! { "$set": { "role": "admin", "updated_at": now } },
+            None,
+        )
+        .await
+        .map_err(AppError::from)?;
+
+    if matches_admin_email {
+        tracing::warn!("Bootstrap: promoted {} ({}) to admin via ADMIN_EMAIL", user.username, user.id);
+    } else {
+        tracing::warn!("Bootstrap: promoted first user {} ({}) to admin", user.username, user.id);
+    }
+    Ok(true)
+}
+
+/// POST /api/auth/session — exchanges the Keycloak bearer token for an
+/// HttpOnly session cookie so the browser no longer has to hold it.
+pub async fn create_session(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<(StatusCode, [(header::HeaderName, HeaderValue); 1])> {
+    if !state.config.auth_cookie_mode {
+        return Err(AppError::NotFound);
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| {
+            AppError::BadRequest("A session must be created with a bearer token".into())
+        })?;
+    let max_age = session_max_age(claims.exp, state.clock.now());
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, session_cookie(token, max_age))],
+    ))
+}
+
+/// Seconds until a token expiring at `exp` does, so the cookie goes with it.
+fn session_max_age(exp: usize, now: DateTime<Utc>) -> i64 {
+    (exp as i64 - now.timestamp()).max(0)
+}
+
+/// POST /api/auth/logout — clears the session cookie.
+pub async fn logout(
+    State(state): State<AppState>,
+) -> AppResult<(StatusCode, [(header::HeaderName, HeaderValue); 1])> {
+    if !state.config.auth_cookie_mode {
+        return Err(AppError::NotFound);
+    }
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, clear_session_cookie())],
+    ))
+}
+
+/// GET /api/auth/csrf — issues a fresh double-submit CSRF token, both as a
+/// cookie and in the body for the frontend to echo in `X-CSRF-Token`.
+pub async fn csrf_token(
+    State(state): State<AppState>,
+) -> AppResult<([(header::HeaderName, HeaderValue); 1], Json<Value>)> {
+    if !state.config.auth_cookie_mode {
+        return Err(AppError::NotFound);
+    }
+    let token = Uuid::new_v4().simple().to_string();
+    Ok((
+        [(header::SET_COOKIE, csrf_cookie(&token))],
+        Json(json!({ "csrf_token": token })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    use crate::clock::FakeClock;
+
+    fn claims(role: &str) -> Claims {
+        Claims {
+            sub: "u1".into(),
+            email: "u1@example.com".into(),
+            email_verified: true,
+            username: "u1".into(),
+            role: role.into(),
+            exp: usize::MAX,
+            scopes: None,
+            auth_time: None,
+            workspace_id: None,
+            workspace_role: None,
+        }
+    }
+
+    /// Calls a route guarded by each extractor, with `claims` in the request
+    /// extensions as `require_auth` would leave them.
+    async fn status(path: &str, claims: Option<Claims>) -> StatusCode {
+        let app = Router::new()
+            .route("/user", get(|CurrentUser(c): CurrentUser| async move { c.sub }))
+            .route("/admin", get(|AdminUser(c): AdminUser| async move { c.sub }));
+        let mut req = Request::builder().uri(path).body(Body::empty()).unwrap();
+        if let Some(claims) = claims {
+            req.extensions_mut().insert(claims);
+        }
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[test]
+    fn session_cookie_lives_until_the_token_expires() {
+        let clock = FakeClock::default();
+        let exp = (clock.now().timestamp() + 300) as usize;
+        assert_eq!(session_max_age(exp, clock.now()), 300);
+        clock.advance(chrono::Duration::seconds(299));
+        assert_eq!(session_max_age(exp, clock.now()), 1);
+        // Past expiry the cookie is cleared rather than given a negative age.
+        clock.advance(chrono::Duration::seconds(2));
+        assert_eq!(session_max_age(exp, clock.now()), 0);
+    }
+
+    #[tokio::test]
+    async fn missing_claims_are_a_401_not_a_panic() {
+        assert_eq!(status("/user", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("/admin", None).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn admin_user_requires_the_admin_role() {
+        assert_eq!(status("/user", Some(claims("user"))).await, StatusCode::OK);
+        assert_eq!(status("/admin", Some(claims("user"))).await, StatusCode::FORBIDDEN);
+        assert_eq!(status("/admin", Some(claims("admin"))).await, StatusCode::OK);
+    }
+
+    #[test]
+    fn workspace_permissions_follow_the_workspace_role() {
+        let member = Claims { workspace_role: Some("member".into()), ..claims("admin") };
+        assert!(!member.workspace_permits(crate::permissions::TASKS_ASSIGN));
+        let manager = Claims { workspace_role: Some("manager".into()), ..claims("user") };
+        assert!(manager.workspace_permits(crate::permissions::TASKS_ASSIGN));
+        assert!(!manager.workspace_permits(USERS_MANAGE));
+        assert!(!claims("admin").workspace_permits(USERS_MANAGE));
+    }
 }
@@ -0,0 +1,97 @@
+use async_compression::tokio::write::GzipEncoder;
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bson::doc;
+use serde::Deserialize;
+use tokio::io::AsyncWrite;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    audit,
+    backup::{self, RestoreReport},
+    errors::AppResult,
+    handlers::auth::{AdminUser, AppState},
+};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BackupQuery {
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RestoreQuery {
+    /// Replace each collection instead of merging the archive over it.
+    #[serde(default)]
+    pub wipe: bool,
+}
+
+/// GET /api/admin/backup?gzip= — streams the archive described in
+/// `backup` as it is read from the database. There are no password hashes
+/// to include or leave out; credentials live in Keycloak.
+pub async fn admin_backup(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<BackupQuery>,
+) -> AppResult<Response> {
+    let (writer, reader) = tokio::io::duplex(64 * 1024);
+    let db = state.db.clone();
+    let gzip = params.gzip;
+    tokio::spawn(async move {
+        let mut out: Box<dyn AsyncWrite + Unpin + Send> =
+            if gzip { Box::new(GzipEncoder::new(writer)) } else { Box::new(writer) };
+        let details = match backup::write_archive(&db, &mut out).await {
+            Ok(counts) => {
+                let counts: bson::Document = counts.into_iter().map(|(c, n)| (c, bson::Bson::Int64(n as i64))).collect();
+                doc! { "gzip": gzip, "counts": counts }
+            }
+            // Usually the client disconnecting; the download is incomplete
+            // and a restore would reject it.
+            Err(e) => {
+                tracing::warn!("Backup aborted: {e}");
+                doc! { "gzip": gzip, "error": e.to_string() }
+            }
+        };
+        audit::record(&db, &claims, audit::BACKUP, details).await;
+    });
+
+    let (content_type, ext) = if gzip { ("application/gzip", "ndjson.gz") } else { ("application/x-ndjson", "ndjson") };
+    let filename = format!("missioncontrol-backup-{}.{ext}", state.clock.now().format("%Y%m%dT%H%M%SZ"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response())
+}
+
+/// POST /api/admin/restore?wipe= — the body is an archive from
+/// `GET /api/admin/backup`, plain or gzipped, up to `RESTORE_MAX_BYTES`.
+/// Nothing live changes unless the whole archive reads back cleanly.
+pub async fn admin_restore(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<RestoreQuery>,
+    body: Body,
+) -> AppResult<Json<RestoreReport>> {
+    let limit = state.config.restore_max_bytes;
+    let input = backup::maybe_gunzip(backup::body_reader(body, limit), limit).await?;
+    let result = backup::restore(&state.db, &state.config, input, params.wipe).await;
+    let details = match &result {
+        Ok(report) => doc! { "wipe": params.wipe, "documents": report.documents as i64 },
+        Err(e) => doc! { "wipe": params.wipe, "error": e.to_string() },
+    };
+    audit::record(&state.db, &claims, audit::RESTORE, details).await;
+    // Even a failed restore may have replaced some CTI.
+    state.cti_cache.invalidate_all().await;
+    let report = result?;
+    tracing::info!(by = %claims.sub, documents = report.documents, wipe = report.wiped, "Restore finished");
+    Ok(Json(report))
+}
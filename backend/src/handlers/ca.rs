@@ -126,7 +126,7 @@ pub async fn ca_cert_status(
 
     let not_before_ts = cert.validity().not_before.timestamp();
     let not_after_ts = cert.validity().not_after.timestamp();
-    let now_ts = Utc::now().timestamp();
+    let now_ts = state.clock.now().timestamp();
     let days_remaining = not_after_ts
         .checked_sub(now_ts)
         .map(|diff| diff / 86400)
@@ -1,17 +1,43 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{Query, State},
+    http::{header, HeaderName, StatusCode},
     Json,
 };
 use bson::doc;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    clock::{Clock, IdGen},
+    cti_cache::CTI_MAX_AGE_SECS,
+    db::CtiRepo,
     errors::{AppError, AppResult},
-    handlers::auth::{AppState, Claims},
-    models::cti::{Category, CtiItem, CtiType},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AdminUser, AppState, CurrentUser},
+        Created,
+    },
+    models::{
+        cti::{Category, CtiItem, CtiItemDetail, CtiType, CtiTypeDetail},
+        id::Id,
+    },
 };
 
+type Cacheable<T> = ([(HeaderName, String); 1], Json<T>);
+
+/// A list response clients may reuse for `CTI_MAX_AGE_SECS`.
+fn cacheable<T>(body: T) -> Cacheable<T> {
+    ([(header::CACHE_CONTROL, format!("private, max-age={CTI_MAX_AGE_SECS}"))], Json(body))
+}
+
+/// The only element of `found`, or 404.
+fn one<T>(found: Vec<T>) -> AppResult<T> {
+    found.into_iter().next().ok_or(AppError::NotFound)
+}
+
+async fn category_name(repo: &dyn CtiRepo, ws: &str, id: &str) -> AppResult<Option<String>> {
+    Ok(repo.find_categories(ws, doc! { "_id": id }).await?.into_iter().next().map(|c| c.name))
+}
+
 // ── Query param structs ──────────────────────────────────────────────────────
 
 #[derive(Debug, Deserialize)]
@@ -46,43 +72,59 @@ pub struct CreateItemRequest {
 // ── Category handlers ────────────────────────────────────────────────────────
 
 pub async fn list_categories(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Cacheable<Vec<Category>>> {
+    let tree = state.cti_cache.tree(state.repos.cti.as_ref(), claims.workspace()?).await?;
+    Ok(cacheable(tree.categories.clone()))
+}
+
+pub async fn get_category(
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-) -> AppResult<Json<Vec<Category>>> {
-    let col = state.db.collection::<Category>("cti_categories");
-    let mut cursor = col.find(None, None).await.map_err(AppError::Database)?;
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<Category>> {
+    Ok(Json(find_category(state.repos.cti.as_ref(), claims.workspace()?, &id).await?))
+}
 
-    let mut items = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        items.push(cursor.deserialize_current().map_err(AppError::Database)?);
-    }
-    Ok(Json(items))
+async fn find_category(repo: &dyn CtiRepo, ws: &str, id: &str) -> AppResult<Category> {
+    one(repo.find_categories(ws, doc! { "_id": id }).await?)
 }
 
 pub async fn create_category(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateCategoryRequest>,
-) -> AppResult<(StatusCode, Json<Category>)> {
-    let category = Category::new(payload.name);
-    let col = state.db.collection::<Category>("cti_categories");
-    col.insert_one(&category, None)
-        .await
-        .map_err(AppError::Database)?;
-    Ok((StatusCode::CREATED, Json(category)))
+    AppJson(payload): AppJson<CreateCategoryRequest>,
+) -> AppResult<Created<Category>> {
+    let ws = claims.workspace()?;
+    let (clock, ids) = (state.clock.as_ref(), state.ids.as_ref());
+    let created = insert_category(state.repos.cti.as_ref(), clock, ids, ws, payload).await?;
+    state.cti_cache.invalidate(ws).await;
+    Ok(created)
+}
+
+async fn insert_category(
+    repo: &dyn CtiRepo,
+    clock: &dyn Clock,
+    ids: &dyn IdGen,
+    ws: &str,
+    payload: CreateCategoryRequest,
+) -> AppResult<Created<Category>> {
+    let mut category = Category::new(clock, ids, payload.name);
+    category.workspace_id = ws.to_string();
+    repo.insert_category(ws, &category).await?;
+    Ok(Created::at(format!("/cti/categories/{}", category.id), category))
 }
 
 pub async fn delete_category(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
+    AppPath(id): AppPath<Id>,
 ) -> AppResult<StatusCode> {
-    let col = state.db.collection::<Category>("cti_categories");
-    let result = col
-        .delete_one(doc! { "_id": &id }, None)
-        .await
-        .map_err(AppError::Database)?;
-    if result.deleted_count == 0 {
+    let ws = claims.workspace()?;
+    let deleted = state.repos.cti.delete_category(ws, &id).await?;
+    state.cti_cache.invalidate(ws).await;
+    if !deleted {
         return Err(AppError::NotFound);
     }
     Ok(StatusCode::NO_CONTENT)
@@ -91,47 +133,62 @@ pub async fn delete_category(
 // ── Type handlers ────────────────────────────────────────────────────────────
 
 pub async fn list_types(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Query(filter): Query<CategoryIdFilter>,
-) -> AppResult<Json<Vec<CtiType>>> {
-    let col = state.db.collection::<CtiType>("cti_types");
-    let mut cursor = col
-        .find(doc! { "category_id": &filter.category_id }, None)
-        .await
-        .map_err(AppError::Database)?;
-
-    let mut items = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        items.push(cursor.deserialize_current().map_err(AppError::Database)?);
-    }
-    Ok(Json(items))
+) -> AppResult<Cacheable<Vec<CtiType>>> {
+    let tree = state.cti_cache.tree(state.repos.cti.as_ref(), claims.workspace()?).await?;
+    Ok(cacheable(tree.types.iter().filter(|t| t.category_id == filter.category_id).cloned().collect()))
+}
+
+pub async fn get_type(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<CtiTypeDetail>> {
+    Ok(Json(find_type(state.repos.cti.as_ref(), claims.workspace()?, &id).await?))
+}
+
+async fn find_type(repo: &dyn CtiRepo, ws: &str, id: &str) -> AppResult<CtiTypeDetail> {
+    let cti_type = one(repo.find_types(ws, doc! { "_id": id }).await?)?;
+    let category_name = category_name(repo, ws, &cti_type.category_id).await?;
+    Ok(CtiTypeDetail { cti_type, category_name })
 }
 
 pub async fn create_type(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateTypeRequest>,
-) -> AppResult<(StatusCode, Json<CtiType>)> {
-    let cti_type = CtiType::new(payload.name, payload.category_id);
-    let col = state.db.collection::<CtiType>("cti_types");
-    col.insert_one(&cti_type, None)
-        .await
-        .map_err(AppError::Database)?;
-    Ok((StatusCode::CREATED, Json(cti_type)))
+    AppJson(payload): AppJson<CreateTypeRequest>,
+) -> AppResult<Created<CtiType>> {
+    let ws = claims.workspace()?;
+    let (clock, ids) = (state.clock.as_ref(), state.ids.as_ref());
+    let created = insert_type(state.repos.cti.as_ref(), clock, ids, ws, payload).await?;
+    state.cti_cache.invalidate(ws).await;
+    Ok(created)
+}
+
+async fn insert_type(
+    repo: &dyn CtiRepo,
+    clock: &dyn Clock,
+    ids: &dyn IdGen,
+    ws: &str,
+    payload: CreateTypeRequest,
+) -> AppResult<Created<CtiType>> {
+    let mut cti_type = CtiType::new(clock, ids, payload.name, payload.category_id);
+    cti_type.workspace_id = ws.to_string();
+    repo.insert_type(ws, &cti_type).await?;
+    Ok(Created::at(format!("/cti/types/{}", cti_type.id), cti_type))
 }
 
 pub async fn delete_type(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
+    AppPath(id): AppPath<Id>,
 ) -> AppResult<StatusCode> {
-    let col = state.db.collection::<CtiType>("cti_types");
-    let result = col
-        .delete_one(doc! { "_id": &id }, None)
-        .await
-        .map_err(AppError::Database)?;
-    if result.deleted_count == 0 {
+    let ws = claims.workspace()?;
+    let deleted = state.repos.cti.delete_type(ws, &id).await?;
+    state.cti_cache.invalidate(ws).await;
+    if !deleted {
         return Err(AppError::NotFound);
     }
     Ok(StatusCode::NO_CONTENT)
@@ -140,48 +197,274 @@ pub async fn delete_type(
 // ── Item handlers ────────────────────────────────────────────────────────────
 
 pub async fn list_items(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Query(filter): Query<TypeIdFilter>,
-) -> AppResult<Json<Vec<CtiItem>>> {
-    let col = state.db.collection::<CtiItem>("cti_items");
-    let mut cursor = col
-        .find(doc! { "type_id": &filter.type_id }, None)
-        .await
-        .map_err(AppError::Database)?;
-
-    let mut items = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        items.push(cursor.deserialize_current().map_err(AppError::Database)?);
-    }
-    Ok(Json(items))
+) -> AppResult<Cacheable<Vec<CtiItem>>> {
+    let tree = state.cti_cache.tree(state.repos.cti.as_ref(), claims.workspace()?).await?;
+    Ok(cacheable(tree.items.iter().filter(|i| i.type_id == filter.type_id).cloned().collect()))
+}
+
+pub async fn get_item(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<CtiItemDetail>> {
+    Ok(Json(find_item(state.repos.cti.as_ref(), claims.workspace()?, &id).await?))
+}
+
+async fn find_item(repo: &dyn CtiRepo, ws: &str, id: &str) -> AppResult<CtiItemDetail> {
+    let item = one(repo.find_items(ws, doc! { "_id": id }).await?)?;
+    let parent = repo.find_types(ws, doc! { "_id": &item.type_id }).await?.into_iter().next();
+    let category_name = match &parent {
+        Some(t) => category_name(repo, ws, &t.category_id).await?,
+        None => None,
+    };
+    Ok(CtiItemDetail { item, type_name: parent.map(|t| t.name), category_name })
 }
 
 pub async fn create_item(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateItemRequest>,
-) -> AppResult<(StatusCode, Json<CtiItem>)> {
-    let item = CtiItem::new(payload.name, payload.type_id);
-    let col = state.db.collection::<CtiItem>("cti_items");
-    col.insert_one(&item, None)
-        .await
-        .map_err(AppError::Database)?;
-    Ok((StatusCode::CREATED, Json(item)))
+    AppJson(payload): AppJson<CreateItemRequest>,
+) -> AppResult<Created<CtiItem>> {
+    let ws = claims.workspace()?;
+    let (clock, ids) = (state.clock.as_ref(), state.ids.as_ref());
+    let created = insert_item(state.repos.cti.as_ref(), clock, ids, ws, payload).await?;
+    state.cti_cache.invalidate(ws).await;
+    Ok(created)
+}
+
+async fn insert_item(
+    repo: &dyn CtiRepo,
+    clock: &dyn Clock,
+    ids: &dyn IdGen,
+    ws: &str,
+    payload: CreateItemRequest,
+) -> AppResult<Created<CtiItem>> {
+    let mut item = CtiItem::new(clock, ids, payload.name, payload.type_id);
+    item.workspace_id = ws.to_string();
+    repo.insert_item(ws, &item).await?;
+    Ok(Created::at(format!("/cti/items/{}", item.id), item))
 }
 
 pub async fn delete_item(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
+    AppPath(id): AppPath<Id>,
 ) -> AppResult<StatusCode> {
-    let col = state.db.collection::<CtiItem>("cti_items");
-    let result = col
-        .delete_one(doc! { "_id": &id }, None)
-        .await
-        .map_err(AppError::Database)?;
-    if result.deleted_count == 0 {
+    let ws = claims.workspace()?;
+    let deleted = state.repos.cti.delete_item(ws, &id).await?;
+    state.cti_cache.invalidate(ws).await;
+    if !deleted {
         return Err(AppError::NotFound);
     }
     Ok(StatusCode::NO_CONTENT)
 }
+
+// ── Cache ────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+pub struct CtiCacheRefreshed {
+    pub workspaces: usize,
+    pub generation: u64,
+}
+
+/// POST /api/admin/cache/cti/refresh — drops and reloads every workspace's
+/// cached taxonomy, for CTI changed outside this instance (another replica,
+/// the `seed` command, or MongoDB directly).
+pub async fn admin_refresh_cti_cache(
+    _: AdminUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<CtiCacheRefreshed>> {
+    state.cti_cache.invalidate_all().await;
+    let workspaces = state.cti_cache.warm(&state.db, state.repos.cti.as_ref()).await?;
+    Ok(Json(CtiCacheRefreshed { workspaces, generation: state.cti_cache.generation().await }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::{http::header, response::IntoResponse};
+    use bson::Document;
+
+    use super::*;
+    use crate::clock::{SystemClock, UuidIds};
+
+    const CLOCK: &SystemClock = &SystemClock;
+    const IDS: &UuidIds = &UuidIds;
+
+    /// In-memory `CtiRepo` that understands only `{ "_id": id }` filters.
+    #[derive(Default)]
+    struct FakeCti {
+        categories: Mutex<Vec<Category>>,
+        types: Mutex<Vec<CtiType>>,
+        items: Mutex<Vec<CtiItem>>,
+    }
+
+    fn by_id<'a>(filter: &'a Document) -> impl Fn(&str, &str, &str) -> bool + 'a {
+        move |ws, doc_ws, id| ws == doc_ws && filter.get_str("_id").is_ok_and(|want| want == id)
+    }
+
+    #[axum::async_trait]
+    impl CtiRepo for FakeCti {
+        async fn list_categories(&self, ws: &str) -> AppResult<Vec<Category>> {
+            Ok(self.categories.lock().unwrap().iter().filter(|c| c.workspace_id == ws).cloned().collect())
+        }
+        async fn find_categories(&self, ws: &str, filter: Document) -> AppResult<Vec<Category>> {
+            let hit = by_id(&filter);
+            Ok(self.categories.lock().unwrap().iter().filter(|c| hit(ws, &c.workspace_id, &c.id)).cloned().collect())
+        }
+        async fn find_types(&self, ws: &str, filter: Document) -> AppResult<Vec<CtiType>> {
+            let hit = by_id(&filter);
+            Ok(self.types.lock().unwrap().iter().filter(|t| hit(ws, &t.workspace_id, &t.id)).cloned().collect())
+        }
+        async fn find_items(&self, ws: &str, filter: Document) -> AppResult<Vec<CtiItem>> {
+            let hit = by_id(&filter);
+            Ok(self.items.lock().unwrap().iter().filter(|i| hit(ws, &i.workspace_id, &i.id)).cloned().collect())
+        }
+        async fn insert_category(&self, ws: &str, category: &Category) -> AppResult<()> {
+            crate::db::check_workspace(ws, &category.workspace_id)?;
+            self.categories.lock().unwrap().push(category.clone());
+            Ok(())
+        }
+        async fn insert_type(&self, ws: &str, cti_type: &CtiType) -> AppResult<()> {
+            crate::db::check_workspace(ws, &cti_type.workspace_id)?;
+            self.types.lock().unwrap().push(cti_type.clone());
+            Ok(())
+        }
+        async fn insert_item(&self, ws: &str, item: &CtiItem) -> AppResult<()> {
+            crate::db::check_workspace(ws, &item.workspace_id)?;
+            self.items.lock().unwrap().push(item.clone());
+            Ok(())
+        }
+        async fn delete_category(&self, ws: &str, id: &str) -> AppResult<bool> {
+            {
+                let mut categories = self.categories.lock().unwrap();
+                let before = categories.len();
+                categories.retain(|c| !(c.workspace_id == ws && c.id == id));
+                if categories.len() == before {
+                    return Ok(false);
+                }
+            }
+            let type_ids: Vec<String> = self
+                .types
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|t| t.workspace_id == ws && t.category_id == id)
+                .map(|t| t.id.clone())
+                .collect();
+            for type_id in type_ids {
+                self.delete_type(ws, &type_id).await?;
+            }
+            Ok(true)
+        }
+        async fn delete_type(&self, ws: &str, id: &str) -> AppResult<bool> {
+            let mut types = self.types.lock().unwrap();
+            let before = types.len();
+            types.retain(|t| !(t.workspace_id == ws && t.id == id));
+            if types.len() == before {
+                return Ok(false);
+            }
+            self.items.lock().unwrap().retain(|i| !(i.workspace_id == ws && i.type_id == id));
+            Ok(true)
+        }
+        async fn delete_item(&self, ws: &str, id: &str) -> AppResult<bool> {
+            let mut items = self.items.lock().unwrap();
+            let before = items.len();
+            items.retain(|i| !(i.workspace_id == ws && i.id == id));
+            Ok(items.len() < before)
+        }
+    }
+
+    /// The `Location` of a 201, which must be a versioned API path.
+    fn location<T: serde::Serialize>(created: Created<T>) -> String {
+        let response = created.into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+        location.strip_prefix("/api/v1").expect("versioned location").to_string()
+    }
+
+    #[tokio::test]
+    async fn created_cti_entries_can_be_fetched_from_their_location() {
+        let repo = FakeCti::default();
+        let ws = "ws1";
+
+        let payload = CreateCategoryRequest { name: "Hardware".into() };
+        let created = insert_category(&repo, CLOCK, IDS, ws, payload).await.unwrap();
+        let category_id = created.body.id.clone();
+        assert_eq!(location(created), format!("/cti/categories/{category_id}"));
+        assert_eq!(find_category(&repo, ws, &category_id).await.unwrap().name, "Hardware");
+
+        let payload = CreateTypeRequest { name: "Laptop".into(), category_id: category_id.clone() };
+        let created = insert_type(&repo, CLOCK, IDS, ws, payload).await.unwrap();
+        let type_id = created.body.id.clone();
+        assert_eq!(location(created), format!("/cti/types/{type_id}"));
+        assert_eq!(find_type(&repo, ws, &type_id).await.unwrap().cti_type.category_id, category_id);
+
+        let payload = CreateItemRequest { name: "Battery".into(), type_id: type_id.clone() };
+        let created = insert_item(&repo, CLOCK, IDS, ws, payload).await.unwrap();
+        let item_id = created.body.id.clone();
+        assert_eq!(location(created), format!("/cti/items/{item_id}"));
+        assert_eq!(find_item(&repo, ws, &item_id).await.unwrap().item.type_id, type_id);
+    }
+
+    #[tokio::test]
+    async fn type_and_item_gets_carry_their_parents_names() {
+        let repo = FakeCti::default();
+        let ws = "ws1";
+        let payload = CreateCategoryRequest { name: "Hardware".into() };
+        let category = insert_category(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+        let payload = CreateTypeRequest { name: "Laptop".into(), category_id: category.id.clone() };
+        let cti_type = insert_type(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+        let payload = CreateItemRequest { name: "Battery".into(), type_id: cti_type.id.clone() };
+        let item = insert_item(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+
+        let detail = find_type(&repo, ws, &cti_type.id).await.unwrap();
+        assert_eq!(detail.category_name.as_deref(), Some("Hardware"));
+        let json = serde_json::to_value(&detail).unwrap();
+        assert_eq!(json["name"], "Laptop");
+        assert_eq!(json["category_name"], "Hardware");
+
+        let detail = find_item(&repo, ws, &item.id).await.unwrap();
+        assert_eq!(detail.type_name.as_deref(), Some("Laptop"));
+        assert_eq!(detail.category_name.as_deref(), Some("Hardware"));
+
+        // An orphaned item still resolves, without names.
+        let payload = CreateItemRequest { name: "Stray".into(), type_id: "gone".into() };
+        let orphan = insert_item(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+        let detail = find_item(&repo, ws, &orphan.id).await.unwrap();
+        assert_eq!((detail.type_name, detail.category_name), (None, None));
+    }
+
+    #[tokio::test]
+    async fn cti_gets_are_404_for_unknown_ids_and_other_workspaces() {
+        let repo = FakeCti::default();
+        let payload = CreateCategoryRequest { name: "Hardware".into() };
+        let created = insert_category(&repo, CLOCK, IDS, "ws1", payload).await.unwrap();
+        assert!(matches!(find_category(&repo, "ws2", &created.body.id).await, Err(AppError::NotFound)));
+        assert!(matches!(find_category(&repo, "ws1", "missing").await, Err(AppError::NotFound)));
+        assert!(matches!(find_type(&repo, "ws1", "missing").await, Err(AppError::NotFound)));
+        assert!(matches!(find_item(&repo, "ws1", "missing").await, Err(AppError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn deleting_a_category_takes_its_types_and_items() {
+        let repo = FakeCti::default();
+        let ws = "ws1";
+        let payload = CreateCategoryRequest { name: "Hardware".into() };
+        let category = insert_category(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+        let payload = CreateTypeRequest { name: "Laptop".into(), category_id: category.id.clone() };
+        let cti_type = insert_type(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+        let payload = CreateItemRequest { name: "Battery".into(), type_id: cti_type.id.clone() };
+        let item = insert_item(&repo, CLOCK, IDS, ws, payload).await.unwrap().body;
+
+        assert!(!repo.delete_category("ws2", &category.id).await.unwrap());
+        assert!(repo.delete_category(ws, &category.id).await.unwrap());
+        assert!(matches!(find_type(&repo, ws, &cti_type.id).await, Err(AppError::NotFound)));
+        assert!(matches!(find_item(&repo, ws, &item.id).await, Err(AppError::NotFound)));
+        assert!(!repo.delete_item(ws, &item.id).await.unwrap());
+    }
+}
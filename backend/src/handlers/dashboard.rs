@@ -1,34 +1,232 @@
-use axum::{extract::State, Json};
-use bson::doc;
-use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bson::{doc, Document};
+use chrono::{Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use mongodb::Collection;
 
 use crate::{
-    errors::AppResult,
-    handlers::auth::{AppState, Claims},
+    db::{collect, escape_regex, TASKS, WORKSPACE_MEMBERS},
+    errors::{AppError, AppResult},
+    handlers::{
+        auth::{AppState, CurrentUser},
+        notifications::unread_count,
+    },
+    models::{
+        dates::to_bson_date,
+        dashboard::{
+            bson_to_u64, zero_fill, DashboardQuery, DashboardResponse, DashboardSnapshot,
+            MyWorkFacets, MyWorkResponse, TaskStats, TimeseriesQuery, TimeseriesResponse,
+            MY_WORK_GROUP_LIMIT,
+        },
+        user::User,
+    },
+    permissions::{has_permission, USERS_MANAGE},
 };
 
+async fn count(collection: &Collection<Document>, filter: Document) -> AppResult<u64> {
+    Ok(collection.count_documents(filter, None).await?)
+}
+
+/// Recomputes the dashboard counts shared by a workspace's members. Each is a
+/// separate count that can be answered from a workspace-prefixed index, run
+/// concurrently; the status counts are grouped and laid out by the workflow.
+async fn compute_snapshot(state: &AppState, ws: &str) -> AppResult<DashboardSnapshot> {
+    let tasks = state.db.collection::<Document>(TASKS);
+    let members = state.db.collection::<Document>(WORKSPACE_MEMBERS);
+    let now = state.clock.now();
+    let since = |days: i64| to_bson_date(now - Duration::days(days));
+    let (last_7, last_30) = (since(7), since(30));
+
+    let by_status = async {
+        let pipeline = vec![
+            doc! { "$match": { "workspace_id": ws } },
+            doc! { "$group": { "_id": "$status", "count": { "$sum": 1 } } },
+        ];
+        let mut counts = HashMap::new();
+        for group in collect(tasks.aggregate(pipeline, None).await?).await? {
+            if let (Ok(status), Some(n)) = (group.get_str("_id"), group.get("count").and_then(bson_to_u64)) {
+                counts.insert(status.to_string(), n);
+            }
+        }
+        Ok::<_, AppError>(counts)
+    };
+
+    let (workflow, by_status, total, unassigned, created_7, created_30, total_users) = tokio::try_join!(
+        state.workflow.get(&state.db),
+        by_status,
+        count(&tasks, doc! { "workspace_id": ws }),
+        count(&tasks, doc! { "workspace_id": ws, "assignee_id": null }),
+        count(&tasks, doc! { "workspace_id": ws, "created_at": { "$gte": last_7 } }),
+        count(&tasks, doc! { "workspace_id": ws, "created_at": { "$gte": last_30 } }),
+        count(&members, doc! { "workspace_id": ws }),
+    )?;
+
+    let mut stats = TaskStats {
+        total,
+        unassigned,
+        created_last_7_days: created_7,
+        created_last_30_days: created_30,
+        ..Default::default()
+    };
+    stats.set_status_counts(&workflow, &by_status);
+    Ok(DashboardSnapshot { tasks: stats, total_users })
+}
+
+/// GET /api/dashboard — shared counts come from a short-lived cache
+/// (`DASHBOARD_CACHE_TTL_SECONDS`); admins can bypass it with `?fresh=true`.
 pub async fn get_dashboard(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-) -> AppResult<Json<Value>> {
-    let users = state.db.collection::<bson::Document>("users");
-    let total_users = users.count_documents(None, None).await?;
-
-    let tasks = state.db.collection::<bson::Document>("tasks");
-    let todo = tasks.count_documents(doc! { "status": "todo" }, None).await?;
-    let in_progress = tasks.count_documents(doc! { "status": "in_progress" }, None).await?;
-    let done = tasks.count_documents(doc! { "status": "done" }, None).await?;
-
-    Ok(Json(json!({
-        "message": format!("Welcome, {}!", claims.email),
-        "user_id": claims.sub,
-        "stats": {
-            "total_users": total_users,
-            "tasks": {
-                "todo": todo,
-                "in_progress": in_progress,
-                "done": done,
-            }
+    Query(params): Query<DashboardQuery>,
+) -> AppResult<Json<DashboardResponse>> {
+    let is_admin = has_permission(&claims.role, USERS_MANAGE);
+    if params.fresh && !is_admin {
+        return Err(AppError::Forbidden);
+    }
+
+    let ws = claims.workspace()?;
+    let snapshot = state
+        .dashboard_cache
+        .get_or_refresh(ws, params.fresh, || compute_snapshot(&state, ws))
+        .await?;
+    let assigned_to_me = async {
+        Ok(state
+            .db
+            .collection::<Document>(TASKS)
+            .count_documents(doc! { "workspace_id": ws, "assignee_id": &claims.sub }, None)
+            .await?)
+    };
+    let (assigned_to_me, unread_count) = tokio::try_join!(assigned_to_me, unread_count(&state, &claims.sub))?;
+
+    Ok(Json(DashboardResponse {
+        message: format!("Welcome, {}!", claims.email),
+        user_id: claims.sub,
+        stats: snapshot.stats_for(assigned_to_me, is_admin),
+        unread_count,
+    }))
+}
+
+/// GET /api/dashboard/me — the caller's open tasks by status and the tasks
+/// that mention them, in one `$facet` aggregation.
+pub async fn get_my_work(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<MyWorkResponse>> {
+    let ws = claims.workspace()?;
+    let workflow = state.workflow.get(&state.db).await?;
+    let mention = doc! {
+        "workspace_id": ws,
+        "notes.note": {
+            "$regex": format!(r"(^|\W)@{}(\W|$)", escape_regex(&claims.username)),
+            "$options": "i",
+        }
+    };
+    let pipeline = vec![doc! {
+        "$facet": {
+            "open_by_status": [
+                { "$match": { "workspace_id": ws, "assignee_id": &claims.sub, "status": { "$in": workflow.open_keys() } } },
+                { "$group": {
+                    "_id": "$status",
+                    "count": { "$sum": 1 },
+                    "tasks": { "$topN": {
+                        "n": MY_WORK_GROUP_LIMIT,
+                        "sortBy": { "updated_at": -1 },
+                        "output": { "id": "$_id", "title": "$title" },
+                    } },
+                } },
+            ],
+            "mentioned": [
+                { "$match": mention.clone() },
+                { "$sort": { "updated_at": -1 } },
+                { "$limit": MY_WORK_GROUP_LIMIT },
+                { "$project": { "_id": 1, "title": 1 } },
+            ],
+            "mentioned_count": [ { "$match": mention }, { "$count": "n" } ],
+        }
+    }];
+
+    let mut cursor = state
+        .db
+        .collection::<Document>(TASKS)
+        .aggregate(pipeline, None)
+        .await?;
+    let facets = if cursor.advance().await? {
+        cursor.deserialize_current()?
+    } else {
+        Document::new()
+    };
+    let facets: MyWorkFacets =
+        bson::from_document(facets).map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+    Ok(Json(MyWorkResponse::new(facets, &workflow)))
+}
+
+/// GET /api/dashboard/timeseries — tasks created or completed per calendar
+/// day in the caller's timezone preference (UTC when unset).
+pub async fn get_timeseries(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(params): Query<TimeseriesQuery>,
+) -> AppResult<Json<TimeseriesResponse>> {
+    let field = params.date_field().map_err(AppError::Validation)?;
+
+    let tz: Tz = state
+        .db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": &claims.sub }, None)
+        .await?
+        .and_then(|u| u.preferences.timezone.parse().ok())
+        .unwrap_or(Tz::UTC);
+
+    let now = state.clock.now();
+    let today = now.with_timezone(&tz).date_naive();
+    let first_day = today - Duration::days(params.days as i64 - 1);
+    let start = tz
+        .from_local_datetime(&first_day.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| now - Duration::days(params.days as i64));
+    let start = to_bson_date(start);
+
+    let mut filter = doc! { "workspace_id": claims.workspace()?, field: { "$gte": start } };
+    if params.metric == "completed" {
+        let workflow = state.workflow.get(&state.db).await?;
+        filter.insert("status", doc! { "$in": workflow.terminal_keys() });
+    }
+    let day = doc! {
+        "$dateToString": {
+            "format": "%Y-%m-%d",
+            "date": format!("${field}"),
+            "timezone": tz.name(),
         }
-    })))
+    };
+    let pipeline = vec![
+        doc! { "$match": filter },
+        doc! { "$group": { "_id": day, "count": { "$sum": 1 } } },
+    ];
+
+    let mut cursor = state
+        .db
+        .collection::<Document>(TASKS)
+        .aggregate(pipeline, None)
+        .await?;
+    let mut counts = HashMap::new();
+    while cursor.advance().await? {
+        let bucket: Document = cursor.deserialize_current()?;
+        if let Ok(date) = bucket.get_str("_id") {
+            let count = bucket.get("count").and_then(bson_to_u64).unwrap_or(0);
+            counts.insert(date.to_string(), count);
+        }
+    }
+
+    Ok(Json(TimeseriesResponse {
+        metric: params.metric,
+        days: params.days,
+        timezone: tz.name().to_string(),
+        points: zero_fill(today, params.days, &counts),
+    }))
 }
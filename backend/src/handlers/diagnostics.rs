@@ -0,0 +1,15 @@
+//! GET /api/admin/diagnostics; see `diagnostics`.
+
+use axum::{extract::State, Json};
+
+use crate::{
+    diagnostics,
+    handlers::auth::{AdminUser, AppState},
+    models::diagnostics::DiagnosticReport,
+};
+
+/// GET /api/admin/diagnostics — the startup self-check, run again now.
+/// Always a 200; the report's `status` says how it went.
+pub async fn admin_diagnostics(_: AdminUser, State(state): State<AppState>) -> Json<DiagnosticReport> {
+    Json(diagnostics::run(&state.db, &state.config).await)
+}
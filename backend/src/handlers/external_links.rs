@@ -0,0 +1,66 @@
+use axum::{extract::State, Json};
+use bson::doc;
+
+use crate::{
+    errors::{AppError, AppResult},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AppState, CurrentUser},
+        tasks::authorize_task_edit,
+    },
+    link_title::fetch_title,
+    models::{
+        dates::{to_bson_date, to_stored_document},
+        id::Id,
+        task::{AddExternalLinkRequest, ExternalLink, Task},
+    },
+};
+
+/// POST /api/tasks/:id/external-links — attaches a URL. Without a `title`,
+/// and with `LINK_TITLE_FETCH` on, the page's own title is used if it can be
+/// read.
+pub async fn add_external_link(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    AppJson(payload): AppJson<AddExternalLinkRequest>,
+) -> AppResult<Json<Task>> {
+    let (url, mut title) = payload.validate().map_err(AppError::Validation)?;
+    authorize_task_edit(&state, &claims, &id).await?;
+    if title.is_none() && state.config.link_title_fetch {
+        title = fetch_title(&url).await;
+    }
+    let link = ExternalLink::new(state.clock.as_ref(), state.ids.as_ref(), url.into(), title, claims.sub.clone());
+    let link_bson = to_stored_document(&link).map_err(AppError::Internal)?;
+    let update = doc! {
+        "$push": { "links_external": link_bson },
+        "$set": { "updated_at": to_bson_date(link.created_at) },
+    };
+    let task = state
+        .repos
+        .tasks
+        .update_fields(claims.workspace()?, &id, doc! {}, update.into())
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(task))
+}
+
+/// DELETE /api/tasks/:id/external-links/:link_id
+pub async fn delete_external_link(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath((task_id, link_id)): AppPath<(Id, Id)>,
+) -> AppResult<Json<Task>> {
+    authorize_task_edit(&state, &claims, &task_id).await?;
+    let update = doc! {
+        "$pull": { "links_external": { "_id": &*link_id } },
+        "$set": { "updated_at": to_bson_date(state.clock.now()) },
+    };
+    let task = state
+        .repos
+        .tasks
+        .update_fields(claims.workspace()?, &task_id, doc! { "links_external._id": &*link_id }, update.into())
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(task))
+}
@@ -0,0 +1,27 @@
+use std::collections::BTreeMap;
+
+use axum::{extract::State, Json};
+
+use crate::{
+    features::Flag,
+    handlers::auth::{AdminUser, AppState, CurrentUser},
+    permissions::{has_permission, USERS_MANAGE},
+};
+
+/// GET /api/features — which features are on, so the frontend can show or
+/// hide them. Admin-only flags are listed for admins alone.
+pub async fn list_features(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> Json<BTreeMap<&'static str, bool>> {
+    Json(state.config.feature_flags.visible_to(has_permission(&claims.role, USERS_MANAGE)))
+}
+
+/// GET /api/admin/features — every flag's effective setting, and whether
+/// configuration set it or it is at its default.
+pub async fn admin_list_features(
+    AdminUser(_): AdminUser,
+    State(state): State<AppState>,
+) -> Json<BTreeMap<&'static str, Flag>> {
+    Json(state.config.feature_flags.all().clone())
+}
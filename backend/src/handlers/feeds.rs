@@ -9,7 +9,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     errors::{AppError, AppResult},
-    handlers::auth::{AppState, Claims},
+    extract::AppJson,
+    handlers::{
+        auth::{AppState, CurrentUser},
+        Created,
+    },
     models::feed::Feed,
 };
 
@@ -33,38 +37,53 @@ pub struct FeedItemsResponse {
 }
 
 pub async fn list_feeds(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
 ) -> AppResult<Json<Vec<Feed>>> {
     let collection = state.db.collection::<Feed>("feeds");
     let mut cursor = collection
         .find(doc! { "user_id": &claims.sub }, None)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
 
     let mut feeds = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        feeds.push(cursor.deserialize_current().map_err(AppError::Database)?);
+    while cursor.advance().await.map_err(AppError::from)? {
+        feeds.push(cursor.deserialize_current().map_err(AppError::from)?);
     }
     Ok(Json(feeds))
 }
 
 pub async fn add_feed(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Json(payload): Json<AddFeedRequest>,
-) -> AppResult<(StatusCode, Json<Feed>)> {
+    AppJson(payload): AppJson<AddFeedRequest>,
+) -> AppResult<Created<Feed>> {
     let feed = Feed::new(claims.sub, payload.name, payload.url);
     let collection = state.db.collection::<Feed>("feeds");
     collection
         .insert_one(&feed, None)
         .await
-        .map_err(AppError::Database)?;
-    Ok((StatusCode::CREATED, Json(feed)))
+        .map_err(AppError::from)?;
+    Ok(Created::at(format!("/feeds/{}", feed.id), feed))
+}
+
+pub async fn get_feed(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Feed>> {
+    let feed = state
+        .db
+        .collection::<Feed>("feeds")
+        .find_one(doc! { "_id": &id, "user_id": &claims.sub }, None)
+        .await
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(feed))
 }
 
 pub async fn delete_feed(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> AppResult<StatusCode> {
@@ -72,7 +91,7 @@ pub async fn delete_feed(
     let result = collection
         .delete_one(doc! { "_id": &id, "user_id": &claims.sub }, None)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
 
     if result.deleted_count == 0 {
         return Err(AppError::NotFound);
@@ -81,7 +100,7 @@ pub async fn delete_feed(
 }
 
 pub async fn get_feed_items(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> AppResult<Json<FeedItemsResponse>> {
@@ -89,7 +108,7 @@ pub async fn get_feed_items(
     let feed = collection
         .find_one(doc! { "_id": &id, "user_id": &claims.sub }, None)
         .await
-        .map_err(AppError::Database)?
+        .map_err(AppError::from)?
         .ok_or(AppError::NotFound)?;
 
     let response = reqwest::get(&feed.url)
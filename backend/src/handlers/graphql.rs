@@ -0,0 +1,19 @@
+use axum::{extract::State, Json};
+
+use crate::{
+    extract::AppJson,
+    graphql,
+    handlers::auth::{AppState, CurrentUser},
+};
+
+/// POST /api/graphql — runs one GraphQL operation as the caller, in their
+/// current workspace. Errors come back in the response's `errors` with a
+/// `code` extension matching the REST error codes, under a 200 status.
+pub async fn graphql_handler(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppJson(request): AppJson<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let request = graphql::request_data(request, &state, claims);
+    Json(state.graphql.execute(request).await)
+}
@@ -1,6 +1,91 @@
-use axum::Json;
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, Json};
+use bson::doc;
 use serde_json::{json, Value};
 
-pub async fn health_check() -> Json<Value> {
-    Json(json!({ "status": "ok", "service": "missoncontrol" }))
+use crate::{
+    errors::mongo::{classify, DbErrorClass},
+    handlers::auth::AppState,
+};
+
+const DB_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Identifies the running build so a deploy can be verified from outside.
+/// `GIT_SHA` is read from the build environment (see the Dockerfile).
+fn build_info() -> Value {
+    json!({
+        "service": "missoncontrol",
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": option_env!("GIT_SHA").unwrap_or("unknown"),
+    })
+}
+
+/// GET /health/live (and the legacy /health) — the process is up. Never
+/// touches dependencies, so a slow database cannot get the instance restarted.
+pub async fn health_live() -> Json<Value> {
+    let mut body = build_info();
+    body["status"] = "ok".into();
+    Json(body)
+}
+
+/// GET /health/ready — 200 only when MongoDB answers a ping, otherwise 503 so
+/// the load balancer stops routing here. Reports how long the ping took, or
+/// why it failed.
+pub async fn health_ready(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let started = Instant::now();
+    let ping = tokio::time::timeout(DB_PING_TIMEOUT, state.db.run_command(doc! { "ping": 1 }, None)).await;
+    let mongodb = match ping {
+        Ok(Ok(_)) => Ok(started.elapsed()),
+        Ok(Err(e)) => {
+            tracing::warn!("Readiness check: MongoDB ping failed: {e}");
+            Err(match classify(&e) {
+                DbErrorClass::Transient => "unreachable",
+                _ => "error",
+            })
+        }
+        Err(_) => {
+            tracing::warn!("Readiness check: MongoDB ping timed out after {DB_PING_TIMEOUT:?}");
+            Err("timeout")
+        }
+    };
+    let (status, body) = readiness(mongodb);
+    (status, Json(body))
+}
+
+fn readiness(mongodb: Result<Duration, &str>) -> (StatusCode, Value) {
+    let mut body = build_info();
+    let (status, overall, component) = match mongodb {
+        Ok(latency) => (StatusCode::OK, "ok", json!({ "status": "ok", "latency_ms": latency.as_millis() as u64 })),
+        Err(reason) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "unavailable",
+            json!({ "status": "down", "reason": reason }),
+        ),
+    };
+    body["status"] = overall.into();
+    body["components"] = json!({ "mongodb": component });
+    (status, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_when_mongo_answers() {
+        let (status, body) = readiness(Ok(Duration::from_millis(3)));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["components"]["mongodb"], json!({ "status": "ok", "latency_ms": 3 }));
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn unavailable_reports_the_failing_component() {
+        let (status, body) = readiness(Err("timeout"));
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["components"]["mongodb"], json!({ "status": "down", "reason": "timeout" }));
+    }
 }
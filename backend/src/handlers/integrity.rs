@@ -0,0 +1,267 @@
+use axum::{extract::State, Json};
+use bson::{doc, Bson, Document};
+use mongodb::{options::UpdateOptions, Collection};
+
+use crate::{
+    audit,
+    db::{collect, CTI_CATEGORIES, CTI_ITEMS, CTI_TYPES, TASKS, USERS},
+    errors::{AppError, AppResult, FieldError},
+    extract::AppJson,
+    handlers::auth::{AdminUser, AppState, Claims},
+    models::{
+        dates::to_bson_date,
+        integrity::{
+            IntegrityCheck, IntegrityFinding, IntegrityReport, RepairAction, RepairOutcome, RepairRequest,
+            RepairResponse, INTEGRITY_SAMPLE_SIZE,
+        },
+        workflow::Workflow,
+    },
+};
+
+/// `$lookup` of `from` documents whose `_id` is `local`, projected to their
+/// id so the join stays small.
+fn lookup_id(from: &str, local: &str, as_field: &str) -> Document {
+    doc! { "$lookup": {
+        "from": from,
+        "localField": local,
+        "foreignField": "_id",
+        "pipeline": [{ "$project": { "_id": 1 } }],
+        "as": as_field,
+    } }
+}
+
+/// Like `lookup_id`, but only matches CTI nodes in the task's own workspace.
+fn lookup_cti(from: &str, local: &str, as_field: &str) -> Document {
+    doc! { "$lookup": {
+        "from": from,
+        "let": { "id": format!("${local}"), "ws": "$workspace_id" },
+        "pipeline": [
+            { "$match": { "$expr": { "$and": [{ "$eq": ["$_id", "$$id"] }, { "$eq": ["$workspace_id", "$$ws"] }] } } },
+            { "$project": { "_id": 1 } },
+        ],
+        "as": as_field,
+    } }
+}
+
+/// Aggregation over `tasks` yielding one `{_id}` per problem `check` finds:
+/// a task id, or a note id for `OrphanedNoteAuthor`. Runs across every
+/// workspace.
+fn check_pipeline(check: IntegrityCheck, workflow: &Workflow) -> Vec<Document> {
+    match check {
+        IntegrityCheck::OrphanedAssignee => vec![
+            doc! { "$match": { "assignee_id": { "$type": "string" } } },
+            lookup_id(USERS, "assignee_id", "user"),
+            doc! { "$match": { "user": { "$size": 0 } } },
+            doc! { "$project": { "_id": 1 } },
+        ],
+        IntegrityCheck::OrphanedCti => vec![
+            doc! { "$match": { "cti": { "$type": "object" } } },
+            lookup_cti(CTI_CATEGORIES, "cti.category_id", "category"),
+            lookup_cti(CTI_TYPES, "cti.type_id", "type"),
+            lookup_cti(CTI_ITEMS, "cti.item_id", "item"),
+            doc! { "$match": { "$or": [
+                { "category": { "$size": 0 } },
+                { "type": { "$size": 0 } },
+                { "item": { "$size": 0 } },
+            ] } },
+            doc! { "$project": { "_id": 1 } },
+        ],
+        IntegrityCheck::InvalidStatus => {
+            let keys: Vec<&str> = workflow.statuses().iter().map(|s| s.key.as_str()).collect();
+            vec![
+                doc! { "$match": { "status": { "$nin": keys } } },
+                doc! { "$project": { "_id": 1 } },
+            ]
+        }
+        IntegrityCheck::OrphanedNoteAuthor => vec![
+            doc! { "$match": { "notes.0": { "$exists": true } } },
+            doc! { "$unwind": "$notes" },
+            lookup_id(USERS, "notes.author", "user"),
+            doc! { "$match": { "user": { "$size": 0 } } },
+            doc! { "$project": { "_id": "$notes._id" } },
+        ],
+    }
+}
+
+fn tasks(state: &AppState) -> Collection<Document> {
+    state.db.collection(TASKS)
+}
+
+/// Reads the `$count` stage's output, which is an int32 or int64.
+fn facet_count(result: &Document) -> u64 {
+    let first = result.get_array("count").ok().and_then(|c| c.first()).and_then(Bson::as_document);
+    match first.and_then(|d| d.get("n")) {
+        Some(Bson::Int32(n)) => *n as u64,
+        Some(Bson::Int64(n)) => *n as u64,
+        _ => 0,
+    }
+}
+
+fn ids_of(docs: &[Document]) -> Vec<String> {
+    docs.iter().filter_map(|d| d.get_str("_id").ok().map(str::to_string)).collect()
+}
+
+async fn run_check(state: &AppState, check: IntegrityCheck, workflow: &Workflow) -> AppResult<IntegrityFinding> {
+    let mut pipeline = check_pipeline(check, workflow);
+    pipeline.push(doc! { "$facet": {
+        "count": [{ "$count": "n" }],
+        "samples": [{ "$limit": INTEGRITY_SAMPLE_SIZE }],
+    } });
+    let result = collect(tasks(state).aggregate(pipeline, None).await?).await?;
+    let result = result.into_iter().next().unwrap_or_default();
+    let samples: Vec<Document> = result
+        .get_array("samples")
+        .map(|s| s.iter().filter_map(Bson::as_document).cloned().collect())
+        .unwrap_or_default();
+    Ok(IntegrityFinding { check, count: facet_count(&result), sample_ids: ids_of(&samples) })
+}
+
+/// GET /api/admin/integrity — counts and sample ids for each kind of
+/// dangling reference or invalid value in tasks, across all workspaces.
+pub async fn admin_integrity_report(
+    _: AdminUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<IntegrityReport>> {
+    let workflow = state.workflow.get(&state.db).await?;
+    let mut checks = Vec::new();
+    for check in IntegrityCheck::ALL {
+        checks.push(run_check(&state, check, &workflow).await?);
+    }
+    Ok(Json(IntegrityReport { total: checks.iter().map(|c| c.count).sum(), checks }))
+}
+
+/// 422 unless every `reassign` target exists: an active user, or a workflow
+/// status for `invalid_status`.
+async fn check_targets(state: &AppState, req: &RepairRequest, workflow: &Workflow) -> AppResult<()> {
+    let mut errors = Vec::new();
+    for (check, action) in req.repairs() {
+        let RepairAction::Reassign { to } = action else { continue };
+        if check == IntegrityCheck::InvalidStatus {
+            if workflow.get(to).is_none() {
+                errors.push(FieldError::new(check.as_str(), "unknown_status", format!("no workflow status '{to}'")));
+            }
+        } else if !state.repos.users.find_by_id(to).await?.is_some_and(|u| u.active) {
+            errors.push(FieldError::new(check.as_str(), "unknown_user", format!("no active user '{to}'")));
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(AppError::Validation(errors)) }
+}
+
+/// The `$set` fixing the tasks `check` found with `action`, stamped by
+/// `actor` at `now`; `None` for deletes.
+fn task_fix(check: IntegrityCheck, action: &RepairAction, actor: &str, now: Bson) -> Option<Document> {
+    let value = match action {
+        RepairAction::Null => Bson::Null,
+        RepairAction::Reassign { to } => to.as_str().into(),
+        RepairAction::Delete => return None,
+    };
+    let mut set = doc! { "updated_at": now.clone() };
+    match check {
+        IntegrityCheck::OrphanedAssignee => {
+            set.insert("assignee_id", value);
+            set.insert("assigned_by", actor);
+            set.insert("assigned_at", now);
+        }
+        IntegrityCheck::OrphanedCti => {
+            set.insert("cti", value);
+        }
+        IntegrityCheck::InvalidStatus => {
+            set.insert("status", value);
+            set.insert("status_changed_at", now);
+        }
+        IntegrityCheck::OrphanedNoteAuthor => {
+            set.insert("notes.$[orphan].author", value);
+        }
+    }
+    Some(set)
+}
+
+/// Applies `action` to the tasks or notes with `ids`, returning how many
+/// changed.
+async fn apply_repair(
+    state: &AppState,
+    claims: &Claims,
+    check: IntegrityCheck,
+    action: &RepairAction,
+    ids: Vec<String>,
+) -> AppResult<u64> {
+    let now = to_bson_date(state.clock.now());
+    if check == IntegrityCheck::OrphanedNoteAuthor {
+        let filter = doc! { "notes._id": { "$in": &ids } };
+        let update = match task_fix(check, action, &claims.sub, now.clone()) {
+            Some(set) => doc! { "$set": set },
+            None => doc! { "$pull": { "notes": { "_id": { "$in": &ids } } }, "$set": { "updated_at": now } },
+        };
+        // Only reassign uses the array filter; Mongo rejects unused ones.
+        let options = matches!(action, RepairAction::Reassign { .. })
+            .then(|| UpdateOptions::builder().array_filters(vec![doc! { "orphan._id": { "$in": &ids } }]).build());
+        tasks(state).update_many(filter, update, options).await?;
+        return Ok(ids.len() as u64);
+    }
+    let filter = doc! { "_id": { "$in": &ids } };
+    match task_fix(check, action, &claims.sub, now) {
+        Some(set) => Ok(tasks(state).update_many(filter, doc! { "$set": set }, None).await?.modified_count),
+        None => Ok(tasks(state).delete_many(filter, None).await?.deleted_count),
+    }
+}
+
+/// POST /api/admin/integrity/repair — re-runs each requested check and
+/// fixes what it finds. Every target is validated before anything changes.
+pub async fn admin_integrity_repair(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppJson(req): AppJson<RepairRequest>,
+) -> AppResult<Json<RepairResponse>> {
+    req.validate().map_err(AppError::Validation)?;
+    let workflow = state.workflow.get(&state.db).await?;
+    check_targets(&state, &req, &workflow).await?;
+
+    let mut repaired = Vec::new();
+    for (check, action) in req.repairs() {
+        let found = collect(tasks(&state).aggregate(check_pipeline(check, &workflow), None).await?).await?;
+        let ids = ids_of(&found);
+        let count = if ids.is_empty() { 0 } else { apply_repair(&state, &claims, check, action, ids).await? };
+        let mut details = doc! { "check": check.as_str(), "action": action.as_str(), "count": count as i64 };
+        if let RepairAction::Reassign { to } = action {
+            details.insert("to", to);
+        }
+        audit::record(&state.db, &claims, audit::INTEGRITY_REPAIR, details).await;
+        tracing::info!(check = check.as_str(), action = action.as_str(), count, "Integrity repair by {}", claims.sub);
+        repaired.push(RepairOutcome { check, action: action.as_str(), count });
+    }
+    Ok(Json(RepairResponse { repaired }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_status_check_matches_statuses_outside_the_workflow() {
+        let pipeline = check_pipeline(IntegrityCheck::InvalidStatus, &Workflow::default());
+        assert_eq!(pipeline[0], doc! { "$match": { "status": { "$nin": ["todo", "in_progress", "done"] } } });
+        let notes = check_pipeline(IntegrityCheck::OrphanedNoteAuthor, &Workflow::default());
+        assert_eq!(notes.last().unwrap(), &doc! { "$project": { "_id": "$notes._id" } });
+    }
+
+    #[test]
+    fn fixes_stamp_what_the_task_update_would() {
+        let now = Bson::Int64(1);
+        let set = task_fix(IntegrityCheck::OrphanedAssignee, &RepairAction::Null, "admin", now.clone()).unwrap();
+        assert_eq!(
+            set,
+            doc! { "updated_at": 1_i64, "assignee_id": null, "assigned_by": "admin", "assigned_at": 1_i64 }
+        );
+        let reassign = RepairAction::Reassign { to: "todo".into() };
+        let set = task_fix(IntegrityCheck::InvalidStatus, &reassign, "admin", now.clone()).unwrap();
+        assert_eq!(set, doc! { "updated_at": 1_i64, "status": "todo", "status_changed_at": 1_i64 });
+        assert!(task_fix(IntegrityCheck::OrphanedCti, &RepairAction::Delete, "admin", now).is_none());
+    }
+
+    #[test]
+    fn count_facet_reads_either_integer_width() {
+        assert_eq!(facet_count(&doc! { "count": [{ "n": 3 }] }), 3);
+        assert_eq!(facet_count(&doc! { "count": [{ "n": 4_i64 }] }), 4);
+        assert_eq!(facet_count(&doc! { "count": [] }), 0);
+    }
+}
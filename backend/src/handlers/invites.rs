@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use bson::doc;
+use chrono::Duration;
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use serde::Deserialize;
+
+use crate::{
+    db::INVITES,
+    errors::{AppError, AppResult, FieldError},
+    extract::AppJson,
+    handlers::{
+        auth::{AdminUser, AppState},
+        Created,
+    },
+    models::{
+        invite::{Invite, InvitePublic},
+        user::normalize_email,
+    },
+    permissions::validate_role,
+};
+
+fn default_role() -> String { "user".to_string() }
+fn default_expires_in_days() -> i64 { 7 }
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInviteRequest {
+    pub email: Option<String>,
+    #[serde(default = "default_role")]
+    pub role: String,
+    #[serde(default = "default_expires_in_days")]
+    pub expires_in_days: i64,
+}
+
+pub async fn admin_create_invite(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateInviteRequest>,
+) -> AppResult<Created<InvitePublic>> {
+    let mut errors = Vec::new();
+    if let Err(e) = validate_role(&payload.role) {
+        errors.push(e);
+    }
+    if !(1..=90).contains(&payload.expires_in_days) {
+        errors.push(FieldError::new(
+            "expires_in_days",
+            "out_of_range",
+            "expires_in_days must be between 1 and 90",
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let invite = Invite::new(
+        state.clock.as_ref(),
+        state.ids.as_ref(),
+        payload.email.filter(|e| !e.trim().is_empty()),
+        payload.role,
+        state.clock.now() + Duration::days(payload.expires_in_days),
+        claims.sub,
+    );
+    state
+        .db
+        .collection::<Invite>(INVITES)
+        .insert_one(&invite, None)
+        .await
+        .map_err(AppError::from)?;
+
+    Ok(Created::at(format!("/admin/invites/{}", invite.id), invite.into()))
+}
+
+pub async fn admin_get_invite(
+    _: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<InvitePublic>> {
+    let invite = state
+        .db
+        .collection::<Invite>(INVITES)
+        .find_one(doc! { "_id": &id }, None)
+        .await
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(invite.into()))
+}
+
+pub async fn admin_list_invites(
+    _: AdminUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<InvitePublic>>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let mut cursor = state
+        .db
+        .collection::<Invite>(INVITES)
+        .find(None, options)
+        .await
+        .map_err(AppError::from)?;
+
+    let mut invites = Vec::new();
+    while cursor.advance().await.map_err(AppError::from)? {
+        let invite: Invite = cursor.deserialize_current().map_err(AppError::from)?;
+        invites.push(invite.into());
+    }
+    Ok(Json(invites))
+}
+
+/// Atomically marks a valid, unexpired invite as used by `user_id`. The
+/// single `find_one_and_update` means two users racing the same code cannot
+/// both win. Returns 403 when no matching invite exists.
+pub async fn consume_invite(
+    state: &AppState,
+    code: &str,
+    email: &str,
+    user_id: &str,
+) -> AppResult<Invite> {
+    let now = bson::DateTime::from_chrono(state.clock.now());
+    let filter = doc! {
+        "code": code,
+        "used": false,
+        "expires_at": { "$gt": now },
+        "$or": [ { "email": null }, { "email": normalize_email(email) } ],
+    };
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+
+    state
+        .db
+        .collection::<Invite>(INVITES)
+        .find_one_and_update(
+            filter,
+            doc! { "$set": { "used": true, "used_by": user_id } },
+            options,
+        )
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| {
+            tracing::warn!("Rejected invite redemption by {user_id}");
+            AppError::Forbidden
+        })
+}
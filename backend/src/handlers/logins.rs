@@ -0,0 +1,167 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap},
+    Json,
+};
+use bson::{doc, Bson};
+use chrono::{DateTime, Utc};
+use mongodb::options::FindOptions;
+
+use crate::{
+    errors::{AppError, AppResult},
+    extract::AppPath,
+    handlers::auth::{AdminUser, AppState, Claims, CurrentUser},
+    models::{
+        dates::to_bson_date,
+        id::Id,
+        login_event::{LoginEvent, LoginEventPublic},
+        user::User,
+    },
+    pagination::{paginate, PageParams, Paginated},
+};
+
+/// Resolves the client address. `X-Forwarded-For` is only honoured when the
+/// deployment says it sits behind a trusted proxy; otherwise anyone could
+/// forge it.
+pub fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>, trust_proxy: bool) -> Option<String> {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        if let Some(ip) = forwarded {
+            return Some(ip.to_string());
+        }
+    }
+    peer.map(|addr| addr.ip().to_string())
+}
+
+/// Records a sign-in if the token's `auth_time` is newer than the stored
+/// `last_login_at`. `/api/auth/me` runs on every token refresh, so this is
+/// what keeps one Keycloak login from producing an event per refresh.
+pub async fn record_login(
+    state: &AppState,
+    claims: &Claims,
+    user: &User,
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+) -> AppResult<Option<DateTime<Utc>>> {
+    let Some(auth_time) = claims
+        .auth_time
+        .and_then(|t| DateTime::<Utc>::from_timestamp(t as i64, 0))
+    else {
+        return Ok(user.last_login_at);
+    };
+    if user.last_login_at.is_some_and(|prev| prev >= auth_time) {
+        return Ok(user.last_login_at);
+    }
+
+    // Compare-and-set on the previous value so concurrent /me calls for the
+    // same login record it once.
+    let previous = user.last_login_at.map_or(Bson::Null, to_bson_date);
+    let new_value = to_bson_date(auth_time);
+    let result = state
+        .db
+        .collection::<User>("users")
+        .update_one(
+            doc! { "_id": &user.id, "last_login_at": previous },
+            doc! { "$set": { "last_login_at": new_value } },
+            None,
+        )
+        .await
+        .map_err(AppError::from)?;
+
+    if result.modified_count == 1 {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let ip = client_ip(headers, peer, state.config.trust_proxy_headers);
+        let event = LoginEvent::new(state.clock.as_ref(), state.ids.as_ref(), user.id.clone(), ip, user_agent, true);
+        state
+            .db
+            .collection::<LoginEvent>("login_events")
+            .insert_one(&event, None)
+            .await
+            .map_err(AppError::from)?;
+    }
+    Ok(Some(auth_time))
+}
+
+async fn load_logins(
+    state: &AppState,
+    user_id: &str,
+    page: PageParams,
+) -> AppResult<Paginated<LoginEventPublic>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let events = paginate(
+        &state.db.collection::<LoginEvent>("login_events"),
+        doc! { "user_id": user_id },
+        options,
+        page,
+    )
+    .await?
+    .in_range(state.config.strict_pagination)?;
+    Ok(events.map(LoginEventPublic::from))
+}
+
+/// GET /api/auth/me/logins
+pub async fn my_logins(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    page: PageParams,
+) -> AppResult<Json<Paginated<LoginEventPublic>>> {
+    Ok(Json(load_logins(&state, &claims.sub, page).await?))
+}
+
+/// GET /api/admin/users/:id/logins
+pub async fn admin_user_logins(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    page: PageParams,
+) -> AppResult<Json<Paginated<LoginEventPublic>>> {
+    Ok(Json(load_logins(&state, &id, page).await?))
+}
+
+/// Peer address, when the server was started with connect info.
+pub fn peer_addr(info: Option<ConnectInfo<SocketAddr>>) -> Option<SocketAddr> {
+    info.map(|ConnectInfo(addr)| addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        h.insert("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        h
+    }
+
+    #[test]
+    fn client_ip_ignores_forwarded_header_when_untrusted() {
+        let peer: SocketAddr = "192.168.1.5:4000".parse().unwrap();
+        let ip = client_ip(&forwarded("203.0.113.9"), Some(peer), false);
+        assert_eq!(ip, Some("192.168.1.5".to_string()));
+    }
+
+    #[test]
+    fn client_ip_uses_first_forwarded_hop_when_trusted() {
+        let peer: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        let ip = client_ip(&forwarded("203.0.113.9, 10.0.0.1"), Some(peer), true);
+        assert_eq!(ip, Some("203.0.113.9".to_string()));
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_peer_without_header() {
+        let peer: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        assert_eq!(client_ip(&HeaderMap::new(), Some(peer), true), Some("10.0.0.2".to_string()));
+        assert_eq!(client_ip(&HeaderMap::new(), None, true), None);
+    }
+}
@@ -0,0 +1,17 @@
+//! Admin maintenance endpoints; see `cleanup`.
+
+use axum::{extract::State, Json};
+
+use crate::{
+    cleanup,
+    handlers::auth::{AdminUser, AppState},
+    models::cleanup::CleanupReport,
+};
+
+/// POST /api/admin/maintenance/cleanup — one cleanup pass now, as the
+/// scheduled job would run it. Safe while the job runs elsewhere.
+pub async fn admin_cleanup(AdminUser(claims): AdminUser, State(state): State<AppState>) -> Json<CleanupReport> {
+    let report = cleanup::run(&state.db, &state.config.cleanup, state.clock.now()).await;
+    tracing::info!(deleted = report.deleted(), "Cleanup run by {}", claims.sub);
+    Json(report)
+}
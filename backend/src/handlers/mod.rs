@@ -1,10 +1,80 @@
 pub mod admin;
+pub mod admin_stats;
+pub mod api_keys;
+pub mod attachments;
 pub mod auth;
+pub mod backup;
 pub mod ca;
 pub mod cti;
 pub mod dashboard;
+pub mod diagnostics;
+pub mod external_links;
+pub mod features;
 pub mod feeds;
+pub mod graphql;
 pub mod health;
+pub mod integrity;
+pub mod invites;
+pub mod logins;
+pub mod maintenance;
+pub mod notifications;
+pub mod preferences;
+pub mod reports;
+pub mod retention;
+pub mod revisions;
+pub mod search;
+pub mod shares;
+pub mod statuses;
+pub mod task_export;
 pub mod tasks;
+pub mod teams;
+pub mod user_export;
 pub mod users;
+pub mod views;
 pub mod weather;
+pub mod webhooks;
+pub mod workspaces;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::routes::API_V1;
+
+/// A `201 Created` carrying `body`, with `Location` set to the canonical
+/// (versioned) URL of the new resource. `path` is relative to the API root,
+/// e.g. `/tasks/<id>`, and must be a route that GETs the resource.
+pub struct Created<T> {
+    pub path: String,
+    pub body: T,
+}
+
+impl<T> Created<T> {
+    pub fn at(path: impl Into<String>, body: T) -> Self {
+        Self { path: path.into(), body }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Created<T> {
+    fn into_response(self) -> Response {
+        let location = format!("{API_V1}{}", self.path);
+        (StatusCode::CREATED, [(header::LOCATION, location)], Json(self.body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn created_points_location_at_the_versioned_url() {
+        let response = Created::at("/tasks/t1", serde_json::json!({ "id": "t1" })).into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::LOCATION], "/api/v1/tasks/t1");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], br#"{"id":"t1"}"#);
+    }
+}
@@ -0,0 +1,330 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{Path, Query, State},
+    Json,
+};
+use bson::doc;
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use serde_json::{json, Value};
+
+use crate::{
+    clock::{Clock, IdGen, SystemClock, UuidIds},
+    config::AppConfig,
+    db::{collect, indexes::case_insensitive, Db, NOTIFICATIONS, USERS},
+    digest,
+    errors::{AppError, AppResult},
+    events::{Actor, DomainEvent, Subscriber},
+    handlers::auth::{AppState, CurrentUser},
+    models::{
+        digest::Digest,
+        notification::{Notification, NotificationPublic, NotificationQuery, ASSIGNED, MENTIONED},
+        task::Task,
+        user::User,
+    },
+    notifier::{templates, Email, Notifier},
+    pagination::{paginate, PageParams, Paginated},
+};
+
+/// Stores notifications for the events that concern someone (assignment,
+/// mentions) and queues an email for each recipient who opted in. Runs as
+/// an event `Subscriber`, off the request path.
+#[derive(Clone)]
+pub struct Notifications {
+    db: Db,
+    notifier: Notifier,
+    frontend_origin: String,
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGen>,
+}
+
+impl Notifications {
+    pub fn new(db: Db, notifier: Notifier, config: &AppConfig) -> Self {
+        let (clock, ids) = (Arc::new(SystemClock), Arc::new(UuidIds));
+        Self { db, notifier, frontend_origin: config.frontend_origin.clone(), clock, ids }
+    }
+
+    /// Stores `notifications` about `task`, made by `actor`. Only storing
+    /// them can fail the event; emails are best effort.
+    async fn notify(&self, task: &Task, actor: &Actor, notifications: Vec<Notification>) -> AppResult<()> {
+        if notifications.is_empty() {
+            return Ok(());
+        }
+        self.db.collection::<Notification>(NOTIFICATIONS).insert_many(&notifications, None).await?;
+        if let Err(e) = self.email(task, actor, &notifications).await {
+            tracing::warn!(task_id = %task.id, "Could not queue notification emails: {e}");
+        }
+        Ok(())
+    }
+
+    async fn email(&self, task: &Task, actor: &Actor, notifications: &[Notification]) -> AppResult<()> {
+        let ids: Vec<&str> = notifications.iter().map(|n| n.recipient_id.as_str()).collect();
+        let cursor = self
+            .db
+            .collection::<User>(USERS)
+            .find(doc! { "_id": { "$in": ids }, "preferences.notifications.email": true }, None)
+            .await?;
+        let users = collect(cursor).await?;
+        let link = format!("{}/tasks/{}", self.frontend_origin.trim_end_matches('/'), task.id);
+        for n in notifications {
+            let Some(user) = users.iter().find(|u| u.id == n.recipient_id && wants_email(u, &n.kind)) else {
+                continue;
+            };
+            let (subject, body) = match n.kind.as_str() {
+                ASSIGNED => templates::task_assigned(&actor.username, &task.title, &link),
+                _ => templates::task_mentioned(&actor.username, &task.title, &link),
+            };
+            self.notifier.enqueue(Email { to: user.email.clone(), subject, body });
+        }
+        Ok(())
+    }
+
+    fn assigned(&self, task: &Task, assignee: &str, actor: &Actor) -> Option<Notification> {
+        assigned(self.clock.as_ref(), self.ids.as_ref(), task, assignee, actor)
+    }
+
+    /// Notifications for the users mentioned in `note`, other than its author.
+    async fn mentions(&self, task: &Task, note: &str, actor: &Actor) -> AppResult<Vec<Notification>> {
+        let names: Vec<String> = mentioned_usernames(note).into_iter().collect();
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        // Usernames are matched case-insensitively, like the dashboard's mention
+        // search, which also lets the query use the username index.
+        let options = FindOptions::builder().collation(case_insensitive()).build();
+        let cursor = self.db.collection::<User>(USERS).find(doc! { "username": { "$in": names } }, options).await?;
+        Ok(collect(cursor)
+            .await?
+            .into_iter()
+            .filter(|u| u.id != actor.id)
+            .map(|u| {
+                Notification::new(
+                    self.clock.as_ref(),
+                    self.ids.as_ref(),
+                    u.id,
+                    MENTIONED,
+                    task.id.clone(),
+                    actor.id.clone(),
+                    format!("{} mentioned you on \"{}\"", actor.username, task.title),
+                )
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Subscriber for Notifications {
+    fn name(&self) -> &'static str {
+        "notifications"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> anyhow::Result<()> {
+        let (task, notifications, actor) = match event {
+            DomainEvent::TaskCreated { task, actor } => {
+                let notification = task.assignee_id.as_deref().and_then(|a| self.assigned(task, a, actor));
+                (task, notification.into_iter().collect(), actor)
+            }
+            DomainEvent::UserAssigned { task, assignee, actor } => {
+                let notification = assignee.as_deref().and_then(|a| self.assigned(task, a, actor));
+                (task, notification.into_iter().collect(), actor)
+            }
+            DomainEvent::NoteAdded { task, note, actor } => (task, self.mentions(task, &note.note, actor).await?, actor),
+            _ => return Ok(()),
+        };
+        Ok(self.notify(task, actor, notifications).await?)
+    }
+}
+
+/// Whether `user` wants an email for a notification of `kind`.
+fn wants_email(user: &User, kind: &str) -> bool {
+    let prefs = &user.preferences.notifications;
+    user.active
+        && prefs.email
+        && match kind {
+            ASSIGNED => prefs.task_assigned,
+            MENTIONED => prefs.task_note_added,
+            _ => false,
+        }
+}
+
+/// Tells `assignee` that `actor` assigned them `task`. Assigning yourself is
+/// not news.
+pub fn assigned(
+    clock: &dyn Clock,
+    ids: &dyn IdGen,
+    task: &Task,
+    assignee: &str,
+    actor: &Actor,
+) -> Option<Notification> {
+    (assignee != actor.id).then(|| {
+        Notification::new(
+            clock,
+            ids,
+            assignee.to_string(),
+            ASSIGNED,
+            task.id.clone(),
+            actor.id.clone(),
+            format!("{} assigned you \"{}\"", actor.username, task.title),
+        )
+    })
+}
+
+/// `@username` mentions in `text`, lower-cased and deduplicated. A mention
+/// starts at the beginning or after a non-word character, so e-mail
+/// addresses are not read as mentions.
+pub fn mentioned_usernames(text: &str) -> BTreeSet<String> {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut names = BTreeSet::new();
+    let mut prev: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        if c == '@' && !prev.is_some_and(|p| p.is_alphanumeric() || p == '_') {
+            let rest = &text[i + 1..];
+            let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            // A trailing dot ends the sentence, not the name.
+            let name = rest[..end].trim_end_matches('.');
+            if !name.is_empty() {
+                names.insert(name.to_lowercase());
+            }
+        }
+        prev = Some(c);
+    }
+    names
+}
+
+/// GET /api/notifications — newest first; `?unread=true` hides read ones.
+pub async fn list_notifications(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(params): Query<NotificationQuery>,
+    page: PageParams,
+) -> AppResult<Json<Paginated<NotificationPublic>>> {
+    let mut filter = doc! { "recipient_id": &claims.sub };
+    if params.unread {
+        filter.insert("read_at", bson::Bson::Null);
+    }
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let notifications = paginate(
+        &state.db.collection::<Notification>(NOTIFICATIONS),
+        filter,
+        options,
+        page,
+    )
+    .await?
+    .in_range(state.config.strict_pagination)?;
+    Ok(Json(notifications.map(NotificationPublic::from)))
+}
+
+/// POST /api/notifications/:id/read — marking one already read keeps its
+/// original `read_at`.
+pub async fn mark_notification_read(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<NotificationPublic>> {
+    let collection = state.db.collection::<Notification>(NOTIFICATIONS);
+    let filter = doc! { "_id": &id, "recipient_id": &claims.sub };
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    let updated = collection
+        .find_one_and_update(
+            doc! { "_id": &id, "recipient_id": &claims.sub, "read_at": null },
+            doc! { "$set": { "read_at": bson::DateTime::now() } },
+            options,
+        )
+        .await?;
+    let notification = match updated {
+        Some(n) => n,
+        None => collection.find_one(filter, None).await?.ok_or(AppError::NotFound)?,
+    };
+    Ok(Json(notification.into()))
+}
+
+/// POST /api/notifications/read-all
+pub async fn mark_all_notifications_read(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Value>> {
+    let result = state
+        .db
+        .collection::<Notification>(NOTIFICATIONS)
+        .update_many(
+            doc! { "recipient_id": &claims.sub, "read_at": null },
+            doc! { "$set": { "read_at": bson::DateTime::now() } },
+            None,
+        )
+        .await
+        .map_err(AppError::from)?;
+    Ok(Json(json!({ "updated": result.modified_count })))
+}
+
+/// GET /api/notifications/digest/preview — what the caller's digest would
+/// list right now, whether or not they get one.
+pub async fn digest_preview(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Digest>> {
+    let user = state
+        .db
+        .collection::<User>(USERS)
+        .find_one(doc! { "_id": &claims.sub }, None)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let open = state.workflow.get(&state.db).await?.open_keys();
+    Ok(Json(digest::assemble(&state.db, &user.id, &open, user.preferences.tz(), state.clock.now()).await?))
+}
+
+/// Unread notifications for `user_id`, as shown on the dashboard.
+pub async fn unread_count(state: &AppState, user_id: &str) -> AppResult<u64> {
+    Ok(state
+        .db
+        .collection::<Notification>(NOTIFICATIONS)
+        .count_documents(doc! { "recipient_id": user_id, "read_at": null }, None)
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(text: &str) -> Vec<String> {
+        mentioned_usernames(text).into_iter().collect()
+    }
+
+    #[test]
+    fn finds_mentions_anywhere_in_a_note() {
+        assert_eq!(names("@alice can you check? cc @Bob."), vec!["alice", "bob"]);
+        assert_eq!(names("(@j.doe) and @ops-team, @alice"), vec!["alice", "j.doe", "ops-team"]);
+    }
+
+    #[test]
+    fn ignores_emails_and_bare_at_signs() {
+        assert!(names("mail alice@example.com @ noon").is_empty());
+        assert_eq!(names("@alice @ALICE"), vec!["alice"]);
+    }
+
+    #[test]
+    fn self_assignment_is_not_notified() {
+        let actor = Actor { id: "u1".into(), username: "alice".into() };
+        let task = Task::new(&SystemClock, &UuidIds, "Rotate certs".into(), "".into());
+        assert!(assigned(&SystemClock, &UuidIds, &task, "u1", &actor).is_none());
+        let n = assigned(&SystemClock, &UuidIds, &task, "u2", &actor).unwrap();
+        assert_eq!((n.recipient_id.as_str(), n.kind.as_str()), ("u2", ASSIGNED));
+        assert_eq!(n.message, "alice assigned you \"Rotate certs\"");
+    }
+
+    #[test]
+    fn emails_follow_notification_preferences() {
+        let mut user: User = serde_json::from_value(serde_json::json!({
+            "_id": "u2", "email": "bob@example.com", "username": "bob", "role": "user",
+            "created_at": "2024-01-01T00:00:00Z", "updated_at": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        assert!(!wants_email(&user, ASSIGNED), "email is opt-in");
+        user.preferences.notifications.email = true;
+        assert!(wants_email(&user, ASSIGNED) && wants_email(&user, MENTIONED));
+        user.preferences.notifications.task_note_added = false;
+        assert!(!wants_email(&user, MENTIONED));
+        user.active = false;
+        assert!(!wants_email(&user, ASSIGNED));
+    }
+}
@@ -0,0 +1,56 @@
+use axum::{extract::State, Json};
+use bson::doc;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::{
+    errors::{AppError, AppResult},
+    extract::AppJson,
+    handlers::auth::{AppState, CurrentUser},
+    models::{
+        dates::to_bson_date,
+        preferences::{UpdatePreferencesRequest, UserPreferences},
+        user::User,
+    },
+};
+
+/// GET /api/auth/me/preferences
+pub async fn get_preferences(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<UserPreferences>> {
+    let user = state
+        .db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": &claims.sub }, None)
+        .await
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(user.preferences))
+}
+
+/// PUT /api/auth/me/preferences — merges the supplied fields into the stored
+/// preferences.
+pub async fn update_preferences(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<UpdatePreferencesRequest>,
+) -> AppResult<Json<UserPreferences>> {
+    let workflow = state.workflow.get(&state.db).await?;
+    let mut set_doc = payload.into_set_doc(&workflow).map_err(AppError::Validation)?;
+    if set_doc.is_empty() {
+        return Err(AppError::BadRequest("no preferences to update".to_string()));
+    }
+    set_doc.insert("updated_at", to_bson_date(state.clock.now()));
+
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+    let user = state
+        .db
+        .collection::<User>("users")
+        .find_one_and_update(doc! { "_id": &claims.sub }, doc! { "$set": set_doc }, options)
+        .await
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(user.preferences))
+}
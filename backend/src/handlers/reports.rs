@@ -0,0 +1,236 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bson::{doc, Document};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    db::{TASKS, USERS},
+    errors::{AppError, AppResult, FieldError},
+    handlers::auth::{AppState, CurrentUser},
+    models::{
+        dates::to_bson_date,
+        report::{
+            AssigneeCount, CtiBucket, CtiLevel, CtiReportQuery, CtiReportResponse, ReportTasks, StaleReportQuery,
+            StaleReportResponse, UnassignedReportQuery, UNCLASSIFIED,
+        },
+        workflow::Workflow,
+    },
+    pagination::{PageParams, PageQuery},
+};
+
+/// Builds the `$match` stage shared by the report levels, within `ws`.
+fn report_filter(ws: &str, params: &CtiReportQuery, workflow: &Workflow) -> AppResult<Document> {
+    let mut filter = doc! { "workspace_id": ws };
+    if let Some(type_id) = &params.type_id {
+        filter.insert("cti.type_id", type_id);
+    } else if let Some(category_id) = &params.category_id {
+        filter.insert("cti.category_id", category_id);
+    }
+
+    let mut created = Document::new();
+    if let Some(from) = params.from {
+        created.insert("$gte", to_bson_date(from));
+    }
+    if let Some(to) = params.to {
+        created.insert("$lt", to_bson_date(to));
+    }
+    if let (Some(from), Some(to)) = (params.from, params.to) {
+        if from >= to {
+            return Err(FieldError::new("to", "invalid_range", "from must be before to").into());
+        }
+    }
+    if !created.is_empty() {
+        filter.insert("created_at", created);
+    }
+
+    if let Some(statuses) = params
+        .status
+        .as_deref()
+        .map(|s| workflow.parse_statuses(s))
+        .transpose()
+        .map_err(|m| FieldError::new("status", "invalid_status", m))?
+        .flatten()
+    {
+        filter.insert("status", doc! { "$in": statuses });
+    }
+    Ok(filter)
+}
+
+/// GET /api/reports/cti — task counts per CTI node, names resolved with
+/// `$lookup`. Tasks without a CTI selection form an "Unclassified" bucket.
+pub async fn cti_report(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(params): Query<CtiReportQuery>,
+) -> AppResult<Json<CtiReportResponse>> {
+    let ws = claims.workspace()?;
+    let level = CtiLevel::for_query(&params);
+    let workflow = state.workflow.get(&state.db).await?;
+    let pipeline = vec![
+        doc! { "$match": report_filter(ws, &params, &workflow)? },
+        doc! { "$group": { "_id": format!("${}", level.task_field()), "count": { "$sum": 1 } } },
+        // Names come only from this workspace's taxonomy.
+        doc! { "$lookup": {
+            "from": level.collection(),
+            "localField": "_id",
+            "foreignField": "_id",
+            "pipeline": [{ "$match": { "workspace_id": ws } }],
+            "as": "node",
+        } },
+        doc! { "$project": {
+            "count": 1,
+            "name": { "$ifNull": [
+                { "$first": "$node.name" },
+                { "$cond": [{ "$eq": ["$_id", null] }, UNCLASSIFIED, "Unknown"] },
+            ] },
+        } },
+        doc! { "$sort": { "count": -1, "name": 1 } },
+    ];
+
+    let mut cursor = state
+        .db
+        .collection::<Document>(TASKS)
+        .aggregate(pipeline, None)
+        .await?;
+    let mut buckets = Vec::new();
+    while cursor.advance().await? {
+        let bucket: Document = cursor.deserialize_current()?;
+        let bucket: CtiBucket =
+            bson::from_document(bucket).map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+        buckets.push(bucket);
+    }
+
+    Ok(Json(CtiReportResponse {
+        level,
+        total: buckets.iter().map(|b| b.count).sum(),
+        buckets,
+    }))
+}
+
+/// Open tasks last updated before `cutoff`. Served by the
+/// `{workspace_id, status, updated_at}` index.
+fn stale_filter(workflow: &Workflow, cutoff: DateTime<Utc>) -> Document {
+    doc! { "status": { "$in": workflow.open_keys() }, "updated_at": { "$lt": to_bson_date(cutoff) } }
+}
+
+/// Open tasks nobody is assigned to; `null` also matches a missing field.
+fn unassigned_filter(workflow: &Workflow) -> Document {
+    doc! { "assignee_id": null, "status": { "$in": workflow.open_keys() } }
+}
+
+/// A page of the tasks matching `filter`, or only their count.
+async fn report_tasks(
+    state: &AppState,
+    ws: &str,
+    filter: Document,
+    sort: Document,
+    page: PageParams,
+    counts_only: bool,
+) -> AppResult<ReportTasks> {
+    if counts_only {
+        let total = state.repos.tasks.count(ws, filter).await?;
+        Ok(ReportTasks::Counts { total })
+    } else {
+        let tasks = state.repos.tasks.find_page(ws, filter, sort, page).await?;
+        Ok(ReportTasks::Page(tasks.in_range(state.config.strict_pagination)?))
+    }
+}
+
+/// GET /api/reports/stale?days=14 — open tasks not updated for `days`,
+/// longest-untouched first, with how many each assignee holds.
+pub async fn stale_report(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    Query(params): Query<StaleReportQuery>,
+) -> AppResult<Json<StaleReportResponse>> {
+    let (page, mut errors) = page.resolve(&state.config.page_limits);
+    errors.extend(params.errors());
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    let ws = claims.workspace()?;
+    let cutoff = state.clock.now() - Duration::days(params.days.into());
+    let workflow = state.workflow.get(&state.db).await?;
+
+    let mut filter = stale_filter(&workflow, cutoff);
+    filter.insert("workspace_id", ws);
+    let pipeline = vec![
+        doc! { "$match": filter },
+        doc! { "$group": { "_id": "$assignee_id", "count": { "$sum": 1 } } },
+        doc! { "$lookup": { "from": USERS, "localField": "_id", "foreignField": "_id", "as": "user" } },
+        doc! { "$project": { "count": 1, "username": { "$first": "$user.username" } } },
+        doc! { "$sort": { "count": -1, "_id": 1 } },
+    ];
+    let by_assignee = async {
+        let mut cursor = state.db.collection::<Document>(TASKS).aggregate(pipeline, None).await?;
+        let mut counts = Vec::new();
+        while cursor.advance().await? {
+            let count: Document = cursor.deserialize_current()?;
+            let count: AssigneeCount =
+                bson::from_document(count).map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+            counts.push(count);
+        }
+        Ok::<_, AppError>(counts)
+    };
+    let tasks = report_tasks(&state, ws, stale_filter(&workflow, cutoff), doc! { "updated_at": 1 }, page, params.counts_only);
+    let (by_assignee, tasks) = tokio::try_join!(by_assignee, tasks)?;
+
+    Ok(Json(StaleReportResponse { days: params.days, cutoff, by_assignee, tasks }))
+}
+
+/// GET /api/reports/unassigned — open tasks without an assignee, oldest first.
+pub async fn unassigned_report(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    page: PageParams,
+    Query(params): Query<UnassignedReportQuery>,
+) -> AppResult<Json<ReportTasks>> {
+    let ws = claims.workspace()?;
+    let sort = doc! { "created_at": 1 };
+    let workflow = state.workflow.get(&state.db).await?;
+    Ok(Json(report_tasks(&state, ws, unassigned_filter(&workflow), sort, page, params.counts_only).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn filter_scopes_drill_down_and_status() {
+        let q = CtiReportQuery {
+            category_id: Some("c1".into()),
+            status: Some("done".into()),
+            ..Default::default()
+        };
+        let f = report_filter("ws1", &q, &Workflow::default()).unwrap();
+        assert_eq!(f.get_str("workspace_id").unwrap(), "ws1");
+        assert_eq!(f.get_str("cti.category_id").unwrap(), "c1");
+        assert_eq!(f.get_document("status").unwrap(), &doc! { "$in": ["done"] });
+        assert!(!f.contains_key("created_at"));
+    }
+
+    #[test]
+    fn filter_rejects_bad_status_and_inverted_range() {
+        let q = CtiReportQuery { status: Some("finished".into()), ..Default::default() };
+        assert!(report_filter("ws1", &q, &Workflow::default()).is_err());
+        let now = Utc::now();
+        let q = CtiReportQuery { from: Some(now), to: Some(now - Duration::days(1)), ..Default::default() };
+        assert!(report_filter("ws1", &q, &Workflow::default()).is_err());
+    }
+
+    #[test]
+    fn stale_and_unassigned_filters_only_match_open_tasks() {
+        let cutoff = Utc::now();
+        let f = stale_filter(&Workflow::default(), cutoff);
+        assert_eq!(f.get_document("status").unwrap(), &doc! { "$in": ["todo", "in_progress"] });
+        assert_eq!(f.get_document("updated_at").unwrap(), &doc! { "$lt": to_bson_date(cutoff) });
+
+        let f = unassigned_filter(&Workflow::default());
+        assert_eq!(f.get("assignee_id"), Some(&bson::Bson::Null));
+        assert_eq!(f.get_document("status").unwrap(), &doc! { "$in": ["todo", "in_progress"] });
+    }
+}
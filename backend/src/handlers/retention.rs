@@ -0,0 +1,38 @@
+//! The retention job's admin endpoints; see `retention`.
+
+use axum::{extract::State, Json};
+use bson::doc;
+use mongodb::options::FindOptions;
+
+use crate::{
+    db::RETENTION_RUNS,
+    errors::AppResult,
+    handlers::auth::{AdminUser, AppState},
+    models::retention::RetentionRun,
+    pagination::{paginate, PageParams, Paginated},
+    retention,
+};
+
+/// POST /api/admin/retention/dry-run — what a run would archive and delete
+/// now, changing nothing. Recorded like any other run.
+pub async fn retention_dry_run(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<RetentionRun>> {
+    let config = &state.config.retention;
+    let run = retention::run(&state.db, config, state.ids.as_ref(), state.clock.now(), true, Some(claims.sub)).await?;
+    Ok(Json(run))
+}
+
+/// GET /api/admin/retention/runs — newest first.
+pub async fn list_retention_runs(
+    _: AdminUser,
+    State(state): State<AppState>,
+    page: PageParams,
+) -> AppResult<Json<Paginated<RetentionRun>>> {
+    let options = FindOptions::builder().sort(doc! { "started_at": -1 }).build();
+    let runs = paginate(&state.db.collection::<RetentionRun>(RETENTION_RUNS), doc! {}, options, page)
+        .await?
+        .in_range(state.config.strict_pagination)?;
+    Ok(Json(runs))
+}
@@ -0,0 +1,107 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bson::doc;
+use mongodb::{options::FindOptions, Collection};
+
+use crate::{
+    db::{collect, Db, TASK_REVISIONS},
+    errors::{AppError, AppResult},
+    extract::AppPath,
+    handlers::{
+        auth::{AppState, Claims, CurrentUser},
+        tasks::{update, UpdateTaskRequest},
+    },
+    models::{
+        id::Id,
+        revision::{RevisionQuery, TaskRevision, MAX_REVISIONS_PER_TASK},
+        task::Task,
+    },
+};
+
+fn revisions(state: &AppState) -> Collection<TaskRevision> {
+    state.db.collection(TASK_REVISIONS)
+}
+
+/// Stores what `field` held on `task_id` before `claims` replaced it, then
+/// drops all but the newest `MAX_REVISIONS_PER_TASK` of the task's
+/// revisions.
+pub async fn record_revision(
+    state: &AppState,
+    claims: &Claims,
+    task_id: &str,
+    field: &str,
+    old_value: &str,
+) -> AppResult<()> {
+    let ws = claims.workspace()?;
+    let revision =
+        TaskRevision::new(state.clock.as_ref(), state.ids.as_ref(), ws, task_id, field, old_value, &claims.sub);
+    revisions(state).insert_one(&revision, None).await?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "created_at": -1, "_id": -1 })
+        .skip(MAX_REVISIONS_PER_TASK)
+        .build();
+    let filter = doc! { "task_id": task_id, "workspace_id": ws };
+    let stale: Vec<String> =
+        collect(revisions(state).find(filter, options).await?).await?.into_iter().map(|r| r.id).collect();
+    if !stale.is_empty() {
+        revisions(state).delete_many(doc! { "_id": { "$in": stale } }, None).await?;
+    }
+    Ok(())
+}
+
+/// Deletes every revision of `task_id`, once the task itself is gone.
+pub async fn delete_revisions(db: &Db, ws: &str, task_id: &str) -> AppResult<()> {
+    let filter = doc! { "task_id": task_id, "workspace_id": ws };
+    db.collection::<TaskRevision>(TASK_REVISIONS).delete_many(filter, None).await?;
+    Ok(())
+}
+
+/// Every revision of `task_id`, oldest first.
+pub async fn task_history(db: &Db, ws: &str, task_id: &str) -> AppResult<Vec<TaskRevision>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": 1, "_id": 1 }).build();
+    let filter = doc! { "task_id": task_id, "workspace_id": ws };
+    Ok(collect(db.collection::<TaskRevision>(TASK_REVISIONS).find(filter, options).await?).await?)
+}
+
+/// GET /api/tasks/:id/revisions?field=description — earlier values of the
+/// task's fields, newest first.
+pub async fn list_task_revisions(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    Query(params): Query<RevisionQuery>,
+) -> AppResult<Json<Vec<TaskRevision>>> {
+    let field = params.field()?;
+    let ws = claims.workspace()?;
+    state.repos.tasks.find_by_id(ws, &id).await?.ok_or(AppError::NotFound)?;
+    let mut filter = doc! { "task_id": &*id, "workspace_id": ws };
+    if let Some(field) = field {
+        filter.insert("field", field);
+    }
+    let options = FindOptions::builder().sort(doc! { "created_at": -1, "_id": -1 }).build();
+    Ok(Json(collect(revisions(&state).find(filter, options).await?).await?))
+}
+
+/// POST /api/tasks/:id/revisions/:rev_id/restore — puts the revision's value
+/// back through the ordinary update, so the value it replaces becomes a
+/// revision in turn. Truncated revisions cannot be restored.
+pub async fn restore_task_revision(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath((task_id, rev_id)): AppPath<(Id, Id)>,
+) -> AppResult<Json<Task>> {
+    let filter = doc! { "_id": &*rev_id, "task_id": &*task_id, "workspace_id": claims.workspace()? };
+    let revision = revisions(&state).find_one(filter, None).await?.ok_or(AppError::NotFound)?;
+    if revision.truncated {
+        return Err(AppError::BadRequest("This revision was truncated when stored and cannot be restored".into()));
+    }
+    let mut payload = UpdateTaskRequest::default();
+    match revision.field.as_str() {
+        "description" => payload.description = Some(revision.old_value),
+        other => return Err(AppError::Internal(anyhow::anyhow!("revision of unknown field '{other}'"))),
+    }
+    Ok(Json(update(&state, &claims, &task_id, payload).await?))
+}
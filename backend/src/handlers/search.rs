@@ -0,0 +1,39 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bson::doc;
+
+use crate::{
+    errors::{AppError, AppResult},
+    handlers::auth::{AppState, CurrentUser},
+    models::search::{search_filter, SearchHit, SearchQuery},
+    pagination::{PageQuery, Paginated},
+};
+
+/// GET /api/search?q= — tasks in the workspace whose title, description or
+/// notes contain `q`, most recently updated first, each with snippets of
+/// where it matched.
+pub async fn search(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    Query(query): Query<SearchQuery>,
+) -> AppResult<Json<Paginated<SearchHit>>> {
+    let (page, mut errors) = page.resolve(&state.config.page_limits);
+    let q = match query.text() {
+        Ok(q) if errors.is_empty() => q,
+        Ok(_) => return Err(AppError::Validation(errors)),
+        Err(e) => {
+            errors.insert(0, e);
+            return Err(AppError::Validation(errors));
+        }
+    };
+    let tasks = state
+        .repos
+        .tasks
+        .find_page(claims.workspace()?, search_filter(q), doc! { "updated_at": -1 }, page)
+        .await?
+        .in_range(state.config.strict_pagination)?;
+    Ok(Json(tasks.map(|task| SearchHit::new(task, q))))
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use mongodb::{
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    Collection,
+};
+
+use crate::{
+    db::{Db, TASK_SHARES},
+    errors::{AppError, AppResult},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AppState, Claims, CurrentUser},
+        tasks::can_modify_task,
+        Created,
+    },
+    models::{
+        api_key::hash_key,
+        dates::to_bson_date,
+        id::Id,
+        share::{CreateShareRequest, CreatedShare, SharedTask, TaskShare},
+        task::Task,
+    },
+};
+
+fn shares(state: &AppState) -> Collection<TaskShare> {
+    state.db.collection(TASK_SHARES)
+}
+
+/// The task, 404 if unknown and 403 unless `claims` may modify it. Unlike
+/// edits, sharing is never opened up by `OPEN_TASK_EDITING`.
+async fn shareable_task(state: &AppState, claims: &Claims, id: &str) -> AppResult<Task> {
+    let task = state.repos.tasks.find_by_id(claims.workspace()?, id).await?.ok_or(AppError::NotFound)?;
+    if !can_modify_task(claims, &task) {
+        return Err(AppError::Forbidden);
+    }
+    Ok(task)
+}
+
+/// POST /api/tasks/:id/share — a read-only link to the task for people
+/// without an account. The token is only ever shown in this response.
+pub async fn create_task_share(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    AppJson(payload): AppJson<CreateShareRequest>,
+) -> AppResult<Created<CreatedShare>> {
+    payload.validate().map_err(|e| AppError::Validation(vec![e]))?;
+    let task = shareable_task(&state, &claims, &id).await?;
+    let (share, token) = TaskShare::generate(state.clock.as_ref(), state.ids.as_ref(), &task, &payload, &claims.sub);
+    shares(&state).insert_one(&share, None).await?;
+    tracing::info!(task_id = %task.id, share_id = %share.id, "Task shared by {}", claims.sub);
+    Ok(Created::at(format!("/shared/{token}"), CreatedShare::new(share, token)))
+}
+
+/// DELETE /api/tasks/:id/share/:share_id — the link stops working at once.
+pub async fn revoke_task_share(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath((task_id, share_id)): AppPath<(Id, Id)>,
+) -> AppResult<StatusCode> {
+    let task = shareable_task(&state, &claims, &task_id).await?;
+    let filter = doc! {
+        "_id": &*share_id,
+        "task_id": &task.id,
+        "workspace_id": &task.workspace_id,
+        "revoked_at": null,
+    };
+    let update = doc! { "$set": { "revoked_at": to_bson_date(state.clock.now()) } };
+    if shares(&state).update_one(filter, update, None).await?.matched_count == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes every share of `task_id`, once the task itself is gone.
+pub async fn delete_shares(db: &Db, ws: &str, task_id: &str) -> AppResult<()> {
+    db.collection::<TaskShare>(TASK_SHARES).delete_many(doc! { "task_id": task_id, "workspace_id": ws }, None).await?;
+    Ok(())
+}
+
+/// GET /api/shared/:token — public. The shared task, redacted; 404 alike for
+/// unknown, revoked and expired tokens. Each successful read is counted.
+pub async fn get_shared_task(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> AppResult<Json<SharedTask>> {
+    let now = to_bson_date(state.clock.now());
+    let filter = doc! {
+        "token_hash": hash_key(&token),
+        "revoked_at": null,
+        "$or": [{ "expires_at": null }, { "expires_at": { "$gt": now.clone() } }],
+    };
+    let update = doc! { "$inc": { "access_count": 1 }, "$set": { "last_accessed_at": now } };
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    let share = shares(&state).find_one_and_update(filter, update, options).await?.ok_or(AppError::NotFound)?;
+
+    let task = state.repos.tasks.find_by_id(&share.workspace_id, &share.task_id).await?.ok_or(AppError::NotFound)?;
+    let ids = SharedTask::user_ids(&task, &share);
+    let usernames = if ids.is_empty() {
+        Default::default()
+    } else {
+        let users = state.repos.users.find_summaries(doc! { "_id": { "$in": ids } }).await?;
+        users.into_iter().map(|u| (u.id, u.username)).collect()
+    };
+    Ok(Json(SharedTask::new(task, &share, &usernames)))
+}
@@ -0,0 +1,193 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use bson::{doc, Document};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::{
+    db::{TASKS, WORKFLOW_STATUSES},
+    errors::{AppError, AppResult, FieldError},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AdminUser, AppState, CurrentUser},
+        Created,
+    },
+    models::{
+        dates::to_bson_date,
+        workflow::{CreateStatusRequest, DeleteStatusQuery, UpdateStatusRequest, Workflow, WorkflowStatus},
+    },
+};
+
+/// The `$set` for an update, or `None` when it changes nothing.
+fn status_set_doc(payload: UpdateStatusRequest) -> Option<Document> {
+    let mut set = Document::new();
+    if let Some(label) = payload.label {
+        set.insert("label", label.trim());
+    }
+    if let Some(color) = payload.color {
+        set.insert("color", color);
+    }
+    if let Some(order) = payload.order {
+        set.insert("order", order);
+    }
+    if let Some(is_terminal) = payload.is_terminal {
+        set.insert("is_terminal", is_terminal);
+    }
+    if set.is_empty() { None } else { Some(set) }
+}
+
+/// Checks a delete's `migrate_to` against the workflow it would leave behind.
+fn check_migration_target(workflow: &Workflow, deleting: &str, target: &str) -> Result<(), FieldError> {
+    if target == deleting || workflow.get(target).is_none() {
+        return Err(FieldError::new(
+            "migrate_to",
+            "unknown_status",
+            format!("migrate_to must name another status, not '{target}'"),
+        ));
+    }
+    Ok(())
+}
+
+/// GET /api/statuses — every workflow status, in board order.
+pub async fn list_statuses(_: CurrentUser, State(state): State<AppState>) -> AppResult<Json<Vec<WorkflowStatus>>> {
+    Ok(Json(state.workflow.get(&state.db).await?.statuses().to_vec()))
+}
+
+/// GET /api/admin/statuses
+pub async fn admin_list_statuses(
+    _: AdminUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<WorkflowStatus>>> {
+    Ok(Json(state.workflow.get(&state.db).await?.statuses().to_vec()))
+}
+
+/// POST /api/admin/statuses
+pub async fn admin_create_status(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateStatusRequest>,
+) -> AppResult<Created<WorkflowStatus>> {
+    payload.validate().map_err(AppError::Validation)?;
+    let workflow = state.workflow.get(&state.db).await?;
+    let order = payload
+        .order
+        .unwrap_or_else(|| workflow.statuses().last().map_or(0, |s| s.order + 1));
+    let status = WorkflowStatus::new(
+        &payload.key,
+        payload.label.trim(),
+        &payload.color,
+        order,
+        payload.is_terminal,
+        state.clock.now(),
+    );
+    let result = state.db.collection::<WorkflowStatus>(WORKFLOW_STATUSES).insert_one(&status, None).await;
+    state.workflow.invalidate().await;
+    result?;
+    Ok(Created::at(format!("/admin/statuses/{}", status.key), status))
+}
+
+/// GET /api/admin/statuses/:key
+pub async fn admin_get_status(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(key): AppPath<String>,
+) -> AppResult<Json<WorkflowStatus>> {
+    let workflow = state.workflow.get(&state.db).await?;
+    Ok(Json(workflow.get(&key).ok_or(AppError::NotFound)?.clone()))
+}
+
+/// PUT /api/admin/statuses/:key
+pub async fn admin_update_status(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(key): AppPath<String>,
+    AppJson(payload): AppJson<UpdateStatusRequest>,
+) -> AppResult<Json<WorkflowStatus>> {
+    payload.validate().map_err(AppError::Validation)?;
+    let collection = state.db.collection::<WorkflowStatus>(WORKFLOW_STATUSES);
+    let Some(mut set) = status_set_doc(payload) else {
+        let status = collection.find_one(doc! { "_id": &key }, None).await?;
+        return status.map(Json).ok_or(AppError::NotFound);
+    };
+    set.insert("updated_at", to_bson_date(state.clock.now()));
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+    let status = collection
+        .find_one_and_update(doc! { "_id": &key }, doc! { "$set": set }, options)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    state.workflow.invalidate().await;
+    Ok(Json(status))
+}
+
+/// DELETE /api/admin/statuses/:key[?migrate_to=<key>] — refused with 409
+/// `status_in_use` while any task, in any workspace, is in the status,
+/// unless `migrate_to` names another status to move those tasks to first.
+/// The last remaining status cannot be deleted.
+pub async fn admin_delete_status(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(key): AppPath<String>,
+    Query(params): Query<DeleteStatusQuery>,
+) -> AppResult<StatusCode> {
+    let workflow = state.workflow.get(&state.db).await?;
+    if workflow.get(&key).is_none() {
+        return Err(AppError::NotFound);
+    }
+    if workflow.statuses().len() == 1 {
+        return Err(AppError::BadRequest("cannot delete the only workflow status".into()));
+    }
+    let tasks = state.db.collection::<Document>(TASKS);
+    match &params.migrate_to {
+        Some(target) => {
+            check_migration_target(&workflow, &key, target)?;
+            let now = to_bson_date(state.clock.now());
+            let set = doc! { "status": target, "status_changed_at": now.clone(), "updated_at": now };
+            let moved = tasks.update_many(doc! { "status": &key }, doc! { "$set": set }, None).await?;
+            tracing::info!(status = %key, migrate_to = %target, tasks = moved.modified_count, "Migrated tasks off a deleted status");
+        }
+        None => {
+            let in_use = tasks.count_documents(doc! { "status": &key }, None).await?;
+            if in_use > 0 {
+                return Err(AppError::StatusInUse { tasks: in_use });
+            }
+        }
+    }
+    let result = state
+        .db
+        .collection::<WorkflowStatus>(WORKFLOW_STATUSES)
+        .delete_one(doc! { "_id": &key }, None)
+        .await;
+    state.workflow.invalidate().await;
+    if result?.deleted_count == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_set_only_the_fields_sent() {
+        assert_eq!(status_set_doc(UpdateStatusRequest::default()), None);
+        let set = status_set_doc(UpdateStatusRequest {
+            label: Some(" Review ".into()),
+            order: Some(4),
+            ..Default::default()
+        });
+        assert_eq!(set, Some(doc! { "label": "Review", "order": 4 }));
+    }
+
+    #[test]
+    fn migrations_must_target_another_existing_status() {
+        let workflow = Workflow::default();
+        assert!(check_migration_target(&workflow, "in_progress", "todo").is_ok());
+        assert_eq!(check_migration_target(&workflow, "todo", "todo").unwrap_err().code, "unknown_status");
+        assert_eq!(check_migration_target(&workflow, "todo", "review").unwrap_err().code, "unknown_status");
+    }
+}
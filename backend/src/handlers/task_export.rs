@@ -0,0 +1,64 @@
+//! GET /api/tasks/:id/export; see `models::task_export`.
+
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use bson::doc;
+
+use crate::{
+    errors::{AppError, AppResult},
+    extract::AppPath,
+    handlers::{
+        attachments::task_attachment_info,
+        auth::{AppState, CurrentUser},
+        revisions::task_history,
+    },
+    models::{
+        id::Id,
+        task_export::{CtiNames, TaskExport, TaskExportFormat, TaskExportQuery},
+    },
+};
+
+/// GET /api/tasks/:id/export?format=json|markdown — the task with its notes,
+/// history, attachment details and the names behind its ids, as one JSON
+/// document or a Markdown summary. Readable by whoever may read the task.
+pub async fn export_task(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    Query(params): Query<TaskExportQuery>,
+) -> AppResult<Response> {
+    let ws = claims.workspace()?;
+    let task = state.repos.tasks.find_by_id(ws, &id).await?.ok_or(AppError::NotFound)?;
+    let (history, attachments) =
+        tokio::try_join!(task_history(&state.db, ws, &id), task_attachment_info(&state.db, ws, &id))?;
+    let cti = match &task.cti {
+        Some(selection) => Some(CtiNames::new(&*state.cti_cache.tree(state.repos.cti.as_ref(), ws).await?, selection)),
+        None => None,
+    };
+    let ids = TaskExport::user_ids(&task, &history, &attachments);
+    let users = if ids.is_empty() {
+        Vec::new()
+    } else {
+        state.repos.users.find_summaries(doc! { "_id": { "$in": ids } }).await?
+    };
+    let assignee = users.iter().find(|u| task.assignee_id.as_deref() == Some(u.id.as_str())).cloned();
+    let export = TaskExport {
+        exported_at: state.clock.now(),
+        assignee,
+        cti,
+        history,
+        attachments,
+        usernames: users.into_iter().map(|u| (u.id, u.username)).collect(),
+        task,
+    };
+    Ok(match params.format {
+        TaskExportFormat::Json => Json(export).into_response(),
+        TaskExportFormat::Markdown => {
+            ([(header::CONTENT_TYPE, "text/markdown; charset=utf-8")], export.to_markdown()).into_response()
+        }
+    })
+}
@@ -1,18 +1,49 @@
+use std::convert::Infallible;
+
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use bson::{doc, to_bson};
-use chrono::Utc;
-use mongodb::options::FindOptions;
-use serde::{Deserialize, Deserializer};
+use bson::{doc, to_bson, Bson, Document};
+use chrono::{DateTime, Utc};
+use mongodb::options::UpdateModifications;
+use futures_util::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{
-    errors::{AppError, AppResult},
-    handlers::auth::{AppState, Claims},
+    config::TaskQuotas,
+    db::{TaskRepo, TASKS, WORKSPACE_MEMBERS},
+    errors::{AppError, AppResult, FieldError},
+    events::{Actor, DomainEvent},
+    features::{TASK_BOARD, TASK_STREAM},
+    extract::{AppJson, AppPath},
+    handlers::{
+        attachments::{delete_task_attachments, discard_attachments, store_attachments, NoteUpload},
+        auth::{AppState, Claims, CurrentUser},
+        revisions::{delete_revisions, record_revision},
+        shares::delete_shares,
+        teams::find_team,
+        views::find_view,
+        Created,
+    },
+    markup::prepare_note,
+    models::board::{board_pipeline, board_statuses, BoardQuery, TaskBoard},
     models::cti::CtiSelection,
-    models::task::{PaginatedTasksResponse, Task, TaskNote, TaskQuery},
+    models::dates::{to_bson_date, to_stored_document},
+    models::id::Id,
+    models::saved_view::ViewSelection,
+    models::task::{restrict_to_members, Task, TaskNote, TaskQuery},
+    models::task_fields::FieldsQuery,
+    models::workflow::Workflow,
+    models::workspace::Membership,
+    pagination::PageQuery,
+    permissions::{has_permission, TASKS_ASSIGN, TASKS_EXPORT, USERS_MANAGE},
 };
 
 /// Custom deserializer that wraps a present field (even if null) in `Some`.
@@ -34,13 +65,15 @@ pub struct CreateTaskRequest {
     pub description: String,
     pub assignee_id: Option<String>,
     pub cti: Option<CtiSelection>,
+    #[serde(default)]
+    pub due_at: Option<DateTime<Utc>>,
 }
 
 /// For update requests we use `Option<Option<T>>` so the client can:
 ///   - omit a field entirely (outer None) → no change
 ///   - send `null` (Some(None)) → clear the field
 ///   - send a value (Some(Some(v))) → set the field
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct UpdateTaskRequest {
     pub title: Option<String>,
     pub description: Option<String>,
@@ -49,6 +82,16 @@ pub struct UpdateTaskRequest {
     pub assignee_id: Option<Option<String>>,
     #[serde(default, deserialize_with = "optional_nullable")]
     pub cti: Option<Option<CtiSelection>>,
+    #[serde(default, deserialize_with = "optional_nullable")]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// Body of POST /api/tasks/:id/assign. `assignee_id` must be present;
+/// `null` unassigns.
+#[derive(Debug, Deserialize)]
+pub struct AssignTaskRequest {
+    #[serde(deserialize_with = "optional_nullable")]
+    pub assignee_id: Option<Option<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,117 +99,559 @@ pub struct AddNoteRequest {
     pub note: String,
 }
 
-pub async fn list_tasks(
-    axum::Extension(_claims): axum::Extension<Claims>,
-    State(state): State<AppState>,
-    Query(params): Query<TaskQuery>,
-) -> AppResult<Json<PaginatedTasksResponse>> {
-    if params.limit == 0 || params.limit > 100 {
-        return Err(AppError::BadRequest(
-            "limit must be between 1 and 100".to_string(),
-        ));
+/// Whether `claims` may edit or delete `task` (or its notes) when open task
+/// editing is turned off: its creator, its assignee or a workspace admin.
+pub fn can_modify_task(claims: &Claims, task: &Task) -> bool {
+    task.created_by.as_deref() == Some(claims.sub.as_str())
+        || task.assignee_id.as_deref() == Some(claims.sub.as_str())
+        || claims.workspace_permits(USERS_MANAGE)
+}
+
+/// 404 for unknown tasks, 403 when the caller may not modify the task.
+/// A no-op when `OPEN_TASK_EDITING` is on.
+pub async fn authorize_task_edit(state: &AppState, claims: &Claims, id: &str) -> AppResult<()> {
+    if state.config.open_task_editing {
+        return Ok(());
     }
-    if params.page == 0 {
-        return Err(AppError::BadRequest("page must be >= 1".to_string()));
+    let task = state.repos.tasks.find_by_id(claims.workspace()?, id).await?.ok_or(AppError::NotFound)?;
+    if !can_modify_task(claims, &task) {
+        return Err(AppError::Forbidden);
     }
+    Ok(())
+}
 
-    let statuses = params.parsed_statuses().map_err(AppError::BadRequest)?;
+/// `{ $cond: [<field> changes to value, then, <field>'s current value] }`,
+/// for stamps that only move when `field` really changes. A missing field
+/// counts as null.
+fn if_changed(field: &str, value: &Bson, then: Bson, stamp: &str) -> Document {
+    doc! {
+        "$cond": [
+            { "$ne": [{ "$ifNull": [format!("${field}"), null] }, { "$literal": value.clone() }] },
+            { "$literal": then },
+            format!("${stamp}"),
+        ]
+    }
+}
 
-    let filter = match statuses {
-        None => doc! {},
-        Some(list) => doc! { "status": { "$in": list } },
-    };
+/// Turns a plain `$set` document into a pipeline `$set` stage. Values are
+/// wrapped in `$literal` so user input starting with `$` is not read as a
+/// field path. A status change also stamps `status_changed_at`, and an
+/// assignee change `assigned_by` (as `actor`) and `assigned_at`; re-sending
+/// the current value leaves them alone.
+fn update_stage(set_doc: Document, new_status: Option<&str>, actor: &str, now: Bson) -> Document {
+    let mut stage = Document::new();
+    if let Some(status) = new_status {
+        stage.insert("status_changed_at", if_changed("status", &status.into(), now.clone(), "status_changed_at"));
+    }
+    if let Some(assignee) = set_doc.get("assignee_id") {
+        stage.insert("assigned_by", if_changed("assignee_id", assignee, actor.into(), "assigned_by"));
+        stage.insert("assigned_at", if_changed("assignee_id", assignee, now, "assigned_at"));
+    }
+    for (k, v) in set_doc {
+        stage.insert(k, doc! { "$literal": v });
+    }
+    doc! { "$set": stage }
+}
+
+/// Whether the update stamped at `now` is the one that moved `task` into a
+/// terminal status.
+fn just_completed(task: &Task, workflow: &Workflow, now: DateTime<Utc>) -> bool {
+    workflow.is_terminal(&task.status) && task.status_changed_at == Some(now)
+}
 
-    let collection = state.db.collection::<Task>("tasks");
+/// Whether the update stamped at `now` changed `task`'s assignee.
+fn just_assigned(task: &Task, now: DateTime<Utc>) -> bool {
+    task.assigned_at == Some(now)
+}
 
-    let total = collection
-        .count_documents(filter.clone(), None)
-        .await
-        .map_err(AppError::Database)?;
+/// Fields whose value differs between `before` and `after`.
+fn changed_fields(before: &Task, after: &Task) -> Vec<&'static str> {
+    let mut changes = Vec::new();
+    if before.title != after.title {
+        changes.push("title");
+    }
+    if before.description != after.description {
+        changes.push("description");
+    }
+    if before.status != after.status {
+        changes.push("status");
+    }
+    if before.assignee_id != after.assignee_id {
+        changes.push("assignee_id");
+    }
+    if before.cti != after.cti {
+        changes.push("cti");
+    }
+    changes
+}
 
-    let skip = (params.page - 1) * params.limit;
-    let options = FindOptions::builder()
-        .skip(skip)
-        .limit(params.limit as i64)
-        .sort(doc! { "created_at": -1 })
-        .build();
+/// The events for an update stamped at `now` that turned `before` into
+/// `task`: `TaskUpdated`, plus `TaskCompleted`, `UserAssigned` and
+/// `CtiChanged` where they apply. Nothing when no value changed.
+fn update_events(before: &Task, task: &Task, workflow: &Workflow, now: DateTime<Utc>, actor: Actor) -> Vec<DomainEvent> {
+    let changes = changed_fields(before, task);
+    if changes.is_empty() {
+        return Vec::new();
+    }
+    let cti_changed = changes.contains(&"cti");
+    let mut events = vec![DomainEvent::TaskUpdated { task: task.clone(), changes, actor: actor.clone() }];
+    if just_completed(task, workflow, now) {
+        events.push(DomainEvent::TaskCompleted { task: task.clone(), actor: actor.clone() });
+    }
+    // Only a change of assignee is news; clients often re-send the current
+    // one with every edit.
+    if just_assigned(task, now) {
+        let assignee = task.assignee_id.clone();
+        events.push(DomainEvent::UserAssigned { task: task.clone(), assignee, actor: actor.clone() });
+    }
+    if cti_changed {
+        events.push(DomainEvent::CtiChanged { task: task.clone(), actor });
+    }
+    events
+}
 
-    let mut cursor = collection
-        .find(filter, options)
-        .await
-        .map_err(AppError::Database)?;
+/// 422 unless `assignee` is an active user who belongs to workspace `ws`.
+async fn check_assignee(state: &AppState, ws: &str, assignee: &str) -> AppResult<()> {
+    let unknown = || FieldError::new("assignee_id", "unknown_user", format!("no active member '{assignee}' in this workspace"));
+    let user = state.repos.users.find_by_id(assignee).await?;
+    if !user.is_some_and(|u| u.active) {
+        return Err(unknown().into());
+    }
+    let membership = state
+        .db
+        .collection::<Document>(WORKSPACE_MEMBERS)
+        .find_one(doc! { "_id": Membership::id_for(ws, assignee) }, None)
+        .await?;
+    if membership.is_none() {
+        return Err(unknown().into());
+    }
+    Ok(())
+}
 
-    let mut tasks = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        tasks.push(cursor.deserialize_current().map_err(AppError::Database)?);
+/// Runs a guarded update. When nothing matched, tells a missing task (404)
+/// apart from one the guard refused, i.e. a reassignment the caller may not
+/// make (403).
+async fn apply_update(
+    tasks: &dyn TaskRepo,
+    ws: &str,
+    id: &str,
+    guard: Document,
+    update: UpdateModifications,
+) -> AppResult<Task> {
+    match tasks.update_fields(ws, id, guard, update).await? {
+        Some(task) => Ok(task),
+        None if tasks.find_by_id(ws, id).await?.is_some() => Err(AppError::Forbidden),
+        None => Err(AppError::NotFound),
     }
+}
 
-    let total_pages = if total == 0 {
-        1
-    } else {
-        (total + params.limit - 1) / params.limit
+/// Carries the total on `HEAD /api/tasks`.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Response for GET /api/tasks/count.
+#[derive(Debug, Serialize)]
+pub struct TaskCount {
+    pub total: u64,
+}
+
+/// The filter and sort for a task list query, shared by the list, count and
+/// `HEAD` endpoints so they reject the same input. `errors` carries any
+/// found already, e.g. in the page parameters.
+async fn task_filter(
+    state: &AppState,
+    claims: &Claims,
+    params: TaskQuery,
+    selection: ViewSelection,
+    mut errors: Vec<FieldError>,
+) -> AppResult<(Document, Document)> {
+    let params = match selection.view.as_deref() {
+        None => params,
+        Some(id) => match find_view(state, claims, id).await? {
+            Some(view) => params.merged_over(&view.filter),
+            None => {
+                errors.push(FieldError::new("view", "unknown_view", format!("no saved view '{id}'")));
+                params
+            }
+        },
     };
+    let workflow = state.workflow.get(&state.db).await?;
+    let (mut filter, sort) = params.to_find(&workflow).unwrap_or_else(|e| {
+        errors.extend(e);
+        Default::default()
+    });
+    if let Some(id) = params.team() {
+        match find_team(state, claims.workspace()?, id).await? {
+            Some(team) => restrict_to_members(&mut filter, team.member_ids),
+            None => errors.push(FieldError::new("team_id", "unknown_team", format!("no team '{id}'"))),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    Ok((filter, sort))
+}
+
+/// GET /api/tasks. With `?view=`, the saved view's filter applies to any
+/// parameter not given explicitly; with `?fields=`, only those fields are
+/// read and returned.
+pub async fn list_tasks(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    Query(params): Query<TaskQuery>,
+    Query(selection): Query<ViewSelection>,
+    Query(fields): Query<FieldsQuery>,
+) -> AppResult<Response> {
+    let fields = fields.parse()?;
+    let (page, errors) = page.resolve(&state.config.page_limits);
+    let (filter, sort) = task_filter(&state, &claims, params, selection, errors).await?;
+    let (ws, strict) = (claims.workspace()?, state.config.strict_pagination);
+    Ok(match fields {
+        Some(fields) => {
+            Json(state.repos.tasks.find_page_fields(ws, filter, sort, &fields, page).await?.in_range(strict)?).into_response()
+        }
+        None => Json(state.repos.tasks.find_page(ws, filter, sort, page).await?.in_range(strict)?).into_response(),
+    })
+}
 
-    Ok(Json(PaginatedTasksResponse {
-        tasks,
-        total,
-        page: params.page,
-        limit: params.limit,
-        total_pages,
-    }))
+/// GET /api/tasks/count — how many tasks the same query as GET /api/tasks
+/// matches, without reading any.
+pub async fn count_tasks(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(params): Query<TaskQuery>,
+    Query(selection): Query<ViewSelection>,
+) -> AppResult<Json<TaskCount>> {
+    let (filter, _) = task_filter(&state, &claims, params, selection, Vec::new()).await?;
+    Ok(Json(TaskCount { total: state.repos.tasks.count(claims.workspace()?, filter).await? }))
 }
 
+/// HEAD /api/tasks — the count as `X-Total-Count`, with no body.
+pub async fn head_tasks(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(params): Query<TaskQuery>,
+    Query(selection): Query<ViewSelection>,
+) -> AppResult<impl IntoResponse> {
+    let (filter, _) = task_filter(&state, &claims, params, selection, Vec::new()).await?;
+    let total = state.repos.tasks.count(claims.workspace()?, filter).await?;
+    Ok([(HeaderName::from_static(TOTAL_COUNT_HEADER), total.to_string())])
+}
+
+/// GET /api/tasks/board — a column per status with its first tasks and
+/// total, for the Kanban view. Takes the filters GET /api/tasks does;
+/// `sort` orders each column.
+pub async fn task_board(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(board): Query<BoardQuery>,
+    Query(params): Query<TaskQuery>,
+    Query(selection): Query<ViewSelection>,
+) -> AppResult<Json<TaskBoard>> {
+    state.config.feature_flags.require(TASK_BOARD, &claims)?;
+    let per_column = board.per_column(state.config.board_column_size, state.config.page_limits.max);
+    let errors = per_column.as_ref().err().cloned().into_iter().collect();
+    let (mut filter, sort) = task_filter(&state, &claims, params, selection, errors).await?;
+    let per_column = per_column?;
+    filter.insert("workspace_id", claims.workspace()?);
+    let workflow = state.workflow.get(&state.db).await?;
+    let statuses = board_statuses(&workflow, &filter);
+    let pipeline = board_pipeline(filter, sort, &statuses, per_column);
+    let mut cursor = state.db.collection::<Document>(TASKS).aggregate(pipeline, None).await?;
+    let result = if cursor.advance().await? { cursor.deserialize_current()? } else { Document::new() };
+    let board = TaskBoard::from_facets(&result, &workflow, &statuses).map_err(|e| AppError::Internal(e.into()))?;
+    Ok(Json(board))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateTaskQuery {
+    /// Create the task even if it looks like a double submit.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// An earlier task, returned with 200 in place of a double-submitted create.
+#[derive(Debug, Serialize)]
+pub struct SuppressedDuplicate {
+    #[serde(flatten)]
+    pub task: Task,
+    pub duplicate_suppressed: bool,
+}
+
+/// The newest task `creator` created with exactly `title` since `since`.
+/// Served by the `{workspace_id, created_by, title, created_at}` index.
+async fn recent_duplicate(
+    tasks: &dyn TaskRepo,
+    ws: &str,
+    creator: &str,
+    title: &str,
+    since: DateTime<Utc>,
+) -> AppResult<Option<Task>> {
+    let filter = doc! { "created_by": creator, "title": title, "created_at": { "$gte": to_bson_date(since) } };
+    tasks.find_first(ws, filter, doc! { "created_at": -1 }).await
+}
+
+/// POST /api/tasks. Repeating one's own title within
+/// `DUPLICATE_TASK_WINDOW_SECONDS` returns the earlier task instead, unless
+/// `?force=true`.
 pub async fn create_task(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateTaskRequest>,
-) -> AppResult<(StatusCode, Json<Task>)> {
-    let mut task = Task::new(payload.title, payload.description);
+    Query(params): Query<CreateTaskQuery>,
+    AppJson(payload): AppJson<CreateTaskRequest>,
+) -> AppResult<Response> {
+    let window = state.config.duplicate_task_window_seconds;
+    if window > 0 && !params.force {
+        let since = state.clock.now() - chrono::Duration::seconds(window as i64);
+        let tasks = state.repos.tasks.as_ref();
+        if let Some(task) = recent_duplicate(tasks, claims.workspace()?, &claims.sub, payload.title.trim(), since).await? {
+            return Ok(Json(SuppressedDuplicate { task, duplicate_suppressed: true }).into_response());
+        }
+    }
+    let task = create(&state, &claims, payload).await?;
+    Ok(Created::at(format!("/tasks/{}", task.id), task).into_response())
+}
+
+/// Fails with `QuotaExceeded` once `current` reaches a non-zero `limit`.
+fn check_quota(quota: &'static str, current: u64, limit: u64) -> AppResult<()> {
+    if limit > 0 && current >= limit {
+        return Err(AppError::QuotaExceeded { quota, current, limit });
+    }
+    Ok(())
+}
+
+/// Admins are exempt from `TASK_QUOTA_*`.
+fn quotas_for(state: &AppState, claims: &Claims) -> Option<TaskQuotas> {
+    (!has_permission(&claims.role, USERS_MANAGE)).then_some(state.config.task_quotas)
+}
+
+/// The creation quotas, each checked with one indexed count. Concurrent
+/// creates can overshoot a limit by a few; these are soft limits.
+async fn check_create_quotas(
+    tasks: &dyn TaskRepo,
+    workflow: &Workflow,
+    quotas: &TaskQuotas,
+    ws: &str,
+    creator: &str,
+    assignee: Option<&str>,
+    now: DateTime<Utc>,
+) -> AppResult<()> {
+    if quotas.max_tasks > 0 {
+        check_quota("tasks", tasks.count(ws, doc! {}).await?, quotas.max_tasks)?;
+    }
+    if quotas.max_created_per_hour > 0 {
+        let since = to_bson_date(now - chrono::Duration::hours(1));
+        let recent = tasks.count(ws, doc! { "created_by": creator, "created_at": { "$gte": since } }).await?;
+        check_quota("created_per_hour", recent, quotas.max_created_per_hour)?;
+    }
+    if let Some(assignee) = assignee.filter(|_| quotas.max_open_per_assignee > 0) {
+        let open = tasks.count(ws, doc! { "assignee_id": assignee, "status": { "$in": workflow.open_keys() } }).await?;
+        check_quota("open_per_assignee", open, quotas.max_open_per_assignee)?;
+    }
+    Ok(())
+}
+
+/// Creates a task in the caller's workspace, for REST and GraphQL alike.
+pub async fn create(state: &AppState, claims: &Claims, payload: CreateTaskRequest) -> AppResult<Task> {
+    let ws = claims.workspace()?;
+    if let Some(assignee) = &payload.assignee_id {
+        check_assignee(state, ws, assignee).await?;
+    }
+    let workflow = state.workflow.get(&state.db).await?;
+    if let Some(quotas) = quotas_for(state, claims) {
+        let assignee = payload.assignee_id.as_deref();
+        let tasks = state.repos.tasks.as_ref();
+        check_create_quotas(tasks, &workflow, &quotas, ws, &claims.sub, assignee, state.clock.now()).await?;
+    }
+    let title = payload.title.trim().to_string();
+    let mut task = Task::new(state.clock.as_ref(), state.ids.as_ref(), title, payload.description);
+    task.workspace_id = ws.to_string();
+    task.status = workflow.initial().to_string();
+    if payload.assignee_id.is_some() {
+        task.assigned_by = Some(claims.sub.clone());
+        task.assigned_at = Some(task.created_at);
+    }
     task.assignee_id = payload.assignee_id;
     task.cti = payload.cti;
+    task.due_at = payload.due_at;
+    task.created_by = Some(claims.sub.clone());
 
-    let collection = state.db.collection::<Task>("tasks");
-    collection
-        .insert_one(&task, None)
-        .await
-        .map_err(AppError::Database)?;
-    Ok((StatusCode::CREATED, Json(task)))
+    state.repos.tasks.insert(ws, &task).await?;
+    state.events.publish(DomainEvent::TaskCreated { task: task.clone(), actor: claims.into() });
+    Ok(task)
 }
 
+/// GET /api/tasks/:id, optionally with `?fields=` as for the list.
 pub async fn get_task(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
-) -> AppResult<Json<Task>> {
-    let collection = state.db.collection::<Task>("tasks");
-    let task = collection
-        .find_one(doc! { "_id": &id }, None)
-        .await
-        .map_err(AppError::Database)?
-        .ok_or(AppError::NotFound)?;
-    Ok(Json(task))
+    AppPath(id): AppPath<Id>,
+    Query(fields): Query<FieldsQuery>,
+) -> AppResult<Response> {
+    let ws = claims.workspace()?;
+    Ok(match fields.parse()? {
+        Some(fields) => {
+            Json(state.repos.tasks.find_by_id_fields(ws, &id, &fields).await?.ok_or(AppError::NotFound)?).into_response()
+        }
+        None => Json(state.repos.tasks.find_by_id(ws, &id).await?.ok_or(AppError::NotFound)?).into_response(),
+    })
 }
 
 pub async fn update_task(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+    AppJson(payload): AppJson<UpdateTaskRequest>,
+) -> AppResult<Json<Task>> {
+    Ok(Json(update(&state, &claims, &id, payload).await?))
+}
+
+/// POST /api/tasks/:id/assign — the same as an update of only `assignee_id`.
+pub async fn assign_task(
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
-    Json(payload): Json<UpdateTaskRequest>,
+    AppPath(id): AppPath<Id>,
+    AppJson(payload): AppJson<AssignTaskRequest>,
 ) -> AppResult<Json<Task>> {
-    let collection = state.db.collection::<Task>("tasks");
+    let payload = UpdateTaskRequest {
+        title: None,
+        description: None,
+        status: None,
+        assignee_id: payload.assignee_id,
+        cti: None,
+        due_at: None,
+    };
+    Ok(Json(update(&state, &claims, &id, payload).await?))
+}
+
+/// POST /api/tasks/:id/claim — assigns an unassigned task to the caller.
+/// Any workspace member may claim. The write only matches while the task is
+/// unassigned, so of two simultaneous claims one gets 409 `already_assigned`.
+pub async fn claim_task(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<Task>> {
+    let ws = claims.workspace()?;
+    let mut before = state.repos.tasks.find_by_id(ws, &id).await?.ok_or(AppError::NotFound)?;
+    let mut retried = false;
+    loop {
+        if let Some(assignee_id) = before.assignee_id.clone() {
+            return Err(AppError::AlreadyAssigned { assignee_id: Some(assignee_id) });
+        }
+        let guard = doc! { "assignee_id": Bson::Null };
+        if let Some(task) = set_assignee(&state, &claims, &before, guard, Bson::String(claims.sub.clone())).await? {
+            return Ok(Json(task));
+        }
+        // Someone else claimed it between the read and the write; if they
+        // have released it again meanwhile, try once more.
+        before = state.repos.tasks.find_by_id(ws, &id).await?.ok_or(AppError::NotFound)?;
+        if retried && before.assignee_id.is_none() {
+            return Err(AppError::AlreadyAssigned { assignee_id: None });
+        }
+        retried = true;
+    }
+}
+
+/// POST /api/tasks/:id/unassign — for the current assignee, or a workspace
+/// admin. Unassigning an unassigned task changes nothing.
+pub async fn unassign_task(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Json<Task>> {
+    let ws = claims.workspace()?;
+    let before = state.repos.tasks.find_by_id(ws, &id).await?.ok_or(AppError::NotFound)?;
+    let Some(assignee) = before.assignee_id.clone() else {
+        return Ok(Json(before));
+    };
+    // For anyone but an admin, only while the task is still the caller's.
+    let guard = if claims.workspace_permits(USERS_MANAGE) {
+        doc! {}
+    } else if assignee == claims.sub {
+        doc! { "assignee_id": &claims.sub }
+    } else {
+        return Err(AppError::Forbidden);
+    };
+    match set_assignee(&state, &claims, &before, guard, Bson::Null).await? {
+        Some(task) => Ok(Json(task)),
+        None => match state.repos.tasks.find_by_id(ws, &id).await? {
+            Some(task) if task.assignee_id.is_none() => Ok(Json(task)),
+            Some(_) => Err(AppError::Forbidden),
+            None => Err(AppError::NotFound),
+        },
+    }
+}
+
+/// Sets `before`'s assignee to `assignee` if `guard` still matches, stamping
+/// and publishing the change as `update` does. `None` when the guard failed.
+async fn set_assignee(
+    state: &AppState,
+    claims: &Claims,
+    before: &Task,
+    guard: Document,
+    assignee: Bson,
+) -> AppResult<Option<Task>> {
+    let ws = claims.workspace()?;
+    let now_dt = state.clock.now();
+    let now = to_bson_date(now_dt);
+    let set_doc = doc! { "updated_at": now.clone(), "assignee_id": assignee };
+    let pipeline = vec![update_stage(set_doc, None, &claims.sub, now)];
+    let Some(task) = state.repos.tasks.update_fields(ws, &before.id, guard, pipeline.into()).await? else {
+        return Ok(None);
+    };
+    let workflow = state.workflow.get(&state.db).await?;
+    for event in update_events(before, &task, &workflow, now_dt, claims.into()) {
+        state.events.publish(event);
+    }
+    Ok(Some(task))
+}
+
+/// Applies `payload` to task `id`, for REST and GraphQL alike. Every
+/// assignment goes through here, so `UserAssigned` is always published.
+pub async fn update(state: &AppState, claims: &Claims, id: &str, payload: UpdateTaskRequest) -> AppResult<Task> {
+    authorize_task_edit(state, claims, id).await?;
+    let ws = claims.workspace()?;
+    let workflow = state.workflow.get(&state.db).await?;
+    if let Some(status) = &payload.status {
+        workflow.validate(status)?;
+    }
+    if let Some(Some(assignee)) = &payload.assignee_id {
+        check_assignee(state, ws, assignee).await?;
+    }
 
-    let mut set_doc = doc! { "updated_at": to_bson(&Utc::now()).unwrap() };
+    // Read first so the update's changes can be told apart from re-sent
+    // values, and a replaced description kept as a revision.
+    let before = state.repos.tasks.find_by_id(ws, id).await?.ok_or(AppError::NotFound)?;
+
+    let now_dt = state.clock.now();
+    let now = to_bson_date(now_dt);
+    let mut set_doc = doc! { "updated_at": now.clone() };
     if let Some(title) = payload.title {
         set_doc.insert("title", title);
     }
     if let Some(description) = payload.description {
         set_doc.insert("description", description);
     }
+    let new_status = payload.status.clone();
     if let Some(status) = payload.status {
         set_doc.insert("status", status);
     }
+    let mut guard = doc! {};
     // assignee_id: Some(None) → clear, Some(Some(v)) → set
     if let Some(assignee) = payload.assignee_id {
+        // Taking a task away from someone else needs tasks:assign; picking up
+        // an unassigned task or handing off your own does not.
+        if !claims.workspace_permits(TASKS_ASSIGN) {
+            guard.insert(
+                "$or",
+                vec![
+                    doc! { "assignee_id": bson::Bson::Null },
+                    doc! { "assignee_id": &claims.sub },
+                    doc! { "assignee_id": assignee.clone() },
+                ],
+            );
+        }
         match assignee {
             None => set_doc.insert("assignee_id", bson::Bson::Null),
             Some(v) => set_doc.insert("assignee_id", v),
@@ -182,93 +667,219 @@ pub async fn update_task(
             ),
         };
     }
+    if let Some(due_at) = payload.due_at {
+        set_doc.insert("due_at", due_at.map_or(bson::Bson::Null, to_bson_date));
+    }
 
-    let options = mongodb::options::FindOneAndUpdateOptions::builder()
-        .return_document(mongodb::options::ReturnDocument::After)
-        .build();
-
-    let task = collection
-        .find_one_and_update(doc! { "_id": &id }, doc! { "$set": set_doc }, options)
-        .await
-        .map_err(AppError::Database)?
-        .ok_or(AppError::NotFound)?;
-
-    Ok(Json(task))
+    let pipeline = vec![update_stage(set_doc, new_status.as_deref(), &claims.sub, now)];
+    let task = apply_update(state.repos.tasks.as_ref(), ws, id, guard, pipeline.into()).await?;
+    // Revisions are written here rather than by a subscriber so the history
+    // is complete as soon as the edit returns.
+    if before.description != task.description {
+        // The edit itself has happened; losing its history is not worth failing it.
+        if let Err(e) = record_revision(state, claims, id, "description", &before.description).await {
+            tracing::warn!(task_id = %id, "Could not record a description revision: {e}");
+        }
+    }
+    for event in update_events(&before, &task, &workflow, now_dt, claims.into()) {
+        state.events.publish(event);
+    }
+    Ok(task)
 }
 
 pub async fn delete_task(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
+    AppPath(id): AppPath<Id>,
 ) -> AppResult<StatusCode> {
-    let collection = state.db.collection::<Task>("tasks");
-    let result = collection
-        .delete_one(doc! { "_id": &id }, None)
-        .await
-        .map_err(AppError::Database)?;
-
-    if result.deleted_count == 0 {
+    authorize_task_edit(&state, &claims, &id).await?;
+    let ws = claims.workspace()?;
+    if !state.repos.tasks.delete(ws, &id).await? {
         return Err(AppError::NotFound);
     }
+    if let Err(e) = delete_revisions(&state.db, ws, &id).await {
+        tracing::warn!(task_id = %*id, "Could not delete the revisions of a deleted task: {e}");
+    }
+    if let Err(e) = delete_shares(&state.db, ws, &id).await {
+        tracing::warn!(task_id = %*id, "Could not delete the share links of a deleted task: {e}");
+    }
+    if let Err(e) = delete_task_attachments(&state.db, ws, &id).await {
+        tracing::warn!(task_id = %*id, "Could not delete the note attachments of a deleted task: {e}");
+    }
+    let event = DomainEvent::TaskDeleted { workspace_id: ws.to_string(), task_id: id.to_string(), actor: (&claims).into() };
+    state.events.publish(event);
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// POST /api/tasks/:id/notes — JSON, or multipart with files; see
+/// `handlers::attachments`.
 pub async fn add_note(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path(id): Path<String>,
-    Json(payload): Json<AddNoteRequest>,
+    AppPath(id): AppPath<Id>,
+    upload: NoteUpload,
 ) -> AppResult<Json<Task>> {
-    let note = TaskNote::new(payload.note, claims.sub);
-    let note_bson = to_bson(&note).map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
-
-    let collection = state.db.collection::<Task>("tasks");
-    let options = mongodb::options::FindOneAndUpdateOptions::builder()
-        .return_document(mongodb::options::ReturnDocument::After)
-        .build();
-
-    let task = collection
-        .find_one_and_update(
-            doc! { "_id": &id },
-            doc! { "$push": { "notes": note_bson }, "$set": { "updated_at": to_bson(&Utc::now()).unwrap() } },
-            options,
-        )
-        .await
-        .map_err(AppError::Database)?
-        .ok_or(AppError::NotFound)?;
+    let body = prepare_note(state.config.note_format, &upload.note.note)?;
+    authorize_task_edit(&state, &claims, &id).await?;
+    let ws = claims.workspace()?;
+    let mut note = TaskNote::new(state.clock.as_ref(), state.ids.as_ref(), body.note, claims.sub.clone());
+    note.rendered_html = body.rendered_html;
+    note.plain_text = body.plain_text;
+    note.seq = next_note_seq(state.repos.tasks.as_ref(), ws, &id).await?;
+    if !upload.files.is_empty() {
+        note.attachment_ids = store_attachments(&state, ws, &id, &note.id, &claims.sub, upload.files).await?;
+    }
+    let note_bson = to_stored_document(&note).map_err(AppError::Internal)?;
 
+    let update = doc! { "$push": { "notes": note_bson }, "$set": { "updated_at": to_bson_date(note.created_at) } };
+    let limit = quotas_for(&state, &claims).map_or(0, |q| q.max_notes_per_task);
+    let task = match push_note(state.repos.tasks.as_ref(), ws, &id, update, limit).await {
+        Ok(task) => task,
+        Err(e) => {
+            discard_attachments(&state, &note.attachment_ids).await;
+            return Err(e);
+        }
+    };
+    state.events.publish(DomainEvent::NoteAdded { task: task.clone(), note, actor: (&claims).into() });
     Ok(Json(task))
 }
 
+/// Takes the next note sequence number for task `id`. Numbers taken by a
+/// note the quota then refuses are skipped, never reused.
+async fn next_note_seq(tasks: &dyn TaskRepo, ws: &str, id: &str) -> AppResult<i64> {
+    let update = doc! { "$inc": { "note_seq": 1 } };
+    let task = tasks.update_fields(ws, id, doc! {}, update.into()).await?.ok_or(AppError::NotFound)?;
+    Ok(task.note_seq)
+}
+
+/// Applies `update` unless the task already has `limit` notes (0 for no
+/// limit), checked in the same write so concurrent notes can't overshoot.
+async fn push_note(tasks: &dyn TaskRepo, ws: &str, id: &str, update: Document, limit: u64) -> AppResult<Task> {
+    let guard = match limit {
+        0 => doc! {},
+        n => doc! { format!("notes.{}", n - 1): { "$exists": false } },
+    };
+    if let Some(task) = tasks.update_fields(ws, id, guard, update.into()).await? {
+        return Ok(task);
+    }
+    match tasks.find_by_id(ws, id).await? {
+        Some(task) => Err(AppError::QuotaExceeded { quota: "notes_per_task", current: task.notes.len() as u64, limit }),
+        None => Err(AppError::NotFound),
+    }
+}
+
 pub async fn delete_note(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Path((task_id, note_id)): Path<(String, String)>,
+    AppPath((task_id, note_id)): AppPath<(Id, Id)>,
 ) -> AppResult<Json<Task>> {
-    let collection = state.db.collection::<Task>("tasks");
-    let options = mongodb::options::FindOneAndUpdateOptions::builder()
-        .return_document(mongodb::options::ReturnDocument::After)
-        .build();
-
-    let task = collection
-        .find_one_and_update(
-            doc! { "_id": &task_id },
-            doc! {
-                "$pull": { "notes": { "_id": &note_id } },
-                "$set": { "updated_at": to_bson(&Utc::now()).unwrap() }
-            },
-            options,
-        )
-        .await
-        .map_err(AppError::Database)?
+    authorize_task_edit(&state, &claims, &task_id).await?;
+    let ws = claims.workspace()?;
+    let before = state.repos.tasks.find_by_id(ws, &task_id).await?.ok_or(AppError::NotFound)?;
+    let attachment_ids =
+        before.notes.into_iter().find(|n| n.id == *note_id).map(|n| n.attachment_ids).unwrap_or_default();
+    let now = to_bson_date(state.clock.now());
+    let update = doc! {
+        "$pull": { "notes": { "_id": &note_id } },
+        "$set": { "updated_at": now }
+    };
+    let task = state
+        .repos
+        .tasks
+        .update_fields(ws, &task_id, doc! {}, update.into())
+        .await?
         .ok_or(AppError::NotFound)?;
+    discard_attachments(&state, &attachment_ids).await;
 
     Ok(Json(task))
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamQuery {
+    /// RFC 3339; only tasks updated at or after this instant are sent.
+    pub updated_since: Option<String>,
+}
+
+/// One line of `GET /api/tasks/stream`. The last line is always `end` or,
+/// if the read failed part way, `error`; a stream with neither was cut off.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum StreamLine {
+    Task { task: Box<Task> },
+    /// `as_of` is when the read started; pass it as the next `updated_since`.
+    End { count: u64, as_of: DateTime<Utc> },
+    Error { message: String, count: u64 },
+}
+
+impl StreamLine {
+    fn to_bytes(&self) -> Bytes {
+        let mut bytes = serde_json::to_vec(self).expect("task lines always serialize");
+        bytes.push(b'\n');
+        bytes.into()
+    }
+}
+
+/// Renders `tasks` as NDJSON, ending with a summary line. A read error
+/// becomes the final line instead of a silently short stream.
+fn ndjson_lines(
+    tasks: BoxStream<'static, AppResult<Task>>,
+    as_of: DateTime<Utc>,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    stream::unfold(Some((tasks, 0u64)), move |state| async move {
+        let (mut tasks, count) = state?;
+        let (line, next) = match tasks.next().await {
+            Some(Ok(task)) => (StreamLine::Task { task: Box::new(task) }, Some((tasks, count + 1))),
+            Some(Err(e)) => {
+                tracing::error!(count, "Task stream aborted: {e}");
+                (StreamLine::Error { message: "reading tasks failed; the export is incomplete".into(), count }, None)
+            }
+            None => (StreamLine::End { count, as_of }, None),
+        };
+        Some((Ok(line.to_bytes()), next))
+    })
+}
+
+/// GET /api/tasks/stream?updated_since= — every task in the workspace as
+/// NDJSON, oldest update first, read from the cursor as the client consumes
+/// it. Admins, or API keys with the `tasks:export` scope.
+pub async fn stream_tasks(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Query(params): Query<StreamQuery>,
+) -> AppResult<Response> {
+    state.config.feature_flags.require(TASK_STREAM, &claims)?;
+    // Keys were already checked for `tasks:export` by `require_auth`.
+    if claims.scopes.is_none() && !claims.workspace_permits(TASKS_EXPORT) {
+        return Err(AppError::Forbidden);
+    }
+    let mut filter = doc! {};
+    if let Some(since) = params.updated_since.as_deref() {
+        let since = DateTime::parse_from_rfc3339(since).map_err(|_| {
+            AppError::Validation(vec![FieldError::new(
+                "updated_since",
+                "invalid_timestamp",
+                "must be an RFC 3339 timestamp",
+            )])
+        })?;
+        filter.insert("updated_at", doc! { "$gte": to_bson_date(since.with_timezone(&Utc)) });
+    }
+    let as_of = state.clock.now();
+    let sort = doc! { "updated_at": 1, "_id": 1 };
+    let tasks = state.repos.tasks.find_stream(claims.workspace()?, filter, sort).await?;
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(ndjson_lines(tasks, as_of))).into_response())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        clock::{Clock, FakeClock, SystemClock, UuidIds},
+        models::{
+            task_fields::{PartialTask, TaskFields},
+            workspace::DEFAULT_WORKSPACE_ID,
+        },
+        pagination::{PageParams, Paginated},
+    };
 
     /// When `assignee_id` is omitted from the JSON payload, the outer Option is None
     /// (meaning "don't touch this field").
@@ -342,4 +953,385 @@ mod tests {
         assert!(req.assignee_id.is_none());
         assert!(req.cti.is_none());
     }
+
+    /// `role` both globally and in the workspace.
+    fn claims(sub: &str, role: &str) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            email: format!("{sub}@example.com"),
+            email_verified: true,
+            username: sub.to_string(),
+            role: role.to_string(),
+            exp: usize::MAX,
+            scopes: None,
+            auth_time: None,
+            workspace_id: Some("ws1".into()),
+            workspace_role: Some(role.to_string()),
+        }
+    }
+
+    fn task_by(creator: Option<&str>, assignee: Option<&str>) -> Task {
+        task_at(&SystemClock, creator, assignee)
+    }
+
+    fn task_at(clock: &dyn Clock, creator: Option<&str>, assignee: Option<&str>) -> Task {
+        let mut t = Task::new(clock, &UuidIds, "T".into(), "D".into());
+        t.created_by = creator.map(str::to_string);
+        t.assignee_id = assignee.map(str::to_string);
+        t
+    }
+
+    #[test]
+    fn creator_and_assignee_may_modify() {
+        let t = task_by(Some("alice"), Some("bob"));
+        assert!(can_modify_task(&claims("alice", "user"), &t));
+        assert!(can_modify_task(&claims("bob", "user"), &t));
+    }
+
+    #[test]
+    fn other_users_may_not_modify() {
+        let t = task_by(Some("alice"), Some("bob"));
+        assert!(!can_modify_task(&claims("carol", "user"), &t));
+        // Legacy tasks have no creator, so only the assignee or an admin remain.
+        assert!(!can_modify_task(&claims("carol", "user"), &task_by(None, None)));
+    }
+
+    #[test]
+    fn only_admins_may_modify_any_task() {
+        let t = task_by(Some("alice"), None);
+        assert!(can_modify_task(&claims("root", "admin"), &t));
+        assert!(!can_modify_task(&claims("mgr", "manager"), &t));
+        // Only the role in this workspace counts.
+        let member = Claims { workspace_role: Some("member".into()), ..claims("root", "admin") };
+        assert!(!can_modify_task(&member, &t));
+    }
+
+    #[test]
+    fn update_stage_wraps_values_as_literals() {
+        let stage = update_stage(doc! { "title": "$where" }, None, "u1", Bson::Null);
+        let set = stage.get_document("$set").unwrap();
+        assert_eq!(set.get_document("title").unwrap(), &doc! { "$literal": "$where" });
+        assert!(!set.contains_key("status_changed_at"));
+        assert!(!set.contains_key("assigned_at"));
+    }
+
+    #[test]
+    fn update_stage_stamps_status_change_conditionally() {
+        let stage = update_stage(doc! { "status": "done" }, Some("done"), "u1", Bson::String("now".into()));
+        let set = stage.get_document("$set").unwrap();
+        let cond = set.get_document("status_changed_at").unwrap().get_array("$cond").unwrap();
+        assert_eq!(cond[2], Bson::String("$status_changed_at".into()));
+    }
+
+    #[test]
+    fn update_stage_stamps_assignment_changes_as_the_actor() {
+        let stage = update_stage(doc! { "assignee_id": Bson::Null }, None, "u1", Bson::String("now".into()));
+        let set = stage.get_document("$set").unwrap();
+        let cond = set.get_document("assigned_by").unwrap().get_array("$cond").unwrap();
+        assert_eq!(cond[0], Bson::Document(doc! { "$ne": [{ "$ifNull": ["$assignee_id", null] }, { "$literal": null }] }));
+        assert_eq!(cond[1], Bson::Document(doc! { "$literal": "u1" }));
+        assert_eq!(cond[2], Bson::String("$assigned_by".into()));
+        let cond = set.get_document("assigned_at").unwrap().get_array("$cond").unwrap();
+        assert_eq!(cond[1], Bson::Document(doc! { "$literal": "now" }));
+        assert!(!set.contains_key("status_changed_at"));
+    }
+
+    #[test]
+    fn only_the_update_that_completes_a_task_counts() {
+        let now = Utc::now();
+        let mut t = task_by(None, None);
+        t.status = "done".into();
+        t.status_changed_at = Some(now);
+        assert!(just_completed(&t, &Workflow::default(), now));
+        // Already done before this update, e.g. a title edit.
+        t.status_changed_at = Some(now - chrono::Duration::hours(1));
+        assert!(!just_completed(&t, &Workflow::default(), now));
+        t.status = "in_progress".into();
+        t.status_changed_at = Some(now);
+        assert!(!just_completed(&t, &Workflow::default(), now));
+    }
+
+    fn event_names(events: &[DomainEvent]) -> Vec<&'static str> {
+        events.iter().map(DomainEvent::name).collect()
+    }
+
+    #[test]
+    fn updates_publish_one_event_per_kind_of_change() {
+        let now = Utc::now();
+        let actor = || Actor { id: "alice".into(), username: "alice".into() };
+        let before = task_by(Some("alice"), None);
+        assert!(update_events(&before, &before, &Workflow::default(), now, actor()).is_empty());
+
+        let mut after = before.clone();
+        after.title = "Renamed".into();
+        after.status = "done".into();
+        after.status_changed_at = Some(now);
+        after.assignee_id = Some("bob".into());
+        after.assigned_at = Some(now);
+        let events = update_events(&before, &after, &Workflow::default(), now, actor());
+        assert_eq!(event_names(&events), ["task_updated", "task_completed", "user_assigned"]);
+        let DomainEvent::TaskUpdated { changes, actor: who, .. } = &events[0] else { unreachable!() };
+        assert_eq!(*changes, ["title", "status", "assignee_id"]);
+        assert_eq!(*who, actor());
+        let DomainEvent::UserAssigned { assignee, .. } = &events[2] else { unreachable!() };
+        assert_eq!(assignee.as_deref(), Some("bob"));
+
+        // The same assignee re-sent is not an assignment.
+        let mut after = before.clone();
+        after.description = "More detail".into();
+        after.assigned_at = Some(now - chrono::Duration::hours(1));
+        let events = update_events(&before, &after, &Workflow::default(), now, actor());
+        assert_eq!(event_names(&events), ["task_updated"]);
+    }
+
+    /// `task` as a projection on `fields` would read it.
+    fn project(task: &Task, fields: &TaskFields) -> PartialTask {
+        let stored = to_stored_document(task).unwrap();
+        let projected = stored.into_iter().filter(|(k, _)| k == "_id" || fields.names().contains(&k.as_str())).collect();
+        bson::from_document::<PartialTask>(projected).unwrap().with_fields(fields)
+    }
+
+    /// In-memory `TaskRepo`. Updates are not interpreted: a non-empty guard
+    /// is treated as refused, otherwise the stored task is returned as is.
+    #[derive(Default)]
+    struct FakeTasks(std::sync::Mutex<Vec<Task>>);
+
+    #[axum::async_trait]
+    impl TaskRepo for FakeTasks {
+        async fn find_page(&self, ws: &str, _: Document, _: Document, page: PageParams) -> AppResult<Paginated<Task>> {
+            let tasks: Vec<Task> = self.0.lock().unwrap().iter().filter(|t| t.workspace_id == ws).cloned().collect();
+            let total = tasks.len() as u64;
+            Ok(Paginated::new(tasks, total, page))
+        }
+        async fn find_by_id(&self, ws: &str, id: &str) -> AppResult<Option<Task>> {
+            Ok(self.0.lock().unwrap().iter().find(|t| t.id == id && t.workspace_id == ws).cloned())
+        }
+        async fn find_page_fields(
+            &self,
+            ws: &str,
+            filter: Document,
+            sort: Document,
+            fields: &TaskFields,
+            page: PageParams,
+        ) -> AppResult<Paginated<PartialTask>> {
+            Ok(self.find_page(ws, filter, sort, page).await?.map(|t| project(&t, fields)))
+        }
+        async fn find_by_id_fields(&self, ws: &str, id: &str, fields: &TaskFields) -> AppResult<Option<PartialTask>> {
+            Ok(self.find_by_id(ws, id).await?.map(|t| project(&t, fields)))
+        }
+        /// Only `created_by` and `title` equality and a `created_at` `$gte`
+        /// are interpreted; the newest match wins.
+        async fn find_first(&self, ws: &str, filter: Document, _: Document) -> AppResult<Option<Task>> {
+            let creator = filter.get_str("created_by").ok();
+            let title = filter.get_str("title").ok();
+            let since = filter.get_document("created_at").and_then(|c| c.get_datetime("$gte")).ok().map(|d| d.to_chrono());
+            let tasks = self.0.lock().unwrap();
+            let matching = tasks.iter().filter(|t| {
+                t.workspace_id == ws
+                    && creator.is_none_or(|c| t.created_by.as_deref() == Some(c))
+                    && title.is_none_or(|title| t.title == title)
+                    && since.is_none_or(|since| t.created_at >= since)
+            });
+            Ok(matching.max_by_key(|t| t.created_at).cloned())
+        }
+        async fn find_stream(
+            &self,
+            ws: &str,
+            _: Document,
+            _: Document,
+        ) -> AppResult<futures_util::stream::BoxStream<'static, AppResult<Task>>> {
+            let tasks: Vec<AppResult<Task>> =
+                self.0.lock().unwrap().iter().filter(|t| t.workspace_id == ws).cloned().map(Ok).collect();
+            Ok(Box::pin(futures_util::stream::iter(tasks)))
+        }
+        async fn insert(&self, ws: &str, task: &Task) -> AppResult<()> {
+            crate::db::check_workspace(ws, &task.workspace_id)?;
+            self.0.lock().unwrap().push(task.clone());
+            Ok(())
+        }
+        async fn update_fields(
+            &self,
+            ws: &str,
+            id: &str,
+            guard: Document,
+            _: UpdateModifications,
+        ) -> AppResult<Option<Task>> {
+            if !guard.is_empty() {
+                return Ok(None);
+            }
+            self.find_by_id(ws, id).await
+        }
+        async fn delete(&self, ws: &str, id: &str) -> AppResult<bool> {
+            let mut tasks = self.0.lock().unwrap();
+            let before = tasks.len();
+            tasks.retain(|t| t.id != id || t.workspace_id != ws);
+            Ok(tasks.len() < before)
+        }
+        /// Only `assignee_id` equality and a `created_at` `$gte` are
+        /// interpreted; other filters count every task in `ws`.
+        async fn count(&self, ws: &str, filter: Document) -> AppResult<u64> {
+            let assignee = filter.get_str("assignee_id").ok();
+            let since = filter.get_document("created_at").and_then(|c| c.get_datetime("$gte")).ok().map(|d| d.to_chrono());
+            let tasks = self.0.lock().unwrap();
+            let matching = tasks.iter().filter(|t| {
+                t.workspace_id == ws
+                    && assignee.is_none_or(|a| t.assignee_id.as_deref() == Some(a))
+                    && since.is_none_or(|since| t.created_at >= since)
+            });
+            Ok(matching.count() as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn refused_update_is_403_and_missing_task_is_404() {
+        let repo = FakeTasks::default();
+        let task = task_by(Some("alice"), Some("bob"));
+        repo.insert(DEFAULT_WORKSPACE_ID, &task).await.unwrap();
+        let guard = doc! { "assignee_id": "carol" };
+
+        let updated = apply_update(&repo, DEFAULT_WORKSPACE_ID, &task.id, doc! {}, doc! {}.into()).await.unwrap();
+        assert_eq!(updated.id, task.id);
+        assert!(matches!(
+            apply_update(&repo, DEFAULT_WORKSPACE_ID, &task.id, guard.clone(), doc! {}.into()).await,
+            Err(AppError::Forbidden)
+        ));
+        assert!(matches!(
+            apply_update(&repo, DEFAULT_WORKSPACE_ID, "missing", guard, doc! {}.into()).await,
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn tasks_in_other_workspaces_are_invisible() {
+        let repo = FakeTasks::default();
+        let task = task_by(Some("alice"), None);
+        assert!(repo.insert("other", &task).await.is_err(), "written into the wrong workspace");
+        repo.insert(DEFAULT_WORKSPACE_ID, &task).await.unwrap();
+
+        assert!(repo.find_by_id("other", &task.id).await.unwrap().is_none());
+        assert_eq!(repo.find_page("other", doc! {}, doc! {}, PageParams::default()).await.unwrap().total, 0);
+        assert!(matches!(
+            apply_update(&repo, "other", &task.id, doc! {}, doc! {}.into()).await,
+            Err(AppError::NotFound)
+        ));
+        assert!(!repo.delete("other", &task.id).await.unwrap());
+        assert!(repo.delete(DEFAULT_WORKSPACE_ID, &task.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn create_quotas_refuse_once_reached() {
+        let repo = FakeTasks::default();
+        let ws = DEFAULT_WORKSPACE_ID;
+        for assignee in ["bob", "bob", "carol"] {
+            repo.insert(ws, &task_by(Some("alice"), Some(assignee))).await.unwrap();
+        }
+        let unlimited = TaskQuotas::default();
+        check_create_quotas(&repo, &Workflow::default(), &unlimited, ws, "alice", Some("bob"), Utc::now()).await.unwrap();
+
+        let quotas = TaskQuotas { max_tasks: 3, ..Default::default() };
+        let err = check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, Utc::now()).await.unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { quota: "tasks", current: 3, limit: 3 }));
+        // Other workspaces have their own count.
+        check_create_quotas(&repo, &Workflow::default(), &quotas, "other", "alice", None, Utc::now()).await.unwrap();
+
+        let quotas = TaskQuotas { max_open_per_assignee: 2, ..Default::default() };
+        let err = check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", Some("bob"), Utc::now()).await.unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { quota: "open_per_assignee", current: 2, limit: 2 }));
+        check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", Some("carol"), Utc::now()).await.unwrap();
+        check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, Utc::now()).await.unwrap();
+
+        let quotas = TaskQuotas { max_created_per_hour: 3, ..Default::default() };
+        let err = check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, Utc::now()).await.unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { quota: "created_per_hour", .. }));
+    }
+
+    #[tokio::test]
+    async fn hourly_quota_window_slides_with_the_clock() {
+        let repo = FakeTasks::default();
+        let ws = DEFAULT_WORKSPACE_ID;
+        let clock = FakeClock::default();
+        let quotas = TaskQuotas { max_created_per_hour: 2, ..Default::default() };
+        for _ in 0..2 {
+            check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, clock.now()).await.unwrap();
+            repo.insert(ws, &task_at(&clock, Some("alice"), None)).await.unwrap();
+            clock.advance(chrono::Duration::minutes(20));
+        }
+        let err = check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, clock.now()).await.unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { quota: "created_per_hour", current: 2, limit: 2 }));
+
+        // The first task still counts at exactly an hour old, and leaves the
+        // window just after.
+        clock.advance(chrono::Duration::minutes(20));
+        assert!(check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, clock.now()).await.is_err());
+        clock.advance(chrono::Duration::minutes(1));
+        check_create_quotas(&repo, &Workflow::default(), &quotas, ws, "alice", None, clock.now()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_a_recent_task_with_the_same_title_and_creator_is_a_duplicate() {
+        let repo = FakeTasks::default();
+        let ws = DEFAULT_WORKSPACE_ID;
+        let clock = FakeClock::default();
+        let task = task_at(&clock, Some("alice"), None);
+        repo.insert(ws, &task).await.unwrap();
+        let since = |clock: &FakeClock| clock.now() - chrono::Duration::seconds(10);
+
+        clock.advance(chrono::Duration::seconds(3));
+        let found = recent_duplicate(&repo, ws, "alice", &task.title, since(&clock)).await.unwrap();
+        assert_eq!(found.unwrap().id, task.id);
+        assert!(recent_duplicate(&repo, ws, "bob", &task.title, since(&clock)).await.unwrap().is_none());
+        assert!(recent_duplicate(&repo, ws, "alice", "Other", since(&clock)).await.unwrap().is_none());
+        assert!(recent_duplicate(&repo, "other", "alice", &task.title, since(&clock)).await.unwrap().is_none());
+
+        clock.advance(chrono::Duration::seconds(8));
+        assert!(recent_duplicate(&repo, ws, "alice", &task.title, since(&clock)).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn suppressed_duplicates_are_the_task_plus_a_flag() {
+        let task = task_by(Some("alice"), None);
+        let json = serde_json::to_value(SuppressedDuplicate { task: task.clone(), duplicate_suppressed: true }).unwrap();
+        assert_eq!(json["_id"], task.id.as_str());
+        assert_eq!(json["duplicate_suppressed"], true);
+    }
+
+    #[tokio::test]
+    async fn note_quota_tells_a_full_task_from_a_missing_one() {
+        let repo = FakeTasks::default();
+        let mut task = task_by(Some("alice"), None);
+        task.notes = vec![TaskNote::new(&SystemClock, &UuidIds, "one".into(), "alice".into())];
+        repo.insert(DEFAULT_WORKSPACE_ID, &task).await.unwrap();
+
+        // The fake refuses any guarded update, as Mongo would for a full task.
+        let err = push_note(&repo, DEFAULT_WORKSPACE_ID, &task.id, doc! {}, 1).await.unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { quota: "notes_per_task", current: 1, limit: 1 }));
+        assert!(matches!(push_note(&repo, DEFAULT_WORKSPACE_ID, "missing", doc! {}, 1).await, Err(AppError::NotFound)));
+        assert_eq!(push_note(&repo, DEFAULT_WORKSPACE_ID, &task.id, doc! {}, 0).await.unwrap().id, task.id);
+    }
+
+    #[tokio::test]
+    async fn stream_ends_with_a_summary_line() {
+        let tasks = vec![Ok(task_by(None, None)), Ok(task_by(None, None))];
+        let body: Vec<Bytes> = ndjson_lines(Box::pin(stream::iter(tasks)), SystemClock.now()).map(Result::unwrap).collect().await;
+        let lines: Vec<serde_json::Value> = body.iter().map(|b| serde_json::from_slice(b).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["kind"], "task");
+        assert_eq!(lines[2]["kind"], "end");
+        assert_eq!(lines[2]["count"], 2);
+    }
+
+    #[tokio::test]
+    async fn stream_read_error_becomes_the_last_line() {
+        let tasks = vec![
+            Ok(task_by(None, None)),
+            Err(AppError::Internal(anyhow::anyhow!("cursor killed"))),
+            Ok(task_by(None, None)),
+        ];
+        let body: Vec<Bytes> = ndjson_lines(Box::pin(stream::iter(tasks)), SystemClock.now()).map(Result::unwrap).collect().await;
+        assert_eq!(body.len(), 2);
+        let last: serde_json::Value = serde_json::from_slice(&body[1]).unwrap();
+        assert_eq!(last["kind"], "error");
+        assert_eq!(last["count"], 1);
+        assert!(!last["message"].as_str().unwrap().contains("cursor killed"));
+    }
 }
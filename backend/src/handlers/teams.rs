@@ -0,0 +1,207 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+
+use crate::{
+    audit,
+    db::{collect, TEAMS},
+    errors::{AppError, AppResult, FieldError},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AdminUser, AppState, CurrentUser},
+        Created,
+    },
+    models::{
+        dates::to_bson_date,
+        id::Id,
+        team::{AddTeamMemberRequest, CreateTeamRequest, Team, TeamPublic, UpdateTeamRequest},
+    },
+};
+
+fn teams(state: &AppState) -> mongodb::Collection<Team> {
+    state.db.collection(TEAMS)
+}
+
+/// Team `id` in workspace `ws`, for resolving the `team_id` task filter.
+pub async fn find_team(state: &AppState, ws: &str, id: &str) -> AppResult<Option<Team>> {
+    Ok(teams(state).find_one(doc! { "_id": id, "workspace_id": ws }, None).await?)
+}
+
+/// Fails with a validation error on `field` unless every id names a user.
+async fn require_users(state: &AppState, field: &str, ids: &[String]) -> AppResult<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let found = state.repos.users.find_summaries(doc! { "_id": { "$in": ids } }).await?;
+    match ids.iter().find(|id| !found.iter().any(|u| &u.id == *id)) {
+        Some(id) => Err(FieldError::new(field, "unknown_user", format!("no user '{id}'")).into()),
+        None => Ok(()),
+    }
+}
+
+fn after_update() -> FindOneAndUpdateOptions {
+    FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build()
+}
+
+/// GET /api/teams — the workspace's teams, by name; open to all its members.
+pub async fn list_teams(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<TeamPublic>>> {
+    let options = FindOptions::builder().sort(doc! { "name": 1 }).build();
+    let cursor = teams(&state).find(doc! { "workspace_id": claims.workspace()? }, options).await?;
+    Ok(Json(collect(cursor).await?.into_iter().map(Into::into).collect()))
+}
+
+/// POST /api/admin/teams
+pub async fn admin_create_team(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateTeamRequest>,
+) -> AppResult<Created<TeamPublic>> {
+    let (name, member_ids, lead_id) = payload.validate().map_err(AppError::Validation)?;
+    require_users(&state, "member_ids", &member_ids).await?;
+    let team = Team::new(state.clock.as_ref(), state.ids.as_ref(), claims.workspace()?, name, member_ids, lead_id);
+    teams(&state).insert_one(&team, None).await?;
+    if !team.member_ids.is_empty() {
+        let details = doc! { "team_id": &team.id, "user_ids": &team.member_ids };
+        audit::record(&state.db, &claims, audit::TEAM_MEMBERS_ADD, details).await;
+    }
+    Ok(Created::at(format!("/admin/teams/{}", team.id), team.into()))
+}
+
+/// GET /api/admin/teams/:id
+pub async fn admin_get_team(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<TeamPublic>> {
+    let team = find_team(&state, claims.workspace()?, &id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(team.into()))
+}
+
+/// PUT /api/admin/teams/:id
+pub async fn admin_update_team(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    AppJson(payload): AppJson<UpdateTeamRequest>,
+) -> AppResult<Json<TeamPublic>> {
+    let name = payload.validate().map_err(AppError::Validation)?;
+    let lead = payload.lead_id.as_deref().map(str::trim);
+    if let Some(lead) = lead.filter(|l| !l.is_empty()) {
+        require_users(&state, "lead_id", &[lead.to_string()]).await?;
+    }
+    let ws = claims.workspace()?;
+    let before = find_team(&state, ws, &id).await?.ok_or(AppError::NotFound)?;
+
+    let mut update = doc! {};
+    let mut set = doc! { "updated_at": to_bson_date(state.clock.now()) };
+    if let Some(name) = name {
+        set.insert("name", name);
+    }
+    match lead {
+        Some("") => {
+            set.insert("lead_id", bson::Bson::Null);
+        }
+        Some(lead) => {
+            set.insert("lead_id", lead);
+            update.insert("$addToSet", doc! { "member_ids": lead });
+        }
+        None => {}
+    }
+    update.insert("$set", set);
+    let team = teams(&state)
+        .find_one_and_update(doc! { "_id": &id, "workspace_id": ws }, update, after_update())
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if let Some(lead) = lead.filter(|l| !l.is_empty() && !before.member_ids.iter().any(|m| m == l)) {
+        let details = doc! { "team_id": &id, "user_ids": [lead] };
+        audit::record(&state.db, &claims, audit::TEAM_MEMBERS_ADD, details).await;
+    }
+    Ok(Json(team.into()))
+}
+
+/// DELETE /api/admin/teams/:id — tasks assigned to its members are untouched.
+pub async fn admin_delete_team(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    let team = teams(&state)
+        .find_one_and_delete(doc! { "_id": &id, "workspace_id": claims.workspace()? }, None)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if !team.member_ids.is_empty() {
+        let details = doc! { "team_id": &id, "user_ids": &team.member_ids, "team_deleted": true };
+        audit::record(&state.db, &claims, audit::TEAM_MEMBERS_REMOVE, details).await;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/admin/teams/:id/members — adding an existing member is a no-op.
+pub async fn admin_add_team_member(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    AppJson(payload): AppJson<AddTeamMemberRequest>,
+) -> AppResult<Json<TeamPublic>> {
+    let user_id = payload.user_id.trim().to_string();
+    require_users(&state, "user_id", std::slice::from_ref(&user_id)).await?;
+    let now = state.clock.now();
+    let mut team = teams(&state)
+        .find_one_and_update(
+            doc! { "_id": &id, "workspace_id": claims.workspace()? },
+            doc! {
+                "$addToSet": { "member_ids": &user_id },
+                "$set": { "updated_at": to_bson_date(now) },
+            },
+            None,
+        )
+        .await?
+        .ok_or(AppError::NotFound)?;
+    // `team` is as it was before the update.
+    if !team.member_ids.contains(&user_id) {
+        let details = doc! { "team_id": &id, "user_ids": [&user_id] };
+        audit::record(&state.db, &claims, audit::TEAM_MEMBERS_ADD, details).await;
+        team.member_ids.push(user_id);
+    }
+    team.updated_at = now;
+    Ok(Json(team.into()))
+}
+
+/// DELETE /api/admin/teams/:id/members/:user_id — also clears the lead if it
+/// was them.
+pub async fn admin_remove_team_member(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppPath((id, user_id)): AppPath<(String, Id)>,
+) -> AppResult<Json<TeamPublic>> {
+    let update = vec![doc! {
+        "$set": {
+            "member_ids": {
+                "$filter": { "input": "$member_ids", "cond": { "$ne": ["$$this", { "$literal": &user_id }] } }
+            },
+            "lead_id": {
+                "$cond": [{ "$eq": ["$lead_id", { "$literal": &user_id }] }, null, "$lead_id"]
+            },
+            "updated_at": to_bson_date(state.clock.now()),
+        }
+    }];
+    let team = teams(&state)
+        .find_one_and_update(
+            doc! { "_id": &id, "workspace_id": claims.workspace()?, "member_ids": &user_id },
+            update,
+            after_update(),
+        )
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let details = doc! { "team_id": &id, "user_ids": [&user_id] };
+    audit::record(&state.db, &claims, audit::TEAM_MEMBERS_REMOVE, details).await;
+    Ok(Json(team.into()))
+}
@@ -0,0 +1,281 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use mongodb::options::FindOptions;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    db::{collect, API_KEYS, NOTIFICATIONS, SAVED_VIEWS, TASKS, TEAMS, WORKSPACE_MEMBERS},
+    errors::{AppError, AppResult},
+    extract::AppPath,
+    handlers::{
+        admin::AdminUserQuery,
+        auth::{AdminUser, AppState},
+    },
+    models::{
+        api_key::{ApiKey, ApiKeyPublic},
+        feed::Feed,
+        id::Id,
+        login_event::{LoginEvent, LoginEventPublic},
+        notification::{Notification, NotificationPublic},
+        preferences::UserPreferences,
+        saved_view::{SavedView, SavedViewPublic},
+        task::Task,
+        team::{Team, TeamPublic},
+        user::{AdminUserDetail, User, UserPublic},
+        weather::WeatherLocation,
+        workspace::Membership,
+    },
+};
+
+/// Columns of the CSV export, in order.
+const CSV_HEADER: &str = "id,email,username,role,created_at,last_login_at,active\r\n";
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportUsersQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// One CSV field, quoted when it holds a delimiter, quote or line break.
+/// Values a spreadsheet would read as a formula get a leading `'`.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv_row(user: &User) -> String {
+    let date = |dt: Option<DateTime<Utc>>| dt.map(|dt| dt.to_rfc3339()).unwrap_or_default();
+    let fields = [
+        csv_field(&user.id),
+        csv_field(&user.email),
+        csv_field(&user.username),
+        csv_field(&user.role),
+        date(Some(user.created_at)),
+        date(user.last_login_at),
+        user.active.to_string(),
+    ];
+    fields.join(",") + "\r\n"
+}
+
+/// `user` as the element of a JSON array at position `index`.
+fn json_element(user: User, index: usize) -> Bytes {
+    let mut bytes = if index == 0 { vec![] } else { vec![b','] };
+    serde_json::to_writer(&mut bytes, &UserPublic::from(user)).expect("users always serialize");
+    bytes.into()
+}
+
+/// GET /api/admin/users/export?format=csv|json — the users the admin list
+/// shows for the same `role`, `q` and `active`, all of them, by username.
+/// A read failure part way aborts the response, so a short file is never
+/// mistaken for a complete one.
+pub async fn admin_export_users(
+    _: AdminUser,
+    State(state): State<AppState>,
+    Query(export): Query<ExportUsersQuery>,
+    Query(params): Query<AdminUserQuery>,
+) -> AppResult<Response> {
+    let users = state.repos.users.find_stream(params.to_filter()?).await?;
+    let users = users.inspect(|r| {
+        if let Err(e) = r {
+            tracing::error!("User export aborted: {e}");
+        }
+    });
+
+    let (content_type, ext, body) = match export.format {
+        ExportFormat::Csv => {
+            let rows = users.map(|r| r.map(|u| Bytes::from(csv_row(&u))));
+            let body = stream::once(async { Ok(Bytes::from_static(CSV_HEADER.as_bytes())) }).chain(rows);
+            ("text/csv; charset=utf-8", "csv", Body::from_stream(body))
+        }
+        ExportFormat::Json => {
+            let elements = users.enumerate().map(|(i, r)| r.map(|u| json_element(u, i)));
+            let body = stream::once(async { Ok(Bytes::from_static(b"[")) })
+                .chain(elements)
+                .chain(stream::once(async { Ok(Bytes::from_static(b"]")) }));
+            ("application/json", "json", Body::from_stream(body))
+        }
+    };
+    let filename = format!("users-{}.{ext}", state.clock.now().format("%Y-%m-%d"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// A note the user wrote, with the task it is on.
+#[derive(Debug, Serialize)]
+pub struct AuthoredNote {
+    pub task_id: String,
+    pub workspace_id: String,
+    pub id: String,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Everything stored about one user, for a data-access request.
+#[derive(Debug, Serialize)]
+pub struct UserDataBundle {
+    pub exported_at: DateTime<Utc>,
+    pub profile: AdminUserDetail,
+    pub preferences: UserPreferences,
+    pub workspace_memberships: Vec<Membership>,
+    pub teams: Vec<TeamPublic>,
+    pub tasks_created: Vec<Task>,
+    pub tasks_assigned: Vec<Task>,
+    pub notes_authored: Vec<AuthoredNote>,
+    pub login_events: Vec<LoginEventPublic>,
+    pub api_keys: Vec<ApiKeyPublic>,
+    pub saved_views: Vec<SavedViewPublic>,
+    pub notifications: Vec<NotificationPublic>,
+    pub feeds: Vec<Feed>,
+    pub weather_locations: Vec<WeatherLocation>,
+}
+
+/// Every document in `collection` matching `filter`, oldest first.
+async fn all<T>(state: &AppState, collection: &str, filter: Document) -> AppResult<Vec<T>>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+    let cursor = state.db.collection::<T>(collection).find(filter, options).await?;
+    Ok(collect(cursor).await?)
+}
+
+/// The notes in `tasks` written by `author`.
+fn notes_by(tasks: Vec<Task>, author: &str) -> Vec<AuthoredNote> {
+    tasks
+        .into_iter()
+        .flat_map(|task| {
+            let (task_id, workspace_id) = (task.id, task.workspace_id);
+            task.notes.into_iter().filter(|n| n.author == author).map(move |n| AuthoredNote {
+                task_id: task_id.clone(),
+                workspace_id: workspace_id.clone(),
+                id: n.id,
+                note: n.note,
+                created_at: n.created_at,
+            })
+        })
+        .collect()
+}
+
+/// GET /api/admin/users/:id/export — a JSON bundle of everything stored
+/// about the user, across all workspaces, downloaded as a file.
+pub async fn admin_export_user_data(
+    _: AdminUser,
+    State(state): State<AppState>,
+    AppPath(id): AppPath<Id>,
+) -> AppResult<Response> {
+    let user = state.repos.users.find_by_id(&id).await?.ok_or(AppError::NotFound)?;
+    let assigned: Vec<Task> = all(&state, TASKS, doc! { "assignee_id": &id }).await?;
+    let bundle = UserDataBundle {
+        exported_at: state.clock.now(),
+        preferences: user.preferences.clone(),
+        profile: AdminUserDetail::new(user, assigned.len() as u64),
+        workspace_memberships: all(&state, WORKSPACE_MEMBERS, doc! { "user_id": &id }).await?,
+        teams: all::<Team>(&state, TEAMS, doc! { "member_ids": &id }).await?.into_iter().map(Into::into).collect(),
+        tasks_created: all(&state, TASKS, doc! { "created_by": &id }).await?,
+        tasks_assigned: assigned,
+        notes_authored: notes_by(all(&state, TASKS, doc! { "notes.author": &id }).await?, &id),
+        login_events: all::<LoginEvent>(&state, "login_events", doc! { "user_id": &id })
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        api_keys: all::<ApiKey>(&state, API_KEYS, doc! { "owner_id": &id })
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        saved_views: all::<SavedView>(&state, SAVED_VIEWS, doc! { "owner_id": &id })
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        notifications: all::<Notification>(&state, NOTIFICATIONS, doc! { "recipient_id": &id })
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        feeds: all(&state, "feeds", doc! { "user_id": &id }).await?,
+        weather_locations: all(&state, "weather_locations", doc! { "user_id": &id }).await?,
+    };
+    let body = serde_json::to_vec_pretty(&bundle).map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+    let filename = format!("user-{id}-{}.json", state.clock.now().format("%Y-%m-%d"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{SystemClock, UuidIds};
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("alice"), "alice");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_fields_never_start_a_formula() {
+        assert_eq!(csv_field("=SUM(A1)"), "'=SUM(A1)");
+        assert_eq!(csv_field("@cmd"), "'@cmd");
+        assert_eq!(csv_field("=1,2"), "\"'=1,2\"");
+    }
+
+    #[test]
+    fn format_defaults_to_csv() {
+        let q: ExportUsersQuery = serde_json::from_str("{}").unwrap();
+        assert_eq!(q.format, ExportFormat::Csv);
+        let q: ExportUsersQuery = serde_json::from_str(r#"{"format":"json"}"#).unwrap();
+        assert_eq!(q.format, ExportFormat::Json);
+    }
+
+    #[test]
+    fn notes_by_keeps_only_the_authors_notes() {
+        let mut task = Task::new(&SystemClock, &UuidIds, "T".into(), "D".into());
+        task.notes = vec![
+            crate::models::task::TaskNote::new(&SystemClock, &UuidIds, "mine".into(), "u1".into()),
+            crate::models::task::TaskNote::new(&SystemClock, &UuidIds, "theirs".into(), "u2".into()),
+        ];
+        let notes = notes_by(vec![task.clone()], "u1");
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].note, "mine");
+        assert_eq!(notes[0].task_id, task.id);
+    }
+}
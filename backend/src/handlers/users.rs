@@ -1,27 +1,59 @@
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use bson::doc;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    errors::AppResult,
-    handlers::auth::{AppState, Claims},
-    models::user::{User, UserPublic},
+    errors::{AppError, AppResult},
+    handlers::{
+        auth::{AppState, CurrentUser},
+        workspaces::member_ids,
+    },
+    models::user::{UserPublic, UserSummary},
+    permissions::{has_permission, USERS_MANAGE},
 };
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ListUsersQuery {
+    #[serde(default)]
+    pub include_inactive: bool,
+    /// Return full `UserPublic` records instead of `{id, username}`. Admin only.
+    #[serde(default)]
+    pub full: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum UserList {
+    Summary(Vec<UserSummary>),
+    Full(Vec<UserPublic>),
+}
+
+/// Feeds the assignee dropdown, so by default it returns only ids and
+/// usernames, and deactivated accounts are left out unless
+/// `?include_inactive=true` is passed. Only members of the caller's
+/// workspace are listed.
 pub async fn list_users(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-) -> AppResult<Json<Vec<UserPublic>>> {
-    let collection = state.db.collection::<User>("users");
-    let mut cursor = collection
-        .find(None, None)
-        .await
-        .map_err(crate::errors::AppError::Database)?;
+    Query(params): Query<ListUsersQuery>,
+) -> AppResult<Json<UserList>> {
+    if params.full && !has_permission(&claims.role, USERS_MANAGE) {
+        return Err(AppError::Forbidden);
+    }
+
+    let mut filter = if params.include_inactive {
+        doc! {}
+    } else {
+        doc! { "active": { "$ne": false } }
+    };
+    filter.insert("_id", doc! { "$in": member_ids(&state.db, claims.workspace()?).await? });
 
-    let mut users = Vec::new();
-    while cursor.advance().await.map_err(crate::errors::AppError::Database)? {
-        let user = cursor
-            .deserialize_current()
-            .map_err(crate::errors::AppError::Database)?;
-        users.push(UserPublic::from(user));
+    if params.full {
+        let users = state.repos.users.find_all(filter).await?;
+        return Ok(Json(UserList::Full(users.into_iter().map(UserPublic::from).collect())));
     }
-    Ok(Json(users))
+    Ok(Json(UserList::Summary(state.repos.users.find_summaries(filter).await?)))
 }
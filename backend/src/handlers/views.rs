@@ -0,0 +1,143 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bson::{doc, to_bson};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+
+use crate::{
+    db::{collect, SAVED_VIEWS},
+    errors::{AppError, AppResult},
+    extract::AppJson,
+    handlers::{
+        auth::{AppState, Claims, CurrentUser},
+        Created,
+    },
+    models::{
+        dates::to_bson_date,
+        saved_view::{CreateViewRequest, SavedView, SavedViewPublic, UpdateViewRequest, DEFAULT_VIEW},
+    },
+};
+
+fn views(state: &AppState) -> mongodb::Collection<SavedView> {
+    state.db.collection(SAVED_VIEWS)
+}
+
+/// The caller's view `id` in their current workspace; `default` names their
+/// default view.
+pub async fn find_view(state: &AppState, claims: &Claims, id: &str) -> AppResult<Option<SavedView>> {
+    let mut filter = doc! { "owner_id": &claims.sub, "workspace_id": claims.workspace()? };
+    if id == DEFAULT_VIEW {
+        filter.insert("is_default", true);
+    } else {
+        filter.insert("_id", id);
+    }
+    Ok(views(state).find_one(filter, None).await?)
+}
+
+/// Leaves `view` as the only default among its owner's views in its workspace.
+async fn clear_other_defaults(state: &AppState, view: &SavedView) -> AppResult<()> {
+    views(state)
+        .update_many(
+            doc! {
+                "owner_id": &view.owner_id,
+                "workspace_id": &view.workspace_id,
+                "is_default": true,
+                "_id": { "$ne": &view.id },
+            },
+            doc! { "$set": { "is_default": false } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// GET /api/views — the caller's views in their current workspace, by name.
+pub async fn list_views(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<SavedViewPublic>>> {
+    let options = FindOptions::builder().sort(doc! { "name": 1 }).build();
+    let cursor = views(&state)
+        .find(doc! { "owner_id": &claims.sub, "workspace_id": claims.workspace()? }, options)
+        .await?;
+    Ok(Json(collect(cursor).await?.into_iter().map(Into::into).collect()))
+}
+
+/// POST /api/views
+pub async fn create_view(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateViewRequest>,
+) -> AppResult<Created<SavedViewPublic>> {
+    let workflow = state.workflow.get(&state.db).await?;
+    let name = payload.validate(&workflow).map_err(AppError::Validation)?;
+    let ws = claims.workspace()?;
+    let (clock, ids) = (state.clock.as_ref(), state.ids.as_ref());
+    let view = SavedView::new(clock, ids, &claims.sub, ws, name, payload.filter, payload.is_default);
+    views(&state).insert_one(&view, None).await?;
+    if view.is_default {
+        clear_other_defaults(&state, &view).await?;
+    }
+    Ok(Created::at(format!("/views/{}", view.id), view.into()))
+}
+
+/// GET /api/views/:id — `id` may be `default`.
+pub async fn get_view(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<SavedViewPublic>> {
+    let view = find_view(&state, &claims, &id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(view.into()))
+}
+
+/// PUT /api/views/:id
+pub async fn update_view(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    AppJson(payload): AppJson<UpdateViewRequest>,
+) -> AppResult<Json<SavedViewPublic>> {
+    let workflow = state.workflow.get(&state.db).await?;
+    let name = payload.validate(&workflow).map_err(AppError::Validation)?;
+    let mut set = doc! { "updated_at": to_bson_date(state.clock.now()) };
+    if let Some(name) = name {
+        set.insert("name", name);
+    }
+    if let Some(filter) = &payload.filter {
+        set.insert("filter", to_bson(filter).map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?);
+    }
+    if let Some(is_default) = payload.is_default {
+        set.insert("is_default", is_default);
+    }
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    let view = views(&state)
+        .find_one_and_update(
+            doc! { "_id": &id, "owner_id": &claims.sub, "workspace_id": claims.workspace()? },
+            doc! { "$set": set },
+            options,
+        )
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if payload.is_default == Some(true) {
+        clear_other_defaults(&state, &view).await?;
+    }
+    Ok(Json(view.into()))
+}
+
+/// DELETE /api/views/:id
+pub async fn delete_view(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    let result = views(&state)
+        .delete_one(doc! { "_id": &id, "owner_id": &claims.sub, "workspace_id": claims.workspace()? }, None)
+        .await?;
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
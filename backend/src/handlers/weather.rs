@@ -8,8 +8,12 @@ use mongodb::options::FindOptions;
 use serde::Deserialize;
 
 use crate::{
-    errors::{AppError, AppResult},
-    handlers::auth::{AppState, Claims},
+    errors::{AppError, AppResult, FieldError},
+    extract::AppJson,
+    handlers::{
+        auth::{AppState, CurrentUser},
+        Created,
+    },
     models::weather::{WeatherAlert, WeatherLocation, WeatherObservation},
     weather_poller,
 };
@@ -22,37 +26,38 @@ pub struct CreateWeatherLocationRequest {
 }
 
 fn validate_coordinates(lat: f64, lon: f64) -> AppResult<()> {
+    let mut errors = Vec::new();
     if !(-90.0..=90.0).contains(&lat) {
-        return Err(AppError::BadRequest("lat must be between -90 and 90".to_string()));
+        errors.push(FieldError::new("lat", "out_of_range", "lat must be between -90 and 90"));
     }
     if !(-180.0..=180.0).contains(&lon) {
-        return Err(AppError::BadRequest("lon must be between -180 and 180".to_string()));
+        errors.push(FieldError::new("lon", "out_of_range", "lon must be between -180 and 180"));
     }
-    Ok(())
+    if errors.is_empty() { Ok(()) } else { Err(AppError::Validation(errors)) }
 }
 
 pub async fn list_weather_locations(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
 ) -> AppResult<Json<Vec<WeatherLocation>>> {
     let collection = state.db.collection::<WeatherLocation>("weather_locations");
     let mut cursor = collection
         .find(doc! { "user_id": &claims.sub }, None)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
 
     let mut locations = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        locations.push(cursor.deserialize_current().map_err(AppError::Database)?);
+    while cursor.advance().await.map_err(AppError::from)? {
+        locations.push(cursor.deserialize_current().map_err(AppError::from)?);
     }
     Ok(Json(locations))
 }
 
 pub async fn create_weather_location(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateWeatherLocationRequest>,
-) -> AppResult<(StatusCode, Json<WeatherLocation>)> {
+    AppJson(payload): AppJson<CreateWeatherLocationRequest>,
+) -> AppResult<Created<WeatherLocation>> {
     validate_coordinates(payload.lat, payload.lon)?;
 
     let location = WeatherLocation::new(claims.sub, payload.label, payload.lat, payload.lon);
@@ -60,12 +65,20 @@ pub async fn create_weather_location(
     collection
         .insert_one(&location, None)
         .await
-        .map_err(AppError::Database)?;
-    Ok((StatusCode::CREATED, Json(location)))
+        .map_err(AppError::from)?;
+    Ok(Created::at(format!("/weather/locations/{}", location.id), location))
+}
+
+pub async fn get_weather_location(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<WeatherLocation>> {
+    Ok(Json(verify_location_ownership(&state, &id, &claims.sub).await?))
 }
 
 pub async fn delete_weather_location(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> AppResult<StatusCode> {
@@ -73,7 +86,7 @@ pub async fn delete_weather_location(
     let result = collection
         .delete_one(doc! { "_id": &id, "user_id": &claims.sub }, None)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
 
     if result.deleted_count == 0 {
         return Err(AppError::NotFound);
@@ -82,7 +95,7 @@ pub async fn delete_weather_location(
 }
 
 pub async fn get_location_alerts(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> AppResult<Json<Vec<WeatherAlert>>> {
@@ -96,17 +109,17 @@ pub async fn get_location_alerts(
     let mut cursor = collection
         .find(doc! { "location_id": &id }, opts)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
 
     let mut alerts = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        alerts.push(cursor.deserialize_current().map_err(AppError::Database)?);
+    while cursor.advance().await.map_err(AppError::from)? {
+        alerts.push(cursor.deserialize_current().map_err(AppError::from)?);
     }
     Ok(Json(alerts))
 }
 
 pub async fn get_location_observations(
-    axum::Extension(claims): axum::Extension<Claims>,
+    CurrentUser(claims): CurrentUser,
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> AppResult<Json<Vec<WeatherObservation>>> {
@@ -120,11 +133,11 @@ pub async fn get_location_observations(
     let mut cursor = collection
         .find(doc! { "location_id": &id }, opts)
         .await
-        .map_err(AppError::Database)?;
+        .map_err(AppError::from)?;
 
     let mut observations = Vec::new();
-    while cursor.advance().await.map_err(AppError::Database)? {
-        observations.push(cursor.deserialize_current().map_err(AppError::Database)?);
+    while cursor.advance().await.map_err(AppError::from)? {
+        observations.push(cursor.deserialize_current().map_err(AppError::from)?);
     }
     Ok(Json(observations))
 }
@@ -138,7 +151,7 @@ async fn verify_location_ownership(
     let location = collection
         .find_one(doc! { "_id": location_id }, None)
         .await
-        .map_err(AppError::Database)?
+        .map_err(AppError::from)?
         .ok_or(AppError::NotFound)?;
 
     if location.user_id != user_id {
@@ -148,7 +161,7 @@ async fn verify_location_ownership(
 }
 
 pub async fn trigger_weather_poll(
-    axum::Extension(_claims): axum::Extension<Claims>,
+    _: CurrentUser,
     State(state): State<AppState>,
 ) -> AppResult<StatusCode> {
     let db = state.db.clone();
@@ -0,0 +1,165 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use mongodb::options::FindOptions;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    db::{collect, WEBHOOKS},
+    errors::{AppError, AppResult, FieldError},
+    extract::AppJson,
+    handlers::{
+        auth::{AdminUser, AppState},
+        Created,
+    },
+    models::webhook::{CreatedWebhook, DeliveryStatus, Webhook, WebhookPublic, WEBHOOK_EVENTS},
+    webhooks::{self, WebhookEvent},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Generated when omitted.
+    pub secret: Option<String>,
+    /// Empty or omitted subscribes to every event.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+fn validate(payload: &CreateWebhookRequest) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if !Url::parse(&payload.url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+        errors.push(FieldError::new("url", "invalid_url", "url must be an absolute http(s) URL"));
+    }
+    if payload.secret.as_deref().is_some_and(|s| s.trim().is_empty()) {
+        errors.push(FieldError::new("secret", "required", "secret must not be blank"));
+    }
+    if let Some(bad) = payload.events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+        errors.push(FieldError::new(
+            "events",
+            "invalid_event",
+            format!("invalid event '{}': must be one of {}", bad, WEBHOOK_EVENTS.join(", ")),
+        ));
+    }
+    errors
+}
+
+/// POST /api/admin/webhooks
+pub async fn admin_create_webhook(
+    AdminUser(claims): AdminUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateWebhookRequest>,
+) -> AppResult<Created<CreatedWebhook>> {
+    let errors = validate(&payload);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    let webhook = Webhook::new(payload.url, payload.secret, payload.events, claims.sub);
+    state
+        .db
+        .collection::<Webhook>(WEBHOOKS)
+        .insert_one(&webhook, None)
+        .await
+        .map_err(AppError::from)?;
+
+    let secret = webhook.secret.clone();
+    let path = format!("/admin/webhooks/{}", webhook.id);
+    Ok(Created::at(path, CreatedWebhook { webhook: webhook.into(), secret }))
+}
+
+/// GET /api/admin/webhooks/:id
+pub async fn admin_get_webhook(
+    _: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<WebhookPublic>> {
+    let webhook = state
+        .db
+        .collection::<Webhook>(WEBHOOKS)
+        .find_one(doc! { "_id": &id }, None)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(webhook.into()))
+}
+
+/// GET /api/admin/webhooks
+pub async fn admin_list_webhooks(
+    _: AdminUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<WebhookPublic>>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let cursor = state.db.collection::<Webhook>(WEBHOOKS).find(None, options).await?;
+    Ok(Json(collect(cursor).await?.into_iter().map(Into::into).collect()))
+}
+
+/// DELETE /api/admin/webhooks/:id
+pub async fn admin_delete_webhook(
+    _: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    let result = state
+        .db
+        .collection::<Webhook>(WEBHOOKS)
+        .delete_one(doc! { "_id": &id }, None)
+        .await
+        .map_err(AppError::from)?;
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/admin/webhooks/:id/test — sends a `ping` once, without retries,
+/// and reports the outcome. Disabled webhooks can be tested too.
+pub async fn admin_test_webhook(
+    _: AdminUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<DeliveryStatus>> {
+    let webhook = state
+        .db
+        .collection::<Webhook>(WEBHOOKS)
+        .find_one(doc! { "_id": &id }, None)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let event = WebhookEvent::ping(&webhook.id);
+    let status = webhooks::deliver(state.webhooks.http(), &webhook, &event, 1, &Default::default()).await;
+    webhooks::record(&state.db, &webhook.id, &status).await.map_err(AppError::Internal)?;
+    Ok(Json(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str, events: &[&str]) -> CreateWebhookRequest {
+        CreateWebhookRequest {
+            url: url.into(),
+            secret: None,
+            events: events.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_http_urls_and_known_events() {
+        assert!(validate(&request("https://hooks.slack.com/services/T/B/X", &[])).is_empty());
+        assert!(validate(&request("http://automation.internal/hook", &["task.created", "task.done"])).is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_urls_and_unknown_events() {
+        let fields: Vec<_> = validate(&request("ftp://example.com", &["task.deleted"]))
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, vec!["url", "events"]);
+        assert_eq!(validate(&request("not a url", &[]))[0].code, "invalid_url");
+    }
+}
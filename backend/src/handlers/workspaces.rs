@@ -0,0 +1,330 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+
+use crate::{
+    db::{collect, Db, USERS, WORKSPACES, WORKSPACE_MEMBERS},
+    errors::{mongo::is_duplicate_key, AppError, AppResult},
+    extract::{AppJson, AppPath},
+    handlers::{
+        auth::{AppState, Claims, CurrentUser},
+        Created,
+    },
+    models::{
+        dates::to_bson_date,
+        id::Id,
+        user::{normalize_email, User},
+        workspace::{
+            AddMemberRequest, CreateWorkspaceRequest, MemberView, Membership, Workspace, WorkspaceView,
+            default_workspace_role, DEFAULT_WORKSPACE_ID, WORKSPACE_ADMIN,
+        },
+    },
+    permissions::{has_permission, USERS_MANAGE},
+};
+
+/// Makes a newly signed-in user with global role `role` a member of the
+/// default workspace; see `default_workspace_role`.
+pub async fn join_default_workspace(state: &AppState, user_id: &str, role: &str) -> AppResult<()> {
+    let role = default_workspace_role(role);
+    let membership = Membership::new(state.clock.as_ref(), DEFAULT_WORKSPACE_ID, user_id, role);
+    match state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .insert_one(&membership, None)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) if is_duplicate_key(&e) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Ids of every member of `workspace_id`, for lists that must not show
+/// users of other workspaces.
+pub async fn member_ids(db: &Db, workspace_id: &str) -> AppResult<Vec<String>> {
+    let ids = db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .distinct("user_id", doc! { "workspace_id": workspace_id }, None)
+        .await?;
+    Ok(ids.into_iter().filter_map(|id| id.as_str().map(str::to_string)).collect())
+}
+
+async fn membership(state: &AppState, workspace_id: &str, user_id: &str) -> AppResult<Option<Membership>> {
+    Ok(state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .find_one(doc! { "_id": Membership::id_for(workspace_id, user_id) }, None)
+        .await?)
+}
+
+/// 404 unless the caller belongs to the workspace (global admins see them
+/// all), and 403 if `manage` is set and they are not one of its admins.
+async fn authorize(state: &AppState, claims: &Claims, workspace_id: &str, manage: bool) -> AppResult<()> {
+    if has_permission(&claims.role, USERS_MANAGE) {
+        return Ok(());
+    }
+    let membership = membership(state, workspace_id, &claims.sub).await?.ok_or(AppError::NotFound)?;
+    if manage && membership.role != WORKSPACE_ADMIN {
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+async fn load_workspace(state: &AppState, id: &str) -> AppResult<Workspace> {
+    state
+        .db
+        .collection::<Workspace>(WORKSPACES)
+        .find_one(doc! { "_id": id }, None)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+async fn active_workspace_id(state: &AppState, user_id: &str) -> AppResult<String> {
+    let user = state.db.collection::<User>(USERS).find_one(doc! { "_id": user_id }, None).await?;
+    Ok(user
+        .and_then(|u| u.active_workspace_id)
+        .unwrap_or_else(|| DEFAULT_WORKSPACE_ID.to_string()))
+}
+
+fn view(workspace: Workspace, role: String, active: &str) -> WorkspaceView {
+    WorkspaceView {
+        active: workspace.id == active,
+        id: workspace.id,
+        name: workspace.name,
+        role,
+        created_at: workspace.created_at,
+    }
+}
+
+/// POST /api/workspaces — the creator becomes its admin.
+pub async fn create_workspace(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppJson(payload): AppJson<CreateWorkspaceRequest>,
+) -> AppResult<Created<WorkspaceView>> {
+    let name = payload.validate().map_err(|e| AppError::Validation(vec![e]))?;
+    let workspace = Workspace::new(state.clock.as_ref(), state.ids.as_ref(), name, claims.sub.clone());
+    let membership = Membership::new(state.clock.as_ref(), &workspace.id, &claims.sub, WORKSPACE_ADMIN);
+    state.db.collection::<Workspace>(WORKSPACES).insert_one(&workspace, None).await?;
+    state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .insert_one(&membership, None)
+        .await?;
+    let active = active_workspace_id(&state, &claims.sub).await?;
+    let path = format!("/workspaces/{}", workspace.id);
+    Ok(Created::at(path, view(workspace, membership.role, &active)))
+}
+
+/// GET /api/workspaces/:id — 404 unless the caller is a member, since the
+/// view carries their role in it.
+pub async fn get_workspace(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<WorkspaceView>> {
+    let membership = membership(&state, &id, &claims.sub).await?.ok_or(AppError::NotFound)?;
+    let workspace = load_workspace(&state, &id).await?;
+    let active = active_workspace_id(&state, &claims.sub).await?;
+    Ok(Json(view(workspace, membership.role, &active)))
+}
+
+/// GET /api/workspaces — the caller's workspaces, with their role in each.
+pub async fn list_workspaces(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<WorkspaceView>>> {
+    let memberships = collect(
+        state
+            .db
+            .collection::<Membership>(WORKSPACE_MEMBERS)
+            .find(doc! { "user_id": &claims.sub }, None)
+            .await?,
+    )
+    .await?;
+    let ids: Vec<&str> = memberships.iter().map(|m| m.workspace_id.as_str()).collect();
+    let workspaces = collect(
+        state
+            .db
+            .collection::<Workspace>(WORKSPACES)
+            .find(doc! { "_id": { "$in": ids } }, None)
+            .await?,
+    )
+    .await?;
+    let active = active_workspace_id(&state, &claims.sub).await?;
+    let mut views: Vec<WorkspaceView> = workspaces
+        .into_iter()
+        .filter_map(|w| {
+            let role = memberships.iter().find(|m| m.workspace_id == w.id)?.role.clone();
+            Some(view(w, role, &active))
+        })
+        .collect();
+    views.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(views))
+}
+
+/// POST /api/workspaces/:id/switch — makes `id` the caller's active
+/// workspace, used whenever a request carries no `X-Workspace-Id`.
+pub async fn switch_workspace(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<WorkspaceView>> {
+    let membership = membership(&state, &id, &claims.sub).await?.ok_or(AppError::NotFound)?;
+    let workspace = load_workspace(&state, &id).await?;
+    state
+        .db
+        .collection::<User>(USERS)
+        .update_one(doc! { "_id": &claims.sub }, doc! { "$set": { "active_workspace_id": &id } }, None)
+        .await?;
+    Ok(Json(view(workspace, membership.role, &id)))
+}
+
+/// GET /api/workspaces/:id/members
+pub async fn list_members(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<MemberView>>> {
+    authorize(&state, &claims, &id, false).await?;
+    load_workspace(&state, &id).await?;
+    let memberships = collect(
+        state
+            .db
+            .collection::<Membership>(WORKSPACE_MEMBERS)
+            .find(doc! { "workspace_id": &id }, None)
+            .await?,
+    )
+    .await?;
+    let user_ids: Vec<&str> = memberships.iter().map(|m| m.user_id.as_str()).collect();
+    let users = collect(
+        state
+            .db
+            .collection::<User>(USERS)
+            .find(doc! { "_id": { "$in": user_ids } }, None)
+            .await?,
+    )
+    .await?;
+    let mut members: Vec<MemberView> = memberships
+        .into_iter()
+        .filter_map(|m| {
+            let user = users.iter().find(|u| u.id == m.user_id)?;
+            Some(MemberView {
+                user_id: m.user_id,
+                username: user.username.clone(),
+                email: user.email.clone(),
+                role: m.role,
+                joined_at: m.created_at,
+            })
+        })
+        .collect();
+    members.sort_by(|a, b| a.username.cmp(&b.username));
+    Ok(Json(members))
+}
+
+/// POST /api/workspaces/:id/members — adds the user with that email, or
+/// changes their role if they are already a member. Needs workspace admin.
+pub async fn add_member(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    AppJson(payload): AppJson<AddMemberRequest>,
+) -> AppResult<Json<MemberView>> {
+    payload.validate().map_err(|e| AppError::Validation(vec![e]))?;
+    authorize(&state, &claims, &id, true).await?;
+    load_workspace(&state, &id).await?;
+    let user = state
+        .db
+        .collection::<User>(USERS)
+        .find_one(doc! { "email": normalize_email(&payload.email) }, None)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if payload.role != WORKSPACE_ADMIN {
+        ensure_other_admin(&state, &id, &user.id).await?;
+    }
+
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+    let membership = state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .find_one_and_update(
+            doc! { "_id": Membership::id_for(&id, &user.id) },
+            doc! {
+                "$set": { "role": &payload.role },
+                "$setOnInsert": {
+                    "workspace_id": &id,
+                    "user_id": &user.id,
+                    "created_at": to_bson_date(state.clock.now()),
+                },
+            },
+            options,
+        )
+        .await?
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Upsert returned no document")))?;
+    tracing::info!(workspace_id = %id, user_id = %user.id, role = %membership.role, by = %claims.sub, "Workspace member set");
+    Ok(Json(MemberView {
+        user_id: user.id,
+        username: user.username,
+        email: user.email,
+        role: membership.role,
+        joined_at: membership.created_at,
+    }))
+}
+
+/// 409 if `user_id` is the workspace's only admin, so it never ends up
+/// without one.
+async fn ensure_other_admin(state: &AppState, workspace_id: &str, user_id: &str) -> AppResult<()> {
+    let others = state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .count_documents(
+            doc! { "workspace_id": workspace_id, "role": WORKSPACE_ADMIN, "user_id": { "$ne": user_id } },
+            None,
+        )
+        .await?;
+    let is_admin = membership(state, workspace_id, user_id)
+        .await?
+        .is_some_and(|m| m.role == WORKSPACE_ADMIN);
+    if is_admin && others == 0 {
+        return Err(AppError::LastAdmin);
+    }
+    Ok(())
+}
+
+/// DELETE /api/workspaces/:id/members/:user_id — workspace admins remove
+/// anyone; members may remove themselves.
+pub async fn remove_member(
+    CurrentUser(claims): CurrentUser,
+    State(state): State<AppState>,
+    AppPath((id, user_id)): AppPath<(String, Id)>,
+) -> AppResult<StatusCode> {
+    authorize(&state, &claims, &id, user_id != claims.sub).await?;
+    ensure_other_admin(&state, &id, &user_id).await?;
+    let result = state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .delete_one(doc! { "_id": Membership::id_for(&id, &user_id) }, None)
+        .await?;
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound);
+    }
+    // Their next request without a header lands back in the default workspace.
+    state
+        .db
+        .collection::<User>(USERS)
+        .update_one(
+            doc! { "_id": &user_id, "active_workspace_id": &id },
+            doc! { "$unset": { "active_workspace_id": "" } },
+            None,
+        )
+        .await?;
+    tracing::info!(workspace_id = %id, user_id = %user_id, by = %claims.sub, "Workspace member removed");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -13,18 +13,28 @@ pub struct RealmAccess {
 pub struct KeycloakClaims {
     pub sub: Option<String>,
     pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
     pub preferred_username: Option<String>,
     #[serde(default)]
     pub realm_access: Option<RealmAccess>,
     pub exp: usize,
+    /// When the user actually authenticated (unchanged across token refreshes).
+    pub auth_time: Option<usize>,
+    /// Set on tokens minted for integrations to limit them to these scopes;
+    /// see `permissions::SCOPES`. Absent on human sessions.
+    #[serde(default)]
+    pub mc_scopes: Option<Vec<String>>,
 }
 
+/// Picks the most privileged application role present in the realm roles.
 pub fn map_role(roles: &[String]) -> String {
-    if roles.iter().any(|r| r == "admin") {
-        "admin".to_string()
-    } else {
-        "user".to_string()
-    }
+    crate::permissions::ROLES
+        .iter()
+        .rev()
+        .find(|role| roles.iter().any(|r| r == *role))
+        .unwrap_or(&"user")
+        .to_string()
 }
 
 pub fn build_validation(config: &AppConfig) -> Validation {
@@ -50,7 +60,8 @@ struct JwkKey {
     e: String,
 }
 
-pub async fn fetch_decoding_key(config: &AppConfig) -> Result<DecodingKey> {
+/// The realm's RSA signing key, as published in its JWKS.
+async fn fetch_signing_jwk(config: &AppConfig) -> Result<JwkKey> {
     let url = format!(
         "{}/realms/{}/protocol/openid-connect/certs",
         config.keycloak_url, config.keycloak_realm
@@ -63,12 +74,47 @@ pub async fn fetch_decoding_key(config: &AppConfig) -> Result<DecodingKey> {
         .await
         .map_err(|e| anyhow!("Failed to parse JWKS response: {e}"))?;
 
-    let key = jwks
-        .keys
+    jwks.keys
         .into_iter()
         .find(|k| k.kty == "RSA" && k.use_.as_deref() == Some("sig"))
-        .ok_or_else(|| anyhow!("No RSA signing key found in Keycloak JWKS"))?;
+        .ok_or_else(|| anyhow!("No RSA signing key found in Keycloak JWKS"))
+}
 
+pub async fn fetch_decoding_key(config: &AppConfig) -> Result<DecodingKey> {
+    let key = fetch_signing_jwk(config).await?;
     DecodingKey::from_rsa_components(&key.n, &key.e)
         .map_err(|e| anyhow!("Failed to build RSA decoding key: {e}"))
 }
+
+/// Size of the realm's signing key in bits, for the startup self-check.
+/// Tokens are RS256, so their strength is the modulus length.
+pub async fn signing_key_bits(config: &AppConfig) -> Result<usize> {
+    Ok(modulus_bits(&fetch_signing_jwk(config).await?.n))
+}
+
+/// Bits in a base64url modulus, to the octet. JWKs carry no leading zero
+/// octets, so a 2048-bit key gives 2048.
+fn modulus_bits(n: &str) -> usize {
+    n.trim_end_matches('=').len() * 6 / 8 * 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modulus_bits_count_decoded_octets() {
+        // 256 octets encode to 342 base64url characters, unpadded.
+        assert_eq!(modulus_bits(&"A".repeat(342)), 2048);
+        assert_eq!(modulus_bits(&"A".repeat(171)), 1024);
+        assert_eq!(modulus_bits("AQAB"), 24);
+    }
+
+    #[test]
+    fn map_role_prefers_the_most_privileged_role() {
+        let roles = |r: &[&str]| r.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(map_role(&roles(&["offline_access", "manager"])), "manager");
+        assert_eq!(map_role(&roles(&["manager", "admin"])), "admin");
+        assert_eq!(map_role(&roles(&["offline_access"])), "user");
+    }
+}
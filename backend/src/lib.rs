@@ -0,0 +1,41 @@
+//! The MissionControl API as a library, so `tests/` can build the real router.
+//! `main.rs` is the binary around it.
+
+pub mod audit;
+pub mod backup;
+pub mod cleanup;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod cti_cache;
+pub mod db;
+pub mod diagnostics;
+pub mod digest;
+pub mod errors;
+pub mod events;
+pub mod extract;
+pub mod features;
+pub mod graphql;
+pub mod handlers;
+pub mod keycloak;
+pub mod link_title;
+pub mod markup;
+pub mod middleware;
+pub mod migrations;
+pub mod models;
+pub mod monitoring;
+pub mod notifier;
+pub mod nws_client;
+pub mod pagination;
+pub mod permissions;
+pub mod retention;
+pub mod retry;
+pub mod routes;
+pub mod server;
+pub mod shutdown;
+pub mod stats_cache;
+pub mod telemetry;
+pub mod user_cache;
+pub mod weather_poller;
+pub mod webhooks;
+pub mod workflow_cache;
@@ -0,0 +1,171 @@
+//! Reading a page's `<title>` for external links added without one, when
+//! `LINK_TITLE_FETCH` is on. The URL comes from a user, so the fetch only
+//! ever reaches public addresses: every hop of a redirect is resolved and
+//! checked, and the connection is pinned to the checked address so DNS
+//! cannot change its answer in between.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use reqwest::{
+    header::{ACCEPT, LOCATION},
+    redirect, Client,
+};
+use url::{Host, Url};
+
+use crate::models::task::EXTERNAL_LINK_TITLE_MAX_CHARS;
+
+/// The whole fetch, redirects included, gives up after this long.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+/// Only this much of the page is read; the title is near the top.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+const MAX_REDIRECTS: usize = 3;
+const USER_AGENT: &str = "MissionControl/1.0 (link preview)";
+
+/// Whether `ip` is reachable on the public internet, rather than loopback,
+/// private, link-local, shared (CGNAT) or otherwise reserved.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(v6),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)) // shared address space
+        || (a == 192 && b == 0 && ip.octets()[2] == 0) // IETF protocol assignments
+        || (a == 198 && (b == 18 || b == 19)) // benchmarking
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local
+        || (first & 0xffc0) == 0xfe80 // link local
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)) // documentation
+}
+
+/// The address to connect to for `url`, if every address its host resolves
+/// to is public.
+async fn resolve_public(url: &Url) -> Option<SocketAddr> {
+    let port = url.port_or_known_default()?;
+    let addrs: Vec<SocketAddr> = match url.host()? {
+        Host::Ipv4(ip) => vec![SocketAddr::new(ip.into(), port)],
+        Host::Ipv6(ip) => vec![SocketAddr::new(ip.into(), port)],
+        Host::Domain(domain) => tokio::net::lookup_host((domain, port)).await.ok()?.collect(),
+    };
+    if addrs.is_empty() || !addrs.iter().all(|a| is_public(a.ip())) {
+        tracing::debug!(url = %url, "Not fetching a link title from a non-public address");
+        return None;
+    }
+    addrs.into_iter().next()
+}
+
+/// The page title at `url`, or `None` if it cannot be read in time, is not
+/// public, or has none.
+pub async fn fetch_title(url: &Url) -> Option<String> {
+    tokio::time::timeout(FETCH_TIMEOUT, fetch(url.clone())).await.ok().flatten()
+}
+
+async fn fetch(mut url: Url) -> Option<String> {
+    for _ in 0..=MAX_REDIRECTS {
+        let addr = resolve_public(&url).await?;
+        let mut builder = Client::builder()
+            .user_agent(USER_AGENT)
+            .redirect(redirect::Policy::none())
+            .no_proxy()
+            .timeout(FETCH_TIMEOUT);
+        if let Some(Host::Domain(domain)) = url.host() {
+            builder = builder.resolve(domain, addr);
+        }
+        let mut res = builder.build().ok()?.get(url.clone()).header(ACCEPT, "text/html").send().await.ok()?;
+        if res.status().is_redirection() {
+            let location = res.headers().get(LOCATION)?.to_str().ok()?;
+            url = url.join(location).ok().filter(|u| matches!(u.scheme(), "http" | "https"))?;
+            continue;
+        }
+        if !res.status().is_success() {
+            return None;
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = res.chunk().await.ok()? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+        return parse_title(&String::from_utf8_lossy(&body));
+    }
+    None
+}
+
+/// The text of the first `<title>` in `html`, with common entities decoded,
+/// whitespace collapsed and cut to `EXTERNAL_LINK_TITLE_MAX_CHARS`.
+fn parse_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let text = html[start..end]
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let title: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let title: String = title.chars().take(EXTERNAL_LINK_TITLE_MAX_CHARS).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_addresses_pass() {
+        for blocked in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1",
+            "255.255.255.255", "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1", "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(blocked.parse().unwrap()), "{blocked} should be blocked");
+        }
+        for allowed in ["93.184.216.34", "8.8.8.8", "100.128.0.1", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(allowed.parse().unwrap()), "{allowed} should be allowed");
+        }
+    }
+
+    #[test]
+    fn titles_are_decoded_collapsed_and_capped() {
+        let page = "<html><head><TITLE lang=en>\n  Deploy  runbook &amp; FAQ\n</TITLE></head></html>";
+        assert_eq!(parse_title(page).as_deref(), Some("Deploy runbook & FAQ"));
+        assert_eq!(parse_title("<title>   </title>"), None);
+        assert_eq!(parse_title("<p>no title</p>"), None);
+        let long = format!("<title>{}</title>", "x".repeat(EXTERNAL_LINK_TITLE_MAX_CHARS * 2));
+        assert_eq!(parse_title(&long).unwrap().chars().count(), EXTERNAL_LINK_TITLE_MAX_CHARS);
+    }
+
+    #[tokio::test]
+    async fn private_hosts_are_never_fetched() {
+        for url in ["http://127.0.0.1:9/", "http://[::1]/", "http://169.254.169.254/latest/meta-data/"] {
+            assert_eq!(fetch_title(&Url::parse(url).unwrap()).await, None);
+        }
+    }
+}
@@ -1,109 +1,153 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::Parser;
 use dotenvy::dotenv;
-use mongodb::{Client, IndexModel, options::IndexOptions};
-use bson::doc;
-use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use mongodb::Client;
+use std::{sync::Arc, time::Duration};
+use tokio_util::sync::CancellationToken;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use x509_parser::prelude::*;
 
-mod config;
-mod db;
-mod errors;
-mod handlers;
-mod keycloak;
-mod middleware;
-mod models;
-mod nws_client;
-mod routes;
-mod weather_poller;
+use missoncontrol::{
+    cleanup, cli, config, db, diagnostics, digest, events, handlers, keycloak, middleware, migrations, notifier,
+    nws_client, retention, routes, server, shutdown, telemetry, weather_poller, webhooks,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
+    let cli = cli::Cli::parse();
 
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            env::var("RUST_LOG")
-                .unwrap_or_else(|_| "missoncontrol=debug,tower_http=debug".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    let mongo_uri = env::var("MONGODB_URI").expect("MONGODB_URI must be set");
-    let mongo_db = env::var("MONGODB_DB").expect("MONGODB_DB must be set");
-
-    let client = Client::with_uri_str(&mongo_uri).await?;
-    let db = client.database(&mongo_db);
-
-    // Ensure unique indexes on email and username (idempotent)
-    let users = db.collection::<bson::Document>("users");
-    for field in ["email", "username"] {
-        let opts = IndexOptions::builder().unique(true).build();
-        let index = IndexModel::builder()
-            .keys(doc! { field: 1 })
-            .options(opts)
-            .build();
-        users.create_index(index, None).await?;
+    let app_config = match config::AppConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprint!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let tracer_provider = match telemetry::provider_from_env() {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Could not set up OpenTelemetry export: {e}");
+            std::process::exit(1);
+        }
+    };
+    let registry = tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(&app_config.log_filter))
+        .with(tracer_provider.as_ref().map(telemetry::layer));
+    match app_config.log_format {
+        config::LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+        config::LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
+            )
+            .init(),
     }
+    middleware::panic::install_panic_hook();
+    app_config.log_summary();
+    if telemetry::enabled() {
+        tracing::info!("Exporting traces over OTLP/HTTP");
+    }
+
+    let client = Client::with_uri_str(&app_config.mongodb_uri)
+        .await
+        .context("Could not parse MONGODB_URI")?;
+    let db = client.database(&app_config.mongodb_db);
+    db::connect::wait_for_mongo(&db, Duration::from_secs(app_config.mongo_connect_max_wait_seconds)).await?;
 
-    // Weather: index locations by user, alerts by nws_id (unique for deduplication)
-    db.collection::<bson::Document>("weather_locations")
-        .create_index(IndexModel::builder().keys(doc! { "user_id": 1 }).build(), None)
-        .await?;
-    db.collection::<bson::Document>("weather_alerts")
-        .create_index(
-            IndexModel::builder()
-                .keys(doc! { "nws_id": 1 })
-                .options(IndexOptions::builder().unique(true).build())
-                .build(),
-            None,
-        )
-        .await?;
-    db.collection::<bson::Document>("weather_alerts")
-        .create_index(
-            IndexModel::builder()
-                .keys(doc! { "location_id": 1, "fetched_at": -1 })
-                .build(),
-            None,
-        )
-        .await?;
-    db.collection::<bson::Document>("weather_observations")
-        .create_index(
-            IndexModel::builder()
-                .keys(doc! { "location_id": 1, "timestamp": -1 })
-                .build(),
-            None,
-        )
-        .await?;
-    // TTL: auto-expire observations older than 48 hours
-    db.collection::<bson::Document>("weather_observations")
-        .create_index(
-            IndexModel::builder()
-                .keys(doc! { "fetched_at": 1 })
-                .options(IndexOptions::builder().expire_after(std::time::Duration::from_secs(172800)).build())
-                .build(),
-            None,
-        )
-        .await?;
-    tracing::info!("MongoDB indexes ensured");
-
-    let app_config = config::AppConfig::from_env();
+    let result = match cli.command.unwrap_or(cli::Command::Serve) {
+        _ if cli.check => cli::check(&db, &app_config).await,
+        cli::Command::Serve => serve(app_config, client, cli.migrate_only).await,
+        cli::Command::CreateAdmin { email, username } => {
+            cli::create_admin(&db, &email, username.as_deref()).await
+        }
+        cli::Command::Seed => cli::seed(&db).await,
+    };
+    if let Some(provider) = tracer_provider {
+        telemetry::shutdown(provider).await;
+    }
+    result
+}
+
+async fn serve(app_config: config::AppConfig, client: Client, migrate_only: bool) -> Result<()> {
+    let db = client.database(&app_config.mongodb_db);
+    let txn = db::Transactions::new(client.clone(), app_config.mongo_transactions);
+    // Loads any TLS certificate now, so a bad one fails before migrations run.
+    let listener = server::Listener::from_config(&app_config).await?;
+
+    // Before indexes, which may depend on migrated data.
+    migrations::run(&db).await?;
+    if migrate_only {
+        tracing::info!("--migrate-only given; exiting");
+        return Ok(());
+    }
+
+    db::indexes::ensure_indexes(&db, &app_config)
+        .await
+        .context("Could not create MongoDB indexes")?;
 
     let keycloak_decoding_key = Arc::new(tokio::sync::RwLock::new(
         keycloak::fetch_decoding_key(&app_config)
             .await
-            .expect("Failed to fetch Keycloak JWKS — is KEYCLOAK_URL/REALM correct?"),
+            .context("Failed to fetch Keycloak JWKS — is KEYCLOAK_URL/REALM correct?")?,
     ));
     tracing::info!("Keycloak JWKS loaded");
+    diagnostics::log(&diagnostics::run(&db, &app_config).await);
 
     let nws = Arc::new(nws_client::NwsClient::new());
     let poll_interval = app_config.weather_poll_interval_minutes;
     let poller_db = db.clone();
     let poller_nws = Arc::clone(&nws);
-    tokio::spawn(async move {
-        weather_poller::run_weather_poller(poller_db, poller_nws, poll_interval).await;
+    let shutdown = CancellationToken::new();
+    let poller_shutdown = shutdown.clone();
+    let poller = tokio::spawn(async move {
+        weather_poller::run_weather_poller(poller_db, poller_nws, poll_interval, poller_shutdown).await;
     });
 
+    let (webhooks, webhook_events) = webhooks::WebhookDispatcher::channel(
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .build()
+            .context("Failed to build webhook reqwest client")?,
+    );
+    let dispatcher = tokio::spawn(webhooks::run_dispatcher(
+        db.clone(),
+        webhooks.http().clone(),
+        webhook_events,
+        app_config.webhook_max_attempts,
+        shutdown.clone(),
+    ));
+
+    let (notifier, emails) = notifier::Notifier::channel();
+    let email_worker = tokio::spawn(notifier::run_notifier(
+        notifier::sender_from_config(&app_config)?,
+        emails,
+        app_config.email_max_attempts,
+        shutdown.clone(),
+    ));
+
+    let digests = tokio::spawn(digest::run_digests(
+        db.clone(),
+        notifier.clone(),
+        app_config.frontend_origin.clone(),
+        shutdown.clone(),
+    ));
+
+    let retention = tokio::spawn(retention::run_retention(db.clone(), app_config.retention, shutdown.clone()));
+    let cleanup = tokio::spawn(cleanup::run_cleanup(db.clone(), app_config.cleanup, shutdown.clone()));
+
+    // Subscribers to task events; see `events`.
+    let (events, domain_events) = events::EventBus::channel();
+    let subscribers: Vec<Arc<dyn events::Subscriber>> = vec![
+        Arc::new(webhooks.clone()),
+        Arc::new(handlers::notifications::Notifications::new(db.clone(), notifier.clone(), &app_config)),
+    ];
+    let event_worker = tokio::spawn(events::run_subscribers(domain_events, subscribers, shutdown.clone()));
+
     let root_cert_pem = tokio::fs::read(&app_config.step_ca_root_cert)
         .await
         .unwrap_or_else(|e| {
@@ -121,7 +165,7 @@ async fn main() -> Result<()> {
                 Err(e) => tracing::warn!("Could not parse step-ca root cert: {e}"),
             }
         }
-        builder.build().expect("Failed to build CA reqwest client")
+        builder.build().context("Failed to build CA reqwest client")?
     };
 
     let intermediate_cert_der: Arc<Vec<u8>> = {
@@ -140,12 +184,37 @@ async fn main() -> Result<()> {
         }
     };
 
-    let app = routes::build_router(db, nws, ca_client, intermediate_cert_der, keycloak_decoding_key);
+    let drain_timeout = Duration::from_secs(app_config.shutdown_drain_seconds);
+    let app = routes::build_router(
+        app_config,
+        db,
+        txn,
+        nws,
+        ca_client,
+        intermediate_cert_der,
+        keycloak_decoding_key,
+        webhooks,
+        notifier,
+        events,
+    );
+
+    tracing::info!("Server listening on {listener}");
+
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        tracing::info!("Shutting down: refusing new connections, draining in-flight requests");
+        signal_shutdown.cancel();
+    });
 
-    let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-    let addr = format!("0.0.0.0:{port}");
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    tracing::info!("Server listening on {addr}");
-    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+    server::serve(listener, app, shutdown.clone(), drain_timeout).await?;
+    tracing::info!("HTTP server stopped");
+
+    let workers = async { tokio::join!(poller, event_worker, dispatcher, email_worker, digests, retention, cleanup) };
+    if tokio::time::timeout(drain_timeout, workers).await.is_err() {
+        tracing::warn!("Background workers did not stop within {drain_timeout:?}");
+    }
+    client.shutdown().await;
+    tracing::info!("MongoDB client closed; shutdown complete");
     Ok(())
 }
@@ -0,0 +1,233 @@
+//! Sanitizing task notes, which some frontends render as HTML. How depends
+//! on `NOTE_FORMAT`: with `html` the note itself is cleaned down to a small
+//! allowlist of formatting tags; with `markdown` it is kept as written and
+//! rendered, then cleaned, into `rendered_html`.
+
+use std::collections::HashSet;
+
+use pulldown_cmark::{html, Options, Parser};
+
+use crate::{config::NoteFormat, errors::AppError};
+
+/// Longest note accepted, in characters, before any sanitizing.
+pub const MAX_NOTE_CHARS: usize = 5000;
+
+/// Tags that survive sanitizing; everything else is dropped, keeping its text
+/// (or, for `script` and `style`, dropping that too).
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "b", "blockquote", "br", "code", "del", "em", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "li",
+    "ol", "p", "pre", "s", "strong", "ul",
+];
+
+/// What to store for a submitted note.
+#[derive(Debug, PartialEq)]
+pub struct NoteBody {
+    pub note: String,
+    pub rendered_html: Option<String>,
+    /// The note without markup, for search.
+    pub plain_text: String,
+}
+
+/// Checks the length of `raw` and sanitizes it as `format` says.
+pub fn prepare_note(format: NoteFormat, raw: &str) -> Result<NoteBody, AppError> {
+    if raw.chars().count() > MAX_NOTE_CHARS {
+        return Err(AppError::BadRequest(format!("Notes are limited to {MAX_NOTE_CHARS} characters")));
+    }
+    Ok(match format {
+        NoteFormat::Html => {
+            let note = sanitize_html(raw);
+            NoteBody { plain_text: html_to_text(&note), note, rendered_html: None }
+        }
+        NoteFormat::Markdown => {
+            let rendered = render_markdown(raw);
+            NoteBody { note: raw.to_string(), plain_text: html_to_text(&rendered), rendered_html: Some(rendered) }
+        }
+    })
+}
+
+/// `input` with only `ALLOWED_TAGS`, links limited to http, https and
+/// mailto, and no attributes besides `href` and `title` on links.
+pub fn sanitize_html(input: &str) -> String {
+    let mut cleaner = ammonia::Builder::empty();
+    cleaner
+        .add_tags(ALLOWED_TAGS)
+        .add_tag_attributes("a", ["href", "title"])
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("noopener noreferrer nofollow"));
+    cleaner.clean(input).to_string()
+}
+
+/// CommonMark (plus strikethrough) as sanitized HTML. Raw HTML in the
+/// source goes through the same allowlist as `sanitize_html`.
+pub fn render_markdown(input: &str) -> String {
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(input, Options::ENABLE_STRIKETHROUGH));
+    sanitize_html(&out)
+}
+
+/// Closing tags that end a line of text.
+const LINE_ENDING_TAGS: &[&str] =
+    &["br", "hr", "/p", "/li", "/h1", "/h2", "/h3", "/h4", "/h5", "/h6", "/blockquote", "/pre"];
+/// Characters Markdown gives a meaning anywhere in a line.
+const MARKDOWN_SPECIAL: &[char] = &['\\', '`', '*', '_', '[', ']', '<', '>', '#', '!', '|', '~', '&'];
+
+/// The text of a note stored as sanitized HTML: tags dropped, block ends
+/// and `<br>` as line breaks, and the entities the sanitizer writes decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + len].trim().trim_end_matches('/').trim();
+        let name = tag.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+        if LINE_ENDING_TAGS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+        rest = &rest[start + len + 1..];
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .trim_end()
+        .to_string()
+}
+
+/// `text` as Markdown that renders to exactly `text`: punctuation Markdown
+/// would act on is backslash-escaped, each line break is kept as a hard
+/// break, and leading indentation (which would start a code block) dropped.
+pub fn escape_markdown(text: &str) -> String {
+    let lines: Vec<String> = text.lines().map(|line| escape_markdown_line(line.trim_start())).collect();
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        out.push_str(line);
+        let next_blank = lines.get(i + 1).is_some_and(|l| l.is_empty());
+        if i + 1 < lines.len() {
+            // A backslash before the newline is a hard break; around blank
+            // lines, which separate paragraphs anyway, it would be literal.
+            out.push_str(if line.is_empty() || next_blank { "\n" } else { "\\\n" });
+        }
+    }
+    out
+}
+
+fn escape_markdown_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    // Digits then `.` or `)` start an ordered list.
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    for (i, c) in line.chars().enumerate() {
+        let starts_block = i == 0 && matches!(c, '-' | '+' | '=');
+        let ends_list_number = i == digits && digits > 0 && matches!(c, '.' | ')');
+        if starts_block || ends_list_number || MARKDOWN_SPECIAL.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOADS: &[&str] = &[
+        r#"<img src=x onerror="alert(1)">"#,
+        "<script>alert(1)</script>",
+        r#"<a href="javascript:alert(1)">click</a>"#,
+        r#"<svg onload=alert(1)>"#,
+        r#"<iframe src="https://evil.example"></iframe>"#,
+        r#"<p style="background:url(javascript:alert(1))" onclick="alert(1)">hi</p>"#,
+        "[click](javascript:alert(1))",
+        "<scr<script>ipt>alert(1)</script>",
+    ];
+
+    fn assert_harmless(html: &str) {
+        let lower = html.to_ascii_lowercase();
+        // Markdown syntax is only text in `html` mode, so look for live
+        // attributes rather than the bare `javascript:` string.
+        let needles = ["<script", "<img", "<svg", "<iframe", "onerror=", "onload=", "onclick=", "href=\"javascript", "style="];
+        for needle in needles {
+            assert!(!lower.contains(needle), "{needle:?} survived in {html:?}");
+        }
+    }
+
+    #[test]
+    fn html_mode_strips_xss_from_the_stored_note() {
+        for payload in PAYLOADS {
+            let body = prepare_note(NoteFormat::Html, payload).unwrap();
+            assert_harmless(&body.note);
+            assert_eq!(body.rendered_html, None);
+        }
+    }
+
+    #[test]
+    fn markdown_mode_renders_without_xss() {
+        for payload in PAYLOADS {
+            let body = prepare_note(NoteFormat::Markdown, payload).unwrap();
+            assert_eq!(body.note, *payload, "the source is kept as written");
+            assert_harmless(body.rendered_html.as_deref().unwrap());
+        }
+    }
+
+    #[test]
+    fn notes_keep_a_plain_text_copy() {
+        let html = prepare_note(NoteFormat::Html, "<p>see the <strong>rollback</strong> plan</p>").unwrap();
+        assert_eq!(html.plain_text, "see the rollback plan");
+        let markdown = prepare_note(NoteFormat::Markdown, "see the **rollback** plan").unwrap();
+        assert_eq!(markdown.plain_text, "see the rollback plan");
+    }
+
+    #[test]
+    fn formatting_and_safe_links_survive() {
+        assert_eq!(sanitize_html("<b>bold</b> <em>it</em>"), "<b>bold</b> <em>it</em>");
+        assert_eq!(
+            render_markdown("**hi** [docs](https://example.com)"),
+            "<p><strong>hi</strong> <a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\">docs</a></p>\n"
+        );
+        assert_eq!(sanitize_html("@alice & <bob>"), "@alice &amp; ");
+    }
+
+    #[test]
+    fn stored_html_reads_back_as_text() {
+        let note = sanitize_html("<p>Step <b>one</b> &amp; two</p><ul><li>a &lt; b</li><li>c</li></ul>x<br>y");
+        assert_eq!(html_to_text(&note), "Step one & two\na < b\nc\nx\ny");
+        assert_eq!(html_to_text("unclosed <b"), "unclosed <b");
+    }
+
+    #[test]
+    fn escaped_markdown_renders_as_the_text() {
+        let text = concat!(
+            "# not a heading\n  - not a list\n1. nor this\n\n",
+            "<script>alert(1)</script> **bold** [x](javascript:alert(1)) | a_b",
+        );
+        let escaped = escape_markdown(text);
+        assert_eq!(
+            escaped,
+            concat!(
+                "\\# not a heading\\\n\\- not a list\\\n1\\. nor this\n\n",
+                "\\<script\\>alert(1)\\</script\\> \\*\\*bold\\*\\* \\[x\\](javascript:alert(1)) \\| a\\_b",
+            )
+        );
+        let mut html = String::new();
+        html::push_html(&mut html, Parser::new_ext(&escaped, Options::ENABLE_STRIKETHROUGH));
+        for tag in ["<script", "<a ", "<strong", "<h1", "<ol", "<ul"] {
+            assert!(!html.contains(tag), "{tag} in {html}");
+        }
+        assert_eq!(html.matches("<br />").count(), 2, "{html}");
+    }
+
+    #[test]
+    fn overlong_notes_are_refused() {
+        let long = "x".repeat(MAX_NOTE_CHARS + 1);
+        assert!(matches!(prepare_note(NoteFormat::Html, &long), Err(AppError::BadRequest(_))));
+        // Characters, not bytes.
+        assert!(prepare_note(NoteFormat::Html, &"é".repeat(MAX_NOTE_CHARS)).is_ok());
+    }
+}
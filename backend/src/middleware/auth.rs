@@ -1,43 +1,203 @@
 use axum::{
     extract::{Request, State},
+    http::Method,
     middleware::Next,
     response::Response,
 };
+use bson::{doc, Document};
+use chrono::Utc;
 use jsonwebtoken::{decode, errors::ErrorKind};
 
 use crate::{
-    errors::AppError,
+    db::API_KEYS,
+    errors::{AppError, AppResult, AuthErrorKind},
     handlers::auth::{AppState, Claims},
     keycloak::{build_validation, fetch_decoding_key, map_role, KeycloakClaims},
+    middleware::cookie::{csrf_ok, read_cookie, SESSION_COOKIE},
+    models::{api_key::hash_key, dates::to_bson_date},
+    permissions::scope_grants,
+    user_cache::UserStatus,
 };
 
+/// Maps a JWT decode failure to the code reported to clients: expiry is the
+/// one case the frontend can recover from by refreshing the token.
+pub fn classify_jwt_error(e: &jsonwebtoken::errors::Error) -> AuthErrorKind {
+    match e.kind() {
+        ErrorKind::ExpiredSignature => AuthErrorKind::Expired,
+        _ => AuthErrorKind::Invalid,
+    }
+}
+
+/// Scope a scoped credential needs for a route. Routes outside this map are
+/// not reachable with one at all. `path` is relative to the API version
+/// prefix (nesting strips it), e.g. `/tasks/abc`.
+pub fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    let read = matches!(*method, Method::GET | Method::HEAD);
+    let under = |prefix: &str| path == prefix || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'));
+    if path == "/tasks/stream" {
+        read.then_some("tasks:export")
+    } else if under("/tasks") {
+        Some(if read { "tasks:read" } else { "tasks:write" })
+    } else if path == "/search" {
+        read.then_some("tasks:read")
+    } else if path.starts_with("/cti/") {
+        Some(if read { "cti:read" } else { "cti:write" })
+    } else if path == "/users" || path == "/teams" {
+        read.then_some("users:read")
+    } else if under("/admin") {
+        Some(if read { "admin:read" } else { "admin:write" })
+    } else {
+        None
+    }
+}
+
+/// Checks a credential's scopes against the route. Scoped credentials (API
+/// keys, integration tokens) need a scope granting `required_scope`: 403
+/// `missing_scope` naming it otherwise, and plain 403 on routes no scope
+/// opens. Human sessions carry no list and implicitly hold every scope; for
+/// everyone, the role's permissions are still checked by the route itself.
+pub fn require_scope(claims: &Claims, method: &Method, path: &str) -> Result<(), AppError> {
+    let Some(granted) = &claims.scopes else {
+        return Ok(());
+    };
+    match required_scope(method, path) {
+        Some(scope) if granted.iter().any(|g| scope_grants(g, scope)) => Ok(()),
+        Some(scope) => {
+            tracing::warn!("Credential for {} lacks scope {scope} for {path}", claims.sub);
+            Err(AppError::MissingScope(scope))
+        }
+        None => {
+            tracing::warn!("Scoped credential for {} used on {path}, which no scope opens", claims.sub);
+            Err(AppError::Forbidden)
+        }
+    }
+}
+
+/// Resolves an `Authorization: ApiKey <key>` credential into claims for the
+/// key's owner, restricted to the key's scopes.
+async fn authenticate_api_key(state: &AppState, secret: &str) -> Result<Claims, AppError> {
+    let key_hash = hash_key(secret);
+    let key = state
+        .db
+        .collection::<Document>(API_KEYS)
+        .find_one(doc! { "key_hash": &key_hash }, None)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| {
+            tracing::warn!("Unknown or revoked API key presented");
+            AppError::Unauthorized(AuthErrorKind::Invalid)
+        })?;
+
+    let key_id = key.get_str("_id").map_err(|_| AppError::Unauthorized(AuthErrorKind::Invalid))?.to_string();
+    let owner_id = key.get_str("owner_id").map_err(|_| AppError::Unauthorized(AuthErrorKind::Invalid))?;
+    let scopes = key
+        .get_array("scopes")
+        .map(|a| a.iter().filter_map(|s| s.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+
+    let owner = state
+        .db
+        .collection::<Document>("users")
+        .find_one(doc! { "_id": owner_id }, None)
+        .await
+        .map_err(AppError::from)?
+        .ok_or_else(|| {
+            tracing::warn!("API key {key_id} belongs to a missing user");
+            AppError::Unauthorized(AuthErrorKind::Invalid)
+        })?;
+    if !owner.get_bool("active").unwrap_or(true) {
+        tracing::warn!("API key {key_id} belongs to an inactive user");
+        return Err(AppError::AccountInactive);
+    }
+    let email_verified = owner.get_bool("email_verified").unwrap_or(false);
+    if state.config.require_verified_email && !email_verified {
+        return Err(AppError::EmailNotVerified);
+    }
+
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        let now = to_bson_date(Utc::now());
+        if let Err(e) = db
+            .collection::<Document>(API_KEYS)
+            .update_one(doc! { "_id": &key_id }, doc! { "$set": { "last_used_at": now } }, None)
+            .await
+        {
+            tracing::warn!("Failed to record API key use for {key_id}: {e}");
+        }
+    });
+
+    Ok(Claims {
+        sub: owner_id.to_string(),
+        email: owner.get_str("email").unwrap_or_default().to_string(),
+        email_verified,
+        username: owner.get_str("username").unwrap_or_default().to_string(),
+        role: owner.get_str("role").unwrap_or("user").to_string(),
+        exp: usize::MAX,
+        scopes: Some(scopes),
+        auth_time: None,
+        workspace_id: None,
+        workspace_role: None,
+    })
+}
+
 pub async fn require_auth(
     State(state): State<AppState>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let auth_header = match req
+    let api_key = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("ApiKey "))
+        .map(str::to_string);
+    if let Some(secret) = api_key {
+        let claims = authenticate_api_key(&state, &secret).await?;
+        require_scope(&claims, req.method(), req.uri().path())?;
+        req.extensions_mut().insert(claims);
+        return Ok(next.run(req).await);
+    }
+
+    let bearer = req
         .headers()
         .get("Authorization")
         .and_then(|v| v.to_str().ok())
         .and_then(|v| v.strip_prefix("Bearer "))
-    {
+        .map(str::to_string);
+
+    // Header auth always wins so API clients are unaffected by cookie mode.
+    let auth_header = match bearer {
         Some(header) => {
             tracing::debug!("Authorization header found, token length: {}", header.len());
             header
         }
-        None => {
-            tracing::warn!("No Authorization header or Bearer token found");
-            return Err(AppError::Unauthorized);
-        }
+        None => match state
+            .config
+            .auth_cookie_mode
+            .then(|| read_cookie(req.headers(), SESSION_COOKIE))
+            .flatten()
+        {
+            Some(cookie) => {
+                if !csrf_ok(req.method(), req.headers()) {
+                    tracing::warn!("Cookie-authenticated request failed CSRF check");
+                    return Err(AppError::Forbidden);
+                }
+                cookie
+            }
+            None => {
+                tracing::warn!("No Authorization header or Bearer token found");
+                return Err(AppError::Unauthorized(AuthErrorKind::Missing));
+            }
+        },
     };
+    let auth_header = auth_header.as_str();
 
     let validation = build_validation(&state.config);
     tracing::debug!("JWT validation config - audience: {:?}, issuer: {:?}", &state.config.keycloak_client_id, format!("{}/realms/{}", &state.config.keycloak_url, &state.config.keycloak_realm));
 
     let decode_result = {
         let key = state.keycloak_decoding_key.read().await;
-        decode::<KeycloakClaims>(auth_header, &*key, &validation)
+        decode::<KeycloakClaims>(auth_header, &key, &validation)
     };
 
     let token_data = match decode_result {
@@ -51,21 +211,21 @@ pub async fn require_auth(
                 .await
                 .map_err(|e| {
                     tracing::error!("Failed to refresh Keycloak JWKS: {}", e);
-                    AppError::Unauthorized
+                    AppError::Unauthorized(AuthErrorKind::Invalid)
                 })?;
             let mut write = state.keycloak_decoding_key.write().await;
             *write = new_key;
             drop(write);
             let key = state.keycloak_decoding_key.read().await;
-            decode::<KeycloakClaims>(auth_header, &*key, &validation)
+            decode::<KeycloakClaims>(auth_header, &key, &validation)
                 .map_err(|e| {
                     tracing::error!("JWT validation failed after key refresh: {}", e);
-                    AppError::Unauthorized
+                    AppError::Unauthorized(classify_jwt_error(&e))
                 })?
         }
         Err(e) => {
             tracing::warn!("JWT validation failed: {} (expected audience: {})", e, &state.config.keycloak_client_id);
-            return Err(AppError::Unauthorized);
+            return Err(AppError::Unauthorized(classify_jwt_error(&e)));
         }
     };
 
@@ -73,29 +233,219 @@ pub async fn require_auth(
 
     let sub = kc.sub.ok_or_else(|| {
         tracing::error!("Token missing 'sub' claim");
-        AppError::Unauthorized
+        AppError::Unauthorized(AuthErrorKind::Invalid)
     })?;
     let email = kc.email.ok_or_else(|| {
         tracing::error!("Token missing 'email' claim");
-        AppError::Unauthorized
+        AppError::Unauthorized(AuthErrorKind::Invalid)
     })?;
     let username = kc.preferred_username.ok_or_else(|| {
         tracing::error!("Token missing 'preferred_username' claim");
-        AppError::Unauthorized
+        AppError::Unauthorized(AuthErrorKind::Invalid)
     })?;
     let realm_access = kc.realm_access.ok_or_else(|| {
         tracing::error!("Token missing 'realm_access' claim");
-        AppError::Unauthorized
+        AppError::Unauthorized(AuthErrorKind::Invalid)
     })?;
     
-    let claims = Claims {
+    if state.config.require_verified_email && !kc.email_verified {
+        tracing::warn!("Rejected request from {sub}: email not verified");
+        return Err(AppError::EmailNotVerified);
+    }
+
+    let mut claims = Claims {
         sub,
         email,
+        email_verified: kc.email_verified,
         username,
         role: map_role(&realm_access.roles),
         exp: kc.exp,
+        scopes: kc.mc_scopes,
+        auth_time: kc.auth_time,
+        workspace_id: None,
+        workspace_role: None,
     };
 
+    if state.config.revalidate_users || state.config.invite_only {
+        let status = state
+            .user_cache
+            .load(&state.db, &claims.sub)
+            .await
+            .map_err(AppError::from)?;
+        apply_status(&mut claims, status, req.uri().path(), state.config.revalidate_users)?;
+    }
+
+    require_scope(&claims, req.method(), req.uri().path())?;
     req.extensions_mut().insert(claims);
     Ok(next.run(req).await)
 }
+
+/// Checks a request to `path` against the caller's local record. The token's
+/// role can be up to a token lifetime stale, so the record is authoritative:
+/// an inactive user is rejected, and with `revalidate` the stored role
+/// replaces the token's. A caller with no record may only reach `/auth/me`,
+/// which provisions it (with an invite, when invite-only); anywhere else they
+/// are rejected, so a deleted user cannot keep acting on their token's role.
+fn apply_status(claims: &mut Claims, status: Option<UserStatus>, path: &str, revalidate: bool) -> AppResult<()> {
+    match status {
+        Some(status) if !status.active => {
+            tracing::warn!("Rejected request from inactive user {}", claims.sub);
+            Err(AppError::AccountInactive)
+        }
+        Some(status) => {
+            if revalidate {
+                claims.role = status.role;
+            }
+            Ok(())
+        }
+        None if path == "/auth/me" => Ok(()),
+        None => {
+            tracing::warn!("Rejected request from {}, who has no local user record", claims.sub);
+            Err(AppError::Forbidden)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct TestClaims {
+        sub: String,
+        exp: i64,
+    }
+
+    const SECRET: &[u8] = b"test-secret-that-is-long-enough-for-hs256";
+
+    fn mint(exp_offset_secs: i64) -> String {
+        let claims = TestClaims {
+            sub: "u1".to_string(),
+            exp: Utc::now().timestamp() + exp_offset_secs,
+        };
+        encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn decode_kind(token: &str, secret: &[u8]) -> Option<AuthErrorKind> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        validation.required_spec_claims = ["exp".to_string()].into_iter().collect();
+        decode::<KeycloakClaims>(token, &DecodingKey::from_secret(secret), &validation)
+            .err()
+            .map(|e| classify_jwt_error(&e))
+    }
+
+    #[test]
+    fn expired_token_is_token_expired() {
+        assert_eq!(decode_kind(&mint(-60), SECRET), Some(AuthErrorKind::Expired));
+    }
+
+    #[test]
+    fn wrong_signature_is_token_invalid() {
+        assert_eq!(
+            decode_kind(&mint(300), b"some-other-secret-entirely-different"),
+            Some(AuthErrorKind::Invalid)
+        );
+    }
+
+    #[test]
+    fn garbage_token_is_token_invalid() {
+        assert_eq!(decode_kind("not.a.jwt", SECRET), Some(AuthErrorKind::Invalid));
+    }
+
+    #[test]
+    fn valid_token_decodes() {
+        assert_eq!(decode_kind(&mint(300), SECRET), None);
+    }
+
+    #[test]
+    fn task_routes_map_to_task_scopes() {
+        assert_eq!(required_scope(&Method::GET, "/tasks"), Some("tasks:read"));
+        assert_eq!(required_scope(&Method::POST, "/tasks"), Some("tasks:write"));
+        assert_eq!(required_scope(&Method::DELETE, "/tasks/abc/notes/n1"), Some("tasks:write"));
+        assert_eq!(required_scope(&Method::GET, "/tasks/stream"), Some("tasks:export"));
+        assert_eq!(required_scope(&Method::POST, "/tasks/stream"), None);
+    }
+
+    #[test]
+    fn cti_routes_map_to_cti_scopes() {
+        assert_eq!(required_scope(&Method::GET, "/cti/categories"), Some("cti:read"));
+        assert_eq!(required_scope(&Method::PUT, "/cti/items/i1"), Some("cti:write"));
+    }
+
+    #[test]
+    fn user_and_admin_routes_map_to_their_scopes() {
+        assert_eq!(required_scope(&Method::GET, "/search"), Some("tasks:read"));
+        assert_eq!(required_scope(&Method::GET, "/users"), Some("users:read"));
+        assert_eq!(required_scope(&Method::GET, "/admin/users"), Some("admin:read"));
+        assert_eq!(required_scope(&Method::PUT, "/admin/users/u1/role"), Some("admin:write"));
+    }
+
+    #[test]
+    fn other_routes_are_closed_to_api_keys() {
+        assert_eq!(required_scope(&Method::POST, "/users"), None);
+        assert_eq!(required_scope(&Method::POST, "/auth/api-keys"), None);
+        assert_eq!(required_scope(&Method::GET, "/tasksx"), None);
+        assert_eq!(required_scope(&Method::GET, "/administrator"), None);
+    }
+
+    fn scoped(scopes: Option<&[&str]>) -> Claims {
+        Claims {
+            sub: "u1".into(),
+            email: "u@example.com".into(),
+            email_verified: true,
+            username: "u".into(),
+            role: "admin".into(),
+            exp: usize::MAX,
+            scopes: scopes.map(|s| s.iter().map(|s| s.to_string()).collect()),
+            auth_time: None,
+            workspace_id: None,
+            workspace_role: None,
+        }
+    }
+
+    #[test]
+    fn scoped_credentials_are_held_to_their_scopes() {
+        let writer = scoped(Some(&["tasks:write"]));
+        assert!(require_scope(&writer, &Method::POST, "/tasks").is_ok());
+        assert!(matches!(require_scope(&writer, &Method::GET, "/users"), Err(AppError::MissingScope("users:read"))));
+        assert!(matches!(require_scope(&writer, &Method::GET, "/notifications"), Err(AppError::Forbidden)));
+
+        let admin = scoped(Some(&["admin:*"]));
+        assert!(require_scope(&admin, &Method::POST, "/admin/restore").is_ok());
+        assert!(matches!(require_scope(&admin, &Method::GET, "/tasks"), Err(AppError::MissingScope("tasks:read"))));
+
+        // A human session: the role alone decides.
+        assert!(require_scope(&scoped(None), &Method::GET, "/notifications").is_ok());
+    }
+
+    fn status(role: &str, active: bool) -> Option<UserStatus> {
+        Some(UserStatus { role: role.into(), active })
+    }
+
+    #[test]
+    fn the_stored_role_replaces_the_tokens() {
+        let mut claims = scoped(None);
+        apply_status(&mut claims, status("user", true), "/tasks", true).unwrap();
+        assert_eq!(claims.role, "user");
+        let mut claims = scoped(None);
+        apply_status(&mut claims, status("user", true), "/tasks", false).unwrap();
+        assert_eq!(claims.role, "admin");
+    }
+
+    #[test]
+    fn inactive_users_are_rejected() {
+        let result = apply_status(&mut scoped(None), status("admin", false), "/tasks", true);
+        assert!(matches!(result, Err(AppError::AccountInactive)));
+    }
+
+    #[test]
+    fn deleted_users_are_rejected_outside_auth_me() {
+        // The token still says admin, but the record is gone.
+        let result = apply_status(&mut scoped(None), None, "/admin/users", true);
+        assert!(matches!(result, Err(AppError::Forbidden)));
+        assert!(apply_status(&mut scoped(None), None, "/auth/me", true).is_ok());
+    }
+}
@@ -0,0 +1,67 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::errors::AppError;
+
+/// Body extractors reject oversized requests (see `DefaultBodyLimit`) with a
+/// plain-text 413; rewrite those into our JSON error envelope.
+pub async fn payload_too_large_as_json(req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        return AppError::PayloadTooLarge.into_response();
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        extract::DefaultBodyLimit,
+        middleware,
+        routing::post,
+        Json, Router,
+    };
+    use tower::ServiceExt;
+
+    async fn post_bytes(len: usize) -> (StatusCode, serde_json::Value) {
+        let app = Router::new()
+            .route("/x", post(|Json(v): Json<serde_json::Value>| async move { Json(v) }))
+            .layer(DefaultBodyLimit::max(64))
+            .layer(middleware::from_fn(payload_too_large_as_json));
+        let body = serde_json::to_vec(&serde_json::json!({ "a": "x".repeat(len) })).unwrap();
+        let req = Request::builder()
+            .method("POST")
+            .uri("/x")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let status = resp.status();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn oversized_body_gets_json_413() {
+        let (status, body) = post_bytes(1000).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], "payload_too_large");
+    }
+
+    #[tokio::test]
+    async fn body_within_limit_passes() {
+        let (status, body) = post_bytes(10).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["a"], "x".repeat(10));
+    }
+}
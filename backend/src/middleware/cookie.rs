@@ -0,0 +1,107 @@
+use axum::http::{header, HeaderMap, HeaderValue, Method};
+
+pub const SESSION_COOKIE: &str = "mc_session";
+pub const CSRF_COOKIE: &str = "mc_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Returns the value of the named cookie from the request's `Cookie` headers.
+pub fn read_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v.to_string())
+}
+
+/// `Set-Cookie` value carrying the JWT. HttpOnly so page scripts never see it.
+pub fn session_cookie(token: &str, max_age_secs: i64) -> HeaderValue {
+    build_cookie(SESSION_COOKIE, token, max_age_secs, true)
+}
+
+pub fn clear_session_cookie() -> HeaderValue {
+    build_cookie(SESSION_COOKIE, "", 0, true)
+}
+
+/// `Set-Cookie` value for the double-submit CSRF token. Deliberately readable
+/// by scripts: the frontend echoes it back in the `X-CSRF-Token` header.
+pub fn csrf_cookie(token: &str) -> HeaderValue {
+    build_cookie(CSRF_COOKIE, token, 60 * 60 * 24, false)
+}
+
+fn build_cookie(name: &str, value: &str, max_age_secs: i64, http_only: bool) -> HeaderValue {
+    let mut cookie = format!(
+        "{name}={value}; Path=/; Secure; SameSite=Strict; Max-Age={}",
+        max_age_secs.max(0)
+    );
+    if http_only {
+        cookie.push_str("; HttpOnly");
+    }
+    HeaderValue::from_str(&cookie).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+/// Double-submit check for cookie-authenticated requests: mutating methods must
+/// echo the CSRF cookie in the `X-CSRF-Token` header.
+pub fn csrf_ok(method: &Method, headers: &HeaderMap) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+    let header_token = headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok());
+    match (read_cookie(headers, CSRF_COOKIE), header_token) {
+        (Some(cookie), Some(header)) => !cookie.is_empty() && cookie == header,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        for (k, v) in pairs {
+            h.append(*k, HeaderValue::from_str(v).unwrap());
+        }
+        h
+    }
+
+    #[test]
+    fn read_cookie_finds_value_among_several() {
+        let h = headers(&[("cookie", "a=1; mc_session=tok; b=2")]);
+        assert_eq!(read_cookie(&h, SESSION_COOKIE), Some("tok".to_string()));
+        assert_eq!(read_cookie(&h, "missing"), None);
+    }
+
+    #[test]
+    fn session_cookie_is_http_only_and_strict() {
+        let c = session_cookie("tok", 300);
+        let s = c.to_str().unwrap();
+        assert!(s.starts_with("mc_session=tok;"));
+        assert!(s.contains("HttpOnly"));
+        assert!(s.contains("Secure"));
+        assert!(s.contains("SameSite=Strict"));
+        assert!(s.contains("Max-Age=300"));
+    }
+
+    #[test]
+    fn csrf_cookie_is_script_readable() {
+        assert!(!csrf_cookie("x").to_str().unwrap().contains("HttpOnly"));
+    }
+
+    #[test]
+    fn csrf_not_required_for_safe_methods() {
+        assert!(csrf_ok(&Method::GET, &HeaderMap::new()));
+    }
+
+    #[test]
+    fn csrf_requires_matching_header_for_mutations() {
+        let ok = headers(&[("cookie", "mc_csrf=abc"), ("x-csrf-token", "abc")]);
+        let mismatch = headers(&[("cookie", "mc_csrf=abc"), ("x-csrf-token", "xyz")]);
+        let missing = headers(&[("cookie", "mc_csrf=abc")]);
+        assert!(csrf_ok(&Method::POST, &ok));
+        assert!(!csrf_ok(&Method::POST, &mismatch));
+        assert!(!csrf_ok(&Method::DELETE, &missing));
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::Cell;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{middleware::request_id::current_request_id, monitoring::UNMATCHED};
+
+/// Debug builds report the request's operation count in this header.
+pub const DB_OPS_HEADER: &str = "x-db-ops";
+
+tokio::task_local! {
+    static DB_OPS: Cell<u32>;
+}
+
+/// Counts one MongoDB operation against the current request, if any. Called
+/// by `QueryTimer`, so it sees every repository call; code that goes to
+/// `state.db` directly is not counted.
+pub fn count_db_op() {
+    let _ = DB_OPS.try_with(|ops| ops.set(ops.get() + 1));
+}
+
+/// Counts the repository operations each request makes and logs a warning,
+/// with the route, when there are more than `budget` (`DB_OPS_BUDGET`; 0
+/// turns the warning off). A guard against endpoints quietly growing into
+/// dozens of round trips. Operations made while a streamed body is sent,
+/// after the handler has returned, are not counted.
+pub async fn db_ops_budget(State(budget): State<u32>, req: Request, next: Next) -> Response {
+    let route = req.extensions().get::<MatchedPath>().map_or(UNMATCHED, |p| p.as_str()).to_string();
+    let method = req.method().to_string();
+    let (ops, mut response) = DB_OPS
+        .scope(Cell::new(0), async {
+            let response = next.run(req).await;
+            (DB_OPS.with(Cell::get), response)
+        })
+        .await;
+    if budget > 0 && ops > budget {
+        metrics::counter!("db_ops_budget_exceeded_total", "route" => route.clone()).increment(1);
+        tracing::warn!(
+            %method,
+            %route,
+            ops,
+            budget,
+            request_id = current_request_id().as_deref().unwrap_or("-"),
+            "Request exceeded its MongoDB operation budget",
+        );
+    }
+    if cfg!(debug_assertions) {
+        response.headers_mut().insert(DB_OPS_HEADER, HeaderValue::from(ops));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{body::Body, middleware, routing::get, Router};
+    use bson::doc;
+    use tower::ServiceExt;
+    use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt};
+
+    use super::*;
+    use crate::db::QueryTimer;
+
+    /// Makes `ops` timed (instant) operations, as a chatty handler would.
+    async fn chatty(ops: u32) -> &'static str {
+        let timer = QueryTimer::new(Duration::ZERO);
+        for _ in 0..ops {
+            timer.time("tasks", "find_one", &doc! {}, async {}).await;
+        }
+        "ok"
+    }
+
+    fn app(budget: u32) -> Router {
+        Router::new()
+            .route("/quiet", get(|| chatty(3)))
+            .route("/tasks/:id/expand", get(|| chatty(20)))
+            .layer(middleware::from_fn_with_state(budget, db_ops_budget))
+    }
+
+    /// Collects formatted log lines.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Logs {
+        type Writer = Logs;
+        fn make_writer(&'a self) -> Logs {
+            self.clone()
+        }
+    }
+
+    async fn call(app: Router, uri: &str) -> Response {
+        app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn going_over_the_budget_logs_the_route() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_writer(logs.clone()).with_ansi(false));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let res = call(app(15), "/quiet").await;
+        assert_eq!(res.headers()[DB_OPS_HEADER], "3");
+        assert!(logs.0.lock().unwrap().is_empty());
+
+        let res = call(app(15), "/tasks/1/expand").await;
+        assert_eq!(res.headers()[DB_OPS_HEADER], "20");
+        let text = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(text.contains("Request exceeded its MongoDB operation budget"), "{text}");
+        for field in ["route=/tasks/:id/expand", "ops=20", "budget=15"] {
+            assert!(text.contains(field), "{text}");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_zero_budget_only_counts() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_writer(logs.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let res = call(app(0), "/tasks/1/expand").await;
+        assert_eq!(res.headers()[DB_OPS_HEADER], "20");
+        assert!(logs.0.lock().unwrap().is_empty());
+        // Outside a request there is nothing to count against.
+        count_db_op();
+    }
+}
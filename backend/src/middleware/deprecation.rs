@@ -0,0 +1,24 @@
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+
+/// When the unversioned `/api/...` aliases are due to be removed.
+pub const LEGACY_API_SUNSET: &str = "Wed, 30 Jun 2027 00:00:00 GMT";
+
+/// Marks responses served from the unversioned `/api/...` aliases as
+/// deprecated (RFC 9745 / RFC 8594) and points at the `/api/v1` equivalent.
+pub async fn legacy_api_deprecation(req: Request, next: Next) -> Response {
+    // Inside the nested router the `/api` prefix has already been stripped.
+    let successor = format!("</api/v1{}>; rel=\"successor-version\"", req.uri().path());
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert("sunset", HeaderValue::from_static(LEGACY_API_SUNSET));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert("link", link);
+    }
+    response
+}
@@ -0,0 +1,122 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::errors::AppError;
+
+/// Route layer for list endpoints: tags successful GET responses with a weak
+/// ETag derived from the body and answers a matching `If-None-Match` with
+/// 304. The handler still runs, so this saves bandwidth rather than queries.
+pub async fn etag(req: Request, next: Next) -> Response {
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(req).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return AppError::Internal(anyhow::anyhow!("reading response body: {e}")).into_response(),
+    };
+    let tag = weak_etag(&bytes);
+
+    if if_none_match.is_some_and(|v| matches(&v, &tag)) {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        not_modified.headers_mut().insert(header::ETAG, tag);
+        return not_modified;
+    }
+    parts.headers.insert(header::ETAG, tag);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn weak_etag(body: &[u8]) -> HeaderValue {
+    let digest = Sha256::digest(body);
+    HeaderValue::from_str(&format!("W/\"{}\"", hex::encode(&digest[..16]))).expect("hex is a valid header value")
+}
+
+/// `If-None-Match` may list several tags or `*`; comparison is weak, so the
+/// `W/` prefix is ignored on both sides.
+fn matches(if_none_match: &HeaderValue, tag: &HeaderValue) -> bool {
+    let Ok(list) = if_none_match.to_str() else { return false };
+    let Ok(tag) = tag.to_str() else { return false };
+    let strip = |t: &str| t.trim().trim_start_matches("W/").to_string();
+    list.split(',').any(|candidate| candidate.trim() == "*" || strip(candidate) == strip(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+    use tower::ServiceExt;
+
+    fn app(version: Arc<AtomicU32>) -> Router {
+        Router::new().route(
+            "/list",
+            get(move || {
+                let version = version.clone();
+                async move { format!("[{}]", version.load(Ordering::SeqCst)) }
+            })
+            .post(|| async { "created" })
+            .layer(middleware::from_fn(etag)),
+        )
+    }
+
+    async fn send(app: &Router, method: Method, if_none_match: Option<&HeaderValue>) -> Response {
+        let mut req = Request::builder().method(method).uri("/list");
+        if let Some(tag) = if_none_match {
+            req = req.header(header::IF_NONE_MATCH, tag);
+        }
+        app.clone().oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn unchanged_list_round_trips_to_304() {
+        let version = Arc::new(AtomicU32::new(1));
+        let app = app(version.clone());
+
+        let first = send(&app, Method::GET, None).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let tag = first.headers()[header::ETAG].clone();
+        assert!(tag.to_str().unwrap().starts_with("W/\""));
+
+        let second = send(&app, Method::GET, Some(&tag)).await;
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(second.headers()[header::ETAG], tag);
+        assert!(to_bytes(second.into_body(), usize::MAX).await.unwrap().is_empty());
+
+        version.store(2, Ordering::SeqCst);
+        let changed = send(&app, Method::GET, Some(&tag)).await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(changed.headers()[header::ETAG], tag);
+    }
+
+    #[tokio::test]
+    async fn non_get_requests_are_untouched() {
+        let app = app(Arc::new(AtomicU32::new(1)));
+        let resp = send(&app, Method::POST, None).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(header::ETAG).is_none());
+    }
+
+    #[test]
+    fn if_none_match_accepts_lists_wildcards_and_strong_forms() {
+        let tag = HeaderValue::from_static("W/\"abc\"");
+        assert!(matches(&HeaderValue::from_static("\"x\", W/\"abc\""), &tag));
+        assert!(matches(&HeaderValue::from_static("\"abc\""), &tag));
+        assert!(matches(&HeaderValue::from_static("*"), &tag));
+        assert!(!matches(&HeaderValue::from_static("W/\"abd\""), &tag));
+    }
+}
@@ -0,0 +1,93 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::errors::AppError;
+
+/// Router fallback for paths no route matches.
+pub async fn route_not_found() -> AppError {
+    AppError::RouteNotFound
+}
+
+/// Axum answers a known path with the wrong method with an empty 405 and an
+/// `Allow` header; rewrite it into our JSON error envelope listing the same
+/// methods. The header is only added as the response leaves the router, so
+/// this must wrap the finished router rather than be one of its layers; the
+/// headers other layers set (CORS, request id) are carried over.
+pub async fn method_not_allowed_as_json(req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED || is_json {
+        return response;
+    }
+    let (mut parts, _) = response.into_parts();
+    let allowed = parts
+        .headers
+        .get(header::ALLOW)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let (json_parts, body) = AppError::MethodNotAllowed(allowed).into_response().into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.extend(json_parts.headers);
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let routes = Router::new()
+            .route("/x", get(|| async { "x" }).post(|| async { "posted" }))
+            .fallback(route_not_found);
+        Router::new()
+            .fallback_service(routes)
+            .layer(middleware::from_fn(method_not_allowed_as_json))
+    }
+
+    async fn call(method: &str, uri: &str) -> (StatusCode, Option<String>, serde_json::Value) {
+        let req = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        let resp = app().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let allow = resp.headers().get(header::ALLOW).map(|v| v.to_str().unwrap().to_string());
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, allow, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn unknown_path_gets_json_404() {
+        let (status, _, body) = call("GET", "/nope").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, serde_json::json!({ "error": "not found", "code": "route_not_found" }));
+    }
+
+    #[tokio::test]
+    async fn wrong_method_lists_allowed_methods() {
+        let (status, allow, body) = call("DELETE", "/x").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["code"], "method_not_allowed");
+        assert_eq!(body["allowed"], serde_json::json!(["GET", "HEAD", "POST"]));
+        assert_eq!(allow.as_deref(), Some("GET,HEAD,POST"));
+    }
+
+    #[tokio::test]
+    async fn matched_routes_pass_through() {
+        let (status, _, _) = call("POST", "/x").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -1,2 +1,14 @@
-pub mod admin;
 pub mod auth;
+pub mod body_limit;
+pub mod cookie;
+pub mod db_budget;
+pub mod deprecation;
+pub mod etag;
+pub mod fallback;
+pub mod panic;
+pub mod permission;
+pub mod request_id;
+pub mod security_headers;
+pub mod timeout;
+pub mod trace;
+pub mod workspace;
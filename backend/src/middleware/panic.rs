@@ -0,0 +1,90 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use axum::response::{IntoResponse, Response};
+
+use crate::errors::AppError;
+
+/// Handler panics caught since startup, reported with each one.
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces the default panic hook, which prints to stderr, with one that
+/// logs the message, location and a backtrace through `tracing`. It runs
+/// inside the panicking request's span, so the line carries its request id;
+/// panics in background tasks are logged the same way.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info.location().map(ToString::to_string).unwrap_or_default();
+        let backtrace = Backtrace::force_capture();
+        tracing::error!(
+            panic = %payload_message(info.payload()),
+            location = %location,
+            "Panic\n{backtrace}"
+        );
+    }));
+}
+
+/// `panic!` payloads are a `&str` or a `String` unless raised with
+/// `panic_any`.
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>")
+}
+
+/// `CatchPanicLayer` handler: the panic becomes our usual JSON 500 with the
+/// request id, instead of a dropped connection.
+pub fn panic_as_json(payload: Box<dyn Any + Send + 'static>) -> Response {
+    let total = PANICS.fetch_add(1, Ordering::Relaxed) + 1;
+    AppError::Internal(anyhow::anyhow!(
+        "handler panicked ({total} since startup): {}",
+        payload_message(payload.as_ref())
+    ))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::request_id::{request_id, REQUEST_ID_HEADER};
+    use axum::{body::Body, extract::Request, http::StatusCode, middleware, routing::get, Router};
+    use tower::ServiceExt;
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    async fn boom() -> &'static str {
+        panic!("to_bson failed")
+    }
+
+    #[tokio::test]
+    async fn panics_become_a_json_500_with_the_request_id() {
+        let app = Router::new()
+            .route("/boom", get(boom))
+            .layer(CatchPanicLayer::custom(panic_as_json))
+            .layer(middleware::from_fn(request_id));
+        let before = PANICS.load(Ordering::Relaxed);
+
+        let req = Request::builder().uri("/boom").header(REQUEST_ID_HEADER, "req-42").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(resp.headers()[REQUEST_ID_HEADER], "req-42");
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "error": "Internal server error", "code": "internal_error", "request_id": "req-42" })
+        );
+        assert!(PANICS.load(Ordering::Relaxed) > before);
+    }
+
+    #[test]
+    fn payload_messages_are_extracted() {
+        assert_eq!(payload_message(&"static"), "static");
+        assert_eq!(payload_message(&String::from("owned")), "owned");
+        assert_eq!(payload_message(&42), "<non-string panic payload>");
+    }
+}
@@ -0,0 +1,80 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    errors::{AppError, AuthErrorKind},
+    handlers::auth::Claims,
+};
+
+/// Route layer, inside `require_workspace`, that requires the caller's role
+/// in the workspace to grant `permission`:
+///
+/// `.layer(middleware::from_fn_with_state(CTI_WRITE, require_permission))`
+pub async fn require_permission(
+    State(permission): State<&'static str>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let claims = req
+        .extensions()
+        .get::<Claims>()
+        .ok_or(AppError::Unauthorized(AuthErrorKind::Missing))?;
+
+    if !claims.workspace_permits(permission) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Method, StatusCode},
+        middleware,
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    /// A global admin, so only `workspace_role` decides.
+    fn claims(workspace_role: &str) -> Claims {
+        Claims {
+            sub: "u1".into(),
+            email: "u1@example.com".into(),
+            email_verified: true,
+            username: "u1".into(),
+            role: "admin".into(),
+            exp: usize::MAX,
+            scopes: None,
+            auth_time: None,
+            workspace_id: Some("ws1".into()),
+            workspace_role: Some(workspace_role.into()),
+        }
+    }
+
+    async fn call(role: &str, method: Method) -> StatusCode {
+        let app = Router::new().route(
+            "/x",
+            post(|| async { "ok" })
+                .layer(middleware::from_fn_with_state("cti:write", require_permission))
+                .get(|| async { "ok" }),
+        );
+        let mut req = Request::builder().method(method).uri("/x").body(Body::empty()).unwrap();
+        req.extensions_mut().insert(claims(role));
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn layer_guards_only_the_methods_it_wraps() {
+        assert_eq!(call("member", Method::GET).await, StatusCode::OK);
+        assert_eq!(call("member", Method::POST).await, StatusCode::FORBIDDEN);
+        assert_eq!(call("manager", Method::POST).await, StatusCode::OK);
+        assert_eq!(call("admin", Method::POST).await, StatusCode::OK);
+    }
+}
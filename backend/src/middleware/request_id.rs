@@ -0,0 +1,107 @@
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The id assigned to the current request, stored in request extensions.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// The id of the request being handled on this task, if any. Lets
+/// `AppError::into_response` report it without every handler threading it
+/// through.
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// A client-supplied id is reused only when it is short and made of
+/// unremarkable characters, so it cannot be used to inject into log lines.
+fn is_acceptable(id: &str) -> bool {
+    (1..=128).contains(&id.len())
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+/// Outermost layer: adopts the incoming `X-Request-Id` or generates a UUID,
+/// records it in request extensions for the trace span, scopes it for error
+/// responses, and echoes it on the response.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_acceptable(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    req.extensions_mut().insert(RequestId(id.clone()));
+    let mut response = CURRENT_REQUEST_ID.scope(id.clone(), next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::AppError;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/ok", get(|| async { "ok" }))
+            .route("/boom", get(|| async { Err::<(), _>(AppError::Internal(anyhow::anyhow!("boom"))) }))
+            .route("/missing", get(|| async { Err::<(), _>(AppError::NotFound) }))
+            .layer(middleware::from_fn(request_id))
+    }
+
+    async fn call(uri: &str, incoming: Option<&str>) -> (String, serde_json::Value) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(id) = incoming {
+            req = req.header(REQUEST_ID_HEADER, id);
+        }
+        let resp = app().oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let id = resp.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (id, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn incoming_id_round_trips() {
+        let (id, _) = call("/ok", Some("abc-123")).await;
+        assert_eq!(id, "abc-123");
+    }
+
+    #[tokio::test]
+    async fn missing_or_unsafe_id_is_replaced() {
+        let (id, _) = call("/ok", None).await;
+        assert!(Uuid::parse_str(&id).is_ok());
+        let (id, _) = call("/ok", Some("evil\" level=error")).await;
+        assert!(Uuid::parse_str(&id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn server_errors_carry_the_request_id() {
+        let (id, body) = call("/boom", Some("trace-me")).await;
+        assert_eq!(id, "trace-me");
+        assert_eq!(body["request_id"], "trace-me");
+        assert_eq!(body["code"], "internal_error");
+    }
+
+    #[tokio::test]
+    async fn client_errors_omit_the_request_id() {
+        let (_, body) = call("/missing", Some("trace-me")).await;
+        assert_eq!(body["code"], "not_found");
+        assert!(body.get("request_id").is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+/// Adds the configured security headers (`AppConfig::security_headers`) to
+/// every response, errors included, and `Cache-Control: no-store` to the
+/// auth endpoints so tokens and profiles stay out of shared caches. A
+/// header a handler set itself is left alone.
+pub async fn security_headers(
+    State(headers): State<Arc<Vec<(HeaderName, HeaderValue)>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let auth = is_auth_path(req.uri().path());
+    let mut response = next.run(req).await;
+    let out = response.headers_mut();
+    for (name, value) in headers.iter() {
+        out.entry(name).or_insert_with(|| value.clone());
+    }
+    if auth {
+        out.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    response
+}
+
+/// `/api/auth/...` under any API version.
+fn is_auth_path(path: &str) -> bool {
+    let Some(rest) = path.strip_prefix("/api/") else {
+        return false;
+    };
+    let rest = match rest.split_once('/') {
+        Some((version, after)) if is_version(version) => after,
+        _ => rest,
+    };
+    rest == "auth" || rest.starts_with("auth/")
+}
+
+/// `v1`, `v2`, ...
+fn is_version(segment: &str) -> bool {
+    segment
+        .strip_prefix('v')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_paths_match_every_version() {
+        assert!(is_auth_path("/api/auth/me"));
+        assert!(is_auth_path("/api/v1/auth/session"));
+        assert!(is_auth_path("/api/v2/auth"));
+        assert!(!is_auth_path("/api/tasks"));
+        assert!(!is_auth_path("/api/v1/authors"));
+        assert!(!is_auth_path("/api/vault/auth/x"));
+        assert!(!is_auth_path("/auth/me"));
+    }
+}
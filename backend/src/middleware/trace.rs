@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http,
+    middleware::Next,
+    response::Response,
+};
+use tower_http::trace::{DefaultOnResponse, OnResponse};
+use tracing::Span;
+
+use crate::{handlers::auth::Claims, middleware::request_id::RequestId, telemetry};
+
+/// The span every request runs in, for `TraceLayer::make_span_with`. Router
+/// layers run after routing, so `route` is the matched template (e.g.
+/// `/api/v1/tasks/:id`) rather than the raw URI; unmatched paths have none.
+/// `status` and `latency_ms` are filled in on response, `user_id` and
+/// `role` by `record_user`.
+pub fn request_span<B>(req: &http::Request<B>) -> Span {
+    let request_id = req.extensions().get::<RequestId>().map(|id| id.0.as_str()).unwrap_or("-");
+    let route = req.extensions().get::<MatchedPath>().map(MatchedPath::as_str).unwrap_or("-");
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        route = %route,
+        request_id = %request_id,
+        status = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+        user_id = tracing::field::Empty,
+        role = tracing::field::Empty,
+        otel.name = tracing::field::Empty,
+        otel.kind = "server",
+        otel.status_code = tracing::field::Empty,
+    );
+    if telemetry::enabled() {
+        span.record("otel.name", format!("{} {route}", req.method()));
+        telemetry::join_remote_trace(&span, req.headers());
+    }
+    span
+}
+
+/// `TraceLayer::on_response`: records the outcome on the request span, then
+/// logs it as tower-http would.
+pub fn record_response<B>(res: &http::Response<B>, latency: Duration, span: &Span) {
+    span.record("status", res.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    if res.status().is_server_error() {
+        span.record("otel.status_code", "error");
+    }
+    DefaultOnResponse::new().on_response(res, latency, span)
+}
+
+/// Goes inside `require_auth`: attributes the request span to the caller.
+pub async fn record_user(req: Request, next: Next) -> Response {
+    if let Some(claims) = req.extensions().get::<Claims>() {
+        let span = Span::current();
+        span.record("user_id", claims.sub.as_str());
+        span.record("role", claims.role.as_str());
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+    use tower_http::trace::TraceLayer;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+    use super::*;
+
+    /// Every field recorded on `request` spans, as strings.
+    #[derive(Clone, Default)]
+    struct Fields(Arc<Mutex<HashMap<String, String>>>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.lock().unwrap().insert(field.name().to_string(), format!("{value:?}"));
+        }
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.lock().unwrap().insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Fields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+            if attrs.metadata().name() == "request" {
+                attrs.record(&mut self.clone());
+            }
+        }
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if ctx.span(id).is_some_and(|s| s.name() == "request") {
+                values.record(&mut self.clone());
+            }
+        }
+    }
+
+    fn claims() -> Claims {
+        Claims {
+            sub: "user-1".into(),
+            email: "u@example.com".into(),
+            email_verified: true,
+            username: "u".into(),
+            role: "manager".into(),
+            exp: usize::MAX,
+            scopes: None,
+            auth_time: None,
+            workspace_id: None,
+            workspace_role: None,
+        }
+    }
+
+    /// Stands in for `require_auth`.
+    async fn fake_auth(mut req: Request, next: Next) -> Response {
+        req.extensions_mut().insert(claims());
+        next.run(req).await
+    }
+
+    #[tokio::test]
+    async fn spans_carry_the_route_template_and_the_caller() {
+        let fields = Fields::default();
+        let subscriber = tracing_subscriber::registry().with(fields.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route("/tasks/:id", get(|| async { "ok" }))
+            .layer(middleware::from_fn(record_user))
+            .layer(middleware::from_fn(fake_auth))
+            .layer(TraceLayer::new_for_http().make_span_with(request_span).on_response(record_response));
+        let req = http::Request::builder().uri("/tasks/6f1c8f9e-0000-4000-8000-000000000000").body(Body::empty()).unwrap();
+        app.oneshot(req).await.unwrap();
+
+        let fields = fields.0.lock().unwrap();
+        assert_eq!(fields["route"], "/tasks/:id");
+        assert_eq!(fields["method"], "GET");
+        assert_eq!(fields["user_id"], "user-1");
+        assert_eq!(fields["role"], "manager");
+        assert_eq!(fields["status"], "200");
+        assert!(fields.contains_key("latency_ms"));
+    }
+}
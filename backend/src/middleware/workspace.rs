@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use bson::doc;
+use mongodb::options::FindOneOptions;
+
+use crate::{
+    db::{USERS, WORKSPACE_MEMBERS},
+    errors::{AppError, AuthErrorKind},
+    handlers::auth::{AppState, Claims},
+    models::workspace::{Membership, DEFAULT_WORKSPACE_ID},
+};
+
+/// Selects the workspace for one request, overriding the caller's active one.
+pub const WORKSPACE_HEADER: &str = "x-workspace-id";
+
+/// The workspace named by `X-Workspace-Id`, if any.
+fn requested_workspace(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(WORKSPACE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// The caller's active workspace, or the default one if they never switched.
+async fn active_workspace(state: &AppState, user_id: &str) -> Result<String, AppError> {
+    let options = FindOneOptions::builder().projection(doc! { "active_workspace_id": 1 }).build();
+    let user = state
+        .db
+        .collection::<bson::Document>(USERS)
+        .find_one(doc! { "_id": user_id }, options)
+        .await?;
+    Ok(user
+        .and_then(|u| u.get_str("active_workspace_id").ok().map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_WORKSPACE_ID.to_string()))
+}
+
+/// Route layer, inside `require_auth`, for everything that lives in a
+/// workspace. Resolves the workspace from `X-Workspace-Id` or the caller's
+/// active one, checks membership (403 otherwise), and records the workspace
+/// and the caller's role there in their `Claims`.
+pub async fn require_workspace(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let mut claims = req
+        .extensions()
+        .get::<Claims>()
+        .cloned()
+        .ok_or(AppError::Unauthorized(AuthErrorKind::Missing))?;
+    let ws = match requested_workspace(req.headers()) {
+        Some(ws) => ws,
+        None => active_workspace(&state, &claims.sub).await?,
+    };
+    let membership = state
+        .db
+        .collection::<Membership>(WORKSPACE_MEMBERS)
+        .find_one(doc! { "_id": Membership::id_for(&ws, &claims.sub) }, None)
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("{} is not a member of workspace {ws}", claims.sub);
+            AppError::Forbidden
+        })?;
+
+    claims.workspace_id = Some(ws);
+    claims.workspace_role = Some(membership.role);
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn header_names_the_workspace() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(requested_workspace(&headers), None);
+        headers.insert(WORKSPACE_HEADER, HeaderValue::from_static("  "));
+        assert_eq!(requested_workspace(&headers), None);
+        headers.insert(WORKSPACE_HEADER, HeaderValue::from_static(" ws1 "));
+        assert_eq!(requested_workspace(&headers).as_deref(), Some("ws1"));
+    }
+}
@@ -0,0 +1,689 @@
+//! Versioned data migrations, run at startup before the server binds.
+//!
+//! Each migration runs once per database; its id is recorded in
+//! `schema_migrations` when it finishes. Ids sort in the order migrations
+//! must run, so new ones go at the end of `registry` with the next number.
+//! Migrations should be idempotent, since one interrupted half-way runs again
+//! on the next start.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    future::Future,
+    pin::Pin,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use bson::{doc, Bson, Document};
+use chrono::{DateTime, Utc};
+use mongodb::{
+    error::{ErrorKind, Result as MongoResult},
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument, UpdateOptions},
+};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        collect, Db, API_KEYS, CTI_CATEGORIES, CTI_ITEMS, CTI_TYPES, INVITES, LOCKS, SCHEMA_MIGRATIONS,
+        TASKS, TEAMS, USERS, WORKFLOW_STATUSES, WORKSPACES, WORKSPACE_MEMBERS,
+    },
+    errors::mongo::is_duplicate_key,
+    markup::html_to_text,
+    models::{
+        dates::to_bson_date,
+        user::normalize_email,
+        workflow::{default_statuses, WorkflowStatus},
+        workspace::{default_workspace_role, Membership, DEFAULT_WORKSPACE_ID, WORKSPACE_MANAGER, WORKSPACE_MEMBER},
+    },
+};
+
+type MigrationFuture = Pin<Box<dyn Future<Output = MongoResult<()>> + Send>>;
+
+pub struct Migration {
+    pub id: &'static str,
+    pub description: &'static str,
+    run: fn(Db) -> MigrationFuture,
+}
+
+/// Every migration, in the order they run.
+pub fn registry() -> Vec<Migration> {
+    vec![
+        Migration {
+            id: "0001_normalize_task_status",
+            description: "Lower-case task statuses and use underscores, e.g. 'In Progress' -> 'in_progress'",
+            run: |db| Box::pin(normalize_task_status(db)),
+        },
+        Migration {
+            id: "0002_task_created_by",
+            description: "Add created_by: null to tasks created before creators were recorded",
+            run: |db| {
+                Box::pin(async move {
+                    db.collection::<Document>(TASKS)
+                        .update_many(
+                            doc! { "created_by": { "$exists": false } },
+                            doc! { "$set": { "created_by": null } },
+                            None,
+                        )
+                        .await?;
+                    Ok(())
+                })
+            },
+        },
+        Migration {
+            id: "0003_done_status_changed_at",
+            description: "Backfill status_changed_at on done tasks from updated_at, so they count as completed",
+            run: |db| {
+                Box::pin(async move {
+                    db.collection::<Document>(TASKS)
+                        .update_many(
+                            doc! { "status": "done", "status_changed_at": { "$in": [null] } },
+                            vec![doc! { "$set": { "status_changed_at": "$updated_at" } }],
+                            None,
+                        )
+                        .await?;
+                    Ok(())
+                })
+            },
+        },
+        Migration {
+            id: "0004_default_workspace",
+            description: "Move existing tasks, CTI and teams into the default workspace and make every user a member",
+            run: |db| Box::pin(default_workspace(db)),
+        },
+        Migration {
+            id: "0005_native_dates",
+            description: "Rewrite RFC 3339 date strings on tasks, users, CTI, workspaces, keys, invites as BSON dates",
+            run: |db| Box::pin(native_dates(db)),
+        },
+        Migration {
+            id: "0006_workflow_statuses",
+            description: "Seed workflow_statuses with todo, in_progress and done",
+            run: |db| Box::pin(seed_workflow_statuses(db)),
+        },
+        Migration {
+            id: "0007_note_sequence",
+            description: "Number existing task notes in created_at order and start each task's note_seq after them",
+            run: |db| Box::pin(note_sequence(db)),
+        },
+        Migration {
+            id: "0008_case_insensitive_emails",
+            description: "Lower-case user emails and rebuild the email and username indexes case-insensitively",
+            run: |db| Box::pin(case_insensitive_emails(db)),
+        },
+        Migration {
+            id: "0009_default_workspace_managers",
+            description: "Make global managers managers of the default workspace, where permissions now come from",
+            run: |db| Box::pin(default_workspace_managers(db)),
+        },
+        Migration {
+            id: "0010_note_plain_text",
+            description: "Store each task note's text without markup in plain_text, which search now matches",
+            run: |db| Box::pin(note_plain_text(db)),
+        },
+    ]
+}
+
+const STATUSES: &[&str] = &["todo", "in_progress", "done"];
+
+/// `"In Progress"`, `"in-progress"` and `" DONE "` become `in_progress` and
+/// `done`; `None` when the result is still not a known status.
+fn normalized_status(raw: &str) -> Option<String> {
+    let status = raw.trim().to_lowercase().replace([' ', '-'], "_");
+    STATUSES.contains(&status.as_str()).then_some(status)
+}
+
+async fn normalize_task_status(db: Db) -> MongoResult<()> {
+    let tasks = db.collection::<Document>(TASKS);
+    let cursor = tasks
+        .find(doc! { "status": { "$nin": STATUSES } }, None)
+        .await?;
+    for task in collect(cursor).await? {
+        let (Ok(id), Ok(raw)) = (task.get_str("_id"), task.get_str("status")) else {
+            continue;
+        };
+        match normalized_status(raw) {
+            Some(status) => {
+                tasks
+                    .update_one(doc! { "_id": id }, doc! { "$set": { "status": status } }, None)
+                    .await?;
+            }
+            None => tracing::warn!(task_id = id, status = raw, "Task has an unknown status; left unchanged"),
+        }
+    }
+    Ok(())
+}
+
+/// Inserts the default statuses, leaving any that already exist alone.
+async fn seed_workflow_statuses(db: Db) -> MongoResult<()> {
+    let statuses = db.collection::<WorkflowStatus>(WORKFLOW_STATUSES);
+    for status in default_statuses(Utc::now()) {
+        match statuses.insert_one(&status, None).await {
+            Ok(_) => {}
+            Err(e) if is_duplicate_key(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Workspace role for an existing user; see `default_workspace_role`.
+fn existing_user_workspace_role(user: &Document) -> &'static str {
+    default_workspace_role(user.get_str("role").unwrap_or_default())
+}
+
+async fn default_workspace(db: Db) -> MongoResult<()> {
+    let now = to_bson_date(Utc::now());
+    db.collection::<Document>(WORKSPACES)
+        .update_one(
+            doc! { "_id": DEFAULT_WORKSPACE_ID },
+            doc! { "$setOnInsert": { "name": "Default", "created_by": null, "created_at": &now } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    for collection in [TASKS, CTI_CATEGORIES, CTI_TYPES, CTI_ITEMS, TEAMS] {
+        db.collection::<Document>(collection)
+            .update_many(
+                doc! { "workspace_id": { "$exists": false } },
+                doc! { "$set": { "workspace_id": DEFAULT_WORKSPACE_ID } },
+                None,
+            )
+            .await?;
+    }
+    // Team names were unique globally; `ensure_indexes` makes them unique per workspace.
+    drop_index(&db, TEAMS, "name_1").await?;
+    let members = db.collection::<Document>(WORKSPACE_MEMBERS);
+    for user in collect(db.collection::<Document>(USERS).find(doc! {}, None).await?).await? {
+        let Ok(user_id) = user.get_str("_id") else {
+            continue;
+        };
+        members
+            .update_one(
+                doc! { "_id": Membership::id_for(DEFAULT_WORKSPACE_ID, user_id) },
+                doc! { "$setOnInsert": {
+                    "workspace_id": DEFAULT_WORKSPACE_ID,
+                    "user_id": user_id,
+                    "role": existing_user_workspace_role(&user),
+                    "created_at": &now,
+                } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Date fields per collection that `0005_native_dates` converts.
+const DATE_FIELDS: &[(&str, &[&str])] = &[
+    (TASKS, &["created_at", "updated_at", "status_changed_at"]),
+    (USERS, &["created_at", "updated_at", "last_login_at"]),
+    (CTI_CATEGORIES, &["created_at"]),
+    (CTI_TYPES, &["created_at"]),
+    (CTI_ITEMS, &["created_at"]),
+    (WORKSPACES, &["created_at"]),
+    (WORKSPACE_MEMBERS, &["created_at"]),
+    (API_KEYS, &["created_at", "last_used_at"]),
+    (INVITES, &["created_at", "expires_at"]),
+];
+
+fn string_to_date(value: &Bson) -> Option<Bson> {
+    let Bson::String(s) = value else { return None };
+    let dt = DateTime::parse_from_rfc3339(s).ok()?;
+    Some(to_bson_date(dt.with_timezone(&Utc)))
+}
+
+/// The `$set` that turns the string dates in `doc` into BSON dates, including
+/// `created_at` on embedded task notes. Strings that are not RFC 3339 are
+/// left alone.
+fn native_date_set(doc: &Document, fields: &[&str]) -> Document {
+    let mut set = Document::new();
+    for &field in fields {
+        if let Some(date) = doc.get(field).and_then(string_to_date) {
+            set.insert(field, date);
+        }
+    }
+    if let Ok(notes) = doc.get_array("notes") {
+        let mut changed = false;
+        let notes: Vec<Bson> = notes
+            .iter()
+            .map(|note| match note {
+                Bson::Document(note) => {
+                    let mut note = note.clone();
+                    if let Some(date) = note.get("created_at").and_then(string_to_date) {
+                        note.insert("created_at", date);
+                        changed = true;
+                    }
+                    Bson::Document(note)
+                }
+                other => other.clone(),
+            })
+            .collect();
+        if changed {
+            set.insert("notes", notes);
+        }
+    }
+    set
+}
+
+async fn native_dates(db: Db) -> MongoResult<()> {
+    for &(collection, fields) in DATE_FIELDS {
+        let docs = db.collection::<Document>(collection);
+        let mut any_string: Vec<Document> = fields.iter().map(|f| doc! { *f: { "$type": "string" } }).collect();
+        if collection == TASKS {
+            any_string.push(doc! { "notes.created_at": { "$type": "string" } });
+        }
+        let mut converted = 0;
+        for doc in collect(docs.find(doc! { "$or": any_string }, None).await?).await? {
+            let set = native_date_set(&doc, fields);
+            if set.is_empty() {
+                continue;
+            }
+            let id = doc.get("_id").cloned().unwrap_or(Bson::Null);
+            docs.update_one(doc! { "_id": id }, doc! { "$set": set }, None).await?;
+            converted += 1;
+        }
+        tracing::info!(collection, converted, "Converted string dates to BSON dates");
+    }
+    Ok(())
+}
+
+/// The `$set` giving the notes on `task` a `seq` of 1, 2, ... in
+/// `created_at` order (stored order for ties) and `note_seq` the last one.
+fn note_sequence_set(task: &Document) -> Document {
+    let mut notes: Vec<Document> = task
+        .get_array("notes")
+        .map(|notes| notes.iter().filter_map(|n| n.as_document().cloned()).collect())
+        .unwrap_or_default();
+    notes.sort_by_key(|n| n.get_datetime("created_at").ok().copied());
+    for (i, note) in notes.iter_mut().enumerate() {
+        note.insert("seq", i as i64 + 1);
+    }
+    doc! { "note_seq": notes.len() as i64, "notes": notes }
+}
+
+async fn note_sequence(db: Db) -> MongoResult<()> {
+    let tasks = db.collection::<Document>(TASKS);
+    let mut numbered = 0;
+    for task in collect(tasks.find(doc! { "note_seq": { "$exists": false } }, None).await?).await? {
+        let id = task.get("_id").cloned().unwrap_or(Bson::Null);
+        tasks.update_one(doc! { "_id": id }, doc! { "$set": note_sequence_set(&task) }, None).await?;
+        numbered += 1;
+    }
+    tracing::info!(numbered, "Numbered task notes");
+    Ok(())
+}
+
+/// The `$set` giving each note on `task` its `plain_text`: the text of
+/// `rendered_html` for markdown notes, else of the stored HTML.
+fn note_text_set(task: &Document) -> Document {
+    let mut notes: Vec<Document> = task
+        .get_array("notes")
+        .map(|notes| notes.iter().filter_map(|n| n.as_document().cloned()).collect())
+        .unwrap_or_default();
+    for note in &mut notes {
+        let html = note.get_str("rendered_html").or_else(|_| note.get_str("note")).unwrap_or_default();
+        let text = html_to_text(html);
+        note.insert("plain_text", text);
+    }
+    doc! { "notes": notes }
+}
+
+async fn note_plain_text(db: Db) -> MongoResult<()> {
+    let tasks = db.collection::<Document>(TASKS);
+    let filter = doc! { "notes": { "$elemMatch": { "plain_text": { "$exists": false } } } };
+    let mut converted = 0;
+    for task in collect(tasks.find(filter, None).await?).await? {
+        let id = task.get("_id").cloned().unwrap_or(Bson::Null);
+        tasks.update_one(doc! { "_id": id }, doc! { "$set": note_text_set(&task) }, None).await?;
+        converted += 1;
+    }
+    tracing::info!(converted, "Stored the plain text of task notes");
+    Ok(())
+}
+
+/// Users whose `field` values differ only in case, as (lower-cased value,
+/// user ids) pairs sorted by value.
+fn case_collisions(users: &[Document], field: &str) -> Vec<(String, Vec<String>)> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for user in users {
+        if let (Ok(id), Ok(value)) = (user.get_str("_id"), user.get_str(field)) {
+            groups.entry(normalize_email(value)).or_default().push(id.to_string());
+        }
+    }
+    groups.into_iter().filter(|(_, ids)| ids.len() > 1).collect()
+}
+
+/// Lower-cases emails, then drops the case-sensitive `email_1` and
+/// `username_1` indexes so `ensure_indexes` recreates them with a
+/// case-insensitive collation. Accounts that would clash are not merged:
+/// each clash is logged and the migration fails until an admin resolves it.
+async fn case_insensitive_emails(db: Db) -> MongoResult<()> {
+    let users = db.collection::<Document>(USERS);
+    let projection = FindOptions::builder().projection(doc! { "email": 1, "username": 1 }).build();
+    let all = collect(users.find(doc! {}, projection).await?).await?;
+    let mut clashes = 0;
+    for field in ["email", "username"] {
+        for (value, user_ids) in case_collisions(&all, field) {
+            tracing::error!(field, value, ?user_ids, "Users differ only in case; rename or delete all but one");
+            clashes += 1;
+        }
+    }
+    if clashes > 0 {
+        return Err(mongodb::error::Error::custom(format!(
+            "{clashes} email or username clashes ignoring case; see the log for the users involved"
+        )));
+    }
+    let lowered = users
+        .update_many(
+            doc! { "$expr": { "$ne": ["$email", { "$toLower": "$email" }] } },
+            vec![doc! { "$set": { "email": { "$toLower": "$email" } } }],
+            None,
+        )
+        .await?;
+    tracing::info!(lowered = lowered.modified_count, "Lower-cased user emails");
+    for index in ["email_1", "username_1"] {
+        drop_index(&db, USERS, index).await?;
+    }
+    Ok(())
+}
+
+/// Drops index `name` from `collection`, if both exist.
+async fn drop_index(db: &Db, collection: &str, name: &str) -> MongoResult<()> {
+    match db.collection::<Document>(collection).drop_index(name, None).await {
+        Ok(()) => Ok(()),
+        // The collection or the index does not exist yet.
+        Err(e) if matches!(*e.kind, ErrorKind::Command(ref c) if c.code == 26 || c.code == 27) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Task, CTI and share permissions now follow the workspace role, and
+/// `0004_default_workspace` made managers plain members.
+async fn default_workspace_managers(db: Db) -> MongoResult<()> {
+    let options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+    let managers = collect(db.collection::<Document>(USERS).find(doc! { "role": "manager" }, options).await?).await?;
+    let ids: Vec<String> = managers
+        .iter()
+        .filter_map(|u| u.get_str("_id").ok())
+        .map(|id| Membership::id_for(DEFAULT_WORKSPACE_ID, id))
+        .collect();
+    let promoted = db
+        .collection::<Document>(WORKSPACE_MEMBERS)
+        .update_many(
+            doc! { "_id": { "$in": ids }, "role": WORKSPACE_MEMBER },
+            doc! { "$set": { "role": WORKSPACE_MANAGER } },
+            None,
+        )
+        .await?;
+    tracing::info!(promoted = promoted.modified_count, "Made managers managers of the default workspace");
+    Ok(())
+}
+
+/// Migrations in `all` that are not in `applied`, in order.
+fn pending<'a>(all: &'a [Migration], applied: &HashSet<String>) -> Vec<&'a Migration> {
+    all.iter().filter(|m| !applied.contains(m.id)).collect()
+}
+
+/// How long a lock holder has before another replica may take over, in case
+/// it died mid-run.
+const LOCK_LEASE: Duration = Duration::from_secs(10 * 60);
+/// How often the holder extends its lease while migrations run.
+const LOCK_RENEW: Duration = Duration::from_secs(60);
+/// How long to wait for another replica's run before giving up.
+const LOCK_WAIT: Duration = Duration::from_secs(15 * 60);
+const LOCK_ID: &str = "schema_migrations";
+
+/// Takes the migrations lock, or returns false if another live holder has it.
+async fn try_lock(db: &Db, owner: &str) -> MongoResult<bool> {
+    let now = bson::DateTime::now();
+    let expires_at = lease_end(now);
+    let result = db
+        .collection::<Document>(LOCKS)
+        .find_one_and_update(
+            doc! { "_id": LOCK_ID, "expires_at": { "$lt": now } },
+            doc! { "$set": { "owner": owner, "expires_at": expires_at } },
+            FindOneAndUpdateOptions::builder()
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await;
+    match result {
+        Ok(_) => Ok(true),
+        // The lock document exists and has not expired.
+        Err(e) if is_duplicate_key(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn lease_end(now: bson::DateTime) -> bson::DateTime {
+    bson::DateTime::from_millis(now.timestamp_millis() + LOCK_LEASE.as_millis() as i64)
+}
+
+/// Extends the lease, or returns false if `owner` no longer holds the lock.
+async fn renew_lock(db: &Db, owner: &str) -> MongoResult<bool> {
+    let result = db
+        .collection::<Document>(LOCKS)
+        .update_one(
+            doc! { "_id": LOCK_ID, "owner": owner },
+            doc! { "$set": { "expires_at": lease_end(bson::DateTime::now()) } },
+            None,
+        )
+        .await?;
+    Ok(result.matched_count == 1)
+}
+
+/// Whether a lease last renewed `since_renewal` ago is still safely ours
+/// until the next attempt to renew it.
+fn lease_outlasts_next_renewal(since_renewal: Duration) -> bool {
+    since_renewal + LOCK_RENEW < LOCK_LEASE
+}
+
+/// Renews the lease every `LOCK_RENEW` while polled, and returns once the
+/// lock is lost, or could not be renewed before it may have expired.
+async fn heartbeat(db: &Db, owner: &str) -> anyhow::Error {
+    let mut renewed_at = tokio::time::Instant::now();
+    loop {
+        tokio::time::sleep(LOCK_RENEW).await;
+        match renew_lock(db, owner).await {
+            Ok(true) => renewed_at = tokio::time::Instant::now(),
+            Ok(false) => return anyhow!("Another instance took over the migrations lock"),
+            Err(e) if lease_outlasts_next_renewal(renewed_at.elapsed()) => {
+                tracing::warn!(error = %e, "Could not renew the migrations lock; retrying");
+            }
+            Err(e) => return anyhow::Error::new(e).context("Could not renew the migrations lock"),
+        }
+    }
+}
+
+async fn unlock(db: &Db, owner: &str) -> MongoResult<()> {
+    db.collection::<Document>(LOCKS)
+        .delete_one(doc! { "_id": LOCK_ID, "owner": owner }, None)
+        .await?;
+    Ok(())
+}
+
+/// Runs every pending migration. Only one process runs them at a time; the
+/// others wait for the lock and then find nothing left to do. The holder
+/// keeps its lease alive while it runs, and stops mid-migration if it cannot,
+/// since another instance may then start the same migrations.
+pub async fn run(db: &Db) -> anyhow::Result<()> {
+    let owner = Uuid::new_v4().to_string();
+    let waiting_since = tokio::time::Instant::now();
+    while !try_lock(db, &owner).await.context("Could not take the migrations lock")? {
+        if waiting_since.elapsed() > LOCK_WAIT {
+            bail!("Timed out waiting for another instance to finish migrations");
+        }
+        tracing::info!("Another instance is running migrations; waiting");
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    let result = tokio::select! {
+        result = apply_pending(db) => result,
+        lost = heartbeat(db, &owner) => Err(lost),
+    };
+    unlock(db, &owner).await.context("Could not release the migrations lock")?;
+    result
+}
+
+async fn apply_pending(db: &Db) -> anyhow::Result<()> {
+    let applied_coll = db.collection::<Document>(SCHEMA_MIGRATIONS);
+    let applied: HashSet<String> = collect(applied_coll.find(doc! {}, None).await?)
+        .await?
+        .iter()
+        .filter_map(|d| d.get_str("_id").ok().map(str::to_string))
+        .collect();
+
+    let all = registry();
+    let pending = pending(&all, &applied);
+    if pending.is_empty() {
+        tracing::info!(applied = applied.len(), "Schema is up to date");
+        return Ok(());
+    }
+    for migration in pending {
+        tracing::info!(id = migration.id, "Running migration: {}", migration.description);
+        (migration.run)(db.clone())
+            .await
+            .with_context(|| format!("Migration {} failed", migration.id))?;
+        applied_coll
+            .insert_one(
+                doc! {
+                    "_id": migration.id,
+                    "description": migration.description,
+                    "applied_at": bson::DateTime::now(),
+                },
+                None,
+            )
+            .await?;
+        tracing::info!(id = migration.id, "Migration applied");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::workspace::WORKSPACE_ADMIN;
+
+    #[test]
+    fn a_lease_that_could_lapse_before_the_next_renewal_is_given_up() {
+        assert!(lease_outlasts_next_renewal(Duration::ZERO));
+        assert!(lease_outlasts_next_renewal(LOCK_LEASE - LOCK_RENEW * 2));
+        assert!(!lease_outlasts_next_renewal(LOCK_LEASE - LOCK_RENEW));
+    }
+
+    #[test]
+    fn ids_are_unique_and_in_order() {
+        let ids: Vec<_> = registry().iter().map(|m| m.id).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(ids, sorted);
+    }
+
+    #[test]
+    fn pending_skips_applied_and_keeps_order() {
+        let all = registry();
+        let applied: HashSet<String> = [all[0].id.to_string()].into();
+        let ids: Vec<_> = pending(&all, &applied).iter().map(|m| m.id).collect();
+        assert_eq!(ids, all[1..].iter().map(|m| m.id).collect::<Vec<_>>());
+        assert_eq!(pending(&all, &HashSet::new()).len(), all.len());
+    }
+
+    #[test]
+    fn statuses_are_normalized() {
+        assert_eq!(normalized_status("In Progress").as_deref(), Some("in_progress"));
+        assert_eq!(normalized_status("in-progress").as_deref(), Some("in_progress"));
+        assert_eq!(normalized_status(" DONE ").as_deref(), Some("done"));
+        assert_eq!(normalized_status("Todo").as_deref(), Some("todo"));
+        assert_eq!(normalized_status("blocked"), None);
+    }
+
+    #[test]
+    fn global_admins_run_the_default_workspace() {
+        assert_eq!(existing_user_workspace_role(&doc! { "role": "admin" }), WORKSPACE_ADMIN);
+        assert_eq!(existing_user_workspace_role(&doc! { "role": "manager" }), WORKSPACE_MANAGER);
+        assert_eq!(existing_user_workspace_role(&doc! {}), WORKSPACE_MEMBER);
+    }
+
+    #[test]
+    fn string_dates_become_bson_dates() {
+        let doc = doc! {
+            "_id": "t1",
+            "created_at": "2024-01-02T03:04:05.678Z",
+            "updated_at": to_bson_date(Utc::now()),
+            "status_changed_at": null,
+            "title": "2024-01-02T03:04:05Z",
+            "notes": [
+                { "_id": "n1", "created_at": "2024-01-02T03:04:05+02:00" },
+                { "_id": "n2", "created_at": to_bson_date(Utc::now()) },
+            ],
+        };
+        let set = native_date_set(&doc, DATE_FIELDS[0].1);
+        assert_eq!(set.keys().collect::<Vec<_>>(), vec!["created_at", "notes"]);
+        assert_eq!(set.get_datetime("created_at").unwrap().timestamp_millis(), 1_704_164_645_678);
+        let notes = set.get_array("notes").unwrap();
+        let first = notes[0].as_document().unwrap().get_datetime("created_at").unwrap();
+        assert_eq!(first.timestamp_millis(), 1_704_157_445_000);
+        assert_eq!(notes[1], doc.get_array("notes").unwrap()[1]);
+
+        let (_, key_fields) = DATE_FIELDS.iter().find(|(c, _)| *c == API_KEYS).unwrap();
+        let key = doc! { "created_at": "2024-01-02T03:04:05Z", "last_used_at": null };
+        assert_eq!(native_date_set(&key, key_fields).keys().collect::<Vec<_>>(), vec!["created_at"]);
+    }
+
+    #[test]
+    fn notes_are_numbered_in_created_order() {
+        let at = |millis| Bson::DateTime(bson::DateTime::from_millis(millis));
+        let task = doc! {
+            "_id": "t1",
+            "notes": [
+                { "_id": "late", "created_at": at(2_000) },
+                { "_id": "early", "created_at": at(1_000) },
+                { "_id": "tied", "created_at": at(2_000) },
+            ],
+        };
+        let set = note_sequence_set(&task);
+        assert_eq!(set.get_i64("note_seq").unwrap(), 3);
+        let numbered: Vec<_> = set
+            .get_array("notes")
+            .unwrap()
+            .iter()
+            .map(|n| (n.as_document().unwrap().get_str("_id").unwrap(), n.as_document().unwrap().get_i64("seq").unwrap()))
+            .collect();
+        assert_eq!(numbered, [("early", 1), ("late", 2), ("tied", 3)]);
+        assert_eq!(note_sequence_set(&doc! { "notes": null }), doc! { "note_seq": 0_i64, "notes": [] });
+    }
+
+    #[test]
+    fn notes_get_the_text_they_render_to() {
+        let task = doc! {
+            "notes": [
+                { "_id": "html", "note": "<p>see the <strong>rollback</strong> plan</p>" },
+                { "_id": "md", "note": "see the **rollback**", "rendered_html": "<p>see the <em>rollback</em></p>" },
+            ],
+        };
+        let set = note_text_set(&task);
+        let text = |i: usize| set.get_array("notes").unwrap()[i].as_document().unwrap().get_str("plain_text").unwrap();
+        assert_eq!((text(0), text(1)), ("see the rollback plan", "see the rollback"));
+    }
+
+    #[test]
+    fn case_clashes_are_grouped_not_merged() {
+        let users = [
+            doc! { "_id": "u1", "email": "Bob@Example.com", "username": "bob" },
+            doc! { "_id": "u2", "email": "bob@example.com", "username": "Bobby" },
+            doc! { "_id": "u3", "email": "carol@example.com", "username": "BOB" },
+            doc! { "_id": "u4", "username": "bobby" },
+        ];
+        assert_eq!(case_collisions(&users, "email"), [("bob@example.com".to_string(), vec!["u1".to_string(), "u2".to_string()])]);
+        let usernames = case_collisions(&users, "username");
+        assert_eq!(usernames.iter().map(|(v, ids)| (v.as_str(), ids.len())).collect::<Vec<_>>(), [("bob", 2), ("bobby", 2)]);
+        assert!(case_collisions(&users[2..3], "email").is_empty());
+    }
+
+    #[test]
+    fn converted_documents_need_nothing_more() {
+        let doc = doc! { "created_at": to_bson_date(Utc::now()), "last_login_at": "not a date" };
+        assert!(native_date_set(&doc, DATE_FIELDS[1].1).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! GET /api/admin/stats: sizing data for capacity planning. Only counts and
+//! sizes; nothing that identifies a user or a task.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Days covered by `TaskStats::created_per_day`, today included.
+pub const CREATED_PER_DAY_DAYS: i64 = 30;
+/// Lower bounds of the `notes_per_task` buckets; the last is open-ended.
+pub const NOTES_BUCKETS: &[u64] = &[0, 1, 2, 5, 10, 25, 50, 100];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminStatsQuery {
+    /// Recompute instead of serving the cached stats.
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminStats {
+    pub generated_at: DateTime<Utc>,
+    /// Every collection, by name.
+    pub collections: Vec<CollectionStats>,
+    /// Absent when the task aggregations failed or ran out of time.
+    pub tasks: Option<TaskStats>,
+    pub users_by_role: Option<BTreeMap<String, u64>>,
+    /// What could not be gathered and why, e.g. `collStats` not permitted.
+    pub warnings: Vec<String>,
+}
+
+/// One collection's size. Without `collStats` only `documents` (an
+/// estimate) is known and the rest are absent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CollectionStats {
+    pub name: String,
+    pub documents: u64,
+    pub avg_document_bytes: Option<u64>,
+    pub data_bytes: Option<u64>,
+    pub storage_bytes: Option<u64>,
+    pub index_bytes: Option<u64>,
+    /// Size of each index, by index name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexes: Option<BTreeMap<String, u64>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStats {
+    pub count: u64,
+    /// Mean BSON size of a task, notes included.
+    pub avg_size_bytes: u64,
+    pub notes_per_task: NotesDistribution,
+    /// Oldest day first, with zero for days without tasks.
+    pub created_per_day: Vec<DayCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayCount {
+    pub date: NaiveDate,
+    pub tasks: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NotesDistribution {
+    pub mean: f64,
+    pub max: u64,
+    pub buckets: Vec<NotesBucket>,
+}
+
+/// Tasks with between `min` and `max` notes, inclusive; `max` is absent on
+/// the last bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NotesBucket {
+    pub min: u64,
+    pub max: Option<u64>,
+    pub tasks: u64,
+}
+
+impl NotesDistribution {
+    /// From `(notes, tasks with that many)` pairs, in any order.
+    pub fn from_counts(counts: &[(u64, u64)]) -> Self {
+        let mut buckets: Vec<NotesBucket> = NOTES_BUCKETS
+            .iter()
+            .enumerate()
+            .map(|(i, &min)| NotesBucket { min, max: NOTES_BUCKETS.get(i + 1).map(|next| next - 1), tasks: 0 })
+            .collect();
+        let (mut tasks, mut notes, mut max) = (0, 0, 0);
+        for &(n, count) in counts {
+            if let Some(bucket) = buckets.iter_mut().rev().find(|b| n >= b.min) {
+                bucket.tasks += count;
+            }
+            tasks += count;
+            notes += n * count;
+            max = max.max(n);
+        }
+        let mean = if tasks == 0 { 0.0 } else { notes as f64 / tasks as f64 };
+        Self { mean, max, buckets }
+    }
+}
+
+/// `(date, tasks)` pairs spread over the `CREATED_PER_DAY_DAYS` days up to
+/// `today`, filling the days without any.
+pub fn created_per_day(counts: &[(NaiveDate, u64)], today: NaiveDate) -> Vec<DayCount> {
+    (0..CREATED_PER_DAY_DAYS)
+        .rev()
+        .map(|ago| today - Duration::days(ago))
+        .map(|date| DayCount { date, tasks: counts.iter().find(|(d, _)| *d == date).map_or(0, |(_, n)| *n) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_fall_into_buckets() {
+        let d = NotesDistribution::from_counts(&[(0, 4), (3, 2), (1, 1), (250, 1)]);
+        assert_eq!(d.max, 250);
+        assert_eq!(d.mean, (3.0 * 2.0 + 1.0 + 250.0) / 8.0);
+        let tasks: Vec<u64> = d.buckets.iter().map(|b| b.tasks).collect();
+        assert_eq!(tasks, [4, 1, 2, 0, 0, 0, 0, 1]);
+        assert_eq!((d.buckets[2].min, d.buckets[2].max), (2, Some(4)));
+        assert_eq!(d.buckets.last().unwrap().max, None);
+        assert_eq!(NotesDistribution::from_counts(&[]).mean, 0.0);
+    }
+
+    #[test]
+    fn every_day_of_the_trend_is_listed() {
+        let today: NaiveDate = "2026-06-30".parse().unwrap();
+        let days = created_per_day(&[("2026-06-29".parse().unwrap(), 7), (today, 2)], today);
+        assert_eq!(days.len(), CREATED_PER_DAY_DAYS as usize);
+        assert_eq!(days[0], DayCount { date: "2026-06-01".parse().unwrap(), tasks: 0 });
+        assert_eq!(days[28].tasks, 7);
+        assert_eq!(days[29], DayCount { date: today, tasks: 2 });
+    }
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, IdGen},
+    models::dates::{bson_date, optional_bson_date},
+    permissions::SCOPES,
+};
+
+/// Scopes an API key may be granted.
+pub const API_KEY_SCOPES: &[&str] = SCOPES;
+
+const KEY_PREFIX: &str = "mc_";
+
+/// Stored API key. Only the SHA-256 of the secret is persisted; the plaintext
+/// is returned once, from the create endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    pub key_hash: String,
+    /// First characters of the key, so owners can tell keys apart.
+    pub key_prefix: String,
+    pub owner_id: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default, with = "optional_bson_date")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    /// Generates a new key, returning the record to store and the plaintext
+    /// secret. The secret is always random, whatever `ids` is.
+    pub fn generate(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        name: String,
+        owner_id: String,
+        scopes: Vec<String>,
+    ) -> (Self, String) {
+        let secret = format!(
+            "{KEY_PREFIX}{}{}",
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+        let key = Self {
+            id: ids.new_id(),
+            name,
+            key_hash: hash_key(&secret),
+            key_prefix: secret[..KEY_PREFIX.len() + 8].to_string(),
+            owner_id,
+            scopes,
+            created_at: clock.now(),
+            last_used_at: None,
+        };
+        (key, secret)
+    }
+}
+
+pub fn hash_key(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// API key as listed to its owner — never includes the hash.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyPublic {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for ApiKeyPublic {
+    fn from(k: ApiKey) -> Self {
+        Self {
+            id: k.id,
+            name: k.name,
+            key_prefix: k.key_prefix,
+            scopes: k.scopes,
+            created_at: k.created_at,
+            last_used_at: k.last_used_at,
+        }
+    }
+}
+
+/// Response to key creation: the only time the plaintext key is shown.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKeyPublic,
+    pub key: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{SystemClock, UuidIds};
+
+    #[test]
+    fn generated_key_hash_matches_secret() {
+        let (key, secret) = ApiKey::generate(&SystemClock, &UuidIds, "ci".into(), "u1".into(), vec![]);
+        assert!(secret.starts_with("mc_"));
+        assert_eq!(key.key_hash, hash_key(&secret));
+        assert!(secret.starts_with(&key.key_prefix));
+        assert!(key.last_used_at.is_none());
+    }
+
+    #[test]
+    fn generated_keys_are_unique() {
+        let (_, a) = ApiKey::generate(&SystemClock, &UuidIds, "a".into(), "u1".into(), vec![]);
+        let (_, b) = ApiKey::generate(&SystemClock, &UuidIds, "b".into(), "u1".into(), vec![]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn public_view_omits_hash() {
+        let (key, _) = ApiKey::generate(&SystemClock, &UuidIds, "ci".into(), "u1".into(), vec!["tasks:read".into()]);
+        let json = serde_json::to_value(ApiKeyPublic::from(key)).unwrap();
+        assert!(json.get("key_hash").is_none());
+        assert_eq!(json["scopes"][0], "tasks:read");
+    }
+}
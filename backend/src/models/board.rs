@@ -0,0 +1,177 @@
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::FieldError,
+    models::{task::Task, workflow::Workflow},
+};
+
+/// Query parameters for GET /api/tasks/board, read alongside `TaskQuery`.
+#[derive(Debug, Default, Deserialize)]
+pub struct BoardQuery {
+    /// Tasks listed per column; `BOARD_COLUMN_SIZE` when absent.
+    pub per_column: Option<u64>,
+}
+
+impl BoardQuery {
+    /// `per_column`, or `default` when absent; at most `max`.
+    pub fn per_column(&self, default: u64, max: u64) -> Result<u64, FieldError> {
+        match self.per_column.unwrap_or(default) {
+            n if (1..=max).contains(&n) => Ok(n),
+            _ => Err(FieldError::new(
+                "per_column",
+                "out_of_range",
+                format!("per_column must be between 1 and {max}"),
+            )),
+        }
+    }
+}
+
+/// Response for GET /api/tasks/board: one column per workflow status.
+#[derive(Debug, Serialize)]
+pub struct TaskBoard {
+    pub columns: Vec<BoardColumn>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BoardColumn {
+    pub status: String,
+    pub label: String,
+    /// Every task in this column that matches the filter.
+    pub total: u64,
+    /// Whether there are more than `tasks` holds.
+    pub has_more: bool,
+    pub tasks: Vec<Task>,
+}
+
+/// The statuses the board shows, in workflow order: all of them, or those
+/// in the filter's `status: { $in: [...] }` when it has one.
+pub fn board_statuses(workflow: &Workflow, filter: &Document) -> Vec<String> {
+    let wanted = filter.get_document("status").ok().and_then(|s| s.get_array("$in").ok());
+    workflow
+        .statuses()
+        .iter()
+        .map(|s| &s.key)
+        .filter(|key| wanted.is_none_or(|list| list.iter().any(|v| v.as_str() == Some(key.as_str()))))
+        .cloned()
+        .collect()
+}
+
+/// Facet holding column `i`'s tasks; status keys are not used as field
+/// names since they could contain characters `$facet` rejects.
+fn column_facet(i: usize) -> String {
+    format!("c{i}")
+}
+
+/// One aggregation for the whole board: the first `per_column` tasks of
+/// each status in `sort` order, plus a count per status.
+pub fn board_pipeline(mut filter: Document, sort: Document, statuses: &[String], per_column: u64) -> Vec<Document> {
+    filter.insert("status", doc! { "$in": statuses });
+    let mut facets = doc! {
+        "totals": [{ "$group": { "_id": "$status", "total": { "$sum": 1 } } }],
+    };
+    for (i, status) in statuses.iter().enumerate() {
+        facets.insert(
+            column_facet(i),
+            vec![
+                doc! { "$match": { "status": status } },
+                doc! { "$sort": sort.clone() },
+                doc! { "$limit": per_column as i64 },
+            ],
+        );
+    }
+    vec![doc! { "$match": filter }, doc! { "$facet": facets }]
+}
+
+impl TaskBoard {
+    /// Reads the single document `board_pipeline` produces.
+    pub fn from_facets(result: &Document, workflow: &Workflow, statuses: &[String]) -> bson::de::Result<Self> {
+        let totals: Vec<(String, u64)> = result
+            .get_array("totals")
+            .map(|t| t.iter().filter_map(Bson::as_document).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|t| {
+                let total = t.get_i32("total").map(i64::from).or_else(|_| t.get_i64("total")).ok()?;
+                Some((t.get_str("_id").ok()?.to_string(), total as u64))
+            })
+            .collect();
+        let mut columns = Vec::with_capacity(statuses.len());
+        for (i, status) in statuses.iter().enumerate() {
+            let tasks = result
+                .get_array(column_facet(i))
+                .map(|docs| docs.iter().filter_map(Bson::as_document).cloned().collect::<Vec<_>>())
+                .unwrap_or_default()
+                .into_iter()
+                .map(bson::from_document)
+                .collect::<Result<Vec<Task>, _>>()?;
+            let total = totals.iter().find(|(s, _)| s == status).map_or(0, |(_, n)| *n);
+            columns.push(BoardColumn {
+                status: status.clone(),
+                label: workflow.get(status).map_or_else(|| status.clone(), |s| s.label.clone()),
+                has_more: total > tasks.len() as u64,
+                total,
+                tasks,
+            });
+        }
+        Ok(Self { columns })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{FakeClock, SequentialIds},
+        models::dates::to_stored_document,
+    };
+
+    fn keys(statuses: &[String]) -> Vec<&str> {
+        statuses.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn columns_follow_the_workflow_and_the_status_filter() {
+        let workflow = Workflow::default();
+        assert_eq!(keys(&board_statuses(&workflow, &doc! {})), ["todo", "in_progress", "done"]);
+        let filter = doc! { "status": { "$in": ["done", "todo"] } };
+        assert_eq!(keys(&board_statuses(&workflow, &filter)), ["todo", "done"]);
+    }
+
+    #[test]
+    fn one_facet_per_column_plus_totals() {
+        let statuses = vec!["todo".to_string(), "done".to_string()];
+        let pipeline = board_pipeline(doc! { "workspace_id": "w1" }, doc! { "created_at": -1 }, &statuses, 50);
+        assert_eq!(pipeline[0], doc! { "$match": { "workspace_id": "w1", "status": { "$in": ["todo", "done"] } } });
+        let facets = pipeline[1].get_document("$facet").unwrap();
+        assert_eq!(facets.keys().collect::<Vec<_>>(), ["totals", "c0", "c1"]);
+        let done = facets.get_array("c1").unwrap();
+        assert_eq!(done[0], Bson::Document(doc! { "$match": { "status": "done" } }));
+        assert_eq!(done[2], Bson::Document(doc! { "$limit": 50_i64 }));
+    }
+
+    #[test]
+    fn columns_report_totals_and_whether_more_exist() {
+        let task = Task::new(&FakeClock::default(), &SequentialIds::default(), "Patch".into(), "".into());
+        let stored = to_stored_document(&task).unwrap();
+        let result = doc! {
+            "totals": [{ "_id": "todo", "total": 3 }, { "_id": "done", "total": 1 }],
+            "c0": [stored.clone()],
+            "c1": [stored],
+            "c2": [],
+        };
+        let statuses = vec!["todo".to_string(), "done".to_string(), "in_progress".to_string()];
+        let board = TaskBoard::from_facets(&result, &Workflow::default(), &statuses).unwrap();
+        let summary: Vec<_> = board.columns.iter().map(|c| (c.status.as_str(), c.total, c.tasks.len(), c.has_more)).collect();
+        assert_eq!(summary, [("todo", 3, 1, true), ("done", 1, 1, false), ("in_progress", 0, 0, false)]);
+        assert_eq!(board.columns[2].label, "In progress");
+    }
+
+    #[test]
+    fn per_column_defaults_and_is_bounded() {
+        assert_eq!(BoardQuery::default().per_column(50, 100).unwrap(), 50);
+        assert_eq!(BoardQuery { per_column: Some(100) }.per_column(50, 100).unwrap(), 100);
+        assert_eq!(BoardQuery { per_column: Some(0) }.per_column(50, 100).unwrap_err().code, "out_of_range");
+        assert!(BoardQuery { per_column: Some(101) }.per_column(50, 100).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! What the cleanup job (`crate::cleanup`) deletes, and the report of one
+//! pass. Unlike the retention rules these only remove documents that no
+//! longer do anything: expired, revoked or already read.
+
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    config::CleanupConfig,
+    db::{INVITES, LOCKS, NOTIFICATIONS, TASK_SHARES},
+    models::{dates::to_bson_date, retention::cutoff},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sweep {
+    /// Unused invites past `expires_at`; used ones record who redeemed them.
+    ExpiredInvites,
+    /// Share links past `expires_at` or revoked.
+    DeadShareLinks,
+    /// Locks whose lease ran out without being released.
+    StaleLocks,
+    /// Read notifications past `NOTIFICATION_RETENTION_DAYS`, should the TTL
+    /// monitor lag.
+    ReadNotifications,
+}
+
+impl Sweep {
+    pub const ALL: [Sweep; 4] =
+        [Sweep::ExpiredInvites, Sweep::DeadShareLinks, Sweep::StaleLocks, Sweep::ReadNotifications];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sweep::ExpiredInvites => "expired_invites",
+            Sweep::DeadShareLinks => "dead_share_links",
+            Sweep::StaleLocks => "stale_locks",
+            Sweep::ReadNotifications => "read_notifications",
+        }
+    }
+
+    pub fn collection(self) -> &'static str {
+        match self {
+            Sweep::ExpiredInvites => INVITES,
+            Sweep::DeadShareLinks => TASK_SHARES,
+            Sweep::StaleLocks => LOCKS,
+            Sweep::ReadNotifications => NOTIFICATIONS,
+        }
+    }
+
+    /// Documents in `collection()` that may be deleted at `now`.
+    pub fn filter(self, now: DateTime<Utc>, config: &CleanupConfig) -> Document {
+        let expired = doc! { "$lt": to_bson_date(now) };
+        match self {
+            Sweep::ExpiredInvites => doc! { "used": false, "expires_at": expired },
+            Sweep::DeadShareLinks => doc! { "$or": [{ "expires_at": expired }, { "revoked_at": { "$ne": null } }] },
+            Sweep::StaleLocks => doc! { "expires_at": expired },
+            Sweep::ReadNotifications => {
+                doc! { "read_at": { "$lt": to_bson_date(cutoff(now, config.read_notification_days)) } }
+            }
+        }
+    }
+}
+
+/// What one sweep deleted in a pass.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SweepOutcome {
+    pub sweep: String,
+    pub collection: String,
+    pub deleted: u64,
+    pub batches: u64,
+    /// Why the sweep stopped early; `deleted` counts what went before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SweepOutcome {
+    pub fn new(sweep: Sweep) -> Self {
+        Self {
+            sweep: sweep.name().to_string(),
+            collection: sweep.collection().to_string(),
+            deleted: 0,
+            batches: 0,
+            error: None,
+        }
+    }
+}
+
+/// One pass of the job, as `POST /api/admin/maintenance/cleanup` returns it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub sweeps: Vec<SweepOutcome>,
+}
+
+impl CleanupReport {
+    pub fn deleted(&self) -> u64 {
+        self.sweeps.iter().map(|s| s.deleted).sum()
+    }
+
+    pub fn failed(&self) -> bool {
+        self.sweeps.iter().any(|s| s.error.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_only_match_what_no_longer_does_anything() {
+        let now: DateTime<Utc> = "2026-10-15T12:00:00Z".parse().unwrap();
+        let config = CleanupConfig { batch_size: 1000, interval_minutes: 60, read_notification_days: 30 };
+        let expired = doc! { "$lt": to_bson_date(now) };
+        assert_eq!(Sweep::ExpiredInvites.filter(now, &config), doc! { "used": false, "expires_at": expired.clone() });
+        assert_eq!(
+            Sweep::DeadShareLinks.filter(now, &config),
+            doc! { "$or": [{ "expires_at": expired.clone() }, { "revoked_at": { "$ne": null } }] }
+        );
+        assert_eq!(Sweep::StaleLocks.filter(now, &config), doc! { "expires_at": expired });
+        let read_before: DateTime<Utc> = "2026-09-15T12:00:00Z".parse().unwrap();
+        assert_eq!(
+            Sweep::ReadNotifications.filter(now, &config),
+            doc! { "read_at": { "$lt": to_bson_date(read_before) } }
+        );
+    }
+
+    #[test]
+    fn reports_total_their_sweeps() {
+        let now = Utc::now();
+        let mut invites = SweepOutcome::new(Sweep::ExpiredInvites);
+        invites.deleted = 3;
+        let mut locks = SweepOutcome::new(Sweep::StaleLocks);
+        locks.deleted = 1;
+        let report = CleanupReport { started_at: now, finished_at: now, sweeps: vec![invites, locks.clone()] };
+        assert_eq!(report.deleted(), 4);
+        assert!(!report.failed());
+        locks.error = Some("timed out".into());
+        assert!(CleanupReport { sweeps: vec![locks], ..report }.failed());
+    }
+}
@@ -1,21 +1,29 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, IdGen},
+    models::{dates::bson_date, workspace::default_workspace_id},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Category {
     #[serde(rename = "_id")]
     pub id: String,
+    #[serde(default = "default_workspace_id")]
+    pub workspace_id: String,
     pub name: String,
+    #[serde(with = "bson_date")]
     pub created_at: DateTime<Utc>,
 }
 
 impl Category {
-    pub fn new(name: String) -> Self {
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, name: String) -> Self {
         Self {
-            id: Uuid::new_v4().to_string(),
+            id: ids.new_id(),
+            workspace_id: default_workspace_id(),
             name,
-            created_at: Utc::now(),
+            created_at: clock.now(),
         }
     }
 }
@@ -24,18 +32,22 @@ impl Category {
 pub struct CtiType {
     #[serde(rename = "_id")]
     pub id: String,
+    #[serde(default = "default_workspace_id")]
+    pub workspace_id: String,
     pub name: String,
     pub category_id: String,
+    #[serde(with = "bson_date")]
     pub created_at: DateTime<Utc>,
 }
 
 impl CtiType {
-    pub fn new(name: String, category_id: String) -> Self {
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, name: String, category_id: String) -> Self {
         Self {
-            id: Uuid::new_v4().to_string(),
+            id: ids.new_id(),
+            workspace_id: default_workspace_id(),
             name,
             category_id,
-            created_at: Utc::now(),
+            created_at: clock.now(),
         }
     }
 }
@@ -44,22 +56,44 @@ impl CtiType {
 pub struct CtiItem {
     #[serde(rename = "_id")]
     pub id: String,
+    #[serde(default = "default_workspace_id")]
+    pub workspace_id: String,
     pub name: String,
     pub type_id: String,
+    #[serde(with = "bson_date")]
     pub created_at: DateTime<Utc>,
 }
 
 impl CtiItem {
-    pub fn new(name: String, type_id: String) -> Self {
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, name: String, type_id: String) -> Self {
         Self {
-            id: Uuid::new_v4().to_string(),
+            id: ids.new_id(),
+            workspace_id: default_workspace_id(),
             name,
             type_id,
-            created_at: Utc::now(),
+            created_at: clock.now(),
         }
     }
 }
 
+/// GET /api/cti/types/:id: the type with its category's name, which is
+/// `None` only if the category has gone.
+#[derive(Debug, Serialize)]
+pub struct CtiTypeDetail {
+    #[serde(flatten)]
+    pub cti_type: CtiType,
+    pub category_name: Option<String>,
+}
+
+/// GET /api/cti/items/:id: the item with the names of its type and category.
+#[derive(Debug, Serialize)]
+pub struct CtiItemDetail {
+    #[serde(flatten)]
+    pub item: CtiItem,
+    pub type_name: Option<String>,
+    pub category_name: Option<String>,
+}
+
 /// Embedded in a Task to record which Category/Type/Item it is classified under.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CtiSelection {
@@ -71,24 +105,25 @@ pub struct CtiSelection {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::{SystemClock, UuidIds};
 
     #[test]
     fn category_new_sets_name_and_generates_id() {
-        let cat = Category::new("Malware".to_string());
+        let cat = Category::new(&SystemClock, &UuidIds, "Malware".to_string());
         assert_eq!(cat.name, "Malware");
         assert!(!cat.id.is_empty());
     }
 
     #[test]
     fn category_ids_are_unique() {
-        let a = Category::new("A".to_string());
-        let b = Category::new("A".to_string());
+        let a = Category::new(&SystemClock, &UuidIds, "A".to_string());
+        let b = Category::new(&SystemClock, &UuidIds, "A".to_string());
         assert_ne!(a.id, b.id);
     }
 
     #[test]
     fn cti_type_new_stores_category_id() {
-        let t = CtiType::new("Ransomware".to_string(), "cat-123".to_string());
+        let t = CtiType::new(&SystemClock, &UuidIds, "Ransomware".to_string(), "cat-123".to_string());
         assert_eq!(t.name, "Ransomware");
         assert_eq!(t.category_id, "cat-123");
         assert!(!t.id.is_empty());
@@ -96,7 +131,7 @@ mod tests {
 
     #[test]
     fn cti_item_new_stores_type_id() {
-        let item = CtiItem::new("LockBit".to_string(), "type-456".to_string());
+        let item = CtiItem::new(&SystemClock, &UuidIds, "LockBit".to_string(), "type-456".to_string());
         assert_eq!(item.name, "LockBit");
         assert_eq!(item.type_id, "type-456");
         assert!(!item.id.is_empty());
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::FieldError, models::workflow::Workflow};
+
+/// How many tasks each "my work" group lists.
+pub const MY_WORK_GROUP_LIMIT: i64 = 5;
+
+/// Response for GET /api/dashboard.
+#[derive(Debug, Serialize)]
+pub struct DashboardResponse {
+    pub message: String,
+    pub user_id: String,
+    pub stats: DashboardStats,
+    /// The caller's unread notifications.
+    pub unread_count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DashboardStats {
+    pub tasks: TaskStats,
+    /// Only reported to admins.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_users: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskStats {
+    pub total: u64,
+    /// Every workflow status, in board order.
+    pub by_status: Vec<StatusCount>,
+    /// Tasks in a status that isn't terminal.
+    pub open: u64,
+    /// Tasks in a terminal status.
+    pub done: u64,
+    /// The `todo` and `in_progress` entries of `by_status`, 0 when the
+    /// workflow has no such status; kept for older clients.
+    pub todo: u64,
+    pub in_progress: u64,
+    pub assigned_to_me: u64,
+    pub unassigned: u64,
+    pub created_last_7_days: u64,
+    pub created_last_30_days: u64,
+}
+
+/// Tasks in one workflow status.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusCount {
+    pub status: String,
+    pub count: u64,
+}
+
+impl TaskStats {
+    /// Fills the status counts from `counts`, tasks per status key. Tasks in
+    /// a status no longer in the workflow only count towards `total`.
+    pub fn set_status_counts(&mut self, workflow: &Workflow, counts: &HashMap<String, u64>) {
+        let count = |key: &str| counts.get(key).copied().unwrap_or(0);
+        self.by_status = workflow
+            .statuses()
+            .iter()
+            .map(|s| StatusCount { status: s.key.clone(), count: count(&s.key) })
+            .collect();
+        let (done, open): (Vec<_>, Vec<_>) = self.by_status.iter().partition(|s| workflow.is_terminal(&s.status));
+        self.done = done.iter().map(|s| s.count).sum();
+        self.open = open.iter().map(|s| s.count).sum();
+        let listed = |key: &str| self.by_status.iter().find(|s| s.status == key).map_or(0, |s| s.count);
+        (self.todo, self.in_progress) = (listed("todo"), listed("in_progress"));
+    }
+}
+
+pub fn bson_to_u64(v: &bson::Bson) -> Option<u64> {
+    match v {
+        bson::Bson::Int32(n) => u64::try_from(*n).ok(),
+        bson::Bson::Int64(n) => u64::try_from(*n).ok(),
+        _ => None,
+    }
+}
+
+/// The caller-independent part of the dashboard, cached in `AppState`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DashboardSnapshot {
+    /// Everything except `assigned_to_me`, which is filled in per request.
+    pub tasks: TaskStats,
+    pub total_users: u64,
+}
+
+impl DashboardSnapshot {
+    pub fn stats_for(&self, assigned_to_me: u64, include_users: bool) -> DashboardStats {
+        DashboardStats {
+            tasks: TaskStats { assigned_to_me, ..self.tasks.clone() },
+            total_users: include_users.then_some(self.total_users),
+        }
+    }
+}
+
+/// Query parameters for GET /api/dashboard.
+#[derive(Debug, Default, Deserialize)]
+pub struct DashboardQuery {
+    /// Recompute instead of serving cached counts. Admin only.
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+/// Just enough of a task to link to it.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaskRef {
+    #[serde(alias = "_id")]
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusGroup {
+    #[serde(alias = "_id")]
+    pub status: String,
+    pub count: u64,
+    /// Most recently updated first, at most `MY_WORK_GROUP_LIMIT`.
+    pub tasks: Vec<TaskRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Count {
+    n: u64,
+}
+
+/// Raw output of the "my work" `$facet` stage.
+#[derive(Debug, Deserialize)]
+pub struct MyWorkFacets {
+    #[serde(default)]
+    open_by_status: Vec<StatusGroup>,
+    #[serde(default)]
+    mentioned: Vec<TaskRef>,
+    #[serde(default)]
+    mentioned_count: Vec<Count>,
+}
+
+/// Response for GET /api/dashboard/me.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MyWorkResponse {
+    /// Open tasks assigned to the caller, grouped by status.
+    pub open: Vec<StatusGroup>,
+    pub open_total: u64,
+    /// Tasks whose notes mention `@username`, most recently updated first.
+    pub mentioned: Vec<TaskRef>,
+    pub mentioned_total: u64,
+}
+
+impl MyWorkResponse {
+    pub fn new(mut f: MyWorkFacets, workflow: &Workflow) -> Self {
+        // Board order for the UI regardless of $group output order.
+        let rank = |s: &str| workflow.statuses().iter().position(|w| w.key == s).unwrap_or(usize::MAX);
+        f.open_by_status.sort_by_key(|g| rank(&g.status));
+        Self {
+            open_total: f.open_by_status.iter().map(|g| g.count).sum(),
+            open: f.open_by_status,
+            mentioned: f.mentioned,
+            mentioned_total: f.mentioned_count.first().map_or(0, |c| c.n),
+        }
+    }
+}
+
+pub const TIMESERIES_MAX_DAYS: u32 = 365;
+
+fn default_metric() -> String { "created".to_string() }
+fn default_days() -> u32 { 30 }
+
+/// Query parameters for GET /api/dashboard/timeseries.
+#[derive(Debug, Deserialize)]
+pub struct TimeseriesQuery {
+    #[serde(default = "default_metric")]
+    pub metric: String,
+    #[serde(default = "default_days")]
+    pub days: u32,
+}
+
+impl TimeseriesQuery {
+    /// The task field whose date places a task in a bucket.
+    pub fn date_field(&self) -> Result<&'static str, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if self.days == 0 || self.days > TIMESERIES_MAX_DAYS {
+            errors.push(FieldError::new(
+                "days",
+                "out_of_range",
+                format!("days must be between 1 and {TIMESERIES_MAX_DAYS}"),
+            ));
+        }
+        let field = match self.metric.as_str() {
+            "created" => "created_at",
+            "completed" => "status_changed_at",
+            other => {
+                errors.push(FieldError::new(
+                    "metric",
+                    "invalid_metric",
+                    format!("invalid metric '{other}': must be created or completed"),
+                ));
+                ""
+            }
+        };
+        if errors.is_empty() { Ok(field) } else { Err(errors) }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TimeseriesPoint {
+    /// Calendar day (`YYYY-MM-DD`) in `TimeseriesResponse::timezone`.
+    pub date: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimeseriesResponse {
+    pub metric: String,
+    pub days: u32,
+    pub timezone: String,
+    pub points: Vec<TimeseriesPoint>,
+}
+
+/// One point per day, oldest first and ending at `today`, with days missing
+/// from `counts` reported as zero.
+pub fn zero_fill(today: NaiveDate, days: u32, counts: &HashMap<String, u64>) -> Vec<TimeseriesPoint> {
+    (0..days as i64)
+        .rev()
+        .map(|back| {
+            let date = (today - Duration::days(back)).format("%Y-%m-%d").to_string();
+            let count = counts.get(&date).copied().unwrap_or(0);
+            TimeseriesPoint { date, count }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    #[test]
+    fn snapshot_fills_in_caller_specific_fields() {
+        let snapshot = DashboardSnapshot {
+            tasks: TaskStats { total: 5, todo: 3, done: 2, unassigned: 1, ..Default::default() },
+            total_users: 9,
+        };
+        let stats = snapshot.stats_for(2, false);
+        assert_eq!(stats.tasks.assigned_to_me, 2);
+        assert_eq!(stats.tasks.todo, 3);
+        assert_eq!(stats.total_users, None);
+        assert_eq!(snapshot.stats_for(0, true).total_users, Some(9));
+    }
+
+    #[test]
+    fn status_counts_follow_the_workflow() {
+        use crate::models::workflow::WorkflowStatus;
+        let now = chrono::Utc::now();
+        let workflow = Workflow::new(vec![
+            WorkflowStatus::new("triage", "Triage", "#000000", 0, false, now),
+            WorkflowStatus::new("in_progress", "In progress", "#000000", 1, false, now),
+            WorkflowStatus::new("closed", "Closed", "#000000", 2, true, now),
+            WorkflowStatus::new("wont_fix", "Won't fix", "#000000", 3, true, now),
+        ]);
+        let counts = HashMap::from([
+            ("triage".to_string(), 4),
+            ("closed".to_string(), 2),
+            ("wont_fix".to_string(), 1),
+            ("todo".to_string(), 7),
+        ]);
+        let mut stats = TaskStats::default();
+        stats.set_status_counts(&workflow, &counts);
+        let by_status: Vec<_> = stats.by_status.iter().map(|s| (s.status.as_str(), s.count)).collect();
+        assert_eq!(by_status, [("triage", 4), ("in_progress", 0), ("closed", 2), ("wont_fix", 1)]);
+        assert_eq!((stats.open, stats.done), (4, 3));
+        assert_eq!((stats.todo, stats.in_progress), (0, 0));
+    }
+
+    #[test]
+    fn total_users_is_omitted_for_non_admins() {
+        let stats = DashboardStats { tasks: TaskStats::default(), total_users: None };
+        let json = serde_json::to_value(stats).unwrap();
+        assert!(json.get("total_users").is_none());
+        assert_eq!(json["tasks"]["todo"], 0);
+    }
+
+    #[test]
+    fn my_work_facets_are_ordered_and_totalled() {
+        let facets = doc! {
+            "open_by_status": [
+                { "_id": "in_progress", "count": 1, "tasks": [ { "id": "t2", "title": "B" } ] },
+                { "_id": "todo", "count": 7, "tasks": [ { "id": "t1", "title": "A" } ] },
+            ],
+            "mentioned": [ { "_id": "t3", "title": "C" } ],
+            "mentioned_count": [ { "n": 1 } ],
+        };
+        let facets: MyWorkFacets = bson::from_document(facets).unwrap();
+        let work = MyWorkResponse::new(facets, &Workflow::default());
+        assert_eq!(work.open[0].status, "todo");
+        assert_eq!(work.open_total, 8);
+        assert_eq!(work.mentioned, vec![TaskRef { id: "t3".into(), title: "C".into() }]);
+        assert_eq!(work.mentioned_total, 1);
+    }
+
+    #[test]
+    fn empty_my_work_facets_are_zero() {
+        let facets: MyWorkFacets = bson::from_document(doc! {}).unwrap();
+        let work = MyWorkResponse::new(facets, &Workflow::default());
+        assert!(work.open.is_empty());
+        assert_eq!(work.open_total + work.mentioned_total, 0);
+    }
+
+    #[test]
+    fn zero_fill_covers_every_day_oldest_first() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+        let counts = HashMap::from([("2024-02-29".to_string(), 4)]);
+        let points = zero_fill(today, 3, &counts);
+        assert_eq!(
+            points,
+            vec![
+                TimeseriesPoint { date: "2024-02-29".into(), count: 4 },
+                TimeseriesPoint { date: "2024-03-01".into(), count: 0 },
+                TimeseriesPoint { date: "2024-03-02".into(), count: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn timeseries_query_validates_metric_and_days() {
+        let q = |metric: &str, days| TimeseriesQuery { metric: metric.into(), days };
+        assert_eq!(q("created", 30).date_field(), Ok("created_at"));
+        assert_eq!(q("completed", 365).date_field(), Ok("status_changed_at"));
+        assert_eq!(q("deleted", 30).date_field().unwrap_err()[0].field, "metric");
+        assert_eq!(q("created", 0).date_field().unwrap_err()[0].field, "days");
+        assert_eq!(q("nope", 366).date_field().unwrap_err().len(), 2);
+    }
+}
@@ -0,0 +1,182 @@
+//! Timestamps stored in Mongo as native BSON dates but rendered as RFC 3339
+//! strings in JSON.
+//!
+//! The driver serializes documents with `human_readable` off and serde_json
+//! has it on, so `serialize` picks the representation from that. Reading
+//! accepts either form, so documents written before migration
+//! `0005_native_dates` still load. Values written with `bson::to_bson`
+//! (human readable by default) come out as strings; use `to_bson_date` for
+//! dates in hand-built update documents instead.
+
+use bson::Bson;
+use chrono::{DateTime, Utc};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// The current time at the millisecond precision BSON dates keep, so it
+/// compares equal to itself after a round trip through the database.
+pub fn now() -> DateTime<Utc> {
+    bson::DateTime::now().to_chrono()
+}
+
+/// `dt` as a BSON date, for `$set` documents and query filters.
+pub fn to_bson_date(dt: DateTime<Utc>) -> Bson {
+    Bson::DateTime(bson::DateTime::from_chrono(dt))
+}
+
+/// Serializes `value` as the driver would, so its dates become BSON dates;
+/// for embedding a model in an update, e.g. a `$push`.
+pub fn to_stored_document<T: Serialize>(value: &T) -> anyhow::Result<bson::Document> {
+    Ok(bson::to_raw_document_buf(value)?.to_document()?)
+}
+
+/// `#[serde(with = "crate::models::dates::bson_date")]`
+pub mod bson_date {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(dt: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            dt.serialize(s)
+        } else {
+            bson::DateTime::from_chrono(*dt).serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<Utc>, D::Error> {
+        d.deserialize_any(DateVisitor)
+    }
+}
+
+/// `#[serde(default, with = "crate::models::dates::optional_bson_date")]`
+pub mod optional_bson_date {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(dt: &Option<DateTime<Utc>>, s: S) -> Result<S::Ok, S::Error> {
+        match dt {
+            Some(dt) => bson_date::serialize(dt, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        d.deserialize_option(OptionalDateVisitor)
+    }
+}
+
+struct DateVisitor;
+
+impl<'de> Visitor<'de> for DateVisitor {
+    type Value = DateTime<Utc>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a BSON date or an RFC 3339 string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        DateTime::parse_from_rfc3339(v).map(|dt| dt.with_timezone(&Utc)).map_err(E::custom)
+    }
+
+    /// BSON dates reach serde as `{ "$date": ... }`.
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let dt = bson::DateTime::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        Ok(dt.to_chrono())
+    }
+}
+
+struct OptionalDateVisitor;
+
+impl<'de> Visitor<'de> for OptionalDateVisitor {
+    type Value = Option<DateTime<Utc>>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a BSON date, an RFC 3339 string or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        bson_date::deserialize(d).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::{doc, Document};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Stamped {
+        #[serde(with = "bson_date")]
+        at: DateTime<Utc>,
+        #[serde(default, with = "optional_bson_date")]
+        seen: Option<DateTime<Utc>>,
+    }
+
+    fn sample() -> Stamped {
+        Stamped { at: now(), seen: None }
+    }
+
+    /// What the driver does on insert.
+    fn stored(value: &Stamped) -> Document {
+        let bytes = bson::to_vec(value).unwrap();
+        bson::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn stored_as_bson_dates() {
+        let mut value = sample();
+        value.seen = Some(value.at);
+        let doc = stored(&value);
+        assert_eq!(doc.get("at"), Some(&to_bson_date(value.at)));
+        assert_eq!(doc.get("seen"), Some(&to_bson_date(value.at)));
+        assert_eq!(stored(&sample()).get("seen"), Some(&Bson::Null));
+    }
+
+    #[test]
+    fn json_keeps_rfc3339_strings() {
+        let value = sample();
+        let json = serde_json::to_value(&value).unwrap();
+        assert_eq!(json["at"], serde_json::json!(value.at));
+        assert!(json["at"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(serde_json::from_value::<Stamped>(json).unwrap(), value);
+    }
+
+    #[test]
+    fn reads_dates_back_through_either_deserializer() {
+        let value = Stamped { at: now(), seen: Some(now()) };
+        let bytes = bson::to_vec(&value).unwrap();
+        assert_eq!(bson::from_slice::<Stamped>(&bytes).unwrap(), value);
+        assert_eq!(bson::from_document::<Stamped>(stored(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn reads_legacy_string_dates() {
+        let doc = doc! { "at": "2024-01-02T03:04:05.123456789Z", "seen": "2024-01-02T03:04:05Z" };
+        let value: Stamped = bson::from_document(doc.clone()).unwrap();
+        assert_eq!(value.at.to_rfc3339(), "2024-01-02T03:04:05.123456789+00:00");
+        let raw: Stamped = bson::from_slice(&bson::to_vec(&doc).unwrap()).unwrap();
+        assert_eq!(raw, value);
+        let missing: Stamped = bson::from_document(doc! { "at": "2024-01-02T03:04:05Z" }).unwrap();
+        assert_eq!(missing.seen, None);
+    }
+
+    #[test]
+    fn embedded_models_store_dates() {
+        let doc = to_stored_document(&sample()).unwrap();
+        assert!(matches!(doc.get("at"), Some(Bson::DateTime(_))));
+    }
+
+    #[test]
+    fn now_survives_a_round_trip() {
+        let at = now();
+        assert_eq!(to_bson_date(at).as_datetime().unwrap().to_chrono(), at);
+    }
+}
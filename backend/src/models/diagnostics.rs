@@ -0,0 +1,126 @@
+//! The startup self-check's report (`crate::diagnostics`), also served at
+//! `GET /api/admin/diagnostics`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Skew beyond this between our clock and MongoDB's gets a warning; token
+/// `exp` checks start misfiring well before minutes of drift.
+pub const CLOCK_SKEW_WARN_SECONDS: i64 = 30;
+/// Smallest RSA signing key not warned about.
+pub const MIN_SIGNING_KEY_BITS: usize = 2048;
+
+/// Ordered by severity, so a report's status is its worst check's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was found, or what went wrong.
+    pub detail: String,
+}
+
+impl Check {
+    pub fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status, detail: detail.into() }
+    }
+
+    /// Ok with `detail`, or failed with the error.
+    pub fn from_result<E: std::fmt::Display>(name: &str, result: Result<String, E>) -> Self {
+        match result {
+            Ok(detail) => Self::new(name, CheckStatus::Ok, detail),
+            Err(e) => Self::new(name, CheckStatus::Fail, e.to_string()),
+        }
+    }
+
+    /// Compares our clock with MongoDB's `server` time; `local` is our time
+    /// halfway through the round trip that read it.
+    pub fn clock_skew(local: DateTime<Utc>, server: DateTime<Utc>) -> Self {
+        let skew = (local - server).num_seconds();
+        let detail = format!("local clock is {skew}s ahead of MongoDB");
+        let status = if skew.abs() > CLOCK_SKEW_WARN_SECONDS { CheckStatus::Warn } else { CheckStatus::Ok };
+        Self::new("clock_skew", status, detail)
+    }
+
+    pub fn signing_key(bits: usize) -> Self {
+        let status = if bits < MIN_SIGNING_KEY_BITS { CheckStatus::Warn } else { CheckStatus::Ok };
+        Self::new("jwt_signing_key", status, format!("RS256 key of {bits} bits"))
+    }
+
+    /// Missing indexes are created at the next startup, so they only warn.
+    pub fn indexes(missing: &[String]) -> Self {
+        if missing.is_empty() {
+            Self::new("indexes", CheckStatus::Ok, "all present")
+        } else {
+            Self::new("indexes", CheckStatus::Warn, format!("missing: {}", missing.join(", ")))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticReport {
+    pub checked_at: DateTime<Utc>,
+    /// The worst of `checks`.
+    pub status: CheckStatus,
+    pub checks: Vec<Check>,
+}
+
+impl DiagnosticReport {
+    pub fn new(checked_at: DateTime<Utc>, checks: Vec<Check>) -> Self {
+        let status = checks.iter().map(|c| c.status).max().unwrap_or(CheckStatus::Ok);
+        Self { checked_at, status, checks }
+    }
+
+    /// No check failed; warnings still pass.
+    pub fn passed(&self) -> bool {
+        self.status != CheckStatus::Fail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn skew_warns_past_the_threshold_either_way() {
+        let now = Utc::now();
+        assert_eq!(Check::clock_skew(now, now - Duration::seconds(5)).status, CheckStatus::Ok);
+        let ahead = Check::clock_skew(now, now - Duration::minutes(2));
+        assert_eq!(ahead.status, CheckStatus::Warn);
+        assert_eq!(ahead.detail, "local clock is 120s ahead of MongoDB");
+        assert_eq!(Check::clock_skew(now, now + Duration::minutes(2)).status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn weak_keys_and_missing_indexes_warn() {
+        assert_eq!(Check::signing_key(2048).status, CheckStatus::Ok);
+        assert_eq!(Check::signing_key(1024).status, CheckStatus::Warn);
+        assert_eq!(Check::indexes(&[]).status, CheckStatus::Ok);
+        let missing = Check::indexes(&["tasks.assignee_id_1_status_1".into()]);
+        assert_eq!(missing.status, CheckStatus::Warn);
+        assert_eq!(missing.detail, "missing: tasks.assignee_id_1_status_1");
+    }
+
+    #[test]
+    fn reports_take_their_worst_status() {
+        let now = Utc::now();
+        let ok = Check::new("mongodb_ping", CheckStatus::Ok, "1ms");
+        let warn = Check::signing_key(1024);
+        let report = DiagnosticReport::new(now, vec![ok.clone(), warn.clone()]);
+        assert_eq!(report.status, CheckStatus::Warn);
+        assert!(report.passed());
+        let failed = Check::from_result("mongodb_write", Err::<String, _>("not authorized"));
+        assert_eq!(failed.detail, "not authorized");
+        let report = DiagnosticReport::new(now, vec![ok, failed, warn]);
+        assert_eq!(report.status, CheckStatus::Fail);
+        assert!(!report.passed());
+    }
+}
@@ -0,0 +1,168 @@
+//! The daily digest: one message listing a user's open tasks that are
+//! overdue or due today, in their timezone. Sent by `digest::run_digests`
+//! and previewed at GET /api/notifications/digest/preview.
+
+use bson::{doc, Document};
+use chrono::{DateTime, NaiveDate, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use crate::models::{
+    dates::to_bson_date,
+    preferences::{DigestFrequency, UserPreferences},
+    task::Task,
+};
+
+/// A task as listed in a digest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DigestTask {
+    pub task_id: String,
+    pub workspace_id: String,
+    pub title: String,
+    pub status: String,
+    pub due_at: DateTime<Utc>,
+}
+
+/// What a user's digest for `date` (a local date in `timezone`) contains.
+/// Both lists are soonest due first.
+#[derive(Debug, Serialize)]
+pub struct Digest {
+    pub date: NaiveDate,
+    pub timezone: String,
+    pub overdue: Vec<DigestTask>,
+    pub due_today: Vec<DigestTask>,
+}
+
+/// When the local day `date` starts in `tz`. A midnight skipped by a DST
+/// change falls back to the first hour that exists.
+pub fn start_of_day(tz: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    (0..24)
+        .find_map(|hour| tz.from_local_datetime(&date.and_hms_opt(hour, 0, 0)?).earliest())
+        .map_or_else(|| Utc.from_utc_datetime(&midnight), |t| t.with_timezone(&Utc))
+}
+
+/// The local date in `tz` whose digest is due at `now`, if any: digests are
+/// on, the preferred hour has come, and none went out that day yet. A digest
+/// missed at its hour (the server was down) goes out later the same day.
+pub fn digest_due(prefs: &UserPreferences, sent_on: Option<NaiveDate>, now: DateTime<Utc>) -> Option<NaiveDate> {
+    if prefs.digest != DigestFrequency::Daily {
+        return None;
+    }
+    let local = now.with_timezone(&prefs.tz());
+    let today = local.date_naive();
+    (local.hour() >= prefs.digest_hour && sent_on != Some(today)).then_some(today)
+}
+
+impl Digest {
+    /// Open tasks assigned to `user_id` and due before the end of `date` in
+    /// `tz`, across workspaces.
+    pub fn filter(user_id: &str, open_statuses: Vec<String>, tz: Tz, date: NaiveDate) -> Document {
+        let end = date.succ_opt().map_or(DateTime::<Utc>::MAX_UTC, |next| start_of_day(tz, next));
+        doc! {
+            "assignee_id": user_id,
+            "status": { "$in": open_statuses },
+            "due_at": { "$lt": to_bson_date(end) },
+        }
+    }
+
+    /// Sorts `tasks` (found with `filter`) into overdue at `now` and due
+    /// later today.
+    pub fn new(tasks: Vec<Task>, tz: Tz, date: NaiveDate, now: DateTime<Utc>) -> Self {
+        let mut tasks: Vec<DigestTask> = tasks
+            .into_iter()
+            .filter_map(|t| {
+                Some(DigestTask {
+                    due_at: t.due_at?,
+                    task_id: t.id,
+                    workspace_id: t.workspace_id,
+                    title: t.title,
+                    status: t.status,
+                })
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.due_at);
+        let (overdue, due_today) = tasks.into_iter().partition(|t| t.due_at < now);
+        Self { date, timezone: tz.name().to_string(), overdue, due_today }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overdue.is_empty() && self.due_today.is_empty()
+    }
+
+    /// E.g. `2 overdue, 1 due today`.
+    pub fn summary(&self) -> String {
+        format!("{} overdue, {} due today", self.overdue.len(), self.due_today.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FakeClock, SequentialIds};
+
+    fn prefs(timezone: &str, hour: u32) -> UserPreferences {
+        UserPreferences {
+            timezone: timezone.into(),
+            digest: DigestFrequency::Daily,
+            digest_hour: hour,
+            ..Default::default()
+        }
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn the_digest_goes_out_once_from_the_local_hour() {
+        // 06:30 UTC is 08:30 in Berlin (CEST) and 02:30 in New York (EDT).
+        let now = utc("2026-06-01T06:30:00Z");
+        assert_eq!(digest_due(&prefs("Europe/Berlin", 8), None, now), Some(date("2026-06-01")));
+        assert_eq!(digest_due(&prefs("Europe/Berlin", 9), None, now), None);
+        assert_eq!(digest_due(&prefs("Europe/Berlin", 8), Some(date("2026-06-01")), now), None);
+        assert_eq!(digest_due(&prefs("Europe/Berlin", 8), Some(date("2026-05-31")), now), Some(date("2026-06-01")));
+        assert_eq!(digest_due(&prefs("America/New_York", 8), None, now), None);
+        // Still the previous day in Honolulu.
+        assert_eq!(digest_due(&prefs("Pacific/Honolulu", 8), None, now), Some(date("2026-05-31")));
+        assert_eq!(digest_due(&UserPreferences::default(), None, now), None);
+    }
+
+    #[test]
+    fn days_start_at_local_midnight_even_across_dst() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        assert_eq!(start_of_day(berlin, date("2026-06-01")), utc("2026-05-31T22:00:00Z"));
+        assert_eq!(start_of_day(berlin, date("2026-01-01")), utc("2025-12-31T23:00:00Z"));
+        // Santiago skips midnight when summer time begins.
+        let santiago: Tz = "America/Santiago".parse().unwrap();
+        assert_eq!(start_of_day(santiago, date("2026-09-06")), utc("2026-09-06T04:00:00Z"));
+    }
+
+    #[test]
+    fn tasks_are_split_into_overdue_and_due_today() {
+        let (clock, ids) = (FakeClock::default(), SequentialIds::default());
+        let task = |title: &str, due: &str| {
+            let mut t = Task::new(&clock, &ids, title.into(), String::new());
+            t.due_at = Some(utc(due));
+            t
+        };
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let tasks = vec![
+            task("later today", "2026-06-01T15:00:00Z"),
+            task("last week", "2026-05-25T09:00:00Z"),
+            task("this morning", "2026-06-01T05:00:00Z"),
+        ];
+        let digest = Digest::new(tasks, tz, date("2026-06-01"), utc("2026-06-01T06:30:00Z"));
+        let titles = |list: &[DigestTask]| list.iter().map(|t| t.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&digest.overdue), ["last week", "this morning"]);
+        assert_eq!(titles(&digest.due_today), ["later today"]);
+        assert_eq!(digest.summary(), "2 overdue, 1 due today");
+
+        let filter = Digest::filter("u1", vec!["todo".into()], tz, date("2026-06-01"));
+        assert_eq!(filter.get("due_at"), Some(&doc! { "$lt": to_bson_date(utc("2026-06-01T22:00:00Z")) }.into()));
+    }
+}
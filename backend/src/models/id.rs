@@ -0,0 +1,129 @@
+//! Ids of tasks, notes, CTI nodes and users as they appear in paths. Every
+//! one is a UUID: generated with `IdGen`/`Uuid::new_v4`, or a Keycloak
+//! subject. Anything else cannot match a document, so it is rejected with
+//! 400 `malformed_id` before the database is asked.
+
+use std::{fmt, ops::Deref, str::FromStr};
+
+use bson::Bson;
+use serde::{de, Deserialize, Deserializer};
+use uuid::Uuid;
+
+/// A path id, normalised to the lowercase hyphenated form ids are stored in.
+/// Take it as `AppPath<Id>` (or `AppPath<(Id, Id)>`), never `Path<String>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Id(String);
+
+/// Why a path segment is not an `Id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedId(String);
+
+impl fmt::Display for MalformedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Clients sometimes paste whole URLs; don't echo an essay back.
+        let shown: String = self.0.chars().take(64).collect();
+        let ellipsis = if shown.len() < self.0.len() { "…" } else { "" };
+        write!(f, "'{shown}{ellipsis}' is not a valid id; ids are UUIDs")
+    }
+}
+
+impl std::error::Error for MalformedId {}
+
+impl FromStr for Id {
+    type Err = MalformedId;
+
+    /// Accepts any form `Uuid` parses (hyphenated, simple, braced, any case)
+    /// and stores the canonical one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::try_parse(s.trim())
+            .map(|uuid| Self(uuid.hyphenated().to_string()))
+            .map_err(|_| MalformedId(s.to_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(de)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Id {
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for Id {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Id {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Stored as the plain string, so `doc! { "_id": &id }` works.
+impl From<Id> for Bson {
+    fn from(id: Id) -> Self {
+        Bson::String(id.0)
+    }
+}
+
+impl PartialEq<Id> for String {
+    fn eq(&self, other: &Id) -> bool {
+        *self == other.0
+    }
+}
+
+impl PartialEq<String> for Id {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuids_parse_to_the_stored_form() {
+        let stored = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        for input in [
+            stored,
+            "67E55044-10B1-426F-9247-BB680E5FE0C8",
+            "67e5504410b1426f9247bb680e5fe0c8",
+            " 67e55044-10b1-426f-9247-bb680e5fe0c8 ",
+        ] {
+            assert_eq!(input.parse::<Id>().unwrap().to_string(), stored, "{input}");
+        }
+    }
+
+    #[test]
+    fn anything_else_is_malformed() {
+        for input in ["", "not-a-uuid", "42", "67e55044-10b1-426f-9247-bb680e5fe0c", "kc-alice"] {
+            assert!(input.parse::<Id>().is_err(), "{input}");
+        }
+        let url = format!("https://missioncontrol.example/tasks/{}", "x".repeat(100));
+        let message = url.parse::<Id>().unwrap_err().to_string();
+        assert!(message.len() < 120 && message.contains('…'), "{message}");
+    }
+
+    #[test]
+    fn deserializes_from_a_string() {
+        let id: Id = serde_json::from_str(r#""67e55044-10b1-426f-9247-bb680e5fe0c8""#).unwrap();
+        assert_eq!(&*id, "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert!(serde_json::from_str::<Id>(r#""nope""#).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::FieldError;
+
+/// How many ids GET /api/admin/integrity lists per problem.
+pub const INTEGRITY_SAMPLE_SIZE: i64 = 10;
+
+/// A kind of inconsistency in stored tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityCheck {
+    /// `assignee_id` names no user.
+    OrphanedAssignee,
+    /// A `cti` category, type or item is missing from the task's workspace.
+    OrphanedCti,
+    /// `status` is not a workflow status.
+    InvalidStatus,
+    /// A note's `author` names no user. Counted per note, not per task.
+    OrphanedNoteAuthor,
+}
+
+impl IntegrityCheck {
+    pub const ALL: [IntegrityCheck; 4] = [
+        IntegrityCheck::OrphanedAssignee,
+        IntegrityCheck::OrphanedCti,
+        IntegrityCheck::InvalidStatus,
+        IntegrityCheck::OrphanedNoteAuthor,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IntegrityCheck::OrphanedAssignee => "orphaned_assignee",
+            IntegrityCheck::OrphanedCti => "orphaned_cti",
+            IntegrityCheck::InvalidStatus => "invalid_status",
+            IntegrityCheck::OrphanedNoteAuthor => "orphaned_note_author",
+        }
+    }
+
+    /// Whether `action` makes sense here: a task must have a status and a
+    /// note an author, and a CTI selection cannot be moved to a single id.
+    pub fn allows(self, action: &RepairAction) -> bool {
+        match action {
+            RepairAction::Null => matches!(self, IntegrityCheck::OrphanedAssignee | IntegrityCheck::OrphanedCti),
+            RepairAction::Reassign { .. } => self != IntegrityCheck::OrphanedCti,
+            RepairAction::Delete => true,
+        }
+    }
+}
+
+/// One check's result. `sample_ids` are task ids, or note ids for
+/// `orphaned_note_author`.
+#[derive(Debug, Serialize)]
+pub struct IntegrityFinding {
+    pub check: IntegrityCheck,
+    pub count: u64,
+    pub sample_ids: Vec<String>,
+}
+
+/// Response for GET /api/admin/integrity, one entry per check.
+#[derive(Debug, Serialize)]
+pub struct IntegrityReport {
+    pub checks: Vec<IntegrityFinding>,
+    pub total: u64,
+}
+
+/// What to do with the tasks (or notes) a check found.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RepairAction {
+    /// Clear the offending field.
+    Null,
+    /// Point it at `to` instead: a user id, or a status key for
+    /// `invalid_status`.
+    Reassign { to: String },
+    /// Delete the tasks, or just the notes for `orphaned_note_author`.
+    Delete,
+}
+
+impl RepairAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepairAction::Null => "null",
+            RepairAction::Reassign { .. } => "reassign",
+            RepairAction::Delete => "delete",
+        }
+    }
+}
+
+/// Body of POST /api/admin/integrity/repair. Checks left out are not run.
+/// Example: `{"orphaned_assignee": {"action": "reassign", "to": "<user id>"}}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepairRequest {
+    pub orphaned_assignee: Option<RepairAction>,
+    pub orphaned_cti: Option<RepairAction>,
+    pub invalid_status: Option<RepairAction>,
+    pub orphaned_note_author: Option<RepairAction>,
+}
+
+impl RepairRequest {
+    /// The requested repairs, in `IntegrityCheck::ALL` order.
+    pub fn repairs(&self) -> Vec<(IntegrityCheck, &RepairAction)> {
+        let requested = [&self.orphaned_assignee, &self.orphaned_cti, &self.invalid_status, &self.orphaned_note_author];
+        IntegrityCheck::ALL
+            .into_iter()
+            .zip(requested)
+            .filter_map(|(check, action)| action.as_ref().map(|a| (check, a)))
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        for (check, action) in self.repairs() {
+            if !check.allows(action) {
+                errors.push(FieldError::new(
+                    check.as_str(),
+                    "invalid_action",
+                    format!("'{}' is not a repair for {}", action.as_str(), check.as_str()),
+                ));
+            } else if matches!(action, RepairAction::Reassign { to } if to.trim().is_empty()) {
+                errors.push(FieldError::new(check.as_str(), "required", "reassign needs a non-empty 'to'"));
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// How many tasks (or notes) one repair changed.
+#[derive(Debug, Serialize)]
+pub struct RepairOutcome {
+    pub check: IntegrityCheck,
+    pub action: &'static str,
+    pub count: u64,
+}
+
+/// Response for POST /api/admin/integrity/repair.
+#[derive(Debug, Serialize)]
+pub struct RepairResponse {
+    pub repaired: Vec<RepairOutcome>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_request_parses_per_check_actions() {
+        let req: RepairRequest = serde_json::from_str(
+            r#"{"orphaned_assignee":{"action":"reassign","to":"u1"},"invalid_status":{"action":"delete"}}"#,
+        )
+        .unwrap();
+        let repairs = req.repairs();
+        assert_eq!(repairs.len(), 2);
+        assert_eq!(repairs[0], (IntegrityCheck::OrphanedAssignee, &RepairAction::Reassign { to: "u1".into() }));
+        assert_eq!(repairs[1], (IntegrityCheck::InvalidStatus, &RepairAction::Delete));
+        req.validate().unwrap();
+        assert!(serde_json::from_str::<RepairRequest>(r#"{"orphaned_asignee":{"action":"null"}}"#).is_err());
+    }
+
+    #[test]
+    fn repairs_that_cannot_apply_are_rejected() {
+        let req = RepairRequest {
+            orphaned_cti: Some(RepairAction::Reassign { to: "i1".into() }),
+            invalid_status: Some(RepairAction::Null),
+            orphaned_note_author: Some(RepairAction::Reassign { to: " ".into() }),
+            ..Default::default()
+        };
+        let errors = req.validate().unwrap_err();
+        let found: Vec<_> = errors.iter().map(|e| (e.field.as_str(), e.code)).collect();
+        assert_eq!(
+            found,
+            [("orphaned_cti", "invalid_action"), ("invalid_status", "invalid_action"), ("orphaned_note_author", "required")]
+        );
+    }
+}
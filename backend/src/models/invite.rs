@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, IdGen},
+    models::{dates::bson_date, user::normalize_email},
+};
+
+/// Single-use invite code required to provision an account when the
+/// deployment runs in invite-only mode.
+///
+/// `expires_at` is stored as a native BSON date so consumption can compare
+/// it against the current time in the same atomic update.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub code: String,
+    /// When set, only this (lower-cased) email address may redeem the code.
+    pub email: Option<String>,
+    pub role: String,
+    #[serde(with = "bson_date")]
+    pub expires_at: DateTime<Utc>,
+    pub used: bool,
+    pub used_by: Option<String>,
+    pub created_by: String,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Invite {
+    /// The code is the credential, so it is always random, whatever `ids` is.
+    pub fn new(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        email: Option<String>,
+        role: String,
+        expires_at: DateTime<Utc>,
+        created_by: String,
+    ) -> Self {
+        Self {
+            id: ids.new_id(),
+            code: Uuid::new_v4().simple().to_string(),
+            email: email.as_deref().map(normalize_email),
+            role,
+            expires_at,
+            used: false,
+            used_by: None,
+            created_by,
+            created_at: clock.now(),
+        }
+    }
+}
+
+/// JSON view of an `Invite` (RFC 3339 timestamps).
+#[derive(Debug, Serialize)]
+pub struct InvitePublic {
+    pub id: String,
+    pub code: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub expires_at: DateTime<Utc>,
+    pub used: bool,
+    pub used_by: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Invite> for InvitePublic {
+    fn from(i: Invite) -> Self {
+        Self {
+            id: i.id,
+            code: i.code,
+            email: i.email,
+            role: i.role,
+            expires_at: i.expires_at,
+            used: i.used,
+            used_by: i.used_by,
+            created_by: i.created_by,
+            created_at: i.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::{SystemClock, UuidIds}, models::dates::to_stored_document};
+    use chrono::Duration;
+
+    #[test]
+    fn invite_new_normalizes_email_and_is_unused() {
+        let i = Invite::new(
+            &SystemClock,
+            &UuidIds,
+            Some(" Bob@Example.com ".into()),
+            "user".into(),
+            Utc::now() + Duration::days(7),
+            "admin-1".into(),
+        );
+        assert_eq!(i.email.as_deref(), Some("bob@example.com"));
+        assert!(!i.used);
+        assert_eq!(i.code.len(), 32);
+    }
+
+    #[test]
+    fn expires_at_is_stored_as_bson_date() {
+        let i = Invite::new(&SystemClock, &UuidIds, None, "user".into(), Utc::now(), "admin-1".into());
+        let doc = to_stored_document(&i).unwrap();
+        assert!(matches!(doc.get("expires_at"), Some(bson::Bson::DateTime(_))));
+    }
+}
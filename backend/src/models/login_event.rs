@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, IdGen},
+    models::dates::bson_date,
+    pagination::PageItem,
+};
+
+/// One sign-in recorded in the `login_events` collection.
+///
+/// `created_at` is stored as a native BSON date so the retention TTL index
+/// applies to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginEvent {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_id: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl LoginEvent {
+    pub fn new(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        user_id: String,
+        ip: Option<String>,
+        user_agent: Option<String>,
+        success: bool,
+    ) -> Self {
+        Self {
+            id: ids.new_id(),
+            user_id,
+            ip,
+            user_agent,
+            success,
+            created_at: clock.now(),
+        }
+    }
+}
+
+/// JSON view of a `LoginEvent` (RFC 3339 timestamps).
+#[derive(Debug, Serialize)]
+pub struct LoginEventPublic {
+    pub id: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<LoginEvent> for LoginEventPublic {
+    fn from(e: LoginEvent) -> Self {
+        Self {
+            id: e.id,
+            ip: e.ip,
+            user_agent: e.user_agent,
+            success: e.success,
+            created_at: e.created_at,
+        }
+    }
+}
+
+/// Login history pages list events under `"events"`.
+impl PageItem for LoginEventPublic {
+    const KEY: &'static str = "events";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::{SystemClock, UuidIds}, models::dates::to_stored_document};
+
+    #[test]
+    fn created_at_is_stored_as_bson_date() {
+        let e = LoginEvent::new(&SystemClock, &UuidIds, "u1".into(), None, None, true);
+        let doc = to_stored_document(&e).unwrap();
+        assert!(matches!(doc.get("created_at"), Some(bson::Bson::DateTime(_))));
+    }
+
+    #[test]
+    fn public_view_renders_rfc3339() {
+        let e = LoginEvent::new(&SystemClock, &UuidIds, "u1".into(), Some("10.0.0.1".into()), None, true);
+        let json = serde_json::to_value(LoginEventPublic::from(e)).unwrap();
+        assert!(json["created_at"].is_string());
+        assert_eq!(json["ip"], "10.0.0.1");
+    }
+}
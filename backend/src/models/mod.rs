@@ -1,5 +1,31 @@
+pub mod admin_stats;
+pub mod api_key;
+pub mod board;
+pub mod cleanup;
 pub mod user;
 pub mod task;
+pub mod task_export;
+pub mod task_fields;
 pub mod cti;
+pub mod dashboard;
+pub mod dates;
+pub mod diagnostics;
+pub mod digest;
 pub mod feed;
-pub mod weather;
\ No newline at end of file
+pub mod id;
+pub mod integrity;
+pub mod invite;
+pub mod login_event;
+pub mod notification;
+pub mod preferences;
+pub mod report;
+pub mod retention;
+pub mod revision;
+pub mod saved_view;
+pub mod search;
+pub mod share;
+pub mod team;
+pub mod weather;
+pub mod webhook;
+pub mod workflow;
+pub mod workspace;
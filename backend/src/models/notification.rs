@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, IdGen},
+    models::dates::bson_date,
+    pagination::PageItem,
+};
+
+/// Someone else assigned the recipient a task.
+pub const ASSIGNED: &str = "assigned";
+/// Someone mentioned the recipient as `@username` in a task note.
+pub const MENTIONED: &str = "mentioned";
+/// The recipient's daily digest; `task_id` is the first task it lists and
+/// `actor_id` the recipient.
+pub const DIGEST: &str = "digest";
+
+/// One entry in a user's notification feed, stored in `notifications`.
+///
+/// Dates are native BSON dates so the retention TTL index on `read_at`
+/// applies; unread notifications (`read_at: null`) are never expired.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub recipient_id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub task_id: String,
+    pub actor_id: String,
+    pub message: String,
+    pub read_at: Option<bson::DateTime>,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn new(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        recipient_id: String,
+        kind: &str,
+        task_id: String,
+        actor_id: String,
+        message: String,
+    ) -> Self {
+        Self {
+            id: ids.new_id(),
+            recipient_id,
+            kind: kind.to_string(),
+            task_id,
+            actor_id,
+            message,
+            read_at: None,
+            created_at: clock.now(),
+        }
+    }
+}
+
+/// JSON view of a `Notification` (RFC 3339 timestamps).
+#[derive(Debug, Serialize)]
+pub struct NotificationPublic {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub task_id: String,
+    pub actor_id: String,
+    pub message: String,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Notification> for NotificationPublic {
+    fn from(n: Notification) -> Self {
+        Self {
+            id: n.id,
+            kind: n.kind,
+            task_id: n.task_id,
+            actor_id: n.actor_id,
+            message: n.message,
+            read_at: n.read_at.map(|d| d.to_chrono()),
+            created_at: n.created_at,
+        }
+    }
+}
+
+impl PageItem for NotificationPublic {
+    const KEY: &'static str = "notifications";
+}
+
+/// Query parameters for GET /api/notifications; `page`/`limit` are read
+/// separately as `PageParams`.
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationQuery {
+    /// Only list notifications that have not been read.
+    #[serde(default)]
+    pub unread: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::{SystemClock, UuidIds}, models::dates::to_stored_document};
+
+    fn notification(kind: &str) -> Notification {
+        Notification::new(&SystemClock, &UuidIds, "u1".into(), kind, "t1".into(), "u2".into(), "m".into())
+    }
+
+    #[test]
+    fn dates_are_stored_as_bson_dates() {
+        let mut n = notification(ASSIGNED);
+        let doc = to_stored_document(&n).unwrap();
+        assert!(matches!(doc.get("created_at"), Some(bson::Bson::DateTime(_))));
+        assert_eq!(doc.get("read_at"), Some(&bson::Bson::Null));
+        assert_eq!(doc.get_str("type").unwrap(), "assigned");
+
+        n.read_at = Some(bson::DateTime::now());
+        let doc = to_stored_document(&n).unwrap();
+        assert!(matches!(doc.get("read_at"), Some(bson::Bson::DateTime(_))));
+    }
+
+    #[test]
+    fn public_view_renders_rfc3339() {
+        let n = notification(MENTIONED);
+        let json = serde_json::to_value(NotificationPublic::from(n)).unwrap();
+        assert!(json["created_at"].is_string());
+        assert!(json["read_at"].is_null());
+        assert_eq!(json["type"], "mentioned");
+        assert!(json.get("recipient_id").is_none());
+    }
+}
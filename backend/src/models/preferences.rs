@@ -0,0 +1,215 @@
+use bson::{doc, Document};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::FieldError, models::workflow::Workflow};
+
+fn default_timezone() -> String { "UTC".to_string() }
+fn default_true() -> bool { true }
+fn default_digest_hour() -> u32 { 8 }
+
+/// Per-user settings stored as the `preferences` sub-document of a user.
+/// Every field has a default so users created before preferences existed
+/// read back a complete object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserPreferences {
+    /// IANA zone name, e.g. `Europe/Berlin`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Status filter applied when the task list opens, e.g. `todo,in_progress`.
+    #[serde(default)]
+    pub default_task_filter: Option<String>,
+    #[serde(default)]
+    pub notifications: NotificationPreferences,
+    /// Whether to get a morning summary of overdue and due-today tasks.
+    #[serde(default)]
+    pub digest: DigestFrequency,
+    /// Local hour, in `timezone`, from which the digest is sent.
+    #[serde(default = "default_digest_hour")]
+    pub digest_hour: u32,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            timezone: default_timezone(),
+            default_task_filter: None,
+            notifications: NotificationPreferences::default(),
+            digest: DigestFrequency::default(),
+            digest_hour: default_digest_hour(),
+        }
+    }
+}
+
+impl UserPreferences {
+    /// `timezone` parsed; UTC if the stored name is no longer known.
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    Daily,
+    #[default]
+    Off,
+}
+
+impl DigestFrequency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Off => "off",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    #[serde(default = "default_true")]
+    pub task_assigned: bool,
+    #[serde(default = "default_true")]
+    pub task_note_added: bool,
+    #[serde(default)]
+    pub email: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self { task_assigned: true, task_note_added: true, email: false }
+    }
+}
+
+/// Partial update for PUT /api/auth/me/preferences. Omitted fields keep their
+/// stored value; unknown keys are rejected so typos don't silently no-op.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdatePreferencesRequest {
+    pub timezone: Option<String>,
+    /// An empty string clears the filter.
+    pub default_task_filter: Option<String>,
+    pub notifications: Option<UpdateNotificationPreferences>,
+    pub digest: Option<DigestFrequency>,
+    /// 0–23.
+    pub digest_hour: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateNotificationPreferences {
+    pub task_assigned: Option<bool>,
+    pub task_note_added: Option<bool>,
+    pub email: Option<bool>,
+}
+
+impl UpdatePreferencesRequest {
+    /// Validates the request and returns a `$set` document of dotted
+    /// `preferences.*` paths, so only the supplied fields are overwritten.
+    /// The document is empty when the request set nothing.
+    pub fn into_set_doc(self, workflow: &Workflow) -> Result<Document, Vec<FieldError>> {
+        let mut set = Document::new();
+        let mut errors = Vec::new();
+        if let Some(tz) = self.timezone {
+            let tz = tz.trim();
+            if tz.parse::<Tz>().is_ok() {
+                set.insert("preferences.timezone", tz);
+            } else {
+                errors.push(FieldError::new("timezone", "invalid_timezone", format!("unknown timezone '{tz}'")));
+            }
+        }
+        if let Some(filter) = self.default_task_filter {
+            match workflow.parse_statuses(&filter) {
+                Ok(Some(statuses)) => {
+                    set.insert("preferences.default_task_filter", statuses.join(","));
+                }
+                Ok(None) => {
+                    set.insert("preferences.default_task_filter", bson::Bson::Null);
+                }
+                Err(message) => {
+                    errors.push(FieldError::new("default_task_filter", "invalid_status", message));
+                }
+            }
+        }
+        if let Some(n) = self.notifications {
+            if let Some(v) = n.task_assigned {
+                set.insert("preferences.notifications.task_assigned", v);
+            }
+            if let Some(v) = n.task_note_added {
+                set.insert("preferences.notifications.task_note_added", v);
+            }
+            if let Some(v) = n.email {
+                set.insert("preferences.notifications.email", v);
+            }
+        }
+        if let Some(digest) = self.digest {
+            set.insert("preferences.digest", digest.as_str());
+        }
+        if let Some(hour) = self.digest_hour {
+            if hour < 24 {
+                set.insert("preferences.digest_hour", hour);
+            } else {
+                errors.push(FieldError::new("digest_hour", "out_of_range", "digest_hour must be between 0 and 23"));
+            }
+        }
+        if errors.is_empty() { Ok(set) } else { Err(errors) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_document_reads_as_defaults() {
+        let p: UserPreferences = serde_json::from_str("{}").unwrap();
+        assert_eq!(p, UserPreferences::default());
+        assert!(p.notifications.task_assigned);
+    }
+
+    #[test]
+    fn partial_update_only_sets_supplied_paths() {
+        let req: UpdatePreferencesRequest =
+            serde_json::from_str(r#"{"timezone":"Europe/Berlin","notifications":{"email":true}}"#).unwrap();
+        let set = req.into_set_doc(&Workflow::default()).unwrap();
+        assert_eq!(set.get_str("preferences.timezone").unwrap(), "Europe/Berlin");
+        assert!(set.get_bool("preferences.notifications.email").unwrap());
+        assert!(!set.contains_key("preferences.notifications.task_assigned"));
+        assert!(!set.contains_key("preferences.default_task_filter"));
+    }
+
+    #[test]
+    fn invalid_timezone_and_filter_are_rejected() {
+        let req = UpdatePreferencesRequest {
+            timezone: Some("Mars/Olympus".into()),
+            default_task_filter: Some("todo,nope".into()),
+            ..Default::default()
+        };
+        let fields: Vec<_> = req.into_set_doc(&Workflow::default()).unwrap_err().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["timezone", "default_task_filter"]);
+    }
+
+    #[test]
+    fn empty_filter_clears_it() {
+        let req = UpdatePreferencesRequest { default_task_filter: Some("".into()), ..Default::default() };
+        let set = req.into_set_doc(&Workflow::default()).unwrap();
+        assert_eq!(set.get("preferences.default_task_filter"), Some(&bson::Bson::Null));
+    }
+
+    #[test]
+    fn digest_settings_are_checked() {
+        let req: UpdatePreferencesRequest = serde_json::from_str(r#"{"digest":"daily","digest_hour":7}"#).unwrap();
+        let set = req.into_set_doc(&Workflow::default()).unwrap();
+        assert_eq!(set, doc! { "preferences.digest": "daily", "preferences.digest_hour": 7 });
+        let req = UpdatePreferencesRequest { digest_hour: Some(24), ..Default::default() };
+        assert_eq!(req.into_set_doc(&Workflow::default()).unwrap_err()[0].code, "out_of_range");
+        assert!(serde_json::from_str::<UpdatePreferencesRequest>(r#"{"digest":"weekly"}"#).is_err());
+        assert_eq!(UserPreferences::default().digest, DigestFrequency::Off);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(serde_json::from_str::<UpdatePreferencesRequest>(r#"{"timezon":"UTC"}"#).is_err());
+        assert!(serde_json::from_str::<UpdatePreferencesRequest>(r#"{"notifications":{"sms":true}}"#).is_err());
+    }
+}
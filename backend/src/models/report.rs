@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::FieldError, models::task::Task, pagination::Paginated};
+
+/// Query parameters for GET /api/reports/cti.
+///
+/// With neither id the report groups by category; `category_id` drills into
+/// that category's types, and `type_id` into that type's items.
+#[derive(Debug, Default, Deserialize)]
+pub struct CtiReportQuery {
+    pub category_id: Option<String>,
+    pub type_id: Option<String>,
+    /// Only tasks created at or after this instant.
+    pub from: Option<DateTime<Utc>>,
+    /// Only tasks created before this instant.
+    pub to: Option<DateTime<Utc>>,
+    /// Comma-separated status filter, e.g. `done`.
+    pub status: Option<String>,
+}
+
+/// Which CTI level a report groups by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CtiLevel {
+    Category,
+    Type,
+    Item,
+}
+
+impl CtiLevel {
+    pub fn for_query(q: &CtiReportQuery) -> Self {
+        if q.type_id.is_some() {
+            CtiLevel::Item
+        } else if q.category_id.is_some() {
+            CtiLevel::Type
+        } else {
+            CtiLevel::Category
+        }
+    }
+
+    /// Task field the report groups on.
+    pub fn task_field(self) -> &'static str {
+        match self {
+            CtiLevel::Category => "cti.category_id",
+            CtiLevel::Type => "cti.type_id",
+            CtiLevel::Item => "cti.item_id",
+        }
+    }
+
+    /// Collection holding the names for this level.
+    pub fn collection(self) -> &'static str {
+        match self {
+            CtiLevel::Category => "cti_categories",
+            CtiLevel::Type => "cti_types",
+            CtiLevel::Item => "cti_items",
+        }
+    }
+}
+
+/// Name of the bucket for tasks without a CTI selection.
+pub const UNCLASSIFIED: &str = "Unclassified";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CtiBucket {
+    /// `None` for the "Unclassified" bucket.
+    #[serde(alias = "_id")]
+    pub id: Option<String>,
+    pub name: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CtiReportResponse {
+    pub level: CtiLevel,
+    pub total: u64,
+    pub buckets: Vec<CtiBucket>,
+}
+
+pub const STALE_MAX_DAYS: u32 = 365;
+
+fn default_stale_days() -> u32 { 14 }
+
+/// Query parameters for GET /api/reports/stale; `page`/`limit` are read
+/// separately as `PageParams`.
+#[derive(Debug, Deserialize)]
+pub struct StaleReportQuery {
+    /// Open tasks not updated for at least this many days are stale.
+    #[serde(default = "default_stale_days")]
+    pub days: u32,
+    /// Only the counts, for dashboard tiles; no task list.
+    #[serde(default)]
+    pub counts_only: bool,
+}
+
+impl Default for StaleReportQuery {
+    fn default() -> Self {
+        Self { days: default_stale_days(), counts_only: false }
+    }
+}
+
+impl StaleReportQuery {
+    pub fn errors(&self) -> Vec<FieldError> {
+        if self.days == 0 || self.days > STALE_MAX_DAYS {
+            vec![FieldError::new(
+                "days",
+                "out_of_range",
+                format!("days must be between 1 and {STALE_MAX_DAYS}"),
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Query parameters for GET /api/reports/unassigned.
+#[derive(Debug, Default, Deserialize)]
+pub struct UnassignedReportQuery {
+    #[serde(default)]
+    pub counts_only: bool,
+}
+
+/// The tasks a report lists: one page of them in the usual envelope, or
+/// with `?counts_only=true` just `{ "total": n }`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ReportTasks {
+    Page(Paginated<Task>),
+    Counts { total: u64 },
+}
+
+/// Stale tasks held by one assignee.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssigneeCount {
+    /// `None` for stale tasks nobody is assigned to.
+    #[serde(alias = "_id")]
+    pub assignee_id: Option<String>,
+    /// Absent when unassigned or the user no longer exists.
+    #[serde(default)]
+    pub username: Option<String>,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StaleReportResponse {
+    pub days: u32,
+    /// Tasks last updated before this instant are stale.
+    pub cutoff: DateTime<Utc>,
+    /// Most stale tasks first.
+    pub by_assignee: Vec<AssigneeCount>,
+    #[serde(flatten)]
+    pub tasks: ReportTasks,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_follows_the_deepest_id_given() {
+        let mut q = CtiReportQuery::default();
+        assert_eq!(CtiLevel::for_query(&q), CtiLevel::Category);
+        q.category_id = Some("c".into());
+        assert_eq!(CtiLevel::for_query(&q), CtiLevel::Type);
+        q.type_id = Some("t".into());
+        assert_eq!(CtiLevel::for_query(&q), CtiLevel::Item);
+        assert_eq!(CtiLevel::Item.task_field(), "cti.item_id");
+    }
+
+    #[test]
+    fn unclassified_bucket_reads_null_id() {
+        let b: CtiBucket = bson::from_document(bson::doc! { "_id": null, "name": UNCLASSIFIED, "count": 2 }).unwrap();
+        assert_eq!(b.id, None);
+        assert_eq!(b.count, 2);
+    }
+
+    #[test]
+    fn stale_days_are_bounded() {
+        assert!(StaleReportQuery::default().errors().is_empty());
+        for days in [0, STALE_MAX_DAYS + 1] {
+            let errors = StaleReportQuery { days, counts_only: false }.errors();
+            assert_eq!(errors[0].field, "days");
+        }
+    }
+
+    #[test]
+    fn counts_only_replaces_the_page_envelope() {
+        let response = StaleReportResponse {
+            days: 14,
+            cutoff: DateTime::from_timestamp(0, 0).unwrap(),
+            by_assignee: vec![AssigneeCount { assignee_id: None, username: None, count: 2 }],
+            tasks: ReportTasks::Counts { total: 2 },
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["total"], 2);
+        assert_eq!(json["by_assignee"][0]["assignee_id"], serde_json::Value::Null);
+        assert!(json.get("tasks").is_none());
+
+        let page = Paginated::new(Vec::new(), 0, Default::default());
+        let json = serde_json::to_value(ReportTasks::Page(page)).unwrap();
+        assert_eq!(json["tasks"], serde_json::json!([]));
+        assert_eq!(json["total_pages"], 1);
+    }
+}
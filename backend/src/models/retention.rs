@@ -0,0 +1,182 @@
+//! Retention rules (`RETENTION_*`) and the summary every run of the
+//! retention job (`crate::retention`) leaves in `retention_runs`.
+
+use bson::{doc, Document};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RetentionConfig,
+    db::{ARCHIVED_TASKS, TASKS},
+    models::dates::{bson_date, to_bson_date},
+    pagination::PageItem,
+};
+
+/// Matching ids listed per rule in a dry run.
+pub const SAMPLE_IDS_MAX: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// Moves tasks in a terminal status to `archived_tasks`.
+    ArchiveDoneTasks,
+    /// Deletes archived tasks with their revisions, shares and attachments.
+    PurgeArchivedTasks,
+    PurgeLoginEvents,
+}
+
+impl Rule {
+    /// In the order a run applies them.
+    pub const ALL: [Rule; 3] = [Rule::ArchiveDoneTasks, Rule::PurgeArchivedTasks, Rule::PurgeLoginEvents];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::ArchiveDoneTasks => "archive_done_tasks",
+            Rule::PurgeArchivedTasks => "purge_archived_tasks",
+            Rule::PurgeLoginEvents => "purge_login_events",
+        }
+    }
+
+    pub fn collection(self) -> &'static str {
+        match self {
+            Rule::ArchiveDoneTasks => TASKS,
+            Rule::PurgeArchivedTasks => ARCHIVED_TASKS,
+            Rule::PurgeLoginEvents => "login_events",
+        }
+    }
+
+    /// Age in days at which the rule applies; 0 when it is off.
+    pub fn days(self, config: &RetentionConfig) -> u64 {
+        match self {
+            Rule::ArchiveDoneTasks => config.archive_done_days,
+            Rule::PurgeArchivedTasks => config.purge_archived_days,
+            Rule::PurgeLoginEvents => config.purge_login_events_days,
+        }
+    }
+
+    /// The rules turned on in `config`, in order.
+    pub fn enabled(config: &RetentionConfig) -> Vec<Rule> {
+        Rule::ALL.into_iter().filter(|r| r.days(config) > 0).collect()
+    }
+
+    /// Documents in `collection()` the rule applies to at `cutoff`.
+    /// `terminal` is the workflow's terminal statuses.
+    pub fn filter(self, cutoff: DateTime<Utc>, terminal: &[String]) -> Document {
+        let before = doc! { "$lt": to_bson_date(cutoff) };
+        match self {
+            Rule::ArchiveDoneTasks => doc! { "status": { "$in": terminal }, "updated_at": before },
+            Rule::PurgeArchivedTasks => doc! { "archived_at": before },
+            Rule::PurgeLoginEvents => doc! { "created_at": before },
+        }
+    }
+}
+
+/// `days` before `now`; documents older than this are affected. Absurdly
+/// many days reach back to the earliest representable date.
+pub fn cutoff(now: DateTime<Utc>, days: u64) -> DateTime<Utc> {
+    i64::try_from(days)
+        .ok()
+        .and_then(Duration::try_days)
+        .and_then(|age| now.checked_sub_signed(age))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+/// What one rule did in a run, or in a dry run would have done.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleOutcome {
+    pub rule: String,
+    pub collection: String,
+    pub older_than_days: u64,
+    #[serde(with = "bson_date")]
+    pub cutoff: DateTime<Utc>,
+    /// Documents archived or deleted; in a dry run, how many match.
+    pub affected: u64,
+    pub batches: u64,
+    /// Some matching ids; dry runs only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sample_ids: Vec<String>,
+    /// Why the rule stopped early; `affected` counts what was done before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RuleOutcome {
+    pub fn new(rule: Rule, config: &RetentionConfig, now: DateTime<Utc>) -> Self {
+        let days = rule.days(config);
+        Self {
+            rule: rule.name().to_string(),
+            collection: rule.collection().to_string(),
+            older_than_days: days,
+            cutoff: cutoff(now, days),
+            affected: 0,
+            batches: 0,
+            sample_ids: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+/// One run of the job, stored in `retention_runs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionRun {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub dry_run: bool,
+    /// The admin who asked for a dry run; absent for scheduled runs.
+    #[serde(default)]
+    pub requested_by: Option<String>,
+    #[serde(with = "bson_date")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "bson_date")]
+    pub finished_at: DateTime<Utc>,
+    /// One per enabled rule, in the order applied.
+    pub rules: Vec<RuleOutcome>,
+}
+
+impl RetentionRun {
+    pub fn failed(&self) -> bool {
+        self.rules.iter().any(|r| r.error.is_some())
+    }
+}
+
+impl PageItem for RetentionRun {
+    const KEY: &'static str = "runs";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RetentionConfig {
+        RetentionConfig {
+            archive_done_days: 180,
+            purge_archived_days: 0,
+            purge_login_events_days: 90,
+            batch_size: 500,
+            interval_hours: 24,
+        }
+    }
+
+    #[test]
+    fn rules_with_zero_days_are_off() {
+        assert_eq!(Rule::enabled(&config()), [Rule::ArchiveDoneTasks, Rule::PurgeLoginEvents]);
+        let off = RetentionConfig { archive_done_days: 0, purge_login_events_days: 0, ..config() };
+        assert!(Rule::enabled(&off).is_empty());
+    }
+
+    #[test]
+    fn filters_select_by_age_and_status() {
+        let now: DateTime<Utc> = "2026-10-15T12:00:00Z".parse().unwrap();
+        let outcome = RuleOutcome::new(Rule::ArchiveDoneTasks, &config(), now);
+        assert_eq!(outcome.cutoff, "2026-04-18T12:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        assert_eq!(outcome.collection, TASKS);
+
+        let terminal = vec!["done".to_string()];
+        let before = doc! { "$lt": to_bson_date(outcome.cutoff) };
+        assert_eq!(
+            Rule::ArchiveDoneTasks.filter(outcome.cutoff, &terminal),
+            doc! { "status": { "$in": ["done"] }, "updated_at": before.clone() }
+        );
+        assert_eq!(Rule::PurgeArchivedTasks.filter(outcome.cutoff, &terminal), doc! { "archived_at": before });
+        assert_eq!(cutoff(now, u64::MAX), DateTime::<Utc>::MIN_UTC);
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, IdGen},
+    errors::FieldError,
+    models::dates::bson_date,
+};
+
+/// Task fields whose earlier values are kept in `task_revisions`.
+pub const REVISION_FIELDS: &[&str] = &["description"];
+/// Revisions kept per task, across fields; older ones are deleted.
+pub const MAX_REVISIONS_PER_TASK: u64 = 20;
+/// Longest `old_value` stored, in bytes. Longer values are cut at a
+/// character boundary and marked `truncated`.
+pub const REVISION_VALUE_MAX_BYTES: usize = 20 * 1024;
+
+/// What a task field held before an edit replaced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRevision {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub workspace_id: String,
+    pub task_id: String,
+    /// One of `REVISION_FIELDS`.
+    pub field: String,
+    pub old_value: String,
+    /// `old_value` is only the first `REVISION_VALUE_MAX_BYTES` of it.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Who made the edit that replaced `old_value`.
+    pub editor_id: String,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl TaskRevision {
+    pub fn new(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        workspace_id: &str,
+        task_id: &str,
+        field: &str,
+        old_value: &str,
+        editor_id: &str,
+    ) -> Self {
+        let (old_value, truncated) = truncate_bytes(old_value, REVISION_VALUE_MAX_BYTES);
+        Self {
+            id: ids.new_id(),
+            workspace_id: workspace_id.to_string(),
+            task_id: task_id.to_string(),
+            field: field.to_string(),
+            old_value: old_value.to_string(),
+            truncated,
+            editor_id: editor_id.to_string(),
+            created_at: clock.now(),
+        }
+    }
+}
+
+/// At most `max` bytes of `s`, ending on a character boundary, and whether
+/// anything was cut.
+fn truncate_bytes(s: &str, max: usize) -> (&str, bool) {
+    if s.len() <= max {
+        return (s, false);
+    }
+    let end = (0..=max).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+    (&s[..end], true)
+}
+
+/// Query parameters for GET /api/tasks/:id/revisions.
+#[derive(Debug, Default, Deserialize)]
+pub struct RevisionQuery {
+    /// Only revisions of this field; all of them when absent.
+    pub field: Option<String>,
+}
+
+impl RevisionQuery {
+    pub fn field(&self) -> Result<Option<&str>, FieldError> {
+        match self.field.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            None => Ok(None),
+            Some(f) if REVISION_FIELDS.contains(&f) => Ok(Some(f)),
+            Some(f) => Err(FieldError::new(
+                "field",
+                "invalid_field",
+                format!("invalid field '{f}': must be one of {}", REVISION_FIELDS.join(", ")),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FakeClock, SequentialIds};
+
+    #[test]
+    fn long_values_are_truncated_on_a_character_boundary() {
+        let clock = FakeClock::default();
+        let short = TaskRevision::new(&clock, &SequentialIds::default(), "ws", "t1", "description", "old", "u1");
+        assert_eq!((short.old_value.as_str(), short.truncated), ("old", false));
+
+        // Two-byte characters, so the limit falls mid-character.
+        let long = "é".repeat(REVISION_VALUE_MAX_BYTES);
+        let rev = TaskRevision::new(&clock, &SequentialIds::default(), "ws", "t1", "description", &long, "u1");
+        assert!(rev.truncated);
+        assert_eq!(rev.old_value.len(), REVISION_VALUE_MAX_BYTES);
+        assert_eq!(truncate_bytes("aé", 2), ("a", true));
+    }
+
+    #[test]
+    fn only_revisable_fields_are_accepted() {
+        let q = |f: &str| RevisionQuery { field: Some(f.into()) };
+        assert_eq!(q("description").field().unwrap(), Some("description"));
+        assert_eq!(q(" ").field().unwrap(), None);
+        assert_eq!(RevisionQuery::default().field().unwrap(), None);
+        assert_eq!(q("title").field().unwrap_err().code, "invalid_field");
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, IdGen},
+    errors::FieldError,
+    models::{
+        dates::bson_date,
+        task::TaskQuery,
+        workflow::Workflow,
+    },
+};
+
+/// `?view=default` picks the caller's default view.
+pub const DEFAULT_VIEW: &str = "default";
+
+/// A named task filter, private to its owner and tied to the workspace its
+/// ids come from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedView {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub owner_id: String,
+    pub workspace_id: String,
+    pub name: String,
+    pub filter: TaskQuery,
+    /// At most one per owner and workspace.
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson_date")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SavedView {
+    pub fn new(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        owner_id: &str,
+        workspace_id: &str,
+        name: String,
+        filter: TaskQuery,
+        is_default: bool,
+    ) -> Self {
+        let now = clock.now();
+        Self {
+            id: ids.new_id(),
+            owner_id: owner_id.to_string(),
+            workspace_id: workspace_id.to_string(),
+            name,
+            filter,
+            is_default,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// A view as returned by /api/views.
+#[derive(Debug, Serialize)]
+pub struct SavedViewPublic {
+    pub id: String,
+    pub name: String,
+    pub filter: TaskQuery,
+    pub is_default: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<SavedView> for SavedViewPublic {
+    fn from(v: SavedView) -> Self {
+        Self {
+            id: v.id,
+            name: v.name,
+            filter: v.filter,
+            is_default: v.is_default,
+            created_at: v.created_at,
+            updated_at: v.updated_at,
+        }
+    }
+}
+
+/// The `view` parameter of GET /api/tasks: a view id or `default`.
+#[derive(Debug, Deserialize)]
+pub struct ViewSelection {
+    pub view: Option<String>,
+}
+
+/// Body of POST /api/views.
+#[derive(Debug, Deserialize)]
+pub struct CreateViewRequest {
+    pub name: String,
+    #[serde(default)]
+    pub filter: TaskQuery,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+impl CreateViewRequest {
+    /// The trimmed name, once the name and filter are valid.
+    pub fn validate(&self, workflow: &Workflow) -> Result<String, Vec<FieldError>> {
+        with_filter_errors(validate_name(&self.name), Some(&self.filter), workflow)
+    }
+}
+
+/// Body of PUT /api/views/:id; omitted fields are left alone.
+#[derive(Debug, Deserialize)]
+pub struct UpdateViewRequest {
+    pub name: Option<String>,
+    pub filter: Option<TaskQuery>,
+    pub is_default: Option<bool>,
+}
+
+impl UpdateViewRequest {
+    /// The trimmed name, if one was given, once the request is valid.
+    pub fn validate(&self, workflow: &Workflow) -> Result<Option<String>, Vec<FieldError>> {
+        with_filter_errors(self.name.as_deref().map(validate_name).transpose(), self.filter.as_ref(), workflow)
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, FieldError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(FieldError::new("name", "invalid_length", "name must be 1-100 characters"));
+    }
+    Ok(name.to_string())
+}
+
+/// `name`, unless it or `filter` is invalid; filter errors are named as in
+/// the request body.
+fn with_filter_errors<T>(
+    name: Result<T, FieldError>,
+    filter: Option<&TaskQuery>,
+    workflow: &Workflow,
+) -> Result<T, Vec<FieldError>> {
+    let mut errors: Vec<FieldError> = match filter.map(|f| f.to_find(workflow)) {
+        Some(Err(errors)) => errors
+            .into_iter()
+            .map(|e| FieldError { field: format!("filter.{}", e.field), ..e })
+            .collect(),
+        _ => vec![],
+    };
+    match name {
+        Ok(name) if errors.is_empty() => Ok(name),
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.insert(0, e);
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{SystemClock, UuidIds};
+
+    #[test]
+    fn create_request_checks_name_and_filter() {
+        let req: CreateViewRequest = serde_json::from_str(r#"{"name":"  Mine  "}"#).unwrap();
+        assert_eq!(req.validate(&Workflow::default()).unwrap(), "Mine");
+        assert!(!req.is_default);
+
+        let req: CreateViewRequest =
+            serde_json::from_str(r#"{"name":" ","filter":{"status":"nope","sort":"title"}}"#).unwrap();
+        let fields: Vec<_> = req.validate(&Workflow::default()).unwrap_err().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, ["name", "filter.status"]);
+    }
+
+    #[test]
+    fn update_request_only_checks_what_it_sets() {
+        let req: UpdateViewRequest = serde_json::from_str(r#"{"is_default":true}"#).unwrap();
+        assert_eq!(req.validate(&Workflow::default()).unwrap(), None);
+        let req: UpdateViewRequest = serde_json::from_str(r#"{"filter":{"sort":"-bogus"}}"#).unwrap();
+        assert_eq!(req.validate(&Workflow::default()).unwrap_err()[0].field, "filter.sort");
+    }
+
+    #[test]
+    fn filter_round_trips_without_unset_fields() {
+        let filter = TaskQuery { assignee_id: Some("u1".into()), ..Default::default() };
+        let view = SavedView::new(&SystemClock, &UuidIds, "u1", "ws", "Mine".into(), filter.clone(), false);
+        let stored = bson::to_document(&view).unwrap();
+        assert_eq!(stored.get_document("filter").unwrap(), &bson::doc! { "assignee_id": "u1" });
+        let back: SavedView = bson::from_document(stored).unwrap();
+        assert_eq!(back.filter, filter);
+    }
+}
@@ -0,0 +1,213 @@
+//! GET /api/search: tasks whose title, description or any note contains
+//! `q`, each with an excerpt of where it matched.
+
+use bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::escape_regex,
+    errors::FieldError,
+    models::task::Task,
+    pagination::PageItem,
+};
+
+/// Longest `q`, in characters.
+pub const SEARCH_QUERY_MAX_CHARS: usize = 200;
+/// Characters of context kept on each side of the match in a snippet.
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+/// Matching notes listed per task; `note_matches` has the full count.
+const NOTE_MATCHES_MAX: usize = 5;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+}
+
+impl SearchQuery {
+    /// The trimmed search text; 422 when it is missing or too long.
+    pub fn text(&self) -> Result<&str, FieldError> {
+        let q = self.q.as_deref().map(str::trim).unwrap_or_default();
+        if q.is_empty() {
+            return Err(FieldError::new("q", "required", "q is required"));
+        }
+        if q.chars().count() > SEARCH_QUERY_MAX_CHARS {
+            return Err(FieldError::new(
+                "q",
+                "too_long",
+                format!("q must be at most {SEARCH_QUERY_MAX_CHARS} characters"),
+            ));
+        }
+        Ok(q)
+    }
+}
+
+/// Tasks with `q` (literally, ignoring case) in the title, the description
+/// or the plain text of a note, so markup inside a phrase doesn't hide it.
+pub fn search_filter(q: &str) -> Document {
+    let pattern = doc! { "$regex": escape_regex(q), "$options": "i" };
+    doc! {
+        "$or": [
+            { "title": pattern.clone() },
+            { "description": pattern.clone() },
+            { "notes": { "$elemMatch": { "plain_text": pattern } } },
+        ]
+    }
+}
+
+/// Part of a field around the match. `highlight` is the match's
+/// `[start, end)` in characters within `text`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snippet {
+    pub text: String,
+    pub highlight: [usize; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteMatch {
+    pub note_id: String,
+    pub snippet: Snippet,
+}
+
+/// One task in the results. `title_match` and `description_match` are
+/// only present when that field matched.
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub task_id: String,
+    pub title: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_match: Option<Snippet>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_match: Option<Snippet>,
+    /// The first matching notes, in note order.
+    pub notes: Vec<NoteMatch>,
+    /// How many notes matched, including any not listed.
+    pub note_matches: usize,
+}
+
+impl PageItem for SearchHit {
+    const KEY: &'static str = "results";
+}
+
+impl SearchHit {
+    /// Where in `task` `q` matched. Notes are searched in their plain text,
+    /// as `search_filter` does.
+    pub fn new(task: Task, q: &str) -> Self {
+        let mut notes = Vec::new();
+        let mut note_matches = 0;
+        for note in &task.notes {
+            if let Some(snippet) = snippet(&note.plain_text, q) {
+                note_matches += 1;
+                if notes.len() < NOTE_MATCHES_MAX {
+                    notes.push(NoteMatch { note_id: note.id.clone(), snippet });
+                }
+            }
+        }
+        Self {
+            title_match: snippet(&task.title, q),
+            description_match: snippet(&task.description, q),
+            task_id: task.id,
+            title: task.title,
+            status: task.status,
+            notes,
+            note_matches,
+        }
+    }
+}
+
+/// Character index of the first case-insensitive occurrence of `needle`.
+fn find_ignoring_case(haystack: &[char], needle: &[char]) -> Option<usize> {
+    let lower = |c: &char| c.to_lowercase().next().unwrap_or(*c);
+    let needle: Vec<char> = needle.iter().map(lower).collect();
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    (0..=haystack.len() - needle.len())
+        .find(|&i| haystack[i..i + needle.len()].iter().map(lower).eq(needle.iter().copied()))
+}
+
+/// The match of `q` in `text` with `SNIPPET_CONTEXT_CHARS` either side,
+/// `…` marking where `text` was cut; `None` if `q` isn't in `text`.
+pub fn snippet(text: &str, q: &str) -> Option<Snippet> {
+    let chars: Vec<char> = text.chars().collect();
+    let q: Vec<char> = q.chars().collect();
+    let at = find_ignoring_case(&chars, &q)?;
+    let start = at.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let end = (at + q.len() + SNIPPET_CONTEXT_CHARS).min(chars.len());
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let offset = usize::from(start > 0);
+    snippet.extend(&chars[start..end]);
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    let highlight_start = at - start + offset;
+    Some(Snippet { text: snippet, highlight: [highlight_start, highlight_start + q.len()] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{FakeClock, SequentialIds},
+        models::task::TaskNote,
+    };
+
+    fn highlighted(s: &Snippet) -> String {
+        s.text.chars().skip(s.highlight[0]).take(s.highlight[1] - s.highlight[0]).collect()
+    }
+
+    #[test]
+    fn q_is_required_and_capped() {
+        assert_eq!(SearchQuery { q: Some("  rollback ".into()) }.text().unwrap(), "rollback");
+        assert_eq!(SearchQuery::default().text().unwrap_err().code, "required");
+        assert_eq!(SearchQuery { q: Some(" ".into()) }.text().unwrap_err().code, "required");
+        let long = "x".repeat(SEARCH_QUERY_MAX_CHARS + 1);
+        assert_eq!(SearchQuery { q: Some(long) }.text().unwrap_err().code, "too_long");
+    }
+
+    #[test]
+    fn the_filter_matches_literally_across_fields_and_notes() {
+        let filter = search_filter("a.b");
+        let or = filter.get_array("$or").unwrap();
+        assert_eq!(or.len(), 3);
+        let pattern = doc! { "$regex": r"a\.b", "$options": "i" };
+        assert_eq!(or[2].as_document().unwrap(), &doc! { "notes": { "$elemMatch": { "plain_text": pattern } } });
+    }
+
+    #[test]
+    fn snippets_stay_short_and_mark_the_match() {
+        let short = snippet("Write the Rollback plan", "rollback").unwrap();
+        assert_eq!(short.text, "Write the Rollback plan");
+        assert_eq!(highlighted(&short), "Rollback");
+
+        let long = format!("{}rollback plan{}", "é".repeat(4000), "z".repeat(1000));
+        let cut = snippet(&long, "ROLLBACK").unwrap();
+        assert_eq!(cut.text.chars().count(), 1 + SNIPPET_CONTEXT_CHARS + 8 + SNIPPET_CONTEXT_CHARS + 1);
+        assert!(cut.text.starts_with('…') && cut.text.ends_with('…'));
+        assert_eq!(highlighted(&cut), "rollback");
+
+        assert!(snippet("nothing here", "rollback").is_none());
+    }
+
+    #[test]
+    fn hits_list_matching_notes_without_markup() {
+        let (clock, ids) = (FakeClock::default(), SequentialIds::default());
+        let mut task = Task::new(&clock, &ids, "Deploy".into(), "no match".into());
+        for i in 0..7 {
+            let text = format!("<p>step {i}: see the <strong>rollback</strong> plan</p>");
+            task.notes.push(TaskNote::new(&clock, &ids, text, "alice".into()));
+        }
+        task.notes.push(TaskNote::new(&clock, &ids, "unrelated".into(), "alice".into()));
+        // What the filter's regex runs on, so the database finds the task too.
+        let stored = bson::to_document(&task.notes[0]).unwrap();
+        assert_eq!(stored.get_str("plain_text").unwrap(), "step 0: see the rollback plan");
+        let hit = SearchHit::new(task, "rollback plan");
+        assert!(hit.title_match.is_none() && hit.description_match.is_none());
+        assert_eq!(hit.note_matches, 7);
+        assert_eq!(hit.notes.len(), NOTE_MATCHES_MAX);
+        assert_eq!(hit.notes[0].snippet.text, "step 0: see the rollback plan");
+    }
+}
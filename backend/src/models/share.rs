@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    clock::{Clock, IdGen},
+    errors::FieldError,
+    models::{
+        api_key::hash_key,
+        dates::{bson_date, optional_bson_date},
+        task::Task,
+    },
+};
+
+/// Longest a share link may live, in days.
+pub const SHARE_MAX_DAYS: i64 = 90;
+
+/// A read-only link to one task for someone without an account. Only the
+/// SHA-256 of the token is stored; the token itself is returned once, from
+/// the create endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskShare {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub workspace_id: String,
+    pub task_id: String,
+    pub token_hash: String,
+    /// Whether the shared view includes the task's notes.
+    pub include_notes: bool,
+    /// Never expires when absent.
+    #[serde(default, with = "optional_bson_date")]
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: String,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default, with = "optional_bson_date")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Times the link has been opened.
+    #[serde(default)]
+    pub access_count: i64,
+    #[serde(default, with = "optional_bson_date")]
+    pub last_accessed_at: Option<DateTime<Utc>>,
+}
+
+impl TaskShare {
+    /// Generates a share of `task`, returning the record to store and the
+    /// plaintext token.
+    pub fn generate(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        task: &Task,
+        req: &CreateShareRequest,
+        created_by: &str,
+    ) -> (Self, String) {
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let now = clock.now();
+        let share = Self {
+            id: ids.new_id(),
+            workspace_id: task.workspace_id.clone(),
+            task_id: task.id.clone(),
+            token_hash: hash_key(&token),
+            include_notes: req.include_notes,
+            expires_at: req.expires_in_days.map(|days| now + Duration::days(days)),
+            created_by: created_by.to_string(),
+            created_at: now,
+            revoked_at: None,
+            access_count: 0,
+            last_accessed_at: None,
+        };
+        (share, token)
+    }
+}
+
+/// Body of POST /api/tasks/:id/share.
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateShareRequest {
+    /// Days until the link stops working; it never does when absent.
+    pub expires_in_days: Option<i64>,
+    #[serde(default)]
+    pub include_notes: bool,
+}
+
+impl CreateShareRequest {
+    pub fn validate(&self) -> Result<(), FieldError> {
+        match self.expires_in_days {
+            Some(days) if !(1..=SHARE_MAX_DAYS).contains(&days) => Err(FieldError::new(
+                "expires_in_days",
+                "out_of_range",
+                format!("expires_in_days must be between 1 and {SHARE_MAX_DAYS}"),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Response to share creation: the only time the token is shown.
+#[derive(Debug, Serialize)]
+pub struct CreatedShare {
+    pub id: String,
+    pub task_id: String,
+    pub token: String,
+    pub include_notes: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CreatedShare {
+    pub fn new(share: TaskShare, token: String) -> Self {
+        Self {
+            id: share.id,
+            task_id: share.task_id,
+            token,
+            include_notes: share.include_notes,
+            expires_at: share.expires_at,
+            created_at: share.created_at,
+        }
+    }
+}
+
+/// A task as GET /api/shared/:token shows it: people appear by username
+/// only, and notes only when the share includes them.
+#[derive(Debug, Serialize)]
+pub struct SharedTask {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub status: String,
+    /// Username of the assignee.
+    pub assignee: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<Vec<SharedNote>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharedNote {
+    pub note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rendered_html: Option<String>,
+    /// Username of the author; `None` for deleted users.
+    pub author: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SharedTask {
+    /// `task` redacted for `share`, with user ids replaced from `usernames`.
+    pub fn new(task: Task, share: &TaskShare, usernames: &HashMap<String, String>) -> Self {
+        let name = |id: &str| usernames.get(id).cloned();
+        let notes = share.include_notes.then(|| {
+            task.notes
+                .into_iter()
+                .map(|n| SharedNote {
+                    author: name(&n.author),
+                    note: n.note,
+                    rendered_html: n.rendered_html,
+                    created_at: n.created_at,
+                })
+                .collect()
+        });
+        Self {
+            id: task.id,
+            title: task.title,
+            description: task.description,
+            status: task.status,
+            assignee: task.assignee_id.as_deref().and_then(name),
+            notes,
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+        }
+    }
+
+    /// Ids of the users `new` needs names for.
+    pub fn user_ids(task: &Task, share: &TaskShare) -> Vec<String> {
+        let mut ids: Vec<String> = task.assignee_id.iter().cloned().collect();
+        if share.include_notes {
+            ids.extend(task.notes.iter().map(|n| n.author.clone()));
+        }
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{FakeClock, SequentialIds},
+        models::task::TaskNote,
+    };
+
+    fn task() -> Task {
+        let clock = FakeClock::default();
+        let ids = SequentialIds::default();
+        let mut task = Task::new(&clock, &ids, "Fix the fence".into(), "".into());
+        task.assignee_id = Some("u1".into());
+        task.notes.push(TaskNote::new(&clock, &ids, "Posts ordered".into(), "u2".into()));
+        task
+    }
+
+    #[test]
+    fn only_the_token_hash_is_stored() {
+        let req = CreateShareRequest { expires_in_days: Some(7), include_notes: false };
+        let clock = FakeClock::default();
+        let (share, token) = TaskShare::generate(&clock, &SequentialIds::default(), &task(), &req, "u1");
+        assert_eq!(token.len(), 64);
+        assert_eq!(share.token_hash, hash_key(&token));
+        assert_eq!(share.expires_at, Some(share.created_at + Duration::days(7)));
+        let doc = crate::models::dates::to_stored_document(&share).unwrap();
+        assert!(!doc.values().any(|v| v.as_str() == Some(token.as_str())));
+        assert!(matches!(doc.get("expires_at"), Some(bson::Bson::DateTime(_))));
+    }
+
+    #[test]
+    fn expiry_is_bounded() {
+        let req = |days| CreateShareRequest { expires_in_days: Some(days), include_notes: false };
+        assert!(req(1).validate().is_ok());
+        assert!(CreateShareRequest::default().validate().is_ok());
+        assert_eq!(req(0).validate().unwrap_err().code, "out_of_range");
+        assert_eq!(req(SHARE_MAX_DAYS + 1).validate().unwrap_err().code, "out_of_range");
+    }
+
+    #[test]
+    fn shared_view_names_people_and_hides_notes_unless_included() {
+        let req = CreateShareRequest::default();
+        let (mut share, _) = TaskShare::generate(&FakeClock::default(), &SequentialIds::default(), &task(), &req, "u1");
+        assert_eq!(SharedTask::user_ids(&task(), &share), vec!["u1".to_string()]);
+        let usernames = HashMap::from([("u1".to_string(), "alice".to_string())]);
+        let view = SharedTask::new(task(), &share, &usernames);
+        assert_eq!(view.assignee.as_deref(), Some("alice"));
+        assert!(view.notes.is_none());
+
+        share.include_notes = true;
+        assert_eq!(SharedTask::user_ids(&task(), &share), vec!["u1".to_string(), "u2".to_string()]);
+        let notes = SharedTask::new(task(), &share, &usernames).notes.unwrap();
+        assert_eq!((notes[0].note.as_str(), notes[0].author.as_deref()), ("Posts ordered", None));
+    }
+}
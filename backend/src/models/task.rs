@@ -1,8 +1,19 @@
+use bson::{doc, Document};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
-use uuid::Uuid;
 
-use crate::models::cti::CtiSelection;
+use crate::{
+    clock::{Clock, IdGen},
+    errors::FieldError,
+    markup::html_to_text,
+    models::{
+        cti::CtiSelection,
+        dates::{bson_date, optional_bson_date},
+        workflow::Workflow,
+        workspace::default_workspace_id,
+    },
+    pagination::PageItem,
+};
 
 fn null_as_empty<'de, D, T>(de: D) -> Result<Vec<T>, D::Error>
 where
@@ -12,32 +23,77 @@ where
     Ok(Option::<Vec<T>>::deserialize(de)?.unwrap_or_default())
 }
 
+/// Notes in the order they were written, by `created_at` and then `seq`,
+/// however the array happens to be stored.
+fn sorted_notes<'de, D>(de: D) -> Result<Vec<TaskNote>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut notes: Vec<TaskNote> = null_as_empty(de)?;
+    notes.sort_by_key(|n| (n.created_at, n.seq));
+    Ok(notes)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     #[serde(rename = "_id")]
     pub id: String,
+    /// Tasks from before workspaces belong to the default one.
+    #[serde(default = "default_workspace_id")]
+    pub workspace_id: String,
     pub title: String,
     pub description: String,
     pub status: String,
-    #[serde(deserialize_with = "null_as_empty")]
+    #[serde(deserialize_with = "sorted_notes")]
     pub notes: Vec<TaskNote>,
+    /// The last `seq` handed to a note on this task; only ever incremented.
+    #[serde(default)]
+    pub note_seq: i64,
     pub assignee_id: Option<String>,
     pub cti: Option<CtiSelection>,
+    /// URLs people attached to the task, oldest first.
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub links_external: Vec<ExternalLink>,
+    /// Absent on tasks created before creators were recorded.
+    #[serde(default)]
+    pub created_by: Option<String>,
+    /// Last time `status` changed; absent until the first change.
+    #[serde(default, with = "optional_bson_date")]
+    pub status_changed_at: Option<DateTime<Utc>>,
+    /// Who last changed `assignee_id`, including to unassigned.
+    #[serde(default)]
+    pub assigned_by: Option<String>,
+    /// When `assignee_id` last changed; absent until the first assignment.
+    #[serde(default, with = "optional_bson_date")]
+    pub assigned_at: Option<DateTime<Utc>>,
+    /// When the task should be done by; drives the daily digest.
+    #[serde(default, with = "optional_bson_date")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(with = "bson_date")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "bson_date")]
     pub updated_at: DateTime<Utc>,
 }
 
 impl Task {
-    pub fn new(title: String, description: String) -> Self {
-        let now = Utc::now();
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, title: String, description: String) -> Self {
+        let now = clock.now();
         Self {
-            id: Uuid::new_v4().to_string(),
+            id: ids.new_id(),
+            workspace_id: default_workspace_id(),
             title,
             description,
             status: "todo".to_string(),
             notes: vec![],
+            note_seq: 0,
             assignee_id: None,
             cti: None,
+            links_external: vec![],
+            created_by: None,
+            status_changed_at: None,
+            assigned_by: None,
+            assigned_at: None,
+            due_at: None,
             created_at: now,
             updated_at: now,
         }
@@ -52,105 +108,270 @@ pub struct TaskNote {
     pub id: String,
     pub note: String,
     pub author: String, // this is the ID of the User
+    /// Sanitized HTML of `note` when notes are markdown (`NOTE_FORMAT`);
+    /// render this, never `note`, as HTML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered_html: Option<String>,
+    /// The note without markup, which `/api/search` matches; see
+    /// `markup::NoteBody`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub plain_text: String,
+    /// Assigned by `add_note` from the task's `note_seq`; orders notes
+    /// written in the same millisecond.
+    #[serde(default)]
+    pub seq: i64,
+    /// Files uploaded with the note, stored in GridFS; download each from
+    /// `GET /api/tasks/:id/attachments/:attachment_id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachment_ids: Vec<String>,
+    #[serde(with = "bson_date")]
     pub created_at: DateTime<Utc>
 }
 
 impl TaskNote {
-    pub fn new(note: String, author: String) -> Self {
-        let now = Utc::now();
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, note: String, author: String) -> Self {
+        let now = clock.now();
         Self {
-            id: Uuid::new_v4().to_string(),
+            id: ids.new_id(),
+            plain_text: html_to_text(&note),
             note,
             author,
+            rendered_html: None,
+            seq: 0,
+            attachment_ids: Vec::new(),
             created_at: now,
         }
     }
 }
 
-fn default_page() -> u64 { 1 }
-fn default_limit() -> u64 { 25 }
+/// Longest URL `ExternalLink` accepts.
+pub const EXTERNAL_LINK_URL_MAX_LEN: usize = 2048;
+/// Longest title, given or fetched, in characters.
+pub const EXTERNAL_LINK_TITLE_MAX_CHARS: usize = 200;
+
+/// A link to something outside Mission Control, such as a runbook or a pull
+/// request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalLink {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub url: String,
+    /// Given by the client, or read from the page when `LINK_TITLE_FETCH`
+    /// is on; otherwise absent.
+    pub title: Option<String>,
+    pub added_by: String,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl ExternalLink {
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, url: String, title: Option<String>, added_by: String) -> Self {
+        Self { id: ids.new_id(), url, title, added_by, created_at: clock.now() }
+    }
+}
+
+/// Body of POST /api/tasks/:id/external-links.
+#[derive(Debug, Default, Deserialize)]
+pub struct AddExternalLinkRequest {
+    pub url: String,
+    pub title: Option<String>,
+}
 
-/// Query parameters for GET /api/tasks
-/// Example: ?page=2&limit=10&status=todo,in_progress
-#[derive(Debug, Deserialize)]
+impl AddExternalLinkRequest {
+    /// The parsed http(s) URL and the trimmed title, if one was given.
+    pub fn validate(&self) -> Result<(url::Url, Option<String>), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let url = url::Url::parse(self.url.trim()).ok().filter(|u| matches!(u.scheme(), "http" | "https"));
+        if url.is_none() {
+            errors.push(FieldError::new("url", "invalid_url", "url must be an absolute http(s) URL"));
+        } else if self.url.trim().len() > EXTERNAL_LINK_URL_MAX_LEN {
+            errors.push(FieldError::new(
+                "url",
+                "too_long",
+                format!("url must be at most {EXTERNAL_LINK_URL_MAX_LEN} characters"),
+            ));
+        }
+        let title = self.title.as_deref().map(str::trim).filter(|t| !t.is_empty());
+        if title.is_some_and(|t| t.chars().count() > EXTERNAL_LINK_TITLE_MAX_CHARS) {
+            errors.push(FieldError::new(
+                "title",
+                "too_long",
+                format!("title must be at most {EXTERNAL_LINK_TITLE_MAX_CHARS} characters"),
+            ));
+        }
+        match url {
+            Some(url) if errors.is_empty() => Ok((url, title.map(str::to_string))),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Filter parameters for GET /api/tasks; `page`/`limit` are read separately
+/// as `PageParams`. Also the filter a saved view stores.
+/// Example: ?page=2&limit=10&status=todo,in_progress&sort=-updated_at
+///
+/// A blank parameter means "no filter", so `?status=` clears a saved view's
+/// status filter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TaskQuery {
-    #[serde(default = "default_page")]
-    pub page: u64,
-    #[serde(default = "default_limit")]
-    pub limit: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee_id: Option<String>,
+    /// Tasks assigned to any member of this team; see `restrict_to_members`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    /// One of `TASK_SORTS`, optionally prefixed with `-` for descending.
+    /// Defaults to `-created_at`, newest first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
 }
 
+/// Fields GET /api/tasks can sort by.
+pub const TASK_SORTS: &[&str] = &["created_at", "updated_at", "title", "status"];
+
 impl TaskQuery {
-    pub fn parsed_statuses(&self) -> Result<Option<Vec<String>>, String> {
-        const VALID: &[&str] = &["todo", "in_progress", "done"];
+    pub fn parsed_statuses(&self, workflow: &Workflow) -> Result<Option<Vec<String>>, String> {
         match &self.status {
             None => Ok(None),
-            Some(s) if s.trim().is_empty() => Ok(None),
-            Some(s) => {
-                let statuses: Vec<String> = s
-                    .split(',')
-                    .map(|v| v.trim().to_string())
-                    .filter(|v| !v.is_empty())
-                    .collect();
-                for status in &statuses {
-                    if !VALID.contains(&status.as_str()) {
-                        return Err(format!(
-                            "invalid status '{}': must be one of todo, in_progress, done",
-                            status
-                        ));
-                    }
-                }
-                if statuses.is_empty() { Ok(None) } else { Ok(Some(statuses)) }
+            Some(s) => workflow.parse_statuses(s),
+        }
+    }
+
+    /// These parameters, falling back to `base`'s for any not given.
+    pub fn merged_over(self, base: &TaskQuery) -> TaskQuery {
+        let or = |own: Option<String>, base: &Option<String>| own.or_else(|| base.clone());
+        TaskQuery {
+            status: or(self.status, &base.status),
+            assignee_id: or(self.assignee_id, &base.assignee_id),
+            team_id: or(self.team_id, &base.team_id),
+            category_id: or(self.category_id, &base.category_id),
+            type_id: or(self.type_id, &base.type_id),
+            item_id: or(self.item_id, &base.item_id),
+            sort: or(self.sort, &base.sort),
+        }
+    }
+
+    /// The blank-as-unset `team_id`, which `to_find` leaves to the caller to
+    /// resolve.
+    pub fn team(&self) -> Option<&str> {
+        self.team_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+    }
+
+    /// The Mongo filter and sort these parameters describe, less `team_id`.
+    /// Statuses must be in `workflow`; ids are not checked against anything:
+    /// one naming a deleted user or CTI node simply matches no tasks.
+    pub fn to_find(&self, workflow: &Workflow) -> Result<(Document, Document), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let mut filter = Document::new();
+        match self.parsed_statuses(workflow) {
+            Ok(Some(list)) => {
+                filter.insert("status", doc! { "$in": list });
+            }
+            Ok(None) => {}
+            Err(message) => errors.push(FieldError::new("status", "invalid_status", message)),
+        }
+        let ids = [
+            ("assignee_id", &self.assignee_id),
+            ("cti.category_id", &self.category_id),
+            ("cti.type_id", &self.type_id),
+            ("cti.item_id", &self.item_id),
+        ];
+        for (key, value) in ids {
+            if let Some(id) = value.as_deref().map(str::trim).filter(|id| !id.is_empty()) {
+                filter.insert(key, id);
             }
         }
+        let sort = match parse_sort(self.sort.as_deref().unwrap_or("")) {
+            Ok(sort) => sort,
+            Err(message) => {
+                errors.push(FieldError::new("sort", "invalid_sort", message));
+                Document::new()
+            }
+        };
+        if errors.is_empty() { Ok((filter, sort)) } else { Err(errors) }
     }
 }
 
-/// Paginated response envelope for GET /api/tasks
-#[derive(Debug, Serialize)]
-pub struct PaginatedTasksResponse {
-    pub tasks: Vec<Task>,
-    pub total: u64,
-    pub page: u64,
-    pub limit: u64,
-    pub total_pages: u64,
+/// Narrows a `to_find` filter to tasks assigned to one of `members`. An
+/// `assignee_id` outside the team matches nothing.
+pub fn restrict_to_members(filter: &mut Document, members: Vec<String>) {
+    let within = match filter.get_str("assignee_id") {
+        Ok(assignee) if members.iter().any(|m| m == assignee) => return,
+        Ok(_) => vec![],
+        Err(_) => members,
+    };
+    filter.insert("assignee_id", doc! { "$in": within });
+}
+
+/// Parses a sort such as `-updated_at`; blank means newest first.
+fn parse_sort(s: &str) -> Result<Document, String> {
+    let s = s.trim();
+    let (field, direction) = match s.strip_prefix('-') {
+        Some(field) => (field, -1),
+        None if s.is_empty() => ("created_at", -1),
+        None => (s, 1),
+    };
+    if !TASK_SORTS.contains(&field) {
+        return Err(format!(
+            "invalid sort '{s}': must be one of {}, optionally prefixed with '-'",
+            TASK_SORTS.join(", ")
+        ));
+    }
+    Ok(doc! { field: direction })
+}
+
+/// GET /api/tasks pages list tasks under `"tasks"`.
+impl PageItem for Task {
+    const KEY: &'static str = "tasks";
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::{FakeClock, SequentialIds, SystemClock, UuidIds};
+    use crate::pagination::{PageParams, Paginated};
 
     #[test]
     fn task_new_defaults() {
-        let t = Task::new("Fix vuln".to_string(), "Patch CVE-2024-1234".to_string());
+        let clock = FakeClock::default();
+        let t = Task::new(&clock, &SequentialIds::default(), "Fix vuln".to_string(), "Patch CVE-2024-1234".to_string());
         assert_eq!(t.title, "Fix vuln");
         assert_eq!(t.description, "Patch CVE-2024-1234");
         assert_eq!(t.status, "todo");
         assert!(t.notes.is_empty());
         assert!(t.assignee_id.is_none());
         assert!(t.cti.is_none());
-        assert!(!t.id.is_empty());
+        assert_eq!(t.id, "id-1");
+        assert_eq!((t.created_at, t.updated_at), (clock.now(), clock.now()));
     }
 
     #[test]
     fn task_ids_are_unique() {
-        let a = Task::new("A".to_string(), "desc".to_string());
-        let b = Task::new("A".to_string(), "desc".to_string());
+        let a = Task::new(&SystemClock, &UuidIds, "A".to_string(), "desc".to_string());
+        let b = Task::new(&SystemClock, &UuidIds, "A".to_string(), "desc".to_string());
         assert_ne!(a.id, b.id);
     }
 
     #[test]
     fn task_note_new_stores_fields() {
-        let n = TaskNote::new("Investigated".to_string(), "user-99".to_string());
+        let clock = FakeClock::default();
+        let n = TaskNote::new(&clock, &SequentialIds::default(), "Investigated".to_string(), "user-99".to_string());
         assert_eq!(n.note, "Investigated");
         assert_eq!(n.author, "user-99");
-        assert!(!n.id.is_empty());
+        assert_eq!(n.id, "id-1");
+        assert_eq!(n.created_at, clock.now());
     }
 
     #[test]
     fn task_with_assignee_and_cti_roundtrips_json() {
-        let mut t = Task::new("T".to_string(), "D".to_string());
+        let mut t = Task::new(&SystemClock, &UuidIds, "T".to_string(), "D".to_string());
         t.assignee_id = Some("user-1".to_string());
         t.cti = Some(CtiSelection {
             category_id: "c".to_string(),
@@ -174,54 +395,142 @@ mod tests {
         assert!(t.notes.is_empty());
     }
 
+    #[test]
+    fn notes_come_back_in_written_order() {
+        let json = r#"{"_id":"x","title":"T","description":"D","status":"todo","assignee_id":null,"cti":null,
+            "created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z","notes":[
+            {"_id":"c","note":"c","author":"u","seq":3,"created_at":"2024-01-02T00:00:00Z"},
+            {"_id":"b","note":"b","author":"u","seq":2,"created_at":"2024-01-01T00:00:00Z"},
+            {"_id":"a","note":"a","author":"u","seq":1,"created_at":"2024-01-01T00:00:00Z"}]}"#;
+        let t: Task = serde_json::from_str(json).unwrap();
+        let ids: Vec<_> = t.notes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert_eq!(t.note_seq, 0);
+    }
+
     #[test]
     fn task_query_defaults() {
         let q: TaskQuery = serde_json::from_str("{}").unwrap();
-        assert_eq!(q.page, 1);
-        assert_eq!(q.limit, 25);
         assert!(q.status.is_none());
     }
 
     #[test]
     fn task_query_parsed_statuses_valid() {
-        let q = TaskQuery { page: 1, limit: 25, status: Some("todo,in_progress".to_string()) };
-        let result = q.parsed_statuses().unwrap();
+        let q = TaskQuery { status: Some("todo,in_progress".to_string()), ..Default::default() };
+        let result = q.parsed_statuses(&Workflow::default()).unwrap();
         assert_eq!(result, Some(vec!["todo".to_string(), "in_progress".to_string()]));
     }
 
     #[test]
     fn task_query_parsed_statuses_invalid() {
-        let q = TaskQuery { page: 1, limit: 25, status: Some("todo,bogus".to_string()) };
-        let err = q.parsed_statuses().unwrap_err();
+        let q = TaskQuery { status: Some("todo,bogus".to_string()), ..Default::default() };
+        let err = q.parsed_statuses(&Workflow::default()).unwrap_err();
         assert!(err.contains("bogus"));
     }
 
     #[test]
     fn task_query_parsed_statuses_none_when_empty_string() {
-        let q = TaskQuery { page: 1, limit: 25, status: Some("".to_string()) };
-        assert_eq!(q.parsed_statuses().unwrap(), None);
+        let q = TaskQuery { status: Some("".to_string()), ..Default::default() };
+        assert_eq!(q.parsed_statuses(&Workflow::default()).unwrap(), None);
     }
 
     #[test]
     fn task_query_parsed_statuses_none_when_absent() {
-        let q = TaskQuery { page: 1, limit: 25, status: None };
-        assert_eq!(q.parsed_statuses().unwrap(), None);
+        let q = TaskQuery::default();
+        assert_eq!(q.parsed_statuses(&Workflow::default()).unwrap(), None);
     }
 
     #[test]
-    fn paginated_response_serializes() {
-        let t = Task::new("T".to_string(), "D".to_string());
-        let r = PaginatedTasksResponse {
-            tasks: vec![t],
-            total: 1,
-            page: 1,
-            limit: 25,
-            total_pages: 1,
+    fn task_query_builds_filter_and_sort() {
+        let q = TaskQuery {
+            status: Some("todo".into()),
+            assignee_id: Some("u1".into()),
+            item_id: Some("i1".into()),
+            type_id: Some(" ".into()),
+            sort: Some("-updated_at".into()),
+            ..Default::default()
+        };
+        let (filter, sort) = q.to_find(&Workflow::default()).unwrap();
+        assert_eq!(filter, doc! { "status": { "$in": ["todo"] }, "assignee_id": "u1", "cti.item_id": "i1" });
+        assert_eq!(sort, doc! { "updated_at": -1 });
+        assert_eq!(TaskQuery::default().to_find(&Workflow::default()).unwrap(), (doc! {}, doc! { "created_at": -1 }));
+    }
+
+    #[test]
+    fn task_query_reports_every_invalid_field() {
+        let q = TaskQuery { status: Some("bogus".into()), sort: Some("-notes".into()), ..Default::default() };
+        let errors = q.to_find(&Workflow::default()).unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["status", "sort"]);
+        assert_eq!(parse_sort("title").unwrap(), doc! { "title": 1 });
+    }
+
+    #[test]
+    fn explicit_params_override_the_base_and_blank_clears_it() {
+        let base = TaskQuery {
+            status: Some("done".into()),
+            assignee_id: Some("u1".into()),
+            sort: Some("title".into()),
+            ..Default::default()
         };
+        let own = TaskQuery { status: Some("".into()), sort: Some("-title".into()), ..Default::default() };
+        let merged = own.merged_over(&base);
+        assert_eq!(merged.status.as_deref(), Some(""));
+        assert_eq!(merged.assignee_id.as_deref(), Some("u1"));
+        assert_eq!(merged.sort.as_deref(), Some("-title"));
+        assert!(!merged.to_find(&Workflow::default()).unwrap().0.contains_key("status"));
+    }
+
+    #[test]
+    fn paginated_response_serializes() {
+        let t = Task::new(&SystemClock, &UuidIds, "T".to_string(), "D".to_string());
+        let r = Paginated::new(vec![t], 1, PageParams::default());
         let json = serde_json::to_value(&r).unwrap();
         assert_eq!(json["total"], 1);
         assert_eq!(json["page"], 1);
         assert_eq!(json["total_pages"], 1);
         assert!(json["tasks"].is_array());
     }
+
+    #[test]
+    fn team_filter_intersects_with_the_assignee() {
+        let members = || vec!["a".to_string(), "b".to_string()];
+        let mut filter = doc! { "status": "todo" };
+        restrict_to_members(&mut filter, members());
+        assert_eq!(filter, doc! { "status": "todo", "assignee_id": { "$in": ["a", "b"] } });
+
+        let mut filter = doc! { "assignee_id": "b" };
+        restrict_to_members(&mut filter, members());
+        assert_eq!(filter, doc! { "assignee_id": "b" });
+
+        let mut filter = doc! { "assignee_id": "z" };
+        restrict_to_members(&mut filter, members());
+        assert_eq!(filter, doc! { "assignee_id": { "$in": [] } });
+    }
+
+    #[test]
+    fn external_links_must_be_http_urls_with_short_titles() {
+        let req = |url: &str, title: Option<&str>| AddExternalLinkRequest { url: url.into(), title: title.map(Into::into) };
+        let (url, title) = req(" https://example.com/runbook ", Some("  Runbook ")).validate().unwrap();
+        assert_eq!((url.as_str(), title.as_deref()), ("https://example.com/runbook", Some("Runbook")));
+        assert_eq!(req("http://example.com", Some(" ")).validate().unwrap().1, None);
+
+        let code = |r: AddExternalLinkRequest| r.validate().unwrap_err()[0].code;
+        assert_eq!(code(req("ftp://example.com", None)), "invalid_url");
+        assert_eq!(code(req("javascript:alert(1)", None)), "invalid_url");
+        assert_eq!(code(req("/relative", None)), "invalid_url");
+        let long = format!("https://example.com/{}", "a".repeat(EXTERNAL_LINK_URL_MAX_LEN));
+        assert_eq!(code(req(&long, None)), "too_long");
+        let title = "t".repeat(EXTERNAL_LINK_TITLE_MAX_CHARS + 1);
+        assert_eq!(code(req("https://example.com", Some(&title))), "too_long");
+    }
+
+    #[test]
+    fn tasks_stored_before_external_links_have_none() {
+        let t = Task::new(&SystemClock, &UuidIds, "T".to_string(), "D".to_string());
+        let mut stored = bson::to_document(&t).unwrap();
+        stored.remove("links_external");
+        let t: Task = bson::from_document(stored).unwrap();
+        assert!(t.links_external.is_empty());
+    }
 }
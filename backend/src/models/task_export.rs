@@ -0,0 +1,210 @@
+//! GET /api/tasks/:id/export: one task with everything needed to read it
+//! outside the app, as JSON or as a Markdown summary for pasting into a
+//! postmortem.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cti_cache::CtiTree,
+    markup::{escape_markdown, html_to_text},
+    models::{cti::CtiSelection, revision::TaskRevision, task::Task, user::UserSummary},
+};
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskExportFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TaskExportQuery {
+    #[serde(default)]
+    pub format: TaskExportFormat,
+}
+
+/// The names behind a task's `cti`; absent for entries since deleted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CtiNames {
+    pub category: Option<String>,
+    #[serde(rename = "type")]
+    pub type_name: Option<String>,
+    pub item: Option<String>,
+}
+
+impl CtiNames {
+    pub fn new(tree: &CtiTree, selection: &CtiSelection) -> Self {
+        Self {
+            category: tree.categories.iter().find(|c| c.id == selection.category_id).map(|c| c.name.clone()),
+            type_name: tree.types.iter().find(|t| t.id == selection.type_id).map(|t| t.name.clone()),
+            item: tree.items.iter().find(|i| i.id == selection.item_id).map(|i| i.name.clone()),
+        }
+    }
+}
+
+/// A file attached to one of the task's notes, without its content.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttachmentInfo {
+    pub id: String,
+    pub note_id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub length: u64,
+    pub uploaded_by: Option<String>,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskExport {
+    pub exported_at: DateTime<Utc>,
+    /// As `GET /api/tasks/:id` returns it, notes in written order.
+    pub task: Task,
+    pub assignee: Option<UserSummary>,
+    pub cti: Option<CtiNames>,
+    /// Earlier values of the task's fields, oldest first.
+    pub history: Vec<TaskRevision>,
+    pub attachments: Vec<AttachmentInfo>,
+    /// The username of every user the export mentions, by id. Deleted
+    /// users are left out.
+    pub usernames: BTreeMap<String, String>,
+}
+
+impl TaskExport {
+    /// Every user id the export mentions, for `usernames`.
+    pub fn user_ids(task: &Task, history: &[TaskRevision], attachments: &[AttachmentInfo]) -> Vec<String> {
+        let mut ids: Vec<String> = [&task.assignee_id, &task.created_by, &task.assigned_by]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .chain(task.notes.iter().map(|n| n.author.clone()))
+            .chain(history.iter().map(|r| r.editor_id.clone()))
+            .chain(attachments.iter().filter_map(|a| a.uploaded_by.clone()))
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// `id`'s username, or the id itself for a deleted user.
+    fn name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.usernames.get(id).map_or(id, String::as_str)
+    }
+
+    /// A readable summary: the task's fields, description and notes, oldest
+    /// first, with their authors and attachments. All user content is
+    /// escaped, so it renders as written.
+    pub fn to_markdown(&self) -> String {
+        let task = &self.task;
+        let when = |dt: DateTime<Utc>| dt.format("%Y-%m-%d %H:%M UTC").to_string();
+        let user = |id: &str| escape_markdown(self.name(id));
+        let mut md = format!("# {}\n\n", escape_markdown(&task.title));
+
+        md.push_str(&format!("- **Status:** {}\n", escape_markdown(&task.status)));
+        let assignee = task.assignee_id.as_deref().map_or("unassigned".to_string(), user);
+        md.push_str(&format!("- **Assignee:** {assignee}\n"));
+        let creator = task.created_by.as_deref().map(|id| format!(" by {}", user(id))).unwrap_or_default();
+        md.push_str(&format!("- **Created:** {}{creator}\n", when(task.created_at)));
+        md.push_str(&format!("- **Updated:** {}\n", when(task.updated_at)));
+        if let Some(due_at) = task.due_at {
+            md.push_str(&format!("- **Due:** {}\n", when(due_at)));
+        }
+        if let Some(cti) = &self.cti {
+            let names: Vec<String> = [&cti.category, &cti.type_name, &cti.item]
+                .into_iter()
+                .map(|n| n.as_deref().map_or("(deleted)".to_string(), escape_markdown))
+                .collect();
+            md.push_str(&format!("- **CTI:** {}\n", names.join(" / ")));
+        }
+        md.push_str(&format!("- **Task ID:** {}\n", escape_markdown(&task.id)));
+
+        md.push_str("\n## Description\n\n");
+        if task.description.trim().is_empty() {
+            md.push_str("_No description._");
+        } else {
+            md.push_str(&escape_markdown(&task.description));
+        }
+        md.push_str("\n\n## Notes\n");
+        if task.notes.is_empty() {
+            md.push_str("\n_No notes._\n");
+        }
+        for note in &task.notes {
+            md.push_str(&format!("\n### {}, {}\n\n", user(&note.author), when(note.created_at)));
+            // With markdown notes `note` is the source, shown as written.
+            let text = if note.rendered_html.is_some() { note.note.clone() } else { html_to_text(&note.note) };
+            md.push_str(&escape_markdown(&text));
+            md.push('\n');
+            let files: Vec<&AttachmentInfo> = self.attachments.iter().filter(|a| a.note_id == note.id).collect();
+            if !files.is_empty() {
+                md.push_str("\nAttachments:\n\n");
+                for file in files {
+                    let (name, kind) = (escape_markdown(&file.filename), escape_markdown(&file.content_type));
+                    md.push_str(&format!("- {name} ({kind}, {} bytes)\n", file.length));
+                }
+            }
+        }
+        md
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{Clock, FakeClock, SequentialIds},
+        models::task::TaskNote,
+    };
+
+    fn export() -> TaskExport {
+        let (clock, ids) = (FakeClock::default(), SequentialIds::default());
+        let mut task = Task::new(&clock, &ids, "Outage *postmortem*".into(), "# Root cause\n<b>DNS</b>".into());
+        task.assignee_id = Some("u1".into());
+        task.created_by = Some("u2".into());
+        let mut note = TaskNote::new(&clock, &ids, "<p>Rolled back &amp; <em>verified</em></p>".into(), "u1".into());
+        note.attachment_ids = vec!["f1".into()];
+        task.notes.push(note.clone());
+        task.notes.push(TaskNote::new(&clock, &ids, "gone".into(), "u9".into()));
+        TaskExport {
+            exported_at: clock.now(),
+            assignee: Some(UserSummary { id: "u1".into(), username: "alice_ops".into() }),
+            cti: Some(CtiNames { category: Some("Network".into()), type_name: None, item: Some("DNS".into()) }),
+            history: Vec::new(),
+            attachments: vec![AttachmentInfo {
+                id: "f1".into(),
+                note_id: note.id,
+                filename: "graph.png".into(),
+                content_type: "image/png".into(),
+                length: 2048,
+                uploaded_by: Some("u1".into()),
+                uploaded_at: clock.now(),
+            }],
+            usernames: BTreeMap::from([("u1".into(), "alice_ops".into()), ("u2".into(), "bob".into())]),
+            task,
+        }
+    }
+
+    #[test]
+    fn user_ids_cover_everyone_mentioned() {
+        let export = export();
+        let ids = TaskExport::user_ids(&export.task, &export.history, &export.attachments);
+        assert_eq!(ids, ["u1", "u2", "u9"]);
+    }
+
+    #[test]
+    fn markdown_escapes_user_content_and_lists_notes_in_order() {
+        let md = export().to_markdown();
+        assert!(md.starts_with("# Outage \\*postmortem\\*\n\n- **Status:** todo\n"), "{md}");
+        assert!(md.contains("- **Assignee:** alice\\_ops\n"), "{md}");
+        assert!(md.contains(" by bob\n"), "{md}");
+        assert!(md.contains("- **CTI:** Network / (deleted) / DNS\n"), "{md}");
+        assert!(md.contains("## Description\n\n\\# Root cause\\\n\\<b\\>DNS\\</b\\>\n"), "{md}");
+        let first = md.find("### alice\\_ops, ").unwrap();
+        let second = md.find("### u9, ").unwrap();
+        assert!(first < second, "{md}");
+        let note = "Rolled back \\& verified\n\nAttachments:\n\n- graph.png (image/png, 2048 bytes)\n";
+        assert!(md.contains(note), "{md}");
+    }
+}
@@ -0,0 +1,209 @@
+//! `?fields=` on GET /api/tasks and /api/tasks/:id. Only the named fields are
+//! read, through a Mongo projection, and returned as a `PartialTask` rather
+//! than a `Task` whose required fields would be missing.
+
+use bson::Document;
+use chrono::{DateTime, Utc};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+
+use crate::{
+    errors::AppError,
+    models::{
+        cti::CtiSelection,
+        dates::optional_bson_date,
+        task::{ExternalLink, TaskNote},
+    },
+    pagination::PageItem,
+};
+
+/// The fields `?fields=` may name, in the order responses list them. `_id`
+/// is always returned; `id` and `_id` are accepted and ignored.
+pub const PROJECTABLE_FIELDS: &[&str] = &[
+    "title",
+    "description",
+    "status",
+    "notes",
+    "assignee_id",
+    "cti",
+    "links_external",
+    "created_by",
+    "status_changed_at",
+    "assigned_by",
+    "assigned_at",
+    "due_at",
+    "created_at",
+    "updated_at",
+];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FieldsQuery {
+    /// Comma-separated, e.g. `id,title,status`.
+    pub fields: Option<String>,
+}
+
+/// A checked `?fields=` list, in `PROJECTABLE_FIELDS` order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskFields(Vec<&'static str>);
+
+impl FieldsQuery {
+    /// `None` when `fields` is absent or blank, meaning the whole task; 400
+    /// naming every field that is not in `PROJECTABLE_FIELDS`.
+    pub fn parse(&self) -> Result<Option<TaskFields>, AppError> {
+        let Some(raw) = self.fields.as_deref().filter(|f| !f.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let names: Vec<&str> = raw.split(',').map(str::trim).filter(|n| !n.is_empty()).collect();
+        let unknown: Vec<&str> =
+            names.iter().copied().filter(|n| !matches!(*n, "id" | "_id") && !PROJECTABLE_FIELDS.contains(n)).collect();
+        if !unknown.is_empty() {
+            return Err(AppError::BadRequest(format!(
+                "unknown field(s) {}: must be among id, {}",
+                unknown.join(", "),
+                PROJECTABLE_FIELDS.join(", ")
+            )));
+        }
+        Ok(Some(TaskFields(PROJECTABLE_FIELDS.iter().copied().filter(|f| names.contains(f)).collect())))
+    }
+}
+
+impl TaskFields {
+    pub fn names(&self) -> &[&'static str] {
+        &self.0
+    }
+
+    /// `{ field: 1, ... }`; Mongo adds `_id` itself.
+    pub fn projection(&self) -> Document {
+        self.0.iter().map(|f| (f.to_string(), 1.into())).collect()
+    }
+}
+
+/// A task read with a projection: `_id` plus whichever fields were asked
+/// for. Serializes only those, so a requested field that is unset comes
+/// back as `null` and the rest are left out.
+#[derive(Debug, Deserialize)]
+pub struct PartialTask {
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub notes: Option<Vec<TaskNote>>,
+    #[serde(default)]
+    pub assignee_id: Option<String>,
+    #[serde(default)]
+    pub cti: Option<CtiSelection>,
+    #[serde(default)]
+    pub links_external: Option<Vec<ExternalLink>>,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default, with = "optional_bson_date")]
+    pub status_changed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub assigned_by: Option<String>,
+    #[serde(default, with = "optional_bson_date")]
+    pub assigned_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "optional_bson_date")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "optional_bson_date")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "optional_bson_date")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    fields: TaskFields,
+}
+
+impl PartialTask {
+    /// Records which fields to serialize, and puts notes in written order as
+    /// `Task` does.
+    pub fn with_fields(mut self, fields: &TaskFields) -> Self {
+        if let Some(notes) = &mut self.notes {
+            notes.sort_by_key(|n| (n.created_at, n.seq));
+        }
+        self.fields = fields.clone();
+        self
+    }
+}
+
+impl Serialize for PartialTask {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.0.len() + 1))?;
+        map.serialize_entry("_id", &self.id)?;
+        for &field in &self.fields.0 {
+            match field {
+                "title" => map.serialize_entry(field, &self.title)?,
+                "description" => map.serialize_entry(field, &self.description)?,
+                "status" => map.serialize_entry(field, &self.status)?,
+                "notes" => map.serialize_entry(field, self.notes.as_deref().unwrap_or_default())?,
+                "assignee_id" => map.serialize_entry(field, &self.assignee_id)?,
+                "cti" => map.serialize_entry(field, &self.cti)?,
+                "links_external" => map.serialize_entry(field, self.links_external.as_deref().unwrap_or_default())?,
+                "created_by" => map.serialize_entry(field, &self.created_by)?,
+                "status_changed_at" => map.serialize_entry(field, &self.status_changed_at)?,
+                "assigned_by" => map.serialize_entry(field, &self.assigned_by)?,
+                "assigned_at" => map.serialize_entry(field, &self.assigned_at)?,
+                "due_at" => map.serialize_entry(field, &self.due_at)?,
+                "created_at" => map.serialize_entry(field, &self.created_at)?,
+                "updated_at" => map.serialize_entry(field, &self.updated_at)?,
+                _ => {}
+            }
+        }
+        map.end()
+    }
+}
+
+/// Listed under `"tasks"`, like full tasks.
+impl PageItem for PartialTask {
+    const KEY: &'static str = "tasks";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::{FakeClock, SequentialIds},
+        models::{dates::to_stored_document, task::Task},
+    };
+
+    fn parse(fields: &str) -> Result<Option<TaskFields>, AppError> {
+        FieldsQuery { fields: Some(fields.into()) }.parse()
+    }
+
+    #[test]
+    fn fields_are_checked_against_the_whitelist() {
+        assert_eq!(FieldsQuery::default().parse().unwrap(), None);
+        assert_eq!(parse(" ").unwrap(), None);
+        let fields = parse("id, updated_at,title,status,assignee_id").unwrap().unwrap();
+        assert_eq!(fields.names(), ["title", "status", "assignee_id", "updated_at"]);
+        assert_eq!(fields.projection(), bson::doc! { "title": 1, "status": 1, "assignee_id": 1, "updated_at": 1 });
+        let Err(AppError::BadRequest(message)) = parse("title,note_seq,workspace_id") else { panic!("expected a 400") };
+        assert!(message.starts_with("unknown field(s) note_seq, workspace_id"), "{message}");
+    }
+
+    #[test]
+    fn only_requested_fields_are_serialized() {
+        let task = Task::new(&FakeClock::default(), &SequentialIds::default(), "Patch".into(), "long".into());
+        // As the projection would return it.
+        let stored: Document = to_stored_document(&task)
+            .unwrap()
+            .into_iter()
+            .filter(|(k, _)| ["_id", "title", "assignee_id", "updated_at"].contains(&k.as_str()))
+            .collect();
+        let fields = parse("title,assignee_id,updated_at,notes").unwrap().unwrap();
+        let partial: PartialTask = bson::from_document(stored).unwrap();
+        let json = serde_json::to_value(partial.with_fields(&fields)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "_id": task.id,
+                "title": "Patch",
+                "notes": [],
+                "assignee_id": null,
+                "updated_at": serde_json::to_value(task.updated_at).unwrap(),
+            })
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, IdGen},
+    errors::FieldError,
+    models::{
+        dates::bson_date,
+        workspace::default_workspace_id,
+    },
+};
+
+/// A named group of users in a workspace, used to filter its tasks by
+/// assignee; deleting one leaves tasks alone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Team {
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(default = "default_workspace_id")]
+    pub workspace_id: String,
+    /// Unique within the workspace.
+    pub name: String,
+    #[serde(default)]
+    pub member_ids: Vec<String>,
+    /// Always one of `member_ids`.
+    #[serde(default)]
+    pub lead_id: Option<String>,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson_date")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Team {
+    pub fn new(
+        clock: &dyn Clock,
+        ids: &dyn IdGen,
+        workspace_id: &str,
+        name: String,
+        member_ids: Vec<String>,
+        lead_id: Option<String>,
+    ) -> Self {
+        let now = clock.now();
+        Self {
+            id: ids.new_id(),
+            workspace_id: workspace_id.to_string(),
+            name,
+            member_ids,
+            lead_id,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// A team as returned by /api/teams and /api/admin/teams.
+#[derive(Debug, Serialize)]
+pub struct TeamPublic {
+    pub id: String,
+    pub workspace_id: String,
+    pub name: String,
+    pub member_ids: Vec<String>,
+    pub lead_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Team> for TeamPublic {
+    fn from(t: Team) -> Self {
+        Self {
+            id: t.id,
+            workspace_id: t.workspace_id,
+            name: t.name,
+            member_ids: t.member_ids,
+            lead_id: t.lead_id,
+            created_at: t.created_at,
+            updated_at: t.updated_at,
+        }
+    }
+}
+
+/// Body of POST /api/admin/teams. The lead is added to the members if
+/// missing.
+#[derive(Debug, Deserialize)]
+pub struct CreateTeamRequest {
+    pub name: String,
+    #[serde(default)]
+    pub member_ids: Vec<String>,
+    pub lead_id: Option<String>,
+}
+
+impl CreateTeamRequest {
+    /// The trimmed name, the deduplicated member ids with the lead among
+    /// them, and the lead; a blank lead means none.
+    pub fn validate(&self) -> Result<(String, Vec<String>, Option<String>), Vec<FieldError>> {
+        let name = validate_name(&self.name).map_err(|e| vec![e])?;
+        let lead = self.lead_id.as_deref().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string);
+        let mut members: Vec<String> = Vec::new();
+        for id in self.member_ids.iter().chain(lead.as_ref()) {
+            if !members.contains(id) {
+                members.push(id.clone());
+            }
+        }
+        Ok((name, members, lead))
+    }
+}
+
+/// Body of PUT /api/admin/teams/:id; omitted fields are left alone and a
+/// blank `lead_id` removes the lead. A new lead is added to the members.
+#[derive(Debug, Deserialize)]
+pub struct UpdateTeamRequest {
+    pub name: Option<String>,
+    pub lead_id: Option<String>,
+}
+
+impl UpdateTeamRequest {
+    /// The trimmed name, if one was given.
+    pub fn validate(&self) -> Result<Option<String>, Vec<FieldError>> {
+        self.name.as_deref().map(validate_name).transpose().map_err(|e| vec![e])
+    }
+}
+
+/// Body of POST /api/admin/teams/:id/members.
+#[derive(Debug, Deserialize)]
+pub struct AddTeamMemberRequest {
+    pub user_id: String,
+}
+
+fn validate_name(name: &str) -> Result<String, FieldError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(FieldError::new("name", "invalid_length", "name must be 1-100 characters"));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_request_folds_the_lead_into_the_members() {
+        let req: CreateTeamRequest =
+            serde_json::from_str(r#"{"name":" Red ","member_ids":["a","b","a"],"lead_id":"c"}"#).unwrap();
+        let (name, members, lead) = req.validate().unwrap();
+        assert_eq!(name, "Red");
+        assert_eq!(members, ["a", "b", "c"]);
+        assert_eq!(lead.as_deref(), Some("c"));
+
+        let req: CreateTeamRequest = serde_json::from_str(r#"{"name":"Blue","lead_id":" "}"#).unwrap();
+        assert_eq!(req.validate().unwrap(), ("Blue".to_string(), vec![], None));
+
+        let req: CreateTeamRequest = serde_json::from_str(r#"{"name":""}"#).unwrap();
+        assert_eq!(req.validate().unwrap_err()[0].field, "name");
+    }
+}
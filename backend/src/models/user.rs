@@ -1,24 +1,81 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    models::{
+        dates::{bson_date, optional_bson_date},
+        preferences::UserPreferences,
+    },
+    pagination::PageItem,
+};
+
+fn default_active() -> bool { true }
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     #[serde(rename = "_id")]
     pub id: String,
     pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
     pub username: String,
     pub role: String,
+    #[serde(with = "bson_date")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "bson_date")]
     pub updated_at: DateTime<Utc>,
+    #[serde(default, with = "optional_bson_date")]
+    pub last_login_at: Option<DateTime<Utc>>,
+    /// Deactivated accounts keep their history but cannot sign in or call the API.
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub preferences: UserPreferences,
+    /// Workspace used when a request names none; unset means the default.
+    #[serde(default)]
+    pub active_workspace_id: Option<String>,
+    /// Set when the account's personal data was erased; see
+    /// `anonymized_username`. Such accounts stay inactive for good.
+    #[serde(default, with = "optional_bson_date")]
+    pub anonymized_at: Option<DateTime<Utc>>,
+    /// Local date (in the user's timezone) of the last digest sent, so a
+    /// restart on the same day does not send another.
+    #[serde(default)]
+    pub digest_sent_on: Option<NaiveDate>,
+}
+
+/// The username an anonymized account is left with. Derived from the id so
+/// it stays unique without revealing anything else.
+pub fn anonymized_username(id: &str) -> String {
+    let short: String = id.chars().filter(char::is_ascii_alphanumeric).take(8).collect();
+    format!("deleted-user-{short}")
+}
+
+/// How emails are stored and looked up: trimmed and lower-cased, so one
+/// address is one account however it was typed.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// The email an anonymized account is left with; `.invalid` never delivers.
+pub fn anonymized_email(id: &str) -> String {
+    format!("{}@anonymized.invalid", anonymized_username(id))
 }
 
 #[derive(Debug, Serialize)]
 pub struct UserPublic {
     pub id: String,
     pub email: String,
+    pub email_verified: bool,
     pub username: String,
     pub role: String,
     pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub active: bool,
+}
+
+impl PageItem for UserPublic {
+    const KEY: &'static str = "users";
 }
 
 impl From<User> for UserPublic {
@@ -26,9 +83,92 @@ impl From<User> for UserPublic {
         Self {
             id: u.id,
             email: u.email,
+            email_verified: u.email_verified,
             username: u.username,
             role: u.role,
             created_at: u.created_at,
+            last_login_at: u.last_login_at,
+            active: u.active,
         }
     }
 }
+
+/// Minimal user shape for pickers such as the assignee dropdown. Read with a
+/// Mongo projection, so nothing else leaves the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSummary {
+    #[serde(alias = "_id")]
+    pub id: String,
+    pub username: String,
+}
+
+/// Response for GET /api/auth/me: the caller's public profile plus their
+/// preferences, which other users never see.
+#[derive(Debug, Serialize)]
+pub struct MeResponse {
+    #[serde(flatten)]
+    pub user: UserPublic,
+    pub preferences: UserPreferences,
+}
+
+/// Admin view of a single user: the public fields plus bookkeeping that the
+/// user list leaves out.
+#[derive(Debug, Serialize)]
+pub struct AdminUserDetail {
+    #[serde(flatten)]
+    pub user: UserPublic,
+    pub updated_at: DateTime<Utc>,
+    pub anonymized_at: Option<DateTime<Utc>>,
+    pub assigned_task_count: u64,
+}
+
+impl AdminUserDetail {
+    pub fn new(user: User, assigned_task_count: u64) -> Self {
+        let (updated_at, anonymized_at) = (user.updated_at, user.anonymized_at);
+        Self { user: user.into(), updated_at, anonymized_at, assigned_task_count }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emails_are_trimmed_and_lower_cased() {
+        assert_eq!(normalize_email("  Bob@Example.COM "), "bob@example.com");
+    }
+
+    #[test]
+    fn legacy_user_without_active_flag_is_active() {
+        let json = r#"{"_id":"u1","email":"a@b.c","username":"a","role":"user","created_at":"2024-01-01T00:00:00Z","updated_at":"2024-01-01T00:00:00Z"}"#;
+        let u: User = serde_json::from_str(json).unwrap();
+        assert!(u.active);
+        assert!(u.last_login_at.is_none());
+        assert!(!u.email_verified);
+        assert_eq!(u.preferences, UserPreferences::default());
+    }
+
+    #[test]
+    fn admin_detail_flattens_public_fields() {
+        let json = r#"{"_id":"u1","email":"a@b.c","username":"a","role":"user","created_at":"2024-01-01T00:00:00Z","updated_at":"2024-02-01T00:00:00Z"}"#;
+        let u: User = serde_json::from_str(json).unwrap();
+        let v = serde_json::to_value(AdminUserDetail::new(u, 3)).unwrap();
+        assert_eq!(v["id"], "u1");
+        assert_eq!(v["assigned_task_count"], 3);
+        assert!(v["updated_at"].is_string());
+    }
+
+    #[test]
+    fn summary_reads_mongo_id_and_writes_plain_id() {
+        let s: UserSummary = bson::from_document(bson::doc! { "_id": "u1", "username": "a" }).unwrap();
+        let v = serde_json::to_value(&s).unwrap();
+        assert_eq!(v, serde_json::json!({ "id": "u1", "username": "a" }));
+    }
+
+    #[test]
+    fn anonymized_placeholders_reveal_only_the_id_prefix() {
+        let id = "3f2a9c1e-77b4-4d0e-9a51-0c6b2f8e1d23";
+        assert_eq!(anonymized_username(id), "deleted-user-3f2a9c1e");
+        assert_eq!(anonymized_email(id), "deleted-user-3f2a9c1e@anonymized.invalid");
+    }
+}
@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::nws_client::{NwsAlert, NwsObservation, QuantitativeValue};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeatherLocation {
     #[serde(rename = "_id")]
@@ -53,32 +55,22 @@ pub struct WeatherAlert {
 }
 
 impl WeatherAlert {
-    pub fn new(
-        location_id: String,
-        nws_id: String,
-        event: String,
-        headline: Option<String>,
-        description: Option<String>,
-        severity: Option<String>,
-        urgency: Option<String>,
-        certainty: Option<String>,
-        effective: Option<DateTime<Utc>>,
-        expires: Option<DateTime<Utc>>,
-    ) -> Self {
-        Self {
+    /// `None` for an alert NWS sent without an id, which cannot be deduplicated.
+    pub fn from_nws(location_id: &str, alert: NwsAlert) -> Option<Self> {
+        Some(Self {
             id: Uuid::new_v4().to_string(),
-            location_id,
-            nws_id,
-            event,
-            headline,
-            description,
-            severity,
-            urgency,
-            certainty,
-            effective,
-            expires,
+            location_id: location_id.to_string(),
+            nws_id: alert.id?,
+            event: alert.event.unwrap_or_default(),
+            headline: alert.headline,
+            description: alert.description,
+            severity: alert.severity,
+            urgency: alert.urgency,
+            certainty: alert.certainty,
+            effective: parse_optional_datetime(alert.effective.as_deref()),
+            expires: parse_optional_datetime(alert.expires.as_deref()),
             fetched_at: Utc::now(),
-        }
+        })
     }
 }
 
@@ -100,35 +92,31 @@ pub struct WeatherObservation {
 }
 
 impl WeatherObservation {
-    pub fn new(
-        location_id: String,
-        station_id: String,
-        timestamp: DateTime<Utc>,
-        temperature_c: Option<f64>,
-        dewpoint_c: Option<f64>,
-        wind_direction_deg: Option<f64>,
-        wind_speed_kmh: Option<f64>,
-        barometric_pressure_pa: Option<f64>,
-        visibility_m: Option<f64>,
-        text_description: Option<String>,
-    ) -> Self {
+    /// Timestamped now if NWS sent no usable timestamp.
+    pub fn from_nws(location_id: &str, station_id: &str, obs: NwsObservation) -> Self {
+        let value = |q: Option<QuantitativeValue>| q.and_then(|q| q.value);
         Self {
             id: Uuid::new_v4().to_string(),
-            location_id,
-            station_id,
-            timestamp,
-            temperature_c,
-            dewpoint_c,
-            wind_direction_deg,
-            wind_speed_kmh,
-            barometric_pressure_pa,
-            visibility_m,
-            text_description,
+            location_id: location_id.to_string(),
+            station_id: station_id.to_string(),
+            timestamp: parse_optional_datetime(obs.timestamp.as_deref()).unwrap_or_else(Utc::now),
+            temperature_c: value(obs.temperature),
+            dewpoint_c: value(obs.dewpoint),
+            wind_direction_deg: value(obs.wind_direction),
+            wind_speed_kmh: value(obs.wind_speed),
+            barometric_pressure_pa: value(obs.barometric_pressure),
+            visibility_m: value(obs.visibility),
+            text_description: obs.text_description,
             fetched_at: Utc::now(),
         }
     }
 }
 
+fn parse_optional_datetime(s: Option<&str>) -> Option<DateTime<Utc>> {
+    s.and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,41 +148,53 @@ mod tests {
 
     #[test]
     fn weather_alert_roundtrips_json() {
-        let alert = WeatherAlert::new(
-            "loc1".to_string(),
-            "urn:oid:test".to_string(),
-            "Tornado Warning".to_string(),
-            None,
-            None,
-            Some("Extreme".to_string()),
-            None,
-            None,
-            None,
-            None,
-        );
+        let nws: NwsAlert = serde_json::from_str(
+            r#"{"id": "urn:oid:test", "event": "Tornado Warning", "severity": "Extreme",
+                "effective": "2024-06-15T14:00:00+00:00"}"#,
+        )
+        .unwrap();
+        let alert = WeatherAlert::from_nws("loc1", nws).unwrap();
+        assert!(alert.effective.is_some());
         let json = serde_json::to_string(&alert).unwrap();
         let back: WeatherAlert = serde_json::from_str(&json).unwrap();
         assert_eq!(back.event, "Tornado Warning");
         assert!(back.headline.is_none());
     }
 
+    #[test]
+    fn alerts_without_an_id_are_skipped() {
+        let nws: NwsAlert = serde_json::from_str(r#"{"event": "Tornado Warning"}"#).unwrap();
+        assert!(WeatherAlert::from_nws("loc1", nws).is_none());
+    }
+
     #[test]
     fn weather_observation_roundtrips_json() {
-        let obs = WeatherObservation::new(
-            "loc1".to_string(),
-            "KOKC".to_string(),
-            Utc::now(),
-            Some(22.5),
-            None,
-            None,
-            None,
-            None,
-            None,
-            Some("Partly Cloudy".to_string()),
-        );
+        let nws: NwsObservation = serde_json::from_str(
+            r#"{"timestamp": "not-a-date", "textDescription": "Partly Cloudy",
+                "temperature": {"value": 22.5, "unitCode": "wmoUnit:degC"}, "dewpoint": {"value": null}}"#,
+        )
+        .unwrap();
+        let obs = WeatherObservation::from_nws("loc1", "KOKC", nws);
+        assert_eq!(obs.dewpoint_c, None);
         let json = serde_json::to_string(&obs).unwrap();
         let back: WeatherObservation = serde_json::from_str(&json).unwrap();
         assert_eq!(back.temperature_c, Some(22.5));
         assert_eq!(back.text_description.as_deref(), Some("Partly Cloudy"));
     }
+
+    #[test]
+    fn parse_optional_datetime_handles_valid_iso8601() {
+        let result = parse_optional_datetime(Some("2024-06-15T14:00:00+00:00"));
+        assert!(result.is_some());
+    }
+
+    #[test]
+    fn parse_optional_datetime_returns_none_for_none_input() {
+        assert!(parse_optional_datetime(None).is_none());
+    }
+
+    #[test]
+    fn parse_optional_datetime_returns_none_for_invalid() {
+        assert!(parse_optional_datetime(Some("not-a-date")).is_none());
+    }
 }
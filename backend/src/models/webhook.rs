@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const TASK_CREATED: &str = "task.created";
+pub const TASK_DONE: &str = "task.done";
+/// A task's assignee changed, to someone or to nobody.
+pub const TASK_ASSIGNED: &str = "task.assigned";
+/// Sent only by the test endpoint, whatever the webhook subscribes to.
+pub const PING: &str = "ping";
+
+/// Events a webhook may subscribe to.
+pub const WEBHOOK_EVENTS: &[&str] = &[TASK_CREATED, TASK_DONE, TASK_ASSIGNED];
+
+/// Outbound webhook. Payloads are signed with `secret` (HMAC-SHA256), which
+/// is stored in plaintext because it is needed to sign; it is only shown
+/// from the create endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    pub enabled: bool,
+    /// Subscribed events from `WEBHOOK_EVENTS`; empty means all of them.
+    pub events: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_delivery: Option<DeliveryStatus>,
+}
+
+impl Webhook {
+    pub fn new(url: String, secret: Option<String>, events: Vec<String>, created_by: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            url,
+            secret: secret.unwrap_or_else(|| format!("whsec_{}", Uuid::new_v4().simple())),
+            enabled: true,
+            events,
+            created_by,
+            created_at: Utc::now(),
+            last_delivery: None,
+        }
+    }
+
+    pub fn wants(&self, event: &str) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.iter().any(|e| e == event))
+    }
+}
+
+/// Outcome of the most recent delivery to a webhook.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeliveryStatus {
+    pub event: String,
+    pub delivered_at: DateTime<Utc>,
+    pub ok: bool,
+    pub attempts: u32,
+    /// HTTP status of the last attempt; absent when it got no response.
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Webhook as listed to admins — never includes the secret.
+#[derive(Debug, Serialize)]
+pub struct WebhookPublic {
+    pub id: String,
+    pub url: String,
+    pub enabled: bool,
+    pub events: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub last_delivery: Option<DeliveryStatus>,
+}
+
+impl From<Webhook> for WebhookPublic {
+    fn from(w: Webhook) -> Self {
+        Self {
+            id: w.id,
+            url: w.url,
+            enabled: w.enabled,
+            events: w.events,
+            created_by: w.created_by,
+            created_at: w.created_at,
+            last_delivery: w.last_delivery,
+        }
+    }
+}
+
+/// Response to webhook creation: the only time the secret is shown.
+#[derive(Debug, Serialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub webhook: WebhookPublic,
+    pub secret: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_is_generated_when_not_given() {
+        let w = Webhook::new("https://example.com/hook".into(), None, vec![], "admin-1".into());
+        assert!(w.secret.starts_with("whsec_"));
+        let w = Webhook::new("https://example.com/hook".into(), Some("s3cret".into()), vec![], "admin-1".into());
+        assert_eq!(w.secret, "s3cret");
+    }
+
+    #[test]
+    fn empty_filter_means_every_event() {
+        let mut w = Webhook::new("https://example.com/hook".into(), None, vec![], "admin-1".into());
+        assert!(w.wants(TASK_CREATED) && w.wants(TASK_DONE));
+        w.events = vec![TASK_DONE.into()];
+        assert!(!w.wants(TASK_CREATED) && w.wants(TASK_DONE));
+        w.enabled = false;
+        assert!(!w.wants(TASK_DONE));
+    }
+}
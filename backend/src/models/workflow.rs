@@ -0,0 +1,280 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::FieldError, models::dates::bson_date};
+
+/// Longest status key accepted; keys end up in URLs and saved filters.
+pub const STATUS_KEY_MAX_LEN: usize = 32;
+
+/// One status a task can be in. `key` is what tasks store in `status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowStatus {
+    #[serde(rename = "_id")]
+    pub key: String,
+    pub label: String,
+    /// `#rrggbb`, for the board column and status badges.
+    pub color: String,
+    /// Board columns run in ascending `order`; the first is where new tasks start.
+    pub order: i32,
+    /// Finished work: excluded from "open" counts and quotas, and moving a
+    /// task here fires `task.done`.
+    pub is_terminal: bool,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "bson_date")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WorkflowStatus {
+    pub fn new(key: &str, label: &str, color: &str, order: i32, is_terminal: bool, now: DateTime<Utc>) -> Self {
+        Self {
+            key: key.to_string(),
+            label: label.to_string(),
+            color: color.to_string(),
+            order,
+            is_terminal,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// The statuses seeded on first run, matching what tasks used before
+/// statuses were configurable.
+pub fn default_statuses(now: DateTime<Utc>) -> Vec<WorkflowStatus> {
+    vec![
+        WorkflowStatus::new("todo", "To do", "#5f6b7a", 0, false, now),
+        WorkflowStatus::new("in_progress", "In progress", "#0972d3", 1, false, now),
+        WorkflowStatus::new("done", "Done", "#037f0c", 2, true, now),
+    ]
+}
+
+/// Every status, in board order. Read through `WorkflowCache`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workflow {
+    statuses: Vec<WorkflowStatus>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Self::new(default_statuses(DateTime::UNIX_EPOCH))
+    }
+}
+
+impl Workflow {
+    /// Sorts by `order`, then key. An empty list means the seed has not run
+    /// yet, so the defaults stand in.
+    pub fn new(mut statuses: Vec<WorkflowStatus>) -> Self {
+        if statuses.is_empty() {
+            return Self::default();
+        }
+        statuses.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.key.cmp(&b.key)));
+        Self { statuses }
+    }
+
+    pub fn statuses(&self) -> &[WorkflowStatus] {
+        &self.statuses
+    }
+
+    pub fn get(&self, key: &str) -> Option<&WorkflowStatus> {
+        self.statuses.iter().find(|s| s.key == key)
+    }
+
+    /// Where new tasks start: the first status that is not terminal, or the
+    /// first of all if every one is.
+    pub fn initial(&self) -> &str {
+        self.statuses
+            .iter()
+            .find(|s| !s.is_terminal)
+            .unwrap_or(&self.statuses[0])
+            .key
+            .as_str()
+    }
+
+    pub fn is_terminal(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|s| s.is_terminal)
+    }
+
+    /// Keys of the statuses that are not terminal, for `$in` filters on
+    /// the `status` index.
+    pub fn open_keys(&self) -> Vec<String> {
+        self.statuses.iter().filter(|s| !s.is_terminal).map(|s| s.key.clone()).collect()
+    }
+
+    pub fn terminal_keys(&self) -> Vec<String> {
+        self.statuses.iter().filter(|s| s.is_terminal).map(|s| s.key.clone()).collect()
+    }
+
+    fn unknown(&self, status: &str) -> String {
+        let keys: Vec<&str> = self.statuses.iter().map(|s| s.key.as_str()).collect();
+        format!("invalid status '{status}': must be one of {}", keys.join(", "))
+    }
+
+    /// Field error for a task `status` input that names no status.
+    pub fn validate(&self, status: &str) -> Result<(), FieldError> {
+        match self.get(status) {
+            Some(_) => Ok(()),
+            None => Err(FieldError::new("status", "invalid_status", self.unknown(status))),
+        }
+    }
+
+    /// Parses a comma-separated status filter such as `todo,in_progress`.
+    /// Blank input means "no filter".
+    pub fn parse_statuses(&self, s: &str) -> Result<Option<Vec<String>>, String> {
+        let statuses: Vec<String> = s
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect();
+        if let Some(bad) = statuses.iter().find(|s| self.get(s).is_none()) {
+            return Err(self.unknown(bad));
+        }
+        if statuses.is_empty() { Ok(None) } else { Ok(Some(statuses)) }
+    }
+}
+
+fn valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    key.len() <= STATUS_KEY_MAX_LEN
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn valid_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn check_label(label: &str, errors: &mut Vec<FieldError>) {
+    if label.trim().is_empty() || label.chars().count() > 64 {
+        errors.push(FieldError::new("label", "invalid_label", "label must be 1 to 64 characters"));
+    }
+}
+
+fn check_color(color: &str, errors: &mut Vec<FieldError>) {
+    if !valid_color(color) {
+        errors.push(FieldError::new("color", "invalid_color", "color must be #rrggbb"));
+    }
+}
+
+fn default_color() -> String { "#5f6b7a".to_string() }
+
+/// Body of POST /api/admin/statuses.
+#[derive(Debug, Deserialize)]
+pub struct CreateStatusRequest {
+    pub key: String,
+    pub label: String,
+    #[serde(default = "default_color")]
+    pub color: String,
+    /// Defaults to after the last status.
+    pub order: Option<i32>,
+    #[serde(default)]
+    pub is_terminal: bool,
+}
+
+impl CreateStatusRequest {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if !valid_key(&self.key) {
+            errors.push(FieldError::new(
+                "key",
+                "invalid_key",
+                format!("key must be lowercase letters, digits and underscores, starting with a letter, at most {STATUS_KEY_MAX_LEN} long"),
+            ));
+        }
+        check_label(&self.label, &mut errors);
+        check_color(&self.color, &mut errors);
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// Body of PUT /api/admin/statuses/:key. The key itself cannot change, as
+/// tasks store it; add a new status and delete this one with `migrate_to`.
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateStatusRequest {
+    pub label: Option<String>,
+    pub color: Option<String>,
+    pub order: Option<i32>,
+    pub is_terminal: Option<bool>,
+}
+
+impl UpdateStatusRequest {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if let Some(label) = &self.label {
+            check_label(label, &mut errors);
+        }
+        if let Some(color) = &self.color {
+            check_color(color, &mut errors);
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// Query parameters for DELETE /api/admin/statuses/:key.
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteStatusQuery {
+    /// Moves tasks in the deleted status here first. Without it, deleting
+    /// a status that tasks still use is refused.
+    pub migrate_to: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(key: &str, order: i32, is_terminal: bool) -> WorkflowStatus {
+        WorkflowStatus::new(key, key, "#000000", order, is_terminal, DateTime::UNIX_EPOCH)
+    }
+
+    #[test]
+    fn statuses_follow_order_and_new_tasks_start_in_the_first_open_one() {
+        let workflow = Workflow::new(vec![
+            status("done", 9, true),
+            status("review", 5, false),
+            status("triage", 0, false),
+        ]);
+        let keys: Vec<_> = workflow.statuses().iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, ["triage", "review", "done"]);
+        assert_eq!(workflow.initial(), "triage");
+        assert_eq!(workflow.open_keys(), ["triage", "review"]);
+        assert_eq!(workflow.terminal_keys(), ["done"]);
+        assert!(workflow.is_terminal("done"));
+        assert!(!workflow.is_terminal("missing"));
+    }
+
+    #[test]
+    fn an_empty_collection_falls_back_to_the_defaults() {
+        let workflow = Workflow::new(Vec::new());
+        assert_eq!(workflow, Workflow::default());
+        assert_eq!(workflow.initial(), "todo");
+        assert_eq!(workflow.open_keys(), ["todo", "in_progress"]);
+    }
+
+    #[test]
+    fn filters_and_inputs_are_checked_against_the_workflow() {
+        let workflow = Workflow::new(vec![status("triage", 0, false), status("done", 1, true)]);
+        assert_eq!(workflow.parse_statuses(" triage, done ").unwrap(), Some(vec!["triage".into(), "done".into()]));
+        assert_eq!(workflow.parse_statuses(" , ").unwrap(), None);
+        let err = workflow.parse_statuses("triage,todo").unwrap_err();
+        assert_eq!(err, "invalid status 'todo': must be one of triage, done");
+        assert!(workflow.validate("triage").is_ok());
+        assert_eq!(workflow.validate("todo").unwrap_err().code, "invalid_status");
+    }
+
+    #[test]
+    fn create_requests_need_a_key_label_and_color() {
+        let req = |key: &str, label: &str, color: &str| CreateStatusRequest {
+            key: key.into(),
+            label: label.into(),
+            color: color.into(),
+            order: None,
+            is_terminal: false,
+        };
+        assert!(req("blocked", "Blocked", "#d91515").validate().is_ok());
+        assert!(req("in_review2", "In review", "#ABCDEF").validate().is_ok());
+        let fields: Vec<_> = req("In Review", " ", "red").validate().unwrap_err().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, ["key", "label", "color"]);
+        assert!(req("2fast", "x", "#000000").validate().is_err());
+        assert!(req(&"a".repeat(STATUS_KEY_MAX_LEN + 1), "x", "#000000").validate().is_err());
+    }
+}
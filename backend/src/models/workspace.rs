@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::{Clock, IdGen},
+    errors::FieldError,
+    models::dates::bson_date,
+};
+
+/// Holds everything that predates workspaces, and is where users land until
+/// they switch.
+pub const DEFAULT_WORKSPACE_ID: &str = "default";
+
+pub fn default_workspace_id() -> String {
+    DEFAULT_WORKSPACE_ID.to_string()
+}
+
+/// Roles within one workspace. Task, CTI and share permissions there come
+/// from this role, not the global one: `admin` and `manager` grant what the
+/// global roles of that name do (see `permissions`), `member` what `user`
+/// does. Workspace admins also manage the members.
+pub const WORKSPACE_ADMIN: &str = "admin";
+pub const WORKSPACE_MANAGER: &str = "manager";
+pub const WORKSPACE_MEMBER: &str = "member";
+pub const WORKSPACE_ROLES: &[&str] = &[WORKSPACE_ADMIN, WORKSPACE_MANAGER, WORKSPACE_MEMBER];
+
+/// The default workspace role for a user with global role `role`, which
+/// keeps their powers there as they were before workspaces.
+pub fn default_workspace_role(role: &str) -> &'static str {
+    match role {
+        "admin" => WORKSPACE_ADMIN,
+        "manager" => WORKSPACE_MANAGER,
+        _ => WORKSPACE_MEMBER,
+    }
+}
+
+/// A tenant: tasks and the CTI taxonomy belong to exactly one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub name: String,
+    /// `None` for the default workspace, which no one created.
+    pub created_by: Option<String>,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Workspace {
+    pub fn new(clock: &dyn Clock, ids: &dyn IdGen, name: String, created_by: String) -> Self {
+        Self {
+            id: ids.new_id(),
+            name,
+            created_by: Some(created_by),
+            created_at: clock.now(),
+        }
+    }
+}
+
+/// A user's membership of a workspace, in `workspace_members`. The id is
+/// `<workspace_id>:<user_id>`, so each user is a member at most once and
+/// the per-request check is a lookup by `_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Membership {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub workspace_id: String,
+    pub user_id: String,
+    pub role: String,
+    #[serde(with = "bson_date")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Membership {
+    pub fn id_for(workspace_id: &str, user_id: &str) -> String {
+        format!("{workspace_id}:{user_id}")
+    }
+
+    pub fn new(clock: &dyn Clock, workspace_id: &str, user_id: &str, role: &str) -> Self {
+        Self {
+            id: Self::id_for(workspace_id, user_id),
+            workspace_id: workspace_id.to_string(),
+            user_id: user_id.to_string(),
+            role: role.to_string(),
+            created_at: clock.now(),
+        }
+    }
+}
+
+/// A workspace as listed to one of its members.
+#[derive(Debug, Serialize)]
+pub struct WorkspaceView {
+    pub id: String,
+    pub name: String,
+    /// The caller's role in it.
+    pub role: String,
+    /// Whether it is the caller's active workspace.
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of POST /api/workspaces.
+#[derive(Debug, Deserialize)]
+pub struct CreateWorkspaceRequest {
+    pub name: String,
+}
+
+impl CreateWorkspaceRequest {
+    pub fn validate(&self) -> Result<String, FieldError> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(FieldError::new("name", "invalid_length", "name must be 1-100 characters"));
+        }
+        Ok(name.to_string())
+    }
+}
+
+/// Body of POST /api/workspaces/:id/members. The user must have signed in
+/// at least once.
+#[derive(Debug, Deserialize)]
+pub struct AddMemberRequest {
+    pub email: String,
+    #[serde(default = "default_member_role")]
+    pub role: String,
+}
+
+fn default_member_role() -> String {
+    WORKSPACE_MEMBER.to_string()
+}
+
+impl AddMemberRequest {
+    pub fn validate(&self) -> Result<(), FieldError> {
+        if !WORKSPACE_ROLES.contains(&self.role.as_str()) {
+            return Err(FieldError::new(
+                "role",
+                "invalid_role",
+                format!("role must be one of {}", WORKSPACE_ROLES.join(", ")),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A member as listed by GET /api/workspaces/:id/members.
+#[derive(Debug, Serialize)]
+pub struct MemberView {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::SystemClock;
+
+    #[test]
+    fn membership_ids_pair_workspace_and_user() {
+        let m = Membership::new(&SystemClock, "ws1", "u1", WORKSPACE_MEMBER);
+        assert_eq!(m.id, "ws1:u1");
+        assert_eq!(Membership::id_for("ws1", "u1"), m.id);
+    }
+
+    #[test]
+    fn global_roles_keep_their_powers_in_the_default_workspace() {
+        assert_eq!(default_workspace_role("admin"), WORKSPACE_ADMIN);
+        assert_eq!(default_workspace_role("manager"), WORKSPACE_MANAGER);
+        assert_eq!(default_workspace_role("user"), WORKSPACE_MEMBER);
+    }
+
+    #[test]
+    fn requests_are_validated() {
+        let req = CreateWorkspaceRequest { name: "  Blue team ".into() };
+        assert_eq!(req.validate().unwrap(), "Blue team");
+        assert!(CreateWorkspaceRequest { name: " ".into() }.validate().is_err());
+
+        let add: AddMemberRequest = serde_json::from_str(r#"{"email":"a@example.com"}"#).unwrap();
+        assert_eq!(add.role, WORKSPACE_MEMBER);
+        assert!(add.validate().is_ok());
+        let add = AddMemberRequest { email: "a@example.com".into(), role: "owner".into() };
+        assert_eq!(add.validate().unwrap_err().code, "invalid_role");
+    }
+}
@@ -0,0 +1,115 @@
+//! Prometheus metrics: per-route request latency and counts, plus the MongoDB
+//! operation timings recorded by `db::QueryTimer`. Scraped from `GET /metrics`,
+//! which sits outside `/api` so the bundled nginx config never exposes it.
+
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+
+/// Latency quantiles reported for every histogram, over a rolling minute.
+const QUANTILES: &[f64] = &[0.5, 0.95, 0.99];
+/// Summaries are kept in this many buckets of `BUCKET` each.
+const BUCKET: Duration = Duration::from_secs(20);
+const BUCKET_COUNT: u32 = 3;
+
+/// Label for requests no route matched, so stray paths can't grow the
+/// number of series without bound.
+pub(crate) const UNMATCHED: &str = "unmatched";
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+fn builder() -> PrometheusBuilder {
+    PrometheusBuilder::new()
+        .set_quantiles(QUANTILES)
+        .and_then(|b| b.set_bucket_duration(BUCKET))
+        .expect("static summary settings are valid")
+        .set_bucket_count(BUCKET_COUNT.try_into().unwrap())
+}
+
+/// Installs the global recorder on first use; later calls (e.g. one router
+/// per integration test) share it.
+pub fn handle() -> &'static PrometheusHandle {
+    HANDLE.get_or_init(|| {
+        let recorder = builder().build_recorder();
+        let handle = recorder.handle();
+        if metrics::set_global_recorder(recorder).is_err() {
+            tracing::warn!("A metrics recorder was already installed; /metrics will be empty");
+        }
+        // Rotates the summary buckets, so quantiles cover recent requests.
+        let upkeep = handle.clone();
+        std::thread::Builder::new()
+            .name("metrics-upkeep".into())
+            .spawn(move || loop {
+                std::thread::sleep(BUCKET / 4);
+                upkeep.run_upkeep();
+            })
+            .expect("could not start the metrics upkeep thread");
+        handle
+    })
+}
+
+/// GET /metrics — the Prometheus text exposition format.
+pub async fn metrics_endpoint() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle().render(),
+    )
+}
+
+/// Records `http_request_duration_seconds` and `http_requests_total`,
+/// labelled with the route template (`/api/v1/tasks/:id`), never the raw path.
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED.to_string(), |p| p.as_str().to_string());
+    let method = req.method().to_string();
+    let start = Instant::now();
+    let response = next.run(req).await;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let status = response.status().as_u16().to_string();
+    metrics::histogram!("http_request_duration_seconds", "method" => method.clone(), "route" => route.clone())
+        .record(elapsed);
+    metrics::counter!("http_requests_total", "method" => method, "route" => route, "status" => status).increment(1);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn requests_are_labelled_by_route_template() {
+        let recorder = builder().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            rt.block_on(async {
+                let app = Router::new()
+                    .route("/tasks/:id", get(|| async { "ok" }))
+                    .layer(axum::middleware::from_fn(track_requests));
+                for uri in ["/tasks/1", "/tasks/2", "/nope"] {
+                    app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+                }
+            });
+        });
+        let text = handle.render();
+        assert!(text.contains(r#"http_requests_total{method="GET",route="/tasks/:id",status="200"} 2"#), "{text}");
+        assert!(text.contains(r#"http_request_duration_seconds{method="GET",route="/tasks/:id",quantile="0.95"}"#), "{text}");
+        assert!(text.contains(r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#), "{text}");
+        assert!(!text.contains("/tasks/1"), "raw paths must not become labels");
+    }
+}
@@ -0,0 +1,239 @@
+//! Outbound email. Handlers `enqueue` messages on a bounded channel and a
+//! background worker hands them to an `EmailSender`, retrying failures, so a
+//! slow mail server never holds up an API response.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use tokio::{sync::mpsc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{AppConfig, SmtpConfig, SmtpTls},
+    retry::backoff,
+};
+
+/// Emails waiting for the worker; beyond this, new ones are dropped rather
+/// than slowing down the request that raised them.
+const QUEUE_CAPACITY: usize = 256;
+/// Retries back off from here: 2 s, 4 s, 8 s, …
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A plain-text email to one recipient.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, email: &Email) -> anyhow::Result<()>;
+}
+
+/// Sends through the SMTP server in `SmtpConfig`.
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpSender {
+    pub fn new(config: &SmtpConfig) -> anyhow::Result<Self> {
+        let builder = match config.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port).timeout(Some(SEND_TIMEOUT));
+        if let Some((user, password)) = &config.credentials {
+            builder = builder.credentials(Credentials::new(user.clone(), password.clone()));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse().context("Invalid SMTP_FROM")?,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailSender for SmtpSender {
+    async fn send(&self, email: &Email) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse().with_context(|| format!("Invalid recipient '{}'", email.to))?)
+            .subject(&email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Used when SMTP is not configured: logs what would have been sent.
+pub struct LogSender;
+
+#[async_trait]
+impl EmailSender for LogSender {
+    async fn send(&self, email: &Email) -> anyhow::Result<()> {
+        tracing::info!(to = %email.to, subject = %email.subject, "SMTP not configured; email not sent");
+        Ok(())
+    }
+}
+
+/// The SMTP sender when `SMTP_HOST` is set, otherwise `LogSender`.
+pub fn sender_from_config(config: &AppConfig) -> anyhow::Result<Arc<dyn EmailSender>> {
+    Ok(match &config.smtp {
+        Some(smtp) => Arc::new(SmtpSender::new(smtp).context("Could not set up SMTP")?),
+        None => Arc::new(LogSender),
+    })
+}
+
+/// Cheap to clone; shared through `AppState`.
+#[derive(Clone)]
+pub struct Notifier {
+    tx: mpsc::Sender<Email>,
+}
+
+impl Notifier {
+    pub fn channel() -> (Self, mpsc::Receiver<Email>) {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        (Self { tx }, rx)
+    }
+
+    /// Queues `email` without waiting; send failures never reach the caller.
+    pub fn enqueue(&self, email: Email) {
+        if let Err(e) = self.tx.try_send(email) {
+            tracing::warn!("Dropped email: {e}");
+        }
+    }
+}
+
+/// Sends queued emails one at a time until `shutdown` is cancelled.
+pub async fn run_notifier(
+    sender: Arc<dyn EmailSender>,
+    mut emails: mpsc::Receiver<Email>,
+    max_attempts: u32,
+    shutdown: CancellationToken,
+) {
+    loop {
+        let email = tokio::select! {
+            email = emails.recv() => match email {
+                Some(email) => email,
+                None => return,
+            },
+            _ = shutdown.cancelled() => {
+                tracing::info!("Email worker stopped");
+                return;
+            }
+        };
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let Err(e) = sender.send(&email).await else {
+                break;
+            };
+            if attempts >= max_attempts {
+                tracing::error!(to = %email.to, attempts, "Giving up on email '{}': {e:#}", email.subject);
+                break;
+            }
+            tracing::warn!(to = %email.to, attempts, "Email send failed; retrying: {e:#}");
+            tokio::select! {
+                _ = tokio::time::sleep(backoff(FIRST_RETRY_DELAY, attempts)) => {}
+                _ = shutdown.cancelled() => return,
+            }
+        }
+    }
+}
+
+/// Message bodies. Each returns `(subject, body)`; `link` points at the task
+/// in the web app.
+pub mod templates {
+    pub fn task_assigned(actor: &str, task_title: &str, link: &str) -> (String, String) {
+        (
+            format!("[MissionControl] {actor} assigned you \"{task_title}\""),
+            format!(
+                "{actor} assigned you a task:\n\n    {task_title}\n\nOpen it: {link}\n\n\
+                 You can turn off these emails under notification preferences.\n"
+            ),
+        )
+    }
+
+    pub fn task_mentioned(actor: &str, task_title: &str, link: &str) -> (String, String) {
+        (
+            format!("[MissionControl] {actor} mentioned you on \"{task_title}\""),
+            format!(
+                "{actor} mentioned you in a note on:\n\n    {task_title}\n\nOpen it: {link}\n\n\
+                 You can turn off these emails under notification preferences.\n"
+            ),
+        )
+    }
+
+    /// `listing` is the tasks, already formatted with their links.
+    pub fn digest(date: &str, summary: &str, listing: &str) -> (String, String) {
+        (
+            format!("[MissionControl] Your tasks for {date}: {summary}"),
+            format!("Your tasks for {date}: {summary}.\n\n{listing}You can turn off the digest under preferences.\n"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Fails the first `failures` sends, then records the rest.
+    #[derive(Default)]
+    struct FlakySender {
+        failures: Mutex<u32>,
+        sent: Mutex<Vec<Email>>,
+    }
+
+    #[async_trait]
+    impl EmailSender for FlakySender {
+        async fn send(&self, email: &Email) -> anyhow::Result<()> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                anyhow::bail!("421 try again later");
+            }
+            self.sent.lock().unwrap().push(email.clone());
+            Ok(())
+        }
+    }
+
+    fn email(to: &str) -> Email {
+        Email { to: to.into(), subject: "s".into(), body: "b".into() }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn worker_retries_then_moves_on() {
+        let sender = Arc::new(FlakySender { failures: Mutex::new(3), ..Default::default() });
+        let (notifier, rx) = Notifier::channel();
+        notifier.enqueue(email("a@example.com"));
+        notifier.enqueue(email("b@example.com"));
+        drop(notifier);
+
+        // "a" fails twice and is given up on; "b" fails once, then goes out.
+        run_notifier(sender.clone(), rx, 2, CancellationToken::new()).await;
+        assert_eq!(*sender.sent.lock().unwrap(), vec![email("b@example.com")]);
+    }
+
+    #[test]
+    fn templates_name_the_actor_and_task() {
+        let (subject, body) = templates::task_assigned("alice", "Rotate certs", "https://mc.example.com/tasks/t1");
+        assert_eq!(subject, "[MissionControl] alice assigned you \"Rotate certs\"");
+        assert!(body.contains("https://mc.example.com/tasks/t1"));
+        let (subject, _) = templates::task_mentioned("bob", "Rotate certs", "");
+        assert!(subject.contains("bob mentioned you"));
+    }
+}
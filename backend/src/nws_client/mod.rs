@@ -9,6 +9,12 @@ pub struct NwsClient {
     client: Client,
 }
 
+impl Default for NwsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NwsClient {
     pub fn new() -> Self {
         let client = Client::builder()
@@ -0,0 +1,365 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Query},
+    http::request::Parts,
+};
+use bson::Document;
+use mongodb::{options::FindOptions, Collection};
+use serde::{de::DeserializeOwned, ser::SerializeMap, Deserialize, Serialize, Serializer};
+
+use crate::{
+    errors::{AppError, FieldError},
+    handlers::auth::AppState,
+};
+
+/// `limit` when the request gives none, unless `DEFAULT_PAGE_SIZE` is set.
+pub const DEFAULT_LIMIT: u64 = 25;
+/// Largest `limit`, unless `MAX_PAGE_SIZE` is set.
+pub const MAX_LIMIT: u64 = 100;
+
+fn default_page() -> u64 { 1 }
+
+/// Page sizes from the config: the default `limit`, the largest allowed,
+/// and whether a larger one is lowered to that (`CLAMP_PAGE_SIZE`) rather
+/// than rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimits {
+    pub default: u64,
+    pub max: u64,
+    pub clamp: bool,
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self { default: DEFAULT_LIMIT, max: MAX_LIMIT, clamp: false }
+    }
+}
+
+impl FromRef<AppState> for PageLimits {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.page_limits
+    }
+}
+
+/// `?page=&limit=` as the client sent them; `resolve` applies `PageLimits`.
+/// Handlers that validate other parameters too take `Query<PageQuery>` and
+/// merge its errors into their own list; the rest take `PageParams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct PageQuery {
+    #[serde(default = "default_page")]
+    pub page: u64,
+    pub limit: Option<u64>,
+}
+
+impl PageQuery {
+    /// The page to fetch, and field errors for out-of-range `page`/`limit`.
+    /// With `limits.clamp`, a `limit` over the maximum is lowered to it and
+    /// kept as `requested_limit` instead of being an error.
+    pub fn resolve(self, limits: &PageLimits) -> (PageParams, Vec<FieldError>) {
+        let mut params = PageParams { page: self.page, limit: self.limit.unwrap_or(limits.default), requested_limit: None };
+        if limits.clamp && params.limit > limits.max {
+            params.requested_limit = Some(params.limit);
+            params.limit = limits.max;
+        }
+        let mut errors = Vec::new();
+        if params.limit == 0 || params.limit > limits.max {
+            errors.push(FieldError::new(
+                "limit",
+                "out_of_range",
+                format!("limit must be between 1 and {}", limits.max),
+            ));
+        }
+        if params.page == 0 {
+            errors.push(FieldError::new("page", "out_of_range", "page must be >= 1"));
+        }
+        (params, errors)
+    }
+}
+
+/// A validated page. As an extractor it reads `PageQuery` and rejects
+/// out-of-range values with a 422.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageParams {
+    pub page: u64,
+    pub limit: u64,
+    /// What the client asked for, when `limit` was clamped below it.
+    pub requested_limit: Option<u64>,
+}
+
+impl Default for PageParams {
+    fn default() -> Self {
+        Self { page: default_page(), limit: DEFAULT_LIMIT, requested_limit: None }
+    }
+}
+
+impl PageParams {
+    fn skip(&self) -> u64 {
+        (self.page - 1) * self.limit
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PageParams
+where
+    S: Send + Sync,
+    PageLimits: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::BadRequest(e.body_text()))?;
+        let (params, errors) = query.resolve(&PageLimits::from_ref(state));
+        if !errors.is_empty() {
+            return Err(AppError::Validation(errors));
+        }
+        Ok(params)
+    }
+}
+
+/// Names the JSON key a page of `Self` is listed under, e.g. `"tasks"`.
+pub trait PageItem {
+    const KEY: &'static str;
+}
+
+/// One page of results:
+/// `{ <T::KEY>: [...], total, page, limit, total_pages, has_next, has_prev }`,
+/// plus `requested_limit` when the client's `limit` was clamped.
+#[derive(Debug)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+    pub total_pages: u64,
+    pub requested_limit: Option<u64>,
+}
+
+/// Always at least 1, so an empty result is still "page 1 of 1".
+pub fn total_pages(total: u64, limit: u64) -> u64 {
+    if total == 0 { 1 } else { total.div_ceil(limit) }
+}
+
+impl<T> Paginated<T> {
+    pub fn new(items: Vec<T>, total: u64, params: PageParams) -> Self {
+        Self {
+            items,
+            total,
+            page: params.page,
+            limit: params.limit,
+            total_pages: total_pages(total, params.limit),
+            requested_limit: params.requested_limit,
+        }
+    }
+
+    /// Whether a later page has results. False past the last page, so
+    /// clients paging forward stop there.
+    pub fn has_next(&self) -> bool {
+        self.page < self.total_pages
+    }
+
+    pub fn has_prev(&self) -> bool {
+        self.page > 1
+    }
+
+    /// With `strict` (`STRICT_PAGINATION`), a page past the last one is a 400
+    /// rather than an empty list. Never when there are no results at all.
+    pub fn in_range(self, strict: bool) -> Result<Self, AppError> {
+        if strict && self.total > 0 && self.page > self.total_pages {
+            return Err(AppError::PageOutOfRange { page: self.page, total_pages: self.total_pages });
+        }
+        Ok(self)
+    }
+
+    /// Converts each item, e.g. from the stored model to its public view.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Paginated<U> {
+        Paginated {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            limit: self.limit,
+            total_pages: self.total_pages,
+            requested_limit: self.requested_limit,
+        }
+    }
+}
+
+impl<T: Serialize + PageItem> Serialize for Paginated<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(T::KEY, &self.items)?;
+        map.serialize_entry("total", &self.total)?;
+        map.serialize_entry("page", &self.page)?;
+        map.serialize_entry("limit", &self.limit)?;
+        map.serialize_entry("total_pages", &self.total_pages)?;
+        map.serialize_entry("has_next", &self.has_next())?;
+        map.serialize_entry("has_prev", &self.has_prev())?;
+        if let Some(requested) = self.requested_limit {
+            map.serialize_entry("requested_limit", &requested)?;
+        }
+        map.end()
+    }
+}
+
+/// Runs the count and the page query concurrently. `options` supplies the
+/// sort and projection; skip and limit are set from `params`, which must
+/// already be validated.
+pub async fn paginate<T>(
+    collection: &Collection<T>,
+    filter: Document,
+    mut options: FindOptions,
+    params: PageParams,
+) -> Result<Paginated<T>, AppError>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    options.skip = Some(params.skip());
+    options.limit = Some(params.limit as i64);
+
+    let count = collection.count_documents(filter.clone(), None);
+    let find = async {
+        let mut cursor = collection.find(filter, options).await?;
+        let mut items = Vec::new();
+        while cursor.advance().await? {
+            items.push(cursor.deserialize_current()?);
+        }
+        Ok::<_, mongodb::error::Error>(items)
+    };
+    let (total, items) = tokio::try_join!(count, find).map_err(AppError::from)?;
+    Ok(Paginated::new(items, total, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[derive(Debug)]
+    struct Thing(u32);
+    impl Serialize for Thing {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(s)
+        }
+    }
+    impl PageItem for Thing {
+        const KEY: &'static str = "things";
+    }
+
+    fn params(page: u64, limit: u64) -> PageParams {
+        PageParams { page, limit, requested_limit: None }
+    }
+
+    fn query(page: u64, limit: Option<u64>) -> PageQuery {
+        PageQuery { page, limit }
+    }
+
+    const CLAMPING: PageLimits = PageLimits { default: 50, max: 500, clamp: true };
+
+    #[test]
+    fn defaults_when_absent() {
+        let q: PageQuery = serde_json::from_str("{}").unwrap();
+        assert_eq!(q.resolve(&PageLimits::default()), (params(1, DEFAULT_LIMIT), vec![]));
+        assert_eq!(q.resolve(&CLAMPING).0, params(1, 50));
+    }
+
+    #[test]
+    fn errors_name_each_bad_field() {
+        let (_, errors) = query(0, Some(0)).resolve(&PageLimits::default());
+        assert_eq!(errors.into_iter().map(|e| e.field).collect::<Vec<_>>(), vec!["limit", "page"]);
+        assert_eq!(query(1, Some(MAX_LIMIT + 1)).resolve(&PageLimits::default()).1[0].code, "out_of_range");
+        assert!(query(3, Some(MAX_LIMIT)).resolve(&PageLimits::default()).1.is_empty());
+    }
+
+    #[test]
+    fn oversized_limits_are_rejected_or_clamped_by_config() {
+        let rejecting = PageLimits { clamp: false, ..CLAMPING };
+        let (_, errors) = query(1, Some(501)).resolve(&rejecting);
+        assert_eq!(errors[0].message, "limit must be between 1 and 500");
+        assert!(query(1, Some(500)).resolve(&rejecting).1.is_empty());
+
+        let (page, errors) = query(2, Some(10_000)).resolve(&CLAMPING);
+        assert!(errors.is_empty());
+        assert_eq!(page, PageParams { page: 2, limit: 500, requested_limit: Some(10_000) });
+        assert_eq!(query(1, Some(500)).resolve(&CLAMPING).0.requested_limit, None);
+        // Zero is never clamped up.
+        assert_eq!(query(1, Some(0)).resolve(&CLAMPING).1[0].field, "limit");
+    }
+
+    #[test]
+    fn total_pages_rounds_up_and_never_hits_zero() {
+        assert_eq!(total_pages(0, 25), 1);
+        assert_eq!(total_pages(1, 25), 1);
+        assert_eq!(total_pages(25, 25), 1);
+        assert_eq!(total_pages(26, 25), 2);
+        assert_eq!(total_pages(100, 10), 10);
+    }
+
+    #[test]
+    fn skip_follows_page_and_limit() {
+        assert_eq!(params(1, 10).skip(), 0);
+        assert_eq!(params(3, 10).skip(), 20);
+    }
+
+    #[test]
+    fn envelope_lists_items_under_the_item_key() {
+        let page = Paginated::new(vec![Thing(1), Thing(2)], 7, params(2, 2));
+        let json = serde_json::to_value(&page).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "things": [1, 2], "total": 7, "page": 2, "limit": 2, "total_pages": 4, "has_next": true, "has_prev": true
+            })
+        );
+        let clamped = Paginated::new(vec![Thing(1)], 1, PageParams { requested_limit: Some(900), ..params(1, 100) });
+        let json = serde_json::to_value(&clamped).unwrap();
+        assert_eq!((&json["limit"], &json["requested_limit"]), (&serde_json::json!(100), &serde_json::json!(900)));
+    }
+
+    fn page_of(total: u64, page: u64, limit: u64) -> Paginated<Thing> {
+        Paginated::new(vec![], total, params(page, limit))
+    }
+
+    #[test]
+    fn page_bounds_hold_at_the_edges() {
+        let flags = |p: Paginated<Thing>| (p.has_prev(), p.has_next());
+        // No results: page 1 of 1, nothing either side.
+        assert_eq!(flags(page_of(0, 1, 10)), (false, false));
+        // Exactly divisible: the last page has no next.
+        assert_eq!(page_of(20, 2, 10).total_pages, 2);
+        assert_eq!(flags(page_of(20, 1, 10)), (false, true));
+        assert_eq!(flags(page_of(20, 2, 10)), (true, false));
+        assert_eq!(flags(page_of(21, 2, 10)), (true, true));
+        // Past the end: earlier pages exist, later ones never do.
+        assert_eq!(flags(page_of(21, 9999, 10)), (true, false));
+    }
+
+    #[test]
+    fn strict_mode_rejects_only_pages_past_a_nonempty_end() {
+        assert!(page_of(30, 3, 10).in_range(true).is_ok());
+        assert!(page_of(30, 9999, 10).in_range(false).is_ok());
+        assert!(page_of(0, 5, 10).in_range(true).is_ok());
+        let err = page_of(30, 4, 10).in_range(true).unwrap_err();
+        assert!(matches!(err, AppError::PageOutOfRange { page: 4, total_pages: 3 }));
+    }
+
+    #[tokio::test]
+    async fn extractor_rejects_out_of_range_with_422() {
+        let app = |limits: PageLimits| {
+            Router::new().route("/x", get(|p: PageParams| async move { p.limit.to_string() })).with_state(limits)
+        };
+        let send = |limits: PageLimits, uri: &'static str| async move {
+            let res = app(limits).oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+            let status = res.status();
+            let body = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            (status, String::from_utf8_lossy(&body).into_owned())
+        };
+        let strict = PageLimits::default();
+        assert_eq!(send(strict, "/x").await, (StatusCode::OK, "25".into()));
+        assert_eq!(send(strict, "/x?page=2&limit=50").await.0, StatusCode::OK);
+        assert_eq!(send(strict, "/x?limit=500").await.0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(send(strict, "/x?page=abc").await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(send(CLAMPING, "/x?limit=5000").await, (StatusCode::OK, "500".into()));
+    }
+}
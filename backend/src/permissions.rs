@@ -0,0 +1,102 @@
+//! Role → permission mapping. Tokens and user records carry only a role name;
+//! what that role may do is decided here, so changing it needs no re-login.
+
+use crate::errors::FieldError;
+
+/// Roles an admin may assign, least to most privileged.
+pub const ROLES: &[&str] = &["user", "manager", "admin"];
+
+pub const CTI_WRITE: &str = "cti:write";
+pub const TASKS_ASSIGN: &str = "tasks:assign";
+/// Streaming every task out through `GET /api/tasks/stream`.
+pub const TASKS_EXPORT: &str = "tasks:export";
+pub const USERS_MANAGE: &str = "users:manage";
+
+/// Scopes a credential can be limited to. API keys always are; a JWT minted
+/// for an integration is when it carries an `mc_scopes` claim. `area:*`
+/// grants every scope in `area`.
+pub const SCOPES: &[&str] = &[
+    "tasks:read",
+    "tasks:write",
+    "tasks:export",
+    "tasks:*",
+    "cti:read",
+    "cti:write",
+    "cti:*",
+    "users:read",
+    "admin:read",
+    "admin:write",
+    "admin:*",
+];
+
+const USER_PERMISSIONS: &[&str] = &[];
+const MANAGER_PERMISSIONS: &[&str] = &[CTI_WRITE, TASKS_ASSIGN];
+
+pub fn is_valid_role(role: &str) -> bool {
+    ROLES.contains(&role)
+}
+
+/// Field error for a `role` input that is not one of `ROLES`.
+pub fn validate_role(role: &str) -> Result<(), FieldError> {
+    if is_valid_role(role) {
+        Ok(())
+    } else {
+        Err(FieldError::new(
+            "role",
+            "invalid_role",
+            format!("Role must be one of {}", ROLES.join(", ")),
+        ))
+    }
+}
+
+/// Admin holds every permission, including ones added after this list.
+pub fn has_permission(role: &str, permission: &str) -> bool {
+    match role {
+        "admin" => true,
+        "manager" => MANAGER_PERMISSIONS.contains(&permission),
+        _ => USER_PERMISSIONS.contains(&permission),
+    }
+}
+
+/// Whether the granted scope `granted` covers `required`, directly or as a
+/// wildcard.
+pub fn scope_grants(granted: &str, required: &str) -> bool {
+    granted == required || granted.strip_suffix('*').is_some_and(|area| required.starts_with(area))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manager_can_manage_cti_but_not_users() {
+        assert!(has_permission("manager", CTI_WRITE));
+        assert!(has_permission("manager", TASKS_ASSIGN));
+        assert!(!has_permission("manager", USERS_MANAGE));
+    }
+
+    #[test]
+    fn admin_is_a_superset() {
+        for p in [CTI_WRITE, TASKS_ASSIGN, USERS_MANAGE, "anything:else"] {
+            assert!(has_permission("admin", p));
+        }
+    }
+
+    #[test]
+    fn user_and_unknown_roles_get_nothing_extra() {
+        assert!(!has_permission("user", CTI_WRITE));
+        assert!(!has_permission("superuser", CTI_WRITE));
+        assert!(!is_valid_role("superuser"));
+        assert!(is_valid_role("manager"));
+        assert_eq!(validate_role("superuser").unwrap_err().code, "invalid_role");
+    }
+
+    #[test]
+    fn wildcards_cover_their_area_only() {
+        assert!(scope_grants("tasks:read", "tasks:read"));
+        assert!(scope_grants("admin:*", "admin:write"));
+        assert!(scope_grants("tasks:*", "tasks:export"));
+        assert!(!scope_grants("tasks:*", "cti:read"));
+        assert!(!scope_grants("tasks:read", "tasks:write"));
+    }
+}
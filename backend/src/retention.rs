@@ -0,0 +1,208 @@
+//! The retention job. Every `RETENTION_INTERVAL_HOURS` it applies the rules
+//! in `models::retention`: done tasks are archived (moved to
+//! `archived_tasks`), archived tasks and old login events deleted. Work goes
+//! in batches of `RETENTION_BATCH_SIZE`, and each run, dry runs included,
+//! is recorded in `retention_runs`.
+
+use bson::{doc, Bson, Document};
+use chrono::{DateTime, Utc};
+use mongodb::options::{FindOptions, ReplaceOptions};
+use tokio::time::{interval, Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    clock::{IdGen, UuidIds},
+    config::RetentionConfig,
+    db::{batch_filter, collect, Db, ARCHIVED_TASKS, RETENTION_RUNS, TASKS},
+    errors::AppResult,
+    handlers::{attachments::delete_task_attachments, revisions::delete_revisions, shares::delete_shares},
+    models::{
+        dates::{self, to_bson_date},
+        retention::{RetentionRun, Rule, RuleOutcome, SAMPLE_IDS_MAX},
+    },
+    workflow_cache,
+};
+
+/// Runs every `config.interval_hours`, starting at once, until `shutdown`
+/// is cancelled. Does nothing while every rule is off.
+pub async fn run_retention(db: Db, config: RetentionConfig, shutdown: CancellationToken) {
+    if Rule::enabled(&config).is_empty() {
+        tracing::info!("Retention job off: no rule is enabled");
+        return;
+    }
+    let mut ticker = interval(Duration::from_secs(config.interval_hours * 3600));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => {
+                tracing::info!("Retention job stopped");
+                return;
+            }
+        }
+        match run(&db, &config, &UuidIds, dates::now(), false, None).await {
+            Ok(run) if run.failed() => tracing::warn!(run_id = %run.id, "Retention run finished with errors"),
+            Ok(run) => tracing::info!(run_id = %run.id, "Retention run finished"),
+            Err(e) => tracing::error!("Retention run failed: {e:?}"),
+        }
+    }
+}
+
+/// Applies every enabled rule as of `now`, or with `dry_run` only finds what
+/// they would affect, and records the run. A rule that fails is noted in
+/// its outcome and the rest still run.
+pub async fn run(
+    db: &Db,
+    config: &RetentionConfig,
+    ids: &dyn IdGen,
+    now: DateTime<Utc>,
+    dry_run: bool,
+    requested_by: Option<String>,
+) -> AppResult<RetentionRun> {
+    let terminal = workflow_cache::load(db).await?.terminal_keys();
+    let mut rules = Vec::new();
+    for rule in Rule::enabled(config) {
+        let mut outcome = RuleOutcome::new(rule, config, now);
+        let filter = rule.filter(outcome.cutoff, &terminal);
+        let result = if dry_run {
+            preview(db, rule, filter, &mut outcome).await
+        } else {
+            apply(db, rule, filter, config.batch_size, now, &mut outcome).await
+        };
+        if let Err(e) = result {
+            tracing::warn!(rule = rule.name(), "Retention rule failed: {e:?}");
+            outcome.error = Some(e.to_string());
+        }
+        rules.push(outcome);
+    }
+    let finished_at = dates::now();
+    let run = RetentionRun { id: ids.new_id(), dry_run, requested_by, started_at: now, finished_at, rules };
+    db.collection::<RetentionRun>(RETENTION_RUNS).insert_one(&run, None).await?;
+    Ok(run)
+}
+
+/// Counts what `rule` matches and lists a few of the ids.
+async fn preview(db: &Db, rule: Rule, filter: Document, outcome: &mut RuleOutcome) -> AppResult<()> {
+    let collection = db.collection::<Document>(rule.collection());
+    outcome.affected = collection.count_documents(filter.clone(), None).await?;
+    let options = FindOptions::builder()
+        .projection(doc! { "_id": 1 })
+        .sort(doc! { "_id": 1 })
+        .limit(SAMPLE_IDS_MAX as i64)
+        .build();
+    outcome.sample_ids = collect(collection.find(filter, options).await?).await?.iter().map(id_of).collect();
+    Ok(())
+}
+
+/// Archives or deletes what `rule` matches, `batch_size` documents at a time.
+async fn apply(
+    db: &Db,
+    rule: Rule,
+    filter: Document,
+    batch_size: u32,
+    now: DateTime<Utc>,
+    outcome: &mut RuleOutcome,
+) -> AppResult<()> {
+    let collection = db.collection::<Document>(rule.collection());
+    loop {
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).limit(i64::from(batch_size)).build();
+        let batch = collect(collection.find(filter.clone(), options).await?).await?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<Bson> = batch.iter().filter_map(|d| d.get("_id").cloned()).collect();
+        match rule {
+            Rule::ArchiveDoneTasks => archive(db, batch, now).await?,
+            Rule::PurgeArchivedTasks => delete_task_data(db, &batch).await,
+            Rule::PurgeLoginEvents => {}
+        }
+        let deleted = collection.delete_many(batch_filter(&filter, &ids), None).await?;
+        if rule == Rule::ArchiveDoneTasks && deleted.deleted_count < ids.len() as u64 {
+            drop_reopened_copies(db, &ids).await?;
+        }
+        if deleted.deleted_count == 0 {
+            // Removed by someone else meanwhile; don't loop over them again.
+            return Ok(());
+        }
+        outcome.affected += deleted.deleted_count;
+        outcome.batches += 1;
+        tracing::info!(
+            rule = rule.name(),
+            batch = outcome.batches,
+            affected = outcome.affected,
+            "Retention batch done"
+        );
+        if ids.len() < batch_size as usize {
+            return Ok(());
+        }
+    }
+}
+
+/// Copies `tasks` to `archived_tasks`, stamped with `archived_at`. A copy
+/// left by an interrupted run is replaced, so the archive holds the task as
+/// it was last archived.
+async fn archive(db: &Db, tasks: Vec<Document>, now: DateTime<Utc>) -> AppResult<()> {
+    let archived = db.collection::<Document>(ARCHIVED_TASKS);
+    let options = ReplaceOptions::builder().upsert(true).build();
+    for mut task in tasks {
+        task.insert("archived_at", to_bson_date(now));
+        let filter = doc! { "_id": task.get("_id").cloned().unwrap_or(Bson::Null) };
+        archived.replace_one(filter, task, options.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Removes the archive copies of those of `ids` still in `tasks`: reopened
+/// since they were read, so the delete left them in place.
+async fn drop_reopened_copies(db: &Db, ids: &[Bson]) -> AppResult<()> {
+    let options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+    let tasks = db.collection::<Document>(TASKS);
+    let kept: Vec<Bson> = collect(tasks.find(doc! { "_id": { "$in": ids } }, options).await?)
+        .await?
+        .iter()
+        .filter_map(|d| d.get("_id").cloned())
+        .collect();
+    if kept.is_empty() {
+        return Ok(());
+    }
+    db.collection::<Document>(ARCHIVED_TASKS).delete_many(doc! { "_id": { "$in": kept } }, None).await?;
+    Ok(())
+}
+
+/// Deletes the revisions, share links and attachments of archived `tasks`,
+/// logging (not failing on) what can't be, as deleting a task does.
+async fn delete_task_data(db: &Db, tasks: &[Document]) {
+    for task in tasks {
+        let (id, ws) = (id_of(task), task.get_str("workspace_id").unwrap_or_default());
+        if let Err(e) = delete_revisions(db, ws, &id).await {
+            tracing::warn!(task_id = %id, "Could not delete the revisions of a purged task: {e}");
+        }
+        if let Err(e) = delete_shares(db, ws, &id).await {
+            tracing::warn!(task_id = %id, "Could not delete the share links of a purged task: {e}");
+        }
+        if let Err(e) = delete_task_attachments(db, ws, &id).await {
+            tracing::warn!(task_id = %id, "Could not delete the note attachments of a purged task: {e}");
+        }
+    }
+}
+
+fn id_of(doc: &Document) -> String {
+    match doc.get("_id") {
+        Some(Bson::String(id)) => id.clone(),
+        Some(Bson::ObjectId(id)) => id.to_hex(),
+        Some(other) => other.to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_read_as_plain_strings() {
+        let oid = bson::oid::ObjectId::new();
+        assert_eq!(id_of(&doc! { "_id": "t-1" }), "t-1");
+        assert_eq!(id_of(&doc! { "_id": oid }), oid.to_hex());
+        assert_eq!(id_of(&doc! {}), "");
+    }
+}
@@ -0,0 +1,22 @@
+//! Backoff between retries, shared by the background workers that redeliver
+//! (`notifier`, `events`, `webhooks`); each passes its own first delay.
+
+use std::time::Duration;
+
+/// Delay before retry `n` (1-based): `first`, then doubling each retry.
+pub fn backoff(first: Duration, retry: u32) -> Duration {
+    first * 2u32.saturating_pow(retry.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_from_the_first_delay() {
+        let first = Duration::from_millis(200);
+        assert_eq!(backoff(first, 1), first);
+        assert_eq!(backoff(first, 2), Duration::from_millis(400));
+        assert_eq!(backoff(first, 4), Duration::from_millis(1600));
+    }
+}
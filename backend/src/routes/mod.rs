@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use axum::{
-    http::Request,
+    extract::DefaultBodyLimit,
     middleware,
     routing::{delete, get, post, put},
     Router,
@@ -10,125 +10,491 @@ use axum::http::header::HeaderName;
 use jsonwebtoken::DecodingKey;
 use tokio::sync::RwLock;
 use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::CompressionLayer,
     cors::CorsLayer,
-    request_id::{MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
     trace::TraceLayer,
 };
 use axum::http::{HeaderValue, Method, header};
-use uuid::Uuid;
-
-/// Always generates a fresh server-side UUID, ignoring any client-supplied header.
-/// Prevents log injection via forged X-Correlation-ID values.
-#[derive(Clone, Default)]
-struct AlwaysMakeRequestUuid;
-
-impl MakeRequestId for AlwaysMakeRequestUuid {
-    fn make_request_id<B>(&mut self, _request: &Request<B>) -> Option<RequestId> {
-        let id = Uuid::new_v4().to_string().parse().ok()?;
-        Some(RequestId::new(id))
-    }
-}
 
 use crate::{
+    clock::{SystemClock, UuidIds},
     config::AppConfig,
-    db::Db,
+    cti_cache::CtiCache,
+    db::{Db, QueryTimer, Repos, Transactions},
+    events::EventBus,
+    graphql,
     handlers::{
-        admin::{admin_delete_user, admin_list_users, admin_update_role, admin_update_user},
-        auth::{me, AppState},
+        admin::{
+            admin_activate_user, admin_anonymize_user, admin_bulk_update_users, admin_deactivate_user,
+            admin_delete_user, admin_get_user, admin_list_users, admin_update_role, admin_update_user, admin_user_tasks,
+        },
+        admin_stats::{admin_stats, ADMIN_STATS_CACHE_TTL},
+        api_keys::{create_api_key, delete_api_key, get_api_key, list_api_keys},
+        attachments::download_attachment,
+        auth::{create_session, csrf_token, logout, me, AppState},
+        backup::{admin_backup, admin_restore},
         ca::{ca_cert_status, ca_crl, ca_health, ca_provisioners, ca_roots},
         cti::{
-            create_category, create_item, create_type, delete_category, delete_item, delete_type,
-            list_categories, list_items, list_types,
+            admin_refresh_cti_cache, create_category, create_item, create_type, delete_category, delete_item,
+            delete_type, get_category, get_item, get_type, list_categories, list_items, list_types,
+        },
+        dashboard::{get_dashboard, get_my_work, get_timeseries},
+        diagnostics::admin_diagnostics,
+        external_links::{add_external_link, delete_external_link},
+        features::{admin_list_features, list_features},
+        feeds::{add_feed, delete_feed, get_feed, get_feed_items, list_feeds},
+        graphql::graphql_handler,
+        health::{health_live, health_ready},
+        integrity::{admin_integrity_report, admin_integrity_repair},
+        invites::{admin_create_invite, admin_get_invite, admin_list_invites},
+        logins::{admin_user_logins, my_logins},
+        maintenance::admin_cleanup,
+        notifications::{digest_preview, list_notifications, mark_all_notifications_read, mark_notification_read},
+        preferences::{get_preferences, update_preferences},
+        reports::{cti_report, stale_report, unassigned_report},
+        retention::{list_retention_runs, retention_dry_run},
+        revisions::{list_task_revisions, restore_task_revision},
+        search::search,
+        shares::{create_task_share, get_shared_task, revoke_task_share},
+        statuses::{
+            admin_create_status, admin_delete_status, admin_get_status, admin_list_statuses, admin_update_status,
+            list_statuses,
+        },
+        task_export::export_task,
+        tasks::{
+            add_note, assign_task, claim_task, count_tasks, create_task, delete_note, delete_task, get_task, head_tasks,
+            list_tasks, stream_tasks, task_board, unassign_task, update_task, TOTAL_COUNT_HEADER,
         },
-        dashboard::get_dashboard,
-        feeds::{add_feed, delete_feed, get_feed_items, list_feeds},
-        health::health_check,
-        tasks::{add_note, create_task, delete_note, delete_task, get_task, list_tasks, update_task},
+        teams::{
+            admin_add_team_member, admin_create_team, admin_delete_team, admin_get_team, admin_remove_team_member,
+            admin_update_team, list_teams,
+        },
+        user_export::{admin_export_user_data, admin_export_users},
         users::list_users,
+        views::{create_view, delete_view, get_view, list_views, update_view},
         weather::{
-            create_weather_location, delete_weather_location, get_location_alerts,
+            create_weather_location, delete_weather_location, get_location_alerts, get_weather_location,
             get_location_observations, list_weather_locations, trigger_weather_poll,
         },
+        webhooks::{
+            admin_create_webhook, admin_delete_webhook, admin_get_webhook, admin_list_webhooks, admin_test_webhook,
+        },
+        workspaces::{
+            add_member, create_workspace, get_workspace, list_members, list_workspaces, remove_member, switch_workspace,
+        },
+    },
+    middleware::{
+        auth::require_auth, body_limit::payload_too_large_as_json,
+        cookie::CSRF_HEADER, db_budget::{db_ops_budget, DB_OPS_HEADER}, deprecation::legacy_api_deprecation, etag::etag,
+        fallback::{method_not_allowed_as_json, route_not_found},
+        panic::panic_as_json,
+        permission::require_permission,
+        request_id::{request_id, REQUEST_ID_HEADER},
+        security_headers::security_headers,
+        timeout::request_timeout,
+        trace::{record_response, record_user, request_span},
+        workspace::{require_workspace, WORKSPACE_HEADER},
     },
-    middleware::{admin::require_admin, auth::require_auth},
+    monitoring::{self, metrics_endpoint, track_requests},
+    notifier::Notifier,
     nws_client::NwsClient,
+    permissions::CTI_WRITE,
+    stats_cache::{KeyedStatsCache, StatsCache},
+    user_cache::UserStatusCache,
+    webhooks::WebhookDispatcher,
+    workflow_cache::WorkflowCache,
 };
 
+/// Where v1 is mounted; `Location` headers point here.
+pub const API_V1: &str = "/api/v1";
+
+/// Mounts v1 at `/api/v1` and again at the unversioned `/api` as a deprecated
+/// alias. A later version is nested alongside as its own router (usually v1
+/// with some routes replaced), so adding one never touches v1:
+///
+/// `mount_api(api_v1).nest("/api/v2", api_v2)`
+fn mount_api<S: Clone + Send + Sync + 'static>(v1: Router<S>) -> Router<S> {
+    Router::new()
+        .nest(API_V1, v1.clone())
+        .nest("/api", v1.layer(middleware::from_fn(legacy_api_deprecation)))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn build_router(
+    config: AppConfig,
     pool: Db,
+    txn: Transactions,
     nws_client: Arc<NwsClient>,
     ca_client: reqwest::Client,
     intermediate_cert_der: Arc<Vec<u8>>,
     keycloak_decoding_key: Arc<RwLock<DecodingKey>>,
+    webhooks: WebhookDispatcher,
+    notifier: Notifier,
+    events: EventBus,
 ) -> Router {
+    let user_cache = UserStatusCache::new(Duration::from_secs(config.user_cache_ttl_seconds));
+    let dashboard_cache = KeyedStatsCache::new(Duration::from_secs(config.dashboard_cache_ttl_seconds));
+    // Installs the recorder before anything is measured.
+    monitoring::handle();
+    let repos = Repos::mongo(&pool, txn, QueryTimer::new(Duration::from_millis(config.slow_query_ms)));
+    let cti_cache = CtiCache::new(Duration::from_secs(config.cti_cache_ttl_seconds));
+    {
+        let (cache, db, repo) = (cti_cache.clone(), pool.clone(), repos.cti.clone());
+        tokio::spawn(async move {
+            match cache.warm(&db, repo.as_ref()).await {
+                Ok(n) => tracing::info!(workspaces = n, "CTI cache warmed"),
+                Err(e) => tracing::warn!("Could not warm the CTI cache: {e}"),
+            }
+        });
+    }
     let state = AppState {
+        repos,
         db: pool,
-        config: AppConfig::from_env(),
+        config,
         nws_client,
         ca_client,
         intermediate_cert_der,
         keycloak_decoding_key,
+        user_cache,
+        dashboard_cache,
+        admin_stats_cache: StatsCache::new(ADMIN_STATS_CACHE_TTL),
+        cti_cache,
+        workflow: WorkflowCache::new(),
+        webhooks,
+        events,
+        notifier,
+        graphql: graphql::schema(),
+        clock: Arc::new(SystemClock),
+        ids: Arc::new(UuidIds),
     };
 
-    let x_correlation_id = HeaderName::from_static("x-correlation-id");
-
     let health_route = Router::new()
-        .route("/health", get(health_check));
+        .route("/health", get(health_live))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route("/metrics", get(metrics_endpoint));
+
+    // Paths below are relative to the API version prefix; see `mount_api`.
+    let public_auth_routes = Router::new()
+        .route("/auth/csrf", get(csrf_token))
+        .route("/auth/logout", post(logout));
+
+    // The token is the credential; see `handlers::shares`.
+    let shared_routes = Router::new().route("/shared/:token", get(get_shared_task));
 
     let admin_routes = Router::new()
-        .route("/api/admin/users", get(admin_list_users))
+        .route("/admin/users", get(admin_list_users))
+        .route("/admin/features", get(admin_list_features))
+        .route("/admin/stats", get(admin_stats))
+        .route("/admin/diagnostics", get(admin_diagnostics))
+        .route("/admin/retention/dry-run", post(retention_dry_run))
+        .route("/admin/retention/runs", get(list_retention_runs))
+        .route("/admin/maintenance/cleanup", post(admin_cleanup))
+        .route("/admin/users/export", get(admin_export_users))
+        .route("/admin/users/bulk", post(admin_bulk_update_users))
         .route(
-            "/api/admin/users/:id",
-            put(admin_update_user).delete(admin_delete_user),
+            "/admin/users/:id",
+            get(admin_get_user).put(admin_update_user).delete(admin_delete_user),
         )
-        .route("/api/admin/users/:id/role", put(admin_update_role))
-        .layer(middleware::from_fn(require_admin));
+        .route("/admin/users/:id/role", put(admin_update_role))
+        .route("/admin/users/:id/deactivate", put(admin_deactivate_user))
+        .route("/admin/users/:id/activate", put(admin_activate_user))
+        .route("/admin/users/:id/logins", get(admin_user_logins))
+        .route("/admin/users/:id/tasks", get(admin_user_tasks))
+        .route("/admin/users/:id/anonymize", post(admin_anonymize_user))
+        .route("/admin/users/:id/export", get(admin_export_user_data))
+        .route("/admin/integrity", get(admin_integrity_report))
+        .route("/admin/integrity/repair", post(admin_integrity_repair))
+        .route("/admin/invites", get(admin_list_invites).post(admin_create_invite))
+        .route("/admin/invites/:id", get(admin_get_invite))
+        .route("/admin/webhooks", get(admin_list_webhooks).post(admin_create_webhook))
+        .route("/admin/webhooks/:id", get(admin_get_webhook).delete(admin_delete_webhook))
+        .route("/admin/webhooks/:id/test", post(admin_test_webhook))
+        .route("/admin/statuses", get(admin_list_statuses).post(admin_create_status))
+        .route(
+            "/admin/statuses/:key",
+            get(admin_get_status).put(admin_update_status).delete(admin_delete_status),
+        );
+
+    // Applied per method so reads stay open while writes need the permission.
+    let cti_write = middleware::from_fn_with_state(CTI_WRITE, require_permission);
+    // Only acts on GET, so it can wrap a whole method router.
+    let etag = middleware::from_fn(etag);
+
+    // Room for a note's text plus every file it may carry.
+    let note_body_limit = state
+        .config
+        .max_body_bytes
+        .saturating_add(state.config.note_attachments_max.saturating_mul(state.config.note_attachment_max_bytes));
+
+    // Everything that lives in a workspace; see `require_workspace`.
+    let workspace_routes = Router::new()
+        .route("/dashboard", get(get_dashboard))
+        .route("/dashboard/me", get(get_my_work))
+        .route("/dashboard/timeseries", get(get_timeseries))
+        .route("/reports/cti", get(cti_report))
+        .route("/reports/stale", get(stale_report))
+        .route("/reports/unassigned", get(unassigned_report))
+        .route("/graphql", post(graphql_handler))
+        .route("/tasks", get(list_tasks).head(head_tasks).post(create_task).layer(etag.clone()))
+        .route("/tasks/count", get(count_tasks))
+        .route("/search", get(search))
+        .route("/users", get(list_users).layer(etag.clone()))
+        .route("/tasks/board", get(task_board))
+        .route("/tasks/:id", get(get_task).put(update_task).delete(delete_task))
+        .route("/tasks/:id/assign", post(assign_task))
+        .route("/tasks/:id/claim", post(claim_task))
+        .route("/tasks/:id/unassign", post(unassign_task))
+        .route("/tasks/:id/notes", post(add_note).layer(DefaultBodyLimit::max(note_body_limit)))
+        .route("/tasks/:id/attachments/:attachment_id", get(download_attachment))
+        .route("/tasks/:id/notes/:note_id", delete(delete_note))
+        .route("/tasks/:id/external-links", post(add_external_link))
+        .route("/tasks/:id/external-links/:link_id", delete(delete_external_link))
+        .route("/tasks/:id/export", get(export_task))
+        .route("/tasks/:id/revisions", get(list_task_revisions))
+        .route("/tasks/:id/revisions/:rev_id/restore", post(restore_task_revision))
+        .route("/tasks/:id/share", post(create_task_share))
+        .route("/tasks/:id/share/:share_id", delete(revoke_task_share))
+        .route("/teams", get(list_teams))
+        .route("/admin/teams", post(admin_create_team))
+        .route("/admin/teams/:id", get(admin_get_team).put(admin_update_team).delete(admin_delete_team))
+        .route("/admin/teams/:id/members", post(admin_add_team_member))
+        .route("/admin/teams/:id/members/:user_id", delete(admin_remove_team_member))
+        .route("/views", get(list_views).post(create_view))
+        .route("/views/:id", get(get_view).put(update_view).delete(delete_view))
+        .route("/cti/categories", post(create_category).layer(cti_write.clone()).get(list_categories).layer(etag.clone()))
+        .route("/cti/categories/:id", delete(delete_category).layer(cti_write.clone()).get(get_category))
+        .route("/cti/types", post(create_type).layer(cti_write.clone()).get(list_types).layer(etag.clone()))
+        .route("/cti/types/:id", delete(delete_type).layer(cti_write.clone()).get(get_type))
+        .route("/cti/items", post(create_item).layer(cti_write.clone()).get(list_items).layer(etag))
+        .route("/cti/items/:id", delete(delete_item).layer(cti_write).get(get_item))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_workspace));
 
     let protected_routes = Router::new()
-        .route("/api/auth/me", get(me))
-        .route("/api/dashboard", get(get_dashboard))
-        .route("/api/users", get(list_users))
-        .route("/api/tasks", get(list_tasks).post(create_task))
-        .route("/api/tasks/:id", get(get_task).put(update_task).delete(delete_task))
-        .route("/api/tasks/:id/notes", post(add_note))
-        .route("/api/tasks/:id/notes/:note_id", delete(delete_note))
-        .route("/api/cti/categories", get(list_categories).post(create_category))
-        .route("/api/cti/categories/:id", delete(delete_category))
-        .route("/api/cti/types", get(list_types).post(create_type))
-        .route("/api/cti/types/:id", delete(delete_type))
-        .route("/api/cti/items", get(list_items).post(create_item))
-        .route("/api/cti/items/:id", delete(delete_item))
-        .route("/api/feeds", get(list_feeds).post(add_feed))
-        .route("/api/feeds/:id", delete(delete_feed))
-        .route("/api/feeds/:id/items", get(get_feed_items))
-        .route("/api/weather/locations", get(list_weather_locations).post(create_weather_location))
-        .route("/api/weather/locations/:id", delete(delete_weather_location))
-        .route("/api/weather/locations/:id/alerts", get(get_location_alerts))
-        .route("/api/weather/locations/:id/observations", get(get_location_observations))
-        .route("/api/weather/poll", post(trigger_weather_poll))
-        .route("/api/ca/health", get(ca_health))
-        .route("/api/ca/roots", get(ca_roots))
-        .route("/api/ca/crl", get(ca_crl))
-        .route("/api/ca/provisioners", get(ca_provisioners))
-        .route("/api/ca/cert-status", get(ca_cert_status))
+        .route("/auth/me", get(me))
+        .route("/auth/me/logins", get(my_logins))
+        .route("/auth/me/preferences", get(get_preferences).put(update_preferences))
+        .route("/auth/session", post(create_session))
+        .route("/auth/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/auth/api-keys/:id", get(get_api_key).delete(delete_api_key))
+        .route("/notifications", get(list_notifications))
+        .route("/notifications/read-all", post(mark_all_notifications_read))
+        .route("/notifications/digest/preview", get(digest_preview))
+        .route("/notifications/:id/read", post(mark_notification_read))
+        .route("/statuses", get(list_statuses))
+        .route("/features", get(list_features))
+        .route("/workspaces", get(list_workspaces).post(create_workspace))
+        .route("/workspaces/:id", get(get_workspace))
+        .route("/workspaces/:id/switch", post(switch_workspace))
+        .route("/workspaces/:id/members", get(list_members).post(add_member))
+        .route("/workspaces/:id/members/:user_id", delete(remove_member))
+        .route("/feeds", get(list_feeds).post(add_feed))
+        .route("/feeds/:id", get(get_feed).delete(delete_feed))
+        .route("/feeds/:id/items", get(get_feed_items))
+        .route("/weather/locations", get(list_weather_locations).post(create_weather_location))
+        .route("/weather/locations/:id", get(get_weather_location).delete(delete_weather_location))
+        .route("/weather/locations/:id/alerts", get(get_location_alerts))
+        .route("/weather/locations/:id/observations", get(get_location_observations))
+        .route("/weather/poll", post(trigger_weather_poll))
+        .route("/ca/health", get(ca_health))
+        .route("/ca/roots", get(ca_roots))
+        .route("/ca/crl", get(ca_crl))
+        .route("/ca/provisioners", get(ca_provisioners))
+        .route("/ca/cert-status", get(ca_cert_status))
+        .merge(workspace_routes)
         .merge(admin_routes)
+        .layer(middleware::from_fn(record_user))
         .layer(middleware::from_fn_with_state(state.clone(), require_auth));
 
-    Router::new()
-        .merge(health_route)
-        .merge(protected_routes)
-        .layer(PropagateRequestIdLayer::new(x_correlation_id.clone()))
-        .layer(SetRequestIdLayer::new(x_correlation_id, AlwaysMakeRequestUuid))
+    let api_v1 = Router::new().merge(public_auth_routes).merge(shared_routes).merge(protected_routes);
+
+    // Backups and exports stream for as long as the data takes, so these skip
+    // the request timeout. The restore body is capped by `RESTORE_MAX_BYTES`
+    // as it is read.
+    let untimed_v1 = Router::new()
+        .route("/admin/backup", get(admin_backup))
+        .route("/admin/restore", post(admin_restore))
+        .route("/admin/cache/cti/refresh", post(admin_refresh_cti_cache))
+        .merge(
+            Router::new()
+                .route("/tasks/stream", get(stream_tasks))
+                .route_layer(middleware::from_fn_with_state(state.clone(), require_workspace)),
+        )
+        .layer(middleware::from_fn(record_user))
+        .layer(middleware::from_fn_with_state(state.clone(), require_auth));
+
+    let routes = Router::new().merge(health_route).merge(mount_api(api_v1));
+    let config = state.config.clone();
+    with_middleware(routes, mount_api(untimed_v1), &config, state)
+}
+
+/// Wraps the routes in the layers every request passes through, adds the JSON
+/// 404/405 fallbacks, and applies `state`. `untimed` routes get every layer
+/// except the body limit and the request timeout.
+fn with_middleware<S: Clone + Send + Sync + 'static>(
+    routes: Router<S>,
+    untimed: Router<S>,
+    config: &AppConfig,
+    state: S,
+) -> Router {
+    let app = routes
+        .fallback(route_not_found)
+        // Routes that need more (e.g. uploads) can override with their own
+        // `DefaultBodyLimit::max(..)` route layer.
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(middleware::from_fn(payload_too_large_as_json))
+        .layer(middleware::from_fn_with_state(
+            Duration::from_secs(config.request_timeout_seconds),
+            request_timeout,
+        ))
+        .merge(untimed)
+        // gzip or brotli, as the client's Accept-Encoding allows.
+        .layer(CompressionLayer::new())
+        // Inside CORS and tracing so a panic's 500 gets the same headers and
+        // access log line as any other response.
+        .layer(CatchPanicLayer::custom(panic_as_json))
+        .layer(middleware::from_fn_with_state(config.db_ops_budget, db_ops_budget))
+        // Outside the panic handler so a panic's 500 is counted too.
+        .layer(middleware::from_fn(track_requests))
         .layer(
             CorsLayer::new()
                 .allow_origin(
-                    state.config.frontend_origin
+                    // Validated by `AppConfig::from_env`.
+                    config.frontend_origin
                         .parse::<HeaderValue>()
                         .expect("Invalid FRONTEND_ORIGIN"),
                 )
                 .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-                .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]),
+                .allow_headers([
+                    header::AUTHORIZATION,
+                    header::CONTENT_TYPE,
+                    HeaderName::from_static(CSRF_HEADER),
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                    HeaderName::from_static(WORKSPACE_HEADER),
+                ])
+                .expose_headers([
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                    HeaderName::from_static(TOTAL_COUNT_HEADER),
+                    HeaderName::from_static(DB_OPS_HEADER),
+                ])
+                .allow_credentials(config.auth_cookie_mode),
         )
-        .layer(TraceLayer::new_for_http())
-        .with_state(state)
+        .layer(
+            TraceLayer::new_for_http().make_span_with(request_span).on_response(record_response),
+        )
+        .layer(middleware::from_fn(request_id))
+        .with_state(state);
+
+    // Outside the router so the 405's `Allow` header has been filled in.
+    Router::new()
+        .fallback_service(app)
+        .layer(middleware::from_fn(method_not_allowed_as_json))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(config.security_headers.clone()),
+            security_headers,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::{HeaderMap, Request, StatusCode}};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let v1 = Router::new()
+            .route("/tasks", get(|| async { "v1 tasks" }))
+            .route("/tasks/:id", get(|axum::extract::Path(id): axum::extract::Path<String>| async move { id }));
+        let v2 = Router::new().route("/tasks", get(|| async { "v2 tasks" }));
+        mount_api(v1).nest("/api/v2", v2)
+    }
+
+    async fn get_path(path: &str) -> (StatusCode, HeaderMap, String) {
+        let resp = app()
+            .oneshot(Request::builder().uri(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let (parts, body) = resp.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (parts.status, parts.headers, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn both_prefixes_reach_v1_handlers() {
+        let (status, headers, body) = get_path("/api/v1/tasks/t1").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "t1"));
+        assert!(headers.get("deprecation").is_none());
+
+        let (status, headers, body) = get_path("/api/tasks/t1").await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "t1"));
+        assert_eq!(headers["deprecation"], "true");
+        assert!(headers.contains_key("sunset"));
+        assert_eq!(headers["link"], "</api/v1/tasks/t1>; rel=\"successor-version\"");
+    }
+
+    async fn call_with_middleware(method: &str, path: &str) -> (StatusCode, HeaderMap, serde_json::Value) {
+        let config = AppConfig::for_tests();
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::ORIGIN, config.frontend_origin.as_str())
+            .body(Body::empty())
+            .unwrap();
+        let resp = with_middleware(app(), Router::new(), &config, ()).oneshot(req).await.unwrap();
+        let (parts, body) = resp.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (parts.status, parts.headers, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn unknown_paths_get_the_json_envelope() {
+        for path in ["/api/taskz", "/api/v1/taskz", "/nowhere"] {
+            let (status, headers, body) = call_with_middleware("GET", path).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{path}");
+            assert_eq!(body, serde_json::json!({ "error": "not found", "code": "route_not_found" }));
+            assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "http://localhost:3000");
+            assert!(headers.contains_key(REQUEST_ID_HEADER));
+        }
+    }
+
+    #[tokio::test]
+    async fn wrong_method_gets_the_json_envelope() {
+        let (status, headers, body) = call_with_middleware("DELETE", "/api/v1/tasks").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["code"], "method_not_allowed");
+        assert_eq!(body["allowed"], serde_json::json!(["GET", "HEAD"]));
+        assert_eq!(headers[header::ALLOW], "GET,HEAD");
+        assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "http://localhost:3000");
+        assert!(headers.contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[tokio::test]
+    async fn security_headers_are_on_success_and_error_responses() {
+        for (method, path, status) in [
+            ("GET", "/api/v1/tasks", StatusCode::OK),
+            ("GET", "/api/taskz", StatusCode::NOT_FOUND),
+            ("DELETE", "/api/v1/tasks", StatusCode::METHOD_NOT_ALLOWED),
+        ] {
+            let (got, headers, _) = call_with_middleware(method, path).await;
+            assert_eq!(got, status, "{path}");
+            assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+            assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+            assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+            assert!(headers.contains_key("permissions-policy"));
+            assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY), "HSTS is off by default");
+            assert!(!headers.contains_key(header::CACHE_CONTROL));
+        }
+        let (_, headers, _) = call_with_middleware("GET", "/api/v1/auth/me").await;
+        assert_eq!(headers[header::CACHE_CONTROL], "no-store");
+    }
+
+    #[tokio::test]
+    async fn later_versions_leave_v1_alone() {
+        assert_eq!(get_path("/api/v2/tasks").await.2, "v2 tasks");
+        assert_eq!(get_path("/api/v1/tasks").await.2, "v1 tasks");
+        assert_eq!(get_path("/api/tasks").await.2, "v1 tasks");
+        assert_eq!(get_path("/api/v2/tasks/t1").await.0, StatusCode::NOT_FOUND);
+    }
 }
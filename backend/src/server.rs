@@ -0,0 +1,285 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::AppConfig;
+
+/// Where the HTTP server accepts connections, chosen from `BIND_ADDR`,
+/// `TLS_CERT_PATH`/`TLS_KEY_PATH` and `UNIX_SOCKET_PATH`.
+pub enum Listener {
+    Tcp(SocketAddr),
+    Tls(SocketAddr, RustlsConfig),
+    Unix(PathBuf),
+}
+
+impl Listener {
+    /// Resolves the listener and loads any certificate up front, so a bad
+    /// path or PEM file stops startup before MongoDB and Keycloak are touched.
+    pub async fn from_config(config: &AppConfig) -> Result<Self> {
+        if let Some(path) = &config.unix_socket_path {
+            return Ok(Self::Unix(path.clone()));
+        }
+        match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert), Some(key)) => Ok(Self::Tls(config.bind_addr, load_tls(cert, key).await?)),
+            _ => Ok(Self::Tcp(config.bind_addr)),
+        }
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "http://{addr} (TCP)"),
+            Self::Tls(addr, _) => write!(f, "https://{addr} (TLS)"),
+            Self::Unix(path) => write!(f, "unix:{} (Unix socket)", path.display()),
+        }
+    }
+}
+
+async fn load_tls(cert: &Path, key: &Path) -> Result<RustlsConfig> {
+    // Only ring is compiled in, but rustls still wants it chosen explicitly.
+    // An error here just means it was already installed.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let cert_pem = tokio::fs::read(cert)
+        .await
+        .with_context(|| format!("Could not read TLS_CERT_PATH '{}'", cert.display()))?;
+    let key_pem = tokio::fs::read(key)
+        .await
+        .with_context(|| format!("Could not read TLS_KEY_PATH '{}'", key.display()))?;
+    RustlsConfig::from_pem(cert_pem, key_pem).await.with_context(|| {
+        format!(
+            "Could not load the TLS certificate '{}' and key '{}'; both must be PEM and belong together",
+            cert.display(),
+            key.display()
+        )
+    })
+}
+
+/// Serves `app` until `shutdown` is cancelled, then lets in-flight requests
+/// finish for up to `drain` before dropping what is left.
+pub async fn serve(listener: Listener, app: Router, shutdown: CancellationToken, drain: Duration) -> Result<()> {
+    let server = async {
+        match listener {
+            Listener::Tcp(addr) => {
+                let tcp = tokio::net::TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Could not bind {addr}"))?;
+                axum::serve(tcp, app.into_make_service_with_connect_info::<SocketAddr>())
+                    .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                    .await?;
+            }
+            Listener::Tls(addr, tls) => {
+                let handle = axum_server::Handle::new();
+                let shutdown_handle = handle.clone();
+                let cancelled = shutdown.clone();
+                tokio::spawn(async move {
+                    cancelled.cancelled().await;
+                    shutdown_handle.graceful_shutdown(Some(drain));
+                });
+                axum_server::bind_rustls(addr, tls)
+                    .handle(handle)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
+                    .with_context(|| format!("Could not serve TLS on {addr}"))?;
+            }
+            #[cfg(unix)]
+            Listener::Unix(path) => unix::serve(&path, app, shutdown.clone()).await?,
+            #[cfg(not(unix))]
+            Listener::Unix(_) => anyhow::bail!("UNIX_SOCKET_PATH is only supported on Unix"),
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    // Graceful shutdown waits for every open connection; cap that wait so a
+    // stuck client cannot hold up a deploy.
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(drain).await;
+        } => tracing::warn!("Drain timeout of {drain:?} elapsed; dropping remaining connections"),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        os::unix::fs::FileTypeExt,
+        path::{Path, PathBuf},
+    };
+
+    use anyhow::{bail, Context, Result};
+    use axum::Router;
+    use hyper_util::{
+        rt::{TokioExecutor, TokioIo},
+        server::{conn::auto, graceful::GracefulShutdown},
+        service::TowerToHyperService,
+    };
+    use tokio::net::UnixListener;
+    use tokio_util::sync::CancellationToken;
+
+    /// Removes the socket file when serving stops, however it stops.
+    struct SocketFile(PathBuf);
+
+    impl Drop for SocketFile {
+        fn drop(&mut self) {
+            if let Err(e) = std::fs::remove_file(&self.0) {
+                tracing::warn!("Could not remove Unix socket '{}': {e}", self.0.display());
+            }
+        }
+    }
+
+    /// Clears a socket left behind by a process that died without cleaning
+    /// up. Refuses to touch anything that is not a socket, or a socket that
+    /// another process is still accepting on.
+    pub(super) fn remove_stale_socket(path: &Path) -> Result<()> {
+        let meta = match std::fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("Could not inspect '{}'", path.display())),
+        };
+        if !meta.file_type().is_socket() {
+            bail!("UNIX_SOCKET_PATH '{}' exists and is not a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            bail!("UNIX_SOCKET_PATH '{}' is in use by another process", path.display());
+        }
+        tracing::info!("Removing stale Unix socket '{}'", path.display());
+        std::fs::remove_file(path).with_context(|| format!("Could not remove stale socket '{}'", path.display()))
+    }
+
+    pub(super) async fn serve(path: &Path, app: Router, shutdown: CancellationToken) -> Result<()> {
+        remove_stale_socket(path)?;
+        let listener =
+            UnixListener::bind(path).with_context(|| format!("Could not bind Unix socket '{}'", path.display()))?;
+        let _socket = SocketFile(path.to_path_buf());
+
+        let graceful = GracefulShutdown::new();
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!("Unix socket accept failed: {e}");
+                        continue;
+                    }
+                },
+                _ = shutdown.cancelled() => break,
+            };
+            let conn = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(app.clone()))
+                .into_owned();
+            let conn = graceful.watch(conn);
+            tokio::spawn(async move {
+                if let Err(e) = conn.await {
+                    tracing::debug!("Unix socket connection closed with an error: {e}");
+                }
+            });
+        }
+        drop(listener);
+        graceful.shutdown().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("missioncontrol-{}-{name}", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn unreadable_certificates_fail_with_the_variable_name() {
+        let mut config = AppConfig::for_tests();
+        config.tls_cert_path = Some(temp_path("missing-cert.pem"));
+        config.tls_key_path = Some(temp_path("missing-key.pem"));
+        let err = Listener::from_config(&config).await.err().unwrap();
+        assert!(err.to_string().starts_with("Could not read TLS_CERT_PATH"), "{err}");
+
+        let cert = temp_path("garbage-cert.pem");
+        std::fs::write(&cert, "not a certificate").unwrap();
+        config.tls_cert_path = Some(cert.clone());
+        config.tls_key_path = Some(cert.clone());
+        let err = Listener::from_config(&config).await.err().unwrap();
+        assert!(err.to_string().starts_with("Could not load the TLS certificate"), "{err}");
+        std::fs::remove_file(cert).unwrap();
+    }
+
+    #[tokio::test]
+    async fn listener_mode_follows_the_config() {
+        let mut config = AppConfig::for_tests();
+        assert_eq!(Listener::from_config(&config).await.unwrap().to_string(), "http://0.0.0.0:8080 (TCP)");
+        config.unix_socket_path = Some("/run/mc.sock".into());
+        assert_eq!(Listener::from_config(&config).await.unwrap().to_string(), "unix:/run/mc.sock (Unix socket)");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_dead_sockets_are_removed() {
+        let path = temp_path("stale.sock");
+        let _ = std::fs::remove_file(&path);
+        unix::remove_stale_socket(&path).unwrap();
+
+        // A bound listener that has been dropped leaves a dead socket file.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        unix::remove_stale_socket(&path).unwrap();
+        assert!(!path.exists());
+
+        let live = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let err = unix::remove_stale_socket(&path).unwrap_err();
+        assert!(err.to_string().contains("in use"), "{err}");
+        drop(live);
+        std::fs::remove_file(&path).unwrap();
+
+        std::fs::write(&path, "data").unwrap();
+        let err = unix::remove_stale_socket(&path).unwrap_err();
+        assert!(err.to_string().contains("not a socket"), "{err}");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_over_a_unix_socket_and_cleans_up_on_shutdown() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let path = temp_path("serve.sock");
+        let app = Router::new().route("/health", axum::routing::get(|| async { "ok" }));
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(serve(
+            Listener::Unix(path.clone()),
+            app,
+            shutdown.clone(),
+            Duration::from_secs(5),
+        ));
+
+        let mut stream = loop {
+            match tokio::net::UnixStream::connect(&path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("ok"), "{response}");
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+}
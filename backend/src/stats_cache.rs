@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{Mutex, RwLock};
+
+/// Holds one lazily computed value for up to `ttl`.
+///
+/// Readers share the cached value; when it expires, the first caller
+/// recomputes it while later callers wait on `refresh` and then reuse that
+/// result, so a burst of requests at expiry triggers a single recompute.
+#[derive(Clone)]
+pub struct StatsCache<T> {
+    ttl: Duration,
+    value: Arc<RwLock<Option<(Instant, T)>>>,
+    refresh: Arc<Mutex<()>>,
+}
+
+impl<T: Clone> StatsCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            value: Arc::new(RwLock::new(None)),
+            refresh: Arc::new(Mutex::new(())),
+        }
+    }
+
+    async fn fresh_value(&self, newer_than: Option<Instant>) -> Option<T> {
+        let value = self.value.read().await;
+        value
+            .as_ref()
+            .filter(|(at, _)| at.elapsed() < self.ttl && newer_than.is_none_or(|t| *at >= t))
+            .map(|(_, v)| v.clone())
+    }
+
+    /// Returns the cached value, computing it with `compute` if it is missing
+    /// or expired. `force` skips the cache (but still single-flights).
+    pub async fn get_or_refresh<F, Fut, E>(&self, force: bool, compute: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let requested_at = Instant::now();
+        if !force {
+            if let Some(v) = self.fresh_value(None).await {
+                return Ok(v);
+            }
+        }
+
+        let _guard = self.refresh.lock().await;
+        // Someone else may have refreshed while we waited for the lock. A
+        // forced refresh only accepts a value computed after it was asked for.
+        let newer_than = force.then_some(requested_at);
+        if let Some(v) = self.fresh_value(newer_than).await {
+            return Ok(v);
+        }
+
+        let computed = compute().await?;
+        *self.value.write().await = Some((Instant::now(), computed.clone()));
+        Ok(computed)
+    }
+}
+
+/// One `StatsCache` per key (e.g. per workspace), each with its own TTL
+/// clock and single-flight refresh. Keys are never evicted, so use it only
+/// for small key sets.
+#[derive(Clone)]
+pub struct KeyedStatsCache<T> {
+    ttl: Duration,
+    caches: Arc<std::sync::Mutex<HashMap<String, StatsCache<T>>>>,
+}
+
+impl<T: Clone> KeyedStatsCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, caches: Arc::new(std::sync::Mutex::new(HashMap::new())) }
+    }
+
+    /// `StatsCache::get_or_refresh` on the cache for `key`.
+    pub async fn get_or_refresh<F, Fut, E>(&self, key: &str, force: bool, compute: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let cache = {
+            let mut caches = self.caches.lock().unwrap_or_else(|e| e.into_inner());
+            caches.entry(key.to_string()).or_insert_with(|| StatsCache::new(self.ttl)).clone()
+        };
+        cache.get_or_refresh(force, compute).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn slow_count(calls: &AtomicUsize) -> Result<usize, ()> {
+        let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Ok(n)
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_compute_once() {
+        let cache = StatsCache::new(Duration::from_secs(30));
+        let calls = AtomicUsize::new(0);
+        let (a, b, c) = tokio::join!(
+            cache.get_or_refresh(false, || slow_count(&calls)),
+            cache.get_or_refresh(false, || slow_count(&calls)),
+            cache.get_or_refresh(false, || slow_count(&calls)),
+        );
+        assert_eq!((a, b, c), (Ok(1), Ok(1), Ok(1)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_value_is_recomputed() {
+        let cache = StatsCache::new(Duration::from_millis(0));
+        let calls = AtomicUsize::new(0);
+        assert_eq!(cache.get_or_refresh(false, || slow_count(&calls)).await, Ok(1));
+        assert_eq!(cache.get_or_refresh(false, || slow_count(&calls)).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn force_bypasses_a_fresh_value() {
+        let cache = StatsCache::new(Duration::from_secs(30));
+        let calls = AtomicUsize::new(0);
+        assert_eq!(cache.get_or_refresh(false, || slow_count(&calls)).await, Ok(1));
+        assert_eq!(cache.get_or_refresh(false, || slow_count(&calls)).await, Ok(1));
+        assert_eq!(cache.get_or_refresh(true, || slow_count(&calls)).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn errors_are_not_cached() {
+        let cache: StatsCache<usize> = StatsCache::new(Duration::from_secs(30));
+        assert_eq!(cache.get_or_refresh(false, || async { Err::<usize, _>("down") }).await, Err("down"));
+        assert_eq!(cache.get_or_refresh(false, || async { Ok::<_, &str>(7) }).await, Ok(7));
+    }
+
+    #[tokio::test]
+    async fn keyed_caches_are_independent() {
+        let cache = KeyedStatsCache::new(Duration::from_secs(30));
+        let calls = AtomicUsize::new(0);
+        assert_eq!(cache.get_or_refresh("a", false, || slow_count(&calls)).await, Ok(1));
+        assert_eq!(cache.get_or_refresh("b", false, || slow_count(&calls)).await, Ok(2));
+        assert_eq!(cache.get_or_refresh("a", false, || slow_count(&calls)).await, Ok(1));
+    }
+}
@@ -0,0 +1,142 @@
+//! Optional OpenTelemetry trace export over OTLP/HTTP.
+//!
+//! Configured with the standard `OTEL_*` variables, which the SDK and
+//! exporter read themselves: `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`), `OTEL_EXPORTER_OTLP_HEADERS`,
+//! `OTEL_SERVICE_NAME`, `OTEL_TRACES_SAMPLER` and friends. With no endpoint
+//! set, or `OTEL_SDK_DISABLED=true`, no layer is installed and request
+//! spans skip the propagation work entirely.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::http::HeaderMap;
+use opentelemetry::{propagation::{Extractor, TextMapPropagator}, trace::TracerProvider};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider},
+    Resource,
+};
+use tracing::Span;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+const ENDPOINT_VARS: &[&str] = &["OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "OTEL_EXPORTER_OTLP_ENDPOINT"];
+
+/// Used when `OTEL_SERVICE_NAME` is unset.
+const SERVICE_NAME: &str = "missioncontrol";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether spans are being exported.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The configured trace endpoint, unless export is off.
+fn endpoint(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    if var("OTEL_SDK_DISABLED").is_some_and(|v| v.trim().eq_ignore_ascii_case("true")) {
+        return None;
+    }
+    if var("OTEL_TRACES_EXPORTER").is_some_and(|v| v.trim() == "none") {
+        return None;
+    }
+    ENDPOINT_VARS.iter().filter_map(|name| var(name)).find(|v| !v.trim().is_empty())
+}
+
+/// Builds the exporting provider if an endpoint is configured. The caller
+/// keeps it for `shutdown`, which flushes spans still queued.
+pub fn provider_from_env() -> anyhow::Result<Option<SdkTracerProvider>> {
+    if endpoint(|name| std::env::var(name).ok()).is_none() {
+        return Ok(None);
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder().with_http().build()?;
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(SERVICE_NAME);
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(Some(provider))
+}
+
+/// The subscriber layer that turns `tracing` spans into OpenTelemetry spans.
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+}
+
+/// Flushes and stops the exporter. Blocks, so run it off the async workers.
+pub async fn shutdown(provider: SdkTracerProvider) {
+    let result = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+    if let Ok(Err(e)) = result {
+        tracing::warn!("Could not flush OpenTelemetry spans: {e}");
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Makes `span` a child of the caller's trace when the request carries a
+/// W3C `traceparent`, so our spans join the gateway's.
+pub fn join_remote_trace(span: &Span, headers: &HeaderMap) {
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    // Fails only if the span is already closed or has children.
+    let _ = span.set_parent(parent);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceContextExt;
+
+    fn vars<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| pairs.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn export_needs_an_endpoint() {
+        assert_eq!(endpoint(vars(&[])), None);
+        assert_eq!(endpoint(vars(&[("OTEL_EXPORTER_OTLP_ENDPOINT", " ")])), None);
+        assert_eq!(
+            endpoint(vars(&[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://tempo:4318")])).as_deref(),
+            Some("http://tempo:4318")
+        );
+        let both = [
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://tempo:4318"),
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "http://jaeger:4318/v1/traces"),
+        ];
+        assert_eq!(endpoint(vars(&both)).as_deref(), Some("http://jaeger:4318/v1/traces"));
+    }
+
+    #[test]
+    fn export_can_be_switched_off() {
+        let on = ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://tempo:4318");
+        assert_eq!(endpoint(vars(&[on, ("OTEL_SDK_DISABLED", "TRUE")])), None);
+        assert_eq!(endpoint(vars(&[on, ("OTEL_TRACES_EXPORTER", "none")])), None);
+        assert!(endpoint(vars(&[on, ("OTEL_SDK_DISABLED", "false")])).is_some());
+    }
+
+    #[test]
+    fn reads_the_w3c_traceparent() {
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".parse().unwrap());
+        let cx = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+        let remote = cx.span().span_context().clone();
+        assert!(remote.is_remote());
+        assert_eq!(remote.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bson::{doc, Document};
+use mongodb::options::FindOneOptions;
+use tokio::sync::RwLock;
+
+use crate::db::{Db, USERS};
+
+/// The subset of a stored user that `require_auth` re-checks on every request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserStatus {
+    pub role: String,
+    pub active: bool,
+}
+
+type Entry = (Instant, Option<UserStatus>);
+
+/// Short-lived, in-process cache of `UserStatus` keyed by user id.
+///
+/// `None` entries are cached too: they record that the user has no local
+/// record, either not provisioned yet (no `/api/auth/me` call so far) or
+/// deleted.
+#[derive(Clone)]
+pub struct UserStatusCache {
+    ttl: Duration,
+    entries: Arc<RwLock<HashMap<String, Entry>>>,
+}
+
+impl UserStatusCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the cached status if present and younger than the TTL.
+    /// The outer `Option` is the cache hit, the inner one the lookup result.
+    pub async fn get(&self, user_id: &str) -> Option<Option<UserStatus>> {
+        let entries = self.entries.read().await;
+        entries
+            .get(user_id)
+            .filter(|(at, _)| at.elapsed() < self.ttl)
+            .map(|(_, status)| status.clone())
+    }
+
+    pub async fn insert(&self, user_id: &str, status: Option<UserStatus>) {
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        entries.insert(user_id.to_string(), (Instant::now(), status));
+    }
+
+    pub async fn invalidate(&self, user_id: &str) {
+        self.entries.write().await.remove(user_id);
+    }
+
+    /// Cached lookup, falling back to MongoDB on a miss or expired entry.
+    pub async fn load(
+        &self,
+        db: &Db,
+        user_id: &str,
+    ) -> Result<Option<UserStatus>, mongodb::error::Error> {
+        if let Some(status) = self.get(user_id).await {
+            return Ok(status);
+        }
+
+        let options = FindOneOptions::builder()
+            .projection(doc! { "role": 1, "active": 1 })
+            .build();
+        let status = db
+            .collection::<Document>(USERS)
+            .find_one(doc! { "_id": user_id }, options)
+            .await?
+            .map(|d| UserStatus {
+                role: d.get_str("role").unwrap_or("user").to_string(),
+                active: d.get_bool("active").unwrap_or(true),
+            });
+
+        self.insert(user_id, status.clone()).await;
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin() -> Option<UserStatus> {
+        Some(UserStatus { role: "admin".to_string(), active: true })
+    }
+
+    #[tokio::test]
+    async fn fresh_entry_is_returned() {
+        let cache = UserStatusCache::new(Duration::from_secs(30));
+        cache.insert("u1", admin()).await;
+        assert_eq!(cache.get("u1").await, Some(admin()));
+    }
+
+    #[tokio::test]
+    async fn unprovisioned_user_is_cached_as_none() {
+        let cache = UserStatusCache::new(Duration::from_secs(30));
+        cache.insert("u1", None).await;
+        assert_eq!(cache.get("u1").await, Some(None));
+    }
+
+    #[tokio::test]
+    async fn expired_entry_is_a_miss() {
+        let cache = UserStatusCache::new(Duration::ZERO);
+        cache.insert("u1", admin()).await;
+        assert_eq!(cache.get("u1").await, None);
+    }
+
+    #[tokio::test]
+    async fn invalidate_removes_entry() {
+        let cache = UserStatusCache::new(Duration::from_secs(30));
+        cache.insert("u1", admin()).await;
+        cache.invalidate("u1").await;
+        assert_eq!(cache.get("u1").await, None);
+    }
+}
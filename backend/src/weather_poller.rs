@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use bson::doc;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use tokio::time::{Duration, interval};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     db::Db,
@@ -10,10 +11,23 @@ use crate::{
     nws_client::NwsClient,
 };
 
-pub async fn run_weather_poller(db: Db, nws_client: Arc<NwsClient>, interval_minutes: u64) {
+/// Polls every `interval_minutes` until `shutdown` is cancelled. A poll
+/// already in progress is allowed to finish.
+pub async fn run_weather_poller(
+    db: Db,
+    nws_client: Arc<NwsClient>,
+    interval_minutes: u64,
+    shutdown: CancellationToken,
+) {
     let mut ticker = interval(Duration::from_secs(interval_minutes * 60));
     loop {
-        ticker.tick().await;
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => {
+                tracing::info!("Weather poller stopped");
+                return;
+            }
+        }
         if let Err(e) = poll_all_locations(&db, &nws_client).await {
             tracing::error!("Weather poll cycle failed: {e:?}");
         }
@@ -103,29 +117,11 @@ async fn fetch_and_store_alerts(
     let collection = db.collection::<bson::Document>("weather_alerts");
 
     for alert in alerts {
-        let Some(nws_id) = alert.id else { continue };
-        let event = alert.event.unwrap_or_default();
-
-        let effective = parse_optional_datetime(alert.effective.as_deref());
-        let expires = parse_optional_datetime(alert.expires.as_deref());
-
-        let new_alert = WeatherAlert::new(
-            location_id.to_string(),
-            nws_id.clone(),
-            event,
-            alert.headline,
-            alert.description,
-            alert.severity,
-            alert.urgency,
-            alert.certainty,
-            effective,
-            expires,
-        );
-
+        let Some(new_alert) = WeatherAlert::from_nws(location_id, alert) else { continue };
         let alert_doc = bson::to_document(&new_alert)?;
         collection
             .update_one(
-                doc! { "nws_id": &nws_id },
+                doc! { "nws_id": &new_alert.nws_id },
                 doc! { "$setOnInsert": alert_doc },
                 mongodb::options::UpdateOptions::builder().upsert(true).build(),
             )
@@ -142,21 +138,7 @@ async fn fetch_and_store_observation(
 ) -> anyhow::Result<()> {
     let obs = nws_client.get_latest_observation(station_id).await?;
 
-    let timestamp = parse_optional_datetime(obs.timestamp.as_deref())
-        .unwrap_or_else(Utc::now);
-
-    let observation = WeatherObservation::new(
-        location_id.to_string(),
-        station_id.to_string(),
-        timestamp,
-        obs.temperature.and_then(|v| v.value),
-        obs.dewpoint.and_then(|v| v.value),
-        obs.wind_direction.and_then(|v| v.value),
-        obs.wind_speed.and_then(|v| v.value),
-        obs.barometric_pressure.and_then(|v| v.value),
-        obs.visibility.and_then(|v| v.value),
-        obs.text_description,
-    );
+    let observation = WeatherObservation::from_nws(location_id, station_id, obs);
 
     db.collection::<WeatherObservation>("weather_observations")
         .insert_one(&observation, None)
@@ -164,29 +146,3 @@ async fn fetch_and_store_observation(
 
     Ok(())
 }
-
-fn parse_optional_datetime(s: Option<&str>) -> Option<DateTime<Utc>> {
-    s.and_then(|s| DateTime::parse_from_rfc3339(s).ok())
-        .map(|dt| dt.with_timezone(&Utc))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn parse_optional_datetime_handles_valid_iso8601() {
-        let result = parse_optional_datetime(Some("2024-06-15T14:00:00+00:00"));
-        assert!(result.is_some());
-    }
-
-    #[test]
-    fn parse_optional_datetime_returns_none_for_none_input() {
-        assert!(parse_optional_datetime(None).is_none());
-    }
-
-    #[test]
-    fn parse_optional_datetime_returns_none_for_invalid() {
-        assert!(parse_optional_datetime(Some("not-a-date")).is_none());
-    }
-}
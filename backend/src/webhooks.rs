@@ -0,0 +1,266 @@
+//! Outbound webhooks. Task events arrive through the `Subscriber` impl (and
+//! test pings straight from `enqueue`) on a bounded channel; a background
+//! dispatcher looks up the subscribed webhooks and delivers each in its own
+//! task, retrying with exponential backoff.
+
+use axum::async_trait;
+use bson::{doc, to_bson};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::{sync::mpsc, time::Duration};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    db::{collect, Db, WEBHOOKS},
+    events::{DomainEvent, Subscriber},
+    models::{
+        task::Task,
+        webhook::{DeliveryStatus, Webhook, PING, TASK_ASSIGNED, TASK_CREATED, TASK_DONE},
+    },
+    retry::backoff,
+};
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+
+/// Events waiting for the dispatcher; beyond this, new events are dropped
+/// rather than slowing down the request that raised them.
+const QUEUE_CAPACITY: usize = 1024;
+/// Retries back off from here: 1 s, 2 s, 4 s, …
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One delivery's JSON body. `text` makes it readable as-is by Slack
+/// incoming webhooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub event: &'static str,
+    pub occurred_at: DateTime<Utc>,
+    pub text: String,
+    pub data: Value,
+}
+
+impl WebhookEvent {
+    fn new(event: &'static str, text: String, data: Value) -> Self {
+        Self { id: Uuid::new_v4().to_string(), event, occurred_at: Utc::now(), text, data }
+    }
+
+    pub fn task_created(task: &Task) -> Self {
+        Self::new(TASK_CREATED, format!("Task created: {}", task.title), json!({ "task": task }))
+    }
+
+    pub fn task_done(task: &Task) -> Self {
+        Self::new(TASK_DONE, format!("Task done: {}", task.title), json!({ "task": task }))
+    }
+
+    pub fn task_assigned(task: &Task) -> Self {
+        let text = match &task.assignee_id {
+            Some(_) => format!("Task assigned: {}", task.title),
+            None => format!("Task unassigned: {}", task.title),
+        };
+        Self::new(TASK_ASSIGNED, text, json!({ "task": task }))
+    }
+
+    pub fn ping(webhook_id: &str) -> Self {
+        Self::new(PING, "MissionControl webhook test".into(), json!({ "webhook_id": webhook_id }))
+    }
+}
+
+/// Cheap to clone; shared through `AppState`.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    tx: mpsc::Sender<WebhookEvent>,
+    http: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn channel(http: reqwest::Client) -> (Self, mpsc::Receiver<WebhookEvent>) {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        (Self { tx, http }, rx)
+    }
+
+    /// The client deliveries go through, for one-off sends such as tests.
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// Queues `event` without waiting; delivery failures never reach the caller.
+    pub fn enqueue(&self, event: WebhookEvent) {
+        if let Err(e) = self.tx.try_send(event) {
+            tracing::warn!("Dropped webhook event: {e}");
+        }
+    }
+}
+
+/// Turns task events into `task.created`, `task.done` and `task.assigned`
+/// deliveries.
+#[async_trait]
+impl Subscriber for WebhookDispatcher {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> anyhow::Result<()> {
+        match event {
+            DomainEvent::TaskCreated { task, .. } => self.enqueue(WebhookEvent::task_created(task)),
+            DomainEvent::TaskCompleted { task, .. } => self.enqueue(WebhookEvent::task_done(task)),
+            DomainEvent::UserAssigned { task, .. } => self.enqueue(WebhookEvent::task_assigned(task)),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// `sha256=<hex HMAC-SHA256 of body keyed with secret>`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Consumes queued events until `shutdown` is cancelled.
+pub async fn run_dispatcher(
+    db: Db,
+    http: reqwest::Client,
+    mut events: mpsc::Receiver<WebhookEvent>,
+    max_attempts: u32,
+    shutdown: CancellationToken,
+) {
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+            _ = shutdown.cancelled() => {
+                tracing::info!("Webhook dispatcher stopped");
+                return;
+            }
+        };
+        let webhooks = match subscribers(&db, event.event).await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                tracing::error!("Could not load webhooks for {}: {e:?}", event.event);
+                continue;
+            }
+        };
+        for webhook in webhooks {
+            let (db, http, event, shutdown) = (db.clone(), http.clone(), event.clone(), shutdown.clone());
+            tokio::spawn(async move {
+                let status = deliver(&http, &webhook, &event, max_attempts, &shutdown).await;
+                if let Err(e) = record(&db, &webhook.id, &status).await {
+                    tracing::warn!(webhook_id = %webhook.id, "Could not record webhook delivery: {e:?}");
+                }
+            });
+        }
+    }
+}
+
+async fn subscribers(db: &Db, event: &str) -> mongodb::error::Result<Vec<Webhook>> {
+    let cursor = db.collection::<Webhook>(WEBHOOKS).find(doc! { "enabled": true }, None).await?;
+    Ok(collect(cursor).await?.into_iter().filter(|w| w.wants(event)).collect())
+}
+
+/// Stores `status` as the webhook's `last_delivery`.
+pub async fn record(db: &Db, webhook_id: &str, status: &DeliveryStatus) -> anyhow::Result<()> {
+    db.collection::<Webhook>(WEBHOOKS)
+        .update_one(
+            doc! { "_id": webhook_id },
+            doc! { "$set": { "last_delivery": to_bson(status)? } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Posts `event` to `webhook`, retrying failures up to `max_attempts` in all.
+/// Stops early, reporting the failure so far, if `shutdown` is cancelled.
+pub async fn deliver(
+    http: &reqwest::Client,
+    webhook: &Webhook,
+    event: &WebhookEvent,
+    max_attempts: u32,
+    shutdown: &CancellationToken,
+) -> DeliveryStatus {
+    let body = serde_json::to_vec(event).unwrap_or_default();
+    let signature = signature(&webhook.secret, &body);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = http
+            .post(&webhook.url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event.event)
+            .body(body.clone())
+            .send()
+            .await;
+        let (status_code, error) = match result {
+            Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16()), None),
+            Ok(resp) => (Some(resp.status().as_u16()), Some(format!("HTTP {}", resp.status()))),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let done = error.is_none() || attempts >= max_attempts;
+        if !done {
+            tracing::debug!(webhook_id = %webhook.id, attempts, "Webhook delivery failed; retrying");
+            tokio::select! {
+                _ = tokio::time::sleep(backoff(FIRST_RETRY_DELAY, attempts)) => continue,
+                _ = shutdown.cancelled() => {}
+            }
+        }
+        if let Some(error) = &error {
+            tracing::warn!(webhook_id = %webhook.id, event = event.event, attempts, "Webhook delivery failed: {error}");
+        }
+        return DeliveryStatus {
+            event: event.event.to_string(),
+            delivered_at: Utc::now(),
+            ok: error.is_none(),
+            attempts,
+            status_code,
+            error,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{SystemClock, UuidIds};
+
+    #[test]
+    fn signature_is_hex_hmac_sha256() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn payload_carries_slack_text_and_task() {
+        let task = Task::new(&SystemClock, &UuidIds, "Patch the VPN".into(), "".into());
+        let json = serde_json::to_value(WebhookEvent::task_done(&task)).unwrap();
+        assert_eq!(json["event"], "task.done");
+        assert_eq!(json["text"], "Task done: Patch the VPN");
+        assert_eq!(json["data"]["task"]["title"], "Patch the VPN");
+    }
+
+    #[tokio::test]
+    async fn enqueue_never_blocks_when_full() {
+        let (dispatcher, mut rx) = WebhookDispatcher::channel(reqwest::Client::new());
+        for _ in 0..QUEUE_CAPACITY + 5 {
+            dispatcher.enqueue(WebhookEvent::ping("w1"));
+        }
+        let mut queued = 0;
+        while rx.try_recv().is_ok() {
+            queued += 1;
+        }
+        assert_eq!(queued, QUEUE_CAPACITY);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{future::Future, sync::Arc};
+
+use bson::doc;
+use mongodb::options::FindOptions;
+use tokio::sync::RwLock;
+
+use crate::{
+    db::{collect, Db, WORKFLOW_STATUSES},
+    errors::AppResult,
+    models::workflow::{Workflow, WorkflowStatus},
+};
+
+#[derive(Default)]
+struct Inner {
+    /// Bumped by every invalidation, so a load that raced with a write is
+    /// returned to its caller but never stored.
+    generation: u64,
+    workflow: Option<Arc<Workflow>>,
+}
+
+/// In-process cache of the workflow statuses, which every task write and
+/// status filter checks against. Kept until a status write invalidates it;
+/// changes made by another instance are only seen after a restart.
+#[derive(Clone, Default)]
+pub struct WorkflowCache {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl WorkflowCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The workflow, loaded from `db` on a miss.
+    pub async fn get(&self, db: &Db) -> AppResult<Arc<Workflow>> {
+        self.get_or_load(load(db)).await
+    }
+
+    async fn get_or_load<F>(&self, load: F) -> AppResult<Arc<Workflow>>
+    where
+        F: Future<Output = AppResult<Workflow>>,
+    {
+        let generation = {
+            let inner = self.inner.read().await;
+            if let Some(workflow) = &inner.workflow {
+                return Ok(workflow.clone());
+            }
+            inner.generation
+        };
+        let workflow = Arc::new(load.await?);
+        let mut inner = self.inner.write().await;
+        if inner.generation == generation {
+            inner.workflow.get_or_insert_with(|| workflow.clone());
+        }
+        Ok(workflow)
+    }
+
+    /// Call after every status write.
+    pub async fn invalidate(&self) {
+        let mut inner = self.inner.write().await;
+        inner.generation += 1;
+        inner.workflow = None;
+    }
+}
+
+/// The workflow straight from `db`, for code without an `AppState` cache.
+pub(crate) async fn load(db: &Db) -> AppResult<Workflow> {
+    let options = FindOptions::builder().sort(doc! { "order": 1 }).build();
+    let cursor = db.collection::<WorkflowStatus>(WORKFLOW_STATUSES).find(None, options).await?;
+    Ok(Workflow::new(collect(cursor).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::DateTime;
+
+    use super::*;
+
+    async fn counted(calls: &AtomicUsize, key: &str) -> AppResult<Workflow> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Ok(Workflow::new(vec![WorkflowStatus::new(key, key, "#000000", 0, false, DateTime::UNIX_EPOCH)]))
+    }
+
+    #[tokio::test]
+    async fn loads_once_until_invalidated() {
+        let cache = WorkflowCache::new();
+        let calls = AtomicUsize::new(0);
+        cache.get_or_load(counted(&calls, "a")).await.unwrap();
+        assert_eq!(cache.get_or_load(counted(&calls, "b")).await.unwrap().initial(), "a");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        cache.invalidate().await;
+        assert_eq!(cache.get_or_load(counted(&calls, "c")).await.unwrap().initial(), "c");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,353 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn admin_routes_are_forbidden_to_regular_users() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+
+    let res = app.get("/api/v1/admin/users", Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert_eq!(res.code(), "forbidden");
+    let res = app.put(&format!("/api/v1/admin/users/{}/role", bob.sub), &bob, json!({ "role": "admin" })).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let res = app.get("/api/v1/admin/users", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn unauthenticated_admin_requests_get_401_not_403() {
+    let app = TestApp::spawn().await;
+    let res = app.get("/api/v1/admin/users", None).await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn workflow_statuses_can_be_added_and_deleted_with_a_migration() {
+    let app = TestApp::spawn().await;
+    let res = app
+        .post("/api/v1/admin/statuses", &app.admin, json!({ "key": "review", "label": "In review", "order": 2 }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    let res = app.get("/api/v1/statuses", Some(&app.admin)).await;
+    let keys: Vec<_> = res.body.as_array().unwrap().iter().map(|s| s["_id"].as_str().unwrap().to_string()).collect();
+    assert_eq!(keys, ["todo", "in_progress", "review", "done"]);
+
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    let res = app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "status": "blocked" })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "status": "review" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+
+    let res = app.delete("/api/v1/admin/statuses/review", &app.admin).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.code(), "status_in_use");
+    assert_eq!(res.body["tasks"], 1);
+    let res = app.delete("/api/v1/admin/statuses/review?migrate_to=review", &app.admin).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = app.delete("/api/v1/admin/statuses/review?migrate_to=in_progress", &app.admin).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+
+    let res = app.get(&format!("/api/v1/tasks/{id}"), Some(&app.admin)).await;
+    assert_eq!(res.body["status"], "in_progress");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn integrity_check_finds_and_repairs_orphaned_assignees() {
+    let app = TestApp::spawn().await;
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    app.db
+        .collection::<bson::Document>("tasks")
+        .update_one(bson::doc! { "_id": &id }, bson::doc! { "$set": { "assignee_id": "gone", "status": "blocked" } }, None)
+        .await
+        .unwrap();
+
+    let bob = app.register("bob", &["user"]).await;
+    assert_eq!(app.get("/api/v1/admin/integrity", Some(&bob)).await.status, StatusCode::FORBIDDEN);
+    let res = app.get("/api/v1/admin/integrity", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["checks"][0], json!({ "check": "orphaned_assignee", "count": 1, "sample_ids": [&id] }));
+    assert_eq!(res.body["checks"][2]["sample_ids"], json!([&id]));
+    assert_eq!(res.body["total"], 2);
+
+    let repair = json!({ "orphaned_assignee": { "action": "reassign", "to": "nobody" } });
+    let res = app.post("/api/v1/admin/integrity/repair", &app.admin, repair).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let repair = json!({
+        "orphaned_assignee": { "action": "reassign", "to": bob.sub },
+        "invalid_status": { "action": "reassign", "to": "todo" },
+    });
+    let res = app.post("/api/v1/admin/integrity/repair", &app.admin, repair).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["repaired"][0], json!({ "check": "orphaned_assignee", "action": "reassign", "count": 1 }));
+
+    let task = app.get(&format!("/api/v1/tasks/{id}"), Some(&app.admin)).await;
+    assert_eq!(task.body["assignee_id"], bob.sub.as_str());
+    assert_eq!(task.body["status"], "todo");
+    assert_eq!(app.get("/api/v1/admin/integrity", Some(&app.admin)).await.body["total"], 0);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn admins_list_a_deactivated_users_tasks() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let res = app.post("/api/v1/tasks", &bob, json!({ "title": "Bob's", "description": "D" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    app.db
+        .collection::<bson::Document>("tasks")
+        .update_one(bson::doc! { "_id": &id }, bson::doc! { "$set": { "assignee_id": &bob.sub } }, None)
+        .await
+        .unwrap();
+    let res = app.put(&format!("/api/v1/admin/users/{}/deactivate", bob.sub), &app.admin, json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+
+    let path = format!("/api/v1/admin/users/{}/tasks", bob.sub);
+    let res = app.get(&path, Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!((res.body["total"].clone(), res.body["tasks"][0]["_id"].clone()), (json!(1), json!(id)));
+    assert_eq!(res.body["has_next"], false);
+    assert_eq!(app.get(&format!("{path}?relation=created"), Some(&app.admin)).await.body["total"], 1);
+    assert_eq!(app.get(&format!("{path}?status=done"), Some(&app.admin)).await.body["total"], 0);
+    let res = app.get(&format!("{path}?relation=watching"), Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let never = "/api/v1/admin/users/00000000-0000-4000-8000-000000000000/tasks";
+    assert_eq!(app.get(never, Some(&app.admin)).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn deleting_a_user_drops_their_memberships() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let members = app.db.collection::<bson::Document>("workspace_members");
+    assert_eq!(app.get("/api/v1/tasks", Some(&bob)).await.status, StatusCode::OK);
+    assert_eq!(members.count_documents(bson::doc! { "user_id": &bob.sub }, None).await.unwrap(), 1);
+
+    let res = app.delete(&format!("/api/v1/admin/users/{}", bob.sub), &app.admin).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(members.count_documents(bson::doc! { "user_id": &bob.sub }, None).await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn bulk_changes_report_each_user_and_are_audited() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let carol = app.register("carol", &["user"]).await;
+    let dave = app.register("dave", &["admin"]).await;
+    let missing = "00000000-0000-4000-8000-000000000000";
+
+    let ids = json!([bob.sub, carol.sub, app.admin.sub, missing, bob.sub]);
+    let res = app.post("/api/v1/admin/users/bulk", &app.admin, json!({ "ids": ids, "action": "deactivate" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(
+        res.body["results"],
+        json!([
+            { "id": bob.sub, "outcome": "applied" },
+            { "id": carol.sub, "outcome": "applied" },
+            { "id": app.admin.sub, "outcome": "skipped", "reason": "self" },
+            { "id": missing, "outcome": "not_found" },
+        ])
+    );
+    let res = app.get(&format!("/api/v1/admin/users/{}", bob.sub), Some(&app.admin)).await;
+    assert_eq!(res.body["active"], false);
+
+    // Demoting an admin goes through the last-admin guard, and is allowed
+    // while another admin remains.
+    let body = json!({ "ids": [dave.sub, carol.sub], "action": { "set_role": "user" } });
+    let res = app.post("/api/v1/admin/users/bulk", &app.admin, body).await;
+    assert_eq!(res.body["results"][0], json!({ "id": dave.sub, "outcome": "applied" }));
+    assert_eq!(res.body["results"][1], json!({ "id": carol.sub, "outcome": "skipped", "reason": "unchanged" }));
+
+    let audited = app
+        .db
+        .collection::<bson::Document>("audit_log")
+        .count_documents(bson::doc! { "action": "user_bulk_update" }, None)
+        .await
+        .unwrap();
+    assert_eq!(audited, 3);
+
+    let res = app.post("/api/v1/admin/users/bulk", &app.admin, json!({ "ids": [], "action": "activate" })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body = json!({ "ids": [bob.sub], "action": { "set_role": "root" } });
+    let res = app.post("/api/v1/admin/users/bulk", &app.admin, body).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn stats_size_collections_and_stay_counts_only() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    for title in ["One", "Two"] {
+        app.post("/api/v1/tasks", &app.admin, json!({ "title": title, "description": "D" })).await;
+    }
+
+    let res = app.get("/api/v1/admin/stats", Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let res = app.get("/api/v1/admin/stats?fresh=true", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["tasks"]["count"], 2);
+    assert_eq!(res.body["tasks"]["notes_per_task"]["buckets"][0]["tasks"], 2);
+    assert_eq!(res.body["tasks"]["created_per_day"].as_array().unwrap().last().unwrap()["tasks"], 2);
+    assert_eq!(res.body["users_by_role"]["user"], 1);
+    let tasks = res.body["collections"].as_array().unwrap().iter().find(|c| c["name"] == "tasks").unwrap().clone();
+    assert_eq!(tasks["documents"], 2);
+    assert!(!res.body.to_string().contains("bob"), "{}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn retention_dry_runs_change_nothing_and_runs_are_recorded() {
+    let app = TestApp::spawn().await;
+    let mut ids = Vec::new();
+    for title in ["Old", "Recent"] {
+        let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": title, "description": "D" })).await;
+        ids.push(res.body["_id"].as_str().unwrap().to_string());
+    }
+    let long_ago = bson::DateTime::from_chrono(chrono::Utc::now() - chrono::Duration::days(200));
+    for (id, updated_at) in [(&ids[0], long_ago), (&ids[1], bson::DateTime::now())] {
+        let update = bson::doc! { "$set": { "status": "done", "updated_at": updated_at } };
+        app.db
+            .collection::<bson::Document>("tasks")
+            .update_one(bson::doc! { "_id": id }, update, None)
+            .await
+            .unwrap();
+    }
+
+    let bob = app.register("bob", &["user"]).await;
+    assert_eq!(app.post("/api/v1/admin/retention/dry-run", &bob, json!({})).await.status, StatusCode::FORBIDDEN);
+    let res = app.post("/api/v1/admin/retention/dry-run", &app.admin, json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["dry_run"], true);
+    assert_eq!(res.body["rules"][0]["rule"], "archive_done_tasks");
+    assert_eq!(res.body["rules"][0]["affected"], 1);
+    assert_eq!(res.body["rules"][0]["sample_ids"], json!([&ids[0]]));
+    assert_eq!(app.get(&format!("/api/v1/tasks/{}", ids[0]), Some(&app.admin)).await.status, StatusCode::OK);
+
+    let config = missoncontrol::config::RetentionConfig {
+        archive_done_days: 180,
+        purge_archived_days: 365,
+        purge_login_events_days: 90,
+        batch_size: 1,
+        interval_hours: 24,
+    };
+    let ids_gen = missoncontrol::clock::UuidIds;
+    let run = missoncontrol::retention::run(&app.db, &config, &ids_gen, chrono::Utc::now(), false, None).await.unwrap();
+    assert_eq!(run.rules[0].affected, 1);
+    assert_eq!(app.get(&format!("/api/v1/tasks/{}", ids[0]), Some(&app.admin)).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("/api/v1/tasks/{}", ids[1]), Some(&app.admin)).await.status, StatusCode::OK);
+    let archived = app.db.collection::<bson::Document>("archived_tasks").find_one(None, None).await.unwrap().unwrap();
+    assert_eq!(archived.get_str("_id").unwrap(), ids[0]);
+    assert!(archived.get_datetime("archived_at").is_ok());
+
+    let res = app.get("/api/v1/admin/retention/runs", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["total"], 2);
+    assert_eq!(res.body["runs"][0]["dry_run"], false);
+    assert_eq!(res.body["runs"][1]["requested_by"], app.admin.sub);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn cleanup_deletes_only_expired_documents() {
+    let app = TestApp::spawn().await;
+    let past = bson::DateTime::from_chrono(chrono::Utc::now() - chrono::Duration::hours(1));
+    let future = bson::DateTime::from_chrono(chrono::Utc::now() + chrono::Duration::hours(1));
+    let invites = app.db.collection::<bson::Document>("invites");
+    let docs = [
+        bson::doc! { "_id": "expired", "code": "a", "used": false, "expires_at": past },
+        bson::doc! { "_id": "redeemed", "code": "b", "used": true, "expires_at": past },
+        bson::doc! { "_id": "open", "code": "c", "used": false, "expires_at": future },
+    ];
+    invites.insert_many(docs, None).await.unwrap();
+    let shares = app.db.collection::<bson::Document>("task_shares");
+    let docs = [
+        bson::doc! { "_id": "revoked", "token_hash": "a", "task_id": "t", "revoked_at": past },
+        bson::doc! { "_id": "lapsed", "token_hash": "b", "task_id": "t", "expires_at": past, "revoked_at": null },
+        bson::doc! { "_id": "live", "token_hash": "c", "task_id": "t", "expires_at": null, "revoked_at": null },
+    ];
+    shares.insert_many(docs, None).await.unwrap();
+    let locks = app.db.collection::<bson::Document>("locks");
+    let docs = [bson::doc! { "_id": "stale", "expires_at": past }, bson::doc! { "_id": "held", "expires_at": future }];
+    locks.insert_many(docs, None).await.unwrap();
+
+    let bob = app.register("bob", &["user"]).await;
+    assert_eq!(app.post("/api/v1/admin/maintenance/cleanup", &bob, json!({})).await.status, StatusCode::FORBIDDEN);
+    let res = app.post("/api/v1/admin/maintenance/cleanup", &app.admin, json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let deleted = |sweep: &str| {
+        res.body["sweeps"].as_array().unwrap().iter().find(|s| s["sweep"] == sweep).unwrap()["deleted"].clone()
+    };
+    assert_eq!(deleted("expired_invites"), 1);
+    assert_eq!(deleted("dead_share_links"), 2);
+    assert_eq!(deleted("stale_locks"), 1);
+    async fn left(collection: &mongodb::Collection<bson::Document>) -> Vec<String> {
+        let ids = collection.distinct("_id", None, None).await.unwrap();
+        let mut ids: Vec<String> = ids.iter().map(|id| id.as_str().unwrap().to_string()).collect();
+        ids.sort();
+        ids
+    }
+    assert_eq!(left(&invites).await, ["open", "redeemed"]);
+    assert_eq!(left(&shares).await, ["live"]);
+    assert_eq!(left(&locks).await, ["held"]);
+
+    // A second pass, as another replica would run it, finds nothing left.
+    let res = app.post("/api/v1/admin/maintenance/cleanup", &app.admin, json!({})).await;
+    assert!(res.body["sweeps"].as_array().unwrap().iter().all(|s| s["deleted"] == 0), "{:?}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn diagnostics_report_each_check() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    assert_eq!(app.get("/api/v1/admin/diagnostics", Some(&bob)).await.status, StatusCode::FORBIDDEN);
+    let res = app.get("/api/v1/admin/diagnostics", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let checks = res.body["checks"].as_array().unwrap();
+    let status = |name: &str| checks.iter().find(|c| c["name"] == name).unwrap()["status"].clone();
+    for name in ["mongodb_ping", "mongodb_write", "mongodb_create_index", "clock_skew"] {
+        assert_eq!(status(name), "ok", "{name}: {:?}", res.body);
+    }
+    // Keycloak is not running under test.
+    assert_eq!(status("jwt_signing_key"), "fail");
+    assert_eq!(res.body["status"], "fail");
+    let left = app.db.collection::<bson::Document>("diagnostics").count_documents(None, None).await.unwrap();
+    assert_eq!(left, 0);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn teams_stay_in_their_workspace() {
+    let app = TestApp::spawn().await;
+    let res = app.post("/api/v1/admin/teams", &app.admin, json!({ "name": "Blue" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    let team_id = res.body["id"].as_str().unwrap().to_string();
+
+    let res = app.post("/api/v1/workspaces", &app.admin, json!({ "name": "Red team" })).await;
+    let ws = res.body["id"].as_str().unwrap().to_string();
+    app.post(&format!("/api/v1/workspaces/{ws}/switch"), &app.admin, json!({})).await;
+    let res = app.get("/api/v1/teams", Some(&app.admin)).await;
+    assert_eq!(res.body, json!([]), "{:?}", res.body);
+    let res = app.get(&format!("/api/v1/admin/teams/{team_id}"), Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = app.get(&format!("/api/v1/tasks?team_id={team_id}"), Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    // The same name is free in another workspace.
+    let res = app.post("/api/v1/admin/teams", &app.admin, json!({ "name": "Blue" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+}
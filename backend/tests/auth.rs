@@ -0,0 +1,73 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use common::{mint_token, validate_token, TestApp, TestUser};
+
+/// Runs without MongoDB, so the harness's tokens are checked everywhere.
+#[test]
+fn minted_tokens_pass_keycloak_validation() {
+    let claims = validate_token(&mint_token("kc-alice", "alice", &["admin"], 60)).unwrap();
+    assert_eq!(claims.sub.as_deref(), Some("kc-alice"));
+    assert_eq!(claims.realm_access.unwrap().roles, ["admin"]);
+    let expired = validate_token(&mint_token("kc-alice", "alice", &[], -3600)).unwrap_err();
+    assert_eq!(*expired.kind(), jsonwebtoken::errors::ErrorKind::ExpiredSignature);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn requests_without_a_valid_token_are_rejected() {
+    let app = TestApp::spawn().await;
+
+    let res = app.get("/api/v1/auth/me", None).await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "token_missing");
+
+    let expired = TestUser { sub: "kc-late".into(), token: mint_token("kc-late", "late", &["user"], -3600) };
+    let res = app.get("/api/v1/auth/me", Some(&expired)).await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "token_expired");
+
+    let garbled = Request::get("/api/v1/tasks")
+        .header(header::AUTHORIZATION, "Bearer not.a.jwt")
+        .body(Body::empty())
+        .unwrap();
+    let res = app.send(garbled).await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "token_invalid");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn first_sign_in_provisions_the_user() {
+    let app = TestApp::spawn().await;
+    let alice = app.register("alice", &["user"]).await;
+
+    let res = app.get("/api/v1/auth/me", Some(&alice)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["user"]["username"], "alice");
+    assert_eq!(res.body["user"]["role"], "user");
+
+    // Provisioning joined the default workspace, so workspace routes open up.
+    let res = app.get("/api/v1/tasks", Some(&alice)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn the_first_user_is_bootstrapped_to_admin() {
+    let app = TestApp::spawn().await;
+    let res = app.get("/api/v1/auth/me", Some(&app.admin)).await;
+    assert_eq!(res.body["user"]["role"], "admin");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn the_legacy_prefix_still_authenticates() {
+    let app = TestApp::spawn().await;
+    let res = app.get("/api/auth/me", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.headers.contains_key("deprecation"));
+}
@@ -0,0 +1,312 @@
+//! Shared setup for the integration tests: the real router from
+//! `routes::build_router`, backed by a throwaway database on the MongoDB at
+//! `MONGODB_TEST_URI`, and Keycloak tokens signed with a test key.
+//!
+//! Tests that need MongoDB are `#[ignore]`d, so a plain `cargo test` reports
+//! them as ignored rather than passed. To run them:
+//!
+//! ```text
+//! docker run -d --rm -p 27017:27017 mongo:7
+//! MONGODB_TEST_URI=mongodb://localhost:27017 cargo test --tests -- --include-ignored
+//! ```
+
+// Each file under tests/ is its own crate and uses a different subset.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    async_trait,
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use missoncontrol::{
+    config::AppConfig,
+    db::{indexes::ensure_indexes, Db, Transactions},
+    events::{self, DomainEvent, EventBus, Subscriber},
+    handlers::notifications::Notifications,
+    keycloak::{build_validation, KeycloakClaims},
+    migrations, notifier, nws_client::NwsClient, routes, webhooks,
+};
+use mongodb::Client;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, RwLock};
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+const PRIVATE_KEY: &[u8] = include_bytes!("jwt_test_key.pem");
+const PUBLIC_KEY: &[u8] = include_bytes!("jwt_test_key.pub.pem");
+
+const KEYCLOAK_URL: &str = "https://keycloak.test";
+const KEYCLOAK_REALM: &str = "missioncontrol";
+const KEYCLOAK_CLIENT_ID: &str = "missioncontrol-app";
+
+/// A signed-in user: their Keycloak subject and a bearer token for them.
+#[derive(Debug, Clone)]
+pub struct TestUser {
+    pub sub: String,
+    pub token: String,
+}
+
+/// A response, with the body parsed as JSON (`Value::Null` when empty).
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl TestResponse {
+    /// The body's error `code`, for asserting on failures.
+    pub fn code(&self) -> &str {
+        self.body["code"].as_str().unwrap_or_default()
+    }
+}
+
+pub struct TestApp {
+    pub router: Router,
+    pub db: Db,
+    /// The first user to sign in to a fresh database is bootstrapped to
+    /// admin, so `spawn` signs one in up front; users made afterwards keep
+    /// the role their token carries.
+    pub admin: TestUser,
+    uri: String,
+    // Held so queued webhooks and emails are accepted rather than dropped
+    // with an error; nothing delivers them.
+    _webhook_events: mpsc::Receiver<webhooks::WebhookEvent>,
+    _emails: mpsc::Receiver<notifier::Email>,
+    /// Every domain event published, in order; see `events_eventually`.
+    events: Arc<Mutex<Vec<DomainEvent>>>,
+}
+
+/// Keeps a copy of every domain event for tests to inspect.
+struct RecordEvents(Arc<Mutex<Vec<DomainEvent>>>);
+
+#[async_trait]
+impl Subscriber for RecordEvents {
+    fn name(&self) -> &'static str {
+        "test_recorder"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> anyhow::Result<()> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+/// Polls `check` until it returns `Some`, for effects that happen off the
+/// request path. Panics after a few seconds.
+pub async fn eventually<T, F, Fut>(mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>,
+{
+    for _ in 0..250 {
+        if let Some(value) = check().await {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("condition not met within 5s");
+}
+
+impl TestApp {
+    /// Builds the app against a fresh, uniquely named database, migrated and
+    /// indexed as on startup. Panics when `MONGODB_TEST_URI` is unset, so an
+    /// ignored test run without MongoDB fails instead of passing.
+    pub async fn spawn() -> Self {
+        Self::spawn_with(&[]).await
+    }
+
+    /// `spawn` with extra environment variables for the app's config.
+    pub async fn spawn_with(vars: &[(&str, &str)]) -> Self {
+        let uri = std::env::var("MONGODB_TEST_URI")
+            .ok()
+            .filter(|u| !u.trim().is_empty())
+            .expect("MONGODB_TEST_URI must point at a MongoDB to run the integration tests");
+        let db_name = format!("mc_test_{}", uuid::Uuid::new_v4().simple());
+        let mut env = vec![("MONGODB_URI", uri.as_str()), ("MONGODB_DB", db_name.as_str())];
+        env.extend_from_slice(vars);
+        let config = test_config(&env);
+
+        let client = Client::with_uri_str(&uri).await.expect("MONGODB_TEST_URI is a valid URI");
+        let db = client.database(&db_name);
+        migrations::run(&db).await.expect("migrations apply to an empty database");
+        ensure_indexes(&db, &config).await.expect("indexes build on an empty database");
+
+        let (webhooks, webhook_events) = webhooks::WebhookDispatcher::channel(reqwest::Client::new());
+        let (notifier, emails) = notifier::Notifier::channel();
+        let (bus, domain_events) = EventBus::channel();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let subscribers: Vec<Arc<dyn Subscriber>> = vec![
+            Arc::new(webhooks.clone()),
+            Arc::new(Notifications::new(db.clone(), notifier.clone(), &config)),
+            Arc::new(RecordEvents(recorded.clone())),
+        ];
+        tokio::spawn(events::run_subscribers(domain_events, subscribers, CancellationToken::new()));
+        let router = routes::build_router(
+            config,
+            db.clone(),
+            Transactions::new(client, false),
+            Arc::new(NwsClient::new()),
+            reqwest::Client::new(),
+            Arc::new(Vec::new()),
+            Arc::new(RwLock::new(DecodingKey::from_rsa_pem(PUBLIC_KEY).expect("test public key parses"))),
+            webhooks,
+            notifier,
+            bus,
+        );
+
+        let mut app = Self {
+            router,
+            db,
+            admin: TestUser { sub: String::new(), token: String::new() },
+            uri,
+            _webhook_events: webhook_events,
+            _emails: emails,
+            events: recorded,
+        };
+        app.admin = app.register("admin", &["admin"]).await;
+        app
+    }
+
+    /// Signs `username` in through `GET /api/v1/auth/me`, which provisions
+    /// their local record and default workspace membership. Subjects are
+    /// UUIDs, as Keycloak's are, so they pass as path ids.
+    pub async fn register(&self, username: &str, roles: &[&str]) -> TestUser {
+        let sub = uuid::Uuid::new_v4().to_string();
+        let token = mint_token(&sub, username, roles, 3600);
+        let user = TestUser { sub, token };
+        let res = self.get("/api/v1/auth/me", Some(&user)).await;
+        assert_eq!(res.status, StatusCode::OK, "registering {username}: {:?}", res.body);
+        user
+    }
+
+    pub async fn get(&self, path: &str, user: Option<&TestUser>) -> TestResponse {
+        self.request(Method::GET, path, user, None).await
+    }
+
+    pub async fn post(&self, path: &str, user: &TestUser, body: Value) -> TestResponse {
+        self.request(Method::POST, path, Some(user), Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str, user: &TestUser, body: Value) -> TestResponse {
+        self.request(Method::PUT, path, Some(user), Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str, user: &TestUser) -> TestResponse {
+        self.request(Method::DELETE, path, Some(user), None).await
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        user: Option<&TestUser>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut req = Request::builder().method(method).uri(path);
+        if let Some(user) = user {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {}", user.token));
+        }
+        let req = match body {
+            Some(body) => req
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => req.body(Body::empty()),
+        }
+        .unwrap();
+        self.send(req).await
+    }
+
+    /// The domain events published so far, once there are at least `n`.
+    pub async fn events_eventually(&self, n: usize) -> Vec<DomainEvent> {
+        eventually(|| async {
+            let events = self.events.lock().unwrap().clone();
+            (events.len() >= n).then_some(events)
+        })
+        .await
+    }
+
+    /// Sends a hand-built request, e.g. one with a raw `Authorization` header.
+    pub async fn send(&self, req: Request<Body>) -> TestResponse {
+        let res = self.router.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let headers = res.headers().clone();
+        let bytes = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap_or_else(|_| json!(String::from_utf8_lossy(&bytes))) };
+        TestResponse { status, headers, body }
+    }
+}
+
+impl Drop for TestApp {
+    /// Drops the test database. `Drop` can't await, and the test's runtime may
+    /// already be winding down, so this runs on a runtime of its own.
+    fn drop(&mut self) {
+        let (uri, name) = (self.uri.clone(), self.db.name().to_string());
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(async {
+                let client = Client::with_uri_str(&uri).await?;
+                client.database(&name).drop(None).await?;
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+            })
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("Could not drop test database {}", self.db.name());
+        }
+    }
+}
+
+/// The app's config for tests: Keycloak pointed at the values `mint_token`
+/// signs for, transactions off (test servers are usually a standalone
+/// mongod), and `vars` on top.
+pub fn test_config(vars: &[(&str, &str)]) -> AppConfig {
+    let mut env: HashMap<String, String> = [
+        ("MONGODB_URI", "mongodb://localhost:27017"),
+        ("MONGODB_DB", "missoncontrol_test"),
+        ("KEYCLOAK_URL", KEYCLOAK_URL),
+        ("KEYCLOAK_REALM", KEYCLOAK_REALM),
+        ("KEYCLOAK_CLIENT_ID", KEYCLOAK_CLIENT_ID),
+        ("MONGO_TRANSACTIONS", "false"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+    env.extend(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    AppConfig::from_lookup(|name| env.get(name).cloned()).expect("test config is valid")
+}
+
+/// Checks `token` the way `require_auth` does.
+pub fn validate_token(token: &str) -> Result<KeycloakClaims, jsonwebtoken::errors::Error> {
+    let key = DecodingKey::from_rsa_pem(PUBLIC_KEY).unwrap();
+    jsonwebtoken::decode(token, &key, &build_validation(&test_config(&[]))).map(|data| data.claims)
+}
+
+/// A Keycloak-style access token for `sub`, valid for `ttl_secs` (negative
+/// for one that has already expired).
+pub fn mint_token(sub: &str, username: &str, roles: &[&str], ttl_secs: i64) -> String {
+    let claims = json!({
+        "sub": sub,
+        "email": format!("{username}@example.com"),
+        "email_verified": true,
+        "preferred_username": username,
+        "realm_access": { "roles": roles },
+        "iss": format!("{KEYCLOAK_URL}/realms/{KEYCLOAK_REALM}"),
+        "aud": KEYCLOAK_CLIENT_ID,
+        "exp": chrono::Utc::now().timestamp() + ttl_secs,
+    });
+    jsonwebtoken::encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        &EncodingKey::from_rsa_pem(PRIVATE_KEY).expect("test private key parses"),
+    )
+    .unwrap()
+}
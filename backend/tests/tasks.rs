@@ -0,0 +1,641 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use common::{eventually, TestApp};
+use missoncontrol::events::DomainEvent;
+use serde_json::json;
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn task_crud_happy_path() {
+    let app = TestApp::spawn().await;
+    let alice = app.register("alice", &["user"]).await;
+
+    let res = app
+        .post("/api/v1/tasks", &alice, json!({ "title": "Rotate certs", "description": "Before Friday" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    assert_eq!(res.headers[header::LOCATION], format!("/api/v1/tasks/{id}"));
+    assert_eq!(res.body["status"], "todo");
+
+    let res = app.get(&format!("/api/v1/tasks/{id}"), Some(&alice)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["title"], "Rotate certs");
+
+    let res = app
+        .put(&format!("/api/v1/tasks/{id}"), &alice, json!({ "title": "Rotate all certs", "status": "in_progress" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["title"], "Rotate all certs");
+    assert_eq!(res.body["status"], "in_progress");
+
+    let res = app.post(&format!("/api/v1/tasks/{id}/notes"), &alice, json!({ "note": "Staging done" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["notes"].as_array().unwrap().len(), 1);
+
+    let res = app.delete(&format!("/api/v1/tasks/{id}"), &alice).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = app.get(&format!("/api/v1/tasks/{id}"), Some(&alice)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    assert_eq!(res.code(), "not_found");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn invalid_task_bodies_are_rejected() {
+    let app = TestApp::spawn().await;
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "No description" })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.body["fields"][0]["field"], "description");
+    assert_eq!(res.body["fields"][0]["code"], "required");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn pagination_edge_cases() {
+    let app = TestApp::spawn().await;
+    for i in 0..3 {
+        let res = app
+            .post("/api/v1/tasks", &app.admin, json!({ "title": format!("Task {i}"), "description": "" }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    }
+
+    let res = app.get("/api/v1/tasks?limit=2", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["tasks"].as_array().unwrap().len(), 2);
+    assert_eq!((res.body["total"].as_u64(), res.body["total_pages"].as_u64()), (Some(3), Some(2)));
+
+    let res = app.get("/api/v1/tasks?limit=2&page=2", Some(&app.admin)).await;
+    assert_eq!(res.body["tasks"].as_array().unwrap().len(), 1);
+
+    // Past the last page is an empty page, not an error.
+    let res = app.get("/api/v1/tasks?limit=2&page=9", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body["tasks"].as_array().unwrap().is_empty());
+    assert_eq!(res.body["total"], 3);
+
+    for query in ["limit=0", "limit=101", "page=0"] {
+        let res = app.get(&format!("/api/v1/tasks?{query}"), Some(&app.admin)).await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY, "{query}");
+        assert_eq!(res.body["fields"][0]["code"], "out_of_range", "{query}");
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn unassigned_and_stale_reports_list_open_tasks() {
+    let app = TestApp::spawn().await;
+    for title in ["First", "Second"] {
+        let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": title, "description": "d" })).await;
+        assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    }
+
+    let res = app.get("/api/v1/reports/unassigned", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["total"], 2);
+    assert_eq!(res.body["tasks"][0]["title"], "First");
+    let res = app.get("/api/v1/reports/unassigned?counts_only=true", Some(&app.admin)).await;
+    assert_eq!(res.body, json!({ "total": 2 }));
+
+    // Nothing is a day old yet.
+    let res = app.get("/api/v1/reports/stale?days=1", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["total"], 0);
+    assert_eq!(res.body["by_assignee"], json!([]));
+    let res = app.get("/api/v1/reports/stale?days=0", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn malformed_ids_are_rejected_before_lookup() {
+    let app = TestApp::spawn().await;
+    let res = app.get("/api/v1/tasks/not-a-uuid", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(res.code(), "malformed_id");
+
+    let missing = uuid::Uuid::new_v4();
+    let res = app.get(&format!("/api/v1/tasks/{missing}"), Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn assigning_records_who_and_notifies_the_assignee() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Patch hosts", "description": "d" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    let assign = format!("/api/v1/tasks/{id}/assign");
+
+    let res = app.post(&assign, &app.admin, json!({ "assignee_id": bob.sub })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["assignee_id"], bob.sub.as_str());
+    assert_eq!(res.body["assigned_by"], app.admin.sub.as_str());
+    assert!(res.body["assigned_at"].is_string());
+
+    // Notifications are stored off the request path.
+    let inbox = eventually(|| async {
+        let res = app.get("/api/v1/notifications", Some(&bob)).await;
+        (res.body["total"] != 0).then_some(res.body)
+    })
+    .await;
+    assert_eq!(inbox["total"], 1, "{inbox:?}");
+
+    let res = app.post(&assign, &app.admin, json!({ "assignee_id": uuid::Uuid::new_v4() })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.body["fields"][0]["code"], "unknown_user");
+    let res = app.post(&assign, &app.admin, json!({})).await;
+    assert_eq!(res.body["fields"][0]["code"], "required");
+
+    let res = app.post(&assign, &app.admin, json!({ "assignee_id": null })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert!(res.body["assignee_id"].is_null());
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn counts_match_the_list_filter() {
+    let app = TestApp::spawn().await;
+    for title in ["A", "B"] {
+        app.post("/api/v1/tasks", &app.admin, json!({ "title": title, "description": "D" })).await;
+    }
+    let res = app.get("/api/v1/tasks/count?status=todo", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body, json!({ "total": 2 }));
+    assert_eq!(app.get("/api/v1/tasks/count?status=done", Some(&app.admin)).await.body["total"], 0);
+
+    let res = app.request(Method::HEAD, "/api/v1/tasks?status=todo", Some(&app.admin), None).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.headers["x-total-count"], "2");
+    assert_eq!(res.body, serde_json::Value::Null);
+
+    let list = app.get("/api/v1/tasks?status=nope", Some(&app.admin)).await;
+    let count = app.get("/api/v1/tasks/count?status=nope", Some(&app.admin)).await;
+    assert_eq!(count.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(count.body["fields"], list.body["fields"]);
+    let head = app.request(Method::HEAD, "/api/v1/tasks?status=nope", Some(&app.admin), None).await;
+    assert_eq!(head.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn feature_flags_gate_the_board() {
+    let app = TestApp::spawn().await;
+    let alice = app.register("alice", &["user"]).await;
+    assert_eq!(app.get("/api/v1/tasks/board", Some(&alice)).await.status, StatusCode::OK);
+    assert_eq!(app.get("/api/v1/features", Some(&alice)).await.body["task_board"], true);
+
+    let app = TestApp::spawn_with(&[("FEATURE_FLAGS", r#"{"task_board": false}"#)]).await;
+    let alice = app.register("alice", &["user"]).await;
+    let res = app.get("/api/v1/tasks/board", Some(&alice)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    assert_eq!(res.body["code"], "route_not_found");
+    assert_eq!(app.get("/api/v1/features", Some(&alice)).await.body["task_board"], false);
+
+    let flags = r#"{"task_board": {"enabled": true, "admin_only": true}}"#;
+    let app = TestApp::spawn_with(&[("FEATURE_FLAGS", flags)]).await;
+    let alice = app.register("alice", &["user"]).await;
+    assert_eq!(app.get("/api/v1/tasks/board", Some(&alice)).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(app.get("/api/v1/tasks/board", Some(&app.admin)).await.status, StatusCode::OK);
+    assert!(app.get("/api/v1/features", Some(&alice)).await.body.get("task_board").is_none());
+    let res = app.get("/api/v1/admin/features", Some(&app.admin)).await;
+    assert_eq!(res.body["task_board"], json!({ "enabled": true, "admin_only": true, "configured": true }));
+    assert_eq!(app.get("/api/v1/admin/features", Some(&alice)).await.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn board_lists_each_column_with_its_total() {
+    let app = TestApp::spawn().await;
+    for title in ["A", "B", "C"] {
+        app.post("/api/v1/tasks", &app.admin, json!({ "title": title, "description": "D" })).await;
+    }
+    let done = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Shipped", "description": "D" })).await;
+    let id = done.body["_id"].as_str().unwrap();
+    app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "status": "done" })).await;
+
+    let res = app.get("/api/v1/tasks/board?per_column=2", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let columns: Vec<_> = res.body["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| (c["status"].as_str().unwrap(), c["total"].as_u64().unwrap(), c["tasks"].as_array().unwrap().len(), c["has_more"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(columns, [("todo", 3, 2, true), ("in_progress", 0, 0, false), ("done", 1, 1, false)]);
+    assert_eq!(res.body["columns"][0]["tasks"][0]["title"], "C");
+
+    let res = app.get("/api/v1/tasks/board?status=done", Some(&app.admin)).await;
+    assert_eq!(res.body["columns"].as_array().unwrap().len(), 1);
+    let res = app.get("/api/v1/tasks/board?per_column=0&status=nope", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.body["fields"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn notes_keep_their_order_across_deletes() {
+    let app = TestApp::spawn().await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+    let path = format!("/api/v1/tasks/{id}/notes");
+    for note in ["one", "two", "three"] {
+        app.post(&path, &app.admin, json!({ "note": note, "created_at": "2000-01-01T00:00:00Z", "seq": 99 })).await;
+    }
+    let first = app.get(&format!("/api/v1/tasks/{id}"), Some(&app.admin)).await.body["notes"][0]["_id"].clone();
+    app.delete(&format!("{path}/{}", first.as_str().unwrap()), &app.admin).await;
+    let res = app.post(&path, &app.admin, json!({ "note": "four" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+
+    let notes = res.body["notes"].as_array().unwrap();
+    let seqs: Vec<_> = notes.iter().map(|n| (n["note"].as_str().unwrap(), n["seq"].as_i64().unwrap())).collect();
+    assert_eq!(seqs, [("two", 2), ("three", 3), ("four", 4)]);
+    assert!(notes.iter().all(|n| n["created_at"] != "2000-01-01T00:00:00Z"));
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn note_attachments_download_and_go_away_with_the_note() {
+    let app = TestApp::spawn_with(&[("NOTE_ATTACHMENTS_MAX", "1")]).await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+    let multipart = |files: &[&str]| {
+        let mut body = "--B\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nsee the screenshot\r\n".to_string();
+        for file in files {
+            body.push_str("--B\r\nContent-Disposition: form-data; name=\"file\"; filename=\"shot.png\"\r\n");
+            body.push_str(&format!("Content-Type: image/png\r\n\r\n{file}\r\n"));
+        }
+        body.push_str("--B--\r\n");
+        Request::post(format!("/api/v1/tasks/{id}/notes"))
+            .header(header::AUTHORIZATION, format!("Bearer {}", app.admin.token))
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=B")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    let res = app.send(multipart(&["one", "two"])).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY, "{:?}", res.body);
+    assert_eq!(res.body["fields"][0]["code"], "too_many");
+
+    let res = app.send(multipart(&["pixels"])).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let note = &res.body["notes"][0];
+    assert_eq!(note["note"], "see the screenshot");
+    let attachment = note["attachment_ids"][0].as_str().unwrap().to_string();
+    let download = format!("/api/v1/tasks/{id}/attachments/{attachment}");
+    let res = app.get(&download, Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.headers[header::CONTENT_TYPE], "image/png");
+    assert_eq!(res.headers[header::CONTENT_DISPOSITION], "inline; filename=\"shot.png\"");
+    assert_eq!(res.body, "pixels");
+
+    // Notes without files are sent as JSON, as before.
+    let res = app.post(&format!("/api/v1/tasks/{id}/notes"), &app.admin, json!({ "note": "plain" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert!(res.body["notes"][1].get("attachment_ids").is_none());
+
+    let note_id = note["_id"].as_str().unwrap();
+    app.delete(&format!("/api/v1/tasks/{id}/notes/{note_id}"), &app.admin).await;
+    assert_eq!(app.get(&download, Some(&app.admin)).await.status, StatusCode::NOT_FOUND);
+    let files = app.db.collection::<bson::Document>("note_attachments.files");
+    assert_eq!(files.count_documents(None, None).await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn search_finds_tasks_by_their_notes() {
+    let app = TestApp::spawn().await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Upgrade db", "description": "D" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+    let long_note = format!("{} Rollback plan: restore the snapshot. {}", "x".repeat(3000), "y".repeat(1500));
+    let res = app.post(&format!("/api/v1/tasks/{id}/notes"), &app.admin, json!({ "note": long_note })).await;
+    let note_id = res.body["notes"][0]["_id"].clone();
+    app.post("/api/v1/tasks", &app.admin, json!({ "title": "Write the rollback plan", "description": "D" })).await;
+    app.post("/api/v1/tasks", &app.admin, json!({ "title": "Unrelated", "description": "D" })).await;
+
+    let res = app.get("/api/v1/search?q=rollback%20plan&limit=10", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["total"], 2);
+    let hit = res.body["results"].as_array().unwrap().iter().find(|h| h["task_id"] == id).unwrap().clone();
+    assert!(hit.get("title_match").is_none());
+    assert_eq!(hit["notes"][0]["note_id"], note_id);
+    let snippet = hit["notes"][0]["snippet"]["text"].as_str().unwrap();
+    assert!(snippet.contains("Rollback plan") && snippet.chars().count() < 200, "{snippet}");
+
+    let res = app.get("/api/v1/search?q=", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.body["fields"][0]["code"], "required");
+
+    // Markup inside the phrase doesn't hide it from the query.
+    let note = json!({ "note": "<p>see the <strong>restore</strong> drill</p>" });
+    app.post(&format!("/api/v1/tasks/{id}/notes"), &app.admin, note).await;
+    let res = app.get("/api/v1/search?q=restore%20drill", Some(&app.admin)).await;
+    assert_eq!(res.body["total"], 1, "{:?}", res.body);
+    assert_eq!(res.body["results"][0]["notes"][0]["snippet"]["text"], "see the restore drill");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn the_digest_preview_lists_overdue_and_due_today_tasks() {
+    let app = TestApp::spawn().await;
+    let alice = app.register("alice", &["user"]).await;
+    let hours = |h: i64| (chrono::Utc::now() + chrono::Duration::hours(h)).to_rfc3339();
+    for (title, due) in [("Renew cert", hours(-48)), ("Next month", hours(24 * 30))] {
+        let body = json!({ "title": title, "description": "D", "assignee_id": alice.sub, "due_at": due });
+        let res = app.post("/api/v1/tasks", &alice, body).await;
+        assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    }
+    app.post("/api/v1/tasks", &alice, json!({ "title": "No due date", "description": "D", "assignee_id": alice.sub }))
+        .await;
+
+    let res = app.get("/api/v1/notifications/digest/preview", Some(&alice)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["timezone"], "UTC");
+    assert_eq!(res.body["overdue"][0]["title"], "Renew cert");
+    assert_eq!(res.body["overdue"].as_array().unwrap().len(), 1);
+    assert!(res.body["due_today"].as_array().unwrap().is_empty());
+
+    let res = app.put("/api/v1/auth/me/preferences", &alice, json!({ "digest": "daily", "digest_hour": 24 })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn only_one_of_two_simultaneous_claims_wins() {
+    let app = TestApp::spawn().await;
+    let alice = app.register("alice", &["user"]).await;
+    let bob = app.register("bob", &["user"]).await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Page: disk full", "description": "D" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+
+    let claim = format!("/api/v1/tasks/{id}/claim");
+    let (a, b) = tokio::join!(app.post(&claim, &alice, json!({})), app.post(&claim, &bob, json!({})));
+    let (won, lost, winner) = if a.status == StatusCode::OK { (a, b, &alice) } else { (b, a, &bob) };
+    assert_eq!(won.status, StatusCode::OK, "{:?}", won.body);
+    assert_eq!(won.body["assignee_id"], winner.sub);
+    assert_eq!(lost.status, StatusCode::CONFLICT, "{:?}", lost.body);
+    assert_eq!(lost.body["code"], "already_assigned");
+    assert_eq!(lost.body["assignee_id"], winner.sub);
+
+    let loser = if winner.sub == alice.sub { &bob } else { &alice };
+    let unassign = format!("/api/v1/tasks/{id}/unassign");
+    assert_eq!(app.post(&unassign, loser, json!({})).await.status, StatusCode::FORBIDDEN);
+    // tasks:assign is not enough to take a task off someone else.
+    let mgr = app.register("mgr", &["manager"]).await;
+    assert_eq!(app.post(&unassign, &mgr, json!({})).await.status, StatusCode::FORBIDDEN);
+    let res = app.post(&unassign, winner, json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert!(res.body["assignee_id"].is_null());
+    assert_eq!(app.post(&claim, loser, json!({})).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn double_submitted_creates_return_the_first_task_unless_forced() {
+    let app = TestApp::spawn().await;
+    let first = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Rotate keys", "description": "D" })).await;
+    assert_eq!(first.status, StatusCode::CREATED, "{:?}", first.body);
+
+    let again = app.post("/api/v1/tasks", &app.admin, json!({ "title": " Rotate keys ", "description": "D" })).await;
+    assert_eq!(again.status, StatusCode::OK, "{:?}", again.body);
+    assert_eq!(again.body["_id"], first.body["_id"]);
+    assert_eq!(again.body["duplicate_suppressed"], true);
+
+    let forced = app.post("/api/v1/tasks?force=true", &app.admin, json!({ "title": "Rotate keys", "description": "D" })).await;
+    assert_eq!(forced.status, StatusCode::CREATED, "{:?}", forced.body);
+    assert_ne!(forced.body["_id"], first.body["_id"]);
+    assert!(forced.body.get("duplicate_suppressed").is_none());
+
+    let bob = app.register("bob", &["user"]).await;
+    let res = app.post("/api/v1/tasks", &bob, json!({ "title": "Rotate keys", "description": "D" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn description_edits_are_kept_as_restorable_revisions() {
+    let app = TestApp::spawn().await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "first" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+    app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "description": "second" })).await;
+    app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "title": "Title only" })).await;
+
+    let res = app.get(&format!("/api/v1/tasks/{id}/revisions?field=description"), Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let revisions = res.body.as_array().unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0]["old_value"], "first");
+    assert_eq!(revisions[0]["truncated"], false);
+    assert_eq!(revisions[0]["editor_id"], app.admin.sub.as_str());
+    let rev_id = revisions[0]["_id"].as_str().unwrap();
+
+    let res = app.post(&format!("/api/v1/tasks/{id}/revisions/{rev_id}/restore"), &app.admin, json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["description"], "first");
+    let res = app.get(&format!("/api/v1/tasks/{id}/revisions"), Some(&app.admin)).await;
+    let old: Vec<_> = res.body.as_array().unwrap().iter().map(|r| r["old_value"].as_str().unwrap()).collect();
+    assert_eq!(old, ["second", "first"]);
+
+    let res = app.get(&format!("/api/v1/tasks/{id}/revisions?field=status"), Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn exports_carry_the_whole_task_as_json_or_markdown() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let body = json!({ "title": "Outage <b>review</b>", "description": "first", "assignee_id": bob.sub });
+    let id = app.post("/api/v1/tasks", &app.admin, body).await.body["_id"].as_str().unwrap().to_string();
+    app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "description": "second" })).await;
+    app.post(&format!("/api/v1/tasks/{id}/notes"), &bob, json!({ "note": "Rolled back *all* hosts" })).await;
+
+    let res = app.get(&format!("/api/v1/tasks/{id}/export"), Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body["task"]["_id"], id.as_str());
+    assert_eq!(res.body["assignee"]["username"], "bob");
+    assert_eq!(res.body["history"][0]["old_value"], "first");
+    assert_eq!(res.body["usernames"][&bob.sub], "bob");
+    assert_eq!(res.body["attachments"], json!([]));
+
+    let res = app.get(&format!("/api/v1/tasks/{id}/export?format=markdown"), Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.headers[header::CONTENT_TYPE], "text/markdown; charset=utf-8");
+    let md = res.body.as_str().unwrap();
+    assert!(md.starts_with("# Outage \\<b\\>review\\</b\\>\n"), "{md}");
+    assert!(md.contains("### bob, ") && md.contains("Rolled back \\*all\\* hosts"), "{md}");
+
+    let res = app.get(&format!("/api/v1/tasks/{id}/export?format=pdf"), Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = app.get(&format!("/api/v1/tasks/{}/export", uuid::Uuid::new_v4()), Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn share_links_show_a_redacted_task_until_revoked() {
+    let app = TestApp::spawn().await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+    app.post(&format!("/api/v1/tasks/{id}/notes"), &app.admin, json!({ "note": "hello" })).await;
+
+    let bob = app.register("bob", &["user"]).await;
+    let res = app.post(&format!("/api/v1/tasks/{id}/share"), &bob, json!({})).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let res = app.post(&format!("/api/v1/tasks/{id}/share"), &app.admin, json!({ "include_notes": true })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    let token = res.body["token"].as_str().unwrap().to_string();
+    let share_id = res.body["id"].as_str().unwrap().to_string();
+
+    let shared = app.get(&format!("/api/v1/shared/{token}"), None).await;
+    assert_eq!(shared.status, StatusCode::OK, "{:?}", shared.body);
+    assert_eq!(shared.body["title"], "T");
+    assert_eq!(shared.body["notes"][0]["note"], "hello");
+    assert!(!shared.body.to_string().contains('@'));
+
+    let res = app.delete(&format!("/api/v1/tasks/{id}/share/{share_id}"), &app.admin).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert_eq!(app.get(&format!("/api/v1/shared/{token}"), None).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/api/v1/shared/not-a-token", None).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn external_links_are_attached_and_removed() {
+    let app = TestApp::spawn().await;
+    let task = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = task.body["_id"].as_str().unwrap().to_string();
+    let path = format!("/api/v1/tasks/{id}/external-links");
+
+    let res = app.post(&path, &app.admin, json!({ "url": "file:///etc/passwd" })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.body["fields"][0]["code"], "invalid_url");
+
+    let res = app.post(&path, &app.admin, json!({ "url": "https://example.com/runbook", "title": "Runbook" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let link = &res.body["links_external"][0];
+    assert_eq!((&link["url"], &link["title"]), (&json!("https://example.com/runbook"), &json!("Runbook")));
+    assert_eq!(link["added_by"], app.admin.sub.as_str());
+    let link_id = link["_id"].as_str().unwrap();
+
+    let res = app.delete(&format!("{path}/{link_id}"), &app.admin).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.body["links_external"], json!([]));
+    assert_eq!(app.delete(&format!("{path}/{link_id}"), &app.admin).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn task_operations_publish_domain_events() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Patch hosts", "description": "d" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    app.put(&format!("/api/v1/tasks/{id}"), &app.admin, json!({ "status": "done", "assignee_id": bob.sub })).await;
+    app.post(&format!("/api/v1/tasks/{id}/notes"), &app.admin, json!({ "note": "Rolled out" })).await;
+    app.delete(&format!("/api/v1/tasks/{id}"), &app.admin).await;
+
+    let events = app.events_eventually(6).await;
+    let names: Vec<_> = events.iter().map(DomainEvent::name).collect();
+    assert_eq!(names, ["task_created", "task_updated", "task_completed", "user_assigned", "note_added", "task_deleted"]);
+    match &events[0] {
+        DomainEvent::TaskCreated { task, actor } => {
+            assert_eq!((task.id.as_str(), task.title.as_str()), (id.as_str(), "Patch hosts"));
+            assert_eq!(actor.id, app.admin.sub);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match &events[1] {
+        DomainEvent::TaskUpdated { task, changes, .. } => {
+            assert_eq!(*changes, ["status", "assignee_id"]);
+            assert_eq!(task.status, "done");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match &events[3] {
+        DomainEvent::UserAssigned { assignee, .. } => assert_eq!(assignee.as_deref(), Some(bob.sub.as_str())),
+        other => panic!("unexpected {other:?}"),
+    }
+    match &events[4] {
+        DomainEvent::NoteAdded { note, .. } => assert_eq!(note.note, "Rolled out"),
+        other => panic!("unexpected {other:?}"),
+    }
+    match &events[5] {
+        DomainEvent::TaskDeleted { task_id, .. } => assert_eq!(*task_id, id),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn fields_limit_what_tasks_return() {
+    let app = TestApp::spawn().await;
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "Patch hosts", "description": "long" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+
+    let res = app.get("/api/v1/tasks?fields=id,title,status,assignee_id,updated_at", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    let task = &res.body["tasks"][0];
+    let mut keys: Vec<_> = task.as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["_id", "assignee_id", "status", "title", "updated_at"]);
+    assert!(task["assignee_id"].is_null());
+    assert_eq!(res.body["total"], 1);
+
+    let res = app.get(&format!("/api/v1/tasks/{id}?fields=description"), Some(&app.admin)).await;
+    assert_eq!(res.body, json!({ "_id": id, "description": "long" }));
+
+    let res = app.get("/api/v1/tasks?fields=title,secret", Some(&app.admin)).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert!(res.body["error"].as_str().unwrap().contains("secret"), "{:?}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn user_lists_only_show_members_of_the_workspace() {
+    let app = TestApp::spawn().await;
+    let bob = app.register("bob", &["user"]).await;
+    let res = app.get("/api/v1/users", Some(&bob)).await;
+    assert_eq!(res.status, StatusCode::OK, "{:?}", res.body);
+    assert_eq!(res.body.as_array().unwrap().len(), 2);
+
+    let res = app.post("/api/v1/workspaces", &app.admin, json!({ "name": "Red team" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{:?}", res.body);
+    let ws = res.body["id"].as_str().unwrap().to_string();
+    app.post(&format!("/api/v1/workspaces/{ws}/switch"), &app.admin, json!({})).await;
+    let res = app.get("/api/v1/users", Some(&app.admin)).await;
+    assert_eq!(res.body, json!([{ "id": app.admin.sub, "username": "admin" }]));
+    let query = json!({ "query": "{ users { id } }" });
+    let res = app.post("/api/v1/graphql", &app.admin, query).await;
+    assert_eq!(res.body["data"]["users"], json!([{ "id": app.admin.sub }]), "{:?}", res.body);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB at MONGODB_TEST_URI"]
+async fn permissions_follow_the_role_in_the_workspace() {
+    let app = TestApp::spawn_with(&[("OPEN_TASK_EDITING", "false")]).await;
+    let mgr = app.register("mgr", &["manager"]).await;
+    let category = json!({ "name": "Network" });
+    // In the default workspace a global manager is one of its managers.
+    assert_eq!(app.post("/api/v1/cti/categories", &mgr, category.clone()).await.status, StatusCode::CREATED);
+
+    let res = app.post("/api/v1/workspaces", &app.admin, json!({ "name": "Red team" })).await;
+    let ws = res.body["id"].as_str().unwrap().to_string();
+    let add = json!({ "email": "mgr@example.com", "role": "member" });
+    assert_eq!(app.post(&format!("/api/v1/workspaces/{ws}/members"), &app.admin, add).await.status, StatusCode::OK);
+    app.post(&format!("/api/v1/workspaces/{ws}/switch"), &mgr, json!({})).await;
+    assert_eq!(app.post("/api/v1/cti/categories", &mgr, category).await.status, StatusCode::FORBIDDEN);
+
+    app.post(&format!("/api/v1/workspaces/{ws}/switch"), &app.admin, json!({})).await;
+    let res = app.post("/api/v1/tasks", &app.admin, json!({ "title": "T", "description": "D" })).await;
+    let id = res.body["_id"].as_str().unwrap().to_string();
+    let res = app.put(&format!("/api/v1/tasks/{id}"), &mgr, json!({ "title": "Mine now" })).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
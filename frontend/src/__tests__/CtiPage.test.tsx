@@ -9,6 +9,10 @@ vi.mock('../components/Layout', () => ({
   default: ({ children }: { children: React.ReactNode }) => <div>{children}</div>,
 }))
 
+vi.mock('../contexts/AuthContext', () => ({
+  useAuth: () => ({ user: { id: 'u1', role: 'manager' } }),
+}))
+
 // Mock the API module
 vi.mock('../services/api', () => {
   const api = {
@@ -52,7 +56,7 @@ describe('CtiPage', () => {
   it('fetches categories on mount', async () => {
     renderCtiPage()
     await waitFor(() => {
-      expect(mockApi.get).toHaveBeenCalledWith('/api/cti/categories')
+      expect(mockApi.get).toHaveBeenCalledWith('/api/v1/cti/categories')
     })
   })
 
@@ -88,7 +92,7 @@ describe('CtiPage', () => {
     await user.click(screen.getAllByRole('button', { name: 'Add' })[0])
 
     await waitFor(() => {
-      expect(mockApi.post).toHaveBeenCalledWith('/api/cti/categories', { name: 'Vulnerability' })
+      expect(mockApi.post).toHaveBeenCalledWith('/api/v1/cti/categories', { name: 'Vulnerability' })
       expect(screen.getByText('Vulnerability')).toBeInTheDocument()
     })
   })
@@ -105,7 +109,7 @@ describe('CtiPage', () => {
 
     await waitFor(() => {
       expect(mockApi.get).toHaveBeenCalledWith(
-        expect.stringContaining('/api/cti/types?category_id=c1')
+        expect.stringContaining('/api/v1/cti/types?category_id=c1')
       )
     })
   })
@@ -122,7 +126,7 @@ describe('CtiPage', () => {
     await userEvent.click(screen.getByRole('button', { name: 'Delete' }))
 
     await waitFor(() => {
-      expect(mockApi.delete).toHaveBeenCalledWith('/api/cti/categories/c1')
+      expect(mockApi.delete).toHaveBeenCalledWith('/api/v1/cti/categories/c1')
       expect(screen.queryByText('Malware')).not.toBeInTheDocument()
     })
   })
@@ -62,7 +62,7 @@ describe('TasksPage', () => {
     vi.clearAllMocks()
     // Default: tasks returns paginated empty, everything else returns []
     mockApi.get.mockImplementation((url: string) => {
-      if (url === '/api/tasks') {
+      if (url === '/api/v1/tasks') {
         return Promise.resolve({ data: paginatedEmpty })
       }
       return Promise.resolve({ data: [] })
@@ -96,7 +96,7 @@ describe('TasksPage', () => {
 
   it('renders a task in the list', async () => {
     mockApi.get.mockImplementation((url: string) => {
-      if (url === '/api/tasks') return Promise.resolve({ data: paginatedWithTask })
+      if (url === '/api/v1/tasks') return Promise.resolve({ data: paginatedWithTask })
       return Promise.resolve({ data: [] })
     })
 
@@ -109,10 +109,10 @@ describe('TasksPage', () => {
 
   it('populates assignee dropdown with users', async () => {
     mockApi.get.mockImplementation((url: string) => {
-      if (url === '/api/users') {
+      if (url === '/api/v1/users') {
         return Promise.resolve({ data: [{ id: 'u1', username: 'alice', email: 'a@a.com', role: 'user' }] })
       }
-      if (url === '/api/tasks') return Promise.resolve({ data: paginatedEmpty })
+      if (url === '/api/v1/tasks') return Promise.resolve({ data: paginatedEmpty })
       return Promise.resolve({ data: [] })
     })
 
@@ -139,7 +139,7 @@ describe('TasksPage', () => {
     await user.click(screen.getByRole('button', { name: 'Create Task' }))
 
     await waitFor(() => {
-      expect(mockApi.post).toHaveBeenCalledWith('/api/tasks', expect.objectContaining({
+      expect(mockApi.post).toHaveBeenCalledWith('/api/v1/tasks', expect.objectContaining({
         title: 'Test Task',
         description: 'Do something',
         assignee_id: null,
@@ -150,7 +150,7 @@ describe('TasksPage', () => {
 
   it('deletes a task after confirming the modal', async () => {
     mockApi.get.mockImplementation((url: string) => {
-      if (url === '/api/tasks') return Promise.resolve({ data: paginatedWithTask })
+      if (url === '/api/v1/tasks') return Promise.resolve({ data: paginatedWithTask })
       return Promise.resolve({ data: [] })
     })
     mockApi.delete.mockResolvedValue({})
@@ -168,7 +168,7 @@ describe('TasksPage', () => {
     await userEvent.click(allDeleteButtons[allDeleteButtons.length - 1])
 
     await waitFor(() => {
-      expect(mockApi.delete).toHaveBeenCalledWith('/api/tasks/t1')
+      expect(mockApi.delete).toHaveBeenCalledWith('/api/v1/tasks/t1')
       expect(screen.queryByText('Test Task')).not.toBeInTheDocument()
     })
   })
@@ -176,7 +176,7 @@ describe('TasksPage', () => {
   it('passes status filter and page as query params', async () => {
     renderTasksPage()
     await waitFor(() => {
-      expect(mockApi.get).toHaveBeenCalledWith('/api/tasks', expect.objectContaining({
+      expect(mockApi.get).toHaveBeenCalledWith('/api/v1/tasks', expect.objectContaining({
         params: expect.objectContaining({
           status: 'todo,in_progress',
           page: 1,
@@ -204,7 +204,7 @@ describe('TasksPage', () => {
 
     await waitFor(() => {
       const taskCalls = mockApi.get.mock.calls.filter(
-        ([url]: [string]) => url === '/api/tasks'
+        ([url]: [string]) => url === '/api/v1/tasks'
       )
       const lastCall = taskCalls[taskCalls.length - 1]
       expect(lastCall[1].params.status).toContain('done')
@@ -221,7 +221,7 @@ describe('TasksPage', () => {
 
   it('renders pagination controls when total_pages > 1', async () => {
     mockApi.get.mockImplementation((url: string) => {
-      if (url === '/api/tasks') {
+      if (url === '/api/v1/tasks') {
         return Promise.resolve({
           data: { tasks: [], total: 50, page: 1, limit: 25, total_pages: 2 },
         })
@@ -238,7 +238,7 @@ describe('TasksPage', () => {
   it('advances to next page on Next click', async () => {
     const user = userEvent.setup()
     mockApi.get.mockImplementation((url: string) => {
-      if (url === '/api/tasks') {
+      if (url === '/api/v1/tasks') {
         return Promise.resolve({
           data: { tasks: [], total: 50, page: 1, limit: 25, total_pages: 2 },
         })
@@ -253,7 +253,7 @@ describe('TasksPage', () => {
 
     await waitFor(() => {
       const taskCalls = mockApi.get.mock.calls.filter(
-        ([url]: [string]) => url === '/api/tasks'
+        ([url]: [string]) => url === '/api/v1/tasks'
       )
       const pages = taskCalls.map(([, opts]: [string, { params: { page: number } }]) => opts.params.page)
       expect(pages).toContain(2)
@@ -133,7 +133,7 @@ describe('WeatherPage', () => {
 
     await waitFor(() => {
       expect(mockApi.post).toHaveBeenCalledWith(
-        '/api/weather/locations',
+        '/api/v1/weather/locations',
         expect.objectContaining({ label: 'New York City' })
       )
     })
@@ -148,7 +148,7 @@ describe('WeatherPage', () => {
     await userEvent.click(screen.getByRole('button', { name: 'Delete' }))
 
     await waitFor(() => {
-      expect(mockApi.delete).toHaveBeenCalledWith('/api/weather/locations/loc1')
+      expect(mockApi.delete).toHaveBeenCalledWith('/api/v1/weather/locations/loc1')
       expect(screen.queryByText('New York City')).not.toBeInTheDocument()
     })
   })
@@ -164,8 +164,8 @@ describe('WeatherPage', () => {
     await userEvent.click(screen.getByText('New York City'))
 
     await waitFor(() => {
-      expect(mockApi.get).toHaveBeenCalledWith('/api/weather/locations/loc1/alerts')
-      expect(mockApi.get).toHaveBeenCalledWith('/api/weather/locations/loc1/observations')
+      expect(mockApi.get).toHaveBeenCalledWith('/api/v1/weather/locations/loc1/alerts')
+      expect(mockApi.get).toHaveBeenCalledWith('/api/v1/weather/locations/loc1/observations')
     })
   })
 
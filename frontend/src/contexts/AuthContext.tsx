@@ -31,7 +31,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     if (oidcAuth.isAuthenticated && token) {
       setFetchFailed(false)
       api
-        .get('/api/auth/me')
+        .get('/api/v1/auth/me')
         .then((res) => setUser(res.data))
         .catch(() => {
           setUser(null)
@@ -10,16 +10,24 @@ import Alert from '@cloudscape-design/components/alert'
 import Modal from '@cloudscape-design/components/modal'
 import StatusIndicator from '@cloudscape-design/components/status-indicator'
 import Spinner from '@cloudscape-design/components/spinner'
+import Select from '@cloudscape-design/components/select'
 import Layout from '../components/Layout'
 import { useAuth } from '../contexts/AuthContext'
 import api from '../services/api'
 
+const roleOptions = [
+  { label: 'user', value: 'user' },
+  { label: 'manager', value: 'manager' },
+  { label: 'admin', value: 'admin' },
+]
+
 interface UserPublic {
   id: string
   email: string
   username: string
   role: string
   created_at: string
+  active: boolean
 }
 
 export default function AdminPage() {
@@ -38,22 +46,32 @@ export default function AdminPage() {
 
   useEffect(() => {
     api
-      .get<UserPublic[]>('/api/admin/users')
-      .then((res) => setUsers(res.data))
+      .get<{ users: UserPublic[] }>('/api/v1/admin/users?limit=100')
+      .then((res) => setUsers(res.data.users))
       .catch(() => setError('Failed to load user list'))
       .finally(() => setLoading(false))
   }, [])
 
-  const handleRoleToggle = async (u: UserPublic) => {
-    const newRole = u.role === 'admin' ? 'user' : 'admin'
+  const handleRoleChange = async (u: UserPublic, newRole: string) => {
+    if (newRole === u.role) return
     try {
-      const res = await api.put<UserPublic>(`/api/admin/users/${u.id}/role`, { role: newRole })
+      const res = await api.put<UserPublic>(`/api/v1/admin/users/${u.id}/role`, { role: newRole })
       setUsers((prev) => prev.map((x) => (x.id === u.id ? res.data : x)))
     } catch {
       setError(`Failed to update role for ${u.username}`)
     }
   }
 
+  const handleActiveToggle = async (u: UserPublic) => {
+    const action = u.active ? 'deactivate' : 'activate'
+    try {
+      const res = await api.put<UserPublic>(`/api/v1/admin/users/${u.id}/${action}`)
+      setUsers((prev) => prev.map((x) => (x.id === u.id ? res.data : x)))
+    } catch {
+      setError(`Failed to ${action} ${u.username}`)
+    }
+  }
+
   const startEdit = (u: UserPublic) => {
     setEditingId(u.id)
     setEditEmail(u.email)
@@ -70,7 +88,7 @@ export default function AdminPage() {
     setEditSaving(true)
     setEditError('')
     try {
-      const res = await api.put<UserPublic>(`/api/admin/users/${id}`, {
+      const res = await api.put<UserPublic>(`/api/v1/admin/users/${id}`, {
         email: editEmail,
         username: editUsername,
       })
@@ -88,7 +106,7 @@ export default function AdminPage() {
 
   const handleDelete = async (id: string) => {
     try {
-      await api.delete(`/api/admin/users/${id}`)
+      await api.delete(`/api/v1/admin/users/${id}`)
       setUsers((prev) => prev.filter((u) => u.id !== id))
       setConfirmDeleteUser(null)
     } catch {
@@ -159,9 +177,25 @@ export default function AdminPage() {
               {
                 id: 'role',
                 header: 'Role',
+                cell: (u: UserPublic) =>
+                  u.id === currentUser?.id ? (
+                    <StatusIndicator type={u.role === 'admin' ? 'success' : 'info'}>
+                      {u.role}
+                    </StatusIndicator>
+                  ) : (
+                    <Select
+                      selectedOption={roleOptions.find((o) => o.value === u.role) ?? { label: u.role, value: u.role }}
+                      onChange={({ detail }) => handleRoleChange(u, detail.selectedOption.value ?? u.role)}
+                      options={roleOptions}
+                    />
+                  ),
+              },
+              {
+                id: 'status',
+                header: 'Status',
                 cell: (u: UserPublic) => (
-                  <StatusIndicator type={u.role === 'admin' ? 'success' : 'info'}>
-                    {u.role}
+                  <StatusIndicator type={u.active ? 'success' : 'stopped'}>
+                    {u.active ? 'active' : 'inactive'}
                   </StatusIndicator>
                 ),
               },
@@ -204,9 +238,9 @@ export default function AdminPage() {
                       <Button
                         variant="inline-link"
                         disabled={isMe}
-                        onClick={() => handleRoleToggle(u)}
+                        onClick={() => handleActiveToggle(u)}
                       >
-                        {u.role === 'admin' ? 'Demote' : 'Promote'}
+                        {u.active ? 'Deactivate' : 'Activate'}
                       </Button>
                       <Button
                         variant="inline-link"
@@ -9,6 +9,7 @@ import SpaceBetween from '@cloudscape-design/components/space-between'
 import Box from '@cloudscape-design/components/box'
 import Alert from '@cloudscape-design/components/alert'
 import Layout from '../components/Layout'
+import { useAuth } from '../contexts/AuthContext'
 import api from '../services/api'
 
 interface Category {
@@ -29,6 +30,8 @@ interface CtiItem {
 }
 
 export default function CtiPage() {
+  const { user } = useAuth()
+  const canEdit = user?.role === 'admin' || user?.role === 'manager'
   const [categories, setCategories] = useState<Category[]>([])
   const [selectedCategory, setSelectedCategory] = useState<Category | null>(null)
   const [newCategoryName, setNewCategoryName] = useState('')
@@ -45,7 +48,7 @@ export default function CtiPage() {
 
   useEffect(() => {
     api
-      .get<Category[]>('/api/cti/categories')
+      .get<Category[]>('/api/v1/cti/categories')
       .then((r) => setCategories(r.data))
       .catch(() => setCategoryError('Failed to load categories'))
   }, [])
@@ -58,7 +61,7 @@ export default function CtiPage() {
     }
     setTypeError('')
     api
-      .get<CtiType[]>(`/api/cti/types?category_id=${selectedCategory._id}`)
+      .get<CtiType[]>(`/api/v1/cti/types?category_id=${selectedCategory._id}`)
       .then((r) => setTypes(r.data))
       .catch(() => setTypeError('Failed to load types'))
   }, [selectedCategory])
@@ -70,7 +73,7 @@ export default function CtiPage() {
     }
     setItemError('')
     api
-      .get<CtiItem[]>(`/api/cti/items?type_id=${selectedType._id}`)
+      .get<CtiItem[]>(`/api/v1/cti/items?type_id=${selectedType._id}`)
       .then((r) => setItems(r.data))
       .catch(() => setItemError('Failed to load items'))
   }, [selectedType])
@@ -79,7 +82,7 @@ export default function CtiPage() {
     e.preventDefault()
     setCategoryError('')
     try {
-      const r = await api.post<Category>('/api/cti/categories', { name: newCategoryName })
+      const r = await api.post<Category>('/api/v1/cti/categories', { name: newCategoryName })
       setCategories((prev) => [...prev, r.data])
       setNewCategoryName('')
     } catch {
@@ -90,7 +93,7 @@ export default function CtiPage() {
   const handleDeleteCategory = async (id: string) => {
     setCategoryError('')
     try {
-      await api.delete(`/api/cti/categories/${id}`)
+      await api.delete(`/api/v1/cti/categories/${id}`)
       setCategories((prev) => prev.filter((c) => c._id !== id))
       if (selectedCategory?._id === id) setSelectedCategory(null)
     } catch {
@@ -103,7 +106,7 @@ export default function CtiPage() {
     if (!selectedCategory) return
     setTypeError('')
     try {
-      const r = await api.post<CtiType>('/api/cti/types', {
+      const r = await api.post<CtiType>('/api/v1/cti/types', {
         name: newTypeName,
         category_id: selectedCategory._id,
       })
@@ -117,7 +120,7 @@ export default function CtiPage() {
   const handleDeleteType = async (id: string) => {
     setTypeError('')
     try {
-      await api.delete(`/api/cti/types/${id}`)
+      await api.delete(`/api/v1/cti/types/${id}`)
       setTypes((prev) => prev.filter((t) => t._id !== id))
       if (selectedType?._id === id) setSelectedType(null)
     } catch {
@@ -130,7 +133,7 @@ export default function CtiPage() {
     if (!selectedType) return
     setItemError('')
     try {
-      const r = await api.post<CtiItem>('/api/cti/items', {
+      const r = await api.post<CtiItem>('/api/v1/cti/items', {
         name: newItemName,
         type_id: selectedType._id,
       })
@@ -144,7 +147,7 @@ export default function CtiPage() {
   const handleDeleteItem = async (id: string) => {
     setItemError('')
     try {
-      await api.delete(`/api/cti/items/${id}`)
+      await api.delete(`/api/v1/cti/items/${id}`)
       setItems((prev) => prev.filter((i) => i._id !== id))
     } catch {
       setItemError('Failed to delete item')
@@ -191,6 +194,7 @@ export default function CtiPage() {
                   </Box>
                   <Button
                     variant="inline-link"
+                    disabled={!canEdit}
                     onClick={(e) => { e.stopPropagation(); handleDeleteCategory(cat._id) }}
                   >
                     Delete
@@ -204,7 +208,7 @@ export default function CtiPage() {
                     onChange={({ detail }) => setNewCategoryName(detail.value)}
                     placeholder="Category name…"
                   />
-                  <Button variant="primary" formAction="submit" disabled={!newCategoryName.trim()}>
+                  <Button variant="primary" formAction="submit" disabled={!canEdit || !newCategoryName.trim()}>
                     Add
                   </Button>
                 </SpaceBetween>
@@ -250,6 +254,7 @@ export default function CtiPage() {
                     </Box>
                     <Button
                       variant="inline-link"
+                      disabled={!canEdit}
                       onClick={(e) => { e.stopPropagation(); handleDeleteType(t._id) }}
                     >
                       Delete
@@ -267,7 +272,7 @@ export default function CtiPage() {
                     <Button
                       variant="primary"
                       formAction="submit"
-                      disabled={!selectedCategory || !newTypeName.trim()}
+                      disabled={!canEdit || !selectedCategory || !newTypeName.trim()}
                     >
                       Add
                     </Button>
@@ -310,6 +315,7 @@ export default function CtiPage() {
                     <Box>{item.name}</Box>
                     <Button
                       variant="inline-link"
+                      disabled={!canEdit}
                       onClick={() => handleDeleteItem(item._id)}
                     >
                       Delete
@@ -327,7 +333,7 @@ export default function CtiPage() {
                     <Button
                       variant="primary"
                       formAction="submit"
-                      disabled={!selectedType || !newItemName.trim()}
+                      disabled={!canEdit || !selectedType || !newItemName.trim()}
                     >
                       Add
                     </Button>
@@ -19,13 +19,19 @@ interface DashboardData {
   message: string
   user_id: string
   stats: {
-    total_users: number
+    total_users?: number
     tasks: {
+      total: number
       todo: number
       in_progress: number
       done: number
+      assigned_to_me: number
+      unassigned: number
+      created_last_7_days: number
+      created_last_30_days: number
     }
   }
+  unread_count: number
 }
 
 interface WeatherState {
@@ -95,7 +101,7 @@ export default function DashboardPage() {
 
   useEffect(() => {
     api
-      .get<DashboardData>('/api/dashboard')
+      .get<DashboardData>('/api/v1/dashboard')
       .then((res) => setData(res.data))
       .catch(() => setError('TELEMETRY FAILURE — could not load dashboard data'))
   }, [])
@@ -103,7 +109,7 @@ export default function DashboardPage() {
   useEffect(() => {
     if (!user) return
     api
-      .get<{ tasks: Task[] }>('/api/tasks?status=todo,in_progress&limit=50')
+      .get<{ tasks: Task[] }>('/api/v1/tasks?status=todo,in_progress&limit=50')
       .then((res) => setMyTasks(res.data.tasks.filter((t) => t.assignee_id === user.id)))
       .catch(() => {})
   }, [user])
@@ -111,13 +117,13 @@ export default function DashboardPage() {
   useEffect(() => {
     setFeedsLoading(true)
     api
-      .get<Feed[]>('/api/feeds')
+      .get<Feed[]>('/api/v1/feeds')
       .then(async (res) => {
         const feeds = res.data.slice(0, 3)
         const fetched = await Promise.allSettled(
           feeds.map((feed) =>
             api
-              .get<{ items: { title: string; link: string; published: string | null }[] }>(`/api/feeds/${feed._id}/items`)
+              .get<{ items: { title: string; link: string; published: string | null }[] }>(`/api/v1/feeds/${feed._id}/items`)
               .then((r) =>
                 r.data.items.slice(0, 3).map((item) => ({ ...item, sourceName: feed.name }))
               )
@@ -208,8 +214,17 @@ export default function DashboardPage() {
               </Container>
               <Container>
                 <SpaceBetween size="xs">
-                  <Box variant="awsui-key-label">Operators</Box>
-                  <Box variant="h1">{data.stats.total_users}</Box>
+                  {data.stats.total_users !== undefined ? (
+                    <>
+                      <Box variant="awsui-key-label">Operators</Box>
+                      <Box variant="h1">{data.stats.total_users}</Box>
+                    </>
+                  ) : (
+                    <>
+                      <Box variant="awsui-key-label">Assigned to Me</Box>
+                      <Box variant="h1">{data.stats.tasks.assigned_to_me}</Box>
+                    </>
+                  )}
                 </SpaceBetween>
               </Container>
             </ColumnLayout>
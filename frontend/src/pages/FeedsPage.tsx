@@ -53,7 +53,7 @@ export default function FeedsPage() {
   const [adding, setAdding] = useState(false)
 
   useEffect(() => {
-    api.get<Feed[]>('/api/feeds').then((res) => setFeeds(res.data)).catch(() => {})
+    api.get<Feed[]>('/api/v1/feeds').then((res) => setFeeds(res.data)).catch(() => {})
   }, [])
 
   const loadItems = (feed: Feed) => {
@@ -62,7 +62,7 @@ export default function FeedsPage() {
     setItemsError('')
     setItemsLoading(true)
     api
-      .get<{ items: FeedItem[] }>(`/api/feeds/${feed._id}/items`)
+      .get<{ items: FeedItem[] }>(`/api/v1/feeds/${feed._id}/items`)
       .then((res) => setItems(res.data.items))
       .catch(() => setItemsError('FEED UNAVAILABLE — could not retrieve articles'))
       .finally(() => setItemsLoading(false))
@@ -76,7 +76,7 @@ export default function FeedsPage() {
     }
     setAdding(true)
     try {
-      const res = await api.post<Feed>('/api/feeds', { name: newName.trim(), url: newUrl.trim() })
+      const res = await api.post<Feed>('/api/v1/feeds', { name: newName.trim(), url: newUrl.trim() })
       setFeeds((f) => [...f, res.data])
       setNewName('')
       setNewUrl('')
@@ -91,7 +91,7 @@ export default function FeedsPage() {
 
   const handleDelete = async (id: string) => {
     try {
-      await api.delete(`/api/feeds/${id}`)
+      await api.delete(`/api/v1/feeds/${id}`)
       setFeeds((f) => f.filter((feed) => feed._id !== id))
       if (selectedFeed?._id === id) {
         setSelectedFeed(null)
@@ -20,11 +20,9 @@ import Spinner from '@cloudscape-design/components/spinner'
 import Layout from '../components/Layout'
 import api from '../services/api'
 
-interface UserPublic {
+interface UserSummary {
   id: string
-  email: string
   username: string
-  role: string
 }
 
 interface Category {
@@ -86,7 +84,7 @@ export default function TaskDetailPage() {
   const [loading, setLoading] = useState(true)
   const [error, setError] = useState('')
 
-  const [users, setUsers] = useState<UserPublic[]>([])
+  const [users, setUsers] = useState<UserSummary[]>([])
   const [categories, setCategories] = useState<Category[]>([])
   const [editTypes, setEditTypes] = useState<CtiType[]>([])
   const [editItems, setEditItems] = useState<CtiItem[]>([])
@@ -107,9 +105,9 @@ export default function TaskDetailPage() {
 
   useEffect(() => {
     Promise.all([
-      api.get<Task>(`/api/tasks/${id}`),
-      api.get<UserPublic[]>('/api/users'),
-      api.get<Category[]>('/api/cti/categories'),
+      api.get<Task>(`/api/v1/tasks/${id}`),
+      api.get<UserSummary[]>('/api/v1/users'),
+      api.get<Category[]>('/api/v1/cti/categories'),
     ])
       .then(([taskRes, usersRes, catRes]) => {
         const t = taskRes.data
@@ -137,7 +135,7 @@ export default function TaskDetailPage() {
     setEditItems([])
     if (!editCategoryId) { setEditTypes([]); return }
     api
-      .get<CtiType[]>(`/api/cti/types?category_id=${editCategoryId}`)
+      .get<CtiType[]>(`/api/v1/cti/types?category_id=${editCategoryId}`)
       .then((r) => {
         setEditTypes(r.data)
         if (task?.cti?.category_id === editCategoryId && task?.cti?.type_id) {
@@ -152,7 +150,7 @@ export default function TaskDetailPage() {
     setEditItemId('')
     if (!editTypeId) { setEditItems([]); return }
     api
-      .get<CtiItem[]>(`/api/cti/items?type_id=${editTypeId}`)
+      .get<CtiItem[]>(`/api/v1/cti/items?type_id=${editTypeId}`)
       .then((r) => {
         setEditItems(r.data)
         if (task?.cti?.type_id === editTypeId && task?.cti?.item_id) {
@@ -172,7 +170,7 @@ export default function TaskDetailPage() {
         editCategoryId && editTypeId && editItemId
           ? { category_id: editCategoryId, type_id: editTypeId, item_id: editItemId }
           : null
-      const res = await api.put<Task>(`/api/tasks/${id}`, {
+      const res = await api.put<Task>(`/api/v1/tasks/${id}`, {
         title,
         description,
         status,
@@ -192,7 +190,7 @@ export default function TaskDetailPage() {
     setNoteError('')
     setAddingNote(true)
     try {
-      const res = await api.post<Task>(`/api/tasks/${id}/notes`, { note: noteText })
+      const res = await api.post<Task>(`/api/v1/tasks/${id}/notes`, { note: noteText })
       setTask(res.data)
       setNoteText('')
     } catch {
@@ -204,7 +202,7 @@ export default function TaskDetailPage() {
 
   const handleDeleteNote = async (noteId: string) => {
     try {
-      const res = await api.delete<Task>(`/api/tasks/${id}/notes/${noteId}`)
+      const res = await api.delete<Task>(`/api/v1/tasks/${id}/notes/${noteId}`)
       setTask(res.data)
     } catch {
       setNoteError('Failed to delete note')
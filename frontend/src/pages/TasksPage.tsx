@@ -19,11 +19,9 @@ import Link from '@cloudscape-design/components/link'
 import Layout from '../components/Layout'
 import api from '../services/api'
 
-interface UserPublic {
+interface UserSummary {
   id: string
-  email: string
   username: string
-  role: string
 }
 
 interface Category {
@@ -66,6 +64,9 @@ interface PaginatedTasksResponse {
   page: number
   limit: number
   total_pages: number
+  has_next: boolean
+  has_prev: boolean
+  requested_limit?: number
 }
 
 const ALL_STATUSES = ['todo', 'in_progress', 'done'] as const
@@ -97,7 +98,7 @@ export default function TasksPage() {
 
   const [statusFilter, setStatusFilter] = useState<TaskStatus[]>(['todo', 'in_progress'])
 
-  const [users, setUsers] = useState<UserPublic[]>([])
+  const [users, setUsers] = useState<UserSummary[]>([])
 
   const [categories, setCategories] = useState<Category[]>([])
   const [formTypes, setFormTypes] = useState<CtiType[]>([])
@@ -116,8 +117,8 @@ export default function TasksPage() {
 
   useEffect(() => {
     Promise.all([
-      api.get<UserPublic[]>('/api/users'),
-      api.get<Category[]>('/api/cti/categories'),
+      api.get<UserSummary[]>('/api/v1/users'),
+      api.get<Category[]>('/api/v1/cti/categories'),
     ])
       .then(([usersRes, catRes]) => {
         setUsers(usersRes.data)
@@ -130,7 +131,7 @@ export default function TasksPage() {
     setLoading(true)
     setError('')
     const statusParam = statusFilter.length > 0 ? statusFilter.join(',') : undefined
-    api.get<PaginatedTasksResponse>('/api/tasks', {
+    api.get<PaginatedTasksResponse>('/api/v1/tasks', {
       params: {
         page,
         limit,
@@ -152,7 +153,7 @@ export default function TasksPage() {
     setFormItems([])
     if (!formCategoryId) { setFormTypes([]); return }
     api
-      .get<CtiType[]>(`/api/cti/types?category_id=${formCategoryId}`)
+      .get<CtiType[]>(`/api/v1/cti/types?category_id=${formCategoryId}`)
       .then((r) => setFormTypes(r.data))
       .catch(() => {})
   }, [formCategoryId])
@@ -161,7 +162,7 @@ export default function TasksPage() {
     setFormItemId('')
     if (!formTypeId) { setFormItems([]); return }
     api
-      .get<CtiItem[]>(`/api/cti/items?type_id=${formTypeId}`)
+      .get<CtiItem[]>(`/api/v1/cti/items?type_id=${formTypeId}`)
       .then((r) => setFormItems(r.data))
       .catch(() => {})
   }, [formTypeId])
@@ -182,7 +183,7 @@ export default function TasksPage() {
         formCategoryId && formTypeId && formItemId
           ? { category_id: formCategoryId, type_id: formTypeId, item_id: formItemId }
           : null
-      const res = await api.post<Task>('/api/tasks', {
+      const res = await api.post<Task>('/api/v1/tasks', {
         title,
         description,
         assignee_id: assigneeId || null,
@@ -208,7 +209,7 @@ export default function TasksPage() {
 
   const handleDelete = async (id: string) => {
     try {
-      await api.delete(`/api/tasks/${id}`)
+      await api.delete(`/api/v1/tasks/${id}`)
       setTasks((prev) => prev.filter((t) => t._id !== id))
       setTotal((prev) => prev - 1)
       setDeleteModalTask(null)
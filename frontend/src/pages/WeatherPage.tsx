@@ -95,7 +95,7 @@ export default function WeatherPage() {
   const [adding, setAdding] = useState(false)
 
   useEffect(() => {
-    api.get<WeatherLocation[]>('/api/weather/locations')
+    api.get<WeatherLocation[]>('/api/v1/weather/locations')
       .then((res) => setLocations(res.data))
       .catch(() => {})
   }, [])
@@ -108,8 +108,8 @@ export default function WeatherPage() {
     setDetailLoading(true)
 
     Promise.all([
-      api.get<WeatherAlert[]>(`/api/weather/locations/${loc._id}/alerts`),
-      api.get<WeatherObservation[]>(`/api/weather/locations/${loc._id}/observations`),
+      api.get<WeatherAlert[]>(`/api/v1/weather/locations/${loc._id}/alerts`),
+      api.get<WeatherObservation[]>(`/api/v1/weather/locations/${loc._id}/observations`),
     ])
       .then(([alertsRes, obsRes]) => {
         setAlerts(alertsRes.data)
@@ -133,7 +133,7 @@ export default function WeatherPage() {
     }
     setAdding(true)
     try {
-      const res = await api.post<WeatherLocation>('/api/weather/locations', {
+      const res = await api.post<WeatherLocation>('/api/v1/weather/locations', {
         label: newLabel.trim(),
         lat,
         lon,
@@ -153,7 +153,7 @@ export default function WeatherPage() {
 
   const handleDelete = async (id: string) => {
     try {
-      await api.delete(`/api/weather/locations/${id}`)
+      await api.delete(`/api/v1/weather/locations/${id}`)
       setLocations((l) => l.filter((loc) => loc._id !== id))
       if (selectedLocation?._id === id) {
         setSelectedLocation(null)
@@ -166,10 +166,10 @@ export default function WeatherPage() {
   const handlePollNow = async () => {
     setPolling(true)
     try {
-      await api.post('/api/weather/poll')
+      await api.post('/api/v1/weather/poll')
       // Re-fetch locations so last_polled_at updates after a short delay
       setTimeout(() => {
-        api.get<WeatherLocation[]>('/api/weather/locations')
+        api.get<WeatherLocation[]>('/api/v1/weather/locations')
           .then((res) => setLocations(res.data))
           .catch(() => {})
           .finally(() => setPolling(false))
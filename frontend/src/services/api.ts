@@ -52,8 +52,8 @@ export interface CaCertStatusResponse {
   status: 'ok' | 'expiring_soon' | 'expired'
 }
 
-export const getCaHealth = () => api.get<CaHealthResponse>('/api/ca/health')
-export const getCaRoots = () => api.get<unknown>('/api/ca/roots')
-export const getCaCrl = () => api.get<CaCrlResponse>('/api/ca/crl')
-export const getCaProvisioners = () => api.get<CaProvisionersResponse>('/api/ca/provisioners')
-export const getCaCertStatus = () => api.get<CaCertStatusResponse>('/api/ca/cert-status')
+export const getCaHealth = () => api.get<CaHealthResponse>('/api/v1/ca/health')
+export const getCaRoots = () => api.get<unknown>('/api/v1/ca/roots')
+export const getCaCrl = () => api.get<CaCrlResponse>('/api/v1/ca/crl')
+export const getCaProvisioners = () => api.get<CaProvisionersResponse>('/api/v1/ca/provisioners')
+export const getCaCertStatus = () => api.get<CaCertStatusResponse>('/api/v1/ca/cert-status')